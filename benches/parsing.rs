@@ -0,0 +1,41 @@
+use codespan::Files;
+use criterion::{criterion_group, criterion_main, Criterion};
+use nickel_lang::parser::{grammar::TermParser, lexer::Lexer};
+use pprof::criterion::{Output, PProfProfiler};
+
+/// Generate a record typical of generated configurations: many fields with string values, some
+/// of them quoted or defined through a path.
+fn generated_record(fields: usize) -> String {
+    let mut source = String::from("{\n");
+
+    for i in 0..fields {
+        source.push_str(&format!(
+            "  field_{i} = {{ name = \"value number {i}\", \"quoted.{i}\" = [1, 2, \"x\\ty\"] }},\n  \
+             path.to.field_{i} | doc \"documentation of {i}\" = m%\"\n    multiline {i}\n  \"%m,\n",
+            i = i
+        ));
+    }
+
+    source.push('}');
+    source
+}
+
+fn parse_generated(c: &mut Criterion) {
+    let source = generated_record(10_000);
+    let file_id = Files::new().add("generated.ncl", source.clone());
+
+    c.bench_function("parse generated record 10000", |b| {
+        b.iter(|| {
+            TermParser::new()
+                .parse_term(file_id, Lexer::new(&source))
+                .unwrap()
+        })
+    });
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
+    targets = parse_generated
+}
+criterion_main!(benches);
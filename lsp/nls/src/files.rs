@@ -1,21 +1,20 @@
 use anyhow::Result;
 use codespan::FileId;
-use codespan_reporting::diagnostic::Diagnostic;
 use log::trace;
 use lsp_server::RequestId;
 use lsp_types::{
     notification::{DidOpenTextDocument, Notification},
-    DidChangeTextDocumentParams, DidOpenTextDocumentParams, PublishDiagnosticsParams, Url,
+    Diagnostic, DidChangeTextDocumentParams, DidOpenTextDocumentParams, PublishDiagnosticsParams,
+    Url,
 };
 use nickel_lang::{
     cache::{CacheError, CacheOp},
-    error::ToDiagnostic,
+    error::{lsp::to_lsp_diagnostics_with, Error},
 };
 
 use crate::trace::{param::FileUpdate, Enrich, Trace};
 
 use super::cache::CacheExt;
-use super::diagnostic::DiagnosticCompat;
 use super::server::Server;
 
 pub fn handle_open(server: &mut Server, params: DidOpenTextDocumentParams) -> Result<()> {
@@ -69,12 +68,22 @@ pub fn handle_save(server: &mut Server, params: DidChangeTextDocumentParams) ->
     Ok(())
 }
 
-fn typecheck(server: &mut Server, file_id: FileId) -> Result<CacheOp<()>, Vec<Diagnostic<FileId>>> {
+/// Convert an error to LSP diagnostics, without adding the generated snippets to the file
+/// database, which would otherwise grow with each new version of the document.
+fn to_diagnostics(server: &Server, error: impl Into<Error>) -> Vec<Diagnostic> {
+    to_lsp_diagnostics_with(
+        &error.into(),
+        server.cache.files(),
+        server.cache.diagnostic_options(),
+    )
+}
+
+fn typecheck(server: &mut Server, file_id: FileId) -> Result<CacheOp<()>, Vec<Diagnostic>> {
     server
         .cache
         .typecheck_with_analysis(file_id, &server.global_env, &mut server.lin_cache)
         .map_err(|error| match error {
-            CacheError::Error(tc_error) => tc_error.to_diagnostic(server.cache.files_mut(), None),
+            CacheError::Error(tc_error) => to_diagnostics(server, tc_error),
             CacheError::NotParsed => unreachable!(),
         })
 }
@@ -83,24 +92,16 @@ fn parse_and_typecheck(server: &mut Server, uri: Url, file_id: FileId) -> Result
     let diagnostics = server
         .cache
         .parse(file_id)
-        .map_err(|parse_err| parse_err.to_diagnostic(server.cache.files_mut(), None))
+        .map_err(|parse_err| to_diagnostics(server, parse_err))
         .map(|parse_errs| {
             // Parse errors are not fatal
-            let mut d = parse_errs
-                .inner()
-                .to_diagnostic(server.cache.files_mut(), None);
+            let mut d = to_diagnostics(server, parse_errs.inner());
             trace!("Parsed, checking types");
             let _ = typecheck(server, file_id).map_err(|mut ty_d| d.append(&mut ty_d));
             d
         })
         .unwrap_or_else(|d| d);
 
-    let diagnostics = diagnostics
-        .into_iter()
-        .map(|d| lsp_types::Diagnostic::from_codespan(d, server.cache.files_mut()))
-        .flatten()
-        .collect();
-
     server.notify(lsp_server::Notification::new(
         "textDocument/publishDiagnostics".into(),
         PublishDiagnosticsParams {
@@ -2,6 +2,7 @@ use std::collections::HashMap;
 
 use codespan::ByteIndex;
 use nickel_lang::{
+    position::RawSpan,
     term::MetaValue,
     typecheck::linearization::{LinearizationState, Scope, ScopeId},
 };
@@ -17,6 +18,10 @@ pub struct Completed {
     pub linearization: Vec<LinearizationItem<Resolved>>,
     scope: HashMap<Scope, Vec<usize>>,
     id_to_index: HashMap<ID, usize>,
+    /// The hover information of the items, see [`HoverIndex`].
+    pub hover: HoverIndex,
+    /// The completion information of the items, see [`CompletionIndex`].
+    pub completion: CompletionIndex,
 }
 
 impl Completed {
@@ -25,11 +30,56 @@ impl Completed {
         scope: HashMap<Scope, Vec<usize>>,
         id_to_index: HashMap<ID, usize>,
     ) -> Self {
-        Self {
+        let mut completed = Self {
             linearization,
             scope,
             id_to_index,
-        }
+            hover: HoverIndex::default(),
+            completion: CompletionIndex::default(),
+        };
+
+        completed.hover = HoverIndex::new(
+            completed
+                .linearization
+                .iter()
+                .map(|item| {
+                    let (ty, meta) = completed.resolve_item_type_meta(item);
+                    HoverItem {
+                        pos: item.pos,
+                        ty: ty.to_string(),
+                        meta,
+                    }
+                })
+                .collect(),
+        );
+
+        let declarations: HashMap<Scope, Vec<String>> = completed
+            .scope
+            .iter()
+            .map(|(scope, ids)| {
+                let names: Vec<String> = ids
+                    .iter()
+                    .filter_map(|id| completed.get_item(*id))
+                    .filter_map(|item| match item.kind {
+                        TermKind::Declaration(ref ident, _, _) => Some(ident.label.clone()),
+                        _ => None,
+                    })
+                    .collect();
+                (scope.clone(), names)
+            })
+            .collect();
+        completed.completion = CompletionIndex::new(
+            completed
+                .linearization
+                .iter()
+                .map(|item| ScopeItem {
+                    pos: item.pos,
+                    scope: item.scope.clone(),
+                })
+                .collect(),
+            declarations,
+        );
+        completed
     }
 
     pub fn get_item(&self, id: usize) -> Option<&LinearizationItem<Resolved>> {
@@ -38,21 +88,6 @@ impl Completed {
             .and_then(|index| self.linearization.get(*index))
     }
 
-    pub fn get_in_scope(
-        &self,
-        LinearizationItem { scope, .. }: &LinearizationItem<Resolved>,
-    ) -> Vec<&LinearizationItem<Resolved>> {
-        let empty = Vec::with_capacity(0);
-        (0..scope.len())
-            .flat_map(|end| {
-                eprintln!("in scope {:?}: {:?}", scope, self.scope.get(scope));
-                self.scope.get(&scope[..=end]).unwrap_or(&empty)
-            })
-            .map(|id| self.get_item(*id))
-            .flatten()
-            .collect()
-    }
-
     /// Finds the index of a linearization item for a given location
     /// The linearization is a list of items that are sorted by their physical occurence.
     /// - Each element has a corresponding span in the source
@@ -78,26 +113,7 @@ impl Completed {
         &self,
         locator: &(codespan::FileId, ByteIndex),
     ) -> Option<&LinearizationItem<Resolved>> {
-        let (file_id, start) = locator;
-        let linearization = &self.linearization;
-        let item = match linearization
-            .binary_search_by_key(locator, |item| (item.pos.src_id, item.pos.start))
-        {
-            // Found item(s) starting at `locator`
-            // search for most precise element
-            Ok(index) => linearization[index..]
-                .iter()
-                .take_while(|item| (item.pos.src_id, item.pos.start) == *locator)
-                .last(),
-            // No perfect match found
-            // iterate back finding the first wrapping linearization item
-            Err(index) => linearization[..index].iter().rfind(|item| {
-                let (istart, iend, ifile) = (item.pos.start, item.pos.end, item.pos.src_id);
-
-                file_id == &ifile && start > &istart && start < &iend
-            }),
-        };
-        item
+        find_item_at(&self.linearization, |item| &item.pos, locator)
     }
 
     /// Resolve type and meta information for a given item
@@ -157,3 +173,104 @@ impl Completed {
 }
 
 impl LinearizationState for Completed {}
+
+/// Find the item at a location in a list of items sorted by position, as described in
+/// [`Completed::item_at`]. `pos` gives the position of an item.
+fn find_item_at<'a, T>(
+    items: &'a [T],
+    pos: impl Fn(&T) -> &RawSpan,
+    locator: &(codespan::FileId, ByteIndex),
+) -> Option<&'a T> {
+    let (file_id, start) = locator;
+    let item = match items.binary_search_by_key(locator, |item| (pos(item).src_id, pos(item).start))
+    {
+        // Found item(s) starting at `locator`
+        // search for most precise element
+        Ok(index) => items[index..]
+            .iter()
+            .take_while(|item| (pos(item).src_id, pos(item).start) == *locator)
+            .last(),
+        // No perfect match found
+        // iterate back finding the first wrapping linearization item
+        Err(index) => items[..index].iter().rfind(|item| {
+            let (istart, iend, ifile) = (pos(item).start, pos(item).end, pos(item).src_id);
+
+            file_id == &ifile && start > &istart && start < &iend
+        }),
+    };
+    item
+}
+
+/// The hover information of an item of a linearization: its position, and its type and metadata,
+/// rendered.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HoverItem {
+    pub pos: RawSpan,
+    pub ty: String,
+    pub meta: Vec<String>,
+}
+
+/// The hover information of the items of a linearization, in the same order.
+///
+/// Contrary to a linearization, which holds terms, the hover information only holds strings and
+/// positions. It can thus be shared between threads, such that hover queries can be served
+/// concurrently.
+#[derive(Debug, Default)]
+pub struct HoverIndex {
+    items: Vec<HoverItem>,
+}
+
+impl HoverIndex {
+    /// Create an index from items sorted by position.
+    pub fn new(items: Vec<HoverItem>) -> Self {
+        HoverIndex { items }
+    }
+
+    /// Find the hover information at a location, see [`Completed::item_at`].
+    pub fn item_at(&self, locator: &(codespan::FileId, ByteIndex)) -> Option<&HoverItem> {
+        find_item_at(&self.items, |item| &item.pos, locator)
+    }
+}
+
+/// The scope of an item of a linearization, together with its position.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScopeItem {
+    pub pos: RawSpan,
+    pub scope: Scope,
+}
+
+/// The completion information of a linearization: the scopes of its items, in the same order, and
+/// the identifiers declared in each scope.
+///
+/// As for [`HoverIndex`], the completion information only holds strings, positions and scopes,
+/// such that completion queries can be served concurrently.
+#[derive(Debug, Default)]
+pub struct CompletionIndex {
+    items: Vec<ScopeItem>,
+    declarations: HashMap<Scope, Vec<String>>,
+}
+
+impl CompletionIndex {
+    /// Create an index from items sorted by position, and from the identifiers declared in each
+    /// scope.
+    pub fn new(items: Vec<ScopeItem>, declarations: HashMap<Scope, Vec<String>>) -> Self {
+        CompletionIndex {
+            items,
+            declarations,
+        }
+    }
+
+    /// Find the scope at a location, see [`Completed::item_at`].
+    pub fn item_at(&self, locator: &(codespan::FileId, ByteIndex)) -> Option<&ScopeItem> {
+        find_item_at(&self.items, |item| &item.pos, locator)
+    }
+
+    /// Return the identifiers in scope of an item, that is the ones declared in the scope of the
+    /// item or in one of its parents, from the outermost scope to the innermost one.
+    pub fn in_scope<'a>(&'a self, item: &'a ScopeItem) -> impl Iterator<Item = &'a str> {
+        (0..item.scope.len())
+            .filter_map(move |end| self.declarations.get(&item.scope[..=end]))
+            .flatten()
+            .map(String::as_str)
+    }
+}
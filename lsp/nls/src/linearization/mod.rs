@@ -152,7 +152,7 @@ impl Linearizer for AnalysisHost {
             }
         }
 
-        if pos == TermPos::None {
+        if !pos.is_def() {
             return;
         }
 
@@ -1,12 +1,12 @@
-use codespan::ByteIndex;
+use codespan::{ByteIndex, FileId, Files};
 use codespan_lsp::position_to_byte_index;
 use log::debug;
 use lsp_server::{RequestId, Response, ResponseError};
-use lsp_types::{CompletionItem, CompletionParams};
+use lsp_types::{CompletionItem, CompletionParams, Position};
 use serde_json::Value;
 
 use crate::{
-    linearization::interface::TermKind,
+    linearization::completed::CompletionIndex,
     server::Server,
     trace::{Enrich, Trace},
 };
@@ -14,49 +14,101 @@ use crate::{
 pub fn handle_completion(
     params: CompletionParams,
     id: RequestId,
-    server: &mut Server,
+    server: &Server,
 ) -> Result<(), ResponseError> {
     let file_id = server
         .cache
         .id_of(params.text_document_position.text_document.uri.as_str())
         .unwrap();
 
-    let start = position_to_byte_index(
+    let linearization = server.lin_cache_get(&file_id)?;
+    let in_scope = completion(
         server.cache.files(),
+        &linearization.completion,
         file_id,
         &params.text_document_position.position,
-    )
-    .unwrap();
-
-    let locator = (file_id, ByteIndex(start as u32));
-    let linearization = server.lin_cache_get(&file_id)?;
+    );
 
     Trace::enrich(&id, linearization);
 
-    let item = linearization.item_at(&locator);
-
-    if item == None {
-        server.reply(Response::new_ok(id, Value::Null));
-        return Ok(());
+    match in_scope {
+        Some(in_scope) => server.reply(Response::new_ok(id, in_scope)),
+        None => server.reply(Response::new_ok(id, Value::Null)),
     }
+    Ok(())
+}
 
-    let item = item.unwrap().to_owned();
+/// Compute the completion items at a position of a file, that is the identifiers in scope.
+///
+/// As for [`hover`](super::hover::hover), this only needs shared, thread-safe data: completion
+/// queries can be answered concurrently.
+pub fn completion(
+    files: &Files<String>,
+    index: &CompletionIndex,
+    file_id: FileId,
+    position: &Position,
+) -> Option<Vec<CompletionItem>> {
+    let start = position_to_byte_index(files, file_id, position).ok()?;
+    let item = index.item_at(&(file_id, ByteIndex(start as u32)))?;
 
-    let in_scope: Vec<_> = linearization
-        .get_in_scope(&item)
-        .iter()
-        .filter_map(|i| match i.kind {
-            TermKind::Declaration(ref ident, _, _) => Some((ident.clone(), i.ty.clone())),
-            _ => None,
-        })
-        .map(|(ident, _)| CompletionItem {
-            label: ident.label,
-            ..Default::default()
-        })
-        .collect();
+    debug!("found closest item: {:?}", item);
 
-    server.reply(Response::new_ok(id, in_scope));
+    Some(
+        index
+            .in_scope(item)
+            .map(|label| CompletionItem {
+                label: String::from(label),
+                ..Default::default()
+            })
+            .collect(),
+    )
+}
 
-    debug!("found closest item: {:?}", item);
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::requests::tests::linearize;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn concurrent_completion() {
+        let (files, file_id, mut completed) = linearize("let foo = 1 in\nlet bar = foo in\nbar");
+        // The linearization itself can't be sent to other threads, contrary to its completion
+        // index.
+        let index = std::mem::take(&mut completed.completion);
+
+        let positions: Vec<Position> = (0..3)
+            .flat_map(|line| (0..16).map(move |character| Position { line, character }))
+            .collect();
+        let expected: Vec<Option<Vec<String>>> = positions
+            .iter()
+            .map(|pos| {
+                completion(&files, &index, file_id, pos)
+                    .map(|items| items.into_iter().map(|item| item.label).collect())
+            })
+            .collect();
+        assert!(expected.iter().flatten().any(|labels| {
+            labels.contains(&String::from("foo")) && labels.contains(&String::from("bar"))
+        }));
+
+        let shared = Arc::new((files, index, positions, expected));
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || {
+                    let (files, index, positions, expected) = &*shared;
+                    for (pos, expected) in positions.iter().zip(expected) {
+                        let labels = completion(files, index, file_id, pos)
+                            .map(|items| items.into_iter().map(|item| item.label).collect());
+                        assert_eq!(&labels, expected);
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
 }
@@ -18,7 +18,7 @@ use crate::{
 pub fn handle_to_definition(
     params: GotoDefinitionParams,
     id: RequestId,
-    server: &mut Server,
+    server: &Server,
 ) -> Result<(), ResponseError> {
     let file_id = server
         .cache
@@ -89,7 +89,7 @@ pub fn handle_to_definition(
 pub fn handle_to_usages(
     params: ReferenceParams,
     id: RequestId,
-    server: &mut Server,
+    server: &Server,
 ) -> Result<(), ResponseError> {
     let file_id = server
         .cache
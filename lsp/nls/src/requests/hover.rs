@@ -1,21 +1,18 @@
-use codespan::ByteIndex;
+use codespan::{ByteIndex, FileId, Files};
 use codespan_lsp::position_to_byte_index;
 use log::debug;
 use lsp_server::{RequestId, Response, ResponseError};
-use lsp_types::{Hover, HoverContents, HoverParams, LanguageString, MarkedString, Range};
+use lsp_types::{Hover, HoverContents, HoverParams, LanguageString, MarkedString, Position, Range};
 use serde_json::Value;
 
 use crate::{
     diagnostic::LocationCompat,
+    linearization::completed::HoverIndex,
     server::Server,
     trace::{Enrich, Trace},
 };
 
-pub fn handle(
-    params: HoverParams,
-    id: RequestId,
-    server: &mut Server,
-) -> Result<(), ResponseError> {
+pub fn handle(params: HoverParams, id: RequestId, server: &Server) -> Result<(), ResponseError> {
     let file_id = server
         .cache
         .id_of(
@@ -27,55 +24,99 @@ pub fn handle(
         )
         .unwrap();
 
-    let start = position_to_byte_index(
+    let linearization = server.lin_cache_get(&file_id)?;
+    let hover = hover(
         server.cache.files(),
+        &linearization.hover,
         file_id,
         &params.text_document_position_params.position,
-    )
-    .unwrap();
-
-    debug!("start of hovered item: ByteIndex({})", start);
-
-    let locator = (file_id, ByteIndex(start as u32));
-
-    let linearization = server.lin_cache_get(&file_id)?;
-    let item = linearization.item_at(&locator);
+    );
 
     Trace::enrich(&id, linearization);
 
-    if item == None {
-        server.reply(Response::new_ok(id, Value::Null));
-        return Ok(());
+    match hover {
+        Some(hover) => server.reply(Response::new_ok(id, hover)),
+        None => server.reply(Response::new_ok(id, Value::Null)),
     }
+    Ok(())
+}
 
-    let item = item.unwrap().to_owned();
+/// Compute the hover information at a position of a file.
+///
+/// This only needs shared, thread-safe data: hover queries can be answered concurrently.
+pub fn hover(
+    files: &Files<String>,
+    index: &HoverIndex,
+    file_id: FileId,
+    position: &Position,
+) -> Option<Hover> {
+    let start = position_to_byte_index(files, file_id, position).ok()?;
 
-    debug!("{:?}", item);
+    debug!("start of hovered item: ByteIndex({})", start);
 
-    let (ty, meta) = linearization.resolve_item_type_meta(&item);
+    let item = index.item_at(&(file_id, ByteIndex(start as u32)))?;
+
+    debug!("{:?}", item);
 
     let range = Range::from_codespan(
         &file_id,
         &(item.pos.start.to_usize()..item.pos.end.to_usize()),
-        server.cache.files(),
+        files,
     );
 
-    server.reply(Response::new_ok(
-        id,
-        Hover {
-            contents: HoverContents::Array(vec![
-                MarkedString::LanguageString(LanguageString {
-                    language: "nickel".into(),
-                    value: ty.to_string(),
-                }),
-                MarkedString::LanguageString(LanguageString {
-                    language: "plain".into(),
-                    value: meta.join("\n"),
-                }),
-            ]),
-
-            range: Some(range),
-        },
-    ));
-    Ok(())
+    Some(Hover {
+        contents: HoverContents::Array(vec![
+            MarkedString::LanguageString(LanguageString {
+                language: "nickel".into(),
+                value: item.ty.clone(),
+            }),
+            MarkedString::LanguageString(LanguageString {
+                language: "plain".into(),
+                value: item.meta.join("\n"),
+            }),
+        ]),
+
+        range: Some(range),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::requests::tests::linearize;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn concurrent_hover() {
+        let (files, file_id, mut completed) = linearize("let x = \"é\" in\nx ++ \"a\"");
+        // The linearization itself can't be sent to other threads, contrary to its hover index.
+        let index = std::mem::take(&mut completed.hover);
+
+        let positions: Vec<Position> = (0..2)
+            .flat_map(|line| (0..12).map(move |character| Position { line, character }))
+            .collect();
+        let expected: Vec<Option<Hover>> = positions
+            .iter()
+            .map(|pos| hover(&files, &index, file_id, pos))
+            .collect();
+        assert!(expected.iter().any(Option::is_some));
+
+        let shared = Arc::new((files, index, positions, expected));
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let shared = Arc::clone(&shared);
+                thread::spawn(move || {
+                    let (files, index, positions, expected) = &*shared;
+                    for (pos, expected) in positions.iter().zip(expected) {
+                        assert_eq!(&hover(files, index, file_id, pos), expected);
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
 }
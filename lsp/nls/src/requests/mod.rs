@@ -2,3 +2,34 @@ pub mod completion;
 pub mod goto;
 pub mod hover;
 pub mod symbols;
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::HashMap;
+
+    use codespan::{FileId, Files};
+    use nickel_lang::cache::Cache;
+
+    use crate::{cache::CacheExt, linearization::completed::Completed};
+
+    /// Parse and typecheck a source with the analysis of the server, as done when a document is
+    /// opened. Return the file database together with the linearization of the source.
+    pub(crate) fn linearize(source: &str) -> (Files<String>, FileId, Completed) {
+        let mut cache = Cache::new();
+        cache.load_stdlib().unwrap();
+        let global_env = cache.mk_types_env().unwrap();
+        let file_id = cache.add_string("test.ncl", String::from(source));
+        cache.parse(file_id).unwrap();
+
+        let mut lin_cache = HashMap::new();
+        cache
+            .typecheck_with_analysis(file_id, &global_env, &mut lin_cache)
+            .unwrap();
+
+        (
+            cache.files().clone(),
+            file_id,
+            lin_cache.remove(&file_id).unwrap(),
+        )
+    }
+}
@@ -12,7 +12,7 @@ use crate::server::Server;
 pub fn handle_document_symbols(
     params: DocumentSymbolParams,
     id: RequestId,
-    server: &mut Server,
+    server: &Server,
 ) -> Result<(), ResponseError> {
     let file_id = server
         .cache
@@ -25,6 +25,10 @@ use crate::{
     trace::Trace,
 };
 
+/// The state of the language server.
+///
+/// Requests (hover, completion, ...) only need a shared reference to the server. The cache is only
+/// mutated when a document is opened or changed, see [`crate::files`].
 pub struct Server {
     pub connection: Connection,
     pub cache: Cache,
@@ -71,7 +75,7 @@ impl Server {
         }
     }
 
-    pub(crate) fn reply(&mut self, response: Response) {
+    pub(crate) fn reply(&self, response: Response) {
         trace!("Sending response: {:#?}", response);
 
         if response.error.is_some() {
@@ -85,7 +89,7 @@ impl Server {
             .send(Message::Response(response))
             .unwrap();
     }
-    pub(crate) fn notify(&mut self, notification: Notification) {
+    pub(crate) fn notify(&self, notification: Notification) {
         trace!("Sending notification: {:#?}", notification);
         self.connection
             .sender
@@ -93,7 +97,7 @@ impl Server {
             .unwrap();
     }
 
-    fn err<E>(&mut self, id: RequestId, err: E)
+    fn err<E>(&self, id: RequestId, err: E)
     where
         E: std::fmt::Display,
     {
@@ -155,7 +159,7 @@ impl Server {
         }
     }
 
-    fn handle_request(&mut self, req: lsp_server::Request) -> Result<()> {
+    fn handle_request(&self, req: lsp_server::Request) -> Result<()> {
         Trace::receive(req.id.clone(), req.method.clone());
 
         let res = match req.method.as_str() {
@@ -0,0 +1,458 @@
+//! Serialization of the AST for tooling.
+//!
+//! The serialization implemented in [`serialize`](../serialize/index.html) is about exporting
+//! evaluated *values* to data formats: it fails on functions, drops metadata, and so on. This
+//! module provides a different representation, meant to be consumed by external tools (linters,
+//! formatters, editors, debugging scripts): any term, evaluated or not, is dumped as a tree of
+//! tagged objects which faithfully mirrors the structure of the AST.
+//!
+//! Each node is an object with a `node` field holding the name of the constructor (e.g. `Fun`,
+//! `Op2` or `Arrow`). Terms also carry a `span` field, which is either `null` or an object
+//! `{file, start, end, inherited}` giving the name of the source file and the byte range of the
+//! node. Identifiers are serialized as plain strings, and the fields of records are sorted by
+//! name, such that the output is deterministic.
+//!
+//! Spans only store a file id. Resolving the file name requires the file database, which is why
+//! the serialization goes through the [`AstDump`] wrapper instead of a direct `Serialize`
+//! implementation on [`RichTerm`] or [`Types`].
+//!
+//! This representation is expected to evolve together with the AST. It is not covered by any
+//! stability guarantee beyond the snapshot tests of this repository.
+use crate::destruct::{Destruct, LastMatch, Match};
+use crate::error::SerializationError;
+use crate::identifier::Ident;
+use crate::label::Label;
+use crate::position::{RawSpan, TermPos};
+use crate::term::{BindingType, Contract, MetaValue, RichTerm, StrChunk, Term, UnaryOp};
+use crate::types::{AbsType, Types};
+use codespan::Files;
+use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+/// Available output formats for the AST dump.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum AstFormat {
+    #[default]
+    Json,
+}
+
+impl fmt::Display for AstFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Json => write!(f, "json"),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ParseAstFormatError(String);
+
+impl fmt::Display for ParseAstFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unsupported AST format {}", self.0)
+    }
+}
+
+impl FromStr for AstFormat {
+    type Err = ParseAstFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "json" => Ok(AstFormat::Json),
+            _ => Err(ParseAstFormatError(String::from(s))),
+        }
+    }
+}
+
+/// Serialize a term to `writer` in the given format.
+pub fn to_writer<W>(
+    writer: W,
+    format: AstFormat,
+    files: &Files<String>,
+    rt: &RichTerm,
+) -> Result<(), SerializationError>
+where
+    W: io::Write,
+{
+    match format {
+        AstFormat::Json => serde_json::to_writer_pretty(writer, &AstDump::new(files, rt))
+            .map_err(|err| SerializationError::Other(err.to_string())),
+    }
+}
+
+/// A part of the AST together with the file database, which implements `Serialize` using the
+/// tooling representation described in the [module documentation](index.html).
+pub struct AstDump<'a, T: ?Sized> {
+    files: &'a Files<String>,
+    value: &'a T,
+}
+
+impl<'a, T: ?Sized> AstDump<'a, T> {
+    pub fn new(files: &'a Files<String>, value: &'a T) -> Self {
+        AstDump { files, value }
+    }
+
+    fn child<U: ?Sized>(&self, value: &'a U) -> AstDump<'a, U> {
+        AstDump::new(self.files, value)
+    }
+}
+
+/// Serialize a node as a map, with a `node` field holding the given tag, followed by the given
+/// entries.
+macro_rules! node {
+    ($ser:expr, $tag:expr $(, $key:literal => $value:expr)* $(,)?) => {{
+        let mut map = $ser.serialize_map(None)?;
+        map.serialize_entry("node", $tag)?;
+        $(map.serialize_entry($key, &$value)?;)*
+        map.end()
+    }};
+}
+
+/// Return the name of a primitive operator, that is the name of the variant without its
+/// arguments.
+fn op_name<Op: fmt::Debug>(op: &Op) -> String {
+    let repr = format!("{:?}", op);
+    match repr.find('(') {
+        Some(idx) => String::from(&repr[..idx]),
+        None => repr,
+    }
+}
+
+impl<'a> Serialize for AstDump<'a, Ident> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.value.label)
+    }
+}
+
+impl<'a> Serialize for AstDump<'a, RawSpan> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let span = self.value;
+        let mut map = serializer.serialize_map(Some(3))?;
+        map.serialize_entry("file", &self.files.name(span.src_id).to_string_lossy())?;
+        map.serialize_entry("start", &span.start.to_usize())?;
+        map.serialize_entry("end", &span.end.to_usize())?;
+        map.end()
+    }
+}
+
+impl<'a> Serialize for AstDump<'a, TermPos> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (span, inherited) = match self.value {
+            TermPos::Original(span) => (span, false),
+            TermPos::Inherited(span) => (span, true),
+            TermPos::None => return serializer.serialize_none(),
+        };
+
+        let mut map = serializer.serialize_map(Some(4))?;
+        map.serialize_entry("file", &self.files.name(span.src_id).to_string_lossy())?;
+        map.serialize_entry("start", &span.start.to_usize())?;
+        map.serialize_entry("end", &span.end.to_usize())?;
+        map.serialize_entry("inherited", &inherited)?;
+        map.end()
+    }
+}
+
+impl<'a, T> Serialize for AstDump<'a, Option<T>>
+where
+    AstDump<'a, T>: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.value {
+            Some(value) => self.child(value).serialize(serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+impl<'a, T> Serialize for AstDump<'a, Vec<T>>
+where
+    AstDump<'a, T>: Serialize,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.value.len()))?;
+        for elt in self.value.iter() {
+            seq.serialize_element(&self.child(elt))?;
+        }
+        seq.end()
+    }
+}
+
+impl<'a> Serialize for AstDump<'a, HashMap<Ident, RichTerm>> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut fields: Vec<_> = self.value.iter().collect();
+        fields.sort_by_key(|(id, _)| *id);
+
+        let mut map = serializer.serialize_map(Some(fields.len()))?;
+        for (id, t) in fields {
+            map.serialize_entry(&id.label, &self.child(t))?;
+        }
+        map.end()
+    }
+}
+
+impl<'a> Serialize for AstDump<'a, (RichTerm, RichTerm)> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (name, value) = self.value;
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("name", &self.child(name))?;
+        map.serialize_entry("value", &self.child(value))?;
+        map.end()
+    }
+}
+
+impl<'a> Serialize for AstDump<'a, StrChunk<RichTerm>> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.value {
+            StrChunk::Literal(s) => node!(serializer, "Literal", "value" => s),
+            StrChunk::Expr(t, indent) => node!(serializer, "Expr",
+                "value" => self.child(t),
+                "indent" => indent,
+            ),
+        }
+    }
+}
+
+impl<'a> Serialize for AstDump<'a, Types> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match &self.value.0 {
+            AbsType::Dyn() => node!(serializer, "Dyn"),
+            AbsType::Num() => node!(serializer, "Num"),
+            AbsType::Bool() => node!(serializer, "Bool"),
+            AbsType::Str() => node!(serializer, "Str"),
+            AbsType::Sym() => node!(serializer, "Sym"),
+            AbsType::Flat(t) => node!(serializer, "Flat", "term" => self.child(t)),
+            AbsType::Arrow(dom, codom) => node!(serializer, "Arrow",
+                "domain" => self.child(dom.as_ref()),
+                "codomain" => self.child(codom.as_ref()),
+            ),
+            AbsType::Var(id) => node!(serializer, "Var", "id" => self.child(id)),
+            AbsType::Forall(id, body) => node!(serializer, "Forall",
+                "var" => self.child(id),
+                "body" => self.child(body.as_ref()),
+            ),
+            AbsType::RowEmpty() => node!(serializer, "RowEmpty"),
+            AbsType::RowExtend(id, ty, tail) => node!(serializer, "RowExtend",
+                "id" => self.child(id),
+                "type" => ty.as_ref().map(|ty| self.child(ty.as_ref())),
+                "tail" => self.child(tail.as_ref()),
+            ),
+            AbsType::Enum(row) => node!(serializer, "Enum", "row" => self.child(row.as_ref())),
+            AbsType::StaticRecord(row) => {
+                node!(serializer, "StaticRecord", "row" => self.child(row.as_ref()))
+            }
+            AbsType::DynRecord(ty) => {
+                node!(serializer, "DynRecord", "type" => self.child(ty.as_ref()))
+            }
+            AbsType::Array(ty) => node!(serializer, "Array", "type" => self.child(ty.as_ref())),
+            AbsType::Alias(id, args, ty) => node!(serializer, "Alias",
+                "id" => self.child(id),
+                "args" => args.iter().map(|arg| self.child(arg.as_ref())).collect::<Vec<_>>(),
+                "expansion" => self.child(ty.as_ref()),
+            ),
+        }
+    }
+}
+
+impl<'a> Serialize for AstDump<'a, Label> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let label = self.value;
+        let mut map = serializer.serialize_map(Some(4))?;
+        map.serialize_entry("types", &self.child(label.types.as_ref()))?;
+        map.serialize_entry("tag", &label.tag)?;
+        map.serialize_entry("span", &self.child(&label.span))?;
+        map.serialize_entry("polarity", &label.polarity)?;
+        map.end()
+    }
+}
+
+impl<'a> Serialize for AstDump<'a, Contract> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(2))?;
+        map.serialize_entry("types", &self.child(&self.value.types))?;
+        map.serialize_entry("span", &self.child(&self.value.label.span))?;
+        map.end()
+    }
+}
+
+impl<'a> Serialize for AstDump<'a, MetaValue> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let meta = self.value;
+        let mut map = serializer.serialize_map(Some(5))?;
+        map.serialize_entry("doc", &meta.doc)?;
+        map.serialize_entry("types", &self.child(&meta.types))?;
+        map.serialize_entry("contracts", &self.child(&meta.contracts))?;
+        map.serialize_entry("priority", &format!("{:?}", meta.priority))?;
+        map.serialize_entry("value", &self.child(&meta.value))?;
+        map.end()
+    }
+}
+
+impl<'a> Serialize for AstDump<'a, Match> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.value {
+            Match::Assign(field, meta, (bind, pat)) => node!(serializer, "Assign",
+                "field" => self.child(field),
+                "meta" => self.child(meta),
+                "bind" => self.child(bind),
+                "pattern" => self.child(pat),
+            ),
+            Match::Simple(field, meta) => node!(serializer, "Simple",
+                "field" => self.child(field),
+                "meta" => self.child(meta),
+            ),
+        }
+    }
+}
+
+impl<'a> Serialize for AstDump<'a, LastMatch> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.value {
+            LastMatch::Match(m) => self.child(m).serialize(serializer),
+            LastMatch::Ellipsis(rest) => node!(serializer, "Ellipsis", "rest" => self.child(rest)),
+        }
+    }
+}
+
+impl<'a> Serialize for AstDump<'a, Destruct> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.value {
+            Destruct::Record {
+                matches,
+                open,
+                rest,
+                span,
+            } => node!(serializer, "Record",
+                "span" => self.child(span),
+                "matches" => self.child(matches),
+                "open" => open,
+                "rest" => self.child(rest),
+            ),
+            Destruct::Array { matches, span } => node!(serializer, "Array",
+                "span" => self.child(span),
+                "matches" => self.child(matches),
+            ),
+            Destruct::Empty => node!(serializer, "Empty"),
+        }
+    }
+}
+
+impl<'a> Serialize for AstDump<'a, UnaryOp> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let name = op_name(self.value);
+        match self.value {
+            UnaryOp::Embed(id) | UnaryOp::StaticAccess(id) => {
+                node!(serializer, &name, "id" => self.child(id))
+            }
+            UnaryOp::Switch(has_default) => {
+                node!(serializer, &name, "has_default" => has_default)
+            }
+            _ => node!(serializer, &name),
+        }
+    }
+}
+
+impl<'a> Serialize for AstDump<'a, RichTerm> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let span = self.child(&self.value.pos);
+
+        match self.value.as_ref() {
+            Term::Null => node!(serializer, "Null", "span" => span),
+            Term::Bool(b) => node!(serializer, "Bool", "span" => span, "value" => b),
+            Term::Num(n) => node!(serializer, "Num", "span" => span, "value" => n),
+            Term::Str(s) => node!(serializer, "Str", "span" => span, "value" => s),
+            Term::StrChunks(chunks) => node!(serializer, "StrChunks",
+                "span" => span,
+                "chunks" => self.child(chunks),
+            ),
+            Term::Fun(id, body) => node!(serializer, "Fun",
+                "span" => span,
+                "arg" => self.child(id),
+                "body" => self.child(body),
+            ),
+            Term::FunPattern(id, pat, body) => node!(serializer, "FunPattern",
+                "span" => span,
+                "arg" => self.child(id),
+                "pattern" => self.child(pat),
+                "body" => self.child(body),
+            ),
+            Term::Lbl(label) => {
+                node!(serializer, "Lbl", "span" => span, "label" => self.child(label))
+            }
+            Term::Let(id, bound, body, btype) => node!(serializer, "Let",
+                "span" => span,
+                "id" => self.child(id),
+                "bound" => self.child(bound),
+                "body" => self.child(body),
+                "revertible" => matches!(btype, BindingType::Revertible(_)),
+            ),
+            Term::LetPattern(id, pat, bound, body) => node!(serializer, "LetPattern",
+                "span" => span,
+                "id" => self.child(id),
+                "pattern" => self.child(pat),
+                "bound" => self.child(bound),
+                "body" => self.child(body),
+            ),
+            Term::App(t1, t2) => node!(serializer, "App",
+                "span" => span,
+                "fun" => self.child(t1),
+                "arg" => self.child(t2),
+            ),
+            Term::Var(id) => node!(serializer, "Var", "span" => span, "id" => self.child(id)),
+            Term::Enum(id) => node!(serializer, "Enum", "span" => span, "id" => self.child(id)),
+            Term::Record(fields, attrs) => node!(serializer, "Record",
+                "span" => span,
+                "fields" => self.child(fields),
+                "open" => attrs.open,
+            ),
+            Term::RecRecord(fields, dyn_fields, attrs, _) => node!(serializer, "RecRecord",
+                "span" => span,
+                "fields" => self.child(fields),
+                "dyn_fields" => self.child(dyn_fields),
+                "open" => attrs.open,
+            ),
+            Term::Switch(exp, cases, default) => node!(serializer, "Switch",
+                "span" => span,
+                "exp" => self.child(exp),
+                "cases" => self.child(cases),
+                "default" => self.child(default),
+            ),
+            Term::Array(ts) => node!(serializer, "Array", "span" => span, "elts" => self.child(ts)),
+            Term::Op1(op, t) => node!(serializer, "Op1",
+                "span" => span,
+                "op" => self.child(op),
+                "arg" => self.child(t),
+            ),
+            Term::Op2(op, t1, t2) => node!(serializer, "Op2",
+                "span" => span,
+                "op" => op_name(op),
+                "args" => [self.child(t1), self.child(t2)],
+            ),
+            Term::OpN(op, ts) => node!(serializer, "OpN",
+                "span" => span,
+                "op" => op_name(op),
+                "args" => self.child(ts),
+            ),
+            Term::Sym(sym) => node!(serializer, "Sym", "span" => span, "value" => sym),
+            Term::Wrapped(sym, t) => node!(serializer, "Wrapped",
+                "span" => span,
+                "sym" => sym,
+                "term" => self.child(t),
+            ),
+            Term::MetaValue(meta) => {
+                node!(serializer, "MetaValue", "span" => span, "meta" => self.child(meta))
+            }
+            Term::Import(path) => node!(serializer, "Import",
+                "span" => span,
+                "path" => path.to_string_lossy(),
+            ),
+            Term::ResolvedImport(file_id) => node!(serializer, "ResolvedImport",
+                "span" => span,
+                "file" => self.files.name(*file_id).to_string_lossy(),
+            ),
+            Term::ParseError => node!(serializer, "ParseError", "span" => span),
+        }
+    }
+}
@@ -1,13 +1,29 @@
 //! Entry point of the program.
-use nickel_lang::error::{Error, IOError};
-use nickel_lang::program::Program;
+#[cfg(feature = "ast-dump")]
+use nickel_lang::ast_dump::AstFormat;
+// Keep the `pprint-ast` subcommand parseable, such that it can report the missing feature.
+#[cfg(not(feature = "ast-dump"))]
+type AstFormat = String;
+use nickel_lang::cache::{StdlibSource, Timings};
+use nickel_lang::check::{self, CheckOptions, SummaryFormat};
+use nickel_lang::error::Error;
+use nickel_lang::error_codes;
+use nickel_lang::eval::cancellation::CancellationToken;
+use nickel_lang::lint::Lint;
+use nickel_lang::program::{ColorOpt, ErrorFormat, FieldOverride, Phase, Program};
 use nickel_lang::repl::query_print;
 #[cfg(feature = "repl")]
 use nickel_lang::repl::rustyline_frontend;
-use nickel_lang::term::{RichTerm, Term};
-use nickel_lang::{serialize, serialize::ExportFormat};
+use nickel_lang::report_policy::{CodePolicy, ReportPolicy};
+use nickel_lang::serialize::{DuplicateKeys, ExportFormat, ExportStyle};
+use nickel_lang::term::Term;
+use std::fs;
+use std::io::Write;
 use std::path::PathBuf;
-use std::{fs, process};
+use std::process;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
 // use std::ffi::OsStr;
 use directories::BaseDirs;
 use structopt::StructOpt;
@@ -19,10 +35,94 @@ struct Opt {
     #[structopt(short = "f", long, global = true, parse(from_os_str))]
     file: Option<PathBuf>,
 
-    #[cfg(debug_assertions)]
-    /// Skip the standard library import, for debugging only, does not affect REPL
-    #[structopt(long)]
-    nostdlib: bool,
+    /// Print the time spent in each phase of the execution on standard error
+    #[structopt(long, global = true)]
+    timings: bool,
+
+    /// Only resolve imports when they are used during evaluation. Unused imports are then not
+    /// required to exist nor to be valid. Ignored by `typecheck`
+    #[structopt(long, global = true)]
+    lazy_imports: bool,
+
+    /// Coloring of error messages: `auto, always, never`. Default: `auto`, which uses colors only
+    /// if the standard error is a terminal
+    #[structopt(long, global = true, default_value = "auto")]
+    color: ColorOpt,
+
+    /// Maximum number of parse errors reported for a file, after cascading errors caused by the
+    /// recovery from an earlier error have been removed. `0` reports all the errors as they are
+    #[structopt(long, global = true, default_value = "20")]
+    max_parse_errors: usize,
+
+    /// Number of calls reported at each end of the call stack of a contract violation. The calls
+    /// in between are omitted. `0` reports the whole call stack
+    #[structopt(long, global = true, default_value = "10")]
+    call_stack_limit: usize,
+
+    /// Behavior on duplicate keys in JSON imports: `error, first, last`. Default: `error`, as
+    /// duplicate keys usually reveal a bug in the program that generated the data
+    #[structopt(long, global = true, default_value = "error")]
+    json_duplicate_keys: DuplicateKeys,
+
+    /// Load the standard library from a directory instead of the embedded sources. The directory
+    /// must have the same layout as the `stdlib` directory of the Nickel repository
+    #[structopt(long, global = true, parse(from_os_str), conflicts_with = "no-stdlib")]
+    stdlib: Option<PathBuf>,
+
+    /// A directory searched for relative imports which are not found next to the importing file.
+    /// Can be repeated: directories are searched in order, then the ones of the
+    /// `NICKEL_IMPORT_PATH` environment variable
+    #[structopt(long, global = true, number_of_values = 1, parse(from_os_str))]
+    import_path: Vec<PathBuf>,
+
+    /// Start without the standard library: only the primitive operations are available
+    #[structopt(long, global = true)]
+    no_stdlib: bool,
+
+    /// A TOML file adjusting the reported errors by error code: severity override, additional
+    /// note, documentation URL, and whether the error makes the command fail
+    #[structopt(long, global = true, parse(from_os_str))]
+    report_policy: Option<PathBuf>,
+
+    /// Format of the reported errors: `human, sarif, json`. Default: `human`. `sarif` writes a
+    /// SARIF 2.1.0 log, as consumed by CI systems to annotate source code. `json` writes an array
+    /// of diagnostics with their labels, positions and notes, for editors and other tools. The
+    /// warnings are reported in the same format
+    #[structopt(long, global = true, default_value = "human")]
+    error_format: ErrorFormat,
+
+    /// Write the errors to a file instead of the standard error, or the standard output for the
+    /// `sarif` and `json` formats
+    #[structopt(long, global = true, parse(from_os_str))]
+    error_output: Option<PathBuf>,
+
+    /// Abort the evaluation after the given number of seconds. Ignored by `repl` and `check`
+    #[structopt(long, global = true)]
+    timeout: Option<u64>,
+
+    /// Report the errors of all the phases (parsing, typechecking and evaluation) in one run,
+    /// instead of stopping at the first failing phase. Only used when evaluating or exporting. The
+    /// exit status is then the one of the earliest failing phase: 3 for parsing, 2 for
+    /// typechecking and 1 otherwise
+    #[structopt(long, global = true)]
+    keep_going: bool,
+
+    /// Report the warnings of a lint as errors. Available lints: `shadow-stdlib`,
+    /// `unused-binding` and `override-bypass`. Can be repeated. Ignored by `repl` and `check`
+    #[structopt(long, global = true, number_of_values = 1)]
+    deny: Vec<Lint>,
+
+    /// Report the warnings of all the lints as errors, as if each lint was given to `--deny`.
+    /// Ignored by `repl` and `check`
+    #[structopt(long, global = true)]
+    deny_warnings: bool,
+
+    /// Override a field of the result with a Nickel expression, as in `server.port=8080`. The
+    /// value is merged with the program: the contracts of the field apply, and only fields with a
+    /// default value or without a value can be overridden. Can be repeated. Ignored by `repl` and
+    /// `check`
+    #[structopt(long = "override", global = true, number_of_values = 1)]
+    overrides: Vec<FieldOverride>,
 
     #[structopt(subcommand)]
     command: Option<Command>,
@@ -31,15 +131,25 @@ struct Opt {
 /// Available subcommands.
 #[derive(StructOpt, Debug)]
 enum Command {
-    /// Export the result to a different format
+    /// Export the result to a different format. The fields of records are output in
+    /// alphabetical order, such that exports are reproducible
     Export {
-        /// Available formats: `raw, json, yaml, toml`. Default format: `json`.
+        /// Available formats: `raw, json, yaml, toml, messagepack`. Default format: `json`.
         #[structopt(long)]
         format: Option<ExportFormat>,
         /// Output file. Standard output by default
         #[structopt(short = "o", long)]
         #[structopt(parse(from_os_str))]
         output: Option<PathBuf>,
+        /// Output minified JSON, without any whitespace
+        #[structopt(long, conflicts_with = "indent")]
+        compact: bool,
+        /// Number of spaces used to indent pretty-printed JSON. Default: `2`
+        #[structopt(long)]
+        indent: Option<usize>,
+        /// Replace the values marked as `sensitive` by a placeholder
+        #[structopt(long)]
+        redact_sensitive: bool,
     },
     /// Print the metadata attached to an attribute, given as a path
     Query {
@@ -57,15 +167,75 @@ enum Command {
     },
     /// Typecheck a program, but do not run it
     Typecheck,
+    /// Parse and typecheck all the files matching the given glob patterns, and print a summary.
+    /// Exit with a non-zero status if any file failed
+    Check {
+        /// Glob patterns of the files to check, such as `config/**/*.ncl`
+        #[structopt(required = true)]
+        patterns: Vec<String>,
+        /// Also fully evaluate the files, such that all the contracts are checked. The result is
+        /// not exported, hence functions and other values which can't be serialized are allowed
+        #[structopt(long)]
+        eval: bool,
+        /// Number of files checked in parallel. Default: 1
+        #[structopt(short = "j", long, default_value = "1")]
+        jobs: usize,
+        /// Available formats: `table, json`. Default format: `table`.
+        #[structopt(long)]
+        format: Option<SummaryFormat>,
+    },
+    /// Print the absolute paths of the files transitively imported by a program, one per line,
+    /// without typechecking nor running it
+    Deps {
+        /// Print a Makefile rule `<target>: <dependencies>` instead, such as `config.json`
+        #[structopt(long)]
+        make: Option<String>,
+    },
+    /// Bundle a program and the files it transitively imports into a single self-contained
+    /// source, whose header maps it back to the original files
+    Bundle {
+        /// The program to bundle. Default to the input file
+        #[structopt(parse(from_os_str))]
+        entry: Option<PathBuf>,
+        /// Output file. Standard output by default
+        #[structopt(short = "o", long)]
+        #[structopt(parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Print the AST of a program, as produced by the parser, without running it. Requires the
+    /// `ast-dump` feature
+    PprintAst {
+        /// Available formats: `json`. Default format: `json`.
+        #[structopt(long)]
+        #[cfg_attr(not(feature = "ast-dump"), allow(dead_code))]
+        format: Option<AstFormat>,
+    },
     /// Start an REPL session
     Repl {
         #[structopt(long)]
         history_file: Option<PathBuf>,
     },
+    /// Print the explanation of an error code, such as `E001`
+    Explain { code: String },
 }
 
 fn main() {
     let opts = Opt::from_args();
+    let max_parse_errors = Some(opts.max_parse_errors).filter(|max| *max > 0);
+    let call_stack_limit = Some(opts.call_stack_limit).filter(|limit| *limit > 0);
+    let stdlib = match (opts.stdlib.clone(), opts.no_stdlib) {
+        (_, true) => StdlibSource::None,
+        (Some(dir), false) => StdlibSource::Dir(dir),
+        (None, false) => StdlibSource::Embedded,
+    };
+    let import_paths = import_paths(&opts.import_path);
+    let report_policy: Option<Arc<dyn ReportPolicy>> = opts.report_policy.as_ref().map(|path| {
+        let policy = CodePolicy::from_file(path).unwrap_or_else(|err| {
+            eprintln!("error: could not load the report policy: {}", err);
+            process::exit(1)
+        });
+        Arc::new(policy) as Arc<dyn ReportPolicy>
+    });
 
     if let Some(Command::Repl { history_file }) = opts.command {
         let histfile = if let Some(h) = history_file {
@@ -77,15 +247,76 @@ fn main() {
                 .join(".nickel_history")
         };
         #[cfg(feature = "repl")]
-        if rustyline_frontend::repl(histfile).is_err() {
+        if rustyline_frontend::repl(
+            histfile,
+            opts.color,
+            max_parse_errors,
+            call_stack_limit,
+            opts.json_duplicate_keys,
+            stdlib,
+            report_policy,
+            import_paths,
+        )
+        .is_err()
+        {
             process::exit(1);
         }
 
         #[cfg(not(feature = "repl"))]
         eprintln!("error: this executable was not compiled with REPL support");
+    } else if let Some(Command::Explain { code }) = opts.command {
+        if let Some(explanation) = error_codes::explain(&code) {
+            println!("{}", explanation);
+        } else {
+            eprintln!("error: unknown error code `{}`", code);
+            process::exit(1);
+        }
+    } else if let Some(Command::Check {
+        patterns,
+        eval,
+        jobs,
+        format,
+    }) = opts.command
+    {
+        let files = check::expand_globs(&patterns);
+        if files.is_empty() {
+            eprintln!("error: no file matches the given patterns");
+            process::exit(1);
+        }
+
+        let summary = check::check_files(
+            &files,
+            CheckOptions {
+                eval,
+                jobs,
+                color: opts.color,
+                max_parse_errors,
+                call_stack_limit,
+                json_duplicate_keys: opts.json_duplicate_keys,
+                stdlib,
+                report_policy,
+                import_paths,
+            },
+        );
+        let result = summary.write(
+            format.unwrap_or_default(),
+            &mut std::io::stdout(),
+            &mut std::io::stderr(),
+        );
+        if let Err(err) = result {
+            eprintln!("error: could not write the summary: {}", err);
+            process::exit(1);
+        }
+
+        process::exit(summary.exit_code());
     } else {
-        let mut program = opts
-            .file
+        let file = match &opts.command {
+            Some(Command::Bundle {
+                entry: Some(entry), ..
+            }) => Some(entry.clone()),
+            _ => opts.file,
+        };
+        let mut program = file
             .map(Program::new_from_file)
             .unwrap_or_else(Program::new_from_stdin)
             .unwrap_or_else(|err| {
@@ -93,13 +324,98 @@ fn main() {
                 process::exit(1)
             });
 
-        #[cfg(debug_assertions)]
-        if opts.nostdlib {
-            program.set_skip_stdlib();
+        if opts.timings {
+            program.enable_timings();
+        }
+
+        program.set_lazy_imports(opts.lazy_imports);
+        program.set_color(opts.color);
+        program.set_max_parse_errors(max_parse_errors);
+        program.set_call_stack_limit(call_stack_limit);
+        program.set_json_duplicate_keys(opts.json_duplicate_keys);
+        program.set_stdlib(stdlib);
+        program.set_import_paths(import_paths);
+        if opts.deny_warnings {
+            program.set_deny(Lint::ALL);
+        } else {
+            program.set_deny(opts.deny);
+        }
+        if let Err(err) = program.set_overrides(opts.overrides) {
+            report(&mut program, err, opts.error_format, opts.error_output);
+            process::exit(1)
+        }
+        if let Some(policy) = report_policy {
+            program.set_report_policy(policy);
+        }
+        if let Some(timeout) = opts.timeout {
+            let token = CancellationToken::new();
+            program.set_cancellation(token.clone());
+            // The watchdog is detached: it doesn't prevent the process from exiting once done.
+            thread::spawn(move || {
+                thread::sleep(Duration::from_secs(timeout));
+                token.cancel();
+            });
+        }
+
+        if opts.keep_going {
+            let result = match opts.command {
+                Some(Command::Export {
+                    format,
+                    output,
+                    compact,
+                    indent,
+                    redact_sensitive,
+                }) => {
+                    program.set_redact_sensitive(redact_sensitive);
+                    program.export_keep_going(
+                        format.unwrap_or_default(),
+                        export_style(compact, indent),
+                        output,
+                    )
+                }
+                None => program
+                    .eval_full_keep_going()
+                    .map(|t| println!("{}", Term::from(t).deep_repr())),
+                Some(_) => {
+                    eprintln!("error: --keep-going is only supported when evaluating or exporting");
+                    process::exit(1)
+                }
+            };
+
+            if let Some(timings) = program.timings() {
+                print_timings(timings);
+            }
+
+            match result {
+                Err(errors) => {
+                    if opts.error_format == ErrorFormat::Human {
+                        report_warnings(&mut program, ErrorFormat::Human, None);
+                    }
+                    let code =
+                        report_phases(&mut program, errors, opts.error_format, opts.error_output);
+                    process::exit(code)
+                }
+                Ok(()) => report_warnings(&mut program, opts.error_format, opts.error_output),
+            }
+
+            return;
         }
 
         let result = match opts.command {
-            Some(Command::Export { format, output }) => export(&mut program, format, output),
+            Some(Command::Export {
+                format,
+                output,
+                compact,
+                indent,
+                redact_sensitive,
+            }) => {
+                program.set_redact_sensitive(redact_sensitive);
+                program.export(
+                    format.unwrap_or_default(),
+                    export_style(compact, indent),
+                    output,
+                )
+            }
             Some(Command::Query {
                 path,
                 doc,
@@ -126,35 +442,220 @@ fn main() {
                 })
             }
             Some(Command::Typecheck) => program.typecheck().map(|_| ()),
-            Some(Command::Repl { .. }) => unreachable!(),
+            Some(Command::Deps { make }) => program.imports().map(|deps| match make {
+                Some(target) => println!("{}", make_rule(&target, &deps)),
+                None => deps.iter().for_each(|dep| println!("{}", dep.display())),
+            }),
+            Some(Command::Bundle { output, .. }) => program.write_bundle(output),
+            #[cfg(feature = "ast-dump")]
+            Some(Command::PprintAst { format }) => program.pprint_ast(format.unwrap_or_default()),
+            #[cfg(not(feature = "ast-dump"))]
+            Some(Command::PprintAst { .. }) => {
+                eprintln!("error: this executable was not compiled with AST dump support");
+                process::exit(1)
+            }
+            Some(Command::Repl { .. })
+            | Some(Command::Check { .. })
+            | Some(Command::Explain { .. }) => unreachable!(),
             None => program
                 .eval_full()
                 .map(|t| println!("{}", Term::from(t).deep_repr())),
         };
 
-        if let Err(err) = result {
-            program.report(err);
-            process::exit(1)
+        if let Some(timings) = program.timings() {
+            print_timings(timings);
+        }
+
+        match result {
+            Err(err) => {
+                // In the other formats, the warnings are written along the error.
+                if opts.error_format == ErrorFormat::Human {
+                    report_warnings(&mut program, ErrorFormat::Human, None);
+                }
+                let fatal = program.is_fatal(&err);
+                report(&mut program, err, opts.error_format, opts.error_output);
+
+                if fatal {
+                    process::exit(1)
+                }
+            }
+            Ok(()) => report_warnings(&mut program, opts.error_format, opts.error_output),
+        }
+    }
+}
+
+/// The import search path: the directories given on the command line, followed by the ones of the
+/// `NICKEL_IMPORT_PATH` environment variable, separated as in `PATH`.
+fn import_paths(cli_paths: &[PathBuf]) -> Vec<PathBuf> {
+    let env_paths: Vec<PathBuf> = std::env::var_os("NICKEL_IMPORT_PATH")
+        .map(|paths| std::env::split_paths(&paths).collect())
+        .unwrap_or_default();
+
+    cli_paths
+        .iter()
+        .cloned()
+        .chain(env_paths)
+        .filter(|path| !path.as_os_str().is_empty())
+        .collect()
+}
+
+/// Report an error in the given format, either to `output` or to the default stream of the format.
+fn report(program: &mut Program, err: Error, format: ErrorFormat, output: Option<PathBuf>) {
+    let result =
+        match (format, output) {
+            (ErrorFormat::Human, None) => {
+                program.report(err);
+                Ok(())
+            }
+            (ErrorFormat::Human, Some(path)) => {
+                fs::File::create(path).and_then(|file| program.report_to_writer(err, file))
+            }
+            (ErrorFormat::Sarif, None) => program.report_sarif_run([&err], &mut std::io::stdout()),
+            (ErrorFormat::Sarif, Some(path)) => fs::File::create(path)
+                .and_then(|mut file| program.report_sarif_run([&err], &mut file)),
+            (ErrorFormat::Json, None) => program.report_json_run([&err], &mut std::io::stdout()),
+            (ErrorFormat::Json, Some(path)) => fs::File::create(path)
+                .and_then(|mut file| program.report_json_run([&err], &mut file)),
+        };
+
+    if let Err(io_err) = result {
+        eprintln!("error: could not write the error report: {}", io_err);
+        process::exit(1);
+    }
+}
+
+/// Report the warnings of the last run when there is no error to report them along. In the human
+/// format, warnings are printed on the standard error. In the other formats, they are written as
+/// a document of their own, either to `output` or to the default stream of the format, if there
+/// is any warning.
+fn report_warnings(program: &mut Program, format: ErrorFormat, output: Option<PathBuf>) {
+    if program.warnings().is_empty() {
+        return;
+    }
+
+    let no_errors: [Error; 0] = [];
+    let result = match (format, output) {
+        (ErrorFormat::Human, _) => {
+            for warning in program.warnings().to_vec() {
+                program.report(warning);
+            }
+            Ok(())
         }
+        (ErrorFormat::Sarif, None) => program.report_sarif_run(&no_errors, &mut std::io::stdout()),
+        (ErrorFormat::Sarif, Some(path)) => fs::File::create(path)
+            .and_then(|mut file| program.report_sarif_run(&no_errors, &mut file)),
+        (ErrorFormat::Json, None) => program.report_json_run(&no_errors, &mut std::io::stdout()),
+        (ErrorFormat::Json, Some(path)) => fs::File::create(path)
+            .and_then(|mut file| program.report_json_run(&no_errors, &mut file)),
+    };
+
+    if let Err(io_err) = result {
+        eprintln!("error: could not write the warning report: {}", io_err);
+        process::exit(1);
+    }
+}
+
+fn export_style(compact: bool, indent: Option<usize>) -> ExportStyle {
+    match (compact, indent) {
+        (true, _) => ExportStyle::Compact,
+        (false, Some(indent)) => ExportStyle::Pretty { indent },
+        (false, None) => ExportStyle::default(),
     }
 }
 
-fn export(
+/// Report the errors of a run in keep-going mode, grouped by phase, and return the exit status of
+/// the run: the one of the earliest phase with a fatal error, or `0` if there is none.
+fn report_phases(
     program: &mut Program,
-    format: Option<ExportFormat>,
+    mut errors: Vec<(Phase, Error)>,
+    format: ErrorFormat,
     output: Option<PathBuf>,
-) -> Result<(), Error> {
-    let rt = program.eval_full().map(RichTerm::from)?;
-    let format = format.unwrap_or_default();
+) -> i32 {
+    errors.sort_by_key(|(phase, _)| *phase);
+    let code = errors
+        .iter()
+        .filter(|(_, err)| program.is_fatal(err))
+        .map(|(phase, _)| phase.exit_code())
+        .max()
+        .unwrap_or(0);
 
-    serialize::validate(format, &rt)?;
+    let result = match (format, output) {
+        (ErrorFormat::Human, None) => {
+            for (phase, group) in group_by_phase(errors) {
+                eprintln!("{} error(s) during {}:", group.len(), phase);
+                for err in group {
+                    program.report(err);
+                }
+            }
+            Ok(())
+        }
+        (ErrorFormat::Human, Some(path)) => fs::File::create(path).and_then(|mut file| {
+            for (phase, group) in group_by_phase(errors) {
+                writeln!(file, "{} error(s) during {}:", group.len(), phase)?;
+                for err in group {
+                    program.report_to_writer(err, &mut file)?;
+                }
+            }
+            Ok(())
+        }),
+        // A SARIF log has no notion of phase: all the errors are written in one log.
+        (ErrorFormat::Sarif, output) => {
+            let errors: Vec<Error> = errors.into_iter().map(|(_, err)| err).collect();
+            match output {
+                None => program.report_sarif_run(&errors, &mut std::io::stdout()),
+                Some(path) => fs::File::create(path)
+                    .and_then(|mut file| program.report_sarif_run(&errors, &mut file)),
+            }
+        }
+        // Each JSON diagnostic gives the kind of its error, from which the phase follows.
+        (ErrorFormat::Json, output) => {
+            let errors: Vec<Error> = errors.into_iter().map(|(_, err)| err).collect();
+            match output {
+                None => program.report_json_run(&errors, &mut std::io::stdout()),
+                Some(path) => fs::File::create(path)
+                    .and_then(|mut file| program.report_json_run(&errors, &mut file)),
+            }
+        }
+    };
 
-    if let Some(file) = output {
-        let file = fs::File::create(&file).map_err(IOError::from)?;
-        serialize::to_writer(file, format, &rt)?;
-    } else {
-        serialize::to_writer(std::io::stdout(), format, &rt)?;
+    if let Err(io_err) = result {
+        eprintln!("error: could not write the error report: {}", io_err);
+        process::exit(1);
     }
 
-    Ok(())
+    code
+}
+
+/// Group errors sorted by phase.
+fn group_by_phase(errors: Vec<(Phase, Error)>) -> Vec<(Phase, Vec<Error>)> {
+    let mut groups: Vec<(Phase, Vec<Error>)> = Vec::new();
+
+    for (phase, err) in errors {
+        match groups.last_mut() {
+            Some((last, group)) if *last == phase => group.push(err),
+            _ => groups.push((phase, vec![err])),
+        }
+    }
+
+    groups
+}
+
+/// Render a Makefile rule without recipe, escaping the spaces of the paths.
+fn make_rule(target: &str, deps: &[PathBuf]) -> String {
+    let escape = |path: &str| path.replace(' ', "\\ ");
+    let deps: Vec<String> = deps
+        .iter()
+        .map(|dep| escape(&dep.to_string_lossy()))
+        .collect();
+
+    format!("{}: {}", escape(target), deps.join(" "))
+}
+
+fn print_timings(timings: &Timings) {
+    eprintln!("stdlib:    {:?}", timings.stdlib);
+    eprintln!("parse:     {:?}", timings.parse);
+    eprintln!("imports:   {:?}", timings.imports);
+    eprintln!("typecheck: {:?}", timings.typecheck);
+    eprintln!("transform: {:?}", timings.transform);
+    eprintln!("eval:      {:?}", timings.eval);
 }
@@ -0,0 +1,374 @@
+//! Bundling of a program and of its imports into a single self-contained source.
+//!
+//! A library spread over several files is awkward to distribute. [`bundle`] inlines each file
+//! transitively imported by a program as a let binding, in dependency order, and replaces the
+//! imports by references to these bindings:
+//!
+//! ```text
+//! # nickel-bundle
+//! # source 84 128 lib.ncl
+//! # source 133 181 main.ncl
+//! let _bundle_1 = (
+//! {
+//!   greet = fun name => "hello, %{name}",
+//! }
+//! ) in
+//! let lib = _bundle_1        in
+//! lib.greet "world"
+//! ```
+//!
+//! The source of a Nickel file is copied verbatim, except for its imports. The variable replacing
+//! an import is padded with spaces, such that the lines and the columns of the rest of the file
+//! are preserved. Each file is evaluated once, as an import would be. Files in an external format,
+//! such as JSON or YAML, are inlined as literal data.
+//!
+//! # Source map
+//!
+//! The header of a bundle is a source map: each `source` line gives the byte range of an inlined
+//! file in the bundle, followed by the original name of the file, relative to the directory of the
+//! program and written with forward slashes on every platform. When the [cache](../cache/struct.Cache.html) parses a bundle, it adds each inlined
+//! file to the file database under its original name, and the labels of the diagnostics located
+//! in a bundle are mapped back to these files (see
+//! [`DiagnosticOptions::source_maps`](../error/struct.DiagnosticOptions.html#structfield.source_maps)).
+use crate::cache::{Cache, InputFormat};
+use crate::error::ImportError;
+use crate::position::{RawSpan, TermPos};
+use crate::term::{RichTerm, Term, TraverseOrder};
+use codespan::FileId;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::ops::Range;
+use std::path::{Component, Path};
+use void::Void;
+
+/// The first line of a bundle.
+pub const HEADER: &str = "# nickel-bundle";
+
+/// The prefix of the lines of the source map.
+const SOURCE_PREFIX: &str = "# source ";
+
+/// A file inlined in a bundle.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Region {
+    /// The byte range of the file in the bundle.
+    pub range: Range<usize>,
+    /// The original name of the file.
+    pub name: String,
+}
+
+/// The source map of a bundle, which is stored in its header.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SourceMap {
+    pub regions: Vec<Region>,
+}
+
+impl SourceMap {
+    /// Read the source map of a bundle. Return `None` if the source is not a bundle, or if the
+    /// source map is invalid.
+    pub fn parse(source: &str) -> Option<SourceMap> {
+        let mut lines = source.lines();
+
+        if lines.next()? != HEADER {
+            return None;
+        }
+
+        let regions = lines
+            .map_while(|line| line.strip_prefix(SOURCE_PREFIX))
+            .map(|line| {
+                let mut parts = line.splitn(3, ' ');
+                let start = parts.next()?.parse().ok()?;
+                let end = parts.next()?.parse().ok()?;
+                let name = String::from(parts.next()?);
+                source.get(start..end)?;
+
+                Some(Region {
+                    range: start..end,
+                    name,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(SourceMap { regions })
+    }
+
+    /// Render the source map as the header of a bundle.
+    fn render(&self) -> String {
+        let mut header = format!("{}\n", HEADER);
+
+        for Region { range, name } in self.regions.iter() {
+            writeln!(
+                &mut header,
+                "{}{} {} {}",
+                SOURCE_PREFIX, range.start, range.end, name
+            )
+            .unwrap();
+        }
+
+        header
+    }
+}
+
+/// Bundle a program and the files it transitively imports into a single source, whose evaluation
+/// gives the same result. See the [module documentation](index.html).
+///
+/// The imports of the program must have been resolved beforehand, eagerly (see
+/// [`Cache::resolve_imports`](../cache/struct.Cache.html#method.resolve_imports)). An import
+/// cycle, which can't be expressed with let bindings, is an error.
+pub fn bundle(cache: &Cache, main_id: FileId) -> Result<String, ImportError> {
+    let mut order = Vec::new();
+    visit(
+        cache,
+        main_id,
+        &mut Vec::new(),
+        &mut HashSet::new(),
+        &mut order,
+    )?;
+
+    let vars: HashMap<FileId, String> = order
+        .iter()
+        .filter(|id| **id != main_id)
+        .enumerate()
+        .map(|(i, id)| (*id, format!("_bundle_{}", i + 1)))
+        .collect();
+    let base = Path::new(cache.name(main_id))
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+
+    let mut body = String::new();
+    let mut regions = Vec::new();
+
+    for id in order.iter() {
+        let source = module_source(cache, *id, &vars);
+
+        if *id != main_id {
+            writeln!(&mut body, "let {} = (", vars[id]).unwrap();
+        }
+
+        let start = body.len();
+        body.push_str(&source);
+        let name = Path::new(cache.name(*id));
+        regions.push(Region {
+            range: start..body.len(),
+            name: portable_name(name.strip_prefix(base).unwrap_or(name)),
+        });
+
+        if *id != main_id {
+            if !source.ends_with('\n') {
+                body.push('\n');
+            }
+            body.push_str(") in\n");
+        }
+    }
+
+    // The ranges of the source map are shifted by the length of the header, which itself depends
+    // on the number of digits of the ranges.
+    let mut shift = 0;
+    let header = loop {
+        let map = SourceMap {
+            regions: regions
+                .iter()
+                .map(|Region { range, name }| Region {
+                    range: (range.start + shift)..(range.end + shift),
+                    name: name.clone(),
+                })
+                .collect(),
+        };
+        let header = map.render();
+
+        if header.len() == shift {
+            break header;
+        }
+        shift = header.len();
+    };
+
+    Ok(header + &body)
+}
+
+/// Render a path with forward slashes whatever the platform, such that the same program gives the
+/// same bundle everywhere.
+fn portable_name(path: &Path) -> String {
+    path.components()
+        .map(|part| match part {
+            // The root is rendered by the separator which follows it.
+            Component::RootDir => Cow::Borrowed(""),
+            part => part.as_os_str().to_string_lossy(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Add the files imported by a file, and then the file itself, to `order`, such that each file
+/// comes after its dependencies. `chain` holds the files being visited, from the outermost one:
+/// importing one of them again closes an import cycle.
+fn visit(
+    cache: &Cache,
+    file_id: FileId,
+    chain: &mut Vec<FileId>,
+    visited: &mut HashSet<FileId>,
+    order: &mut Vec<FileId>,
+) -> Result<(), ImportError> {
+    if !visited.insert(file_id) {
+        return Ok(());
+    }
+
+    chain.push(file_id);
+
+    for (id, range) in imports(cache, file_id) {
+        if let Some(start) = chain.iter().position(|chain_id| *chain_id == id) {
+            let files = chain[start..]
+                .iter()
+                .chain(std::iter::once(&id))
+                .map(|id| cache.name(*id).to_string_lossy().into_owned())
+                .collect();
+            let pos = TermPos::Original(RawSpan {
+                src_id: file_id,
+                start: (range.start as u32).into(),
+                end: (range.end as u32).into(),
+            });
+
+            return Err(ImportError::ImportCycle(files, pos));
+        }
+
+        visit(cache, id, chain, visited, order)?;
+    }
+
+    chain.pop();
+    order.push(file_id);
+    Ok(())
+}
+
+/// The resolved imports of a file, together with their byte range in the source, in order.
+fn imports(cache: &Cache, file_id: FileId) -> Vec<(FileId, Range<usize>)> {
+    let term = match cache.get_ref(file_id) {
+        Some(term) => term.clone(),
+        None => return Vec::new(),
+    };
+
+    let mut imports = Vec::new();
+    term.traverse(
+        &mut |rt: RichTerm, imports: &mut Vec<(FileId, Range<usize>)>| -> Result<RichTerm, Void> {
+            match (rt.as_ref(), rt.pos) {
+                (Term::ResolvedImport(id), TermPos::Original(span)) if span.src_id == file_id => {
+                    imports.push((*id, span.start.to_usize()..span.end.to_usize()))
+                }
+                _ => (),
+            }
+            Ok(rt)
+        },
+        &mut imports,
+        TraverseOrder::TopDown,
+    )
+    .unwrap();
+
+    // Desugaring may duplicate a term, and thus an import.
+    imports.sort_by_key(|(_, range)| (range.start, range.end));
+    imports.dedup_by(|(_, r1), (_, r2)| r1 == r2);
+    imports
+}
+
+/// The source of a file, as inlined in a bundle.
+fn module_source(cache: &Cache, file_id: FileId, vars: &HashMap<FileId, String>) -> String {
+    let name = cache.name(file_id);
+
+    match InputFormat::from_path_buf(Path::new(name)) {
+        Some(InputFormat::Nickel) | None => {
+            let source = cache.files().source(file_id);
+            let mut result = String::with_capacity(source.len());
+            let mut last = 0;
+
+            for (id, range) in imports(cache, file_id) {
+                result.push_str(&source[last..range.start]);
+                result.push_str(&padded(&vars[&id], &source[range.clone()]));
+                last = range.end;
+            }
+
+            result.push_str(&source[last..]);
+            result
+        }
+        Some(_) => {
+            let term = cache
+                .get_ref(file_id)
+                .expect("bundle::module_source(): expected imported data to be parsed");
+            let mut result = String::new();
+            write_data(&mut result, term, 0);
+            result.push('\n');
+            result
+        }
+    }
+}
+
+/// Pad a variable replacing an import with spaces, keeping the line breaks of the import, such
+/// that the replacement has the same length as the import when possible.
+fn padded(var: &str, import: &str) -> String {
+    let mut result = String::from(var);
+
+    for c in import.chars().skip(var.len()) {
+        result.push(if c == '\n' { '\n' } else { ' ' });
+    }
+
+    result
+}
+
+/// Write a value of an external format, such as JSON, as a Nickel expression. Fields are sorted
+/// by name, such that the output is deterministic.
+fn write_data(out: &mut String, rt: &RichTerm, indent: usize) {
+    let pad = " ".repeat(indent + 2);
+
+    match rt.as_ref() {
+        Term::Null => out.push_str("null"),
+        Term::Bool(b) => write!(out, "{}", b).unwrap(),
+        Term::Num(n) if n.is_sign_negative() => write!(out, "({})", n).unwrap(),
+        Term::Num(n) => write!(out, "{}", n).unwrap(),
+        Term::Str(s) => write_string(out, s),
+        Term::Array(elts) if elts.is_empty() => out.push_str("[]"),
+        Term::Array(elts) => {
+            out.push_str("[\n");
+            for elt in elts.iter() {
+                out.push_str(&pad);
+                write_data(out, elt, indent + 2);
+                out.push_str(",\n");
+            }
+            write!(out, "{}]", " ".repeat(indent)).unwrap();
+        }
+        Term::Record(fields, _) if fields.is_empty() => out.push_str("{}"),
+        Term::Record(fields, _) => {
+            let mut fields: Vec<_> = fields.iter().collect();
+            fields.sort_by(|(id1, _), (id2, _)| id1.label.cmp(&id2.label));
+
+            out.push_str("{\n");
+            for (id, value) in fields {
+                out.push_str(&pad);
+                write_string(out, &id.label);
+                out.push_str(" = ");
+                write_data(out, value, indent + 2);
+                out.push_str(",\n");
+            }
+            write!(out, "{}}}", " ".repeat(indent)).unwrap();
+        }
+        t => panic!(
+            "bundle::write_data(): unexpected term in imported data: {}",
+            t.shallow_repr()
+        ),
+    }
+}
+
+/// Write a string literal, escaping the characters which can't appear as is.
+fn write_string(out: &mut String, s: &str) {
+    out.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '%' => out.push_str("\\%"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_ascii_control() => write!(out, "\\x{:02x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+}
@@ -1,14 +1,29 @@
 //! Source cache.
-
-use crate::error::{Error, ImportError, ParseError, ParseErrors, TypecheckError};
+//!
+//! Files are named after the path they were loaded from, as given by the user or as resolved from
+//! an import. Diagnostics render these names as native paths of the platform, that is with
+//! backslashes on Windows, such that they can be opened as is. Artifacts meant to be moved from a
+//! platform to another, such as the source map of a [bundle](../bundle/index.html), use forward
+//! slashes instead.
+
+use crate::bundle::{Region, SourceMap};
+use crate::error::{
+    DiagnosticOptions, Error, FileOperation, IOError, ImportError, ParseError, ParseErrors,
+    TypecheckError, MAX_PARSE_DIAGNOSTICS,
+};
+use crate::eval::cancellation::CancellationToken;
+use crate::identifier::Ident;
 use crate::parser::lexer::Lexer;
-use crate::position::TermPos;
+use crate::position::{RawSpan, TermPos};
+use crate::redact::Redactions;
+use crate::report_policy::ReportPolicy;
+use crate::serialize::{self, DuplicateKeys};
 use crate::stdlib as nickel_stdlib;
-use crate::term::{RichTerm, SharedTerm, Term};
+use crate::term::{RichTerm, SharedTerm, Term, TraverseOrder};
 use crate::transform::import_resolution;
 use crate::typecheck;
 use crate::typecheck::{linearization::StubHost, type_check};
-use crate::types::UnboundTypeVariableError;
+use crate::types::{Types, UnboundTypeVariableError};
 use crate::{eval, parser, transform};
 use codespan::{FileId, Files};
 use io::Read;
@@ -19,7 +34,8 @@ use std::fs;
 use std::io;
 use std::path::{Path, PathBuf};
 use std::result::Result;
-use std::time::SystemTime;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 use void::Void;
 
 /// Supported input formats.
@@ -32,7 +48,7 @@ pub enum InputFormat {
 }
 
 impl InputFormat {
-    fn from_path_buf(path_buf: &Path) -> Option<InputFormat> {
+    pub(crate) fn from_path_buf(path_buf: &Path) -> Option<InputFormat> {
         match path_buf.extension().and_then(OsStr::to_str) {
             Some("ncl") => Some(InputFormat::Nickel),
             Some("json") => Some(InputFormat::Json),
@@ -43,6 +59,19 @@ impl InputFormat {
     }
 }
 
+/// Where the standard library is loaded from. See [`Cache::set_stdlib`](struct.Cache.html#method.set_stdlib).
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub enum StdlibSource {
+    /// The sources embedded in the interpreter at compile time.
+    #[default]
+    Embedded,
+    /// A directory holding the modules of the standard library, with the same layout as the
+    /// embedded sources (see [`stdlib::MODULE_FILES`](../stdlib/constant.MODULE_FILES.html)).
+    Dir(PathBuf),
+    /// No standard library: programs start with an empty initial environment.
+    None,
+}
+
 /// File and terms cache.
 ///
 /// Manage a file database, which stores a set of sources (the original source code as string) and
@@ -57,6 +86,15 @@ impl InputFormat {
 /// Terms possibly undergo typechecking and program transformation. The state of each entry (that
 /// is, the operations that have been performed on this term) is stored in an
 /// [`EntryState`](./enum.EntryState.html).
+///
+/// # Concurrency
+///
+/// Read-only queries, such as [`files`](#method.files), [`terms`](#method.terms) or
+/// [`id_of`](#method.id_of), take `&self`, while mutation is confined to adding or updating sources
+/// and to the processing phases ([`parse`](#method.parse), [`prepare`](#method.prepare), ...).
+/// The file database can be shared between threads, for example to render diagnostics with
+/// [`to_simple_diagnostics`](../error/fn.to_simple_diagnostics.html), which doesn't mutate it. The
+/// cache itself can't, as terms are reference counted with `Rc`.
 #[derive(Debug, Clone)]
 pub struct Cache {
     /// The content of the program sources plus imports.
@@ -65,14 +103,62 @@ pub struct Cache {
     file_ids: HashMap<OsString, NameIdEntry>,
     /// Map containing for each FileIDs a list of files they import.
     imports: HashMap<FileId, HashSet<FileId>>,
+    /// Map containing for each FileId the files which import it. Contrary to `imports`, which only
+    /// records the first import of a file, every import is recorded. See
+    /// [`dependents`](#method.dependents).
+    dependents: HashMap<FileId, HashSet<FileId>>,
+    /// The number of sources typechecked so far. See
+    /// [`typecheck_count`](#method.typecheck_count).
+    typecheck_count: usize,
     /// The table storing parsed terms corresponding to the entries of the file database.
     terms: HashMap<FileId, CachedTerm>,
     /// The list of ids corresponding to the stdlib modules
     stdlib_ids: Option<Vec<FileId>>,
+    /// The durations of the different phases of the pipeline, if collected. See
+    /// [`enable_timings`](#method.enable_timings).
+    timings: Option<Timings>,
+    /// If imports are resolved lazily, that is when they are forced during evaluation instead of
+    /// at the imports resolution phase. See [`set_lazy_imports`](#method.set_lazy_imports).
+    lazy_imports: bool,
+    /// The maximum number of parse errors reported for a source, after the deduplication of
+    /// cascading errors, or `None` to report all errors. See
+    /// [`set_max_parse_errors`](#method.set_max_parse_errors).
+    max_parse_errors: Option<usize>,
+    /// How to handle duplicate keys when parsing JSON sources. See
+    /// [`set_json_duplicate_keys`](#method.set_json_duplicate_keys).
+    json_duplicate_keys: DuplicateKeys,
+    /// The options used when reporting errors. See
+    /// [`diagnostic_options`](#method.diagnostic_options).
+    diagnostic_options: DiagnosticOptions,
+    /// Where the standard library is loaded from. See [`set_stdlib`](#method.set_stdlib).
+    stdlib: StdlibSource,
+    /// The token checked during evaluation to abort it, if any. See
+    /// [`set_cancellation`](#method.set_cancellation).
+    cancellation: Option<CancellationToken>,
+    /// The directories searched for relative imports which are not found next to the importing
+    /// file. See [`set_import_paths`](#method.set_import_paths).
+    import_paths: Vec<PathBuf>,
+}
 
-    #[cfg(debug_assertions)]
-    /// Skip loading the stdlib, used for debugging purpose
-    pub skip_stdlib: bool,
+/// The cumulated durations of the different phases of the processing of a program.
+///
+/// Each phase accumulates the time spent on all the sources it has been applied to, which
+/// includes imports. Timings are only collected when explicitly enabled, see
+/// [`Cache::enable_timings`](struct.Cache.html#method.enable_timings).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Timings {
+    /// Loading, parsing and preparing the standard library.
+    pub stdlib: Duration,
+    /// Parsing the program.
+    pub parse: Duration,
+    /// Resolving (loading and parsing) imports.
+    pub imports: Duration,
+    /// Typechecking.
+    pub typecheck: Duration,
+    /// Program transformations.
+    pub transform: Duration,
+    /// Evaluation.
+    pub eval: Duration,
 }
 
 /// wrapping eval environment with typing environment
@@ -207,20 +293,197 @@ impl Cache {
             file_ids: HashMap::new(),
             terms: HashMap::new(),
             imports: HashMap::new(),
+            dependents: HashMap::new(),
+            typecheck_count: 0,
             stdlib_ids: None,
+            timings: None,
+            lazy_imports: false,
+            max_parse_errors: Some(MAX_PARSE_DIAGNOSTICS),
+            json_duplicate_keys: DuplicateKeys::default(),
+            diagnostic_options: DiagnosticOptions::default(),
+            stdlib: StdlibSource::default(),
+            cancellation: None,
+            import_paths: Vec::new(),
+        }
+    }
+
+    /// Defer the resolution of imports to evaluation time. Imports are then left unresolved by
+    /// [`resolve_imports`](#method.resolve_imports), and an imported file is only read, parsed and
+    /// transformed when the corresponding import is forced. A file which is never used can thus be
+    /// missing or broken without causing any error.
+    ///
+    /// An unresolved import is typed as `Dyn`, and lazily imported files are not statically
+    /// typechecked. Imports are resolved eagerly by default.
+    pub fn set_lazy_imports(&mut self, lazy: bool) {
+        self.lazy_imports = lazy;
+    }
+
+    /// Return `true` if imports are resolved lazily. See
+    /// [`set_lazy_imports`](#method.set_lazy_imports).
+    pub fn lazy_imports(&self) -> bool {
+        self.lazy_imports
+    }
 
-            #[cfg(debug_assertions)]
-            skip_stdlib: false,
+    /// Set the maximum number of parse errors reported for a source. Parse errors are
+    /// deduplicated and limited using
+    /// [`ParseErrors::dedup_and_limit`](../error/struct.ParseErrors.html#method.dedup_and_limit),
+    /// unless `max` is `None`, in which case all the errors are reported as they are. Default to
+    /// [`MAX_PARSE_DIAGNOSTICS`](../error/constant.MAX_PARSE_DIAGNOSTICS.html).
+    pub fn set_max_parse_errors(&mut self, max: Option<usize>) {
+        self.max_parse_errors = max;
+        self.diagnostic_options.max_parse_errors = max;
+    }
+
+    /// Deduplicate and limit parse errors according to the maximum number of parse errors set by
+    /// [`set_max_parse_errors`](#method.set_max_parse_errors).
+    pub fn process_parse_errors(&self, errs: ParseErrors) -> ParseErrors {
+        match self.max_parse_errors {
+            Some(max) => errs.dedup_and_limit(max),
+            None => errs,
         }
     }
 
+    /// Set how duplicate keys are handled when parsing JSON sources, typically JSON imports.
+    /// Default to [`DuplicateKeys::Error`](../serialize/enum.DuplicateKeys.html).
+    pub fn set_json_duplicate_keys(&mut self, duplicate_keys: DuplicateKeys) {
+        self.json_duplicate_keys = duplicate_keys;
+    }
+
+    /// Set the number of calls reported at each end of the call stack of a blame error, or `None`
+    /// to report the whole call stack. Default to
+    /// [`DEFAULT_CALL_STACK_LIMIT`](../error/constant.DEFAULT_CALL_STACK_LIMIT.html).
+    pub fn set_call_stack_limit(&mut self, limit: Option<usize>) {
+        self.diagnostic_options.call_stack_limit = limit;
+    }
+
+    /// Set where the standard library is loaded from. Default to the embedded sources. It must be
+    /// set before the standard library is loaded, and has no effect afterwards.
+    pub fn set_stdlib(&mut self, stdlib: StdlibSource) {
+        self.stdlib = stdlib;
+    }
+
+    /// Set the import search path: the directories searched, in order, for a relative import which
+    /// is not found next to the importing file. Empty by default.
+    ///
+    /// An import `import "lib/contracts.ncl"` in `/project/main.ncl` is first looked for at
+    /// `/project/lib/contracts.ncl`, then at `<dir>/lib/contracts.ncl` for each directory of the
+    /// search path. The first existing file is imported. Absolute imports are not searched.
+    pub fn set_import_paths(&mut self, paths: Vec<PathBuf>) {
+        self.import_paths = paths;
+    }
+
+    /// The import search path. See [`set_import_paths`](#method.set_import_paths).
+    pub fn import_paths(&self) -> &[PathBuf] {
+        &self.import_paths
+    }
+
+    /// Return the paths an import is looked for at, in order: relatively to the importing file,
+    /// or to the current directory if there is none, then relatively to each directory of the
+    /// import search path if the import is relative.
+    pub fn import_candidates(&self, path: &OsStr, parent: Option<PathBuf>) -> Vec<PathBuf> {
+        let mut candidates = vec![with_parent(path, parent)];
+
+        if Path::new(path).is_relative() {
+            candidates.extend(self.import_paths.iter().map(|dir| dir.join(path)));
+        }
+
+        candidates
+    }
+
+    /// Return the first existing path among the [candidates](#method.import_candidates) of an
+    /// import, or the first candidate if none exists.
+    pub fn locate_import(&self, path: &OsStr, parent: Option<PathBuf>) -> PathBuf {
+        let mut candidates = self.import_candidates(path, parent);
+
+        match candidates.iter().position(|candidate| candidate.exists()) {
+            Some(index) => candidates.swap_remove(index),
+            None => candidates.swap_remove(0),
+        }
+    }
+
+    /// Set the token checked during evaluation, such that the evaluation can be aborted from another
+    /// thread. See [`eval::cancellation`](../eval/cancellation/index.html).
+    pub fn set_cancellation(&mut self, token: CancellationToken) {
+        self.cancellation = Some(token);
+    }
+
+    /// The token checked during evaluation, if any. See [`set_cancellation`](#method.set_cancellation).
+    pub fn cancellation(&self) -> Option<&CancellationToken> {
+        self.cancellation.as_ref()
+    }
+
+    /// Set the policy applied to the diagnostics of errors before they are rendered, see
+    /// [`report_policy`](../report_policy/index.html). No policy is applied by default.
+    pub fn set_report_policy(&mut self, policy: Arc<dyn ReportPolicy>) {
+        self.diagnostic_options.policy = Some(policy);
+    }
+
+    /// Record the toplevel declaration made by an input of the REPL, to be labeled in the
+    /// diagnostics of later inputs. See
+    /// [`DiagnosticOptions::definitions`](../error/struct.DiagnosticOptions.html#structfield.definitions).
+    pub fn add_definition(&mut self, file_id: FileId, id: Ident) {
+        self.diagnostic_options.definitions.insert(file_id, id);
+    }
+
+    /// Forget the toplevel declarations recorded with [`add_definition`](#method.add_definition).
+    pub fn clear_definitions(&mut self) {
+        self.diagnostic_options.definitions.clear();
+    }
+
+    /// The options used when reporting errors, see
+    /// [`DiagnosticOptions`](../error/struct.DiagnosticOptions.html).
+    pub fn diagnostic_options(&self) -> &DiagnosticOptions {
+        &self.diagnostic_options
+    }
+
+    /// The sensitive values met during evaluation so far, which must be redacted from anything
+    /// shown to the user. See [`redact`](../redact/index.html).
+    pub fn redactions(&self) -> &Redactions {
+        &self.diagnostic_options.redactions
+    }
+
+    /// Start collecting the durations of the different phases of the pipeline. Collection is
+    /// disabled by default.
+    pub fn enable_timings(&mut self) {
+        self.timings.get_or_insert_with(Timings::default);
+    }
+
+    /// Return the timings collected so far, or `None` if their collection is not enabled.
+    pub fn timings(&self) -> Option<&Timings> {
+        self.timings.as_ref()
+    }
+
+    /// Return the current instant if timings are collected, or `None` otherwise.
+    pub fn timing_start(&self) -> Option<Instant> {
+        self.timings.as_ref().map(|_| Instant::now())
+    }
+
+    /// Add the time elapsed since `start` to a phase of the timings. Do nothing if `start` is
+    /// `None`, that is if timings are not collected.
+    pub fn record_timing<F>(&mut self, start: Option<Instant>, phase: F)
+    where
+        F: FnOnce(&mut Timings) -> &mut Duration,
+    {
+        if let (Some(start), Some(timings)) = (start, self.timings.as_mut()) {
+            *phase(timings) += start.elapsed();
+        }
+    }
+
+    /// Add a source to the file database, and record its id as the one of the last file in the
+    /// diagnostic options.
+    fn add_to_files(&mut self, name: impl Into<OsString>, source: String) -> FileId {
+        let file_id = self.files.add(name, source);
+        self.diagnostic_options.last_file = Some(file_id);
+        file_id
+    }
+
     /// Load a file in the file database. Do not insert an entry in the name-id table.
     fn load_file(&mut self, path: impl Into<OsString>) -> io::Result<FileId> {
         let path = path.into();
         let mut buffer = String::new();
         fs::File::open(&path)
             .and_then(|mut file| file.read_to_string(&mut buffer))
-            .map(|_| self.files.add(path, buffer))
+            .map(|_| self.add_to_files(path, buffer))
     }
 
     /// Same as [`add_file`](#method.add_file), but assume that the path is already normalized,
@@ -299,7 +562,7 @@ impl Cache {
     /// will override the old entry in the name-id table.
     pub fn add_string(&mut self, source_name: impl Into<OsString>, s: String) -> FileId {
         let source_name = source_name.into();
-        let id = self.files.add(source_name.clone(), s);
+        let id = self.add_to_files(source_name.clone(), s);
         self.file_ids.insert(
             source_name,
             NameIdEntry {
@@ -323,7 +586,7 @@ impl Cache {
             self.terms.remove(&file_id);
             file_id
         } else {
-            let file_id = self.files.add(source_name.clone(), s);
+            let file_id = self.add_to_files(source_name.clone(), s);
             self.file_ids.insert(
                 source_name,
                 NameIdEntry {
@@ -342,6 +605,7 @@ impl Cache {
             Ok(CacheOp::Cached(parse_errs.clone()))
         } else {
             let (term, parse_errs) = self.parse_nocache(file_id)?;
+            self.register_bundle(file_id);
             self.terms.insert(
                 file_id,
                 CachedTerm {
@@ -365,6 +629,9 @@ impl Cache {
             Ok(CacheOp::Cached(parse_errs.clone()))
         } else {
             let (term, parse_errs) = self.parse_nocache_multi(file_id, format)?;
+            if format == InputFormat::Nickel {
+                self.register_bundle(file_id);
+            }
             self.terms.insert(
                 file_id,
                 CachedTerm {
@@ -377,6 +644,26 @@ impl Cache {
         }
     }
 
+    /// If a source is a [bundle](../bundle/index.html), add the files inlined in it to the file
+    /// database, such that the diagnostics located in the bundle are reported in these files
+    /// instead.
+    fn register_bundle(&mut self, file_id: FileId) {
+        let source_map = match SourceMap::parse(self.files.source(file_id)) {
+            Some(source_map) => source_map,
+            None => return,
+        };
+
+        let regions = source_map
+            .regions
+            .into_iter()
+            .map(|Region { range, name }| {
+                let source = self.files.source(file_id)[range.clone()].to_owned();
+                (range, self.add_to_files(name, source))
+            })
+            .collect();
+        self.diagnostic_options.source_maps.insert(file_id, regions);
+    }
+
     /// Parse a source without querying nor populating the cache.
     pub fn parse_nocache(&self, file_id: FileId) -> Result<(RichTerm, ParseErrors), ParseError> {
         self.parse_nocache_multi(file_id, InputFormat::Nickel)
@@ -395,20 +682,39 @@ impl Cache {
                 let (t, parse_errs) = parser::grammar::TermParser::new()
                     .parse_term_tolerant(file_id, Lexer::new(buf))?;
 
-                Ok((t, parse_errs))
+                Ok((t, self.process_parse_errors(parse_errs)))
             }
-            InputFormat::Json => serde_json::from_str(self.files.source(file_id))
-                .map(|t| (t, ParseErrors::default()))
-                .map_err(|err| ParseError::from_serde_json(err, file_id, &self.files)),
-            InputFormat::Yaml => serde_yaml::from_str(self.files.source(file_id))
-                .map(|t| (t, ParseErrors::default()))
-                .map_err(|err| (ParseError::from_serde_yaml(err, file_id))),
+            InputFormat::Json => {
+                serialize::json_from_str(self.files.source(file_id), self.json_duplicate_keys)
+                    .map(|t| (self.with_provenance(t, file_id), ParseErrors::default()))
+                    .map_err(|err| ParseError::from_serde_json(err, file_id, &self.files))
+            }
+            InputFormat::Yaml => serialize::yaml_from_str(self.files.source(file_id))
+                .map(|t| (self.with_provenance(t, file_id), ParseErrors::default()))
+                .map_err(|err| ParseError::from_serde_yaml(err, file_id, &self.files)),
             InputFormat::Toml => toml::from_str(self.files.source(file_id))
-                .map(|t| (t, ParseErrors::default()))
-                .map_err(|err| (ParseError::from_toml(err, file_id, &self.files))),
+                .map(|t| (self.with_provenance(t, file_id), ParseErrors::default()))
+                .map_err(|err| ParseError::from_toml(err, file_id, &self.files)),
         }
     }
 
+    /// Deserializers of external formats don't provide positions. Record the source of each node
+    /// of a deserialized term in its position, such that errors involving this term can still
+    /// tell which file it comes from.
+    fn with_provenance(&self, rt: RichTerm, file_id: FileId) -> RichTerm {
+        let result: Result<RichTerm, Void> = rt.traverse(
+            &mut |mut rt: RichTerm, _: &mut ()| {
+                if let TermPos::None = rt.pos {
+                    rt.pos = TermPos::File(file_id);
+                }
+                Ok(rt)
+            },
+            &mut (),
+            TraverseOrder::BottomUp,
+        );
+        result.unwrap()
+    }
+
     /// Typecheck an entry of the cache and update its state accordingly, or do nothing if the
     /// entry has already been typechecked. Require that the corresponding source has been parsed.
     /// If the source contains imports, recursively typecheck on the imports too.
@@ -423,6 +729,7 @@ impl Cache {
             }
             Some(CachedTerm { term, state, .. }) if *state >= EntryState::Parsed => {
                 if *state < EntryState::Typechecking {
+                    self.typecheck_count += 1;
                     type_check(term, global_env, self, StubHost::<(), (), _>::new())?;
                     self.update_state(file_id, EntryState::Typechecking);
                 }
@@ -571,12 +878,20 @@ impl Cache {
     /// nothing if the imports of the entry have already been resolved. Require that the
     /// corresponding source has been parsed.
     /// If resolved imports contain imports themselves, resolve them recursively.
+    ///
+    /// If imports are lazy (see [`set_lazy_imports`](#method.set_lazy_imports)), the entry is
+    /// left as it is, and its imports are resolved only when they are forced during evaluation.
     pub fn resolve_imports(
         &mut self,
         file_id: FileId,
     ) -> Result<CacheOp<()>, CacheError<ImportError>> {
         match self.entry_state(file_id) {
             Some(state) if state >= EntryState::ImportsResolved => Ok(CacheOp::Cached(())),
+            // In lazy mode, imports are left untouched and resolved on demand by the evaluator.
+            Some(state) if state >= EntryState::Parsed && self.lazy_imports => {
+                self.update_state(file_id, EntryState::ImportsResolved);
+                Ok(CacheOp::Done(()))
+            }
             Some(state) if state >= EntryState::Parsed => {
                 if state < EntryState::ImportsResolving {
                     let CachedTerm {
@@ -592,9 +907,7 @@ impl Cache {
                         },
                     );
 
-                    for id in pending {
-                        self.resolve_imports(id)?;
-                    }
+                    self.resolve_pending_imports(&pending)?;
                 } else {
                     let pending = self.imports.get(&file_id).cloned().unwrap_or_default();
 
@@ -610,6 +923,226 @@ impl Cache {
         }
     }
 
+    /// Resolve the imports of the files stacked by
+    /// [`import_resolution::resolve_imports`](../transform/import_resolution/fn.resolve_imports.html).
+    /// An error occurring in one of these files is wrapped in [`ImportError::Transitive`] together
+    /// with the position of its import, such that the whole import chain can be reported.
+    pub fn resolve_pending_imports(
+        &mut self,
+        pending: &[(FileId, TermPos)],
+    ) -> Result<(), CacheError<ImportError>> {
+        for (id, pos) in pending {
+            self.resolve_imports(*id)
+                .map_err(|cache_err| match cache_err {
+                    CacheError::Error(err) => {
+                        CacheError::Error(ImportError::Transitive(Box::new(err), *pos))
+                    }
+                    CacheError::NotParsed => CacheError::NotParsed,
+                })?;
+        }
+
+        Ok(())
+    }
+
+    /// Return the absolute paths of the files transitively imported by an entry, including
+    /// imported data files such as JSON, YAML or TOML ones, sorted and without duplicates. The
+    /// entry itself is not included, unless it is imported back by one of its imports.
+    ///
+    /// Only resolved imports are taken into account: imports must have been resolved beforehand,
+    /// eagerly (see [`resolve_imports`](#method.resolve_imports)). Import cycles are allowed, each
+    /// file being visited once.
+    pub fn dependencies(&self, file_id: FileId) -> Vec<PathBuf> {
+        let mut visited = HashSet::new();
+        let mut stack = vec![file_id];
+
+        while let Some(id) = stack.pop() {
+            let term = match self.terms.get(&id) {
+                Some(CachedTerm { term, .. }) => term.clone(),
+                None => continue,
+            };
+
+            let mut imports = Vec::new();
+            term.traverse(
+                &mut |rt: RichTerm, imports: &mut Vec<FileId>| -> Result<RichTerm, Void> {
+                    if let Term::ResolvedImport(id) = rt.as_ref() {
+                        imports.push(*id);
+                    }
+                    Ok(rt)
+                },
+                &mut imports,
+                TraverseOrder::TopDown,
+            )
+            .unwrap();
+
+            stack.extend(imports.into_iter().filter(|id| visited.insert(*id)));
+        }
+
+        let mut paths: Vec<PathBuf> = visited
+            .into_iter()
+            .map(|id| {
+                let path = self.files.name(id);
+                fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path))
+            })
+            .collect();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+
+    /// Return the files which transitively import an entry, the entry excluded, in topological
+    /// order: each file comes after the files it imports. Only resolved imports are taken into
+    /// account.
+    pub fn dependents(&self, file_id: FileId) -> Vec<FileId> {
+        fn visit(
+            deps: &HashMap<FileId, HashSet<FileId>>,
+            id: FileId,
+            visited: &mut HashSet<FileId>,
+            order: &mut Vec<FileId>,
+        ) {
+            if !visited.insert(id) {
+                return;
+            }
+
+            for dep in deps.get(&id).into_iter().flatten() {
+                visit(deps, *dep, visited, order);
+            }
+
+            order.push(id);
+        }
+
+        let mut order = Vec::new();
+        visit(&self.dependents, file_id, &mut HashSet::new(), &mut order);
+        // A file is visited after all the files which import it.
+        order.pop();
+        order.reverse();
+        order
+    }
+
+    /// Check a source again after an edit, together with the files which transitively import it,
+    /// and return the errors of each checked file. The new content of the source must have been
+    /// stored beforehand in the file database under the same id (see
+    /// [`files_mut`](#method.files_mut)).
+    ///
+    /// The source is parsed, its imports are resolved and it is typechecked again. A file only sees
+    /// the files it imports through their apparent type: a dependent is thus typechecked again,
+    /// in topological order, only if the apparent type of one of its imports has changed. Other
+    /// entries are left untouched, and don't appear in the result.
+    ///
+    /// If the source can't be parsed or its imports can't be resolved, its entry and its
+    /// dependents are left as they were before the edit. Dependents are typechecked in place:
+    /// this is meant for entries which have not been transformed, as is the case in an editor.
+    pub fn invalidate_and_check(
+        &mut self,
+        file_id: FileId,
+        global_env: &typecheck::Environment,
+    ) -> Vec<(FileId, Vec<Error>)> {
+        let dependents = self.dependents(file_id);
+        let interfaces: HashMap<FileId, String> = std::iter::once(file_id)
+            .chain(dependents.iter().copied())
+            .filter_map(|id| Some((id, self.interface(id)?)))
+            .collect();
+
+        let mut errors = match self.reload(file_id) {
+            Ok(errors) => errors,
+            Err(errors) => return vec![(file_id, errors)],
+        };
+        if let Err(CacheError::Error(err)) = self.typecheck(file_id, global_env) {
+            errors.push(err.into());
+        }
+
+        let mut result = vec![(file_id, errors)];
+        let mut changed = HashSet::new();
+        if self.interface(file_id) != interfaces.get(&file_id).cloned() {
+            changed.insert(file_id);
+        }
+
+        for id in dependents {
+            let affected = changed.iter().any(|changed_id| {
+                matches!(self.dependents.get(changed_id), Some(deps) if deps.contains(&id))
+            });
+            if !affected {
+                continue;
+            }
+
+            let term = self.get_owned(id).unwrap();
+            self.typecheck_count += 1;
+            let errors = type_check(&term, global_env, self, StubHost::<(), (), _>::new())
+                .err()
+                .map(Error::from)
+                .into_iter()
+                .collect();
+            result.push((id, errors));
+
+            if self.interface(id) != interfaces.get(&id).cloned() {
+                changed.insert(id);
+            }
+        }
+
+        result
+    }
+
+    /// Parse an edited source and resolve its imports, replacing its entry. Return the non fatal
+    /// parse errors. In case of failure, restore the entry as it was before the edit.
+    fn reload(&mut self, file_id: FileId) -> Result<Vec<Error>, Vec<Error>> {
+        let format = InputFormat::from_path_buf(Path::new(self.name(file_id)))
+            .unwrap_or(InputFormat::Nickel);
+        let (term, parse_errs) = self
+            .parse_nocache_multi(file_id, format)
+            .map_err(|err| vec![err.into()])?;
+
+        let old_entry = self.terms.insert(
+            file_id,
+            CachedTerm {
+                term,
+                state: EntryState::Parsed,
+                parse_errs: parse_errs.clone(),
+            },
+        );
+        // The imports of the new version are recorded again during their resolution.
+        let old_dependents = self.dependents.clone();
+        for deps in self.dependents.values_mut() {
+            deps.remove(&file_id);
+        }
+
+        let mut errors = Vec::new();
+        if !parse_errs.no_errors() {
+            errors.push(parse_errs.into());
+        }
+
+        match self.resolve_imports(file_id) {
+            Ok(_) => Ok(errors),
+            Err(cache_err) => {
+                errors.push(
+                    cache_err
+                        .unwrap_error("cache::reload(): expected source to be parsed")
+                        .into(),
+                );
+                self.dependents = old_dependents;
+                match old_entry {
+                    Some(entry) => self.terms.insert(file_id, entry),
+                    None => self.terms.remove(&file_id),
+                };
+                Err(errors)
+            }
+        }
+    }
+
+    /// The apparent type of an entry, which is the type seen by the files importing it, as a
+    /// string. Types are compared in this form, which ignores positions, such that an edit which
+    /// only moves an annotation doesn't change the apparent type.
+    fn interface(&self, file_id: FileId) -> Option<String> {
+        let term = self.get_ref(file_id)?;
+        let ty: Types = typecheck::apparent_type(term.as_ref(), None, Some(self)).into();
+        Some(ty.to_string())
+    }
+
+    /// The number of sources typechecked so far by the cache, including the standard library if
+    /// it has been typechecked. Used to check that [`invalidate_and_check`](#method.invalidate_and_check)
+    /// only typechecks the affected files again.
+    pub fn typecheck_count(&self) -> usize {
+        self.typecheck_count
+    }
+
     /// Prepare a source for evaluation: parse it, resolve the imports,
     /// typecheck it and apply program transformations,
     /// if it was not already done.
@@ -620,7 +1153,11 @@ impl Cache {
     ) -> Result<CacheOp<()>, Error> {
         let mut result = CacheOp::Cached(());
 
-        match self.parse(file_id)? {
+        let start = self.timing_start();
+        let parse_res = self.parse(file_id);
+        self.record_timing(start, |t| &mut t.parse);
+
+        match parse_res? {
             CacheOp::Done(e) | CacheOp::Cached(e) if !e.no_errors() => return Err(e.into()),
             CacheOp::Done(_) => {
                 result = CacheOp::Done(());
@@ -628,7 +1165,10 @@ impl Cache {
             _ => {}
         };
 
-        let import_res = self.resolve_imports(file_id).map_err(|cache_err| {
+        let start = self.timing_start();
+        let import_res = self.resolve_imports(file_id);
+        self.record_timing(start, |t| &mut t.imports);
+        let import_res = import_res.map_err(|cache_err| {
             cache_err.unwrap_error(
                 "cache::prepare(): expected source to be parsed before imports resolutions",
             )
@@ -637,7 +1177,10 @@ impl Cache {
             result = CacheOp::Done(());
         };
 
-        let typecheck_res = self.typecheck(file_id, global_env).map_err(|cache_err| {
+        let start = self.timing_start();
+        let typecheck_res = self.typecheck(file_id, global_env);
+        self.record_timing(start, |t| &mut t.typecheck);
+        let typecheck_res = typecheck_res.map_err(|cache_err| {
             cache_err
                 .unwrap_error("cache::prepare(): expected source to be parsed before typechecking")
         })?;
@@ -645,7 +1188,10 @@ impl Cache {
             result = CacheOp::Done(());
         };
 
-        let transform_res = self.transform(file_id).map_err(|cache_err| {
+        let start = self.timing_start();
+        let transform_res = self.transform(file_id);
+        self.record_timing(start, |t| &mut t.transform);
+        let transform_res = transform_res.map_err(|cache_err| {
             Error::ParseErrors(
                 cache_err
                     .unwrap_error(
@@ -672,7 +1218,7 @@ impl Cache {
         &mut self,
         file_id: FileId,
         global_env: &typecheck::Environment,
-    ) -> Result<(RichTerm, Vec<FileId>), Error> {
+    ) -> Result<(RichTerm, Vec<(FileId, TermPos)>), Error> {
         let (term, errs) = self.parse_nocache(file_id)?;
         if errs.no_errors() {
             return Err(Error::ParseErrors(errs));
@@ -773,26 +1319,64 @@ impl Cache {
     }
 
     /// Load and parse the standard library in the cache.
+    ///
+    /// When the standard library is loaded from a directory (see
+    /// [`set_stdlib`](#method.set_stdlib)), each module is checked to be a record, such that an
+    /// invalid module is reported with a diagnostic pointing to the file, instead of failing when
+    /// building the initial environments.
     pub fn load_stdlib(&mut self) -> Result<CacheOp<()>, Error> {
         if self.stdlib_ids.is_some() {
             return Ok(CacheOp::Cached(()));
         }
 
-        let file_ids: Vec<FileId> = nickel_stdlib::modules()
-            .into_iter()
-            .map(|(name, content)| self.add_string(OsString::from(name), String::from(content)))
-            .collect();
+        let file_ids: Vec<FileId> = match &self.stdlib {
+            StdlibSource::Embedded => nickel_stdlib::modules()
+                .into_iter()
+                .map(|(name, content)| self.add_string(OsString::from(name), String::from(content)))
+                .collect(),
+            StdlibSource::Dir(dir) => {
+                let dir = dir.clone();
+                nickel_stdlib::MODULE_FILES
+                    .iter()
+                    .map(|file| {
+                        let path = dir.join(file);
+                        self.add_file(path.clone())
+                            .map_err(|err| IOError::file(path, FileOperation::Read, err).into())
+                    })
+                    .collect::<Result<_, Error>>()?
+            }
+            StdlibSource::None => Vec::new(),
+        };
 
         for file_id in file_ids.iter() {
             let errs = self.parse(*file_id)?.inner();
             if !errs.no_errors() {
                 return Err(errs.into());
             }
+
+            let term = self.get_ref(*file_id).unwrap();
+            if !matches!(term.as_ref(), Term::RecRecord(..)) {
+                return Err(ImportError::InvalidStdlibModule(
+                    self.name(*file_id).to_string_lossy().into_owned(),
+                    term.pos,
+                )
+                .into());
+            }
         }
         self.stdlib_ids.replace(file_ids);
         Ok(CacheOp::Done(()))
     }
 
+    /// The id of the module defining the builtin contracts, or `None` if the standard library is
+    /// not loaded or is disabled. Used to hide the internals of the builtin contracts from error
+    /// messages.
+    pub fn contracts_id(&self) -> Option<FileId> {
+        self.stdlib_ids
+            .as_ref()
+            .and_then(|ids| ids.get(nickel_stdlib::CONTRACT_INDEX))
+            .copied()
+    }
+
     /// Typecheck the standard library. Currently only used in the test suite.
     pub fn typecheck_stdlib(&mut self) -> Result<CacheOp<()>, CacheError<TypecheckError>> {
         // We have a small bootstraping problem: to typecheck the global environment, we already
@@ -826,10 +1410,14 @@ impl Cache {
     /// type environment, use `load_stdlib()` then `mk_global_type` to avoid
     /// transformations and evaluation preparation.
     pub fn prepare_stdlib(&mut self) -> Result<GlobalEnv, Error> {
-        #[cfg(debug_assertions)]
-        if self.skip_stdlib {
-            return Ok(GlobalEnv::new());
-        }
+        let start = self.timing_start();
+        let result = self.prepare_stdlib_untimed();
+        self.record_timing(start, |t| &mut t.stdlib);
+        result
+    }
+
+    /// Actual implementation of [`prepare_stdlib`](#method.prepare_stdlib), without timing.
+    fn prepare_stdlib_untimed(&mut self) -> Result<GlobalEnv, Error> {
         self.load_stdlib()?;
         let type_env = self.mk_types_env().unwrap();
 
@@ -924,10 +1512,37 @@ pub trait ImportResolver {
         pos: &TermPos,
     ) -> Result<(ResolvedTerm, FileId), ImportError>;
 
+    /// Resolve an import encountered during evaluation, when imports are resolved lazily.
+    ///
+    /// Contrary to [`resolve`](#tymethod.resolve), the imported term is fully prepared for
+    /// evaluation, such that it can be directly retrieved with [`get`](#tymethod.get) using the
+    /// returned file id.
+    fn resolve_lazy(
+        &mut self,
+        path: &OsStr,
+        parent: Option<PathBuf>,
+        pos: &TermPos,
+    ) -> Result<FileId, ImportError>;
+
     /// Get a resolved import from the term cache.
     fn get(&self, file_id: FileId) -> Option<RichTerm>;
 
     fn get_path(&self, file_id: FileId) -> &OsStr;
+
+    /// Record a sensitive string met during evaluation, to be redacted from error messages. Do
+    /// nothing by default. See [`redact`](../redact/index.html).
+    fn add_sensitive(&mut self, _value: String) {}
+
+    /// Record the span of the expression defining a sensitive value, to be redacted from the
+    /// sources shown by error messages. Do nothing by default. See
+    /// [`redact`](../redact/index.html).
+    fn add_sensitive_span(&mut self, _span: RawSpan) {}
+
+    /// Whether the evaluation has been cancelled. Always `false` by default. See
+    /// [`eval::cancellation`](../eval/cancellation/index.html).
+    fn is_cancelled(&self) -> bool {
+        false
+    }
 }
 
 impl ImportResolver for Cache {
@@ -937,27 +1552,39 @@ impl ImportResolver for Cache {
         parent: Option<PathBuf>,
         pos: &TermPos,
     ) -> Result<(ResolvedTerm, FileId), ImportError> {
-        let path_buf = with_parent(path, parent.clone());
+        let path_buf = self.locate_import(path, parent.clone());
         let format = InputFormat::from_path_buf(&path_buf).unwrap_or(InputFormat::Nickel);
         let id_op = self.get_or_add_file(&path_buf).map_err(|err| {
-            ImportError::IOError(
-                path.to_string_lossy().into_owned(),
-                format!("{}", err),
-                *pos,
-            )
+            let msg = if self.import_paths.is_empty() || Path::new(path).is_absolute() {
+                format!("{}", err)
+            } else {
+                // List the directories which were tried, in order.
+                let local = parent
+                    .as_deref()
+                    .and_then(Path::parent)
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .unwrap_or_else(|| Path::new("."));
+                let dirs: Vec<String> = std::iter::once(local)
+                    .chain(self.import_paths.iter().map(PathBuf::as_path))
+                    .map(|dir| dir.display().to_string())
+                    .collect();
+                format!("{} (searched in: {})", err, dirs.join(", "))
+            };
+
+            ImportError::IOError(path.to_string_lossy().into_owned(), msg, *pos)
         })?;
+        if let Some(parent_id) = parent.as_ref().and_then(|parent| self.id_of(parent)) {
+            self.dependents
+                .entry(id_op.inner())
+                .or_default()
+                .insert(parent_id);
+        }
+
         let file_id = match id_op {
             CacheOp::Cached(id) => return Ok((ResolvedTerm::FromCache(), id)),
             CacheOp::Done(id) => {
-                if let Some(parent) = parent {
-                    let parent_id = self.id_of(parent).unwrap();
-                    if let Some(imports) = self.imports.get_mut(&parent_id) {
-                        imports.insert(id);
-                    } else {
-                        let mut imports = HashSet::new();
-                        imports.insert(id);
-                        self.imports.insert(parent_id, imports);
-                    }
+                if let Some(span) = pos.into_opt() {
+                    self.diagnostic_options.import_sites.insert(id, span);
                 }
                 id
             }
@@ -967,9 +1594,53 @@ impl ImportResolver for Cache {
         self.parse_multi(file_id, format)
             .map_err(|err| ImportError::ParseErrors(err.into(), *pos))?;
 
+        // Only the imports which could be parsed are recorded.
+        if let Some(parent) = parent {
+            let parent_id = self.id_of(parent).unwrap();
+            if let Some(imports) = self.imports.get_mut(&parent_id) {
+                imports.insert(file_id);
+            } else {
+                let mut imports = HashSet::new();
+                imports.insert(file_id);
+                self.imports.insert(parent_id, imports);
+            }
+        }
+
         Ok((ResolvedTerm::FromFile { path: path_buf }, file_id))
     }
 
+    fn resolve_lazy(
+        &mut self,
+        path: &OsStr,
+        parent: Option<PathBuf>,
+        pos: &TermPos,
+    ) -> Result<FileId, ImportError> {
+        let (_, file_id) = self.resolve(path, parent, pos)?;
+
+        // If the file couldn't be parsed when it was first imported, it is parsed again, such that
+        // the error is reported each time the import is forced.
+        if self.entry_state(file_id).is_none() {
+            let format = InputFormat::from_path_buf(&PathBuf::from(self.get_path(file_id)))
+                .unwrap_or(InputFormat::Nickel);
+            self.parse_multi(file_id, format)
+                .map_err(|err| ImportError::ParseErrors(err.into(), *pos))?;
+        }
+
+        self.resolve_imports(file_id).map_err(|cache_err| {
+            cache_err.unwrap_error("cache::resolve_lazy(): expected import to be parsed")
+        })?;
+        self.transform(file_id).map_err(|cache_err| {
+            ImportError::ParseErrors(
+                cache_err
+                    .unwrap_error("cache::resolve_lazy(): expected import to be parsed")
+                    .into(),
+                *pos,
+            )
+        })?;
+
+        Ok(file_id)
+    }
+
     fn get(&self, file_id: FileId) -> Option<RichTerm> {
         self.terms
             .get(&file_id)
@@ -982,6 +1653,21 @@ impl ImportResolver for Cache {
     fn get_path(&self, file_id: FileId) -> &OsStr {
         self.files.name(file_id)
     }
+
+    fn add_sensitive(&mut self, value: String) {
+        self.diagnostic_options.redactions.add(value);
+    }
+
+    fn add_sensitive_span(&mut self, span: RawSpan) {
+        self.diagnostic_options.redactions.add_span(span);
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancellation
+            .as_ref()
+            .map(CancellationToken::is_cancelled)
+            .unwrap_or(false)
+    }
 }
 
 /// Compute the path of a file relatively to a parent.
@@ -1022,6 +1708,15 @@ pub mod resolvers {
             panic!("cache::resolvers: dummy resolver should not have been invoked");
         }
 
+        fn resolve_lazy(
+            &mut self,
+            _path: &OsStr,
+            _parent: Option<PathBuf>,
+            _pos: &TermPos,
+        ) -> Result<FileId, ImportError> {
+            panic!("cache::resolvers: dummy resolver should not have been invoked");
+        }
+
         fn get(&self, _file_id: FileId) -> Option<RichTerm> {
             panic!("cache::resolvers: dummy resolver should not have been invoked");
         }
@@ -1092,6 +1787,15 @@ pub mod resolvers {
             }
         }
 
+        fn resolve_lazy(
+            &mut self,
+            path: &OsStr,
+            parent: Option<PathBuf>,
+            pos: &TermPos,
+        ) -> Result<FileId, ImportError> {
+            self.resolve(path, parent, pos).map(|(_, file_id)| file_id)
+        }
+
         fn get(&self, file_id: FileId) -> Option<RichTerm> {
             self.term_cache.get(&file_id).cloned()
         }
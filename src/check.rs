@@ -0,0 +1,605 @@
+//! Batch validation of Nickel files.
+//!
+//! This module implements the driver of the `check` subcommand, which parses, typechecks and
+//! optionally evaluates every file matching a list of glob patterns, without exporting anything.
+//! The outcome of each file is gathered in a [`CheckSummary`], which can be rendered as a table or
+//! serialized to JSON.
+//!
+//! # Isolation
+//!
+//! The failure of one file never aborts the check of the others. Files are checked using a shared
+//! [`Cache`], such that the standard library and the imports common to several files are only
+//! loaded and typechecked once. Since the cache is not thread-safe, each worker of the parallel
+//! mode (see [`CheckOptions::jobs`]) owns its own cache, which is then shared by all the files
+//! processed by this worker.
+//!
+//! # Glob patterns
+//!
+//! Patterns are expanded by [`expand_globs`]. A component of a pattern may contain the wildcards
+//! `*` (any sequence of characters), `?` (any character) and character classes such as `[a-z]` or
+//! `[!0-9]`. A component `**` matches any number of nested directories. Wildcards never match a
+//! leading `.`, such that hidden files and directories are ignored unless the pattern explicitly
+//! starts with a dot.
+use crate::cache::{Cache, CacheOp, GlobalEnv, StdlibSource};
+use crate::error::{
+    Error, FileOperation, IOError, SimpleDiagnostic, DEFAULT_CALL_STACK_LIMIT,
+    MAX_PARSE_DIAGNOSTICS,
+};
+use crate::eval;
+use crate::program::{report_simple, report_to, ColorOpt};
+use crate::report_policy::ReportPolicy;
+use crate::serialize::DuplicateKeys;
+use codespan::FileId;
+use codespan_reporting::term::termcolor::{Buffer, ColorChoice};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// The stack size of the worker threads of the parallel mode. Evaluation is deeply recursive, so
+/// we use the usual size of the stack of the main thread instead of the smaller default of
+/// spawned threads.
+const WORKER_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Options of a batch check.
+#[derive(Clone, Debug)]
+pub struct CheckOptions {
+    /// Evaluate the files after typechecking.
+    pub eval: bool,
+    /// The number of files checked in parallel. `0` and `1` both mean a sequential check.
+    pub jobs: usize,
+    /// Whether to use colors in the rendered diagnostics (see [`CheckResult::report`]).
+    pub color: ColorOpt,
+    /// The maximum number of parse errors reported for a file. See
+    /// [`Cache::set_max_parse_errors`].
+    pub max_parse_errors: Option<usize>,
+    /// The number of calls reported at each end of the call stack of a blame error. See
+    /// [`Cache::set_call_stack_limit`].
+    pub call_stack_limit: Option<usize>,
+    /// How to handle duplicate keys in JSON files. See [`Cache::set_json_duplicate_keys`].
+    pub json_duplicate_keys: DuplicateKeys,
+    /// Where the standard library is loaded from. See [`Cache::set_stdlib`].
+    pub stdlib: StdlibSource,
+    /// The policy applied to the diagnostics of the failures. See [`Cache::set_report_policy`].
+    pub report_policy: Option<Arc<dyn ReportPolicy>>,
+    /// The directories searched for imports. See [`Cache::set_import_paths`].
+    pub import_paths: Vec<PathBuf>,
+}
+
+impl Default for CheckOptions {
+    fn default() -> Self {
+        CheckOptions {
+            eval: false,
+            jobs: 1,
+            color: ColorOpt::Never,
+            max_parse_errors: Some(MAX_PARSE_DIAGNOSTICS),
+            call_stack_limit: Some(DEFAULT_CALL_STACK_LIMIT),
+            json_duplicate_keys: DuplicateKeys::default(),
+            stdlib: StdlibSource::default(),
+            report_policy: None,
+            import_paths: Vec::new(),
+        }
+    }
+}
+
+/// Available formats for the summary of a batch check.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum SummaryFormat {
+    /// A human-readable table, preceded by the full diagnostics of the failures.
+    #[default]
+    Table,
+    /// A JSON object, including the diagnostics of the failures.
+    Json,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ParseSummaryFormatError(String);
+
+impl fmt::Display for ParseSummaryFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unsupported summary format {} (expected table or json)",
+            self.0
+        )
+    }
+}
+
+impl FromStr for SummaryFormat {
+    type Err = ParseSummaryFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "table" => Ok(SummaryFormat::Table),
+            "json" => Ok(SummaryFormat::Json),
+            _ => Err(ParseSummaryFormatError(s.to_string())),
+        }
+    }
+}
+
+/// The outcome of the check of one file.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Ok,
+    /// The file could not be read.
+    #[serde(rename = "io_error")]
+    IOError,
+    ParseError,
+    ImportError,
+    TypeError,
+    EvalError,
+}
+
+impl CheckStatus {
+    fn from_error(error: &Error) -> Self {
+        match error {
+            Error::IOError(_) => CheckStatus::IOError,
+            Error::ParseErrors(_) => CheckStatus::ParseError,
+            Error::ImportError(_) => CheckStatus::ImportError,
+            // Lints are checked together with typing.
+            Error::TypecheckError(_) | Error::DeniedWarnings(_) => CheckStatus::TypeError,
+            Error::EvalError(_) | Error::SerializationError(_) | Error::ReplError(_) => {
+                CheckStatus::EvalError
+            }
+        }
+    }
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckStatus::Ok => write!(f, "ok"),
+            CheckStatus::IOError => write!(f, "io error"),
+            CheckStatus::ParseError => write!(f, "parse error"),
+            CheckStatus::ImportError => write!(f, "import error"),
+            CheckStatus::TypeError => write!(f, "type error"),
+            CheckStatus::EvalError => write!(f, "eval error"),
+        }
+    }
+}
+
+/// The result of the check of one file.
+#[derive(Clone, Debug, Serialize)]
+pub struct CheckResult {
+    pub file: PathBuf,
+    pub status: CheckStatus,
+    /// The message of the first diagnostic, if the check failed.
+    pub message: Option<String>,
+    /// The diagnostics of the failure, if any.
+    pub diagnostics: Vec<SimpleDiagnostic>,
+    /// The diagnostics of the failure, if any, as rendered on a terminal.
+    #[serde(skip)]
+    pub report: String,
+}
+
+impl CheckResult {
+    fn ok(file: PathBuf) -> Self {
+        CheckResult {
+            file,
+            status: CheckStatus::Ok,
+            message: None,
+            diagnostics: Vec::new(),
+            report: String::new(),
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.status == CheckStatus::Ok
+    }
+}
+
+/// The results of a batch check, in the order of the checked files.
+#[derive(Clone, Debug, Serialize)]
+pub struct CheckSummary {
+    pub results: Vec<CheckResult>,
+}
+
+impl CheckSummary {
+    /// The number of files whose check failed.
+    pub fn failures(&self) -> usize {
+        self.results.iter().filter(|res| !res.is_ok()).count()
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failures() == 0
+    }
+
+    /// The exit status of the `check` subcommand: non-zero if any file failed.
+    pub fn exit_code(&self) -> i32 {
+        if self.is_success() {
+            0
+        } else {
+            1
+        }
+    }
+
+    /// Write the summary in the given format.
+    ///
+    /// In the table format, the full diagnostics of the failures are written to `diag_writer`
+    /// before the table is written to `writer`. In the JSON format, the diagnostics are part of
+    /// the JSON object and `diag_writer` is not used.
+    pub fn write<W: Write, D: Write>(
+        &self,
+        format: SummaryFormat,
+        writer: &mut W,
+        diag_writer: &mut D,
+    ) -> io::Result<()> {
+        match format {
+            SummaryFormat::Table => {
+                for res in self.results.iter().filter(|res| !res.is_ok()) {
+                    diag_writer.write_all(res.report.as_bytes())?;
+                }
+
+                self.write_table(writer)
+            }
+            SummaryFormat::Json => {
+                serde_json::to_writer_pretty(&mut *writer, self)?;
+                writeln!(writer)
+            }
+        }
+    }
+
+    /// Write the summary as a table, with one line per file, followed by the number of failures.
+    pub fn write_table<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let rows: Vec<(String, String, &str)> = self
+            .results
+            .iter()
+            .map(|res| {
+                (
+                    res.file.display().to_string(),
+                    res.status.to_string(),
+                    res.message.as_deref().unwrap_or(""),
+                )
+            })
+            .collect();
+
+        let file_width = rows.iter().map(|row| row.0.len()).fold(4, usize::max);
+        let status_width = rows.iter().map(|row| row.1.len()).fold(6, usize::max);
+
+        writeln!(
+            writer,
+            "{:file_width$}  {:status_width$}  MESSAGE",
+            "FILE",
+            "STATUS",
+            file_width = file_width,
+            status_width = status_width
+        )?;
+
+        for (file, status, msg) in rows.iter() {
+            let line = format!(
+                "{:file_width$}  {:status_width$}  {}",
+                file,
+                status,
+                msg,
+                file_width = file_width,
+                status_width = status_width
+            );
+            writeln!(writer, "{}", line.trim_end())?;
+        }
+
+        writeln!(
+            writer,
+            "\n{} file(s) checked, {} failed",
+            self.results.len(),
+            self.failures()
+        )
+    }
+}
+
+/// Check the files matching `patterns` (see [`expand_globs`]).
+pub fn check_globs<I, S>(patterns: I, opts: CheckOptions) -> CheckSummary
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    check_files(&expand_globs(patterns), opts)
+}
+
+/// Check a list of files. The results are given in the order of `files`.
+pub fn check_files(files: &[PathBuf], opts: CheckOptions) -> CheckSummary {
+    let jobs = opts.jobs.clamp(1, files.len().max(1));
+
+    if jobs == 1 {
+        let mut checker = Checker::new(opts);
+        let results = files.iter().map(|file| checker.check(file)).collect();
+        return CheckSummary { results };
+    }
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<CheckResult>>> = Mutex::new(vec![None; files.len()]);
+
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            thread::Builder::new()
+                .stack_size(WORKER_STACK_SIZE)
+                .spawn_scoped(scope, || {
+                    let mut checker = Checker::new(opts.clone());
+
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let file = match files.get(index) {
+                            Some(file) => file,
+                            None => break,
+                        };
+                        let result = checker.check(file);
+                        results.lock().unwrap()[index] = Some(result);
+                    }
+                })
+                .expect("check::check_files(): could not spawn a worker thread");
+        }
+    });
+
+    let results = results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|res| res.expect("check::check_files(): a file was not checked"))
+        .collect();
+    CheckSummary { results }
+}
+
+/// Check files one by one, using one cache for all of them.
+struct Checker {
+    cache: Cache,
+    opts: CheckOptions,
+    /// The global environment, once the standard library has been successfully prepared.
+    global_env: Option<GlobalEnv>,
+}
+
+impl Checker {
+    fn new(opts: CheckOptions) -> Self {
+        let mut cache = Cache::new();
+        cache.set_max_parse_errors(opts.max_parse_errors);
+        cache.set_call_stack_limit(opts.call_stack_limit);
+        cache.set_json_duplicate_keys(opts.json_duplicate_keys);
+        cache.set_stdlib(opts.stdlib.clone());
+        cache.set_import_paths(opts.import_paths.clone());
+        if let Some(policy) = &opts.report_policy {
+            cache.set_report_policy(Arc::clone(policy));
+        }
+
+        Checker {
+            cache,
+            opts,
+            global_env: None,
+        }
+    }
+
+    fn check(&mut self, path: &Path) -> CheckResult {
+        match self.check_(path) {
+            Ok(()) => CheckResult::ok(path.to_path_buf()),
+            Err(err) => self.mk_failure(path, err),
+        }
+    }
+
+    fn check_(&mut self, path: &Path) -> Result<(), Error> {
+        let file_id = self
+            .cache
+            .get_or_add_file(path)
+            .map_err(|err| IOError::file(path, FileOperation::Read, err))?
+            .inner();
+
+        let global_env = match self.global_env.take() {
+            Some(global_env) => global_env,
+            None => self.cache.prepare_stdlib()?,
+        };
+        let result = self.check_id(file_id, &global_env);
+        self.global_env = Some(global_env);
+        result
+    }
+
+    fn check_id(&mut self, file_id: FileId, global_env: &GlobalEnv) -> Result<(), Error> {
+        match self.cache.parse(file_id)? {
+            CacheOp::Done(errs) | CacheOp::Cached(errs) if !errs.no_errors() => {
+                return Err(errs.into())
+            }
+            _ => (),
+        }
+
+        if self.opts.eval {
+            self.cache.prepare(file_id, &global_env.type_env)?;
+            let term = self.cache.get_owned(file_id).unwrap();
+            eval::eval_full(term, &global_env.eval_env, &mut self.cache)?;
+        } else {
+            self.cache.resolve_imports(file_id).map_err(|cache_err| {
+                cache_err.unwrap_error("check::check_id(): expected source to be parsed")
+            })?;
+            self.cache
+                .typecheck(file_id, &global_env.type_env)
+                .map_err(|cache_err| {
+                    cache_err.unwrap_error("check::check_id(): expected source to be parsed")
+                })?;
+        }
+
+        Ok(())
+    }
+
+    fn mk_failure(&mut self, path: &Path, error: Error) -> CheckResult {
+        let diagnostics = report_simple(&self.cache, &error);
+        let message = diagnostics.first().map(|diag| diag.message.clone());
+
+        let mut buffer = match ColorChoice::from(self.opts.color) {
+            ColorChoice::Never => Buffer::no_color(),
+            _ => Buffer::ansi(),
+        };
+        let status = CheckStatus::from_error(&error);
+        report_to(&mut self.cache, error, &mut buffer)
+            .expect("check::mk_failure(): could not render diagnostics");
+
+        CheckResult {
+            file: path.to_path_buf(),
+            status,
+            message,
+            diagnostics,
+            report: String::from_utf8_lossy(buffer.as_slice()).into_owned(),
+        }
+    }
+}
+
+/// Expand a list of glob patterns into the list of the matching files.
+///
+/// The matches of each pattern are sorted, and a file matched by several patterns is only listed
+/// once. A pattern without wildcards is kept as it is, even if the corresponding file doesn't
+/// exist, such that checking it reports an error instead of silently ignoring it.
+pub fn expand_globs<I, S>(patterns: I) -> Vec<PathBuf>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<str>,
+{
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+
+    for pattern in patterns {
+        for file in expand_glob(pattern.as_ref()) {
+            if seen.insert(file.clone()) {
+                files.push(file);
+            }
+        }
+    }
+
+    files
+}
+
+fn is_glob(component: &str) -> bool {
+    component.contains(['*', '?', '['])
+}
+
+/// Expand one glob pattern.
+fn expand_glob(pattern: &str) -> Vec<PathBuf> {
+    if !is_glob(pattern) {
+        return vec![PathBuf::from(pattern)];
+    }
+
+    let root = if pattern.starts_with('/') {
+        PathBuf::from("/")
+    } else {
+        PathBuf::new()
+    };
+    let mut candidates = vec![root];
+
+    for component in pattern.split('/').filter(|comp| !comp.is_empty()) {
+        candidates = if component == "**" {
+            candidates
+                .into_iter()
+                .flat_map(|dir| {
+                    let mut dirs = vec![dir.clone()];
+                    subdirs(&dir, &mut dirs);
+                    dirs
+                })
+                .collect()
+        } else if is_glob(component) {
+            let pattern: Vec<char> = component.chars().collect();
+
+            candidates
+                .into_iter()
+                .flat_map(|dir| {
+                    let mut matches: Vec<PathBuf> = read_dir(&dir)
+                        .filter(|(name, _)| {
+                            let name: Vec<char> = name.chars().collect();
+                            (pattern[0] == '.' || name[0] != '.') && glob_match(&pattern, &name)
+                        })
+                        .map(|(name, _)| dir.join(name))
+                        .collect();
+                    matches.sort();
+                    matches
+                })
+                .collect()
+        } else {
+            candidates
+                .into_iter()
+                .map(|dir| dir.join(component))
+                .collect()
+        }
+    }
+
+    candidates.retain(|path| path.is_file());
+    candidates
+}
+
+/// Iterate over the entries of a directory, given by their name and their path. The empty path
+/// stands for the current directory. Errors are ignored.
+fn read_dir(dir: &Path) -> impl Iterator<Item = (String, PathBuf)> {
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+
+    fs::read_dir(dir).into_iter().flatten().filter_map(|entry| {
+        let entry = entry.ok()?;
+        let name = entry.file_name().into_string().ok()?;
+        Some((name, entry.path()))
+    })
+}
+
+/// Push all the non-hidden subdirectories of `dir`, recursively, in sorted order.
+fn subdirs(dir: &Path, acc: &mut Vec<PathBuf>) {
+    let mut children: Vec<PathBuf> = read_dir(dir)
+        .filter(|(name, path)| !name.starts_with('.') && path.is_dir())
+        .map(|(name, _)| dir.join(name))
+        .collect();
+    children.sort();
+
+    for child in children {
+        acc.push(child.clone());
+        subdirs(&child, acc);
+    }
+}
+
+/// Match a name against a pattern component, supporting `*`, `?` and character classes.
+fn glob_match(pattern: &[char], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| glob_match(rest, &name[i..])),
+        Some(('?', rest)) => !name.is_empty() && glob_match(rest, &name[1..]),
+        Some(('[', rest)) => match class_match(rest, name.first()) {
+            Some((matched, rest)) => matched && glob_match(rest, &name[1..]),
+            // An unclosed class is matched literally.
+            None => name.first() == Some(&'[') && glob_match(rest, &name[1..]),
+        },
+        Some((c, rest)) => name.first() == Some(c) && glob_match(rest, &name[1..]),
+    }
+}
+
+/// Match a character against a character class, `pattern` starting right after the opening
+/// bracket. Return whether the character matched together with the rest of the pattern after the
+/// closing bracket, or `None` if the class is not closed.
+fn class_match<'a>(pattern: &'a [char], c: Option<&char>) -> Option<(bool, &'a [char])> {
+    let (negated, pattern) = match pattern.split_first() {
+        Some(('!', rest)) => (true, rest),
+        _ => (false, pattern),
+    };
+    // A closing bracket in first position is a literal character.
+    let end = pattern
+        .iter()
+        .skip(1)
+        .position(|c| *c == ']')
+        .map(|pos| pos + 1)?;
+    let (class, rest) = (&pattern[..end], &pattern[end + 1..]);
+
+    let c = match c {
+        Some(c) => *c,
+        None => return Some((false, rest)),
+    };
+
+    let mut matched = false;
+    let mut i = 0;
+    while i < class.len() {
+        if i + 2 < class.len() && class[i + 1] == '-' {
+            matched |= class[i] <= c && c <= class[i + 2];
+            i += 3;
+        } else {
+            matched |= class[i] == c;
+            i += 1;
+        }
+    }
+
+    Some((matched != negated, rest))
+}
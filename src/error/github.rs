@@ -0,0 +1,136 @@
+//! Export of errors as [GitHub Actions workflow
+//! commands](https://docs.github.com/en/actions/using-workflow-commands-for-github-actions), which
+//! are shown as annotations of the lines of a pull request.
+//!
+//! Each error gives one `::error` command, built from its leading diagnostic, that is the one
+//! describing the error itself:
+//!
+//! - The location of the command is the first primary label of the diagnostic which annotates a
+//!   file. An error without such a label, such as an error without a position, gives a command
+//!   without location.
+//! - The message of the command is the message of the diagnostic, prefixed by its error code,
+//!   followed by the text of the label and by the notes, one per line. Line breaks are escaped,
+//!   as required by the syntax of workflow commands.
+use super::{to_simple_diagnostics, Error, SimpleDiagnostic, SimpleLabel};
+use codespan::Files;
+use std::fmt::Write;
+
+/// Convert errors to GitHub Actions workflow commands, one per line. As for
+/// [`errors_to_sarif`](../sarif/fn.errors_to_sarif.html), no reporting policy is applied and
+/// sensitive values are not redacted.
+pub fn to_github_annotations(errors: &[Error], files: &Files<String>) -> String {
+    let mut output = String::new();
+
+    for error in errors {
+        if let Some(diag) = to_simple_diagnostics(error, files, None).first() {
+            writeln!(&mut output, "{}", annotation(diag)).unwrap();
+        }
+    }
+
+    output
+}
+
+/// Convert a diagnostic to an `::error` workflow command.
+fn annotation(diag: &SimpleDiagnostic) -> String {
+    let label = diag
+        .labels
+        .iter()
+        .find(|label| label.primary && label.file_name.is_some() && label.snippet.is_none());
+
+    let mut message = match &diag.code {
+        Some(code) => format!("[{}] {}", code, diag.message),
+        None => diag.message.clone(),
+    };
+    if let Some(label) = label.filter(|label| !label.label_text.is_empty()) {
+        message.push('\n');
+        message.push_str(&label.label_text);
+    }
+    for note in diag.notes.iter() {
+        message.push('\n');
+        message.push_str(note);
+    }
+
+    match label {
+        Some(label) => format!("::error {}::{}", properties(label), escape_data(&message)),
+        None => format!("::error::{}", escape_data(&message)),
+    }
+}
+
+/// The location properties of a command. Contrary to simple labels, lines and columns are
+/// 1-based in workflow commands.
+fn properties(label: &SimpleLabel) -> String {
+    let ((start_line, start_col), (end_line, end_col)) = label.line_col_range;
+
+    format!(
+        "file={},line={},col={},endLine={},endColumn={}",
+        escape_property(label.file_name.as_deref().unwrap_or_default()),
+        start_line + 1,
+        start_col + 1,
+        end_line + 1,
+        end_col + 1
+    )
+}
+
+/// Escape the message of a command.
+fn escape_data(s: &str) -> String {
+    s.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escape the value of a property, which can't contain the separators of properties either.
+fn escape_property(s: &str) -> String {
+    escape_data(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{EvalError, IOError};
+    use crate::identifier::Ident;
+    use crate::position::{RawSpan, TermPos};
+
+    #[test]
+    fn located_error() {
+        let mut files = Files::new();
+        let src_id = files.add("dir,1/main.ncl", String::from("let x = 1 in\ny + x"));
+        let pos = TermPos::Original(RawSpan {
+            src_id,
+            start: 13.into(),
+            end: 14.into(),
+        });
+
+        let errors = vec![Error::from(EvalError::UnboundIdentifier(
+            Ident::from("y"),
+            pos,
+            None,
+        ))];
+
+        assert_eq!(
+            to_github_annotations(&errors, &files),
+            "::error file=dir%2C1/main.ncl,line=2,col=1,endLine=2,endColumn=2::\
+             [E008] unbound identifier%0Athis identifier is unbound\n"
+        );
+    }
+
+    #[test]
+    fn error_without_span() {
+        let files = Files::new();
+        let errors = vec![
+            Error::from(IOError::Other(String::from("disk full: 100%"))),
+            Error::from(EvalError::UnboundIdentifier(
+                Ident::from("y"),
+                TermPos::None,
+                None,
+            )),
+        ];
+        let output = to_github_annotations(&errors, &files);
+        let lines: Vec<&str> = output.lines().collect();
+
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("::error::"), "{}", lines[0]);
+        assert!(lines[0].contains("disk full: 100%25"), "{}", lines[0]);
+        // The label of a generated snippet doesn't give a location.
+        assert!(lines[1].starts_with("::error::[E008]"), "{}", lines[1]);
+    }
+}
@@ -0,0 +1,275 @@
+//! Export of errors as JSON diagnostics, for build pipelines and other tools which need a stable,
+//! machine-readable representation of errors rather than the rendered terminal output.
+//!
+//! Each error gives one JSON object, built from the diagnostics of the error:
+//!
+//! - The kind, the subkind and the code of the error (see [`Error::kind`], [`Error::subkind`] and
+//!   [`Error::code`]).
+//! - The severity, the message, the labels and the notes of the leading diagnostic, that is the
+//!   one describing the error itself. The other diagnostics, such as the declaration of the
+//!   contract of a blame error, are given in the same shape under `related`.
+//! - For a blame error, the call stack, as an array of frames from the most nested call to the
+//!   least nested one. Consecutive identical calls are collapsed into one frame, whose `repeat`
+//!   field counts them.
+//!
+//! Warnings are given in the same shape, with the kind `warning`.
+//!
+//! Contrary to [`SimpleLabel`]s, lines and columns are 1-based. Columns are counted in Unicode
+//! characters: use the byte range for byte offsets. A label annotating a snippet generated during
+//! error reporting (see [`SimpleLabel::snippet`]) has no file and is marked as `synthetic`.
+use super::{
+    serialize_severity, to_simple_diagnostics_with, DiagnosticOptions, Error, ErrorKind, EvalError,
+    SimpleDiagnostic, SimpleLabel, Warning,
+};
+use codespan::{FileId, Files};
+use serde_json::{json, Map, Value};
+
+/// Convert an error to a JSON diagnostic. No reporting policy is applied and sensitive values are
+/// not redacted.
+///
+/// As for the rendered diagnostics, the call stack of a blame error is only given when
+/// `contract_id`, the id of the source of the standard contracts, is known: the calls made from
+/// the standard contracts are filtered out of the call stack.
+pub fn to_json_diagnostic(
+    error: &Error,
+    files: &Files<String>,
+    contract_id: Option<FileId>,
+) -> Value {
+    to_json_diagnostic_with(error, files, contract_id, &DiagnosticOptions::default())
+}
+
+/// Same as [`to_json_diagnostic`], but with explicit [`DiagnosticOptions`], including the
+/// reporting policy.
+pub fn to_json_diagnostic_with(
+    error: &Error,
+    files: &Files<String>,
+    contract_id: Option<FileId>,
+    opts: &DiagnosticOptions,
+) -> Value {
+    // The call stack is reported separately: the diagnostics are generated without it.
+    let diags = to_simple_diagnostics_with(error, files, None, opts);
+    let mut output = common_fields(
+        kind_name(error.kind()),
+        error.subkind(),
+        error.code(),
+        diags,
+        || error.to_string(),
+    );
+
+    if let (Error::EvalError(EvalError::BlameError(_, call_stack)), Some(id)) = (error, contract_id)
+    {
+        let (calls, curr_call) = call_stack.group_by_calls(id);
+        let frames = curr_call.into_iter().chain(calls).map(|call| {
+            let mut frame = location(files, call.span.src_id, call.span.start, call.span.end);
+            frame.insert(
+                String::from("function"),
+                json!(call.head.map(|id| id.to_string())),
+            );
+            frame.insert(String::from("repeat"), json!(call.repeat));
+            Value::Object(frame)
+        });
+
+        output.insert(String::from("call_stack"), frames.collect());
+    }
+
+    Value::Object(output)
+}
+
+/// Convert a warning to a JSON diagnostic, in the same shape as the one of an error. The kind of a
+/// warning is `"warning"`. Warnings which are denied are errors, and are converted by
+/// [`to_json_diagnostic_with`].
+pub fn warning_to_json_diagnostic_with(
+    warning: &Warning,
+    files: &Files<String>,
+    contract_id: Option<FileId>,
+    opts: &DiagnosticOptions,
+) -> Value {
+    let diags = to_simple_diagnostics_with(warning, files, contract_id, opts);
+    Value::Object(common_fields(
+        "warning",
+        warning.subkind(),
+        warning.code(),
+        diags,
+        || warning.to_string(),
+    ))
+}
+
+/// The fields of a JSON diagnostic common to errors and warnings: the kind, the subkind, the code,
+/// the leading diagnostic and the related ones. If there is no diagnostic, the leading one is
+/// made of `message` only.
+fn common_fields(
+    kind: &str,
+    subkind: &str,
+    code: &str,
+    diags: Vec<SimpleDiagnostic>,
+    message: impl FnOnce() -> String,
+) -> Map<String, Value> {
+    let mut diags = diags.into_iter();
+    let mut output = Map::new();
+
+    output.insert(String::from("kind"), json!(kind));
+    output.insert(String::from("subkind"), json!(subkind));
+    output.insert(String::from("code"), json!(code));
+
+    match diags.next() {
+        Some(diag) => output.extend(diagnostic(&diag)),
+        None => {
+            output.insert(String::from("severity"), json!("error"));
+            output.insert(String::from("message"), json!(message()));
+            output.insert(String::from("labels"), json!([]));
+            output.insert(String::from("notes"), json!([]));
+        }
+    }
+
+    output.insert(
+        String::from("related"),
+        diags.map(|diag| Value::Object(diagnostic(&diag))).collect(),
+    );
+    output
+}
+
+/// The name of a kind of error in JSON diagnostics.
+fn kind_name(kind: ErrorKind) -> &'static str {
+    match kind {
+        ErrorKind::Eval => "eval",
+        ErrorKind::Typecheck => "typecheck",
+        ErrorKind::Parse => "parse",
+        ErrorKind::Import => "import",
+        ErrorKind::Serialization => "serialization",
+        ErrorKind::IO => "io",
+        ErrorKind::Repl => "repl",
+        ErrorKind::DeniedWarning => "denied_warning",
+    }
+}
+
+/// Convert a diagnostic to the fields of a JSON diagnostic.
+fn diagnostic(diag: &SimpleDiagnostic) -> Map<String, Value> {
+    let mut output = Map::new();
+    // Serializing a severity to a JSON value can't fail.
+    let severity = serialize_severity(&diag.severity, serde_json::value::Serializer).unwrap();
+    output.insert(String::from("severity"), severity);
+    output.insert(String::from("message"), json!(diag.message));
+    output.insert(
+        String::from("labels"),
+        diag.labels.iter().map(label).collect(),
+    );
+    output.insert(String::from("notes"), json!(diag.notes));
+    output
+}
+
+/// Convert a label to JSON. The range of a synthetic label is relative to its snippet.
+fn label(label: &SimpleLabel) -> Value {
+    let ((start_line, start_col), (end_line, end_col)) = label.line_col_range;
+
+    json!({
+        "file": label.file_name,
+        "synthetic": label.snippet.is_some(),
+        "snippet": label.snippet,
+        "byte_range": {
+            "start": label.byte_range.start,
+            "end": label.byte_range.end,
+        },
+        "start": { "line": start_line + 1, "column": start_col + 1 },
+        "end": { "line": end_line + 1, "column": end_col + 1 },
+        "message": label.label_text,
+        "primary": label.primary,
+    })
+}
+
+/// The location of a span of a file, in the same shape as a label.
+fn location(
+    files: &Files<String>,
+    file_id: FileId,
+    start: codespan::ByteIndex,
+    end: codespan::ByteIndex,
+) -> Map<String, Value> {
+    let line_col = |index: codespan::ByteIndex| {
+        files
+            .location(file_id, index)
+            .map(|loc| json!({ "line": loc.line.to_usize() + 1, "column": loc.column.to_usize() + 1 }))
+            .unwrap_or(Value::Null)
+    };
+
+    let mut output = Map::new();
+    output.insert(
+        String::from("file"),
+        json!(files.name(file_id).to_string_lossy()),
+    );
+    output.insert(
+        String::from("byte_range"),
+        json!({ "start": start.to_usize(), "end": end.to_usize() }),
+    );
+    output.insert(String::from("start"), line_col(start));
+    output.insert(String::from("end"), line_col(end));
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::IOError;
+    use crate::identifier::Ident;
+    use crate::position::{RawSpan, TermPos};
+
+    #[test]
+    fn located_error() {
+        let mut files = Files::new();
+        let src_id = files.add("main.ncl", String::from("let x = 1 in\ny + x"));
+        let pos = TermPos::Original(RawSpan {
+            src_id,
+            start: 13.into(),
+            end: 14.into(),
+        });
+
+        let error = Error::from(EvalError::UnboundIdentifier(Ident::from("y"), pos, None));
+        let diag = to_json_diagnostic(&error, &files, None);
+
+        assert_eq!(diag["kind"], "eval");
+        assert_eq!(diag["subkind"], "UnboundIdentifier");
+        assert_eq!(diag["code"], "E008");
+        assert_eq!(diag["severity"], "error");
+        assert_eq!(diag["message"], "unbound identifier");
+        assert_eq!(
+            diag["labels"],
+            json!([{
+                "file": "main.ncl",
+                "synthetic": false,
+                "snippet": null,
+                "byte_range": { "start": 13, "end": 14 },
+                "start": { "line": 2, "column": 1 },
+                "end": { "line": 2, "column": 2 },
+                "message": "this identifier is unbound",
+                "primary": true,
+            }])
+        );
+        assert!(diag.get("call_stack").is_none());
+    }
+
+    #[test]
+    fn synthetic_label() {
+        let files = Files::new();
+        let error = Error::from(EvalError::UnboundIdentifier(
+            Ident::from("y"),
+            TermPos::None,
+            None,
+        ));
+        let diag = to_json_diagnostic(&error, &files, None);
+
+        let label = &diag["labels"][0];
+        assert_eq!(label["file"], Value::Null);
+        assert_eq!(label["synthetic"], true);
+        assert_eq!(label["snippet"], "y");
+        assert_eq!(label["byte_range"], json!({ "start": 0, "end": 1 }));
+    }
+
+    #[test]
+    fn error_without_position() {
+        let files = Files::new();
+        let error = Error::from(IOError::Other(String::from("disk full")));
+        let diag = to_json_diagnostic(&error, &files, None);
+
+        assert_eq!(diag["kind"], "io");
+        assert_eq!(diag["labels"], json!([]));
+        assert_eq!(diag["related"], json!([]));
+    }
+}
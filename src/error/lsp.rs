@@ -0,0 +1,210 @@
+//! Conversion of errors to [LSP](https://microsoft.github.io/language-server-protocol/)
+//! diagnostics, for editor integrations.
+//!
+//! Each diagnostic of an error is converted to an LSP diagnostic:
+//!
+//! - The range of the diagnostic is the one of its first primary label. Positions are converted
+//!   from byte offsets to lines and UTF-16 code units, as required by the protocol.
+//! - The other labels are the related information of the diagnostic. Related information needs
+//!   the URI of a file: labels of sources which aren't files, such as the standard input or
+//!   snippets generated during error reporting (see [`label_alt`](../fn.label_alt.html)), are
+//!   dropped.
+//! - The notes are appended to the message, one per line.
+use super::{generate_diagnostics, DiagnosticFiles, DiagnosticOptions, Error, SnippetFiles};
+use codespan::{FileId, Files};
+use codespan_reporting::diagnostic::{self, LabelStyle, Severity};
+use lsp_types::{
+    Diagnostic, DiagnosticRelatedInformation, DiagnosticSeverity, Location, NumberOrString,
+    Position, Range, Url,
+};
+use std::path::Path;
+
+/// Convert an error to LSP diagnostics, without mutating the file database. No reporting policy is
+/// applied.
+pub fn to_lsp_diagnostics(err: &Error, files: &Files<String>) -> Vec<Diagnostic> {
+    to_lsp_diagnostics_with(err, files, &DiagnosticOptions::default())
+}
+
+/// Same as [`to_lsp_diagnostics`], but with explicit [`DiagnosticOptions`], including the
+/// reporting policy.
+pub fn to_lsp_diagnostics_with(
+    err: &Error,
+    files: &Files<String>,
+    opts: &DiagnosticOptions,
+) -> Vec<Diagnostic> {
+    let (files, diags) = generate_diagnostics(err, files, None, opts);
+
+    let in_file = |label: &&diagnostic::Label<FileId>| !files.is_snippet(label.file_id);
+
+    diags
+        .into_iter()
+        .map(|diag| {
+            let primary = diag
+                .labels
+                .iter()
+                .filter(in_file)
+                .find(|label| label.style == LabelStyle::Primary);
+
+            let related: Vec<DiagnosticRelatedInformation> = diag
+                .labels
+                .iter()
+                .filter(in_file)
+                .filter(|label| !matches!(primary, Some(primary) if std::ptr::eq(*label, primary)))
+                .filter_map(|label| {
+                    Some(DiagnosticRelatedInformation {
+                        location: Location {
+                            uri: uri(&files, label.file_id)?,
+                            range: range(&files, label),
+                        },
+                        message: label.message.clone(),
+                    })
+                })
+                .collect();
+
+            let mut message = if diag.message.is_empty() {
+                primary
+                    .map(|label| label.message.clone())
+                    .unwrap_or_default()
+            } else {
+                diag.message.clone()
+            };
+            for note in diag.notes.iter() {
+                message.push('\n');
+                message.push_str(note);
+            }
+
+            Diagnostic {
+                range: primary
+                    .map(|label| range(&files, label))
+                    .unwrap_or_default(),
+                severity: Some(severity(diag.severity)),
+                code: diag.code.clone().map(NumberOrString::String),
+                source: Some(String::from("nickel")),
+                message,
+                related_information: if related.is_empty() {
+                    None
+                } else {
+                    Some(related)
+                },
+                ..Default::default()
+            }
+        })
+        .collect()
+}
+
+fn severity(severity: Severity) -> DiagnosticSeverity {
+    match severity {
+        Severity::Bug | Severity::Error => DiagnosticSeverity::Error,
+        Severity::Warning => DiagnosticSeverity::Warning,
+        Severity::Note => DiagnosticSeverity::Information,
+        Severity::Help => DiagnosticSeverity::Hint,
+    }
+}
+
+/// The URI of a source, if it is a file designated by an absolute path.
+fn uri(files: &SnippetFiles, file_id: FileId) -> Option<Url> {
+    let path = Path::new(files.name(file_id));
+
+    if path.is_absolute() {
+        Url::from_file_path(path).ok()
+    } else {
+        None
+    }
+}
+
+fn range(files: &SnippetFiles, label: &diagnostic::Label<FileId>) -> Range {
+    // The labels which don't belong to any database have been dropped.
+    let source = files.source(label.file_id).unwrap();
+    Range::new(
+        position(source, label.range.start),
+        position(source, label.range.end),
+    )
+}
+
+/// Convert a byte offset to an LSP position, whose character offset is counted in UTF-16 code
+/// units. The offset must be on a character boundary.
+fn position(source: &str, byte_index: usize) -> Position {
+    let before = &source[..byte_index];
+    let line_start = before.rfind('\n').map(|index| index + 1).unwrap_or(0);
+    let line = before.matches('\n').count();
+    let character = before[line_start..].encode_utf16().count();
+
+    Position::new(line as u32, character as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{EvalError, ParseError, ParseErrors};
+    use crate::position::{RawSpan, TermPos};
+    use crate::term::{RichTerm, Term};
+
+    #[test]
+    fn utf16_positions() {
+        let source = "let x = \"é😀\" in\n  \"😀\" + 1";
+        assert_eq!(position(source, 0), Position::new(0, 0));
+        // `é` is one UTF-16 code unit but two bytes, the emoji two code units but four bytes.
+        let after_emoji = source.find("\" in").unwrap();
+        assert_eq!(position(source, after_emoji), Position::new(0, 12));
+        let second_line = source.find("  \"😀").unwrap();
+        assert_eq!(position(source, second_line + 2), Position::new(1, 2));
+        assert_eq!(position(source, source.len()), Position::new(1, 10));
+    }
+
+    #[test]
+    fn labels() {
+        let mut files = Files::new();
+        let source = String::from("\"😀\" + 1\n# comment");
+        let file_id = files.add("/tmp/input.ncl", source);
+        let stdin_id = files.add("<stdin>", String::from("true"));
+        let other_id = files.add("/tmp/other.ncl", String::from("true"));
+        let span = |src_id, start: u32, end: u32| RawSpan {
+            src_id,
+            start: start.into(),
+            end: end.into(),
+        };
+
+        let type_error = |value_id| {
+            Error::EvalError(EvalError::TypeError(
+                String::from("Num"),
+                String::from("+, 1st argument"),
+                TermPos::Original(span(file_id, 0, 6)),
+                RichTerm::new(Term::Bool(true), TermPos::Original(span(value_id, 0, 4))),
+            ))
+        };
+        let diags = to_lsp_diagnostics(&type_error(stdin_id), &files);
+
+        assert_eq!(diags.len(), 1);
+        let diag = &diags[0];
+        assert_eq!(diag.severity, Some(DiagnosticSeverity::Error));
+        assert_eq!(
+            diag.code,
+            Some(NumberOrString::String(String::from("E003")))
+        );
+        assert_eq!(
+            diag.range,
+            Range::new(Position::new(0, 0), Position::new(0, 4))
+        );
+        assert!(diag.message.starts_with("type error\n"));
+        // The secondary label is in a source which isn't a file.
+        assert_eq!(diag.related_information, None);
+
+        let diags = to_lsp_diagnostics(&type_error(other_id), &files);
+        let related = diags[0].related_information.as_ref().unwrap();
+        assert_eq!(related.len(), 1);
+        assert_eq!(related[0].location.uri.as_str(), "file:///tmp/other.ncl");
+        assert_eq!(
+            related[0].location.range,
+            Range::new(Position::new(0, 0), Position::new(0, 4))
+        );
+        assert_eq!(related[0].message, "evaluated to this");
+
+        let err = Error::ParseErrors(ParseErrors::from(vec![ParseError::UnexpectedEOF(
+            file_id,
+            Vec::new(),
+        )]));
+        let diags = to_lsp_diagnostics(&err, &files);
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].range.start, Position::new(1, 9));
+    }
+}
@@ -0,0 +1,4220 @@
+//! Error types and error reporting.
+//!
+//! Define error types for different phases of the execution, together with functions to generate a
+//! [codespan](https://crates.io/crates/codespan-reporting) diagnostic from them.
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fmt::Write;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use codespan::{FileId, Files, Span};
+use codespan_reporting::diagnostic::{Diagnostic, Label, LabelStyle, Severity};
+use codespan_reporting::term::termcolor::{Buffer, ColorChoice};
+use lalrpop_util::ErrorRecovery;
+
+use crate::{
+    cache::InputFormat,
+    eval::callstack::{CallDescr, CallStack},
+    identifier::Ident,
+    label,
+    label::ty_path,
+    lint::Lint,
+    parser,
+    parser::{
+        error::{LexicalError, ParseError as InternalParseError},
+        lexer,
+        lexer::{ExpectedToken, Token, TokenCategory},
+        utils::{mk_char_span, mk_span},
+    },
+    position::{RawSpan, TermPos},
+    redact::{self, Redactions},
+    repl,
+    report_policy::{self, ReportPolicy},
+    serialize::{self, ExportFormat},
+    term::{RichTerm, Term},
+    types::Types,
+};
+
+pub mod github;
+pub mod json;
+#[cfg(feature = "lsp")]
+pub mod lsp;
+pub mod sarif;
+pub(crate) mod suggest;
+
+/// A general error occurring during either parsing or evaluation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    EvalError(EvalError),
+    TypecheckError(TypecheckError),
+    ParseErrors(ParseErrors),
+    ImportError(ImportError),
+    SerializationError(SerializationError),
+    IOError(IOError),
+    ReplError(ReplError),
+    /// The warnings of the lints which were denied, see [`crate::lint`]. There is at least one
+    /// warning.
+    DeniedWarnings(Vec<Warning>),
+}
+
+/// The category of an [`Error`], that is its variant without the underlying error. Host programs
+/// can branch on the kind of an error without matching on every variant of the underlying
+/// errors, whose name is given by [`Error::subkind`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum ErrorKind {
+    Eval,
+    Typecheck,
+    Parse,
+    Import,
+    Serialization,
+    IO,
+    Repl,
+    DeniedWarning,
+}
+
+/// An error occurring during evaluation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    /// A blame occurred: a contract have been broken somewhere.
+    BlameError(label::Label, CallStack),
+    /// A field required by a record contract is missing a definition.
+    MissingFieldDef(Option<label::Label>, CallStack),
+    /// Mismatch between the expected type and the actual type of an expression.
+    TypeError(
+        /* expected type */ String,
+        /* operation */ String,
+        /* position of the original unevaluated expression */ TermPos,
+        /* evaluated expression */ RichTerm,
+    ),
+    /// The operands of a binary operator don't have the types it expects.
+    BinaryTypeError {
+        /// The operator.
+        op: String,
+        /// The type expected for both operands.
+        expected: String,
+        /// The evaluated first operand, at the position of the original expression.
+        fst: RichTerm,
+        /// The evaluated second operand, at the position of the original expression.
+        snd: RichTerm,
+        /// The position of the operation.
+        pos: TermPos,
+    },
+    /// A term which is not a function has been applied to an argument.
+    NotAFunc(
+        /* term */ RichTerm,
+        /* arg */ RichTerm,
+        /* app position */ TermPos,
+    ),
+    /// A field access, or another record operation requiring the existence of a specific field,
+    /// has been performed on a record missing that field.
+    FieldMissing(
+        /* field identifier */ String,
+        /* operator */ String,
+        RichTerm,
+        TermPos,
+        /* index of the record in an array, when accessing the elements of an array */
+        Option<usize>,
+    ),
+    /// A field has been accessed, statically or dynamically, on a value which is not a record.
+    NotARecord {
+        /// The accessed field.
+        field: String,
+        /// The evaluated value.
+        value: RichTerm,
+        /// The position of the original unevaluated expression whose field is accessed.
+        record_pos: TermPos,
+        /// The position of the accessed field in the access.
+        access_pos: TermPos,
+    },
+    /// Too few arguments were provided to a builtin function.
+    NotEnoughArgs(
+        /* required arg count */ usize,
+        /* primitive */ String,
+        TermPos,
+    ),
+    /// Attempted to merge incompatible values: for example, tried to merge two distinct default
+    /// values into one record field.
+    MergeIncompatibleArgs(
+        /* left operand */ RichTerm,
+        /* right operand */ RichTerm,
+        /* original merge */ TermPos,
+    ),
+    /// An unbound identifier was referenced.
+    UnboundIdentifier(
+        Ident,
+        TermPos,
+        /* the closest identifier in scope, if any (see [`suggest::closest`]) */
+        Option<Ident>,
+    ),
+    /// No case of a switch matches the value, which is either an enum tag without a corresponding
+    /// case or not an enum tag at all, and there is no default case.
+    NonExhaustiveMatch {
+        /// The evaluated scrutinee.
+        value: RichTerm,
+        /// The tags handled by the switch, in alphabetical order.
+        handled: Vec<Ident>,
+        /// The position of the switch.
+        pos: TermPos,
+    },
+    /// A thunk was entered during its own update. The last element of the call stack is the
+    /// variable referring to this thunk, whose position is the one of the error.
+    InfiniteRecursion(CallStack, TermPos),
+    /// The evaluation was cancelled (see [`crate::eval::cancellation`]). The position is the one of
+    /// the expression being evaluated when the cancellation was observed.
+    Cancelled(TermPos),
+    /// A serialization error occurred during a call to the builtin `serialize`.
+    SerializationError(SerializationError),
+    /// A parse error occurred during a call to the builtin `deserialize`.
+    DeserializationError(
+        String,               /* format */
+        String,               /* error message */
+        TermPos,              /* position of the call to deserialize */
+        String,               /* deserialized string */
+        Option<usize>,        /* byte offset of the error in the deserialized string */
+        Option<Range<usize>>, /* first occurrence of a duplicate key in the deserialized string */
+    ),
+    /// An import forced during evaluation couldn't be resolved. Only happens if imports are
+    /// resolved lazily.
+    ImportError(ImportError),
+    /// An unexpected internal error.
+    InternalError(String, TermPos),
+    /// Errors occurring rarely enough to not deserve a dedicated variant.
+    Other(String, TermPos),
+}
+
+/// An error occurring during the static typechecking phase.
+#[derive(Debug, PartialEq, Clone)]
+pub enum TypecheckError {
+    /// An unbound identifier was referenced.
+    UnboundIdentifier(
+        Ident,
+        TermPos,
+        /* the closest identifier in scope, if any (see [`suggest::closest`]) */
+        Option<Ident>,
+    ),
+    /// An ill-formed type, such as a non-row type appearing in a row.
+    IllformedType(Types),
+    /// A specific row was expected to be in the type of an expression, but was not.
+    MissingRow(
+        Ident,
+        /* the expected type */ Types,
+        /* the inferred/annotated type */ Types,
+        TermPos,
+        /* the closest enclosing record literal, if any */ TermPos,
+    ),
+    /// A dynamic tail was expected to be in the type of an expression, but was not.
+    MissingDynTail(
+        /* the expected type */ Types,
+        /* the inferred/annotated type */ Types,
+        TermPos,
+    ),
+    /// A specific row was not expected to be in the type of an expression.
+    ExtraRow(
+        Ident,
+        /* the expected type */ Types,
+        /* the inferred/annotated type */ Types,
+        TermPos,
+        /* the definition of the extra field, if any */ TermPos,
+    ),
+    /// A additional dynamic tail was not expected to be in the type of an expression.
+    ExtraDynTail(
+        /* the expected type */ Types,
+        /* the inferred/annotated type */ Types,
+        TermPos,
+    ),
+
+    /// An unbound type variable was referenced.
+    UnboundTypeVariable(Ident, TermPos),
+    /// The actual (inferred or annotated) type of an expression is incompatible with its expected
+    /// type.
+    TypeMismatch(
+        /* the expected type */ Types,
+        /* the actual type */ Types,
+        TermPos,
+    ),
+    /// Two incompatible kind (enum vs record) have been deduced for the same identifier of a row type.
+    RowKindMismatch(
+        Ident,
+        /* the expected type */ Option<Types>,
+        /* the actual type */ Option<Types>,
+        TermPos,
+    ),
+    /// Two incompatible types have been deduced for the same identifier in a row type.
+    RowMismatch(
+        Ident,
+        /* the expected row type (whole) */ Types,
+        /* the actual row type (whole) */ Types,
+        /* error at the given row */ Box<TypecheckError>,
+        TermPos,
+    ),
+    /// Two incompatible types have been deduced for the same identifier of a row type.
+    ///
+    /// This is similar to `RowKindMismatch` but occurs in a slightly different situation. Consider a a
+    /// unification variable `t`, which is a placeholder to be filled by a concrete type later in
+    /// the typechecking phase.  If `t` appears as the tail of a row type, i.e. the type of some
+    /// expression is inferred to be `{ field: Type | t}`, then `t` must not be unified later with
+    /// a type including a different declaration for field, such as `field: Type2`.
+    ///
+    /// A [constraint](../typecheck/type.RowConstr.html) is added accordingly, and if this
+    /// constraint is violated (that is if `t` does end up being unified with a type of the form
+    /// `{ .., field: Type2, .. }`), `RowConflict` is raised.  We do not have access to the
+    /// original `field: Type` declaration, as opposed to `RowKindMismatch`, which corresponds to the
+    /// direct failure to unify `{ .. , x: T1, .. }` and `{ .., x: T2, .. }`.
+    RowConflict(
+        Ident,
+        /* the second type assignment which violates the constraint */ Option<Types>,
+        /* the expected type of the subexpression */ Types,
+        /* the actual type of the subexpression */ Types,
+        TermPos,
+    ),
+    /// Type mismatch on a subtype of an an arrow type.
+    ///
+    /// The unification of two arrow types requires the unification of the domain and the codomain
+    /// (and recursively so, if they are themselves arrow types). When the unification of a subtype
+    /// fails, we want to report which part of the arrow types is problematic, and why, rather than
+    /// a generic `TypeMismatch`. Indeed, failing to unify two arrow types is a common type error
+    /// which deserves a good reporting, that can be caused e.g. by applying a function to an
+    /// argument of a wrong type in some cases:
+    ///
+    /// ```text
+    /// let id_mono = fun x => x in let _ign = id_mono true in id_mono 0 : Num
+    /// ```
+    ///
+    /// This specific error stores additionally the [type path](../label/ty_path/index.html) that
+    /// identifies the subtype where unification failed and the corresponding error.
+    ArrowTypeMismatch(
+        /* the expected arrow type */ Types,
+        /* the actual arrow type */ Types,
+        /* the path to the incompatible subtypes */ ty_path::Path,
+        /* the error on the subtype unification */ Box<TypecheckError>,
+        TermPos,
+    ),
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ParseErrors {
+    pub errors: Vec<ParseError>,
+}
+
+impl ParseErrors {
+    pub fn new(errors: Vec<ParseError>) -> ParseErrors {
+        ParseErrors { errors }
+    }
+
+    pub fn errors(self) -> Option<Vec<ParseError>> {
+        if self.errors.is_empty() {
+            None
+        } else {
+            Some(self.errors)
+        }
+    }
+
+    pub fn no_errors(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub const fn none() -> ParseErrors {
+        ParseErrors { errors: Vec::new() }
+    }
+
+    /// Post-process the errors produced by error recovery, which often reports a cascade of
+    /// errors for a single syntactic mistake.
+    ///
+    /// An error identical to an earlier one is dropped. So is an unexpected or extra token whose
+    /// span overlaps or starts at most [`PARSE_ERRORS_PROXIMITY`] bytes after the one of an
+    /// earlier such error, as it is most likely a consequence of the latter. At most `max` errors
+    /// are then kept, in their original order.
+    pub fn dedup_and_limit(self, max: usize) -> ParseErrors {
+        let mut errors: Vec<ParseError> = Vec::new();
+
+        for err in self.errors {
+            let is_cascade = err.recovery_span().is_some_and(|span| {
+                errors
+                    .iter()
+                    .filter_map(ParseError::recovery_span)
+                    .any(|prev| {
+                        prev.src_id == span.src_id
+                            && span.end >= prev.start
+                            && span.start.to_usize() <= prev.end.to_usize() + PARSE_ERRORS_PROXIMITY
+                    })
+            });
+
+            if !is_cascade && !errors.contains(&err) {
+                errors.push(err);
+            }
+        }
+
+        errors.truncate(max);
+        ParseErrors { errors }
+    }
+
+    pub fn from_recoverable<'a>(
+        errs: Vec<ErrorRecovery<usize, Token<'a>, parser::error::ParseError>>,
+        file_id: FileId,
+        source: &str,
+    ) -> Self {
+        ParseErrors {
+            errors: errs
+                .into_iter()
+                .map(|e| ParseError::from_lalrpop(e.error, file_id, source))
+                .collect(),
+        }
+    }
+}
+
+impl From<ParseError> for ParseErrors {
+    fn from(e: ParseError) -> ParseErrors {
+        ParseErrors { errors: vec![e] }
+    }
+}
+
+impl From<Vec<ParseError>> for ParseErrors {
+    fn from(errors: Vec<ParseError>) -> ParseErrors {
+        ParseErrors { errors }
+    }
+}
+
+/// The default maximum number of diagnostics reported for a list of parse errors. See
+/// [`ParseErrors::to_diagnostic_capped`] and [`DiagnosticOptions::max_parse_errors`].
+pub const MAX_PARSE_DIAGNOSTICS: usize = 20;
+
+impl ParseErrors {
+    /// Same as [`ToDiagnostic::to_diagnostic`], but produce at most `max` diagnostics. Errors are
+    /// reported in their original order. If some errors are left out, a final note diagnostic
+    /// reports how many.
+    pub fn to_diagnostic_capped(
+        &self,
+        files: &mut dyn DiagnosticFiles,
+        contract_id: Option<FileId>,
+        max: usize,
+    ) -> Vec<Diagnostic<FileId>> {
+        let mut diags = Vec::new();
+        let mut reported = 0;
+
+        for err in self.errors.iter() {
+            let err_diags = err.to_diagnostic(files, contract_id);
+
+            if diags.len() + err_diags.len() > max {
+                break;
+            }
+
+            diags.extend(err_diags);
+            reported += 1;
+        }
+
+        let remaining = self.errors.len() - reported;
+        if remaining > 0 {
+            let plural = if remaining > 1 { "s" } else { "" };
+            diags.push(
+                Diagnostic::note()
+                    .with_message(format!("... and {} more error{}", remaining, plural)),
+            );
+        }
+
+        diags
+    }
+}
+
+impl ToDiagnostic<FileId> for ParseErrors {
+    fn to_diagnostic(
+        &self,
+        files: &mut dyn DiagnosticFiles,
+        contract_id: Option<FileId>,
+    ) -> Vec<Diagnostic<FileId>> {
+        self.to_diagnostic_with(files, contract_id, &DiagnosticOptions::default())
+    }
+
+    fn to_diagnostic_with(
+        &self,
+        files: &mut dyn DiagnosticFiles,
+        contract_id: Option<FileId>,
+        opts: &DiagnosticOptions,
+    ) -> Vec<Diagnostic<FileId>> {
+        match opts.max_parse_errors {
+            Some(max) => self.to_diagnostic_capped(files, contract_id, max),
+            None => self
+                .errors
+                .iter()
+                .flat_map(|err| err.to_diagnostic(files, contract_id))
+                .collect(),
+        }
+    }
+}
+
+/// The distance in bytes under which an error is considered to be a consequence of an earlier
+/// one. See [`ParseErrors::dedup_and_limit`].
+pub const PARSE_ERRORS_PROXIMITY: usize = 8;
+
+/// An error occurring during parsing.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParseError {
+    /// Unexpected end of file.
+    UnexpectedEOF(FileId, /* tokens expected by the parser */ Vec<String>),
+    /// Unexpected token.
+    UnexpectedToken(
+        RawSpan,
+        /* tokens expected by the parser */ Vec<String>,
+    ),
+    /// Superfluous, unexpected token.
+    ExtraToken(RawSpan),
+    /// A closing brace '}' does not match an opening brace '{'. This rather precise error is detected by the because
+    /// of how interpolated strings are lexed.
+    UnmatchedCloseBrace(
+        RawSpan,
+        /* the opening brace of the last brace group closed before */ Option<RawSpan>,
+    ),
+    /// Invalid escape sequence in a string literal.
+    InvalidEscapeSequence(RawSpan),
+    /// Invalid ASCII escape code in a string literal.
+    InvalidAsciiEscapeCode(RawSpan),
+    /// Error when parsing an external format such as JSON, YAML, etc.
+    ExternalFormatError(
+        String, /* format */
+        String, /* error message */
+        Option<RawSpan>,
+        Option<RawSpan>, /* first occurrence of a duplicate key */
+    ),
+    /// Unbound type variable
+    UnboundTypeVariables(Vec<Ident>, RawSpan),
+    /// Illegal record literal in the uniterm syntax. In practice, this is a record with a
+    /// polymorphic tail that contains a construct that wasn't permitted inside a record type in
+    /// the original syntax. Typically, a field assignment:
+    ///
+    /// ```nickel
+    /// forall a. {foo : Num; a} # allowed
+    /// forall a. {foo : Num = 1; a} # InvalidUniRecord error: giving a value to foo is forbidden
+    /// ```
+    ///
+    /// See [RFC002](../../rfcs/002-merge-types-terms-syntax.md) for more details.
+    InvalidUniRecord(
+        RawSpan, /* illegal (in conjunction with a tail) construct position */
+        RawSpan, /* tail position */
+        RawSpan, /* whole record position */
+    ),
+    /// A field of a record literal is defined twice with values that can't be merged.
+    DuplicateField(
+        Vec<Ident>, /* the field path */
+        RawSpan,    /* first definition */
+        RawSpan,    /* second definition */
+    ),
+    /// A field is overridden twice on the command line (see
+    /// [`FieldOverride`](../program/struct.FieldOverride.html)). The spans are the values of the
+    /// overrides.
+    DuplicateOverride(
+        String,  /* the field path */
+        RawSpan, /* first override */
+        RawSpan, /* second override */
+    ),
+    /// A type alias is applied to a number of arguments different from its number of parameters.
+    TypeAliasArity(
+        Ident,   /* the alias */
+        usize,   /* the number of parameters */
+        usize,   /* the number of arguments */
+        RawSpan, /* the application of the alias */
+    ),
+}
+
+/// An error occurring during the resolution of an import.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ImportError {
+    /// An IO error occurred during an import.
+    IOError(
+        /* imported file */ String,
+        /* error message */ String,
+        /* import position */ TermPos,
+    ),
+    /// A parse error occurred during an import.
+    ParseErrors(
+        /* error */ ParseErrors,
+        /* import position */ TermPos,
+    ),
+    /// A module of a standard library loaded from a directory is not a record.
+    InvalidStdlibModule(/* module */ String, /* module position */ TermPos),
+    /// An error occurred while resolving the imports of an imported file.
+    Transitive(
+        /* error */ Box<ImportError>,
+        /* import position of the file */ TermPos,
+    ),
+    /// A file is imported again while it is being evaluated, directly or through other imports,
+    /// such that its evaluation can't terminate. Also raised when bundling a program whose files
+    /// import each other, which a bundle can't express.
+    ImportCycle(
+        /* the files of the cycle, starting and ending with the same one */ Vec<String>,
+        /* position of the import closing the cycle */ TermPos,
+    ),
+}
+
+/// An error occurred during serialization.
+///
+/// The field path of a variant locates the offending term inside the exported value.
+#[derive(Debug, PartialEq, Clone)]
+pub enum SerializationError {
+    /// Encountered a null value for a format that doesn't support them.
+    UnsupportedNull(ExportFormat, RichTerm, label::field_path::Path),
+    /// Tried exporting something else than a `Str` to raw format.
+    NotAString(RichTerm),
+    /// A term contains constructs that cannot be serialized.
+    NonSerializable(RichTerm, label::field_path::Path),
+    /// The top-level value can't be represented in the format, such as a number for TOML, whose
+    /// documents are always tables.
+    UnsupportedFormat(ExportFormat, RichTerm),
+    /// An array mixes records with other values, which the format can't represent, such as TOML,
+    /// where arrays of tables only contain tables.
+    MixedArray(ExportFormat, RichTerm, label::field_path::Path),
+    Other(String),
+}
+
+/// A general I/O error, occurring when reading a source file or writing an export.
+#[derive(Debug, PartialEq, Clone)]
+pub enum IOError {
+    /// An operation on a file failed.
+    File {
+        path: PathBuf,
+        op: FileOperation,
+        kind: std::io::ErrorKind,
+        msg: String,
+    },
+    /// Any other I/O error.
+    Other(String),
+}
+
+/// The file operation which caused an [`IOError::File`].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FileOperation {
+    Read,
+    Create,
+    Write,
+    Rename,
+}
+
+impl std::fmt::Display for FileOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FileOperation::Read => write!(f, "read"),
+            FileOperation::Create => write!(f, "create"),
+            FileOperation::Write => write!(f, "write"),
+            FileOperation::Rename => write!(f, "rename"),
+        }
+    }
+}
+
+impl IOError {
+    /// Create an error from the failure of an operation on a file.
+    pub fn file(path: impl Into<PathBuf>, op: FileOperation, error: std::io::Error) -> Self {
+        IOError::File {
+            path: path.into(),
+            op,
+            kind: error.kind(),
+            msg: error.to_string(),
+        }
+    }
+}
+
+/// An error occurring during an REPL session.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ReplError {
+    UnknownCommand(String),
+    MissingArg {
+        cmd: repl::command::CommandType,
+        msg_opt: Option<String>,
+    },
+    /// A REPL command was given an argument it doesn't accept, such as an unknown option.
+    InvalidArg {
+        cmd: repl::command::CommandType,
+        arg: String,
+    },
+}
+
+/// A warning: a suspicious construct which doesn't prevent the program from running. Warnings are
+/// produced by [lints](../lint/index.html).
+#[derive(Debug, PartialEq, Clone)]
+pub enum Warning {
+    /// A binding shadows a name of the standard library.
+    ShadowedStdlib(Ident),
+    /// A let binding is never used.
+    UnusedBinding(Ident),
+    /// The contracts of a default value are dropped if it is overridden.
+    OverrideBypass(TermPos),
+}
+
+impl Warning {
+    /// Return the lint which produced this warning.
+    pub fn lint(&self) -> Lint {
+        match self {
+            Warning::ShadowedStdlib(_) => Lint::ShadowStdlib,
+            Warning::UnusedBinding(_) => Lint::UnusedBinding,
+            Warning::OverrideBypass(_) => Lint::OverrideBypass,
+        }
+    }
+}
+
+impl From<EvalError> for Error {
+    fn from(error: EvalError) -> Error {
+        Error::EvalError(error)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Error {
+        Error::ParseErrors(ParseErrors {
+            errors: vec![error],
+        })
+    }
+}
+
+impl From<ParseErrors> for Error {
+    fn from(errors: ParseErrors) -> Error {
+        Error::ParseErrors(errors)
+    }
+}
+
+impl From<TypecheckError> for Error {
+    fn from(error: TypecheckError) -> Error {
+        Error::TypecheckError(error)
+    }
+}
+
+impl From<ImportError> for Error {
+    fn from(error: ImportError) -> Error {
+        Error::ImportError(error)
+    }
+}
+
+impl From<SerializationError> for Error {
+    fn from(error: SerializationError) -> Error {
+        Error::SerializationError(error)
+    }
+}
+
+impl From<IOError> for Error {
+    fn from(error: IOError) -> Error {
+        Error::IOError(error)
+    }
+}
+
+impl From<std::io::Error> for IOError {
+    fn from(error: std::io::Error) -> IOError {
+        IOError::Other(error.to_string())
+    }
+}
+
+impl From<SerializationError> for EvalError {
+    fn from(error: SerializationError) -> EvalError {
+        EvalError::SerializationError(error)
+    }
+}
+
+// The `code()` methods below return the stable code identifying the kind of an error, which is
+// attached to its diagnostic. The long-form explanation of each code is stored in the
+// [`error_codes`](../error_codes/index.html) registry.
+
+impl Error {
+    /// Return the error code of this error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::EvalError(err) => err.code(),
+            Error::TypecheckError(err) => err.code(),
+            Error::ParseErrors(errs) => errs.code(),
+            Error::ImportError(err) => err.code(),
+            Error::SerializationError(err) => err.code(),
+            Error::IOError(err) => err.code(),
+            Error::ReplError(err) => err.code(),
+            Error::DeniedWarnings(warnings) => {
+                warnings.first().map(Warning::code).unwrap_or_default()
+            }
+        }
+    }
+
+    /// Convert this error to a machine-readable JSON diagnostic. See [`json`] for the format, and
+    /// [`ToDiagnostic::to_diagnostic`] for `contract_id`.
+    pub fn to_json_diagnostic(
+        &self,
+        files: &Files<String>,
+        contract_id: Option<FileId>,
+    ) -> serde_json::Value {
+        json::to_json_diagnostic(self, files, contract_id)
+    }
+
+    /// Return the category of this error.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::EvalError(_) => ErrorKind::Eval,
+            Error::TypecheckError(_) => ErrorKind::Typecheck,
+            Error::ParseErrors(_) => ErrorKind::Parse,
+            Error::ImportError(_) => ErrorKind::Import,
+            Error::SerializationError(_) => ErrorKind::Serialization,
+            Error::IOError(_) => ErrorKind::IO,
+            Error::ReplError(_) => ErrorKind::Repl,
+            Error::DeniedWarnings(_) => ErrorKind::DeniedWarning,
+        }
+    }
+
+    /// Return the name of the variant of the underlying error, such as `"BlameError"` for an
+    /// `Error::EvalError(EvalError::BlameError(..))`. Only the variant of the underlying error is
+    /// given: an evaluation error wrapping an import error has the subkind `"ImportError"`.
+    pub fn subkind(&self) -> &'static str {
+        match self {
+            Error::EvalError(err) => err.subkind(),
+            Error::TypecheckError(err) => err.subkind(),
+            Error::ParseErrors(errs) => errs.subkind(),
+            Error::ImportError(err) => err.subkind(),
+            Error::SerializationError(err) => err.subkind(),
+            Error::IOError(err) => err.subkind(),
+            Error::ReplError(err) => err.subkind(),
+            Error::DeniedWarnings(warnings) => {
+                warnings.first().map(Warning::subkind).unwrap_or_default()
+            }
+        }
+    }
+}
+
+impl EvalError {
+    /// Return the error code of this error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            EvalError::BlameError(..) => "E001",
+            EvalError::MissingFieldDef(..) => "E002",
+            EvalError::TypeError(..) => "E003",
+            EvalError::BinaryTypeError { .. } => "E053",
+            EvalError::NotAFunc(..) => "E004",
+            EvalError::FieldMissing(..) => "E005",
+            EvalError::NotARecord { .. } => "E051",
+            EvalError::NotEnoughArgs(..) => "E006",
+            EvalError::MergeIncompatibleArgs(..) => "E007",
+            EvalError::UnboundIdentifier(..) => "E008",
+            EvalError::InfiniteRecursion(..) => "E009",
+            EvalError::SerializationError(err) => err.code(),
+            EvalError::DeserializationError(..) => "E010",
+            EvalError::ImportError(err) => err.code(),
+            EvalError::InternalError(..) => "E011",
+            EvalError::Other(..) => "E012",
+            EvalError::NonExhaustiveMatch { .. } => "E044",
+            EvalError::Cancelled(..) => "E045",
+        }
+    }
+
+    /// Return the name of the variant of this error, such as `"BlameError"`.
+    pub fn subkind(&self) -> &'static str {
+        match self {
+            EvalError::BlameError(..) => "BlameError",
+            EvalError::MissingFieldDef(..) => "MissingFieldDef",
+            EvalError::TypeError(..) => "TypeError",
+            EvalError::BinaryTypeError { .. } => "BinaryTypeError",
+            EvalError::NotAFunc(..) => "NotAFunc",
+            EvalError::FieldMissing(..) => "FieldMissing",
+            EvalError::NotARecord { .. } => "NotARecord",
+            EvalError::NotEnoughArgs(..) => "NotEnoughArgs",
+            EvalError::MergeIncompatibleArgs(..) => "MergeIncompatibleArgs",
+            EvalError::UnboundIdentifier(..) => "UnboundIdentifier",
+            EvalError::InfiniteRecursion(..) => "InfiniteRecursion",
+            EvalError::SerializationError(..) => "SerializationError",
+            EvalError::DeserializationError(..) => "DeserializationError",
+            EvalError::ImportError(..) => "ImportError",
+            EvalError::InternalError(..) => "InternalError",
+            EvalError::Other(..) => "Other",
+            EvalError::NonExhaustiveMatch { .. } => "NonExhaustiveMatch",
+            EvalError::Cancelled(..) => "Cancelled",
+        }
+    }
+}
+
+impl TypecheckError {
+    /// Return the error code of this error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            // Reported with the same diagnostic as `EvalError::UnboundIdentifier`.
+            TypecheckError::UnboundIdentifier(..) => "E008",
+            TypecheckError::IllformedType(..) => "E013",
+            TypecheckError::MissingRow(..) => "E014",
+            TypecheckError::MissingDynTail(..) => "E015",
+            TypecheckError::ExtraRow(..) => "E016",
+            TypecheckError::ExtraDynTail(..) => "E017",
+            TypecheckError::UnboundTypeVariable(..) => "E018",
+            TypecheckError::TypeMismatch(..) => "E019",
+            TypecheckError::RowKindMismatch(..) => "E020",
+            TypecheckError::RowMismatch(..) => "E021",
+            TypecheckError::RowConflict(..) => "E022",
+            TypecheckError::ArrowTypeMismatch(..) => "E023",
+        }
+    }
+
+    /// Return the name of the variant of this error, such as `"UnboundIdentifier"`.
+    pub fn subkind(&self) -> &'static str {
+        match self {
+            TypecheckError::UnboundIdentifier(..) => "UnboundIdentifier",
+            TypecheckError::IllformedType(..) => "IllformedType",
+            TypecheckError::MissingRow(..) => "MissingRow",
+            TypecheckError::MissingDynTail(..) => "MissingDynTail",
+            TypecheckError::ExtraRow(..) => "ExtraRow",
+            TypecheckError::ExtraDynTail(..) => "ExtraDynTail",
+            TypecheckError::UnboundTypeVariable(..) => "UnboundTypeVariable",
+            TypecheckError::TypeMismatch(..) => "TypeMismatch",
+            TypecheckError::RowKindMismatch(..) => "RowKindMismatch",
+            TypecheckError::RowMismatch(..) => "RowMismatch",
+            TypecheckError::RowConflict(..) => "RowConflict",
+            TypecheckError::ArrowTypeMismatch(..) => "ArrowTypeMismatch",
+        }
+    }
+}
+
+impl ParseErrors {
+    /// Return the error code of the first error, which is the one reported first. Each error
+    /// carries its own code in the diagnostics.
+    pub fn code(&self) -> &'static str {
+        self.errors
+            .first()
+            .map(ParseError::code)
+            .unwrap_or_default()
+    }
+
+    /// Return the name of the variant of the first error, as for [`code`](#method.code).
+    pub fn subkind(&self) -> &'static str {
+        self.errors
+            .first()
+            .map(ParseError::subkind)
+            .unwrap_or_default()
+    }
+}
+
+impl ParseError {
+    /// Return the error code of this error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::UnexpectedEOF(..) => "E024",
+            ParseError::UnexpectedToken(..) => "E025",
+            ParseError::ExtraToken(..) => "E026",
+            ParseError::UnmatchedCloseBrace(..) => "E027",
+            ParseError::InvalidEscapeSequence(..) => "E028",
+            ParseError::InvalidAsciiEscapeCode(..) => "E029",
+            ParseError::ExternalFormatError(..) => "E030",
+            // Same error as `TypecheckError::UnboundTypeVariable`, detected during parsing.
+            ParseError::UnboundTypeVariables(..) => "E018",
+            ParseError::InvalidUniRecord(..) => "E031",
+            ParseError::DuplicateField(..) => "E032",
+            ParseError::DuplicateOverride(..) => "E049",
+            ParseError::TypeAliasArity(..) => "E052",
+        }
+    }
+
+    /// Return the name of the variant of this error, such as `"UnexpectedEOF"`.
+    pub fn subkind(&self) -> &'static str {
+        match self {
+            ParseError::UnexpectedEOF(..) => "UnexpectedEOF",
+            ParseError::UnexpectedToken(..) => "UnexpectedToken",
+            ParseError::ExtraToken(..) => "ExtraToken",
+            ParseError::UnmatchedCloseBrace(..) => "UnmatchedCloseBrace",
+            ParseError::InvalidEscapeSequence(..) => "InvalidEscapeSequence",
+            ParseError::InvalidAsciiEscapeCode(..) => "InvalidAsciiEscapeCode",
+            ParseError::ExternalFormatError(..) => "ExternalFormatError",
+            ParseError::UnboundTypeVariables(..) => "UnboundTypeVariables",
+            ParseError::InvalidUniRecord(..) => "InvalidUniRecord",
+            ParseError::DuplicateField(..) => "DuplicateField",
+            ParseError::DuplicateOverride(..) => "DuplicateOverride",
+            ParseError::TypeAliasArity(..) => "TypeAliasArity",
+        }
+    }
+}
+
+impl ImportError {
+    /// Return the error code of this error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ImportError::IOError(..) => "E033",
+            // The diagnostics of parse errors are reported as is, with an additional label.
+            ImportError::ParseErrors(errs, _) => errs.code(),
+            ImportError::InvalidStdlibModule(..) => "E043",
+            ImportError::Transitive(err, _) => err.code(),
+            ImportError::ImportCycle(..) => "E048",
+        }
+    }
+
+    /// Return the name of the variant of this error, such as `"IOError"`.
+    pub fn subkind(&self) -> &'static str {
+        match self {
+            ImportError::IOError(..) => "IOError",
+            ImportError::ParseErrors(..) => "ParseErrors",
+            ImportError::InvalidStdlibModule(..) => "InvalidStdlibModule",
+            ImportError::Transitive(..) => "Transitive",
+            ImportError::ImportCycle(..) => "ImportCycle",
+        }
+    }
+}
+
+impl SerializationError {
+    /// Return the error code of this error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            SerializationError::UnsupportedNull(..) => "E034",
+            SerializationError::NotAString(..) => "E035",
+            SerializationError::NonSerializable(..) => "E036",
+            SerializationError::Other(..) => "E037",
+            SerializationError::UnsupportedFormat(..) => "E042",
+            SerializationError::MixedArray(..) => "E046",
+        }
+    }
+
+    /// Return the name of the variant of this error, such as `"UnsupportedNull"`.
+    pub fn subkind(&self) -> &'static str {
+        match self {
+            SerializationError::UnsupportedNull(..) => "UnsupportedNull",
+            SerializationError::NotAString(..) => "NotAString",
+            SerializationError::NonSerializable(..) => "NonSerializable",
+            SerializationError::Other(..) => "Other",
+            SerializationError::UnsupportedFormat(..) => "UnsupportedFormat",
+            SerializationError::MixedArray(..) => "MixedArray",
+        }
+    }
+}
+
+impl IOError {
+    /// Return the error code of this error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            IOError::File { .. } => "E038",
+            IOError::Other(..) => "E039",
+        }
+    }
+
+    /// Return the name of the variant of this error, such as `"File"`.
+    pub fn subkind(&self) -> &'static str {
+        match self {
+            IOError::File { .. } => "File",
+            IOError::Other(..) => "Other",
+        }
+    }
+}
+
+impl Warning {
+    /// Return the error code of this warning.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Warning::ShadowedStdlib(_) => "E047",
+            Warning::UnusedBinding(_) => "E054",
+            Warning::OverrideBypass(_) => "E055",
+        }
+    }
+
+    /// Return the name of the variant of this warning, such as `"ShadowedStdlib"`.
+    pub fn subkind(&self) -> &'static str {
+        match self {
+            Warning::ShadowedStdlib(..) => "ShadowedStdlib",
+            Warning::UnusedBinding(..) => "UnusedBinding",
+            Warning::OverrideBypass(..) => "OverrideBypass",
+        }
+    }
+}
+
+impl ReplError {
+    /// Return the error code of this error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ReplError::UnknownCommand(..) => "E040",
+            ReplError::MissingArg { .. } => "E041",
+            ReplError::InvalidArg { .. } => "E050",
+        }
+    }
+
+    /// Return the name of the variant of this error, such as `"UnknownCommand"`.
+    pub fn subkind(&self) -> &'static str {
+        match self {
+            ReplError::UnknownCommand(..) => "UnknownCommand",
+            ReplError::MissingArg { .. } => "MissingArg",
+            ReplError::InvalidArg { .. } => "InvalidArg",
+        }
+    }
+}
+
+/// Attach an error code to the leading diagnostic of an error, that is the one describing the
+/// error itself, as opposed to the following ones (underlying errors, call stack, etc.).
+fn with_code(mut diags: Vec<Diagnostic<FileId>>, code: &'static str) -> Vec<Diagnostic<FileId>> {
+    if let Some(head) = diags.first_mut() {
+        head.code = Some(String::from(code));
+    }
+
+    diags
+}
+
+// The `Display` implementations below give a one-line summary of an error, which doesn't require
+// the file database. They are intended for host programs using Nickel as a library. The complete
+// error report is generated by `ToDiagnostic`.
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Error::EvalError(err) => err.fmt(f),
+            Error::TypecheckError(err) => err.fmt(f),
+            Error::ParseErrors(errs) => errs.fmt(f),
+            Error::ImportError(err) => err.fmt(f),
+            Error::SerializationError(err) => err.fmt(f),
+            Error::IOError(err) => err.fmt(f),
+            Error::ReplError(err) => err.fmt(f),
+            Error::DeniedWarnings(warnings) => match warnings.as_slice() {
+                [] => write!(f, "denied warning"),
+                [warning] => warning.fmt(f),
+                [warning, rest @ ..] => {
+                    let plural = if rest.len() > 1 { "s" } else { "" };
+                    write!(f, "{} (and {} more warning{})", warning, rest.len(), plural)
+                }
+            },
+        }
+    }
+}
+
+// `Error` is a transparent wrapper: its message is the one of the wrapped error, which is thus
+// not reported again as the source. The source of the wrapped error is forwarded instead.
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::EvalError(err) => err.source(),
+            Error::TypecheckError(err) => err.source(),
+            Error::ParseErrors(errs) => errs.source(),
+            Error::ImportError(err) => err.source(),
+            Error::SerializationError(err) => err.source(),
+            Error::IOError(err) => err.source(),
+            Error::ReplError(err) => err.source(),
+            Error::DeniedWarnings(_) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Warning::ShadowedStdlib(id) => write!(f, "`{}` shadows the standard library", id),
+            Warning::UnusedBinding(id) => write!(f, "unused binding `{}`", id),
+            Warning::OverrideBypass(_) => {
+                write!(f, "overriding this default value drops its contracts")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Warning {}
+
+impl std::fmt::Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EvalError::BlameError(l, _) => {
+                write!(f, "{}", blame_summary(l))?;
+
+                if let Some((expected, actual)) = blame_kind_mismatch(l) {
+                    write!(f, ": expected `{}`, got `{}`", expected, actual)
+                } else if !l.tag.is_empty() {
+                    write!(f, ": {}", escape(&l.tag))
+                } else {
+                    Ok(())
+                }
+            }
+            EvalError::MissingFieldDef(..) => write!(f, "missing field definition"),
+            EvalError::TypeError(expd, op, ..) => {
+                write!(f, "type error: {} expected a {}", op, expd)
+            }
+            EvalError::BinaryTypeError {
+                op,
+                expected,
+                fst,
+                snd,
+                ..
+            } => write!(
+                f,
+                "type error: `{}` expected two {} operands, got {} and {}",
+                op,
+                expected,
+                type_of_operand(fst),
+                type_of_operand(snd)
+            ),
+            EvalError::NotAFunc(..) => write!(f, "not a function"),
+            EvalError::FieldMissing(field, op, _, _, None) => {
+                write!(f, "missing field {} required by {}", field, op)
+            }
+            EvalError::FieldMissing(field, op, _, _, Some(index)) => write!(
+                f,
+                "missing field {} required by {} in the element at index {}",
+                field, op, index
+            ),
+            EvalError::NotARecord { field, value, .. } => write!(
+                f,
+                "field {} accessed on a value of type {}, which is not a record",
+                field,
+                value
+                    .term
+                    .type_of()
+                    .unwrap_or_else(|| String::from("<unevaluated>"))
+            ),
+            EvalError::NotEnoughArgs(count, op, _) => write!(
+                f,
+                "not enough arguments: {} expects {} argument(s)",
+                op, count
+            ),
+            EvalError::MergeIncompatibleArgs(..) => write!(f, "non mergeable terms"),
+            EvalError::UnboundIdentifier(ident, ..) => write!(f, "unbound identifier `{}`", ident),
+            EvalError::NonExhaustiveMatch { value, .. } => match value.as_ref() {
+                Term::Enum(tag) => write!(f, "non-exhaustive match: tag `{}` is not handled", tag),
+                _ => write!(f, "non-exhaustive match: expected an enum tag"),
+            },
+            EvalError::InfiniteRecursion(..) => write!(f, "infinite recursion"),
+            EvalError::Cancelled(..) => write!(f, "evaluation cancelled"),
+            EvalError::SerializationError(err) => err.fmt(f),
+            EvalError::DeserializationError(format, msg, ..) => {
+                write!(f, "{} parse error: {}", format, msg)
+            }
+            EvalError::ImportError(err) => err.fmt(f),
+            EvalError::InternalError(msg, _) => write!(f, "internal error: {}", msg),
+            EvalError::Other(msg, _) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EvalError::SerializationError(err) => err.source(),
+            EvalError::ImportError(err) => err.source(),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for TypecheckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TypecheckError::UnboundIdentifier(ident, ..) => {
+                write!(f, "unbound identifier `{}`", ident)
+            }
+            TypecheckError::IllformedType(ty) => write!(f, "ill-formed type `{}`", ty),
+            TypecheckError::MissingRow(ident, ..) => {
+                write!(f, "type error: missing row `{}`", ident)
+            }
+            TypecheckError::MissingDynTail(..) => {
+                write!(f, "type error: missing dynamic tail `| Dyn`")
+            }
+            TypecheckError::ExtraRow(ident, ..) => write!(f, "type error: extra row `{}`", ident),
+            TypecheckError::ExtraDynTail(..) => {
+                write!(f, "type error: extra dynamic tail `| Dyn`")
+            }
+            TypecheckError::UnboundTypeVariable(ident, _) => {
+                write!(f, "unbound type variable `{}`", ident)
+            }
+            TypecheckError::TypeMismatch(expd, actual, _) => write!(
+                f,
+                "incompatible types: expected `{}`, inferred `{}`",
+                expd, actual
+            ),
+            TypecheckError::RowKindMismatch(ident, ..) => {
+                write!(f, "incompatible row kinds for `{}`", ident)
+            }
+            TypecheckError::RowMismatch(ident, ..) => {
+                write!(f, "incompatible rows declaration for `{}`", ident)
+            }
+            TypecheckError::RowConflict(ident, ..) => {
+                write!(f, "multiple rows declaration for `{}`", ident)
+            }
+            TypecheckError::ArrowTypeMismatch(expd, actual, ..) => write!(
+                f,
+                "function types mismatch: expected `{}`, inferred `{}`",
+                expd, actual
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TypecheckError {}
+
+impl std::fmt::Display for ParseErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.errors.as_slice() {
+            [] => write!(f, "parse error"),
+            [err] => err.fmt(f),
+            [err, rest @ ..] => {
+                let plural = if rest.len() > 1 { "s" } else { "" };
+                write!(f, "{} (and {} more error{})", err, rest.len(), plural)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseErrors {}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ParseError::UnexpectedEOF(..) => write!(f, "unexpected end of file"),
+            ParseError::UnexpectedToken(..) => write!(f, "unexpected token"),
+            ParseError::ExtraToken(_) => write!(f, "superfluous unexpected token"),
+            ParseError::UnmatchedCloseBrace(..) => write!(f, "unmatched closing brace '}}'"),
+            ParseError::InvalidEscapeSequence(_) => write!(f, "invalid escape sequence"),
+            ParseError::InvalidAsciiEscapeCode(_) => write!(f, "invalid ascii escape code"),
+            ParseError::ExternalFormatError(format, msg, ..) => {
+                write!(f, "{} parse error: {}", format, msg)
+            }
+            ParseError::UnboundTypeVariables(idents, _) => write!(
+                f,
+                "unbound type variable(s): {}",
+                idents
+                    .iter()
+                    .map(|x| format!("`{}`", x))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            ParseError::InvalidUniRecord(..) => write!(f, "invalid record literal"),
+            ParseError::DuplicateField(path, ..) => {
+                write!(
+                    f,
+                    "duplicate definition of the field `{}`",
+                    fmt_field_path(path)
+                )
+            }
+            ParseError::DuplicateOverride(path, ..) => {
+                write!(f, "the field `{}` is overridden twice", path)
+            }
+            ParseError::TypeAliasArity(id, expected, given, _) => write!(
+                f,
+                "the type alias `{}` expects {} argument(s), but {} were given",
+                id, expected, given
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ImportError::IOError(path, msg, _) => write!(f, "import of {} failed: {}", path, msg),
+            ImportError::ParseErrors(errs, _) => write!(f, "import failed: {}", errs),
+            ImportError::InvalidStdlibModule(module, _) => {
+                write!(
+                    f,
+                    "invalid standard library module {}: not a record",
+                    module
+                )
+            }
+            ImportError::Transitive(err, _) => err.fmt(f),
+            ImportError::ImportCycle(chain, _) => {
+                write!(f, "import cycle: {}", chain.join(" imports "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ImportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        // The messages of the parse errors and of the transitive error are already part of the
+        // message of the import error.
+        match self {
+            ImportError::Transitive(err, _) => err.source(),
+            ImportError::IOError(..)
+            | ImportError::ParseErrors(..)
+            | ImportError::InvalidStdlibModule(..)
+            | ImportError::ImportCycle(..) => None,
+        }
+    }
+}
+
+impl std::fmt::Display for SerializationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SerializationError::UnsupportedNull(format, ..) => {
+                write!(f, "{} doesn't support null values", format)
+            }
+            SerializationError::NotAString(rt) => write!(
+                f,
+                "raw export only supports `Str`, got {}",
+                rt.as_ref()
+                    .type_of()
+                    .unwrap_or_else(|| String::from("<unevaluated>"))
+            ),
+            SerializationError::NonSerializable(..) => write!(f, "non serializable term"),
+            SerializationError::UnsupportedFormat(format, rt) => write!(
+                f,
+                "{} requires a record at the top-level, got {}",
+                format,
+                rt.as_ref()
+                    .type_of()
+                    .unwrap_or_else(|| String::from("<unevaluated>"))
+            ),
+            SerializationError::MixedArray(format, ..) => {
+                write!(
+                    f,
+                    "{} doesn't support arrays mixing records and other values",
+                    format
+                )
+            }
+            SerializationError::Other(msg) => write!(f, "error during serialization: {}", msg),
+        }
+    }
+}
+
+impl std::error::Error for SerializationError {}
+
+impl std::fmt::Display for IOError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            IOError::File { path, op, msg, .. } => {
+                write!(f, "could not {} the file `{}`: {}", op, path.display(), msg)
+            }
+            IOError::Other(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for IOError {}
+
+impl std::fmt::Display for ReplError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ReplError::UnknownCommand(cmd) => write!(f, "unknown command `{}`", cmd),
+            ReplError::MissingArg { cmd, .. } => write!(f, "{}: missing argument", cmd),
+            ReplError::InvalidArg { cmd, arg } => {
+                write!(f, "{}: invalid argument `{}`", cmd, arg)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplError {}
+
+/// Return an escaped version of a string. Used to sanitize strings before inclusion in error
+/// messages, which can contain ASCII code sequences, and in particular ANSI escape codes, that
+/// could alter Nickel's error messages.
+pub fn escape(s: &str) -> String {
+    String::from_utf8(
+        s.bytes()
+            .flat_map(std::ascii::escape_default)
+            .collect::<Vec<u8>>(),
+    )
+    .expect("escape(): converting from a string should give back a valid UTF8 string")
+}
+
+/// Render a static field path, quoting the names which are not valid identifiers, such that the
+/// path `a.b` and the quoted field `"a.b"` are told apart.
+fn fmt_field_path(path: &[Ident]) -> String {
+    fn is_identifier(name: &str) -> bool {
+        let mut chars = name.strip_prefix('_').unwrap_or(name).chars();
+
+        chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    }
+
+    path.iter()
+        .map(|id| {
+            if is_identifier(&id.label) {
+                id.label.clone()
+            } else {
+                format!("\"{}\"", escape(&id.label))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Format a number with a comma as thousands separator, as in `9,980`.
+fn with_thousands_sep(n: usize) -> String {
+    let digits = n.to_string();
+    let mut result = String::with_capacity(digits.len() + digits.len() / 3);
+    // The number of digits before the first separator, if any.
+    let head = digits.len() % 3;
+
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && i % 3 == head {
+            result.push(',');
+        }
+        result.push(c);
+    }
+
+    result
+}
+
+impl From<ReplError> for Error {
+    fn from(error: ReplError) -> Error {
+        Error::ReplError(error)
+    }
+}
+
+impl ParseError {
+    /// The span of an error which may be the consequence of the recovery from an earlier error.
+    fn recovery_span(&self) -> Option<RawSpan> {
+        match self {
+            ParseError::UnexpectedToken(span, _) | ParseError::ExtraToken(span) => Some(*span),
+            _ => None,
+        }
+    }
+
+    /// Convert an error of the parser. `source` is the parsed source, which is used to make
+    /// the spans pointing to a single character cover the whole character.
+    pub fn from_lalrpop<T>(
+        error: lalrpop_util::ParseError<usize, T, InternalParseError>,
+        file_id: FileId,
+        source: &str,
+    ) -> ParseError {
+        match error {
+            lalrpop_util::ParseError::InvalidToken { location } => {
+                ParseError::UnexpectedToken(mk_char_span(file_id, source, location, 1), Vec::new())
+            }
+            lalrpop_util::ParseError::UnrecognizedToken {
+                token: (start, _, end),
+                expected,
+            } => ParseError::UnexpectedToken(mk_span(file_id, start, end), expected),
+            lalrpop_util::ParseError::UnrecognizedEOF { expected, .. } => {
+                ParseError::UnexpectedEOF(file_id, expected)
+            }
+            lalrpop_util::ParseError::ExtraToken {
+                token: (start, _, end),
+            } => ParseError::ExtraToken(mk_span(file_id, start, end)),
+            lalrpop_util::ParseError::User { error } => match error {
+                InternalParseError::Lexical(LexicalError::Generic(start, end)) => {
+                    ParseError::UnexpectedToken(mk_span(file_id, start, end), Vec::new())
+                }
+                InternalParseError::Lexical(LexicalError::UnmatchedCloseBrace(
+                    location,
+                    opening,
+                )) => ParseError::UnmatchedCloseBrace(
+                    mk_char_span(file_id, source, location, 1),
+                    opening.map(|opening| mk_char_span(file_id, source, opening, 1)),
+                ),
+                InternalParseError::Lexical(LexicalError::InvalidEscapeSequence(location)) => {
+                    ParseError::InvalidEscapeSequence(mk_char_span(file_id, source, location, 1))
+                }
+                InternalParseError::Lexical(LexicalError::InvalidAsciiEscapeCode(location)) => {
+                    ParseError::InvalidAsciiEscapeCode(mk_char_span(file_id, source, location, 2))
+                }
+                InternalParseError::UnboundTypeVariables(idents, span) => {
+                    ParseError::UnboundTypeVariables(idents, span)
+                }
+                InternalParseError::InvalidUniRecord(illegal_pos, tail_pos, pos) => {
+                    ParseError::InvalidUniRecord(illegal_pos, tail_pos, pos)
+                }
+                InternalParseError::DuplicateField(path, fst_span, snd_span) => {
+                    ParseError::DuplicateField(path, fst_span, snd_span)
+                }
+                InternalParseError::TypeAliasArity(id, expected, given, span) => {
+                    ParseError::TypeAliasArity(id, expected, given, span)
+                }
+            },
+        }
+    }
+
+    pub fn from_serde_json(
+        error: serialize::JsonError,
+        file_id: FileId,
+        files: &Files<String>,
+    ) -> Self {
+        let source = files.source(file_id);
+
+        if let Some((first, second)) = error.duplicate_key {
+            return ParseError::ExternalFormatError(
+                String::from("json"),
+                error.error.to_string(),
+                Some(mk_span(file_id, second.start, second.end)),
+                Some(mk_span(file_id, first.start, first.end)),
+            );
+        }
+
+        let error = error.error;
+        // error.line() should start at `1` according to the documentation, but in practice, it may
+        // be 0 for the error `json parse error: data did not match any variant of untagged enum
+        // Term`. Although this error should not happen, if it does, it's better to get a message
+        // than a panic message `subtract with overflow`.
+        let line_span = if error.line() == 0 {
+            None
+        } else {
+            files.line_span(file_id, (error.line() - 1) as u32).ok()
+        };
+
+        // The column is a 1-based byte offset in the line.
+        let start = line_span.map(|ls| ls.start().to_usize() + error.column().saturating_sub(1));
+        ParseError::ExternalFormatError(
+            String::from("json"),
+            error.to_string(),
+            start.map(|start| mk_char_span(file_id, source, start, 1)),
+            None,
+        )
+    }
+
+    pub fn from_serde_yaml(
+        error: serde_yaml::Error,
+        file_id: FileId,
+        files: &Files<String>,
+    ) -> Self {
+        let source = files.source(file_id);
+        // The index of the location is a number of characters, not of bytes.
+        let start = error.location().map(|loc| {
+            source
+                .char_indices()
+                .nth(loc.index())
+                .map_or(source.len(), |(start, _)| start)
+        });
+        ParseError::ExternalFormatError(
+            String::from("yaml"),
+            error.to_string(),
+            start.map(|start| mk_char_span(file_id, source, start, 1)),
+            None,
+        )
+    }
+
+    pub fn from_toml(error: toml::de::Error, file_id: FileId, files: &Files<String>) -> Self {
+        // The column is a byte offset in the line.
+        let start = error.line_col().and_then(|(line, col)| {
+            Some(
+                files
+                    .line_span(file_id, line as u32)
+                    .ok()?
+                    .start()
+                    .to_usize()
+                    + col,
+            )
+        });
+        ParseError::ExternalFormatError(
+            String::from("toml"),
+            error.to_string(),
+            start.map(|start| mk_char_span(file_id, files.source(file_id), start, 1)),
+            None,
+        )
+    }
+}
+
+pub const INTERNAL_ERROR_MSG: &str =
+    "This error should not happen. This is likely a bug in the Nickel interpreter. Please consider\
+ reporting it at https://github.com/tweag/nickel/issues with the above error message.";
+
+/// A trait for converting an error to a diagnostic.
+pub trait ToDiagnostic<FileId> {
+    /// Convert an error to a list of printable formatted diagnostic.
+    ///
+    /// # Arguments
+    ///
+    /// - `files`: to know why it takes a mutable reference to the file database, see
+    ///   [`label_alt`](fn.label_alt.html). A `Files<String>` can be passed directly, see
+    ///   [`DiagnosticFiles`].
+    /// - `contract_id` is required to format the callstack when reporting blame errors. For some
+    ///   errors (such as [`ParseError`](./enum.ParseError.html)), contracts may not have been loaded
+    ///   yet, hence the optional. See also [`process_callstack`](fn.process_callstack.html).
+    ///
+    /// # Return
+    ///
+    /// Return a list of diagnostics. Most errors generate only one, but showing the callstack
+    /// ordered requires to sidestep a limitation of codespan. The current solution is to generate
+    /// one diagnostic per callstack element. See [this
+    /// issue](https://github.com/brendanzab/codespan/issues/285).
+    fn to_diagnostic(
+        &self,
+        files: &mut dyn DiagnosticFiles,
+        contract_id: Option<FileId>,
+    ) -> Vec<Diagnostic<FileId>>;
+
+    /// Same as [`to_diagnostic`](#tymethod.to_diagnostic), but with explicit [`DiagnosticOptions`].
+    /// Errors which are not affected by any option don't need to override the default
+    /// implementation, which ignores them.
+    fn to_diagnostic_with(
+        &self,
+        files: &mut dyn DiagnosticFiles,
+        contract_id: Option<FileId>,
+        _opts: &DiagnosticOptions,
+    ) -> Vec<Diagnostic<FileId>> {
+        self.to_diagnostic(files, contract_id)
+    }
+}
+
+/// The file database diagnostics are generated with. Generating a diagnostic may read the names
+/// and the sources of files, and add snippets for labels to point to (see
+/// [`label_alt`](fn.label_alt.html)).
+pub trait DiagnosticFiles {
+    /// The name of a file.
+    fn name(&self, file_id: FileId) -> &OsStr;
+
+    /// The span of the full source of a file.
+    fn source_span(&self, file_id: FileId) -> Span;
+
+    /// The one-based line and column of a byte index in a file, if it is valid.
+    fn location(&self, file_id: FileId, byte_index: usize) -> Option<(usize, usize)>;
+
+    /// Add a snippet to the database, and return its id.
+    fn add_snippet(&mut self, name: &str, source: String) -> FileId;
+
+    /// The representation of a term in a snippet: its shallow representation by default. See
+    /// [`Term::shallow_repr`].
+    fn term_repr(&self, term: &Term) -> String {
+        term.shallow_repr()
+    }
+}
+
+impl DiagnosticFiles for Files<String> {
+    fn name(&self, file_id: FileId) -> &OsStr {
+        Files::name(self, file_id)
+    }
+
+    fn source_span(&self, file_id: FileId) -> Span {
+        Files::source_span(self, file_id)
+    }
+
+    fn location(&self, file_id: FileId, byte_index: usize) -> Option<(usize, usize)> {
+        let location = Files::location(self, file_id, byte_index as u32).ok()?;
+        Some((location.line.to_usize() + 1, location.column.to_usize() + 1))
+    }
+
+    fn add_snippet(&mut self, name: &str, source: String) -> FileId {
+        self.add(name, source)
+    }
+}
+
+/// The default maximum number of call descriptions reported at each end of the call stack of a
+/// blame error. See [`DiagnosticOptions::call_stack_limit`].
+pub const DEFAULT_CALL_STACK_LIMIT: usize = 10;
+
+/// Options controlling the generation of diagnostics.
+#[derive(Clone, Debug)]
+pub struct DiagnosticOptions {
+    /// The number of calls reported at each end of the call stack of a blame error. When the
+    /// call stack is longer than twice this limit, the calls in the middle are replaced by a note
+    /// saying how many were omitted. `None` reports the whole call stack.
+    pub call_stack_limit: Option<usize>,
+    /// The policy applied to the diagnostics before they are rendered, if any. It is applied by
+    /// the renderers, such as [`to_simple_diagnostics_with`], and not by
+    /// [`ToDiagnostic::to_diagnostic_with`].
+    pub policy: Option<Arc<dyn ReportPolicy>>,
+    /// The position of the first import of each imported file. Values coming from a source in
+    /// an external format, such as YAML, are reported together with the import of this source.
+    /// It is filled by the cache when resolving imports.
+    pub import_sites: HashMap<FileId, RawSpan>,
+    /// The files inlined in each [bundle](../bundle/index.html), given by their byte range in the
+    /// bundle. It is filled by the cache when parsing a bundle, and applied by the renderers with
+    /// [`map_sources`](#method.map_sources).
+    pub source_maps: HashMap<FileId, Vec<(Range<usize>, FileId)>>,
+    /// The id of the last file added to the file database, if known. The renderers, such as
+    /// [`to_simple_diagnostics_with`], add the snippets generated with the diagnostics to a
+    /// separate database, and use this id to tell the files from the snippets. When it is unknown,
+    /// they copy the file database to find it out. It is filled by the cache.
+    pub last_file: Option<FileId>,
+    /// The toplevel declaration made by each input of the REPL, if any. A diagnostic pointing to
+    /// an earlier input labels the declaration with the name of the input, as in ``defined in
+    /// `<repl:3>` ``, and the one of an unbound identifier labels the latest declaration of the
+    /// suggested name. It is filled by the REPL.
+    pub definitions: HashMap<FileId, Ident>,
+    /// The maximum number of diagnostics reported for the parse errors of a source. The errors
+    /// left out are summed up in a final note, see [`ParseErrors::to_diagnostic_capped`]. `None`
+    /// reports all of them. It is set by the cache together with the number of parse errors kept
+    /// after parsing (see
+    /// [`Cache::set_max_parse_errors`](../cache/struct.Cache.html#method.set_max_parse_errors)).
+    pub max_parse_errors: Option<usize>,
+    /// The sensitive values met during evaluation, which are redacted from the diagnostics (see
+    /// [`crate::redact`]). It is filled by the cache during evaluation.
+    pub redactions: Redactions,
+}
+
+impl Default for DiagnosticOptions {
+    fn default() -> Self {
+        DiagnosticOptions {
+            call_stack_limit: Some(DEFAULT_CALL_STACK_LIMIT),
+            policy: None,
+            import_sites: HashMap::new(),
+            source_maps: HashMap::new(),
+            last_file: None,
+            definitions: HashMap::new(),
+            max_parse_errors: Some(MAX_PARSE_DIAGNOSTICS),
+            redactions: Redactions::new(),
+        }
+    }
+}
+
+impl DiagnosticOptions {
+    /// Apply the [policy](#structfield.policy), if any, to the diagnostics of an error.
+    pub fn apply_policy(&self, diags: Vec<Diagnostic<FileId>>) -> Vec<Diagnostic<FileId>> {
+        match &self.policy {
+            Some(policy) => report_policy::apply(policy.as_ref(), diags),
+            None => diags,
+        }
+    }
+
+    /// Move the labels located in a bundle to the inlined file they belong to, if any. See
+    /// [`source_maps`](#structfield.source_maps).
+    pub fn map_sources(&self, diags: &mut [Diagnostic<FileId>]) {
+        for label in diags.iter_mut().flat_map(|diag| diag.labels.iter_mut()) {
+            let region = self.source_maps.get(&label.file_id).and_then(|regions| {
+                regions.iter().find(|(range, _)| {
+                    range.start <= label.range.start && label.range.end <= range.end
+                })
+            });
+
+            if let Some((range, file_id)) = region {
+                label.file_id = *file_id;
+                label.range = (label.range.start - range.start)..(label.range.end - range.start);
+            }
+        }
+    }
+}
+
+// Helpers for the creation of codespan `Label`s
+
+/// Create a primary label from a span.
+fn primary(span: &RawSpan) -> Label<FileId> {
+    Label::primary(span.src_id, span.start.to_usize()..span.end.to_usize())
+}
+
+/// Create a secondary label from a span.
+fn secondary(span: &RawSpan) -> Label<FileId> {
+    Label::secondary(span.src_id, span.start.to_usize()..span.end.to_usize())
+}
+
+/// Create a label from an optional span, or fallback to annotating the alternative snippet
+/// `alt_term` if the span is `None`.
+///
+/// When `span_opt` is `None`, the code snippet `alt_term` is added to `files` under a special
+/// name and is referred to instead.
+///
+/// This is useful because during evaluation, some terms are the results of computations. They
+/// correspond to nothing in the original source, and thus have a position set to `None`(e.g. the
+/// result of `let x = 1 + 1 in x`).  In such cases it may still be valuable to print the term (or
+/// a terse representation) in the error diagnostic rather than nothing, because if you have let `x
+/// = 1 + 1 in` and then 100 lines later, `x arg` - causing a `NotAFunc` error - it may be helpful
+/// to know that `x` holds the value `2`.
+///
+/// For example, if one wants to report an error on a record, `alt_term` may be defined to `{ ...  }`.
+/// Then, if this record has no position (`span_opt` is `None`), the error will be reported as:
+///
+/// ```text
+/// error: some error
+///   -- <unknown> (generated by evaluation):1:2
+///   |
+/// 1 | { ... }
+///     ^^^^^^^ some annotation
+/// ```
+///
+/// The reason for the mutable reference to `files` is that codespan do no let you annotate
+/// something that is not in `files`: you can't provide a raw snippet, you need to provide a
+/// `FileId` referring to a file. This leaves the following possibilities:
+///
+/// 1. Do nothing: just elude annotations which refer to the term
+/// 2. Print the term and the annotation as a note together with the diagnostic. Notes are
+///    additional text placed at the end of diagnostic. What you lose:
+///     - pretty formatting of annotations for such snippets
+///     - style consistency: the style of the error now depends on the term being from the source
+///     or a byproduct of evaluation
+/// 3. Add the term to files, take 1: pass a reference to files so that the code building the
+///    diagnostic can itself add arbitrary snippets if necessary, and get back their `FileId`. This
+///    is what is done here.
+/// 4. Add the term to files, take 2: make a wrapper around the `Files` and `FileId` structures of
+///    codespan which handle source mapping. `FileId` could be something like
+///    `Either<codespan::FileId, CustomId = u32>` so that `to_diagnostic` could construct and use these
+///    separate ids, and return the corresponding snippets to be added together with the
+///    diagnostic without modifying external state. Or even have `FileId = Either<codespan::FileId`,
+///    `LoneCode = String or (Id, String)>` so we don't have to return the additional list of
+///    snippets. This adds some boilerplate, that we wanted to avoid, but this stays on the
+///    reasonable side of being an alternative.
+fn label_alt(
+    span_opt: Option<RawSpan>,
+    alt_term: String,
+    style: LabelStyle,
+    files: &mut dyn DiagnosticFiles,
+) -> Label<FileId> {
+    match span_opt {
+        Some(span) => Label::new(
+            style,
+            span.src_id,
+            span.start.to_usize()..span.end.to_usize(),
+        ),
+        None => {
+            let range = 0..alt_term.len();
+            Label::new(
+                style,
+                files.add_snippet("<unknown> (generated by evaluation)", alt_term),
+                range,
+            )
+        }
+    }
+}
+
+/// Create a secondary label from an optional span, or fallback to annotating the alternative snippet
+/// `alt_term` if the span is `None`.
+///
+/// See [`label_alt`](fn.label_alt.html).
+fn primary_alt(
+    span_opt: Option<RawSpan>,
+    alt_term: String,
+    files: &mut dyn DiagnosticFiles,
+) -> Label<FileId> {
+    label_alt(span_opt, alt_term, LabelStyle::Primary, files)
+}
+
+/// Create a primary label from a term, or fallback to annotating the shallow representation of this term
+/// if its span is `None`.
+///
+/// See [`label_alt`](fn.label_alt.html).
+fn primary_term(term: &RichTerm, files: &mut dyn DiagnosticFiles) -> Label<FileId> {
+    let repr = files.term_repr(term.as_ref());
+    primary_alt(term.pos.into_opt(), repr, files)
+}
+
+/// Create a secondary label from an optional span, or fallback to annotating the alternative snippet
+/// `alt_term` if the span is `None`.
+///
+/// See [`label_alt`](fn.label_alt.html).
+fn secondary_alt(
+    span_opt: TermPos,
+    alt_term: String,
+    files: &mut dyn DiagnosticFiles,
+) -> Label<FileId> {
+    label_alt(span_opt.into_opt(), alt_term, LabelStyle::Secondary, files)
+}
+
+/// Create a secondary label from a term, or fallback to annotating the shallow representation of this term
+/// if its span is `None`.
+///
+/// See [`label_alt`](fn.label_alt.html).
+fn secondary_term(term: &RichTerm, files: &mut dyn DiagnosticFiles) -> Label<FileId> {
+    let repr = files.term_repr(term.as_ref());
+    secondary_alt(term.pos, repr, files)
+}
+
+/// The type of an operand of a [`EvalError::BinaryTypeError`], with an indefinite article, as in
+/// `a Str` or `an Array`.
+fn type_of_operand(term: &RichTerm) -> String {
+    match term.term.type_of() {
+        Some(ty) if ty.starts_with(['A', 'E', 'I', 'O', 'U']) => format!("an {}", ty),
+        Some(ty) => format!("a {}", ty),
+        None => String::from("<unevaluated>"),
+    }
+}
+
+/// Maximum number of fields listed in the note of [`available_fields_notes`]. The remaining ones
+/// are only counted.
+const MAX_AVAILABLE_FIELDS: usize = 10;
+
+/// Generate the notes of a [`EvalError::FieldMissing`] when the value is a record: the fields of
+/// the record, sorted and truncated after [`MAX_AVAILABLE_FIELDS`] names, and the field closest to
+/// the missing one, if any.
+fn available_fields_notes(field: &str, term: &RichTerm) -> Vec<String> {
+    let fields = match term.as_ref() {
+        Term::Record(fields, _) => fields,
+        _ => return Vec::new(),
+    };
+
+    let mut names: Vec<&str> = fields.keys().map(|id| id.as_ref()).collect();
+    names.sort_unstable();
+
+    if names.is_empty() {
+        return vec![String::from("the record has no fields")];
+    }
+
+    let closest = suggest::closest(field, names.iter().copied());
+    let mut listed: Vec<String> = names
+        .iter()
+        .take(MAX_AVAILABLE_FIELDS)
+        .map(|name| escape(name))
+        .collect();
+
+    if names.len() > MAX_AVAILABLE_FIELDS {
+        listed.push(format!("and {} more", names.len() - MAX_AVAILABLE_FIELDS));
+    }
+
+    let mut notes = vec![format!("available fields: {}", listed.join(", "))];
+    if let Some(closest) = closest {
+        notes.push(format!("help: did you mean `{}`?", escape(closest)));
+    }
+
+    notes
+}
+
+/// If the position of a term points to a source in an external format (JSON, YAML or TOML), that
+/// is if the term comes from the import of such a file, return the name of the format and of the
+/// file.
+fn external_origin(term: &RichTerm, files: &dyn DiagnosticFiles) -> Option<(&'static str, String)> {
+    external_source(term.pos.file_id()?, files)
+}
+
+/// If a source is in an external format (JSON, YAML or TOML), return the name of the format and
+/// of the file.
+fn external_source(file_id: FileId, files: &dyn DiagnosticFiles) -> Option<(&'static str, String)> {
+    let name = files.name(file_id);
+
+    let format = match InputFormat::from_path_buf(Path::new(name))? {
+        InputFormat::Nickel => return None,
+        InputFormat::Json => "JSON",
+        InputFormat::Yaml => "YAML",
+        InputFormat::Toml => "TOML",
+    };
+
+    Some((format, name.to_string_lossy().into_owned()))
+}
+
+/// Return the end of the files of a database, that is a marker such that any id from this marker
+/// onwards isn't a file of this database. Positions and labels may refer to another file database,
+/// as the one used for reporting may not be the one of the evaluation, and must be checked against
+/// this marker before being looked up.
+fn files_end(files: &mut dyn DiagnosticFiles) -> FileId {
+    files.add_snippet("", String::new())
+}
+
+/// Locate the fields responsible for a blame error (see [`label::Label::fields`]), with a note
+/// giving the position of each field, as in ``field `a` is set at main.ncl:3:5``.
+fn blame_fields_notes(l: &label::Label, files: &mut dyn DiagnosticFiles) -> Vec<String> {
+    if !l.fields.iter().any(|id| id.pos.is_def()) {
+        return Vec::new();
+    }
+
+    let end = files_end(files);
+
+    l.fields
+        .iter()
+        .filter_map(|id| {
+            let span = id.pos.into_opt().filter(|span| span.src_id < end)?;
+            let (line, column) = files.location(span.src_id, span.start.to_usize())?;
+            Some(format!(
+                "field `{}` is set at {}:{}:{}",
+                id,
+                files.name(span.src_id).to_string_lossy(),
+                line,
+                column
+            ))
+        })
+        .collect()
+}
+
+/// Tell which file the values of a diagnostic coming from a source in an external format were
+/// imported from, with a note for each such source one of the labels points to, or one of the
+/// values reported by the diagnostic comes from (see [`TermPos::File`]). The import of the source
+/// is labeled as well, if it is known (see [`DiagnosticOptions::import_sites`]).
+fn note_external_sources(
+    diagnostic: &mut Diagnostic<FileId>,
+    values: &[TermPos],
+    files: &mut dyn DiagnosticFiles,
+    opts: &DiagnosticOptions,
+) {
+    let end = files_end(files);
+    let mut sources: Vec<FileId> = Vec::new();
+
+    let label_ids = diagnostic.labels.iter().map(|label| label.file_id);
+    let value_ids = values.iter().filter_map(TermPos::file_id);
+
+    for file_id in label_ids.chain(value_ids) {
+        if file_id < end && !sources.contains(&file_id) && external_source(file_id, files).is_some()
+        {
+            sources.push(file_id);
+        }
+    }
+
+    for file_id in sources {
+        let (_, name) = external_source(file_id, files).unwrap();
+        diagnostic
+            .notes
+            .push(format!("this value was imported from {}", name));
+
+        if let Some(span) = opts.import_sites.get(&file_id) {
+            diagnostic
+                .labels
+                .push(secondary(span).with_message(format!("{} imported here", name)));
+        }
+    }
+}
+
+/// Generate a codespan label that describes the [type path](../label/enum.TyPath.html) of a
+/// (Nickel) label, and notes to hint at the situation that may have caused the corresponding
+/// error.
+fn report_ty_path(
+    l: &label::Label,
+    files: &mut dyn DiagnosticFiles,
+) -> (Label<FileId>, Vec<String>) {
+    let end_note = String::from("Note: this is an illustrative example. The actual error may involve deeper nested functions calls.");
+
+    let (msg, notes) = if l.path.is_empty() {
+        (String::from("expected type"), Vec::new())
+    } else if ty_path::has_no_arrow(&l.path) {
+        match l.path.last() {
+            Some(ty_path::Elem::Array) => (String::from("expected array element type"), Vec::new()),
+            Some(ty_path::Elem::Field(_)) => (String::from("expected field type"), Vec::new()),
+            _ => unreachable!(),
+        }
+    }
+    // If the path is only composed of codomains, polarity is necessarily true and the cause of the
+    // blame is the return value of the function
+    else if ty_path::is_only_codom(&l.path) {
+        (
+            String::from("expected return type"),
+            vec![
+                String::from(
+                    "This error may happen in the following situation:
+1. A function `f` is bound by a contract: e.g. `Bool -> Num`.
+2. `f` returns a value of the wrong type: e.g. `f = fun c => \"string\"` while `Num` is expected.",
+                ),
+                String::from(
+                    "Either change the contract accordingly, or change the return value of `f`",
+                ),
+            ],
+        )
+    } else {
+        // We ignore the `Field` and `Array` elements of the path, since they do not impact
+        // polarity, and only consider "higher-order" elements to customize error messages.
+        let last = l
+            .path
+            .iter()
+            .filter(|elt| matches!(*elt, ty_path::Elem::Domain | ty_path::Elem::Codomain))
+            .last()
+            .unwrap();
+        match last {
+            ty_path::Elem::Domain if l.polarity => {
+                (String::from("expected type of an argument of an inner call"),
+                 vec![
+                     String::from("This error may happen in the following situation:
+1. A function `f` is bound by a contract: e.g. `(Str -> Str) -> Str)`.
+2. `f` takes another function `g` as an argument: e.g. `f = fun g => g 0`.
+3. `f` calls `g` with an argument that does not respect the contract: e.g. `g 0` while `Str -> Str` is expected."),
+                     String::from("Either change the contract accordingly, or call `g` with a `Str` argument."),
+                     end_note,
+                 ])
+            }
+            ty_path::Elem::Codomain if l.polarity => {
+                (String::from("expected return type of a sub-function passed as an argument of an inner call"),
+                 vec![
+                     String::from("This error may happen in the following situation:
+1. A function `f` is bound by a contract: e.g. `((Num -> Num) -> Num) -> Num)`.
+2. `f` take another function `g` as an argument: e.g. `f = fun g => g (fun x => true)`.
+3. `g` itself takes a function as an argument.
+4. `f` passes a function that does not respect the contract to `g`: e.g. `g (fun x => true)` (expected to be of type `Num -> Num`)."),
+                     String::from("Either change the contract accordingly, or call `g` with a function that returns a value of type `Num`."),
+                     end_note,
+                 ])
+            }
+            ty_path::Elem::Domain => {
+                (String::from("expected type of the argument provided by the caller"),
+                 vec![
+                     String::from("This error may happen in the following situation:
+1. A function `f` is bound by a contract: e.g. `Num -> Num`.
+2. `f` is called with an argument of the wrong type: e.g. `f false`."),
+                     String::from("Either change the contract accordingly, or call `f` with an argument of the right type."),
+                     end_note,
+                 ])
+            }
+            ty_path::Elem::Codomain => {
+                (String::from("expected return type of a function provided by the caller"),
+                 vec![
+                     String::from("This error may happen in the following situation:
+1. A function `f` is bound by a contract: e.g. `(Num -> Num) -> Num`.
+2. `f` takes another function `g` as an argument: e.g. `f = fun g => g 0`.
+3. `f` is called by with an argument `g` that does not respect the contract: e.g. `f (fun x => false)`."),
+                     String::from("Either change the contract accordingly, or call `f` with a function that returns a value of the right type."),
+                     end_note,
+                 ])
+            }
+            _ => panic!(),
+        }
+    };
+
+    let (start, end) = ty_path::span(l.path.iter().peekable(), &l.types);
+    let label = Label::new(
+        LabelStyle::Secondary,
+        files.add_snippet("", format!("{}", l.types)),
+        start..end,
+    )
+    .with_message(msg);
+    (label, notes)
+}
+
+/// Detect a mismatch between the top-level kind (record, array or function) expected by the
+/// subcontract being checked by a label, and the kind of the value that was actually provided.
+///
+/// Return the pair `(expected, actual)` of the kinds, using the same names as
+/// [`Term::type_of`](../term/enum.Term.html#method.type_of), or `None` if there is no such
+/// mismatch or if it can't be determined, e.g. if the value hasn't been evaluated.
+fn blame_kind_mismatch(l: &label::Label) -> Option<(String, String)> {
+    use crate::types::AbsType;
+
+    let expected = match ty_path::subtype(l.path.iter(), &l.types)?.0 {
+        AbsType::StaticRecord(_) | AbsType::DynRecord(_) => "Record",
+        AbsType::Array(_) => "Array",
+        AbsType::Arrow(..) => "Fun",
+        _ => return None,
+    };
+    let actual = l.arg_thunk.as_ref()?.borrow().body.as_ref().type_of()?;
+
+    if expected == actual {
+        None
+    } else {
+        Some((String::from(expected), actual))
+    }
+}
+
+/// Tell who broke the contract of a blame error: a value, a function or the caller of a function.
+fn blame_summary(l: &label::Label) -> &'static str {
+    if !l.polarity {
+        // A custom contract may flip the polarity of its label without any arrow in the path (see
+        // `contract.label.flip_polarity`): a negative blame is always on the environment.
+        "contract broken by the caller"
+    } else if ty_path::has_no_arrow(&l.path) {
+        "contract broken by a value"
+    } else {
+        "contract broken by a function"
+    }
+}
+
+/// Return a note diagnostic showing where a contract was declared, together with the annotated
+/// value and the identifier it is bound to, if known.
+fn blame_label_note(l: &label::Label) -> Diagnostic<FileId> {
+    let mut labels = vec![primary(&l.span).with_message("contract declared here")];
+
+    // The annotated value is already reported by the error if the contract was applied to it
+    // directly.
+    if let Some(value_span) = l.value_pos.as_opt_ref() {
+        if l.arg_pos.as_opt_ref() != Some(value_span) {
+            labels.push(secondary(value_span).with_message("applied to this value"));
+        }
+    }
+
+    if let Some(Ident {
+        label: name,
+        pos: TermPos::Original(span),
+    }) = &l.binding
+    {
+        labels.push(secondary(span).with_message(format!("bound as `{}`", name)));
+    }
+
+    Diagnostic::note().with_labels(labels)
+}
+
+/// Extract from the call stack of an infinite recursion the chain of references forming the cycle,
+/// that is the variables and the fields entered since the identifier of the recursive reference was
+/// first entered, together with the position of each reference. The first element and the last
+/// one have the same name. Return `None` if the start of the cycle can't be found, for example
+/// because the first evaluation of the identifier wasn't triggered by a variable or a field
+/// access.
+///
+/// Function parameters are skipped, as well as the variables and fields accessed to get a
+/// function which is then called, such as `f` in `f x`: their evaluation has completed before the
+/// cycle was closed. Generated identifiers, introduced by the [share normal
+/// form](../transform/share_normal_form/index.html) transformation, stand for the value of the
+/// field or the variable entered just before them, and are named after it.
+fn recursion_cycle(call_stack: &CallStack) -> Option<Vec<(Ident, TermPos)>> {
+    use crate::eval::callstack::StackElem;
+    use crate::eval::IdentKind;
+
+    let elems = call_stack.as_ref();
+    let refs: Vec<(&Ident, TermPos)> = elems
+        .iter()
+        .enumerate()
+        .filter_map(|(i, elem)| {
+            let (id, pos) = match elem {
+                StackElem::Var { kind, id, pos } if *kind != IdentKind::Lambda => (id, *pos),
+                StackElem::Field { id, pos_access, .. } => (id, *pos_access),
+                _ => return None,
+            };
+            let called = matches!(
+                elems[i + 1..].iter().find(|elem| match elem {
+                    StackElem::Var { id, .. } => !id.is_generated(),
+                    StackElem::App(pos) => matches!(pos, TermPos::Original(_)),
+                    _ => true,
+                }),
+                Some(StackElem::Fun(_))
+            );
+
+            (!called).then_some((id, pos))
+        })
+        .collect();
+
+    // The recursive reference is the last element of the call stack.
+    let (closing, entered) = refs.split_last()?;
+    let start = entered
+        .iter()
+        .rposition(|(id, _)| id.label == closing.0.label)?;
+
+    let mut names: Vec<Option<&Ident>> = Vec::with_capacity(refs.len());
+    let mut cycle: Vec<(Ident, TermPos)> = Vec::new();
+
+    for (i, (id, pos)) in refs.iter().enumerate() {
+        let name = if id.is_generated() {
+            names.last().copied().flatten()
+        } else {
+            Some(*id)
+        };
+        names.push(name);
+
+        match name {
+            Some(name) if i >= start => {
+                let alias = id.is_generated()
+                    && matches!(cycle.last(), Some((prev, _)) if prev.label == name.label);
+                if !alias {
+                    cycle.push((name.clone(), *pos));
+                }
+            }
+            _ => (),
+        }
+    }
+
+    (cycle.len() >= 2).then_some(cycle)
+}
+
+/// Sort a list of diagnostics by the position of their primary label, that is by `(file_id,
+/// start)`. The sort is stable: diagnostics at the same position keep their original order.
+///
+/// Labelled notes, such as the call stack or the "contract declared here" note of a blame error,
+/// stay attached to the diagnostic they follow and are moved together with it. Diagnostics
+/// without a primary label (and notes without any label, like the summary of omitted parse
+/// errors) are put last.
+pub fn sort_diagnostics(diags: &mut Vec<Diagnostic<FileId>>) {
+    fn primary_pos(diag: &Diagnostic<FileId>) -> Option<(FileId, usize)> {
+        diag.labels
+            .iter()
+            .find(|label| label.style == LabelStyle::Primary)
+            .map(|label| (label.file_id, label.range.start))
+    }
+
+    let mut groups: Vec<Vec<Diagnostic<FileId>>> = Vec::new();
+
+    for diag in diags.drain(..) {
+        match groups.last_mut() {
+            Some(group) if diag.severity == Severity::Note && !diag.labels.is_empty() => {
+                group.push(diag)
+            }
+            _ => groups.push(vec![diag]),
+        }
+    }
+
+    // `None` is smaller than any `Some`: the leading boolean puts diagnostics without a position
+    // last.
+    groups.sort_by_key(|group| {
+        let pos = primary_pos(&group[0]);
+        (pos.is_none(), pos)
+    });
+    diags.extend(groups.into_iter().flatten());
+}
+
+impl ToDiagnostic<FileId> for Error {
+    fn to_diagnostic(
+        &self,
+        files: &mut dyn DiagnosticFiles,
+        contract_id: Option<FileId>,
+    ) -> Vec<Diagnostic<FileId>> {
+        self.to_diagnostic_with(files, contract_id, &DiagnosticOptions::default())
+    }
+
+    fn to_diagnostic_with(
+        &self,
+        files: &mut dyn DiagnosticFiles,
+        contract_id: Option<FileId>,
+        opts: &DiagnosticOptions,
+    ) -> Vec<Diagnostic<FileId>> {
+        let mut diags = match self {
+            Error::ParseErrors(errs) => errs.to_diagnostic_with(files, contract_id, opts),
+            Error::TypecheckError(err) => err.to_diagnostic(files, contract_id),
+            Error::EvalError(err) => err.to_diagnostic_with(files, contract_id, opts),
+            Error::ImportError(err) => err.to_diagnostic_with(files, contract_id, opts),
+            Error::SerializationError(err) => err.to_diagnostic(files, contract_id),
+            Error::IOError(err) => err.to_diagnostic(files, contract_id),
+            Error::ReplError(err) => err.to_diagnostic(files, contract_id),
+            Error::DeniedWarnings(warnings) => warnings
+                .iter()
+                .flat_map(|warning| {
+                    let mut diags = warning.to_diagnostic(files, contract_id);
+                    if let Some(head) = diags.first_mut() {
+                        head.severity = Severity::Error;
+                        head.notes
+                            .push(format!("the `{}` lint is denied", warning.lint()));
+                    }
+                    diags
+                })
+                .collect(),
+        };
+
+        label_definitions(self, &mut diags, files, opts);
+        sort_diagnostics(&mut diags);
+        diags
+    }
+}
+
+/// Label the toplevel declarations of the REPL an error refers to (see
+/// [`DiagnosticOptions::definitions`]): the ones of the inputs its diagnostics point to, and the
+/// latest declaration of the name suggested for an unbound identifier. Each declaration is labeled
+/// once, in the first diagnostic referring to it.
+fn label_definitions(
+    error: &Error,
+    diags: &mut [Diagnostic<FileId>],
+    files: &dyn DiagnosticFiles,
+    opts: &DiagnosticOptions,
+) {
+    if opts.definitions.is_empty() {
+        return;
+    }
+
+    let closest = match error {
+        Error::EvalError(EvalError::UnboundIdentifier(_, _, Some(closest)))
+        | Error::TypecheckError(TypecheckError::UnboundIdentifier(_, _, Some(closest))) => {
+            Some(closest)
+        }
+        _ => None,
+    };
+    // The latest declaration is the one which is in scope, having possibly shadowed the others.
+    let suggested = closest.and_then(|closest| {
+        opts.definitions
+            .iter()
+            .filter(|(_, id)| *id == closest)
+            .max_by_key(|(file_id, _)| **file_id)
+            .map(|(_, id)| id)
+    });
+
+    let mut labeled: Vec<RawSpan> = Vec::new();
+    for (index, diag) in diags.iter_mut().enumerate() {
+        let mut definitions: Vec<&Ident> = diag
+            .labels
+            .iter()
+            .filter_map(|label| opts.definitions.get(&label.file_id))
+            .collect();
+        if index == 0 {
+            definitions.extend(suggested);
+        }
+
+        for id in definitions {
+            match id.pos.into_opt() {
+                Some(span) if !labeled.contains(&span) => {
+                    diag.labels.push(secondary(&span).with_message(format!(
+                        "`{}` defined in `{}`",
+                        id,
+                        files.name(span.src_id).to_string_lossy()
+                    )));
+                    labeled.push(span);
+                }
+                _ => (),
+            }
+        }
+    }
+}
+
+impl ToDiagnostic<FileId> for EvalError {
+    fn to_diagnostic(
+        &self,
+        files: &mut dyn DiagnosticFiles,
+        contract_id: Option<FileId>,
+    ) -> Vec<Diagnostic<FileId>> {
+        self.to_diagnostic_with(files, contract_id, &DiagnosticOptions::default())
+    }
+
+    fn to_diagnostic_with(
+        &self,
+        files: &mut dyn DiagnosticFiles,
+        contract_id: Option<FileId>,
+        opts: &DiagnosticOptions,
+    ) -> Vec<Diagnostic<FileId>> {
+        let diags = match self {
+            EvalError::BlameError(l, call_stack) => {
+                let mut msg = String::new();
+
+                // Writing in a string should not raise an error, hence the fearless `unwrap()`
+                write!(&mut msg, "{}", blame_summary(l)).unwrap();
+
+                let kind_mismatch = blame_kind_mismatch(l);
+
+                if let Some((ref expected, ref actual)) = kind_mismatch {
+                    write!(&mut msg, ": expected `{}`, got `{}`", expected, actual).unwrap();
+                } else if !l.tag.is_empty() {
+                    write!(&mut msg, ": {}", &escape(&l.tag)).unwrap();
+                }
+
+                let (path_label, mut notes) = report_ty_path(l, files);
+
+                if !l.field_path.is_empty() {
+                    notes.insert(0, format!("at field path `{}`", l.field_path));
+                }
+
+                // When the kind mismatch leads the message, the tag is relegated to the notes.
+                if kind_mismatch.is_some() && !l.tag.is_empty() {
+                    notes.insert(0, escape(&l.tag));
+                }
+
+                notes.extend(blame_fields_notes(l, files));
+                let mut labels = vec![path_label];
+
+                if let Some(ref arg_pos) = l.arg_pos.into_opt() {
+                    // In some cases, if the blame error is located in an argument or return value
+                    // of an higher order functions for example, the original argument position can
+                    // point to the builtin implementation contract like `func` or `record`, so
+                    // there's no good reason to show it. Note than even in that case, the
+                    // information contained in the argument thunk can still be useful.
+                    if contract_id
+                        .map(|ctrs_id| arg_pos.src_id != ctrs_id)
+                        .unwrap_or(true)
+                    {
+                        labels.push(primary(arg_pos).with_message("applied to this expression"));
+                    }
+                }
+
+                if let Some(example_pos) = l.example_pos.as_opt_ref() {
+                    labels.push(
+                        secondary(example_pos)
+                            .with_message("the contract was derived from this example"),
+                    );
+                }
+
+                // If we have a reference to the thunk that was being tested, we can try to show
+                // more information about the final, evaluated value that is responsible for the
+                // blame.
+                let mut values = vec![l.arg_pos];
+
+                if let Some(ref thunk) = l.arg_thunk {
+                    let mut val = thunk.get_owned().body;
+                    values.push(val.pos);
+
+                    match (val.pos, l.arg_pos.as_opt_ref(), contract_id) {
+                        // Avoid showing a position inside builtin contracts, it's rarely
+                        // informative.
+                        (TermPos::Original(val_pos), _, Some(c_id)) if val_pos.src_id == c_id => {
+                            val.pos = TermPos::None;
+                            labels.push(
+                                secondary_term(&val, files).with_message("evaluated to this value"),
+                            );
+                        }
+                        // Do not show the same thing twice: if arg_pos and val_pos are the same,
+                        // the first label "applied to this value" is sufficient.
+                        (TermPos::Original(ref val_pos), Some(arg_pos), _)
+                            if val_pos == arg_pos => {}
+                        (TermPos::Original(ref val_pos), ..) => labels
+                            .push(secondary(val_pos).with_message("evaluated to this expression")),
+                        // If the final thunk is a direct reduct of the original value, rather
+                        // print the actual value than referring to the same position as
+                        // before.
+                        (TermPos::Inherited(ref val_pos), Some(arg_pos), _)
+                            if val_pos == arg_pos =>
+                        {
+                            val.pos = TermPos::None;
+                            labels.push(
+                                secondary_term(&val, files).with_message("evaluated to this value"),
+                            );
+                        }
+                        // Finally, if the parameter reduced to a value which originates from a
+                        // different expression, show both the expression and the value.
+                        (TermPos::Inherited(ref val_pos), ..) => {
+                            labels.push(
+                                secondary(val_pos).with_message("evaluated to this expression"),
+                            );
+                            val.pos = TermPos::None;
+                            labels.push(
+                                secondary_term(&val, files).with_message("evaluated to this value"),
+                            );
+                        }
+                        (TermPos::File(_) | TermPos::None, ..) => labels.push(
+                            secondary_term(&val, files).with_message("evaluated to this value"),
+                        ),
+                    }
+                }
+
+                let mut diagnostic = Diagnostic::error()
+                    .with_message(msg)
+                    .with_labels(labels)
+                    .with_notes(notes);
+                note_external_sources(&mut diagnostic, &values, files, opts);
+
+                let mut diagnostics = vec![diagnostic, blame_label_note(l)];
+
+                if ty_path::is_only_codom(&l.path) {
+                } else if let Some(id) = contract_id {
+                    let (calls, curr_call) = call_stack.group_by_calls(id);
+                    let diag_curr_call = curr_call.map(|cdescr| {
+                        let name = cdescr
+                            .head
+                            .map(|ident| ident.to_string())
+                            .unwrap_or_else(|| String::from("<func>"));
+                        Diagnostic::note().with_labels(vec![primary(&cdescr.span)
+                            .with_message(format!("While calling to {}", name))])
+                    });
+                    let diag_call = |i: usize, cdescr: CallDescr| {
+                        let name = cdescr
+                            .head
+                            .map(|ident| ident.to_string())
+                            .unwrap_or_else(|| String::from("<func>"));
+                        let msg = if cdescr.repeat > 1 {
+                            format!(
+                                "({}) calling {} (repeated {} times)",
+                                i + 1,
+                                name,
+                                with_thousands_sep(cdescr.repeat)
+                            )
+                        } else {
+                            format!("({}) calling {}", i + 1, name)
+                        };
+
+                        Diagnostic::note()
+                            .with_labels(vec![secondary(&cdescr.span).with_message(msg)])
+                    };
+
+                    diagnostics.extend(diag_curr_call);
+
+                    match opts.call_stack_limit {
+                        // Only keep the first and the last calls of giant call stacks, which
+                        // typically come from deep recursions.
+                        Some(limit) if calls.len() > 2 * limit => {
+                            let omitted = calls.len() - 2 * limit;
+                            let mut calls = calls.into_iter().enumerate();
+
+                            diagnostics.extend(
+                                calls
+                                    .by_ref()
+                                    .take(limit)
+                                    .map(|(i, cdescr)| diag_call(i, cdescr)),
+                            );
+                            diagnostics.push(Diagnostic::note().with_message(format!(
+                                "... {} frames omitted ...",
+                                with_thousands_sep(omitted)
+                            )));
+                            diagnostics.extend(
+                                calls.skip(omitted).map(|(i, cdescr)| diag_call(i, cdescr)),
+                            );
+                        }
+                        _ => diagnostics.extend(
+                            calls
+                                .into_iter()
+                                .enumerate()
+                                .map(|(i, cdescr)| diag_call(i, cdescr)),
+                        ),
+                    }
+                }
+
+                diagnostics
+            }
+            EvalError::MissingFieldDef(label, callstack) => {
+                use crate::eval::callstack::StackElem;
+
+                // The following code determines what was the last accessed record field by looking
+                // at the call stack. Because of recursive records though, the fields may actually
+                // be accessed via a variable:
+                //
+                // ```
+                //  {
+                //    foo | Dyn
+                //        | doc "Oops, undefined :(",
+                //    bar = 1 + foo,
+                //  }.bar
+                //  ```
+                //
+                // Here, the missing field doesn't correspond to a field access, but to a variable
+                // occurrence `foo`. Thus, we take the last non-generated identifier accessed
+                // (either variable or field) as the name of the missing field.
+                let mut field: Option<String> = None;
+                let mut pos_record = TermPos::None;
+                let mut pos_access: Option<TermPos> = None;
+
+                for elt in callstack.as_ref().iter().rev() {
+                    match elt {
+                        StackElem::Var { id, pos, .. } if !id.is_generated() && field.is_none() => {
+                            field = Some(id.to_string());
+                            pos_access = Some(*pos);
+                        }
+                        StackElem::Field {
+                            id,
+                            pos_record: pos_rec,
+                            pos_access: pos_acc,
+                            ..
+                        } => {
+                            field.get_or_insert(id.to_string());
+                            pos_access.get_or_insert(*pos_acc);
+                            pos_record = *pos_rec;
+                            break;
+                        }
+                        _ => (),
+                    }
+                }
+
+                let mut labels = vec![];
+
+                if let Some(span) = pos_record.into_opt() {
+                    labels.push(primary(&span).with_message("in this record"));
+                }
+
+                if let Some(span) = pos_access.map(TermPos::into_opt).flatten() {
+                    labels.push(secondary(&span).with_message("accessed here"));
+                }
+
+                let mut diags = vec![Diagnostic::error()
+                    .with_message(format!(
+                        "missing definition for `{}`",
+                        field.unwrap_or(String::from("?"))
+                    ))
+                    .with_labels(labels)
+                    .with_notes(vec![])];
+
+                if let Some(label) = label {
+                    diags.push(blame_label_note(label));
+                }
+
+                diags
+            }
+            EvalError::TypeError(expd, msg, orig_pos_opt, t) => {
+                let label = format!(
+                    "this expression has type {}, but {} was expected",
+                    t.term
+                        .type_of()
+                        .unwrap_or_else(|| String::from("<unevaluated>")),
+                    expd,
+                );
+
+                let labels = match orig_pos_opt {
+                    TermPos::Original(pos) | TermPos::Inherited(pos) if orig_pos_opt != &t.pos => {
+                        vec![
+                            primary(pos).with_message(label),
+                            secondary_term(t, files).with_message("evaluated to this"),
+                        ]
+                    }
+                    _ => vec![primary_term(t, files).with_message(label)],
+                };
+
+                let mut diagnostic = Diagnostic::error()
+                    .with_message("type error")
+                    .with_labels(labels)
+                    .with_notes(vec![msg.clone()]);
+                note_external_sources(&mut diagnostic, &[t.pos], files, opts);
+
+                vec![diagnostic]
+            }
+            EvalError::BinaryTypeError {
+                op,
+                expected,
+                fst,
+                snd,
+                pos,
+            } => {
+                let mut labels = Vec::new();
+
+                if let Some(span) = pos.as_opt_ref() {
+                    labels.push(
+                        primary(span)
+                            .with_message(format!("`{}` expects two {} operands", op, expected)),
+                    );
+                }
+
+                labels.push(
+                    secondary_term(fst, files)
+                        .with_message(format!("this is {}", type_of_operand(fst))),
+                );
+                labels.push(
+                    secondary_term(snd, files)
+                        .with_message(format!("this is {}", type_of_operand(snd))),
+                );
+
+                let mut diagnostic = Diagnostic::error()
+                    .with_message(format!("type error in `{}`", op))
+                    .with_labels(labels);
+                note_external_sources(&mut diagnostic, &[fst.pos, snd.pos], files, opts);
+
+                vec![diagnostic]
+            }
+            EvalError::NotAFunc(t, arg, pos_opt) => vec![Diagnostic::error()
+                .with_message("not a function")
+                .with_labels(vec![
+                    primary_term(t, files)
+                        .with_message("this term is applied, but it is not a function"),
+                    secondary_alt(
+                        *pos_opt,
+                        format!(
+                            "({}) ({})",
+                            files.term_repr(t.as_ref()),
+                            files.term_repr(arg.as_ref())
+                        ),
+                        files,
+                    )
+                    .with_message("applied here"),
+                ])],
+            EvalError::FieldMissing(field, op, t, span_opt, index) => {
+                let mut labels = Vec::new();
+                let mut notes = Vec::new();
+                let available_fields = available_fields_notes(field, t);
+                let field = escape(field);
+
+                if let Some(span) = span_opt.into_opt() {
+                    labels.push(
+                        Label::primary(span.src_id, span.start.to_usize()..span.end.to_usize())
+                            .with_message(format!("this requires field {} to exist", field)),
+                    );
+                } else {
+                    notes.push(format!(
+                        "field {} was required by the operator {}",
+                        field, op
+                    ));
+                }
+
+                if let Some(span) = t.pos.as_opt_ref() {
+                    labels.push(
+                        secondary(span).with_message(format!("field {} is missing here", field)),
+                    );
+                }
+
+                if let Some(index) = index {
+                    notes.push(format!(
+                        "The record missing field {} is the element at index {} of the array.",
+                        field, index
+                    ));
+                }
+
+                notes.extend(available_fields);
+
+                vec![Diagnostic::error()
+                    .with_message("missing field")
+                    .with_labels(labels)
+                    .with_notes(notes)]
+            }
+            EvalError::NotARecord {
+                field,
+                value,
+                record_pos,
+                access_pos,
+            } => {
+                let field = escape(field);
+                let label = format!(
+                    "this expression has type {}, but a record was expected",
+                    value
+                        .term
+                        .type_of()
+                        .unwrap_or_else(|| String::from("<unevaluated>")),
+                );
+
+                let mut labels = match record_pos {
+                    TermPos::Original(pos) | TermPos::Inherited(pos)
+                        if value.pos.as_opt_ref() != Some(pos) =>
+                    {
+                        vec![
+                            primary(pos).with_message(label),
+                            secondary_term(value, files).with_message("evaluated to this"),
+                        ]
+                    }
+                    _ => vec![primary_term(value, files).with_message(label)],
+                };
+
+                if let Some(span) = access_pos.as_opt_ref() {
+                    labels.push(secondary(span).with_message("field access here"));
+                }
+
+                // Accessing a field of an array or of a string is a common mistake, which
+                // deserves a more specific suggestion.
+                let note = match value.as_ref() {
+                    Term::Array(..) => format!(
+                        "To access the field `{}` of each element of an array, use \
+                         `array.map (fun s => s.{}) <array>` or `array.pluck \"{}\" <array>`.",
+                        field, field, field
+                    ),
+                    Term::Str(..) => String::from(
+                        "Strings don't have fields. Use the functions of the `string` module \
+                         instead, such as `string.length`.",
+                    ),
+                    _ => String::from(
+                        "Only records have fields. Records can be combined with the merge \
+                         operator `&`, or updated with the functions of the `record` module, \
+                         such as `record.insert`.",
+                    ),
+                };
+
+                let mut diagnostic = Diagnostic::error()
+                    .with_message(format!("field `{}` accessed on a non-record value", field))
+                    .with_labels(labels)
+                    .with_notes(vec![note]);
+                note_external_sources(&mut diagnostic, &[value.pos], files, opts);
+
+                vec![diagnostic]
+            }
+            EvalError::NotEnoughArgs(count, op, span_opt) => {
+                let mut labels = Vec::new();
+                let mut notes = Vec::new();
+                let msg = format!(
+                    "{} expects {} arguments, but not enough were provided",
+                    op, count
+                );
+
+                if let Some(span) = span_opt.into_opt() {
+                    labels.push(
+                        Label::primary(span.src_id, span.start.to_usize()..span.end.to_usize())
+                            .with_message(msg),
+                    );
+                } else {
+                    notes.push(msg);
+                }
+
+                vec![Diagnostic::error()
+                    .with_message("not enough arguments")
+                    .with_labels(labels)
+                    .with_notes(notes)]
+            }
+            EvalError::MergeIncompatibleArgs(t1, t2, span_opt) => {
+                let origin1 = external_origin(t1, files);
+                let origin2 = external_origin(t2, files);
+
+                // A term coming from an external format has no span: its value is shown instead.
+                let mut labels = vec![
+                    primary_term(t1, files).with_message("cannot merge this expression"),
+                    primary_term(t2, files).with_message("with this expression"),
+                ];
+
+                if let TermPos::Original(span) | TermPos::Inherited(span) = span_opt {
+                    labels.push(secondary(span).with_message("merged here"));
+                }
+
+                let mut notes = Vec::new();
+
+                if let (true, true, Some(ty1), Some(ty2)) = (
+                    t1.as_ref().is_whnf(),
+                    t2.as_ref().is_whnf(),
+                    t1.as_ref().type_of(),
+                    t2.as_ref().type_of(),
+                ) {
+                    if ty1 != ty2 {
+                        notes.push(format!(
+                            "the left value is a {} and the right value is a {} \u{2014} merge \
+requires equal values of the same type",
+                            ty1, ty2
+                        ));
+
+                        for (side, origin) in [("left", origin1), ("right", origin2)] {
+                            if let Some((format, name)) = origin {
+                                notes.push(format!(
+                                    "the {} value was imported from the {} file `{}`: its \
+conversion is the likely source of the type mismatch",
+                                    side, format, name
+                                ));
+                            }
+                        }
+                    }
+                }
+
+                vec![Diagnostic::error()
+                    .with_message("non mergeable terms")
+                    .with_labels(labels)
+                    .with_notes(notes)]
+            }
+            EvalError::UnboundIdentifier(ident, span_opt, closest) => {
+                // Builtin contracts are inserted by the interpreter and defined by the standard
+                // library: they are only unbound if the standard library is disabled.
+                let notes = if ident.as_ref().starts_with('$') {
+                    vec![format!(
+                        "`{}` is a builtin contract, defined by the standard library, which is not loaded",
+                        ident
+                    )]
+                } else if let Some(closest) = closest {
+                    vec![format!("help: did you mean `{}`?", closest)]
+                } else {
+                    Vec::new()
+                };
+
+                vec![Diagnostic::error()
+                    .with_message("unbound identifier")
+                    .with_labels(vec![primary_alt(
+                        span_opt.into_opt(),
+                        ident.to_string(),
+                        files,
+                    )
+                    .with_message("this identifier is unbound")])
+                    .with_notes(notes)]
+            }
+            EvalError::NonExhaustiveMatch {
+                value,
+                handled,
+                pos,
+            } => {
+                let label = match value.as_ref() {
+                    Term::Enum(tag) => {
+                        format!("this evaluates to the tag `{}`, which is not handled", tag)
+                    }
+                    t => format!(
+                        "this evaluates to a value of type {}, but an enum tag was expected",
+                        t.type_of().unwrap_or_else(|| String::from("<unevaluated>"))
+                    ),
+                };
+
+                let mut labels = vec![primary_term(value, files).with_message(label)];
+                if let Some(span) = pos.as_opt_ref() {
+                    labels.push(secondary(span).with_message("in this match"));
+                }
+
+                let mut notes = Vec::new();
+                if handled.is_empty() {
+                    notes.push(String::from("the match doesn't handle any tag"));
+                } else {
+                    notes.push(format!(
+                        "the handled tags are {}",
+                        handled
+                            .iter()
+                            .map(|tag| format!("`{}`", tag))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ));
+                }
+                notes.push(String::from(
+                    "add the missing case, or a default case `_ => <exp>`",
+                ));
+
+                vec![Diagnostic::error()
+                    .with_message("non-exhaustive match")
+                    .with_labels(labels)
+                    .with_notes(notes)]
+            }
+            EvalError::InfiniteRecursion(call_stack, span_opt) => {
+                let (labels, notes) = match (span_opt.as_opt_ref(), recursion_cycle(call_stack)) {
+                    (Some(span), Some(cycle)) => {
+                        // Each reference of the cycle but the first one gives a hop, from the
+                        // identifier being evaluated to the identifier it refers to. The last hop
+                        // closes the cycle.
+                        let mut labels: Vec<Label<FileId>> = cycle
+                            .windows(2)
+                            .filter_map(|hop| {
+                                let (from, _) = &hop[0];
+                                let (to, pos) = &hop[1];
+                                let message = if from.label == to.label {
+                                    format!("`{}` depends on itself", from)
+                                } else {
+                                    format!("`{}` depends on `{}`", from, to)
+                                };
+                                Some(secondary(pos.as_opt_ref()?).with_message(message))
+                            })
+                            .collect();
+
+                        match labels.last_mut() {
+                            Some(last) => last.style = LabelStyle::Primary,
+                            None => labels.push(primary(span).with_message("recursive reference")),
+                        }
+
+                        let path = cycle
+                            .iter()
+                            .map(|(id, _)| format!("`{}`", id))
+                            .collect::<Vec<_>>()
+                            .join(" -> ");
+
+                        (labels, vec![format!("The cycle is {}.", path)])
+                    }
+                    (Some(span), None) => (
+                        vec![primary(span).with_message("recursive reference")],
+                        Vec::new(),
+                    ),
+                    (None, _) => (Vec::new(), Vec::new()),
+                };
+
+                vec![Diagnostic::error()
+                    .with_message("infinite recursion")
+                    .with_labels(labels)
+                    .with_notes(notes)]
+            }
+            EvalError::Cancelled(span_opt) => {
+                let labels = span_opt
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("evaluation stopped here")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message("evaluation cancelled")
+                    .with_labels(labels)
+                    .with_notes(vec![String::from(
+                        "the evaluation was interrupted before completion, for example because it \
+                        exceeded its time limit",
+                    )])]
+            }
+            EvalError::Other(msg, span_opt) => {
+                let labels = span_opt
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("here")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error().with_message(msg).with_labels(labels)]
+            }
+            EvalError::InternalError(msg, span_opt) => {
+                let labels = span_opt
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("here")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message(format!("internal error: {}", msg))
+                    .with_labels(labels)
+                    .with_notes(vec![String::from(INTERNAL_ERROR_MSG)])]
+            }
+            EvalError::SerializationError(err) => err.to_diagnostic(files, contract_id),
+            EvalError::ImportError(err) => err.to_diagnostic_with(files, contract_id, opts),
+            EvalError::DeserializationError(format, msg, span_opt, source, offset, first_opt) => {
+                let mut labels = Vec::new();
+
+                // As for imports (see `ParseError::from_serde_json`), the error is located inside
+                // the deserialized string, which is added as a snippet.
+                if let Some(offset) = offset {
+                    let file_id =
+                        files.add_snippet(&format!("<deserialized {}>", format), source.clone());
+                    let span = mk_char_span(file_id, source, *offset, 1);
+                    labels.push(primary(&span).with_message("here"));
+
+                    if let Some(first) = first_opt {
+                        let span = mk_span(file_id, first.start, first.end);
+                        labels.push(secondary(&span).with_message("first occurrence"));
+                    }
+                }
+
+                if let Some(span) = span_opt.as_opt_ref() {
+                    labels.push(if labels.is_empty() {
+                        primary(span).with_message("here")
+                    } else {
+                        secondary(span).with_message("deserialized here")
+                    });
+                }
+
+                vec![Diagnostic::error()
+                    .with_message(format!("{} parse error: {}", format, msg))
+                    .with_labels(labels)]
+            }
+        };
+
+        with_code(diags, self.code())
+    }
+}
+
+/// Beyond this number of tokens, the list of tokens expected by the parser is summarized by
+/// categories. See [`expected_tokens_note`].
+const MAX_EXPECTED_TOKENS: usize = 8;
+
+/// Maximum number of alternatives listed in the note of [`expected_tokens_note`], once the tokens
+/// have been summarized by categories. The remaining ones are only counted.
+const MAX_EXPECTED_ALTERNATIVES: usize = 12;
+
+/// Generate a note listing the tokens expected by the parser, or `None` if there are none (after
+/// filtering out internal tokens). The alternatives are de-duplicated and sorted. Long lists are
+/// collapsed: the tokens belonging to a category are replaced by the description of the category,
+/// such as "an expression", and the list is truncated after [`MAX_EXPECTED_ALTERNATIVES`] items.
+fn expected_tokens_note(expected: &[String]) -> Option<String> {
+    let tokens: Vec<ExpectedToken> = expected
+        .iter()
+        .filter_map(|terminal| lexer::expected_token(terminal))
+        .collect();
+
+    let mut reprs: Vec<String> = if tokens.len() > MAX_EXPECTED_TOKENS {
+        let mut categories: Vec<TokenCategory> =
+            tokens.iter().filter_map(|token| token.category).collect();
+        categories.sort();
+        categories.dedup();
+
+        categories
+            .into_iter()
+            .map(|category| String::from(category.description()))
+            .chain(
+                tokens
+                    .into_iter()
+                    .filter(|token| token.category.is_none())
+                    .map(|token| token.repr),
+            )
+            .collect()
+    } else {
+        tokens.into_iter().map(|token| token.repr).collect()
+    };
+
+    // Concrete tokens, sorted on their syntax without the surrounding backquotes, come before
+    // descriptions.
+    let sort_key = |repr: &String| (!repr.starts_with('`'), repr.trim_matches('`').to_owned());
+    reprs.sort_by_key(sort_key);
+    reprs.dedup();
+
+    if reprs.len() > MAX_EXPECTED_ALTERNATIVES {
+        let others = reprs.len() - MAX_EXPECTED_ALTERNATIVES;
+        reprs.truncate(MAX_EXPECTED_ALTERNATIVES);
+        reprs.push(format!("and {} more", others));
+    }
+
+    match reprs.as_slice() {
+        [] => None,
+        [repr] => Some(format!("expected {}", repr)),
+        _ => Some(format!("expected one of: {}", reprs.join(", "))),
+    }
+}
+
+impl ToDiagnostic<FileId> for ParseError {
+    fn to_diagnostic(
+        &self,
+        files: &mut dyn DiagnosticFiles,
+        _contract_id: Option<FileId>,
+    ) -> Vec<Diagnostic<FileId>> {
+        let diagnostic = match self {
+            ParseError::UnexpectedEOF(file_id, expected) => {
+                let end = files.source_span(*file_id).end();
+                Diagnostic::error()
+                    .with_message(format!(
+                        "unexpected end of file when parsing {}",
+                        files.name(*file_id).to_string_lossy()
+                    ))
+                    .with_labels(vec![primary(&RawSpan {
+                        start: end,
+                        end,
+                        src_id: *file_id,
+                    })])
+                    .with_notes(expected_tokens_note(expected).into_iter().collect())
+            }
+            ParseError::UnexpectedToken(span, expected) => Diagnostic::error()
+                .with_message("unexpected token")
+                .with_labels(vec![primary(span)])
+                .with_notes(expected_tokens_note(expected).into_iter().collect()),
+            ParseError::ExtraToken(span) => Diagnostic::error()
+                .with_message("superfluous unexpected token")
+                .with_labels(vec![primary(span)]),
+            ParseError::UnmatchedCloseBrace(span, opening) => {
+                let labels = match opening {
+                    Some(opening) => vec![
+                        primary(span),
+                        secondary(opening).with_message("possibly intended to close this `{`"),
+                    ],
+                    None => vec![primary(span).with_message("no matching `{` found")],
+                };
+
+                Diagnostic::error()
+                    .with_message("unmatched closing brace \'}\'")
+                    .with_labels(labels)
+            }
+            ParseError::InvalidEscapeSequence(span) => Diagnostic::error()
+                .with_message("invalid escape sequence")
+                .with_labels(vec![primary(span)]),
+            ParseError::InvalidAsciiEscapeCode(span) => Diagnostic::error()
+                .with_message("invalid ascii escape code")
+                .with_labels(vec![primary(span)]),
+            ParseError::ExternalFormatError(format, msg, span_opt, first_opt) => {
+                let mut labels: Vec<_> = span_opt.iter().map(primary).collect();
+
+                if let Some(first) = first_opt {
+                    labels.push(secondary(first).with_message("first occurrence"));
+                }
+
+                Diagnostic::error()
+                    .with_message(format!("{} parse error: {}", format, msg))
+                    .with_labels(labels)
+            }
+            ParseError::UnboundTypeVariables(idents, span) => Diagnostic::error()
+                .with_message(format!(
+                    "unbound type variable(s): {}",
+                    idents
+                        .iter()
+                        .map(|x| format!("`{}`", x))
+                        .collect::<Vec<_>>()
+                        .join(",")
+                ))
+                .with_labels(vec![primary(span)]),
+            ParseError::InvalidUniRecord(illegal_span, tail_span, span) => Diagnostic::error()
+                .with_message(format!("invalid record literal"))
+                .with_labels(vec![
+                    primary(span),
+                    secondary(illegal_span).with_message("can't use this record construct"),
+                    secondary(tail_span).with_message("in presence of a tail"),
+                ])
+                .with_notes(vec![
+                    String::from("Using a polymorphic tail in a record `{ ..; a}` requires the rest of the record to be only composed of type annotations, of the form `<field>: <type>`."),
+                    String::from("Value assignements, such as `<field> = <expr>`, metadata, etc. are forbidden."),
+                ]),
+            ParseError::DuplicateField(path, fst_span, snd_span) => Diagnostic::error()
+                .with_message(format!(
+                    "duplicate definition of the field `{}`",
+                    fmt_field_path(path)
+                ))
+                .with_labels(vec![
+                    primary(snd_span).with_message("redefined here"),
+                    secondary(fst_span).with_message("first defined here"),
+                ]),
+            ParseError::DuplicateOverride(path, fst_span, snd_span) => Diagnostic::error()
+                .with_message(format!("the field `{}` is overridden twice", path))
+                .with_labels(vec![
+                    primary(snd_span).with_message("overridden again here"),
+                    secondary(fst_span).with_message("first overridden here"),
+                ])
+                .with_notes(vec![String::from(
+                    "Each field can be overridden at most once on the command line.",
+                )]),
+            ParseError::TypeAliasArity(id, expected, given, span) => Diagnostic::error()
+                .with_message(format!("wrong number of arguments for the type alias `{}`", id))
+                .with_labels(vec![primary(span).with_message(format!(
+                    "`{}` expects {} argument(s), but {} were given",
+                    id, expected, given
+                ))]),
+        };
+
+        vec![diagnostic.with_code(self.code())]
+    }
+}
+
+impl ToDiagnostic<FileId> for TypecheckError {
+    fn to_diagnostic(
+        &self,
+        files: &mut dyn DiagnosticFiles,
+        contract_id: Option<FileId>,
+    ) -> Vec<Diagnostic<FileId>> {
+        fn mk_expr_label(span_opt: &TermPos) -> Vec<Label<FileId>> {
+            span_opt
+                .as_opt_ref()
+                .map(|span| vec![primary(span).with_message("this expression")])
+                .unwrap_or_default()
+        }
+
+        // Label the expression, plus the offending row if its position is known and distinct
+        // from the one of the expression.
+        fn mk_row_labels(span_opt: &TermPos, row_pos: &TermPos, msg: String) -> Vec<Label<FileId>> {
+            let mut labels = mk_expr_label(span_opt);
+
+            match row_pos.as_opt_ref() {
+                Some(span) if row_pos != span_opt => labels.push(secondary(span).with_message(msg)),
+                _ => (),
+            }
+
+            labels
+        }
+
+        let diags = match self {
+            TypecheckError::UnboundIdentifier(ident, pos_opt, closest) =>
+            // Use the same diagnostic as `EvalError::UnboundIdentifier` for consistency.
+                {
+                    EvalError::UnboundIdentifier(ident.clone(), *pos_opt, closest.clone())
+                        .to_diagnostic(files, contract_id)
+                }
+            TypecheckError::IllformedType(ty) => {
+                let ty_fmted = format!("{}", ty);
+                let len = ty_fmted.len();
+
+                let label = Label::new(LabelStyle::Secondary, files.add_snippet("", ty_fmted), 0..len)
+                    .with_message("ill-formed type");
+
+                vec![Diagnostic::error()
+                    .with_message("ill-formed type")
+                    .with_labels(vec![label])]
+            }
+            TypecheckError::MissingRow(ident, expd, actual, span_opt, row_pos) =>
+                vec![Diagnostic::error()
+                    .with_message(format!("type error: missing row `{}`", ident))
+                    .with_labels(mk_row_labels(span_opt, row_pos, format!("this record is missing the field `{}`", ident)))
+                    .with_notes(vec![
+                        format!("The type of the expression was expected to be `{}` which contains the field `{}`", expd, ident),
+                        format!("The type of the expression was inferred to be `{}`, which does not contain the field `{}`", actual, ident),
+                    ])]
+            ,
+            TypecheckError::MissingDynTail(expd, actual, span_opt) =>
+                vec![Diagnostic::error()
+                    .with_message(String::from("type error: missing dynamic tail `| Dyn`"))
+                    .with_labels(mk_expr_label(span_opt))
+                    .with_notes(vec![
+                        format!("The type of the expression was expected to be `{}` which contains the tail `| Dyn`", expd),
+                        format!("The type of the expression was inferred to be `{}`, which does not contain the tail `| Dyn`", actual),
+                    ])]
+            ,
+
+            TypecheckError::ExtraRow(ident, expd, actual, span_opt, row_pos) =>
+                vec![Diagnostic::error()
+                    .with_message(format!("type error: extra row `{}`", ident))
+                    .with_labels(mk_row_labels(span_opt, row_pos, format!("the extra field `{}` is defined here", ident)))
+                    .with_notes(vec![
+                        format!("The type of the expression was expected to be `{}`, which does not contain the field `{}`", expd, ident),
+                        format!("The type of the expression was inferred to be `{}`, which contains the extra field `{}`", actual, ident),
+                    ])]
+            ,
+            TypecheckError::ExtraDynTail(expd, actual, span_opt) =>
+                vec![Diagnostic::error()
+                    .with_message(String::from("type error: extra dynamic tail `| Dyn`"))
+                    .with_labels(mk_expr_label(span_opt))
+                    .with_notes(vec![
+                        format!("The type of the expression was expected to be `{}`, which does not contain the tail `| Dyn`", expd),
+                        format!("The type of the expression was inferred to be `{}`, which contains the extra tail `| Dyn`", actual),
+                    ])]
+            ,
+
+            TypecheckError::UnboundTypeVariable(ident, span_opt) =>
+                vec![Diagnostic::error()
+                    .with_message(String::from("unbound type variable"))
+                    .with_labels(vec![primary_alt(span_opt.into_opt(), ident.to_string(), files).with_message("this type variable is unbound")])
+                    .with_notes(vec![
+                        format!("Maybe you forgot to put a `forall {}.` somewhere in the enclosing type ?", ident),
+                    ])]
+            ,
+            TypecheckError::TypeMismatch(expd, actual, span_opt) => {
+                let path = ty_path::divergence(expd, actual);
+
+                // When the types share some structure, point at the first components which differ
+                // rather than only printing the whole types.
+                let (mut labels, notes) = if path.is_empty() {
+                    (Vec::new(), vec![
+                        format!("The type of the expression was expected to be `{}`", expd),
+                        format!("The type of the expression was inferred to be `{}`", actual),
+                        String::from("These types are not compatible"),
+                    ])
+                } else {
+                    let (expd_start, expd_end) = ty_path::span(path.iter().peekable(), expd);
+                    let (actual_start, actual_end) = ty_path::span(path.iter().peekable(), actual);
+
+                    (vec![
+                        Label::secondary(
+                            files.add_snippet("", format!("{}", expd)),
+                            expd_start..expd_end,
+                        )
+                            .with_message("this part of the expected type"),
+                        Label::secondary(
+                            files.add_snippet("", format!("{}", actual)),
+                            actual_start..actual_end,
+                        )
+                            .with_message("does not match this part of the inferred type"),
+                    ], vec![String::from("These types are not compatible")])
+                };
+                labels.extend(mk_expr_label(span_opt));
+
+                vec![
+                    Diagnostic::error()
+                        .with_message("incompatible types")
+                        .with_labels(labels)
+                        .with_notes(notes)]
+            }
+            TypecheckError::RowKindMismatch(ident, expd, actual, span_opt) => {
+                let notes = match (expd, actual) {
+                    (Some(_), None) => vec![
+                        format!("The row type of `{}` was expected to be `an enum type`, but was inferred to be `a record type`", ident),
+                        String::from("Enum row types and record row types are not compatible"),
+                    ],
+                    (None, Some(_)) => vec![
+                        format!("The row type of `{}` was expected to be `a record type`, but was inferred to be `an enum type`", ident),
+                        String::from("Enum row types and record row types are not compatible"),
+                    ],
+                    // The unifier only raises this error for rows of different kinds.
+                    _ => vec![String::from(INTERNAL_ERROR_MSG)],
+                };
+
+                vec![
+                    Diagnostic::error()
+                        .with_message("incompatible row kinds")
+                        .with_labels(mk_expr_label(span_opt))
+                        .with_notes(notes)]
+            }
+            TypecheckError::RowMismatch(ident, expd, actual, err_, span_opt) => {
+                // If the unification error is on a nested field, we will have a succession of
+                // `RowMismatch` errors wrapping the underlying error. In this case, instead of
+                // showing a cascade of similar error messages, we determine the full path of the
+                // nested field (e.g. `pkg.subpkg1.meta.url`) and only show once the row mismatch
+                // error followed by the underlying error.
+                let mut err = (*err_).clone();
+                let mut path = vec![ident.clone()];
+
+                while let TypecheckError::RowMismatch(id_next, _, _, next, _) = *err {
+                    path.push(id_next);
+                    err = next;
+                }
+
+                let path_str: Vec<String> = path.clone().into_iter().map(|ident| format!("{}", ident)).collect();
+                let field = path_str.join(".");
+
+                let note1 = match expd.row_find_path(path.as_slice()) {
+                    Some(ty) => format!("The type of the expression was expected to have the row `{}: {}`", field, ty),
+                    None => format!("The type of the expression was expected to be `{}`", expd)
+                };
+
+                let note2 = match actual.row_find_path(path.as_slice()) {
+                    Some(ty) => format!("The type of the expression was inferred to have the row `{}: {}`", field, ty),
+                    None => format!("The type of the expression was inferred to be `{}`", actual)
+                };
+
+                let mut diags = vec![Diagnostic::error()
+                    .with_message("incompatible rows declaration")
+                    .with_labels(mk_expr_label(span_opt))
+                    .with_notes(vec![
+                        note1,
+                        note2,
+                        format!("Could not match the two declaration of `{}`", field),
+                    ])
+                ];
+
+                // We generate a diagnostic for the underlying error, but append a prefix to the
+                // error message to make it clear that this is not a separated error but a more
+                // precise description of why the unification of a row failed.
+                diags.extend((*err).to_diagnostic(files, contract_id).into_iter()
+                    .map(|mut diag| {
+                        diag.message = format!("While typing field `{}`: {}", field, diag.message);
+                        diag
+                    }));
+                diags
+            }
+            TypecheckError::RowConflict(ident, conflict, _expd, _actual, span_opt) => {
+                // Enum rows don't have a type.
+                let row = match conflict {
+                    Some(ty) => format!("{}: {}", ident, ty),
+                    None => ident.to_string(),
+                };
+
+                vec![
+                    Diagnostic::error()
+                        .with_message("multiple rows declaration")
+                        .with_labels(mk_expr_label(span_opt))
+                        .with_notes(vec![
+                            format!("The type of the expression was inferred to have the row `{}`", row),
+                            format!("But this type appears inside another row type, which already has a declaration for the field `{}`", ident),
+                            String::from("A type cannot have two conflicting declaration for the same row"),
+                        ])]
+            }
+            TypecheckError::ArrowTypeMismatch(expd, actual, path, err, span_opt) => {
+                let (expd_start, expd_end) = ty_path::span(path.iter().peekable(), expd);
+                let (actual_start, actual_end) = ty_path::span(path.iter().peekable(), actual);
+
+                let mut labels = vec![
+                    Label::secondary(
+                        files.add_snippet("", format!("{}", expd)),
+                        expd_start..expd_end,
+                    )
+                        .with_message("this part of the expected type"),
+                    Label::secondary(
+                        files.add_snippet("", format!("{}", actual)),
+                        actual_start..actual_end,
+                    )
+                        .with_message("does not match this part of the inferred type"),
+                ];
+                labels.extend(mk_expr_label(span_opt));
+
+                let mut diags = vec![Diagnostic::error()
+                    .with_message("function types mismatch")
+                    .with_labels(labels)
+                    .with_notes(vec![
+                        format!("The type of the expression was expected to be `{}`", expd),
+                        format!("The type of the expression was inferred to be `{}`", actual),
+                        String::from("Could not match the two function types"),
+                    ])
+                ];
+
+                // We generate a diagnostic for the underlying error, but append a prefix to the
+                // error message to make it clear that this is not a separated error but a more
+                // precise description of why the unification of the row failed.
+                match err.as_ref() {
+                    // If the underlying error is a type mismatch, printing won't add any useful
+                    // information, so we just ignore it.
+                    TypecheckError::TypeMismatch(_, _, _) => (),
+                    err => {
+                        diags.extend(err.to_diagnostic(files, contract_id).into_iter()
+                            .map(|mut diag| {
+                                diag.message = format!("While matching function types: {}", diag.message);
+                                diag
+                            }));
+                    }
+                }
+
+                diags
+            }
+        };
+
+        with_code(diags, self.code())
+    }
+}
+
+impl ToDiagnostic<FileId> for ImportError {
+    fn to_diagnostic(
+        &self,
+        files: &mut dyn DiagnosticFiles,
+        contract_id: Option<FileId>,
+    ) -> Vec<Diagnostic<FileId>> {
+        self.to_diagnostic_with(files, contract_id, &DiagnosticOptions::default())
+    }
+
+    fn to_diagnostic_with(
+        &self,
+        files: &mut dyn DiagnosticFiles,
+        contract_id: Option<FileId>,
+        opts: &DiagnosticOptions,
+    ) -> Vec<Diagnostic<FileId>> {
+        let diags = match self {
+            ImportError::IOError(path, error, span_opt) => {
+                let labels = span_opt
+                    .as_opt_ref()
+                    .map(|span| vec![secondary(span).with_message("imported here")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message(format!("import of {} failed: {}", path, error))
+                    .with_labels(labels)]
+            }
+            ImportError::ParseErrors(error, span_opt) => {
+                imported_here(error.to_diagnostic_with(files, contract_id, opts), span_opt)
+            }
+            ImportError::InvalidStdlibModule(module, span_opt) => {
+                let labels = span_opt
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("expected a record")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message(format!("invalid standard library module {}", module))
+                    .with_labels(labels)
+                    .with_notes(vec![String::from(
+                        "each module of the standard library must be a record, whose fields are \
+                        added to the initial environment",
+                    )])]
+            }
+            ImportError::Transitive(err, span_opt) => {
+                imported_here(err.to_diagnostic_with(files, contract_id, opts), span_opt)
+            }
+            ImportError::ImportCycle(chain, span_opt) => {
+                let labels = span_opt
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("this import closes the cycle")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::error()
+                    .with_message("import cycle")
+                    .with_labels(labels)
+                    .with_notes(vec![chain.join(" imports ")])]
+            }
+        };
+
+        with_code(diags, self.code())
+    }
+}
+
+/// Label the position of an import in the first diagnostic of the error of the imported file. If
+/// there is no such diagnostic, which happens for an empty list of parse errors, a generic one is
+/// created instead.
+fn imported_here(
+    mut diagnostics: Vec<Diagnostic<FileId>>,
+    span_opt: &TermPos,
+) -> Vec<Diagnostic<FileId>> {
+    if diagnostics.is_empty() {
+        diagnostics.push(
+            Diagnostic::error()
+                .with_message("import failed")
+                .with_notes(vec![String::from(INTERNAL_ERROR_MSG)]),
+        );
+    }
+
+    if let Some(span) = span_opt.as_opt_ref() {
+        diagnostics[0]
+            .labels
+            .push(secondary(span).with_message("imported here"));
+    }
+
+    diagnostics
+}
+
+impl ToDiagnostic<FileId> for SerializationError {
+    fn to_diagnostic(
+        &self,
+        files: &mut dyn DiagnosticFiles,
+        _contract_id: Option<FileId>,
+    ) -> Vec<Diagnostic<FileId>> {
+        let diags = match self {
+            SerializationError::NotAString(rt) => vec![Diagnostic::error()
+                .with_message(format!(
+                    "raw export only supports `Str`, got {}",
+                    rt.as_ref()
+                        .type_of()
+                        .unwrap_or_else(|| String::from("<unevaluated>"))
+                ))
+                .with_labels(vec![primary_term(rt, files)])],
+            SerializationError::UnsupportedNull(format, rt, path) => vec![Diagnostic::error()
+                .with_message(format!("{} doesn't support null values", format))
+                .with_labels(vec![serialized_term(rt, files)])
+                .with_notes(field_path_note(path))],
+            SerializationError::NonSerializable(rt, path) => vec![Diagnostic::error()
+                .with_message("non serializable term")
+                .with_labels(vec![serialized_term(rt, files)])
+                .with_notes(field_path_note(path))],
+            SerializationError::UnsupportedFormat(format, rt) => vec![Diagnostic::error()
+                .with_message(format!(
+                    "{} requires a record at the top-level, got {}",
+                    format,
+                    rt.as_ref()
+                        .type_of()
+                        .unwrap_or_else(|| String::from("<unevaluated>"))
+                ))
+                .with_labels(vec![primary_term(rt, files)])
+                .with_notes(vec![format!(
+                    "a {} document is a table: wrap the value in a record, such as `{{value = ..}}`",
+                    format
+                )])],
+            SerializationError::MixedArray(format, rt, path) => {
+                let mut notes = field_path_note(path);
+                notes.push(format!(
+                    "an array of tables in {} only contains tables: wrap the other elements in records",
+                    format
+                ));
+
+                vec![Diagnostic::error()
+                    .with_message(format!(
+                        "{} doesn't support arrays mixing records and other values",
+                        format
+                    ))
+                    .with_labels(vec![serialized_term(rt, files)])
+                    .with_notes(notes)]
+            }
+            SerializationError::Other(msg) => vec![Diagnostic::error()
+                .with_message("error during serialization")
+                .with_notes(vec![msg.clone()])],
+        };
+
+        with_code(diags, self.code())
+    }
+}
+
+/// Label the position of a term which failed to serialize. A term whose position is inherited is
+/// the result of the evaluation of the labeled expression, rather than the expression itself.
+fn serialized_term(rt: &RichTerm, files: &mut dyn DiagnosticFiles) -> Label<FileId> {
+    let label = primary_term(rt, files);
+
+    if let TermPos::Inherited(_) = rt.pos {
+        let repr = files.term_repr(rt.as_ref());
+        label.with_message(format!("evaluated to `{}`", repr))
+    } else {
+        label
+    }
+}
+
+/// The note locating a term which failed to serialize inside the exported value, if it isn't the
+/// exported value itself.
+fn field_path_note(path: &label::field_path::Path) -> Vec<String> {
+    if path.is_empty() {
+        Vec::new()
+    } else {
+        vec![format!("at field path `{}`", path)]
+    }
+}
+
+impl ToDiagnostic<FileId> for IOError {
+    fn to_diagnostic(
+        &self,
+        _files: &mut dyn DiagnosticFiles,
+        _contract_id: Option<FileId>,
+    ) -> Vec<Diagnostic<FileId>> {
+        let diags = match self {
+            IOError::File {
+                path,
+                op,
+                kind,
+                msg,
+            } => {
+                let mut notes = Vec::new();
+                match kind {
+                    std::io::ErrorKind::NotFound => {
+                        if let Some(parent) = path.parent().filter(|p| !p.exists()) {
+                            notes.push(format!(
+                                "the directory `{}` doesn't exist",
+                                parent.display()
+                            ))
+                        }
+                    }
+                    std::io::ErrorKind::PermissionDenied if *op == FileOperation::Read => notes
+                        .push(String::from(
+                            "check that you have the permission to read the file",
+                        )),
+                    std::io::ErrorKind::PermissionDenied => notes.push(String::from(
+                        "check that you have the permission to write to the file and to its directory",
+                    )),
+                    _ => (),
+                };
+
+                vec![Diagnostic::error()
+                    .with_message(format!(
+                        "could not {} the file `{}`: {}",
+                        op,
+                        path.display(),
+                        msg
+                    ))
+                    .with_notes(notes)]
+            }
+            IOError::Other(msg) => vec![Diagnostic::error().with_message(msg.clone())],
+        };
+
+        with_code(diags, self.code())
+    }
+}
+
+impl ToDiagnostic<FileId> for ReplError {
+    fn to_diagnostic(
+        &self,
+        _files: &mut dyn DiagnosticFiles,
+        _contract_id: Option<FileId>,
+    ) -> Vec<Diagnostic<FileId>> {
+        let diags = match self {
+            ReplError::UnknownCommand(s) => vec![Diagnostic::error()
+                .with_message(format!("unknown command `{}`", s))
+                .with_notes(vec![String::from(
+                    "type `:?` or `:help` for a list of available commands.",
+                )])],
+            ReplError::MissingArg { cmd, msg_opt } => {
+                let mut notes = msg_opt
+                    .as_ref()
+                    .map(|msg| vec![msg.clone()])
+                    .unwrap_or_default();
+                notes.push(format!(
+                    "type `:? {}` or `:help {}` for more information.",
+                    cmd, cmd
+                ));
+
+                vec![Diagnostic::error()
+                    .with_message(format!("{}: missing argument", cmd))
+                    .with_notes(notes)]
+            }
+            ReplError::InvalidArg { cmd, arg } => vec![Diagnostic::error()
+                .with_message(format!("{}: invalid argument `{}`", cmd, arg))
+                .with_notes(vec![format!(
+                    "type `:? {}` or `:help {}` for more information.",
+                    cmd, cmd
+                )])],
+        };
+
+        with_code(diags, self.code())
+    }
+}
+
+impl ToDiagnostic<FileId> for Warning {
+    fn to_diagnostic(
+        &self,
+        _files: &mut dyn DiagnosticFiles,
+        _contract_id: Option<FileId>,
+    ) -> Vec<Diagnostic<FileId>> {
+        let diags = match self {
+            Warning::ShadowedStdlib(id) => {
+                let labels = id
+                    .pos
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("this binding")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::warning()
+                    .with_message(format!("`{}` shadows the standard library", id))
+                    .with_labels(labels)
+                    .with_notes(vec![
+                        format!(
+                            "`{}` refers to this binding instead of the standard library `{}` in its scope",
+                            id, id
+                        ),
+                        format!("rename the binding, for example to `my_{}`", id),
+                    ])]
+            }
+            Warning::UnusedBinding(id) => {
+                let labels = id
+                    .pos
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("this binding is never used")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::warning()
+                    .with_message(format!("unused binding `{}`", id))
+                    .with_labels(labels)
+                    .with_notes(vec![format!(
+                        "if this is intentional, start the name with an underscore, as in `_{}`",
+                        id
+                    )])]
+            }
+            Warning::OverrideBypass(pos) => {
+                let labels = pos
+                    .as_opt_ref()
+                    .map(|span| vec![primary(span).with_message("this default value is computed")])
+                    .unwrap_or_default();
+
+                vec![Diagnostic::warning()
+                    .with_message("overriding this default value drops its contracts")
+                    .with_labels(labels)
+                    .with_notes(vec![
+                        String::from("the contracts annotating the fields of a computed default value don't apply to a value overriding it"),
+                        String::from("annotate the field itself with a contract, which applies whatever the overriding value"),
+                    ])]
+            }
+        };
+
+        with_code(diags, self.code())
+    }
+}
+
+/// A self-contained, plain data representation of a diagnostic.
+///
+/// Contrary to codespan's [`Diagnostic`], a simple diagnostic doesn't refer to the file database:
+/// everything needed to display it is inlined, which makes it suitable for serialization, e.g. to
+/// be sent to a JavaScript frontend. See [`to_simple_diagnostics`].
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct SimpleDiagnostic {
+    #[serde(serialize_with = "serialize_severity")]
+    pub severity: Severity,
+    /// The error code, see [`crate::error_codes`].
+    pub code: Option<String>,
+    pub message: String,
+    pub notes: Vec<String>,
+    pub labels: Vec<SimpleLabel>,
+}
+
+/// A label of a [`SimpleDiagnostic`].
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct SimpleLabel {
+    /// The name of the annotated file, or `None` if the label annotates a snippet which was
+    /// generated during error reporting (see [`label_alt`](fn.label_alt.html)).
+    pub file_name: Option<String>,
+    /// The annotated byte range, relative to the file, or to `snippet` when the latter is set.
+    pub byte_range: std::ops::Range<usize>,
+    /// The 0-indexed `(line, column)` start and end positions of the annotated range. Columns
+    /// are counted in Unicode characters, not in bytes: use `byte_range` for byte offsets.
+    pub line_col_range: ((usize, usize), (usize, usize)),
+    pub label_text: String,
+    /// The generated snippet annotated by this label, if any.
+    pub snippet: Option<String>,
+    /// Whether this is a primary label, pointing to the cause of the diagnostic, or a secondary
+    /// one giving additional context.
+    pub primary: bool,
+}
+
+fn serialize_severity<S>(severity: &Severity, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: serde::Serializer,
+{
+    let repr = match severity {
+        Severity::Bug => "bug",
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+        Severity::Help => "help",
+    };
+    serializer.serialize_str(repr)
+}
+
+/// A file database made of the files of a program, which are borrowed, and of the snippets added
+/// while generating diagnostics, which are kept apart. Reporting an error thus neither mutates
+/// nor copies the files of the program.
+pub(crate) struct SnippetFiles<'a> {
+    files: &'a Files<String>,
+    /// The snippets, stored after placeholders standing for the files of `files`, such that their
+    /// ids don't clash with the ones of the files.
+    snippets: Files<String>,
+    /// The first id which doesn't belong to `files`.
+    end: FileId,
+    /// The id of the last snippet, or `end` if there is none.
+    last: FileId,
+    /// The sensitive values to hide from the snippets, if any.
+    redactions: Option<&'a Redactions>,
+}
+
+impl<'a> SnippetFiles<'a> {
+    /// Create a database of snippets on top of `files`. `last_file` is the id of the last file of
+    /// `files` (see [`DiagnosticOptions::last_file`]). If it is unknown, it is found out on a copy
+    /// of `files`.
+    pub(crate) fn new(files: &'a Files<String>, last_file: Option<FileId>) -> Self {
+        let mut snippets = Files::new();
+        // File ids are allocated in increasing order: the first placeholder whose id is not the
+        // one of a file of `files` marks the end of the files.
+        let mut placeholders = std::iter::repeat_with(|| snippets.add("", String::new()));
+        let end = match last_file {
+            Some(last_file) => placeholders.find(|id| *id > last_file),
+            None => {
+                let end = files.clone().add("", String::new());
+                placeholders.find(|id| *id >= end)
+            }
+        }
+        .unwrap();
+
+        SnippetFiles {
+            files,
+            snippets,
+            end,
+            last: end,
+            redactions: None,
+        }
+    }
+
+    /// Return the database holding a file or a snippet, if any.
+    fn database(&self, file_id: FileId) -> Option<&Files<String>> {
+        if file_id < self.end {
+            Some(self.files)
+        } else if file_id > self.end && file_id <= self.last {
+            Some(&self.snippets)
+        } else {
+            None
+        }
+    }
+
+    /// Return `true` if an id refers to a snippet.
+    pub(crate) fn is_snippet(&self, file_id: FileId) -> bool {
+        file_id > self.end && file_id <= self.last
+    }
+
+    /// The source of a file or of a snippet, or `None` if the id refers to neither of them.
+    pub(crate) fn source(&self, file_id: FileId) -> Option<&str> {
+        self.database(file_id)
+            .map(|files| files.source(file_id).as_str())
+    }
+
+    /// Move the labels pointing to a source which defines sensitive values to a copy of this
+    /// source, where the expressions of these values are replaced by
+    /// [`redact::SOURCE_PLACEHOLDER`]. This is done by the renderers which print the sources.
+    pub(crate) fn redact_sources(&mut self, diags: &mut [Diagnostic<FileId>]) {
+        let redactions = match self.redactions {
+            Some(redactions) => redactions,
+            None => return,
+        };
+
+        let mut spans: HashMap<FileId, Vec<Range<usize>>> = HashMap::new();
+        for span in redactions.spans() {
+            spans
+                .entry(span.src_id)
+                .or_default()
+                .push(span.start.to_usize()..span.end.to_usize());
+        }
+
+        for (file_id, mut ranges) in spans {
+            let used = diags
+                .iter()
+                .any(|diag| diag.labels.iter().any(|label| label.file_id == file_id));
+            let source = match self.database(file_id) {
+                Some(files) if used && !self.is_snippet(file_id) => files.source(file_id),
+                _ => continue,
+            };
+
+            // Merge the nested and overlapping ranges, such as the ones of a sensitive record
+            // holding a sensitive field.
+            ranges.sort_by_key(|range| range.start);
+            let mut merged: Vec<Range<usize>> = Vec::new();
+            for range in ranges {
+                match merged.last_mut() {
+                    Some(last) if range.start < last.end => last.end = last.end.max(range.end),
+                    _ => merged.push(range),
+                }
+            }
+
+            if merged.iter().any(|range| range.end > source.len()) {
+                continue;
+            }
+
+            let (redacted, map) = redact::redact_source(source, &merged);
+            let name = DiagnosticFiles::name(self, file_id)
+                .to_string_lossy()
+                .into_owned();
+            let redacted_id = self.add_snippet(&name, redacted);
+
+            for label in diags
+                .iter_mut()
+                .flat_map(|diag| diag.labels.iter_mut())
+                .filter(|label| label.file_id == file_id)
+            {
+                label.file_id = redacted_id;
+                label.range = map(label.range.clone());
+            }
+        }
+    }
+}
+
+impl DiagnosticFiles for SnippetFiles<'_> {
+    fn name(&self, file_id: FileId) -> &OsStr {
+        self.database(file_id)
+            .map(|files| files.name(file_id))
+            .unwrap_or_default()
+    }
+
+    fn source_span(&self, file_id: FileId) -> Span {
+        self.database(file_id)
+            .map(|files| files.source_span(file_id))
+            .unwrap_or_default()
+    }
+
+    fn location(&self, file_id: FileId, byte_index: usize) -> Option<(usize, usize)> {
+        self.database(file_id)
+            .and_then(|files| DiagnosticFiles::location(files, file_id, byte_index))
+    }
+
+    fn add_snippet(&mut self, name: &str, source: String) -> FileId {
+        self.last = self.snippets.add(name, source);
+        self.last
+    }
+
+    fn term_repr(&self, term: &Term) -> String {
+        match self.redactions {
+            Some(redactions) => redactions.repr(term),
+            None => term.shallow_repr(),
+        }
+    }
+}
+
+impl<'a> codespan_reporting::files::Files<'a> for SnippetFiles<'_> {
+    type FileId = FileId;
+    type Name = String;
+    type Source = &'a str;
+
+    fn name(&'a self, id: FileId) -> Result<String, codespan_reporting::files::Error> {
+        let files = self
+            .database(id)
+            .ok_or(codespan_reporting::files::Error::FileMissing)?;
+        codespan_reporting::files::Files::name(files, id)
+    }
+
+    fn source(&'a self, id: FileId) -> Result<&'a str, codespan_reporting::files::Error> {
+        self.source(id)
+            .ok_or(codespan_reporting::files::Error::FileMissing)
+    }
+
+    fn line_index(
+        &'a self,
+        id: FileId,
+        byte_index: usize,
+    ) -> Result<usize, codespan_reporting::files::Error> {
+        let files = self
+            .database(id)
+            .ok_or(codespan_reporting::files::Error::FileMissing)?;
+        codespan_reporting::files::Files::line_index(files, id, byte_index)
+    }
+
+    fn line_range(
+        &'a self,
+        id: FileId,
+        line_index: usize,
+    ) -> Result<Range<usize>, codespan_reporting::files::Error> {
+        let files = self
+            .database(id)
+            .ok_or(codespan_reporting::files::Error::FileMissing)?;
+        codespan_reporting::files::Files::line_range(files, id, line_index)
+    }
+}
+
+/// Generate the diagnostics of an error without mutating nor copying `files`, as done by the
+/// renderers: the snippets are added to a separate database, the labels located in a bundle are
+/// moved to their inlined file, the labels which can't be rendered are dropped, and the policy of
+/// `opts` is applied. The sensitive values of `opts` are hidden from the snippets, the messages
+/// and the notes. Return the diagnostics together with the database they refer to.
+pub(crate) fn generate_diagnostics<'a, E>(
+    error: &E,
+    files: &'a Files<String>,
+    contract_id: Option<FileId>,
+    opts: &'a DiagnosticOptions,
+) -> (SnippetFiles<'a>, Vec<Diagnostic<FileId>>)
+where
+    E: ToDiagnostic<FileId>,
+{
+    let mut files = SnippetFiles::new(files, opts.last_file);
+    if !opts.redactions.is_empty() {
+        files.redactions = Some(&opts.redactions);
+    }
+
+    let mut diags = error.to_diagnostic_with(&mut files, contract_id, opts);
+    opts.map_sources(&mut diags);
+    drop_invalid_labels(&files, &mut diags);
+
+    if !opts.redactions.is_empty() {
+        for diag in diags.iter_mut() {
+            diag.message = opts.redactions.redact(&diag.message);
+            for note in diag.notes.iter_mut() {
+                *note = opts.redactions.redact(note);
+            }
+            for label in diag.labels.iter_mut() {
+                label.message = opts.redactions.redact(&label.message);
+            }
+        }
+    }
+
+    (files, opts.apply_policy(diags))
+}
+
+/// Convert an error to a list of [`SimpleDiagnostic`]s without mutating the file database.
+///
+/// The diagnostics are generated by [`ToDiagnostic::to_diagnostic`]. The snippets that it adds
+/// (see [`label_alt`](fn.label_alt.html)) are kept in a separate database, and inlined in the
+/// corresponding labels instead of being referred to by a `FileId`.
+pub fn to_simple_diagnostics<E>(
+    error: &E,
+    files: &Files<String>,
+    contract_id: Option<FileId>,
+) -> Vec<SimpleDiagnostic>
+where
+    E: ToDiagnostic<FileId>,
+{
+    to_simple_diagnostics_with(error, files, contract_id, &DiagnosticOptions::default())
+}
+
+/// Same as [`to_simple_diagnostics`], but with explicit [`DiagnosticOptions`], including the
+/// reporting policy.
+pub fn to_simple_diagnostics_with<E>(
+    error: &E,
+    files: &Files<String>,
+    contract_id: Option<FileId>,
+    opts: &DiagnosticOptions,
+) -> Vec<SimpleDiagnostic>
+where
+    E: ToDiagnostic<FileId>,
+{
+    let (files, diags) = generate_diagnostics(error, files, contract_id, opts);
+
+    diags
+        .into_iter()
+        .map(|diag| SimpleDiagnostic {
+            severity: diag.severity,
+            code: diag.code,
+            message: diag.message,
+            notes: diag.notes,
+            labels: diag
+                .labels
+                .into_iter()
+                .map(|label| {
+                    // The labels which don't belong to any database have been dropped.
+                    let database = files.database(label.file_id).unwrap();
+                    let location = |index: usize| {
+                        database
+                            .location(label.file_id, index as u32)
+                            .map(|loc| (loc.line.to_usize(), loc.column.to_usize()))
+                            .unwrap_or((0, 0))
+                    };
+                    let line_col_range = (location(label.range.start), location(label.range.end));
+
+                    let (file_name, snippet) = if files.is_snippet(label.file_id) {
+                        (None, Some(database.source(label.file_id).clone()))
+                    } else {
+                        let name = database.name(label.file_id).to_string_lossy().into_owned();
+                        (Some(name), None)
+                    };
+
+                    SimpleLabel {
+                        file_name,
+                        byte_range: label.range,
+                        line_col_range,
+                        label_text: label.message,
+                        snippet,
+                        primary: label.style == LabelStyle::Primary,
+                    }
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Remove the labels which can't be rendered: the ones referring neither to a file nor to a
+/// snippet of `files`, or to a range outside of their source, which happens if the source has been
+/// updated since the error was produced. Rendering such labels panics or fails. A note is added to
+/// the diagnostics which lost labels.
+pub(crate) fn drop_invalid_labels(files: &SnippetFiles, diags: &mut [Diagnostic<FileId>]) {
+    for diag in diags.iter_mut() {
+        let count = diag.labels.len();
+
+        diag.labels
+            .retain(|label| match files.source(label.file_id) {
+                Some(source) => {
+                    label.range.start <= label.range.end
+                        && label.range.end <= source.len()
+                        && source.is_char_boundary(label.range.start)
+                        && source.is_char_boundary(label.range.end)
+                }
+                None => false,
+            });
+
+        if diag.labels.len() < count {
+            diag.notes.push(String::from(
+                "Some locations of this error are not shown, as they are outside of their source",
+            ));
+            diag.notes.push(String::from(INTERNAL_ERROR_MSG));
+        }
+    }
+}
+
+/// Render a list of diagnostics to a string, as they would be printed on a terminal.
+///
+/// ANSI color codes are emitted unless `color` is [`ColorChoice::Never`]. Contrary to a terminal,
+/// a string has no way of telling whether colors are supported: [`ColorChoice::Auto`] thus uses
+/// colors.
+pub fn render_diagnostics(
+    files: &Files<String>,
+    diags: &[Diagnostic<FileId>],
+    color: ColorChoice,
+) -> String {
+    let files = SnippetFiles::new(files, None);
+    let mut diags = diags.to_vec();
+    drop_invalid_labels(&files, &mut diags);
+
+    render_snippet_diagnostics(&files, &diags, color)
+}
+
+/// Same as [`render_diagnostics`], for diagnostics produced by [`generate_diagnostics`], whose
+/// invalid labels have already been dropped.
+pub(crate) fn render_snippet_diagnostics(
+    files: &SnippetFiles,
+    diags: &[Diagnostic<FileId>],
+    color: ColorChoice,
+) -> String {
+    let config = codespan_reporting::term::Config::default();
+    let mut buffer = match color {
+        ColorChoice::Never => Buffer::no_color(),
+        _ => Buffer::ansi(),
+    };
+
+    diags
+        .iter()
+        .try_for_each(|d| codespan_reporting::term::emit(&mut buffer, &config, files, d))
+        .expect("error::render_diagnostics(): could not render a diagnostic");
+
+    String::from_utf8_lossy(buffer.as_slice()).into_owned()
+}
@@ -0,0 +1,257 @@
+//! Export of diagnostics in the [SARIF](https://sarifweb.azurewebsites.net/) format.
+//!
+//! SARIF (Static Analysis Results Interchange Format) is a JSON format consumed by CI systems and
+//! code hosting platforms to annotate the lines of a pull request. The diagnostics of an error are
+//! converted to a SARIF 2.1.0 log, with one run and one result per diagnostic:
+//!
+//! - The rule of a result is the error code of the diagnostic. The rules of the run are described
+//!   by the explanations of the [registry](../../error_codes/index.html).
+//! - Primary labels are the locations of the result, and secondary labels its related locations.
+//!   A diagnostic without any label, such as the one of an error without a position, gives a
+//!   result without locations.
+//! - A label annotating a snippet generated during error reporting (see
+//!   [`SimpleLabel::snippet`](../struct.SimpleLabel.html#structfield.snippet)) doesn't refer to any
+//!   file: the snippet is reported as the context region of the location instead.
+//!
+//! The conversion works on [`SimpleDiagnostic`]s, such that the output goes through the same
+//! redaction and reporting policy as the other formats (see
+//! [`report_simple`](../../program/fn.report_simple.html)). [`errors_to_sarif`] converts errors
+//! directly, for tools which only have a file database at hand.
+use super::{to_simple_diagnostics, Error, SimpleDiagnostic, SimpleLabel};
+use crate::error_codes;
+use codespan::Files;
+use codespan_reporting::diagnostic::Severity;
+use serde_json::{json, Map, Value};
+
+/// The version of the SARIF specification implemented.
+pub const SARIF_VERSION: &str = "2.1.0";
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Convert diagnostics to a SARIF log.
+pub fn to_sarif(diagnostics: &[SimpleDiagnostic]) -> Value {
+    let mut codes: Vec<&str> = diagnostics
+        .iter()
+        .filter_map(|diag| diag.code.as_deref())
+        .collect();
+    codes.sort_unstable();
+    codes.dedup();
+
+    let rules: Vec<Value> = codes
+        .into_iter()
+        .map(|code| {
+            let mut rule = Map::new();
+            rule.insert(String::from("id"), json!(code));
+
+            if let Some(explanation) = error_codes::explain(code) {
+                let summary = explanation.lines().next().unwrap_or_default();
+                rule.insert(String::from("shortDescription"), json!({ "text": summary }));
+                rule.insert(
+                    String::from("fullDescription"),
+                    json!({ "text": explanation }),
+                );
+            }
+
+            Value::Object(rule)
+        })
+        .collect();
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": SARIF_VERSION,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "nickel",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://nickel-lang.org",
+                    "rules": rules,
+                }
+            },
+            // The columns of simple diagnostics are counted in characters.
+            "columnKind": "unicodeCodePoints",
+            "results": diagnostics.iter().map(result).collect::<Vec<_>>(),
+        }]
+    })
+}
+
+/// Convert a batch of errors to a SARIF log, with one result per diagnostic. Contrary to
+/// [`Program::report_sarif_all`](../../program/struct.Program.html#method.report_sarif_all), no
+/// reporting policy is applied and sensitive values are not redacted.
+pub fn errors_to_sarif(errors: &[Error], files: &Files<String>) -> Value {
+    let diagnostics: Vec<SimpleDiagnostic> = errors
+        .iter()
+        .flat_map(|error| to_simple_diagnostics(error, files, None))
+        .collect();
+    to_sarif(&diagnostics)
+}
+
+/// Convert a diagnostic to a SARIF result.
+fn result(diag: &SimpleDiagnostic) -> Value {
+    let mut text = diag.message.clone();
+    for note in diag.notes.iter() {
+        text.push('\n');
+        text.push_str(note);
+    }
+
+    let (primary, secondary): (Vec<&SimpleLabel>, Vec<&SimpleLabel>) =
+        diag.labels.iter().partition(|label| label.primary);
+
+    let mut result = Map::new();
+    if let Some(code) = &diag.code {
+        result.insert(String::from("ruleId"), json!(code));
+    }
+    result.insert(String::from("level"), json!(level(diag.severity)));
+    result.insert(String::from("message"), json!({ "text": text }));
+    if !primary.is_empty() {
+        result.insert(
+            String::from("locations"),
+            primary.into_iter().map(location).collect(),
+        );
+    }
+    if !secondary.is_empty() {
+        result.insert(
+            String::from("relatedLocations"),
+            secondary.into_iter().map(location).collect(),
+        );
+    }
+
+    Value::Object(result)
+}
+
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Bug | Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note | Severity::Help => "note",
+    }
+}
+
+/// Convert a label to a SARIF location. Contrary to simple labels, lines and columns are 1-based
+/// in SARIF.
+fn location(label: &SimpleLabel) -> Value {
+    let ((start_line, start_col), (end_line, end_col)) = label.line_col_range;
+
+    let physical_location = match (&label.file_name, &label.snippet) {
+        (Some(file_name), None) => json!({
+            "artifactLocation": { "uri": file_name },
+            "region": {
+                "startLine": start_line + 1,
+                "startColumn": start_col + 1,
+                "endLine": end_line + 1,
+                "endColumn": end_col + 1,
+                "byteOffset": label.byte_range.start,
+                "byteLength": label.byte_range.len(),
+            }
+        }),
+        (_, snippet) => json!({
+            "contextRegion": {
+                "snippet": { "text": snippet.as_deref().unwrap_or_default() }
+            }
+        }),
+    };
+
+    let mut location = Map::new();
+    location.insert(String::from("physicalLocation"), physical_location);
+    if !label.label_text.is_empty() {
+        location.insert(String::from("message"), json!({ "text": label.label_text }));
+    }
+
+    Value::Object(location)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{EvalError, IOError};
+    use crate::identifier::Ident;
+    use crate::position::{RawSpan, TermPos};
+    use codespan::Files;
+
+    #[test]
+    fn file_location() {
+        let mut files = Files::new();
+        let src_id = files.add("main.ncl", String::from("let x = 1 in\ny + x"));
+        let pos = TermPos::Original(RawSpan {
+            src_id,
+            start: 13.into(),
+            end: 14.into(),
+        });
+
+        let diags = to_simple_diagnostics(
+            &EvalError::UnboundIdentifier(Ident::from("y"), pos, None),
+            &files,
+            None,
+        );
+        let log = to_sarif(&diags);
+
+        assert_eq!(log["version"], "2.1.0");
+        let run = &log["runs"][0];
+        assert_eq!(run["tool"]["driver"]["rules"][0]["id"], "E008");
+
+        let result = &run["results"][0];
+        assert_eq!(result["ruleId"], "E008");
+        assert_eq!(result["level"], "error");
+        assert_eq!(result["message"]["text"], "unbound identifier");
+
+        let location = &result["locations"][0];
+        assert_eq!(location["message"]["text"], "this identifier is unbound");
+        assert_eq!(
+            location["physicalLocation"],
+            json!({
+                "artifactLocation": { "uri": "main.ncl" },
+                "region": {
+                    "startLine": 2,
+                    "startColumn": 1,
+                    "endLine": 2,
+                    "endColumn": 2,
+                    "byteOffset": 13,
+                    "byteLength": 1,
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn snippet_location() {
+        let files = Files::new();
+        let diags = to_simple_diagnostics(
+            &EvalError::UnboundIdentifier(Ident::from("y"), TermPos::None, None),
+            &files,
+            None,
+        );
+        let log = to_sarif(&diags);
+
+        let location = &log["runs"][0]["results"][0]["locations"][0];
+        assert_eq!(
+            location["physicalLocation"],
+            json!({ "contextRegion": { "snippet": { "text": "y" } } })
+        );
+    }
+
+    #[test]
+    fn batch_of_errors() {
+        let mut files = Files::new();
+        let src_id = files.add("main.ncl", String::from("y"));
+        let pos = TermPos::Original(RawSpan {
+            src_id,
+            start: 0.into(),
+            end: 1.into(),
+        });
+
+        let errors = vec![
+            Error::from(EvalError::UnboundIdentifier(Ident::from("y"), pos, None)),
+            Error::from(IOError::Other(String::from("disk full"))),
+        ];
+        let log = errors_to_sarif(&errors, &files);
+
+        let results = log["runs"][0]["results"].as_array().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0]["ruleId"], "E008");
+        assert_eq!(
+            results[0]["locations"][0]["physicalLocation"]["artifactLocation"]["uri"],
+            "main.ncl"
+        );
+        assert!(results[1].get("locations").is_none());
+    }
+}
@@ -0,0 +1,100 @@
+//! Suggestions of close names, for the errors caused by a typo in an identifier or a field name.
+use crate::identifier::Ident;
+
+/// The [optimal string alignment
+/// distance](https://en.wikipedia.org/wiki/Damerau%E2%80%93Levenshtein_distance) between two
+/// strings, that is the minimal number of insertions, deletions, substitutions and transpositions
+/// of adjacent characters turning one into the other, where no substring is edited twice.
+/// Transpositions are common typos, such as `prot` for `port`.
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // The distances between the prefixes of `a` of length `i - 2`, `i - 1` and `i`, and each
+    // prefix of `b`.
+    let mut before_prev: Vec<usize> = Vec::new();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+
+    for i in 1..=a.len() {
+        let mut row = vec![i; b.len() + 1];
+
+        for j in 1..=b.len() {
+            let substitution = prev[j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            row[j] = substitution.min(row[j - 1] + 1).min(prev[j] + 1);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                row[j] = row[j].min(before_prev[j - 2] + 1);
+            }
+        }
+
+        before_prev = std::mem::replace(&mut prev, row);
+    }
+
+    prev[b.len()]
+}
+
+/// Return the candidate closest to `name`, if it is close enough to be the name that was meant.
+///
+/// A candidate is only suggested if its distance to `name` is at most a third of the length of
+/// `name`, such that short names, for which any other name is at a small distance, get no
+/// suggestion. Ties are broken by the alphabetical order, to not depend on the order of the
+/// candidates.
+pub fn closest<'a, I>(name: &str, candidates: I) -> Option<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let threshold = name.chars().count() / 3;
+
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (distance(name, candidate), candidate))
+        .filter(|(dist, _)| *dist <= threshold)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// Return the identifier of `candidates` closest to `id`, as for [`closest`]. Identifiers
+/// generated by program transformations are never suggested.
+pub fn closest_ident<'a, I>(id: &Ident, candidates: I) -> Option<Ident>
+where
+    I: IntoIterator<Item = &'a Ident>,
+{
+    closest(
+        id.as_ref(),
+        candidates
+            .into_iter()
+            .filter(|candidate| !candidate.is_generated())
+            .map(|candidate| candidate.as_ref()),
+    )
+    .map(Ident::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levenshtein() {
+        assert_eq!(distance("", ""), 0);
+        assert_eq!(distance("abc", ""), 3);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("length", "lenght"), 1);
+        assert_eq!(distance("ca", "abc"), 3);
+        assert_eq!(distance("héllo", "hello"), 1);
+    }
+
+    #[test]
+    fn closest_candidate() {
+        let candidates = ["foo_bar", "foo_baz", "port", "host"];
+
+        assert_eq!(closest("foo_bra", candidates), Some("foo_bar"));
+        assert_eq!(closest("foo_ba", candidates), Some("foo_bar"));
+        assert_eq!(closest("prot", candidates), Some("port"));
+        assert_eq!(closest("pots", candidates), None);
+        assert_eq!(closest("porti", candidates), Some("port"));
+        // Short names get no suggestion.
+        assert_eq!(closest("x", ["y"]), None);
+        // The name itself is not suggested.
+        assert_eq!(closest("host", candidates), None);
+    }
+}
@@ -0,0 +1,961 @@
+//! Registry of error codes.
+//!
+//! Each kind of error is identified by a stable code of the form `EXXX`, which is shown next to
+//! the message of the corresponding diagnostic (see the `code()` methods of the error types of
+//! [`crate::error`]). The registry maps each code to a long-form explanation, which is printed by
+//! `nickel explain <code>` or by the `:explain` command of the REPL.
+//!
+//! Codes are never reused: when an error kind is removed, its code must stay retired.
+
+/// The registry of error codes, together with their explanation.
+pub const REGISTRY: &[(&str, &str)] = &[
+    (
+        "E001",
+        r#"A contract was broken.
+
+Contracts are checked at runtime, when the value they are attached to is evaluated. The
+diagnostic points to the contract which failed and to the value which was blamed.
+
+Erroneous code example:
+
+    let port | Num = "80" in port + 1
+
+If the contract is a function type, the blame may be on the caller, which passed an argument
+violating the domain, or on the function itself, which returned a value violating the codomain.
+Fix the value, or relax the contract if it is too strict."#,
+    ),
+    (
+        "E002",
+        r#"A field without a definition was accessed.
+
+A field can be declared with metadata only, such as a contract or documentation, and receive
+its value later through merging. If no value is ever merged in, evaluating the field fails.
+
+Erroneous code example:
+
+    let config = {port | Num} in config.port
+
+Provide a definition, either directly or by merging the record with another one that defines
+the field: `config & {port = 80}`."#,
+    ),
+    (
+        "E003",
+        r#"A dynamic type error occurred.
+
+A primitive operation was applied to a value of the wrong type, for example an arithmetic
+operation on a string.
+
+Erroneous code example:
+
+    1 + "1"
+
+Convert the value beforehand, e.g. with `string.to_num`, or use a static type annotation to
+catch such errors before evaluation."#,
+    ),
+    (
+        "E004",
+        r#"A value which is not a function was applied to an argument.
+
+Erroneous code example:
+
+    let x = 1 in x 2
+
+This often happens when an argument is missing parentheses, such as in `f g x`, which applies
+`f` to both `g` and `x`, when `f (g x)` was meant."#,
+    ),
+    (
+        "E005",
+        r#"A field was accessed on a record which doesn't define it.
+
+Erroneous code example:
+
+    {foo = 1}.bar
+
+The diagnostic lists the fields the record does define, and suggests the closest one when the
+missing field looks like a typo. Check the spelling of the field, or use `record.has_field` to
+handle the case where the field is absent."#,
+    ),
+    (
+        "E006",
+        r#"A primitive operation was not given enough arguments.
+
+This is usually the sign of a partial application of a primitive operation where a function
+was expected to be fully applied. Check the number of arguments passed to the operation."#,
+    ),
+    (
+        "E007",
+        r#"Two values which cannot be merged were merged together.
+
+Merging records combines their fields recursively, but two values which are not records can
+only be merged if they are equal.
+
+Erroneous code example:
+
+    {port = 80} & {port = 8080}
+
+Make one of the values a default value, with `port | default = 80`, such that the other one
+overrides it. A frequent source of this error is a value coming from an imported file with a
+different type than expected, such as a number written as a string in YAML."#,
+    ),
+    (
+        "E008",
+        r#"An identifier was used without being bound.
+
+Erroneous code example:
+
+    let x = 1 in y + 1
+
+Check the spelling of the identifier, and that it is in scope: variables bound by a `let` are
+only visible in its body, and fields of a record are only visible from inside the record. When
+an identifier in scope has a close spelling, the diagnostic suggests it."#,
+    ),
+    (
+        "E009",
+        r#"A value depends on itself.
+
+Erroneous code example:
+
+    {x = x + 1}.x
+
+A recursive definition must eventually produce a value without requiring its own value, for
+example through a function with a base case. When the value is part of a cycle of fields or
+variables, such as `{a = b, b = a}`, the diagnostic labels each hop of the cycle."#,
+    ),
+    (
+        "E010",
+        r#"Deserialization of a string failed.
+
+The string passed to `builtin.deserialize` is not a valid document in the requested format.
+The diagnostic includes the error reported by the underlying parser."#,
+    ),
+    (
+        "E011",
+        r#"An internal error occurred.
+
+This is a bug of the Nickel interpreter, and not a problem with your program. Please report it,
+together with the program which triggered it."#,
+    ),
+    (
+        "E012",
+        r#"An evaluation error occurred.
+
+This error doesn't belong to a more specific category. The message of the diagnostic describes
+what went wrong."#,
+    ),
+    (
+        "E013",
+        r#"A type is ill-formed.
+
+A type which is not a row was found in a row position, for example as the tail of a record or
+enum type. Only type variables and `Dyn` can be used as a tail."#,
+    ),
+    (
+        "E014",
+        r#"A record or an enum type is missing a row.
+
+The type of an expression was expected to contain a field or an enum tag, but the inferred or
+annotated type doesn't.
+
+Erroneous code example:
+
+    let f : {foo : Num} -> Num = fun r => r.foo in f {bar = 1} : Num
+
+Add the missing field or tag, or make the expected type polymorphic in the remaining rows:
+`forall a. {foo : Num; a} -> Num`."#,
+    ),
+    (
+        "E015",
+        r#"A record type is missing a dynamic tail.
+
+The type of an expression was expected to be a record type with a dynamic tail, `{foo : Num;
+Dyn}`, which accepts any additional field, but the inferred or annotated type is a closed
+record type."#,
+    ),
+    (
+        "E016",
+        r#"A record or an enum type has an unexpected row.
+
+The inferred or annotated type of an expression contains a field or an enum tag which doesn't
+appear in the expected type.
+
+Erroneous code example:
+
+    {foo = 1, bar = 2} : {foo : Num}
+
+Remove the extra field or tag, or add it to the expected type."#,
+    ),
+    (
+        "E017",
+        r#"A record type has an unexpected dynamic tail.
+
+The inferred or annotated type of an expression is a record type with a dynamic tail, but the
+expected type is a closed record type, which can't accept arbitrary additional fields."#,
+    ),
+    (
+        "E018",
+        r#"A type variable was used without being bound.
+
+Type variables must be introduced by a `forall` before they can be used.
+
+Erroneous code example:
+
+    let f : forall a. {foo : Num; b} -> Num = fun r => r.foo in f
+
+Introduce the variable: `forall b. {foo : Num; b} -> Num`."#,
+    ),
+    (
+        "E019",
+        r#"The type of an expression doesn't match the expected type.
+
+Erroneous code example:
+
+    let x : Num = "a" in x
+
+Either the expression or the expected type is wrong. Remember that inside a statically typed
+block, values coming from untyped code have type `Dyn` and must be given a more precise type
+with a contract annotation, such as `(x | Num)`."#,
+    ),
+    (
+        "E020",
+        r#"A row was used both as a record field and as an enum tag.
+
+Enum row types and record row types are not compatible: the same row type can't be unified
+with both a record type and an enum type."#,
+    ),
+    (
+        "E021",
+        r#"Two incompatible types were inferred for the same row.
+
+The types of a field or of an enum tag differ between the expected and the inferred type of
+an expression. The diagnostic is followed by the underlying error on the type of the row.
+
+Erroneous code example:
+
+    {foo = "a"} : {foo : Num}"#,
+    ),
+    (
+        "E022",
+        r#"A row was declared twice in the same type.
+
+The inferred type of an expression contains a row which is already declared by an enclosing
+row type, with a conflicting type. A type can't have two declarations for the same row."#,
+    ),
+    (
+        "E023",
+        r#"Two function types don't match.
+
+The domains or the codomains of the expected and the inferred function types are
+incompatible. This typically happens when a function is passed as an argument, but doesn't
+have the type expected by the caller.
+
+Erroneous code example:
+
+    let g : (Num -> Num) -> Num = fun f => f 0 in
+    let h : Str -> Str = fun s => s in
+    g h : Num"#,
+    ),
+    (
+        "E024",
+        r#"The end of the file was reached while parsing an expression.
+
+Some construct was not terminated, typically a string, a parenthesis, a record or an array.
+The diagnostic lists the tokens which the parser expected."#,
+    ),
+    (
+        "E025",
+        r#"The parser encountered an unexpected token.
+
+Erroneous code example:
+
+    let x = 1 in in x
+
+The diagnostic lists the tokens which the parser expected at this position."#,
+    ),
+    (
+        "E026",
+        r#"A superfluous token was found after a complete expression.
+
+Erroneous code example:
+
+    1 + 1)
+
+Remove the token, or check for a missing opening delimiter earlier in the file."#,
+    ),
+    (
+        "E027",
+        r#"A closing brace doesn't match any opening brace.
+
+Erroneous code example:
+
+    {foo = 1}}"#,
+    ),
+    (
+        "E028",
+        r#"A string literal contains an invalid escape sequence.
+
+The valid escape sequences are `\"`, `\'`, `\\`, `\%`, `\n`, `\r`, `\t` and ASCII escape codes
+`\xHH`. To write a literal backslash, use `\\`."#,
+    ),
+    (
+        "E029",
+        r#"A string literal contains an invalid ASCII escape code.
+
+ASCII escape codes are of the form `\xHH`, where `HH` is a two-digits hexadecimal number
+at most `7F`."#,
+    ),
+    (
+        "E030",
+        r#"A file in an external format could not be parsed.
+
+An imported JSON, YAML or TOML file is not valid. The diagnostic includes the error reported by
+the underlying parser."#,
+    ),
+    (
+        "E031",
+        r#"A record literal with a polymorphic tail contains forbidden constructs.
+
+A record with a tail, such as `{foo : Num; a}`, is a record type: it can only be composed of
+type annotations of the form `<field> : <type>`.
+
+Erroneous code example:
+
+    {foo : Num = 1; a}
+
+Value assignments and metadata are forbidden in the presence of a tail."#,
+    ),
+    (
+        "E032",
+        r#"A field of a record literal is defined twice.
+
+Erroneous code example:
+
+    {foo = 1, foo = 2}
+
+Remove one of the definitions. Defining several times a field whose values can be merged, such
+as two records, is accepted."#,
+    ),
+    (
+        "E033",
+        r#"An import failed.
+
+The imported file couldn't be read. Paths of imports are relative to the directory of the
+importing file. Check that the file exists and can be read."#,
+    ),
+    (
+        "E034",
+        r#"A null value was exported to a format which doesn't support it.
+
+TOML has no representation for `null`. Remove the null values, or export to another format."#,
+    ),
+    (
+        "E035",
+        r#"A value other than a string was exported in the raw format.
+
+The raw export format only accepts a string, which is output as is. Use a conversion function such
+as `string.from_num`, or export to a structured format such as JSON."#,
+    ),
+    (
+        "E036",
+        r#"A value can't be serialized.
+
+Only records, arrays, strings, numbers, booleans, enum tags and `null` can be exported.
+Functions, for example, can't.
+
+Erroneous code example (with `nickel export`):
+
+    {f = fun x => x}"#,
+    ),
+    (
+        "E037",
+        r#"An error occurred during serialization.
+
+The message of the diagnostic is the error reported by the serializer."#,
+    ),
+    (
+        "E038",
+        r#"An operation on a file failed.
+
+A source file couldn't be read, or an export couldn't be written. The diagnostic indicates the
+operation, the file and the reason of the failure, such as a missing directory or insufficient
+permissions."#,
+    ),
+    (
+        "E039",
+        r#"An I/O error occurred.
+
+The message of the diagnostic is the error reported by the system."#,
+    ),
+    (
+        "E040",
+        r#"An unknown REPL command was entered.
+
+Type `:help` to list the available commands."#,
+    ),
+    (
+        "E041",
+        r#"A REPL command was given no argument.
+
+Type `:help <command>` to show the usage of a command."#,
+    ),
+    (
+        "E042",
+        r#"A value can't be exported at the top-level of a document in the requested format.
+
+A TOML document is a table: only a record can be exported to TOML.
+
+Erroneous code example (with `nickel export --format toml`):
+
+    [1, 2]
+
+Wrap the value in a record, such as `{value = [1, 2]}`, or export to another format."#,
+    ),
+    (
+        "E043",
+        r#"A module of the standard library is not a record.
+
+A standard library loaded from a directory with `--stdlib <dir>` must have the same layout as the
+embedded one: the directory contains the files `builtin.ncl`, `contract.ncl`, `array.ncl`,
+`record.ncl`, `string.ncl`, `num.ncl` and `function.ncl`, and each of them is a record literal,
+whose fields are added to the initial environment.
+
+Erroneous code example (as the content of `string.ncl`):
+
+    let length = fun s => %str_length% s in {length}
+
+Write the module as a record literal: `{length = fun s => %str_length% s}`."#,
+    ),
+    (
+        "E044",
+        r#"No case of a switch matched the value.
+
+A switch without a default case must handle every tag that the matched value can evaluate to.
+
+Erroneous code example:
+
+    switch { `http => 80, `https => 443 } `ftp
+
+Add a case for the missing tag, or a default case `_ => <exp>`. The error also occurs when the
+matched value is not an enum tag at all, for example a string: use a static type annotation to
+catch such errors before evaluation."#,
+    ),
+    (
+        "E045",
+        r#"The evaluation was cancelled before completion.
+
+A host program can abort an evaluation from another thread, typically when it exceeds a time
+limit, such as the one set by the `--timeout` option of the command-line interface. The location
+of the error is the expression being evaluated when the cancellation took effect, which hints at
+where the program spends its time.
+
+Erroneous code example, with `--timeout 1`:
+
+    {loop = fun n => if n < 0 then 0 else loop (n + 1)}.loop 0
+
+Check for non-terminating recursion, or increase the time limit."#,
+    ),
+    (
+        "E046",
+        r#"An array mixing records with other values can't be exported in the requested format.
+
+In TOML, records are tables, and an array containing tables is an array of tables, which can't
+contain any other kind of value.
+
+Erroneous code example (with `nickel export --format toml`):
+
+    {servers = [{name = "a"}, "b"]}
+
+Wrap the other elements in records, such as `{name = "b"}`, or export to another format."#,
+    ),
+    (
+        "E047",
+        r#"A binding shadows a name of the standard library.
+
+This is a warning, which becomes an error with `--deny shadow-stdlib`. In the scope of the
+binding, the name refers to the binding instead of the standard library, which usually makes the
+uses of the standard library fail with a confusing error.
+
+Erroneous code example:
+
+    let string = "foo" in string.length string
+
+Rename the binding, such as `let name = "foo" in string.length name`."#,
+    ),
+    (
+        "E048",
+        r#"A file is imported again while it is being evaluated.
+
+Files may import each other, as records are lazy: a file can use a field of a file which imports
+it back, as long as the field doesn't depend on the importing file itself. The evaluation of a
+file which needs its own value, directly or through other imports, can't terminate.
+
+Erroneous code example, in a file `a.ncl`:
+
+    (import "b.ncl") + 1
+
+where `b.ncl` contains `import "a.ncl"`. Move the shared values to a third file imported by
+both. A bundle (see `nickel bundle`) can't express any import cycle, even a terminating one."#,
+    ),
+    (
+        "E049",
+        r#"A field is overridden several times on the command line.
+
+Overrides given with `--override path.to.field=value` are merged with the program, and the order
+of the command-line arguments doesn't matter: the overrides of a same field can't be ordered.
+
+Erroneous example:
+
+    nickel export -f config.ncl --override server.replicas=3 --override server.replicas=4
+
+Keep a single override of the field."#,
+    ),
+    (
+        "E050",
+        r#"A REPL command was given an argument it doesn't accept, such as an unknown option.
+
+Erroneous example:
+
+    :env --verbose --stdlib
+
+Type `:help <command>` to show the usage of a command."#,
+    ),
+    (
+        "E051",
+        r#"A field is accessed on a value which is not a record.
+
+Only records have fields. A common mistake is to access a field of an array of records, instead
+of the field of each of its elements, or to use a field access in place of a function of the
+standard library.
+
+Erroneous code examples:
+
+    let servers = [{name = "a"}, {name = "b"}] in servers.name
+    "hello".length
+
+Use `array.map (fun s => s.name) servers` or `array.pluck "name" servers` to access a field of
+each element of an array, and the functions of the `string` module, such as `string.length`, to
+operate on strings."#,
+    ),
+    (
+        "E052",
+        r#"A type alias is applied to a number of arguments different from its number of parameters.
+
+A type alias declared with parameters, as in `let type Pair a b = {fst: a, snd: b} in ...`, must
+be given exactly one type for each of its parameters wherever it is used.
+
+Erroneous code example:
+
+    let type Pair a b = {fst: a, snd: b} in
+    {fst = 1, snd = "a"} : Pair Num
+
+Give the missing arguments, as in `Pair Num Str`."#,
+    ),
+    (
+        "E053",
+        r#"The operands of a binary operator don't have the types it expects.
+
+Arithmetic and comparison operators, such as `+`, `-` or `<`, operate on numbers. The `++`
+operator concatenates two strings, and the `@` operator two arrays. The diagnostic shows the type
+each operand has been evaluated to.
+
+Erroneous code examples:
+
+    let port = "8080" in port + 1
+    "version: " ++ 2
+    [1, 2] @ 3
+
+Convert the operands first, for example with `string.to_number` or `string.from_num`, and
+wrap single elements in an array, as in `[1, 2] @ [3]`. Strings can also be built by
+interpolation: `"version: %{string.from_num 2}"`."#,
+    ),
+    (
+        "E054",
+        r#"A let binding is never used in its body.
+
+This is a warning, which becomes an error with `--deny unused-binding` or `--deny-warnings`. An
+unused binding is often a leftover, or the sign of a typo in a variable which happens to refer to
+another binding.
+
+Erroneous code example:
+
+    let port = 80 in
+    let host = "localhost" in
+    "%{host}:8080"
+
+Remove the binding, or use it. To keep a binding which is unused on purpose, start its name with
+an underscore, as in `let _port = 80 in ...`."#,
+    ),
+    (
+        "E055",
+        r#"A default value may be overridden, but it is computed: the contracts of its fields are then
+dropped by the override.
+
+This is a warning, which becomes an error with `--deny override-bypass` or `--deny-warnings`. When
+a default value is overridden, the contracts annotating its fields still apply to the overriding
+value, but only if the default value is written as a record, or as a merge of records. The
+contracts of a record returned by a function application, a field access, a switch or an import
+are not retained.
+
+Erroneous code example:
+
+    let mk_server = fun host => {host | Str = host, port | Num = 80} in
+    {server | default = mk_server "localhost"} & {server = {host = "localhost", port = "80"}}
+
+Here, `port` is not checked against `Num`. Annotate the field itself with a contract, as in
+`server | default | Server = ...`, which applies whatever the overriding value."#,
+    ),
+];
+
+/// Return the explanation of an error code, or `None` if the code is unknown. The code is case
+/// insensitive.
+pub fn explain(code: &str) -> Option<&'static str> {
+    REGISTRY
+        .iter()
+        .find(|(c, _)| c.eq_ignore_ascii_case(code.trim()))
+        .map(|(_, explanation)| *explanation)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::*;
+    use crate::eval::callstack::CallStack;
+    use crate::identifier::Ident;
+    use crate::label::Label;
+    use crate::position::{RawSpan, TermPos};
+    use crate::repl::command::CommandType;
+    use crate::serialize::ExportFormat;
+    use crate::term::{RichTerm, Term};
+    use crate::types::{AbsType, Types};
+    use codespan::Files;
+    use std::collections::HashSet;
+
+    #[test]
+    fn codes_are_unique() {
+        let mut seen = HashSet::new();
+
+        for (code, explanation) in REGISTRY {
+            assert!(seen.insert(code), "duplicate error code {}", code);
+            assert!(
+                code.len() == 4
+                    && code.starts_with('E')
+                    && code[1..].chars().all(|c| c.is_ascii_digit()),
+                "ill-formed error code {}",
+                code
+            );
+            assert!(
+                !explanation.trim().is_empty(),
+                "empty explanation for {}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn explain_is_case_insensitive() {
+        assert_eq!(explain("E014"), explain("e014"));
+        assert!(explain("E014").is_some());
+        assert!(explain("E999").is_none());
+    }
+
+    /// Check that the codes of `samples` are registered, and that the samples cover the `count`
+    /// variants of their type, as numbered by `variant`. `variant` is an exhaustive match: adding
+    /// a variant to an error type requires to number it, and then to add a sample.
+    fn check_registered<T: std::fmt::Debug>(
+        samples: &[T],
+        count: usize,
+        variant: impl Fn(&T) -> usize,
+        code: impl Fn(&T) -> &'static str,
+    ) {
+        let registered: HashSet<&str> = REGISTRY.iter().map(|(code, _)| *code).collect();
+        let mut covered = HashSet::new();
+
+        for sample in samples {
+            assert!(
+                registered.contains(code(sample)),
+                "the code {} of {:?} is not registered",
+                code(sample),
+                sample
+            );
+            assert!(variant(sample) < count, "unexpected variant {:?}", sample);
+            covered.insert(variant(sample));
+        }
+
+        let missing: Vec<usize> = (0..count).filter(|i| !covered.contains(i)).collect();
+        assert!(
+            missing.is_empty(),
+            "no sample for the variants {:?}",
+            missing
+        );
+    }
+
+    #[test]
+    fn codes_of_errors_are_registered() {
+        let mut files = Files::new();
+        let file_id = files.add("<test>", String::from("1 + 1"));
+        let span = RawSpan {
+            src_id: file_id,
+            start: 0.into(),
+            end: 1.into(),
+        };
+        let pos = TermPos::Original(span);
+        let term = || RichTerm::from(Term::Null);
+        let ty = || Types(AbsType::Num());
+        let id = || Ident::from("x");
+        let str = || String::from("x");
+
+        let serialization_errors = vec![
+            SerializationError::UnsupportedNull(ExportFormat::Json, term(), Default::default()),
+            SerializationError::NotAString(term()),
+            SerializationError::NonSerializable(term(), Default::default()),
+            SerializationError::UnsupportedFormat(ExportFormat::Raw, term()),
+            SerializationError::MixedArray(ExportFormat::Toml, term(), Default::default()),
+            SerializationError::Other(str()),
+        ];
+        check_registered(
+            &serialization_errors,
+            6,
+            |err| match err {
+                SerializationError::UnsupportedNull(..) => 0,
+                SerializationError::NotAString(..) => 1,
+                SerializationError::NonSerializable(..) => 2,
+                SerializationError::UnsupportedFormat(..) => 3,
+                SerializationError::MixedArray(..) => 4,
+                SerializationError::Other(..) => 5,
+            },
+            SerializationError::code,
+        );
+
+        let parse_errors = vec![
+            ParseError::UnexpectedEOF(file_id, Vec::new()),
+            ParseError::UnexpectedToken(span, Vec::new()),
+            ParseError::ExtraToken(span),
+            ParseError::UnmatchedCloseBrace(span, None),
+            ParseError::InvalidEscapeSequence(span),
+            ParseError::InvalidAsciiEscapeCode(span),
+            ParseError::ExternalFormatError(str(), str(), None, None),
+            ParseError::UnboundTypeVariables(vec![id()], span),
+            ParseError::InvalidUniRecord(span, span, span),
+            ParseError::DuplicateField(vec![id()], span, span),
+            ParseError::DuplicateOverride(str(), span, span),
+            ParseError::TypeAliasArity(id(), 1, 2, span),
+        ];
+        check_registered(
+            &parse_errors,
+            12,
+            |err| match err {
+                ParseError::UnexpectedEOF(..) => 0,
+                ParseError::UnexpectedToken(..) => 1,
+                ParseError::ExtraToken(..) => 2,
+                ParseError::UnmatchedCloseBrace(..) => 3,
+                ParseError::InvalidEscapeSequence(..) => 4,
+                ParseError::InvalidAsciiEscapeCode(..) => 5,
+                ParseError::ExternalFormatError(..) => 6,
+                ParseError::UnboundTypeVariables(..) => 7,
+                ParseError::InvalidUniRecord(..) => 8,
+                ParseError::DuplicateField(..) => 9,
+                ParseError::DuplicateOverride(..) => 10,
+                ParseError::TypeAliasArity(..) => 11,
+            },
+            ParseError::code,
+        );
+
+        let import_errors = vec![
+            ImportError::IOError(str(), str(), pos),
+            ImportError::ParseErrors(ParseErrors::from(parse_errors[0].clone()), pos),
+            ImportError::InvalidStdlibModule(str(), pos),
+            ImportError::Transitive(Box::new(ImportError::IOError(str(), str(), pos)), pos),
+            ImportError::ImportCycle(vec![str(), str()], pos),
+        ];
+        check_registered(
+            &import_errors,
+            5,
+            |err| match err {
+                ImportError::IOError(..) => 0,
+                ImportError::ParseErrors(..) => 1,
+                ImportError::InvalidStdlibModule(..) => 2,
+                ImportError::Transitive(..) => 3,
+                ImportError::ImportCycle(..) => 4,
+            },
+            ImportError::code,
+        );
+
+        let eval_errors = vec![
+            EvalError::BlameError(Label::dummy(), CallStack::new()),
+            EvalError::MissingFieldDef(None, CallStack::new()),
+            EvalError::TypeError(str(), str(), pos, term()),
+            EvalError::BinaryTypeError {
+                op: str(),
+                expected: str(),
+                fst: term(),
+                snd: term(),
+                pos,
+            },
+            EvalError::NotAFunc(term(), term(), pos),
+            EvalError::FieldMissing(str(), str(), term(), pos, None),
+            EvalError::NotARecord {
+                field: str(),
+                value: term(),
+                record_pos: pos,
+                access_pos: pos,
+            },
+            EvalError::NotEnoughArgs(2, str(), pos),
+            EvalError::MergeIncompatibleArgs(term(), term(), pos),
+            EvalError::UnboundIdentifier(id(), pos, None),
+            EvalError::NonExhaustiveMatch {
+                value: term(),
+                handled: vec![id()],
+                pos,
+            },
+            EvalError::InfiniteRecursion(CallStack::new(), pos),
+            EvalError::Cancelled(pos),
+            EvalError::SerializationError(serialization_errors[0].clone()),
+            EvalError::DeserializationError(str(), str(), pos, str(), None, None),
+            EvalError::ImportError(import_errors[0].clone()),
+            EvalError::InternalError(str(), pos),
+            EvalError::Other(str(), pos),
+        ];
+        check_registered(
+            &eval_errors,
+            18,
+            |err| match err {
+                EvalError::BlameError(..) => 0,
+                EvalError::MissingFieldDef(..) => 1,
+                EvalError::TypeError(..) => 2,
+                EvalError::BinaryTypeError { .. } => 3,
+                EvalError::NotAFunc(..) => 4,
+                EvalError::FieldMissing(..) => 5,
+                EvalError::NotARecord { .. } => 6,
+                EvalError::NotEnoughArgs(..) => 7,
+                EvalError::MergeIncompatibleArgs(..) => 8,
+                EvalError::UnboundIdentifier(..) => 9,
+                EvalError::NonExhaustiveMatch { .. } => 10,
+                EvalError::InfiniteRecursion(..) => 11,
+                EvalError::Cancelled(..) => 12,
+                EvalError::SerializationError(..) => 13,
+                EvalError::DeserializationError(..) => 14,
+                EvalError::ImportError(..) => 15,
+                EvalError::InternalError(..) => 16,
+                EvalError::Other(..) => 17,
+            },
+            EvalError::code,
+        );
+
+        let mismatch = || Box::new(TypecheckError::TypeMismatch(ty(), ty(), pos));
+        let typecheck_errors = vec![
+            TypecheckError::UnboundIdentifier(id(), pos, None),
+            TypecheckError::IllformedType(ty()),
+            TypecheckError::MissingRow(id(), ty(), ty(), pos, pos),
+            TypecheckError::MissingDynTail(ty(), ty(), pos),
+            TypecheckError::ExtraRow(id(), ty(), ty(), pos, pos),
+            TypecheckError::ExtraDynTail(ty(), ty(), pos),
+            TypecheckError::UnboundTypeVariable(id(), pos),
+            TypecheckError::TypeMismatch(ty(), ty(), pos),
+            TypecheckError::RowKindMismatch(id(), None, Some(ty()), pos),
+            TypecheckError::RowMismatch(id(), ty(), ty(), mismatch(), pos),
+            TypecheckError::RowConflict(id(), None, ty(), ty(), pos),
+            TypecheckError::ArrowTypeMismatch(ty(), ty(), Vec::new(), mismatch(), pos),
+        ];
+        check_registered(
+            &typecheck_errors,
+            12,
+            |err| match err {
+                TypecheckError::UnboundIdentifier(..) => 0,
+                TypecheckError::IllformedType(..) => 1,
+                TypecheckError::MissingRow(..) => 2,
+                TypecheckError::MissingDynTail(..) => 3,
+                TypecheckError::ExtraRow(..) => 4,
+                TypecheckError::ExtraDynTail(..) => 5,
+                TypecheckError::UnboundTypeVariable(..) => 6,
+                TypecheckError::TypeMismatch(..) => 7,
+                TypecheckError::RowKindMismatch(..) => 8,
+                TypecheckError::RowMismatch(..) => 9,
+                TypecheckError::RowConflict(..) => 10,
+                TypecheckError::ArrowTypeMismatch(..) => 11,
+            },
+            TypecheckError::code,
+        );
+
+        let io_errors = vec![
+            IOError::File {
+                path: str().into(),
+                op: FileOperation::Read,
+                kind: std::io::ErrorKind::NotFound,
+                msg: str(),
+            },
+            IOError::Other(str()),
+        ];
+        check_registered(
+            &io_errors,
+            2,
+            |err| match err {
+                IOError::File { .. } => 0,
+                IOError::Other(..) => 1,
+            },
+            IOError::code,
+        );
+
+        let repl_errors = vec![
+            ReplError::UnknownCommand(str()),
+            ReplError::MissingArg {
+                cmd: CommandType::Load,
+                msg_opt: None,
+            },
+            ReplError::InvalidArg {
+                cmd: CommandType::Load,
+                arg: str(),
+            },
+        ];
+        check_registered(
+            &repl_errors,
+            3,
+            |err| match err {
+                ReplError::UnknownCommand(..) => 0,
+                ReplError::MissingArg { .. } => 1,
+                ReplError::InvalidArg { .. } => 2,
+            },
+            ReplError::code,
+        );
+
+        let warnings = vec![
+            Warning::ShadowedStdlib(id()),
+            Warning::UnusedBinding(id()),
+            Warning::OverrideBypass(TermPos::None),
+        ];
+        check_registered(
+            &warnings,
+            3,
+            |warning| match warning {
+                Warning::ShadowedStdlib(..) => 0,
+                Warning::UnusedBinding(..) => 1,
+                Warning::OverrideBypass(..) => 2,
+            },
+            Warning::code,
+        );
+
+        let errors = vec![
+            Error::from(eval_errors[0].clone()),
+            Error::from(typecheck_errors[0].clone()),
+            Error::from(ParseErrors::from(parse_errors[0].clone())),
+            Error::from(import_errors[0].clone()),
+            Error::from(serialization_errors[0].clone()),
+            Error::from(io_errors[0].clone()),
+            Error::from(repl_errors[0].clone()),
+            Error::DeniedWarnings(vec![warnings[0].clone()]),
+        ];
+        check_registered(
+            &errors,
+            8,
+            |err| match err {
+                Error::EvalError(..) => 0,
+                Error::TypecheckError(..) => 1,
+                Error::ParseErrors(..) => 2,
+                Error::ImportError(..) => 3,
+                Error::SerializationError(..) => 4,
+                Error::IOError(..) => 5,
+                Error::ReplError(..) => 6,
+                Error::DeniedWarnings(..) => 7,
+            },
+            Error::code,
+        );
+    }
+}
@@ -18,6 +18,9 @@ pub struct CallDescr {
     pub head: Option<Ident>,
     /// The position of the application.
     pub span: RawSpan,
+    /// The number of consecutive identical calls this description stands for, as produced by
+    /// recursive functions. See [`CallStack::group_by_calls`].
+    pub repeat: usize,
 }
 
 /// A call stack element.
@@ -128,6 +131,9 @@ impl CallStack {
     /// The final call description list is reversed such that the most nested calls, which are
     /// usually the most relevant to understand the error, are printed first.
     ///
+    /// Finally, consecutive identical calls, which are typical of recursive functions, are
+    /// collapsed into one call description whose `repeat` field counts them.
+    ///
     /// # Arguments
     ///
     /// - `contract_id`: the `FileId` of the source containing standard contracts, to filter their
@@ -181,6 +187,7 @@ impl CallStack {
                         Some(CallDescr {
                             head: ref mut head @ None,
                             span: span_call,
+                            ..
                         }) if pos.unwrap() <= *span_call => *head = Some(id.clone()),
                         _ => (),
                     };
@@ -191,7 +198,11 @@ impl CallStack {
                         Some(CallDescr {
                             span: span_call, ..
                         }) if span <= *span_call && span.start == span_call.start => (),
-                        _ => pending.push(CallDescr { head: None, span }),
+                        _ => pending.push(CallDescr {
+                            head: None,
+                            span,
+                            repeat: 1,
+                        }),
                     }
                 }
                 StackElem::Fun(pos) => {
@@ -211,7 +222,20 @@ impl CallStack {
         }
 
         entered.reverse();
-        (entered, pending.pop())
+
+        // Collapse consecutive identical calls, which would otherwise flood the error report in
+        // case of a deep recursion.
+        let mut calls: Vec<CallDescr> = Vec::with_capacity(entered.len());
+        for cdescr in entered {
+            match calls.last_mut() {
+                Some(last) if last.head == cdescr.head && last.span == cdescr.span => {
+                    last.repeat += cdescr.repeat
+                }
+                _ => calls.push(cdescr),
+            }
+        }
+
+        (calls, pending.pop())
     }
 
     /// Return the length of the callstack. Wrapper for `callstack.0.len()`.
@@ -0,0 +1,109 @@
+//! Cooperative cancellation of evaluation.
+//!
+//! A host program may want to abort an evaluation which takes too long without killing the
+//! process, as done by the `--timeout` option of the command-line interface. The evaluation is
+//! given a [`CancellationToken`], which can be triggered from another thread. The abstract machine
+//! checks the token every [`CHECK_INTERVAL`] steps, and fails with
+//! [`EvalError::Cancelled`](../../error/enum.EvalError.html#variant.Cancelled) once the token has
+//! been triggered.
+//!
+//! The traversals done after the abstract machine has finished, namely the substitution of a fully
+//! evaluated term and its serialization, check the token as well.
+use std::cell::Cell;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// The number of steps of the abstract machine between two checks of the cancellation token.
+pub const CHECK_INTERVAL: usize = 1024;
+
+/// A token shared between an evaluation and the threads which may cancel it. Cloning a token gives
+/// a handle to the same underlying flag.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request the cancellation of the evaluations using this token.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// A countdown checking a cancellation condition every [`CHECK_INTERVAL`] calls, for the
+/// traversals of terms which don't run on the abstract machine. Once the condition has held, the
+/// check keeps failing.
+pub(crate) struct Check<'a> {
+    condition: &'a dyn Fn() -> bool,
+    steps_until_check: Cell<usize>,
+    cancelled: Cell<bool>,
+}
+
+impl<'a> Check<'a> {
+    pub fn new(condition: &'a dyn Fn() -> bool) -> Self {
+        Check {
+            condition,
+            steps_until_check: Cell::new(CHECK_INTERVAL),
+            cancelled: Cell::new(false),
+        }
+    }
+
+    /// Count one step, and return whether the traversal has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        if !self.cancelled.get() {
+            self.steps_until_check.set(self.steps_until_check.get() - 1);
+
+            if self.steps_until_check.get() == 0 {
+                self.cancelled.set((self.condition)());
+                self.steps_until_check.set(CHECK_INTERVAL);
+            }
+        }
+
+        self.cancelled.get()
+    }
+
+    /// Whether the traversal has been cancelled, without counting a step.
+    pub fn cancelled(&self) -> bool {
+        self.cancelled.get()
+    }
+}
+
+/// A writer which fails once its token has been triggered, such that the serialization of a large
+/// value can be cancelled. The token is checked each time data is handed to the underlying writer.
+pub struct CancellableWriter<W> {
+    inner: W,
+    token: CancellationToken,
+}
+
+impl<W> CancellableWriter<W> {
+    pub fn new(inner: W, token: CancellationToken) -> Self {
+        CancellableWriter { inner, token }
+    }
+
+    fn check(&self) -> io::Result<()> {
+        if self.token.is_cancelled() {
+            Err(io::Error::other("cancelled"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<W: io::Write> io::Write for CancellableWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.check()?;
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.check()?;
+        self.inner.flush()
+    }
+}
@@ -1,5 +1,12 @@
 //! Compute the fixpoint of a recursive record.
 use super::*;
+use crate::position::TermPos;
+
+/// The error of a reference to an identifier which is not bound in `env`.
+fn unbound_identifier(id: &Ident, pos: TermPos, env: &Environment) -> EvalError {
+    let closest = suggest::closest_ident(id, env.iter_elems().map(|(id, _)| id));
+    EvalError::UnboundIdentifier(id.clone(), pos, closest)
+}
 
 /// Build a recursive environment from record bindings. For each field, `rec_env` either extracts
 /// the corresponding thunk from the environment in the general case, or create a closure on the
@@ -14,7 +21,7 @@ pub fn rec_env<'a, I: Iterator<Item = (&'a Ident, &'a RichTerm)>>(
             Term::Var(ref var_id) => {
                 let thunk = env
                     .get(var_id)
-                    .ok_or_else(|| EvalError::UnboundIdentifier(var_id.clone(), rt.pos))?;
+                    .ok_or_else(|| unbound_identifier(var_id, rt.pos, env))?;
                 Ok((id.clone(), thunk))
             }
             _ => {
@@ -46,7 +53,7 @@ pub fn patch_field(
     if let Term::Var(var_id) = &*rt.term {
         let mut thunk = env
             .get(var_id)
-            .ok_or_else(|| EvalError::UnboundIdentifier(var_id.clone(), rt.pos))?;
+            .ok_or_else(|| unbound_identifier(var_id, rt.pos, env))?;
 
         let deps = thunk.deps();
 
@@ -58,6 +65,9 @@ pub fn patch_field(
             ThunkDeps::Unknown => thunk.borrow_mut().env.extend(rec_env.clone()),
             ThunkDeps::Empty => (),
         };
+
+        // The dependencies are now bound: a memoized value computed with the same ones is valid.
+        thunk.restore_memo();
     }
 
     // Thanks to the share normal form transformation, the content is either a constant or a
@@ -26,6 +26,38 @@ pub enum ThunkState {
 pub struct ThunkData {
     inner: InnerThunkData,
     state: ThunkState,
+    /// Set when the content of the thunk is explicitly memoized (see `Memo` in
+    /// [`UnaryOp`](../../term/enum.UnaryOp.html)). The value of a memoized revertible thunk is
+    /// recorded in `memo` once evaluated.
+    memoized: bool,
+    /// The value of a memoized revertible thunk, shared with its reverted copies. See [`Memo`].
+    memo: Option<Rc<Memo>>,
+}
+
+/// The value of a memoized revertible thunk, keyed on the environment it has been computed in.
+///
+/// A revertible thunk is reverted when the record defining it is merged, as the fields it depends
+/// on may have been overridden, and the reverted copy is then evaluated anew. The value of a
+/// memoized thunk is instead reused by a reverted copy whose dependencies are bound to the same
+/// thunks as when the value was computed, that is as long as none of them has been overridden or
+/// reverted in turn.
+#[derive(Clone, Debug)]
+pub struct Memo {
+    /// The thunks bound to the dependencies of the thunk when its value was computed.
+    deps: Vec<(Ident, Weak<RefCell<ThunkData>>)>,
+    value: Closure,
+}
+
+impl PartialEq for Memo {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+            && self.deps.len() == other.deps.len()
+            && self
+                .deps
+                .iter()
+                .zip(other.deps.iter())
+                .all(|((id1, data1), (id2, data2))| id1 == id2 && Weak::ptr_eq(data1, data2))
+    }
 }
 
 /// The part of [ThunkData] responsible for storing the closure itself. It can either be:
@@ -51,6 +83,8 @@ impl ThunkData {
         ThunkData {
             inner: InnerThunkData::Standard(closure),
             state: ThunkState::Suspended,
+            memoized: false,
+            memo: None,
         }
     }
 
@@ -65,6 +99,8 @@ impl ThunkData {
                 deps,
             },
             state: ThunkState::Suspended,
+            memoized: false,
+            memo: None,
         }
     }
 
@@ -95,10 +131,31 @@ impl ThunkData {
         }
     }
 
-    /// Update the cached closure.
+    /// Update the cached closure. The value of a memoized revertible thunk is recorded together
+    /// with the thunks bound to its dependencies, if they are known.
     pub fn update(&mut self, new: Closure) {
         match self.inner {
             InnerThunkData::Standard(ref mut closure) => *closure = new,
+            InnerThunkData::Revertible {
+                ref mut cached,
+                deps: Some(ref deps),
+                ..
+            } if self.memoized => {
+                let deps = deps
+                    .iter()
+                    .filter_map(|id| {
+                        cached
+                            .env
+                            .get(id)
+                            .map(|thunk| (id.clone(), Rc::downgrade(&thunk.data)))
+                    })
+                    .collect();
+                self.memo = Some(Rc::new(Memo {
+                    deps,
+                    value: new.clone(),
+                }));
+                *cached = Rc::new(new);
+            }
             InnerThunkData::Revertible { ref mut cached, .. } => *cached = Rc::new(new),
         }
 
@@ -107,7 +164,8 @@ impl ThunkData {
 
     /// Create fresh unevaluated thunk data from `self`, reverted to its original state before the
     /// first update. For standard thunk data, the content is unchanged and the state is conserved:
-    /// in this case, `revert()` is the same as `clone()`.
+    /// in this case, `revert()` is the same as `clone()`. The memoized value, if any, is kept
+    /// aside: see [`restore_memo`](#method.restore_memo).
     pub fn revert(&self) -> Self {
         match self.inner {
             InnerThunkData::Standard(_) => self.clone(),
@@ -120,10 +178,39 @@ impl ThunkData {
                     deps: deps.clone(),
                 },
                 state: ThunkState::Suspended,
+                memoized: false,
+                memo: self.memo.clone(),
             },
         }
     }
 
+    /// Restore the memoized value of an unevaluated reverted thunk, if its dependencies are bound
+    /// to the same thunks as when this value was computed. Otherwise, the thunk is left untouched
+    /// and will be evaluated anew.
+    pub fn restore_memo(&mut self) {
+        let (memo, cached) = match (&self.memo, &mut self.inner) {
+            (Some(memo), InnerThunkData::Revertible { cached, .. })
+                if self.state == ThunkState::Suspended =>
+            {
+                (memo, cached)
+            }
+            _ => return,
+        };
+
+        let unchanged = memo.deps.iter().all(|(id, data)| {
+            cached
+                .env
+                .get(id)
+                .map(|thunk| Weak::ptr_eq(data, &Rc::downgrade(&thunk.data)))
+                .unwrap_or(false)
+        });
+
+        if unchanged {
+            *cached = Rc::new(memo.value.clone());
+            self.state = ThunkState::Evaluated;
+        }
+    }
+
     /// Return the potential field dependencies stored in a revertible thunk. See [`transform::free_vars`]
     pub fn deps(&self) -> ThunkDeps {
         match self.inner {
@@ -217,6 +304,11 @@ impl Thunk {
         self.ident_kind
     }
 
+    /// Whether two thunks share the same data, as opposed to having equal contents.
+    pub fn ptr_eq(&self, other: &Thunk) -> bool {
+        Rc::ptr_eq(&self.data, &other.data)
+    }
+
     /// Consume the thunk and return an owned closure. Avoid cloning if this thunk is the only
     /// reference to the inner closure.
     pub fn into_closure(self) -> Closure {
@@ -250,6 +342,12 @@ impl Thunk {
     pub fn deps(&self) -> ThunkDeps {
         self.data.borrow().deps().clone()
     }
+
+    /// Restore the memoized value of a reverted thunk, once its environment has been patched. See
+    /// [`ThunkData::restore_memo`].
+    pub fn restore_memo(&mut self) {
+        self.data.borrow_mut().restore_memo();
+    }
 }
 
 /// Possible alternatives for the field dependencies of a thunk.
@@ -294,4 +392,23 @@ impl ThunkUpdateFrame {
             false
         }
     }
+
+    /// Restore the corresponding thunk to the suspended state, if it is still black-holed. Used
+    /// when its evaluation is aborted, such that it can be evaluated again later.
+    pub fn reset(self) {
+        if let Some(data) = Weak::upgrade(&self.data) {
+            let mut data = data.borrow_mut();
+            if data.state == ThunkState::Blackholed {
+                data.state = ThunkState::Suspended;
+            }
+        }
+    }
+
+    /// Mark the corresponding thunk as memoized, such that its value is reused by its reverted
+    /// copies as long as its dependencies are unchanged. See [`Memo`].
+    pub fn memoize(&self) {
+        if let Some(data) = Weak::upgrade(&self.data) {
+            data.borrow_mut().memoized = true;
+        }
+    }
 }
@@ -51,12 +51,59 @@
 //! evaluates to the simple value
 //! - *Contract check*: merging a `Contract` or a `ContractDefault` with a simple value `t`
 //! evaluates to a contract check, that is an `Assume(..., t)`
+//!
+//! ## Contract accumulation
+//!
+//! Contracts accumulate across merge layers: whatever the order of the operands and the
+//! priorities involved, all the contracts of both sides are kept in the result and apply to the
+//! final value, with their original label.
+//!
+//! In particular, when a value overrides another one of lower priority (typically a default
+//! value), the overridden value is discarded but its contracts are not. The two values are merged
+//! in *override* mode (see [`MergeMode::Override`]), which recursively keeps the fields and the
+//! values of the overriding side, but retains the contracts annotating the fields of the
+//! overridden side:
+//!
+//! ```text
+//! {port | default = {number | Num = 80}} & {port = {number = "8080"}}
+//! ```
+//!
+//! Here, `port` is overridden by `{number = "8080"}`, but `number` is still checked against `Num`
+//! and the evaluation fails.
+//!
+//! To preserve laziness, an overridden value is never evaluated unless it is syntactically a
+//! record or an enriched value, that is, unless it may carry contracts (see
+//! [`may_carry_contracts`]). In particular, contracts coming from an overridden value which is the
+//! result of a computation (say, a function application returning a record) are not retained.
+//!
+//! ## Record contracts
+//!
+//! Applying a record contract to a value, as in `value | Schema`, merges the value with the
+//! contract in *contract* mode (see [`MergeMode::Contract`]). Besides checking that the value
+//! doesn't have extra fields, the metadata of each field of the value is combined with the
+//! metadata of the corresponding field of the contract (recursively, see
+//! [`MergeMode::ContractField`]) as follows:
+//!
+//! - *Documentation*: the documentation of the value wins. The documentation of the contract fills
+//!   in when the value has none, such that a schema can be the single source of documentation.
+//! - *Default values*: a default value of the contract is only used when the value doesn't define
+//!   the field, or defines it without a value. Otherwise, it is discarded without being evaluated,
+//!   whatever the priority of the value's side.
+//! - *Contracts*: contracts accumulate, as for standard merging.
+//!
+//! ```text
+//! {port = 8080} | {port | Num | doc "The port to listen on" | default = 80}
+//! ```
+//!
+//! Here, the resulting `port` is `8080`, is checked against `Num`, and is documented by `"The
+//! port to listen on"`.
 use super::*;
 use crate::error::EvalError;
-use crate::label::Label;
+use crate::label::{field_path, Label};
 use crate::position::TermPos;
 use crate::term::{
-    make as mk_term, BinaryOp, Contract, MetaValue, RecordAttrs, RichTerm, SharedTerm, Term,
+    make as mk_term, BinaryOp, Contract, MergePriority, MetaValue, RecordAttrs, RichTerm,
+    SharedTerm, Term,
 };
 use crate::transform::Closurizable;
 use std::collections::HashMap;
@@ -69,6 +116,33 @@ pub enum MergeMode {
     Standard,
     /// Merging to apply a record contract to a value, with the associated label.
     Contract(Label),
+    /// Merging a value with a lower priority value that it overrides. Only the contracts of the
+    /// overridden value are retained, while its fields and values are discarded.
+    Override,
+    /// Merging a field of a value with the corresponding field of a record contract, which is done
+    /// when applying a record contract. Behaves as standard merging, excepted that the default
+    /// value of the contract's side is discarded if the value's side has a value. The field path
+    /// is the location of the field in the value the record contract was applied to, which is
+    /// recorded in the labels of the contracts of the contract's side.
+    ContractField(field_path::Path),
+}
+
+impl MergeMode {
+    /// Return true if the merge is part of the application of a record contract, that is if the
+    /// mode is either `Contract` or `ContractField`.
+    fn is_contract(&self) -> bool {
+        matches!(self, MergeMode::Contract(_) | MergeMode::ContractField(_))
+    }
+
+    /// Return the location of the merged values in the value a record contract is applied to, if
+    /// the merge is part of the application of a record contract.
+    fn field_path(&self) -> Option<&field_path::Path> {
+        match self {
+            MergeMode::Contract(lbl) => Some(&lbl.field_path),
+            MergeMode::ContractField(path) => Some(path),
+            MergeMode::Standard | MergeMode::Override => None,
+        }
+    }
 }
 
 impl Default for MergeMode {
@@ -82,8 +156,9 @@ impl Default for MergeMode {
 ///
 /// # Mode
 ///
-/// In `Contract` mode (see [`MergingMode`]()), `t1` must be the value and `t2` must be the
-/// contract. It is important as `merge` is not commutative in this mode.
+/// In `Contract` and `ContractField` modes (see [`MergeMode`]), `t1` must be the value and `t2`
+/// must be the contract. In `Override` mode, `t1` must be the overriding value and `t2` the overridden one.
+/// It is important as `merge` is not commutative in these modes.
 pub fn merge(
     t1: RichTerm,
     mut env1: Environment,
@@ -92,6 +167,22 @@ pub fn merge(
     pos_op: TermPos,
     mode: MergeMode,
 ) -> Result<Closure, EvalError> {
+    // In override mode, the overridden value can only contribute contracts, which may only be
+    // found inside metavalues or records. Otherwise, the overriding value is left unchanged.
+    if mode == MergeMode::Override
+        && !matches!(
+            (t1.as_ref(), t2.as_ref()),
+            (Term::Record(..), Term::Record(..))
+                | (Term::MetaValue(_), _)
+                | (_, Term::MetaValue(_))
+        )
+    {
+        return Ok(Closure {
+            body: t1,
+            env: env1,
+        });
+    }
+
     // Merging a simple value and a metavalue is equivalent to first wrapping the simple value in a
     // new metavalue (with no attribute set excepted the value), and then merging the two
     let (t1, t2) = match (t1.term.is_metavalue(), t2.term.is_metavalue()) {
@@ -229,16 +320,38 @@ pub fn merge(
                 types: types1,
                 contracts: contracts1,
                 priority: priority1,
+                sensitive: sensitive1,
                 value: value1,
             } = meta1;
             let MetaValue {
                 doc: doc2,
-                types: types2,
-                contracts: contracts2,
+                types: mut types2,
+                contracts: mut contracts2,
                 priority: priority2,
+                sensitive: sensitive2,
                 value: value2,
             } = meta2;
 
+            // When applying a record contract, the contracts of the contract's side are relative
+            // to the location of the field in the checked value, which is recorded for error
+            // reporting.
+            if let Some(path) = mode.field_path().filter(|path| !path.is_empty()) {
+                for ctr in types2.iter_mut().chain(contracts2.iter_mut()) {
+                    ctr.label.field_path = path.concat(&ctr.label.field_path);
+                }
+            }
+
+            // In override mode, the value of meta2 is discarded whatever its priority. When
+            // applying a record contract, a default value of the contract's side is discarded if
+            // the value's side already has a value.
+            let (value2, priority2) = if mode == MergeMode::Override
+                || (mode.is_contract() && value1.is_some() && priority2 == MergePriority::Default)
+            {
+                (None, priority1)
+            } else {
+                (value2, priority2)
+            };
+
             let doc = merge_doc(doc1, doc2);
 
             // If:
@@ -282,16 +395,63 @@ pub fn merge(
             };
 
             // Selecting either meta1's value, meta2's value, or the merge of the two values,
-            // depending on which is defined and respective priorities.
+            // depending on which is defined and respective priorities. When a value overrides
+            // the other one, they are merged in override mode to retain the contracts of the
+            // overridden value.
+            let values_mode = match mode.field_path() {
+                Some(path) => MergeMode::ContractField(path.clone()),
+                None => MergeMode::Standard,
+            };
             let (value, priority, mut env) = match (value1, value2) {
                 (Some(t1), Some(t2)) if priority1 == priority2 => {
                     let mut env = Environment::new();
                     (
-                        Some(merge_closurize(&mut env, t1, val_env1, t2, val_env2)),
+                        Some(merge_closurize(
+                            &mut env,
+                            t1,
+                            val_env1,
+                            t2,
+                            val_env2,
+                            &values_mode,
+                        )),
                         priority1,
                         env,
                     )
                 }
+                (Some(t1), Some(t2))
+                    if priority1 > priority2 && may_carry_contracts(&t2, &val_env2) =>
+                {
+                    let mut env = Environment::new();
+                    (
+                        Some(merge_closurize(
+                            &mut env,
+                            t1,
+                            val_env1,
+                            t2,
+                            val_env2,
+                            &MergeMode::Override,
+                        )),
+                        priority1,
+                        env,
+                    )
+                }
+                (Some(t1), Some(t2))
+                    if priority2 > priority1 && may_carry_contracts(&t1, &val_env1) =>
+                {
+                    let mut env = Environment::new();
+                    (
+                        Some(merge_closurize(
+                            &mut env,
+                            t2,
+                            val_env2,
+                            t1,
+                            val_env1,
+                            &MergeMode::Override,
+                        )),
+                        priority2,
+                        env,
+                    )
+                }
                 (Some(t1), _) if priority1 > priority2 => (Some(t1), priority1, val_env1),
                 (Some(t1), None) => (Some(t1), priority1, val_env1),
                 (_, Some(t2)) if priority2 > priority1 => (Some(t2), priority2, val_env2),
@@ -333,6 +493,8 @@ pub fn merge(
                 types,
                 contracts,
                 priority,
+                // A value stays sensitive even if it is merged with a non sensitive one.
+                sensitive: sensitive1 || sensitive2,
                 value,
             };
 
@@ -381,15 +543,25 @@ pub fn merge(
                 m.insert(field, t.closurize(&mut env, env1.clone()));
             }
 
-            for (field, t) in right.into_iter() {
-                m.insert(field, t.closurize(&mut env, env2.clone()));
+            // In override mode, the fields that only belong to the overridden value are discarded
+            if mode != MergeMode::Override {
+                for (field, t) in right.into_iter() {
+                    m.insert(field, t.closurize(&mut env, env2.clone()));
+                }
             }
 
             for (field, (t1, t2)) in center.into_iter() {
-                m.insert(
-                    field,
-                    merge_closurize(&mut env, t1, env1.clone(), t2, env2.clone()),
-                );
+                let t = if mode == MergeMode::Override && !may_carry_contracts(&t2, &env2) {
+                    t1.closurize(&mut env, env1.clone())
+                } else if let Some(path) = mode.field_path() {
+                    let field_mode =
+                        MergeMode::ContractField(path.with(field_path::Elem::Field(field.clone())));
+                    merge_closurize(&mut env, t1, env1.clone(), t2, env2.clone(), &field_mode)
+                } else {
+                    merge_closurize(&mut env, t1, env1.clone(), t2, env2.clone(), &mode)
+                };
+
+                m.insert(field, t);
             }
 
             let rec_env = fixpoint::rec_env(m.iter(), &env)?;
@@ -443,10 +615,15 @@ fn cross_apply_contracts<'a>(
     let result = it2
         .try_fold(t1, |acc, ctr| {
             let ty_closure = ctr.types.clone().closurize(&mut env1_local, env2.clone());
-            mk_term::assume(ty_closure, ctr.label.clone(), acc)
+            // The contract is not applied to the value it annotates anymore.
+            let label = Label {
+                value_pos: TermPos::None,
+                ..ctr.label.clone()
+            };
+            mk_term::assume(ty_closure, label, acc)
                 .map_err(|crate::types::UnboundTypeVariableError(id)| {
                     let pos = id.pos;
-                    EvalError::UnboundIdentifier(id, pos)
+                    EvalError::UnboundIdentifier(id, pos, None)
                 })
                 .map(|rt| rt.with_pos(pos))
         })?
@@ -455,6 +632,32 @@ fn cross_apply_contracts<'a>(
     Ok((result, env))
 }
 
+/// Determine if a term, that is going to be overridden by a merge, may carry contracts that must
+/// be retained. This is the case if the term is a record, an enriched value, or a merge involving
+/// such values, possibly behind variables or let-bindings. This check doesn't evaluate anything,
+/// such that overridden values stay lazy. See also the `override-bypass` lint in
+/// [`crate::lint`], which flags the default values whose contracts are not retained.
+fn may_carry_contracts(rt: &RichTerm, env: &Environment) -> bool {
+    match rt.as_ref() {
+        Term::Var(id) => env
+            .get(id)
+            .map(|thunk| {
+                let clos = thunk.borrow();
+                may_carry_contracts(&clos.body, &clos.env)
+            })
+            .unwrap_or(false),
+        // The share normal form transformation introduces let-bindings in front of records and
+        // enriched values.
+        Term::Let(_, _, body, _) => may_carry_contracts(body, env),
+        // The result of merging values may carry contracts if one of the operands does.
+        Term::Op2(BinaryOp::Merge(), t1, t2) | Term::Op2(BinaryOp::MergeOverride(), t1, t2) => {
+            may_carry_contracts(t1, env) || may_carry_contracts(t2, env)
+        }
+        Term::Record(..) | Term::RecRecord(..) | Term::MetaValue(_) => true,
+        _ => false,
+    }
+}
+
 /// Merge the two optional documentations of a metavalue.
 fn merge_doc(doc1: Option<String>, doc2: Option<String>) -> Option<String> {
     //FIXME: how to merge documentation? Just concatenate?
@@ -462,17 +665,27 @@ fn merge_doc(doc1: Option<String>, doc2: Option<String>) -> Option<String> {
 }
 
 /// Take the current environment, two terms with their local environment, and return a term which
-/// is the closurized merge of the two.
+/// is the closurized merge of the two. The merge is performed in override mode if `mode` is
+/// [`MergeMode::Override`], in contract field mode if `mode` is either [`MergeMode::Contract`] or
+/// [`MergeMode::ContractField`], and in standard mode otherwise.
 fn merge_closurize(
     env: &mut Environment,
     t1: RichTerm,
     env1: Environment,
     t2: RichTerm,
     env2: Environment,
+    mode: &MergeMode,
 ) -> RichTerm {
+    let op = match mode {
+        MergeMode::Override => BinaryOp::MergeOverride(),
+        MergeMode::Contract(lbl) => BinaryOp::MergeContractField(lbl.field_path.clone()),
+        MergeMode::ContractField(path) => BinaryOp::MergeContractField(path.clone()),
+        MergeMode::Standard => BinaryOp::Merge(),
+    };
+
     let mut local_env = Environment::new();
     let body = RichTerm::from(Term::Op2(
-        BinaryOp::Merge(),
+        op,
         t1.closurize(&mut local_env, env1),
         t2.closurize(&mut local_env, env2),
     ));
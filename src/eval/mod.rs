@@ -30,9 +30,11 @@
 //! - **Thunk on stack**: If the evaluation of the current term is done, and there is one (or
 //! several) thunk on the stack, this means we have to perform an update. Consecutive thunks are
 //! popped from the stack and are updated to point to the current evaluated term.
-//! - **Import**: Import must have been resolved before the evaluation starts. An unresolved import
-//! causes an [`InternalError`](../error/enum.EvalError.html#variant.InternalError). A resolved
-//! import, identified by a `FileId`, is retrieved from the import resolver and evaluation proceeds.
+//! - **Import**: Imports are usually resolved before the evaluation starts. A resolved import,
+//!   identified by a `FileId`, is retrieved from the import resolver and evaluation proceeds. If
+//!   imports are resolved lazily, an unresolved import is resolved by the import resolver when it
+//!   is forced, and an error is reported as an
+//!   [`ImportError`](../error/enum.EvalError.html#variant.ImportError).
 //!
 //! ## Contracts
 //!
@@ -91,9 +93,10 @@
 use crate::{
     cache::ImportResolver,
     environment::Environment as GenericEnvironment,
-    error::EvalError,
+    error::{suggest, EvalError, ImportError},
     identifier::Ident,
     match_sharedterm, mk_app,
+    position::TermPos,
     term::{
         make as mk_term, BinaryOp, BindingType, MetaValue, RichTerm, SharedTerm, StrChunk, Term,
         UnaryOp,
@@ -101,6 +104,7 @@ use crate::{
 };
 
 pub mod callstack;
+pub mod cancellation;
 pub mod fixpoint;
 pub mod lazy;
 pub mod merge;
@@ -108,9 +112,12 @@ pub mod operation;
 pub mod stack;
 
 use callstack::*;
+use codespan::FileId;
 use lazy::*;
 use operation::{continuate_operation, OperationCont};
 use stack::Stack;
+use std::collections::HashMap;
+use std::path::PathBuf;
 
 impl AsRef<Vec<StackElem>> for CallStack {
     fn as_ref(&self) -> &Vec<StackElem> {
@@ -199,7 +206,8 @@ pub fn eval_full<R>(
 where
     R: ImportResolver,
 {
-    eval_deep_closure(t0, global_env, resolver).map(|(term, env)| subst(term, global_env, &env))
+    let (term, env) = eval_deep_closure(t0, global_env, resolver)?;
+    subst_cancellable(term, global_env, &env, &|| resolver.is_cancelled())
 }
 
 /// Fully evaluates a Nickel term like `eval_full`, but does not substitute all variables.
@@ -257,7 +265,8 @@ where
             if let Some(t) = meta.value.take() {
                 let (evaluated, env) =
                     eval_closure(Closure { body: t, env }, global_env, resolver, true)?;
-                let substituted = subst(evaluated, global_env, &env);
+                let substituted =
+                    subst_cancellable(evaluated, global_env, &env, &|| resolver.is_cancelled())?;
 
                 meta.value = Some(substituted);
             }
@@ -290,16 +299,77 @@ where
 ///  - an evaluation error
 ///  - the evaluated term with its final environment
 pub fn eval_closure<R>(
+    clos: Closure,
+    global_env: &Environment,
+    resolver: &mut R,
+    enriched_strict: bool,
+) -> Result<(RichTerm, Environment), EvalError>
+where
+    R: ImportResolver,
+{
+    let mut stack = Stack::new();
+    let result = eval_loop(clos, global_env, resolver, enriched_strict, &mut stack);
+
+    // The thunks being evaluated when the evaluation was cancelled are left black-holed, which
+    // would make any later access to them fail with an infinite recursion error.
+    if let Err(EvalError::Cancelled(_)) = result {
+        stack.reset_thunks();
+    }
+
+    // An error raised while evaluating a sensitive value may happen before the value is
+    // evaluated, typically when a contract attached to it fails. The offending value is
+    // recorded here instead.
+    if let Err(ref err) = result {
+        if stack.has_sensitive() {
+            for value in offending_strs(err) {
+                resolver.add_sensitive(value);
+            }
+        }
+    }
+
+    result
+}
+
+/// Return the string values responsible for an error, if any.
+fn offending_strs(err: &EvalError) -> Vec<String> {
+    let terms = match err {
+        EvalError::BlameError(l, _) => l
+            .arg_thunk
+            .as_ref()
+            .map(|thunk| thunk.get_owned().body)
+            .into_iter()
+            .collect(),
+        EvalError::TypeError(_, _, _, rt) => vec![rt.clone()],
+        EvalError::BinaryTypeError { fst, snd, .. } => vec![fst.clone(), snd.clone()],
+        _ => Vec::new(),
+    };
+
+    terms
+        .into_iter()
+        .filter_map(|term| match term.as_ref() {
+            Term::Str(s) => Some(s.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The main loop of evaluation. See [`eval_closure`].
+fn eval_loop<R>(
     mut clos: Closure,
     global_env: &Environment,
     resolver: &mut R,
     mut enriched_strict: bool,
+    stack: &mut Stack,
 ) -> Result<(RichTerm, Environment), EvalError>
 where
     R: ImportResolver,
 {
     let mut call_stack = CallStack::new();
-    let mut stack = Stack::new();
+    let mut imports = ImportThunks::new();
+    // The token is checked on the first step: an evaluation made of many short runs of the
+    // machine, such as the evaluation of the fields of a record one by one, would never check it
+    // otherwise.
+    let mut steps_until_check = 1;
 
     loop {
         let Closure {
@@ -310,16 +380,25 @@ where
             mut env,
         } = clos;
 
+        steps_until_check -= 1;
+        if steps_until_check == 0 {
+            if resolver.is_cancelled() {
+                return Err(EvalError::Cancelled(pos));
+            }
+            steps_until_check = cancellation::CHECK_INTERVAL;
+        }
+
         if let Some(strict) = stack.pop_strictness_marker() {
             enriched_strict = strict;
         }
 
         clos = match &*shared_term {
             Term::Var(x) => {
-                let mut thunk = env
-                    .get(x)
-                    .or_else(|| global_env.get(x))
-                    .ok_or_else(|| EvalError::UnboundIdentifier(x.clone(), pos))?;
+                let mut thunk = env.get(x).or_else(|| global_env.get(x)).ok_or_else(|| {
+                    let bound = env.iter_elems().chain(global_env.iter_elems());
+                    let closest = suggest::closest_ident(x, bound.map(|(id, _)| id));
+                    EvalError::UnboundIdentifier(x.clone(), pos, closest)
+                })?;
                 std::mem::drop(env); // thunk may be a 1RC pointer
 
                 if thunk.state() != ThunkState::Evaluated {
@@ -327,7 +406,8 @@ where
                         match thunk.mk_update_frame() {
                             Ok(thunk_upd) => stack.push_thunk(thunk_upd),
                             Err(BlackholedError) => {
-                                return Err(EvalError::InfiniteRecursion(call_stack, pos))
+                                call_stack.enter_var(thunk.ident_kind(), x.clone(), pos);
+                                return Err(EvalError::InfiniteRecursion(call_stack, pos));
                             }
                         }
                     }
@@ -563,7 +643,7 @@ where
                         },
                         env,
                     };
-                    update_thunks(&mut stack, &update_closure);
+                    update_thunks(stack, &update_closure);
 
                     let Closure {
                         body: RichTerm { term, .. },
@@ -572,8 +652,20 @@ where
 
                     match term.into_owned() {
                         Term::MetaValue(MetaValue {
-                            value: Some(inner), ..
-                        }) => Closure { body: inner, env },
+                            value: Some(inner),
+                            sensitive,
+                            ..
+                        }) => {
+                            if sensitive {
+                                stack.push_sensitive();
+
+                                if let Some(span) = inner.pos.into_opt() {
+                                    resolver.add_sensitive_span(span);
+                                }
+                            }
+
+                            Closure { body: inner, env }
+                        }
                         _ => unreachable!(),
                     }
                 } else {
@@ -585,22 +677,35 @@ where
                     return Err(EvalError::MissingFieldDef(label, call_stack));
                 }
             }
-            Term::ResolvedImport(id) => {
-                if let Some(t) = resolver.get(*id) {
-                    Closure::atomic_closure(t)
-                } else {
-                    return Err(EvalError::InternalError(
-                        format!("Resolved import not found ({:?})", id),
+            Term::ResolvedImport(id) => enter_import(*id, pos, resolver, stack, &mut imports)?,
+            Term::Import(path) => {
+                // An unresolved import is only encountered if imports are resolved lazily. The
+                // path is relative to the file where the import appears.
+                let parent = pos
+                    .as_opt_ref()
+                    .map(|span| PathBuf::from(resolver.get_path(span.src_id)));
+                let id = resolver
+                    .resolve_lazy(path, parent, &pos)
+                    .map_err(EvalError::ImportError)?;
+
+                enter_import(id, pos, resolver, stack, &mut imports)?
+            }
+            // A sensitive value has been evaluated: record it, and proceed with the evaluation
+            _ if stack.is_top_sensitive() => {
+                stack.pop_sensitive();
+
+                if let Term::Str(s) = &*shared_term {
+                    resolver.add_sensitive(s.clone());
+                }
+
+                Closure {
+                    body: RichTerm {
+                        term: shared_term,
                         pos,
-                    ));
+                    },
+                    env,
                 }
             }
-            Term::Import(path) => {
-                return Err(EvalError::InternalError(
-                    format!("Unresolved import ({})", path.to_string_lossy()),
-                    pos,
-                ))
-            }
             // Continuation of operations and thunk update
             _ if stack.is_top_thunk() || stack.is_top_cont() => {
                 clos = Closure {
@@ -611,10 +716,10 @@ where
                     env,
                 };
                 if stack.is_top_thunk() {
-                    update_thunks(&mut stack, &clos);
+                    update_thunks(stack, &clos);
                     clos
                 } else {
-                    continuate_operation(clos, &mut stack, &mut call_stack)?
+                    continuate_operation(clos, stack, &mut call_stack)?
                 }
             }
             // Function call
@@ -649,6 +754,64 @@ where
     }
 }
 
+/// The thunks of the files imported during an evaluation, together with the order in which they
+/// were first imported. An imported file is evaluated once, as a let-bound variable would be.
+type ImportThunks = HashMap<FileId, (usize, Thunk)>;
+
+/// Return the closure to evaluate for an import of the file `id`, and push an update frame if the
+/// file hasn't been evaluated yet.
+///
+/// Files may import each other, as long as the evaluation terminates: importing a file again
+/// while it is being evaluated, that is re-entering its black-holed thunk, is an import cycle.
+fn enter_import<R>(
+    id: FileId,
+    pos: TermPos,
+    resolver: &R,
+    stack: &mut Stack,
+    imports: &mut ImportThunks,
+) -> Result<Closure, EvalError>
+where
+    R: ImportResolver,
+{
+    if !imports.contains_key(&id) {
+        let t = resolver.get(id).ok_or_else(|| {
+            EvalError::InternalError(format!("Resolved import not found ({:?})", id), pos)
+        })?;
+        let thunk = Thunk::new(Closure::atomic_closure(t), IdentKind::Let);
+        imports.insert(id, (imports.len(), thunk));
+    }
+
+    let (order, mut thunk) = imports[&id].clone();
+
+    if thunk.state() != ThunkState::Evaluated {
+        match thunk.mk_update_frame() {
+            Ok(thunk_upd) => stack.push_thunk(thunk_upd),
+            Err(BlackholedError) => {
+                // The files being evaluated are the ones whose thunk is black-holed, nested in
+                // the order of their import.
+                let mut cycle: Vec<(usize, FileId)> = imports
+                    .iter()
+                    .filter(|(_, (other, thunk))| {
+                        *other >= order && thunk.state() == ThunkState::Blackholed
+                    })
+                    .map(|(id, (order, _))| (*order, *id))
+                    .collect();
+                cycle.sort_unstable();
+
+                let files = cycle
+                    .into_iter()
+                    .map(|(_, id)| id)
+                    .chain(std::iter::once(id))
+                    .map(|id| resolver.get_path(id).to_string_lossy().into_owned())
+                    .collect();
+                return Err(EvalError::ImportError(ImportError::ImportCycle(files, pos)));
+            }
+        }
+    }
+
+    Ok(thunk.into_closure())
+}
+
 /// Pop and update all the thunks on the top of the stack with the given closure.
 fn update_thunks(stack: &mut Stack, closure: &Closure) {
     while let Some(thunk) = stack.pop_thunk() {
@@ -658,6 +821,18 @@ fn update_thunks(stack: &mut Stack, closure: &Closure) {
 
 /// Recursively substitute each variable occurrence of a term for its value in the environment.
 pub fn subst(rt: RichTerm, global_env: &Environment, env: &Environment) -> RichTerm {
+    subst_cancellable(rt, global_env, env, &|| false)
+        .expect("eval::subst(): a substitution which can't be cancelled failed")
+}
+
+/// Same as [`subst`], but the substitution of a large term can be cancelled: `is_cancelled` is
+/// checked regularly, and the substitution fails with [`EvalError::Cancelled`] once it holds.
+pub fn subst_cancellable(
+    rt: RichTerm,
+    global_env: &Environment,
+    env: &Environment,
+    is_cancelled: &dyn Fn() -> bool,
+) -> Result<RichTerm, EvalError> {
     use std::borrow::Cow;
     use std::collections::HashSet;
 
@@ -667,8 +842,14 @@ pub fn subst(rt: RichTerm, global_env: &Environment, env: &Environment) -> RichT
         rt: RichTerm,
         global_env: &Environment,
         env: &Environment,
+        check: &cancellation::Check,
         bound: Cow<HashSet<Ident>>,
     ) -> RichTerm {
+        // Once cancelled, the rest of the term is left as it is: the result is discarded anyway.
+        if check.is_cancelled() {
+            return rt;
+        }
+
         let RichTerm { term, pos } = rt;
         match term.into_owned() {
             Term::Var(id) if !bound.as_ref().contains(&id) => env
@@ -676,7 +857,7 @@ pub fn subst(rt: RichTerm, global_env: &Environment, env: &Environment) -> RichT
                 .or_else(|| global_env.get(&id))
                 .map(|thunk| {
                     let closure = thunk.get_owned();
-                    subst_(closure.body, global_env, &closure.env, bound)
+                    subst_(closure.body, global_env, &closure.env, check, bound)
                 })
                 .unwrap_or_else(|| RichTerm::new(Term::Var(id), pos)),
             v @ Term::Null
@@ -694,56 +875,56 @@ pub fn subst(rt: RichTerm, global_env: &Environment, env: &Environment) -> RichT
             | v @ Term::Import(_)
             | v @ Term::ResolvedImport(_) => RichTerm::new(v, pos),
             Term::Let(id, t1, t2, btype) => {
-                let t1 = subst_(t1, global_env, env, Cow::Borrowed(bound.as_ref()));
-                let t2 = subst_(t2, global_env, env, bound);
+                let t1 = subst_(t1, global_env, env, check, Cow::Borrowed(bound.as_ref()));
+                let t2 = subst_(t2, global_env, env, check, bound);
 
                 RichTerm::new(Term::Let(id, t1, t2, btype), pos)
             }
             p @ Term::LetPattern(..) => panic!("Pattern {:?} has not been transformed before evaluation", p),
             p @ Term::FunPattern(..) => panic!("Pattern {:?} has not been transformed before evaluation", p),
             Term::App(t1, t2) => {
-                let t1 = subst_(t1, global_env, env, Cow::Borrowed(bound.as_ref()));
-                let t2 = subst_(t2, global_env, env, bound);
+                let t1 = subst_(t1, global_env, env, check, Cow::Borrowed(bound.as_ref()));
+                let t2 = subst_(t2, global_env, env, check, bound);
 
                 RichTerm::new(Term::App(t1, t2), pos)
             }
             Term::Switch(t, cases, default) => {
                 let default =
-                    default.map(|d| subst_(d, global_env, env, Cow::Borrowed(bound.as_ref())));
+                    default.map(|d| subst_(d, global_env, env, check, Cow::Borrowed(bound.as_ref())));
                 let cases = cases
                     .into_iter()
                     .map(|(id, t)| {
                         (
                             id,
-                            subst_(t, global_env, env, Cow::Borrowed(bound.as_ref())),
+                            subst_(t, global_env, env, check, Cow::Borrowed(bound.as_ref())),
                         )
                     })
                     .collect();
-                let t = subst_(t, global_env, env, bound);
+                let t = subst_(t, global_env, env, check, bound);
 
                 RichTerm::new(Term::Switch(t, cases, default), pos)
             }
             Term::Op1(op, t) => {
-                let t = subst_(t, global_env, env, bound);
+                let t = subst_(t, global_env, env, check, bound);
 
                 RichTerm::new(Term::Op1(op, t), pos)
             }
             Term::Op2(op, t1, t2) => {
-                let t1 = subst_(t1, global_env, env, Cow::Borrowed(bound.as_ref()));
-                let t2 = subst_(t2, global_env, env, bound);
+                let t1 = subst_(t1, global_env, env, check, Cow::Borrowed(bound.as_ref()));
+                let t2 = subst_(t2, global_env, env, check, bound);
 
                 RichTerm::new(Term::Op2(op, t1, t2), pos)
             }
             Term::OpN(op, ts) => {
                 let ts = ts
                     .into_iter()
-                    .map(|t| subst_(t, global_env, env, Cow::Borrowed(bound.as_ref())))
+                    .map(|t| subst_(t, global_env, env, check, Cow::Borrowed(bound.as_ref())))
                     .collect();
 
                 RichTerm::new(Term::OpN(op, ts), pos)
             }
             Term::Wrapped(i, t) => {
-                let t = subst_(t, global_env, env, bound);
+                let t = subst_(t, global_env, env, check, bound);
 
                 RichTerm::new(Term::Wrapped(i, t), pos)
             }
@@ -753,7 +934,7 @@ pub fn subst(rt: RichTerm, global_env: &Environment, env: &Environment) -> RichT
                     .map(|(id, t)| {
                         (
                             id,
-                            subst_(t, global_env, env, Cow::Borrowed(bound.as_ref())),
+                            subst_(t, global_env, env, check, Cow::Borrowed(bound.as_ref())),
                         )
                     })
                     .collect();
@@ -766,7 +947,7 @@ pub fn subst(rt: RichTerm, global_env: &Environment, env: &Environment) -> RichT
                     .map(|(id, t)| {
                         (
                             id,
-                            subst_(t, global_env, env, Cow::Borrowed(bound.as_ref())),
+                            subst_(t, global_env, env, check, Cow::Borrowed(bound.as_ref())),
                         )
                     })
                     .collect();
@@ -775,8 +956,8 @@ pub fn subst(rt: RichTerm, global_env: &Environment, env: &Environment) -> RichT
                     .into_iter()
                     .map(|(id_t, t)| {
                         (
-                            subst_(id_t, global_env, env, Cow::Borrowed(bound.as_ref())),
-                            subst_(t, global_env, env, Cow::Borrowed(bound.as_ref())),
+                            subst_(id_t, global_env, env, check, Cow::Borrowed(bound.as_ref())),
+                            subst_(t, global_env, env, check, Cow::Borrowed(bound.as_ref())),
                         )
                     })
                     .collect();
@@ -786,7 +967,7 @@ pub fn subst(rt: RichTerm, global_env: &Environment, env: &Environment) -> RichT
             Term::Array(ts) => {
                 let ts = ts
                     .into_iter()
-                    .map(|t| subst_(t, global_env, env, Cow::Borrowed(bound.as_ref())))
+                    .map(|t| subst_(t, global_env, env, check, Cow::Borrowed(bound.as_ref())))
                     .collect();
 
                 RichTerm::new(Term::Array(ts), pos)
@@ -797,7 +978,7 @@ pub fn subst(rt: RichTerm, global_env: &Environment, env: &Environment) -> RichT
                     .map(|chunk| match chunk {
                         chunk @ StrChunk::Literal(_) => chunk,
                         StrChunk::Expr(t, indent) => StrChunk::Expr(
-                            subst_(t, global_env, env, Cow::Borrowed(bound.as_ref())),
+                            subst_(t, global_env, env, check, Cow::Borrowed(bound.as_ref())),
                             indent,
                         ),
                     })
@@ -842,7 +1023,7 @@ pub fn subst(rt: RichTerm, global_env: &Environment, env: &Environment) -> RichT
                 //     Contract { types, ..ctr }
                 // });
 
-                let value = meta.value.map(|t| subst_(t, global_env, env, bound));
+                let value = meta.value.map(|t| subst_(t, global_env, env, check, bound));
 
                 let meta = MetaValue {
                     doc: meta.doc,
@@ -855,7 +1036,15 @@ pub fn subst(rt: RichTerm, global_env: &Environment, env: &Environment) -> RichT
         }
     }
 
-    subst_(rt, global_env, env, Cow::Owned(HashSet::new()))
+    let pos = rt.pos;
+    let check = cancellation::Check::new(is_cancelled);
+    let result = subst_(rt, global_env, env, &check, Cow::Owned(HashSet::new()));
+
+    if check.cancelled() {
+        Err(EvalError::Cancelled(pos))
+    } else {
+        Ok(result)
+    }
 }
 
 #[cfg(test)]
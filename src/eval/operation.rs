@@ -16,13 +16,13 @@ use super::{
 use crate::{
     error::EvalError,
     identifier::Ident,
-    label::ty_path,
+    label::{field_path, ty_path},
     match_sharedterm, mk_app, mk_fun, mk_opn, mk_record,
     position::TermPos,
     serialize,
-    serialize::ExportFormat,
+    serialize::{DuplicateKeys, ExportFormat, ExportStyle},
     term::make as mk_term,
-    term::{BinaryOp, NAryOp, RichTerm, StrChunk, Term, UnaryOp},
+    term::{BinaryOp, NAryOp, RichTerm, SharedTerm, StrChunk, Term, UnaryOp},
     transform::Closurizable,
 };
 use md5::digest::Digest;
@@ -31,6 +31,264 @@ use std::iter::Extend;
 
 generate_counter!(FreshVariableCounter, usize);
 
+/// The maximum length of an array generated by [`NAryOp::ArrayRangeStep`]. Ranges are eagerly
+/// allocated, so this guards against exhausting the memory because of an absurd range.
+const MAX_RANGE_LENGTH: usize = 1 << 24;
+
+/// The largest integer `n` such that all the integers between `-n` and `n` are exactly
+/// representable as a number, that is `2^53 - 1`. Bitwise operations require their operands to be
+/// in this range.
+const MAX_SAFE_INTEGER: f64 = 9_007_199_254_740_991.0;
+
+/// Convert the argument of a bitwise operation to an `i64`, or fail if it isn't an integer in the
+/// safe range (see [`MAX_SAFE_INTEGER`]).
+fn to_safe_integer(n: f64, op_name: &str, arg: &str, pos: TermPos) -> Result<i64, EvalError> {
+    if n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER {
+        Ok(n as i64)
+    } else {
+        Err(EvalError::Other(
+            format!(
+                "{}: expected the {} argument to be an integer between -(2^53 - 1) and 2^53 - 1, got {}",
+                op_name, arg, n
+            ),
+            pos,
+        ))
+    }
+}
+
+/// Build an operand of an [`EvalError::BinaryTypeError`] from its value. The operand is reported
+/// at the position of its original expression, or at the position of its value if there is none.
+fn operand(term: SharedTerm, pos: TermPos, expr_pos: TermPos) -> RichTerm {
+    let pos = if expr_pos.is_def() { expr_pos } else { pos };
+    RichTerm { term, pos }
+}
+
+/// The maximum precision accepted by [`NAryOp::StrFromNum`].
+const MAX_FORMAT_PRECISION: usize = 100;
+
+/// The maximum indentation accepted by [`NAryOp::SerializeWith`].
+const MAX_INDENT: usize = 16;
+
+/// The notation used to format a number, see [`format_num`].
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum NumNotation {
+    /// Fixed notation for numbers whose absolute value is in `[1e-7, 1e21)` or zero, and
+    /// scientific notation otherwise.
+    Auto,
+    /// Always use the fixed notation, e.g. `1234.5`.
+    Fixed,
+    /// Always use the scientific notation, e.g. `1.2345e+3`.
+    Scientific,
+}
+
+/// Format a number as a string. The output doesn't depend on the locale: the decimal separator
+/// is always `.`, there are no digit group separators, and the exponent of the scientific notation
+/// is always introduced by `e` followed by an explicit sign.
+///
+/// `precision` is the number of digits after the decimal point (of the mantissa, for the
+/// scientific notation). If `precision` is `None`, the shortest representation which reads back
+/// to the same number is used. If `strip_trailing_zeros` is set, the zeros at the end of the
+/// fractional part are removed, as well as the decimal point if nothing remains after it.
+///
+/// The number must be finite.
+pub fn format_num(
+    n: f64,
+    precision: Option<usize>,
+    notation: NumNotation,
+    strip_trailing_zeros: bool,
+) -> String {
+    fn strip(s: &str) -> &str {
+        if s.contains('.') {
+            s.trim_end_matches('0').trim_end_matches('.')
+        } else {
+            s
+        }
+    }
+
+    let scientific = match notation {
+        NumNotation::Auto => n != 0.0 && (n.abs() < 1e-7 || n.abs() >= 1e21),
+        NumNotation::Fixed => false,
+        NumNotation::Scientific => true,
+    };
+
+    if scientific {
+        let repr = match precision {
+            Some(p) => format!("{:.*e}", p, n),
+            None => format!("{:e}", n),
+        };
+        // Rust always outputs an exponent, without a sign if the exponent is positive
+        let (mantissa, exp) = repr.split_at(repr.find('e').unwrap());
+        let exp = &exp[1..];
+        let mantissa = if strip_trailing_zeros {
+            strip(mantissa)
+        } else {
+            mantissa
+        };
+        let sign = if exp.starts_with('-') { "" } else { "+" };
+        format!("{}e{}{}", mantissa, sign, exp)
+    } else {
+        let repr = match precision {
+            Some(p) => format!("{:.*}", p, n),
+            None => n.to_string(),
+        };
+
+        if strip_trailing_zeros {
+            String::from(strip(&repr))
+        } else {
+            repr
+        }
+    }
+}
+
+/// Parse a number literal, as produced by [`format_num`]. Are accepted an optional sign, a
+/// mantissa with a `.` as an optional decimal separator, and an optional exponent introduced by `e`
+/// or `E`. In particular, infinite values, `NaN`, and locale-specific forms (such as `1,5` or `1
+/// 000`) are rejected.
+///
+/// In case of error, return the byte offset of the offending character, if any, together with an
+/// error message.
+pub fn parse_num(s: &str) -> Result<f64, (Option<usize>, String)> {
+    let bytes = s.as_bytes();
+    let mut idx = 0;
+
+    let digits = |idx: &mut usize| {
+        let start = *idx;
+        while *idx < bytes.len() && bytes[*idx].is_ascii_digit() {
+            *idx += 1;
+        }
+        *idx - start
+    };
+
+    if idx < bytes.len() && (bytes[idx] == b'+' || bytes[idx] == b'-') {
+        idx += 1;
+    }
+
+    let mut mantissa_digits = digits(&mut idx);
+    if idx < bytes.len() && bytes[idx] == b'.' {
+        idx += 1;
+        mantissa_digits += digits(&mut idx);
+    }
+
+    if mantissa_digits == 0 {
+        return Err(match s[idx..].chars().next() {
+            Some(c) => unexpected_char(c, idx),
+            None => (None, String::from("expected at least one digit")),
+        });
+    }
+
+    if idx < bytes.len() && (bytes[idx] == b'e' || bytes[idx] == b'E') {
+        idx += 1;
+        if idx < bytes.len() && (bytes[idx] == b'+' || bytes[idx] == b'-') {
+            idx += 1;
+        }
+
+        if digits(&mut idx) == 0 {
+            return Err(match s[idx..].chars().next() {
+                Some(c) => unexpected_char(c, idx),
+                None => (
+                    None,
+                    String::from("expected at least one digit in the exponent"),
+                ),
+            });
+        }
+    }
+
+    if let Some(c) = s[idx..].chars().next() {
+        return Err(unexpected_char(c, idx));
+    }
+
+    // The syntax has been validated above, so parsing can only fail by overflowing.
+    match s.parse::<f64>() {
+        Ok(n) if n.is_finite() => Ok(n),
+        _ => Err((None, String::from("the number is too large"))),
+    }
+}
+
+fn unexpected_char(c: char, offset: usize) -> (Option<usize>, String) {
+    let msg = if c == ',' || c == '_' || c.is_whitespace() {
+        format!(
+            "unexpected `{}`. The decimal separator must be `.` and digit group separators are not allowed",
+            c
+        )
+    } else {
+        format!("unexpected `{}`", c)
+    };
+
+    (Some(offset), msg)
+}
+
+/// Convert a 0-based line and a byte offset in this line to a byte offset in `s`. The result is
+/// clamped to the length of `s`, as deserializers may report errors right after the last
+/// character.
+fn line_col_offset(s: &str, line: usize, col: usize) -> usize {
+    let line_start: usize = s.split_inclusive('\n').take(line).map(str::len).sum();
+    std::cmp::min(line_start + col, s.len())
+}
+
+/// Deserialize the string `s` to a term, given the enum tag of a format (`Json`, `Yaml` or
+/// `Toml`). `duplicate_keys` is only relevant for JSON. Return `None` if the tag is not a supported
+/// format.
+fn deserialize(
+    format: &Ident,
+    s: &str,
+    duplicate_keys: DuplicateKeys,
+    pos_op: TermPos,
+) -> Result<Option<RichTerm>, EvalError> {
+    let mk_err = |format: &str, err: String, offset: Option<usize>| {
+        EvalError::DeserializationError(
+            String::from(format),
+            err,
+            pos_op,
+            s.to_owned(),
+            offset,
+            None,
+        )
+    };
+
+    let rt = match format.as_ref() {
+        // Lines are 1-based, and columns are 1-based byte offsets in the line.
+        // A duplicate key is located at its second occurrence, and its first one is reported too.
+        "Json" => serialize::json_from_str(s, duplicate_keys).map_err(|err| {
+            if let Some((first, second)) = err.duplicate_key {
+                return EvalError::DeserializationError(
+                    String::from("json"),
+                    err.error.to_string(),
+                    pos_op,
+                    s.to_owned(),
+                    Some(second.start),
+                    Some(first),
+                );
+            }
+
+            let err = err.error;
+            let offset = err
+                .line()
+                .checked_sub(1)
+                .map(|line| line_col_offset(s, line, err.column().saturating_sub(1)));
+            mk_err("json", err.to_string(), offset)
+        })?,
+        // The index of the location is a number of characters.
+        "Yaml" => serialize::yaml_from_str(s).map_err(|err| {
+            let offset = err.location().map(|loc| {
+                s.char_indices()
+                    .nth(loc.index())
+                    .map_or(s.len(), |(offset, _)| offset)
+            });
+            mk_err("yaml", err.to_string(), offset)
+        })?,
+        // Lines and columns are 0-based, and columns are byte offsets in the line.
+        "Toml" => toml::from_str(s).map_err(|err| {
+            let offset = err
+                .line_col()
+                .map(|(line, col)| line_col_offset(s, line, col));
+            mk_err("toml", err.to_string(), offset)
+        })?,
+        _ => return Ok(None),
+    };
+
+    Ok(Some(rt))
+}
+
 /// Result of the equality of two terms.
 ///
 /// The equality of two terms can either be computed directly for base types (`Num`, `Str`, etc.),
@@ -204,6 +462,14 @@ fn process_unary_operation(
             Term::Bool(matches!(*t, Term::Record(..) | Term::RecRecord(..))),
             pos_op_inh,
         ))),
+        UnaryOp::TypeOf() => {
+            let type_of = t.type_of().unwrap_or_else(|| String::from("Other"));
+
+            Ok(Closure::atomic_closure(RichTerm::new(
+                Term::Enum(Ident::from(type_of)),
+                pos_op_inh,
+            )))
+        }
         UnaryOp::BoolAnd() =>
         // The syntax should not allow partially applied boolean operators.
         {
@@ -269,6 +535,22 @@ fn process_unary_operation(
                 ))
             }
         }
+        UnaryOp::BitNot() => {
+            if let Term::Num(n) = *t {
+                let i = to_safe_integer(n, "bitNot", "1st", arg_pos)?;
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Num(!i as f64),
+                    pos_op_inh,
+                )))
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("Num"),
+                    String::from("bitNot"),
+                    arg_pos,
+                    RichTerm { term: t, pos },
+                ))
+            }
+        }
         UnaryOp::Blame() => match_sharedterm! { t, with {
                 Term::Lbl(label) => Err(
                     EvalError::BlameError(
@@ -311,48 +593,40 @@ fn process_unary_operation(
                 None
             };
 
-            if let Term::Enum(en) = &*t {
-                let Closure {
-                    body:
-                        RichTerm {
-                            term: cases_term, ..
-                        },
+            let Closure {
+                body: RichTerm {
+                    term: cases_term, ..
+                },
+                env: cases_env,
+            } = cases_closure;
+
+            let mut cases = match cases_term.into_owned() {
+                Term::Record(map, _) => map,
+                _ => panic!("invalid argument for switch"),
+            };
+
+            let case = match &*t {
+                Term::Enum(en) => cases.remove(en).map(|body| Closure {
+                    body,
                     env: cases_env,
-                } = cases_closure;
+                }),
+                _ => None,
+            };
 
-                let mut cases = match cases_term.into_owned() {
-                    Term::Record(map, _) => map,
-                    _ => panic!("invalid argument for switch"),
-                };
+            case.or(default).ok_or_else(|| {
+                let mut handled: Vec<Ident> = cases.into_keys().collect();
+                handled.sort();
 
-                cases
-                    .remove(en)
-                    .map(|body| Closure {
-                        body,
-                        env: cases_env,
-                    })
-                    .or(default)
-                    .ok_or_else(||
-                        // ? We should have a dedicated error for unmatched pattern
-                        EvalError::TypeError(
-                            String::from("Enum"),
-                            String::from("switch"),
-                            arg_pos,
-                            RichTerm {
-                                term: t,
-                                pos,
-                            },
-                        ))
-            } else if let Some(clos) = default {
-                Ok(clos)
-            } else {
-                Err(EvalError::TypeError(
-                    String::from("Enum"),
-                    String::from("switch"),
-                    arg_pos,
-                    RichTerm { term: t, pos },
-                ))
-            }
+                EvalError::NonExhaustiveMatch {
+                    // Point to the scrutinee rather than to the definition of its value.
+                    value: RichTerm {
+                        term: t,
+                        pos: if arg_pos.is_def() { arg_pos } else { pos },
+                    },
+                    handled,
+                    pos: pos_op,
+                }
+            })
         }
         UnaryOp::ChangePolarity() => match_sharedterm! {t, with {
                 Term::Lbl(l) => {
@@ -387,6 +661,33 @@ fn process_unary_operation(
                 ))
             }
         }
+        UnaryOp::LabelPath() => {
+            if let Term::Lbl(l) = &*t {
+                let path = l
+                    .path
+                    .iter()
+                    .map(|elem| match elem {
+                        ty_path::Elem::Domain => Term::Enum(Ident::from("Domain")),
+                        ty_path::Elem::Codomain => Term::Enum(Ident::from("Codomain")),
+                        ty_path::Elem::Array => Term::Enum(Ident::from("Element")),
+                        ty_path::Elem::Field(id) => Term::Str(id.to_string()),
+                    })
+                    .map(RichTerm::from)
+                    .collect();
+
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Array(path),
+                    pos_op_inh,
+                )))
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("Label"),
+                    String::from("labelPath"),
+                    arg_pos,
+                    RichTerm { term: t, pos },
+                ))
+            }
+        }
         UnaryOp::GoDom() => match_sharedterm! {t, with {
                 Term::Lbl(l) => {
                     let mut l = l;
@@ -470,12 +771,42 @@ fn process_unary_operation(
                         String::from("(.)"),
                         RichTerm { term: t, pos },
                         pos_op,
+                        None,
                     )), //TODO include the position of operators on the stack
                 }
+            } else {
+                let access_pos = if id.pos.is_def() { id.pos } else { pos_op };
+
+                Err(EvalError::NotARecord {
+                    field: id.label,
+                    value: RichTerm { term: t, pos },
+                    record_pos: arg_pos,
+                    access_pos,
+                })
+            }
+        }
+        UnaryOp::PluckField(id, index) => {
+            if let Term::Record(static_map, ..) = &*t {
+                match static_map.get(&id) {
+                    Some(e) => {
+                        call_stack.enter_field(id, pos, e.pos, pos_op);
+                        Ok(Closure {
+                            body: e.clone(),
+                            env,
+                        })
+                    }
+                    None => Err(EvalError::FieldMissing(
+                        id.label,
+                        String::from("pluck"),
+                        RichTerm { term: t, pos },
+                        pos_op,
+                        Some(index),
+                    )),
+                }
             } else {
                 Err(EvalError::TypeError(
                     String::from("Record"),
-                    String::from("field access"),
+                    format!("pluck, element at index {}", index),
                     arg_pos,
                     RichTerm { term: t, pos },
                 ))
@@ -648,6 +979,13 @@ fn process_unary_operation(
                 Err(EvalError::NotEnoughArgs(2, String::from("seq"), pos_op))
             }
         }
+        UnaryOp::Memo() => {
+            stack.memoize_thunks();
+            Ok(Closure {
+                body: RichTerm { term: t, pos },
+                env,
+            })
+        }
         UnaryOp::DeepSeq(_) => {
             /// Build a closure that forces a given list of terms, and at the end resumes the
             /// evaluation of the argument on the top of the stack. The argument must iterate over
@@ -967,6 +1305,30 @@ fn process_unary_operation(
                 ))
             }
         }
+        UnaryOp::StrToNum() => {
+            if let Term::Str(s) = &*t {
+                let n = parse_num(s).map_err(|(offset, msg)| {
+                    let at = offset
+                        .map(|offset| format!(" at offset {}", offset))
+                        .unwrap_or_default();
+                    EvalError::Other(
+                        format!("strToNum: invalid num literal `{}`{}: {}", s, at, msg),
+                        pos,
+                    )
+                })?;
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Num(n),
+                    pos_op_inh,
+                )))
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("Str"),
+                    String::from("strToNum"),
+                    arg_pos,
+                    RichTerm { term: t, pos },
+                ))
+            }
+        }
         UnaryOp::EnumFromStr() => {
             if let Term::Str(s) = &*t {
                 Ok(Closure::atomic_closure(RichTerm::new(
@@ -1016,66 +1378,32 @@ fn process_binary_operation(
     let pos_op_inh = pos_op.into_inherited();
 
     match b_op {
-        BinaryOp::Plus() => {
-            if let Term::Num(n1) = *t1 {
-                if let Term::Num(n2) = *t2 {
-                    Ok(Closure::atomic_closure(RichTerm::new(
-                        Term::Num(n1 + n2),
-                        pos_op_inh,
-                    )))
-                } else {
-                    Err(EvalError::TypeError(
-                        String::from("Num"),
-                        String::from("+, 2nd argument"),
-                        snd_pos,
-                        RichTerm {
-                            term: t2,
-                            pos: pos2,
-                        },
-                    ))
-                }
-            } else {
-                Err(EvalError::TypeError(
-                    String::from("Num"),
-                    String::from("+, 1st argument"),
-                    fst_pos,
-                    RichTerm {
-                        term: t1,
-                        pos: pos1,
-                    },
-                ))
-            }
-        }
-        BinaryOp::Sub() => {
-            if let Term::Num(n1) = *t1 {
-                if let Term::Num(n2) = *t2 {
-                    Ok(Closure::atomic_closure(RichTerm::new(
-                        Term::Num(n1 - n2),
-                        pos_op_inh,
-                    )))
-                } else {
-                    Err(EvalError::TypeError(
-                        String::from("Num"),
-                        String::from("-, 2nd argument"),
-                        snd_pos,
-                        RichTerm {
-                            term: t2,
-                            pos: pos2,
-                        },
-                    ))
-                }
-            } else {
-                Err(EvalError::TypeError(
-                    String::from("Num"),
-                    String::from("-, 1st argument"),
-                    fst_pos,
-                    RichTerm {
-                        term: t1,
-                        pos: pos1,
-                    },
-                ))
-            }
-        }
+        BinaryOp::Plus() => match (&*t1, &*t2) {
+            (Term::Num(n1), Term::Num(n2)) => Ok(Closure::atomic_closure(RichTerm::new(
+                Term::Num(n1 + n2),
+                pos_op_inh,
+            ))),
+            _ => Err(EvalError::BinaryTypeError {
+                op: String::from("+"),
+                expected: String::from("Num"),
+                fst: operand(t1, pos1, fst_pos),
+                snd: operand(t2, pos2, snd_pos),
+                pos: pos_op,
+            }),
+        },
+        BinaryOp::Sub() => match (&*t1, &*t2) {
+            (Term::Num(n1), Term::Num(n2)) => Ok(Closure::atomic_closure(RichTerm::new(
+                Term::Num(n1 - n2),
+                pos_op_inh,
+            ))),
+            _ => Err(EvalError::BinaryTypeError {
+                op: String::from("-"),
+                expected: String::from("Num"),
+                fst: operand(t1, pos1, fst_pos),
+                snd: operand(t2, pos2, snd_pos),
+                pos: pos_op,
+            }),
+        },
         BinaryOp::Mult() => {
             if let Term::Num(n1) = *t1 {
                 if let Term::Num(n2) = *t2 {
@@ -1200,37 +1528,98 @@ fn process_binary_operation(
                 ))
             }
         }
-        BinaryOp::StrConcat() => {
-            if let Term::Str(s1) = &*t1 {
-                if let Term::Str(s2) = &*t2 {
-                    let ss: [&str; 2] = [s1, s2];
+        BinaryOp::BitAnd()
+        | BinaryOp::BitOr()
+        | BinaryOp::BitXor()
+        | BinaryOp::ShiftLeft()
+        | BinaryOp::ShiftRight() => {
+            let op_name = match b_op {
+                BinaryOp::BitAnd() => "bitAnd",
+                BinaryOp::BitOr() => "bitOr",
+                BinaryOp::BitXor() => "bitXor",
+                BinaryOp::ShiftLeft() => "shiftLeft",
+                _ => "shiftRight",
+            };
+
+            match (&*t1, &*t2) {
+                (Term::Num(n1), Term::Num(n2)) => {
+                    let i1 = to_safe_integer(*n1, op_name, "1st", fst_pos)?;
+                    let i2 = to_safe_integer(*n2, op_name, "2nd", snd_pos)?;
+
+                    let result = match b_op {
+                        BinaryOp::BitAnd() => i1 & i2,
+                        BinaryOp::BitOr() => i1 | i2,
+                        BinaryOp::BitXor() => i1 ^ i2,
+                        _ if i2 < 0 => {
+                            return Err(EvalError::Other(
+                                format!(
+                                    "{}: expected the shift amount to be non-negative, got {}",
+                                    op_name, i2
+                                ),
+                                snd_pos,
+                            ))
+                        }
+                        BinaryOp::ShiftLeft() => {
+                            // The operand is at most 2^53 in absolute value: shifting it by up to
+                            // 64 bits can't overflow an `i128`.
+                            let shifted = if i1 == 0 {
+                                Some(0)
+                            } else if i2 <= 64 {
+                                Some((i1 as i128) << i2)
+                            } else {
+                                None
+                            };
+
+                            match shifted {
+                                Some(s) if s.abs() <= MAX_SAFE_INTEGER as i128 => s as i64,
+                                _ => return Err(EvalError::Other(
+                                    format!("shiftLeft: the result of shifting {} by {} bits is not an integer between -(2^53 - 1) and 2^53 - 1", i1, i2),
+                                    pos_op,
+                                )),
+                            }
+                        }
+                        // Shifting by 63 bits or more gives either 0 or -1, depending on the sign.
+                        _ => i1 >> i2.min(63),
+                    };
+
                     Ok(Closure::atomic_closure(RichTerm::new(
-                        Term::Str(ss.concat()),
+                        Term::Num(result as f64),
                         pos_op_inh,
                     )))
-                } else {
-                    Err(EvalError::TypeError(
-                        String::from("Str"),
-                        String::from("++, 2nd argument"),
-                        snd_pos,
-                        RichTerm {
-                            term: t2,
-                            pos: pos2,
-                        },
-                    ))
                 }
-            } else {
-                Err(EvalError::TypeError(
-                    String::from("Str"),
-                    String::from("++, 1st argument"),
+                (Term::Num(_), _) => Err(EvalError::TypeError(
+                    String::from("Num"),
+                    format!("{}, 2nd argument", op_name),
+                    snd_pos,
+                    RichTerm {
+                        term: t2,
+                        pos: pos2,
+                    },
+                )),
+                (_, _) => Err(EvalError::TypeError(
+                    String::from("Num"),
+                    format!("{}, 1st argument", op_name),
                     fst_pos,
                     RichTerm {
                         term: t1,
                         pos: pos1,
                     },
-                ))
+                )),
             }
         }
+        BinaryOp::StrConcat() => match (&*t1, &*t2) {
+            (Term::Str(s1), Term::Str(s2)) => Ok(Closure::atomic_closure(RichTerm::new(
+                Term::Str([s1.as_str(), s2.as_str()].concat()),
+                pos_op_inh,
+            ))),
+            _ => Err(EvalError::BinaryTypeError {
+                op: String::from("++"),
+                expected: String::from("Str"),
+                fst: operand(t1, pos1, fst_pos),
+                snd: operand(t2, pos2, snd_pos),
+                pos: pos_op,
+            }),
+        },
         BinaryOp::Assume() => {
             if let Term::Lbl(l) = &*t2 {
                 // Track the contract argument for better error reporting, and push back the label
@@ -1364,17 +1753,72 @@ fn process_binary_operation(
                 ))
             }
         },
-        BinaryOp::Eq() => {
-            let mut env = Environment::new();
-
-            let c1 = Closure {
-                body: RichTerm {
-                    term: t1,
-                    pos: pos1,
-                },
-                env: env1,
-            };
-            let c2 = Closure {
+        BinaryOp::WithExample() => match_sharedterm! {t2, with {
+                Term::Lbl(l) => {
+                    let mut l = l;
+                    l.example_pos = pos1;
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Lbl(l),
+                        pos_op_inh,
+                    )))
+                }
+            } else {
+                Err(EvalError::TypeError(
+                    String::from("Label"),
+                    String::from("with_example, 2nd argument"),
+                    snd_pos,
+                    RichTerm {
+                        term: t2,
+                        pos: pos2,
+                    },
+                ))
+            }
+        },
+        BinaryOp::WithFields() => match (&*t1, &*t2) {
+            (Term::Record(fields, _), Term::Lbl(l)) => {
+                let mut l = l.clone();
+                l.fields = fields.keys().cloned().collect();
+                l.fields.sort_by_key(|id| {
+                    (
+                        id.pos.as_opt_ref().map(|span| (span.src_id, span.start)),
+                        id.label.clone(),
+                    )
+                });
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Lbl(l),
+                    pos_op_inh,
+                )))
+            }
+            (Term::Record(..), _) => Err(EvalError::TypeError(
+                String::from("Label"),
+                String::from("with_fields, 2nd argument"),
+                snd_pos,
+                RichTerm {
+                    term: t2,
+                    pos: pos2,
+                },
+            )),
+            _ => Err(EvalError::TypeError(
+                String::from("Record"),
+                String::from("with_fields, 1st argument"),
+                fst_pos,
+                RichTerm {
+                    term: t1,
+                    pos: pos1,
+                },
+            )),
+        },
+        BinaryOp::Eq() => {
+            let mut env = Environment::new();
+
+            let c1 = Closure {
+                body: RichTerm {
+                    term: t1,
+                    pos: pos1,
+                },
+                env: env1,
+            };
+            let c2 = Closure {
                 body: RichTerm {
                     term: t2,
                     pos: pos2,
@@ -1415,131 +1859,64 @@ fn process_binary_operation(
                 }
             }
         }
-        BinaryOp::LessThan() => {
-            if let Term::Num(n1) = *t1 {
-                if let Term::Num(n2) = *t2 {
-                    Ok(Closure::atomic_closure(RichTerm::new(
-                        Term::Bool(n1 < n2),
-                        pos_op_inh,
-                    )))
-                } else {
-                    Err(EvalError::TypeError(
-                        String::from("Num"),
-                        String::from("<, 2nd argument"),
-                        snd_pos,
-                        RichTerm {
-                            term: t2,
-                            pos: pos2,
-                        },
-                    ))
-                }
-            } else {
-                Err(EvalError::TypeError(
-                    String::from("Num"),
-                    String::from("<, 1st argument"),
-                    fst_pos,
-                    RichTerm {
-                        term: t1,
-                        pos: pos1,
-                    },
-                ))
-            }
-        }
-        BinaryOp::LessOrEq() => {
-            if let Term::Num(n1) = *t1 {
-                if let Term::Num(n2) = *t2 {
-                    Ok(Closure::atomic_closure(RichTerm::new(
-                        Term::Bool(n1 <= n2),
-                        pos_op_inh,
-                    )))
-                } else {
-                    Err(EvalError::TypeError(
-                        String::from("Num"),
-                        String::from("<, 2nd argument"),
-                        snd_pos,
-                        RichTerm {
-                            term: t2,
-                            pos: pos2,
-                        },
-                    ))
-                }
-            } else {
-                Err(EvalError::TypeError(
-                    String::from("Num"),
-                    String::from("<, 1st argument"),
-                    fst_pos,
-                    RichTerm {
-                        term: t1,
-                        pos: pos1,
-                    },
-                ))
-            }
-        }
-        BinaryOp::GreaterThan() => {
-            if let Term::Num(n1) = *t1 {
-                if let Term::Num(n2) = *t2 {
-                    Ok(Closure::atomic_closure(RichTerm::new(
-                        Term::Bool(n1 > n2),
-                        pos_op_inh,
-                    )))
-                } else {
-                    Err(EvalError::TypeError(
-                        String::from("Num"),
-                        String::from(">, 2nd argument"),
-                        snd_pos,
-                        RichTerm {
-                            term: t2,
-                            pos: pos2,
-                        },
-                    ))
-                }
-            } else {
-                Err(EvalError::TypeError(
-                    String::from("Num"),
-                    String::from(">, 1st argument"),
-                    fst_pos,
-                    RichTerm {
-                        term: t1,
-                        pos: pos1,
-                    },
-                ))
-            }
-        }
-        BinaryOp::GreaterOrEq() => {
-            if let Term::Num(n1) = *t1 {
-                if let Term::Num(n2) = *t2 {
-                    Ok(Closure::atomic_closure(RichTerm::new(
-                        Term::Bool(n1 >= n2),
-                        pos_op_inh,
-                    )))
-                } else {
-                    Err(EvalError::TypeError(
-                        String::from("Num"),
-                        String::from(">=, 2nd argument"),
-                        snd_pos,
-                        RichTerm {
-                            term: t2,
-                            pos: pos2,
-                        },
-                    ))
-                }
-            } else {
-                Err(EvalError::TypeError(
-                    String::from("Num"),
-                    String::from(">=, 1st argument"),
-                    fst_pos,
-                    RichTerm {
-                        term: t1,
-                        pos: pos1,
-                    },
-                ))
-            }
-        }
+        BinaryOp::LessThan() => match (&*t1, &*t2) {
+            (Term::Num(n1), Term::Num(n2)) => Ok(Closure::atomic_closure(RichTerm::new(
+                Term::Bool(n1 < n2),
+                pos_op_inh,
+            ))),
+            _ => Err(EvalError::BinaryTypeError {
+                op: String::from("<"),
+                expected: String::from("Num"),
+                fst: operand(t1, pos1, fst_pos),
+                snd: operand(t2, pos2, snd_pos),
+                pos: pos_op,
+            }),
+        },
+        BinaryOp::LessOrEq() => match (&*t1, &*t2) {
+            (Term::Num(n1), Term::Num(n2)) => Ok(Closure::atomic_closure(RichTerm::new(
+                Term::Bool(n1 <= n2),
+                pos_op_inh,
+            ))),
+            _ => Err(EvalError::BinaryTypeError {
+                op: String::from("<="),
+                expected: String::from("Num"),
+                fst: operand(t1, pos1, fst_pos),
+                snd: operand(t2, pos2, snd_pos),
+                pos: pos_op,
+            }),
+        },
+        BinaryOp::GreaterThan() => match (&*t1, &*t2) {
+            (Term::Num(n1), Term::Num(n2)) => Ok(Closure::atomic_closure(RichTerm::new(
+                Term::Bool(n1 > n2),
+                pos_op_inh,
+            ))),
+            _ => Err(EvalError::BinaryTypeError {
+                op: String::from(">"),
+                expected: String::from("Num"),
+                fst: operand(t1, pos1, fst_pos),
+                snd: operand(t2, pos2, snd_pos),
+                pos: pos_op,
+            }),
+        },
+        BinaryOp::GreaterOrEq() => match (&*t1, &*t2) {
+            (Term::Num(n1), Term::Num(n2)) => Ok(Closure::atomic_closure(RichTerm::new(
+                Term::Bool(n1 >= n2),
+                pos_op_inh,
+            ))),
+            _ => Err(EvalError::BinaryTypeError {
+                op: String::from(">="),
+                expected: String::from("Num"),
+                fst: operand(t1, pos1, fst_pos),
+                snd: operand(t2, pos2, snd_pos),
+                pos: pos_op,
+            }),
+        },
         BinaryOp::GoField() => match_sharedterm! {t1, with {
                 Term::Str(field) => match_sharedterm! {t2, with {
                         Term::Lbl(l) => {
                             let mut l = l;
-                            l.path.push(ty_path::Elem::Field(Ident::from(field)));
+                            l.path.push(ty_path::Elem::Field(Ident::from(&field)));
+                            l.field_path.0.push(field_path::Elem::Field(Ident::from(field)));
                             Ok(Closure::atomic_closure(RichTerm::new(
                                 Term::Lbl(l),
                                 pos_op_inh,
@@ -1569,6 +1946,42 @@ fn process_binary_operation(
                 ))
             }
         },
+        BinaryOp::GoArrayElem() => match (&*t1, &*t2) {
+            (Term::Num(n), Term::Lbl(l)) if *n >= 0.0 && n.fract() == 0.0 => {
+                let mut l = l.clone();
+                l.path.push(ty_path::Elem::Array);
+                l.field_path.0.push(field_path::Elem::Index(*n as usize));
+                Ok(Closure::atomic_closure(RichTerm::new(
+                    Term::Lbl(l),
+                    pos_op_inh,
+                )))
+            }
+            (Term::Num(n), Term::Lbl(_)) => Err(EvalError::Other(
+                format!(
+                    "go_array_elem: expected the index to be a non-negative integer, got {}",
+                    n
+                ),
+                pos_op,
+            )),
+            (Term::Num(_), _) => Err(EvalError::TypeError(
+                String::from("Label"),
+                String::from("go_array_elem, 2nd argument"),
+                snd_pos,
+                RichTerm {
+                    term: t2,
+                    pos: pos2,
+                },
+            )),
+            (_, _) => Err(EvalError::TypeError(
+                String::from("Num"),
+                String::from("go_array_elem, 1st argument"),
+                fst_pos,
+                RichTerm {
+                    term: t1,
+                    pos: pos1,
+                },
+            )),
+        },
         BinaryOp::DynAccess() => match_sharedterm! {t1, with {
                 Term::Str(id) => {
                     if let Term::Record(static_map, _attrs) = &*t2 {
@@ -1588,18 +2001,19 @@ fn process_binary_operation(
                                     pos: pos2,
                                 },
                                 pos_op,
+                                None,
                             )),
                         }
                     } else {
-                        Err(EvalError::TypeError(
-                            String::from("Record"),
-                            String::from(".$"),
-                            snd_pos,
-                            RichTerm {
+                        Err(EvalError::NotARecord {
+                            field: id,
+                            value: RichTerm {
                                 term: t2,
                                 pos: pos2,
                             },
-                        ))
+                            record_pos: snd_pos,
+                            access_pos: fst_pos,
+                        })
                     }
                 }
             } else {
@@ -1669,6 +2083,7 @@ fn process_binary_operation(
                                         pos2,
                                     ),
                                     pos_op,
+                                    None,
                                 )),
                                 Some(_) => Ok(Closure {
                                     body: RichTerm::new(Term::Record(static_map, attrs), pos_op_inh),
@@ -1731,6 +2146,61 @@ fn process_binary_operation(
                 ))
             }
         },
+        BinaryOp::RecordDiff() | BinaryOp::RecordIntersect() | BinaryOp::RecordKeysEqual() => {
+            let op_name = match b_op {
+                BinaryOp::RecordDiff() => "recordDiff",
+                BinaryOp::RecordIntersect() => "recordIntersect",
+                _ => "recordKeysEqual",
+            };
+
+            match_sharedterm! {t1, with {
+                    Term::Record(fields1, attrs) => match &*t2 {
+                        Term::Record(fields2, _) => {
+                            // Only the names of the fields are compared: their values stay
+                            // unevaluated, as closures in their original environment.
+                            let result = match b_op {
+                                BinaryOp::RecordKeysEqual() => Term::Bool(
+                                    fields1.len() == fields2.len()
+                                        && fields1.keys().all(|id| fields2.contains_key(id)),
+                                ),
+                                _ => {
+                                    let keep_common = matches!(b_op, BinaryOp::RecordIntersect());
+                                    let fields = fields1
+                                        .into_iter()
+                                        .filter(|(id, _)| fields2.contains_key(id) == keep_common)
+                                        .collect();
+                                    Term::Record(fields, attrs)
+                                }
+                            };
+
+                            Ok(Closure {
+                                body: RichTerm::new(result, pos_op_inh),
+                                env: env1,
+                            })
+                        }
+                        _ => Err(EvalError::TypeError(
+                            String::from("Record"),
+                            format!("{}, 2nd argument", op_name),
+                            snd_pos,
+                            RichTerm {
+                                term: t2,
+                                pos: pos2,
+                            },
+                        )),
+                    }
+                } else {
+                    Err(EvalError::TypeError(
+                        String::from("Record"),
+                        format!("{}, 1st argument", op_name),
+                        fst_pos,
+                        RichTerm {
+                            term: t1,
+                            pos: pos1,
+                        },
+                    ))
+                }
+            }
+        }
         BinaryOp::ArrayConcat() => match_sharedterm! {t1, with {
                 Term::Array(ts1) => match_sharedterm! {t2, with {
                         Term::Array(ts2) => {
@@ -1751,28 +2221,24 @@ fn process_binary_operation(
                             })
                         }
                     } else {
-                        Err(EvalError::TypeError(
-                            String::from("Array"),
-                            String::from("@, 2nd operand"),
-                            snd_pos,
-                            RichTerm {
-                                term: t2,
-                                pos: pos2,
-                            },
-                        ))
-
+                        // The first operand has been moved out: put it back together.
+                        Err(EvalError::BinaryTypeError {
+                            op: String::from("@"),
+                            expected: String::from("Array"),
+                            fst: operand(SharedTerm::new(Term::Array(ts1)), pos1, fst_pos),
+                            snd: operand(t2, pos2, snd_pos),
+                            pos: pos_op,
+                        })
                     }
                 },
             } else {
-                Err(EvalError::TypeError(
-                    String::from("Array"),
-                    String::from("@, 1st operand"),
-                    fst_pos,
-                    RichTerm {
-                        term: t1,
-                        pos: pos1,
-                    },
-                ))
+                Err(EvalError::BinaryTypeError {
+                    op: String::from("@"),
+                    expected: String::from("Array"),
+                    fst: operand(t1, pos1, fst_pos),
+                    snd: operand(t2, pos2, snd_pos),
+                    pos: pos_op,
+                })
             }
         },
         BinaryOp::ArrayElemAt() => match (&*t1, &*t2) {
@@ -1808,6 +2274,104 @@ fn process_binary_operation(
                 },
             )),
         },
+        BinaryOp::ArrayChunks() | BinaryOp::ArrayWindows() => {
+            let op_name = if let BinaryOp::ArrayChunks() = b_op {
+                "chunks"
+            } else {
+                "windows"
+            };
+
+            match (&*t1, &*t2) {
+                (Term::Array(ts), Term::Num(n)) => {
+                    let size = *n as usize;
+                    if n.fract() != 0.0 {
+                        Err(EvalError::Other(format!("{}: expected the 2nd argument to be an integer, got the floating-point value {}", op_name, n), pos_op))
+                    } else if *n <= 0.0 {
+                        Err(EvalError::Other(
+                            format!(
+                                "{}: expected the size to be strictly positive, got {}",
+                                op_name, n
+                            ),
+                            pos_op,
+                        ))
+                    } else {
+                        let mk_array =
+                            |slice: &[RichTerm]| RichTerm::from(Term::Array(slice.to_vec()));
+                        let result: Vec<RichTerm> = if let BinaryOp::ArrayChunks() = b_op {
+                            ts.chunks(size).map(mk_array).collect()
+                        } else {
+                            ts.windows(size).map(mk_array).collect()
+                        };
+
+                        Ok(Closure {
+                            body: RichTerm::new(Term::Array(result), pos_op_inh),
+                            env: env1,
+                        })
+                    }
+                }
+                (Term::Array(_), _) => Err(EvalError::TypeError(
+                    String::from("Num"),
+                    format!("{}, 2nd argument", op_name),
+                    snd_pos,
+                    RichTerm {
+                        term: t2,
+                        pos: pos2,
+                    },
+                )),
+                (_, _) => Err(EvalError::TypeError(
+                    String::from("Array"),
+                    format!("{}, 1st argument", op_name),
+                    fst_pos,
+                    RichTerm {
+                        term: t1,
+                        pos: pos1,
+                    },
+                )),
+            }
+        }
+        BinaryOp::ArrayPluck() => match (&*t1, &*t2) {
+            (Term::Str(field), Term::Array(ts)) => {
+                let mut shared_env = Environment::new();
+                let id = Ident::from(field);
+
+                // As for `ArrayMap`, the elements are closurized to preserve lazyness: a field is
+                // only accessed when the corresponding element of the result is evaluated.
+                let ts = ts
+                    .iter()
+                    .enumerate()
+                    .map(|(index, t)| {
+                        RichTerm::new(
+                            Term::Op1(UnaryOp::PluckField(id.clone(), index), t.clone()),
+                            pos_op_inh,
+                        )
+                        .closurize(&mut shared_env, env2.clone())
+                    })
+                    .collect();
+
+                Ok(Closure {
+                    body: RichTerm::new(Term::Array(ts), pos_op_inh),
+                    env: shared_env,
+                })
+            }
+            (Term::Str(_), _) => Err(EvalError::TypeError(
+                String::from("Array"),
+                String::from("pluck, 2nd argument"),
+                snd_pos,
+                RichTerm {
+                    term: t2,
+                    pos: pos2,
+                },
+            )),
+            (_, _) => Err(EvalError::TypeError(
+                String::from("Str"),
+                String::from("pluck, 1st argument"),
+                fst_pos,
+                RichTerm {
+                    term: t1,
+                    pos: pos1,
+                },
+            )),
+        },
         BinaryOp::Merge() => merge(
             RichTerm {
                 term: t1,
@@ -1822,6 +2386,34 @@ fn process_binary_operation(
             pos_op,
             MergeMode::Standard,
         ),
+        BinaryOp::MergeOverride() => merge(
+            RichTerm {
+                term: t1,
+                pos: pos1,
+            },
+            env1,
+            RichTerm {
+                term: t2,
+                pos: pos2,
+            },
+            env2,
+            pos_op,
+            MergeMode::Override,
+        ),
+        BinaryOp::MergeContractField(path) => merge(
+            RichTerm {
+                term: t1,
+                pos: pos1,
+            },
+            env1,
+            RichTerm {
+                term: t2,
+                pos: pos2,
+            },
+            env2,
+            pos_op,
+            MergeMode::ContractField(path),
+        ),
 
         BinaryOp::Hash() => {
             let mk_err_fst = |t1| {
@@ -1915,7 +2507,7 @@ fn process_binary_operation(
 
                 serialize::validate(format, &rt2)?;
                 Ok(Closure::atomic_closure(RichTerm::new(
-                    Term::Str(serialize::to_string(format, &rt2)?),
+                    Term::Str(serialize::to_string(format, ExportStyle::default(), &rt2)?),
                     pos_op_inh,
                 )))
             } else {
@@ -1937,29 +2529,9 @@ fn process_binary_operation(
 
             if let Term::Enum(id) = &*t1 {
                 if let Term::Str(s) = &*t2 {
-                    let rt: RichTerm = match id.as_ref() {
-                        "Json" => serde_json::from_str(s).map_err(|err| {
-                            EvalError::DeserializationError(
-                                String::from("json"),
-                                format!("{}", err),
-                                pos_op,
-                            )
-                        })?,
-                        "Yaml" => serde_yaml::from_str(s).map_err(|err| {
-                            EvalError::DeserializationError(
-                                String::from("yaml"),
-                                format!("{}", err),
-                                pos_op,
-                            )
-                        })?,
-                        "Toml" => toml::from_str(s).map_err(|err| {
-                            EvalError::DeserializationError(
-                                String::from("toml"),
-                                format!("{}", err),
-                                pos_op,
-                            )
-                        })?,
-                        _ => return mk_err_fst(t1),
+                    let rt = match deserialize(id, s, DuplicateKeys::default(), pos_op)? {
+                        Some(rt) => rt,
+                        None => return mk_err_fst(t1),
                     };
 
                     Ok(Closure::atomic_closure(rt.with_pos(pos_op_inh)))
@@ -2245,6 +2817,318 @@ fn process_nary_operation(
                 )),
             }
         }
+        NAryOp::StrFromNum() => {
+            let mut args_wo_env = args
+                .into_iter()
+                .map(|(clos, pos)| (clos.body.term, clos.body.pos, pos));
+            let (fst, pos1, fst_pos) = args_wo_env.next().unwrap();
+            let (snd, pos2, snd_pos) = args_wo_env.next().unwrap();
+            let (thd, pos3, thd_pos) = args_wo_env.next().unwrap();
+            let (fth, pos4, fth_pos) = args_wo_env.next().unwrap();
+            debug_assert!(args_wo_env.next().is_none());
+
+            let n = match &*fst {
+                Term::Num(n) if n.is_finite() => *n,
+                Term::Num(n) => {
+                    return Err(EvalError::Other(
+                        format!("strFromNum: cannot format the non-finite number {}", n),
+                        fst_pos,
+                    ))
+                }
+                _ => {
+                    return Err(EvalError::TypeError(
+                        String::from("Num"),
+                        String::from("strFromNum, 1st argument"),
+                        fst_pos,
+                        RichTerm {
+                            term: fst,
+                            pos: pos1,
+                        },
+                    ))
+                }
+            };
+
+            let precision = match &*snd {
+                Term::Null => None,
+                Term::Num(p)
+                    if p.fract() == 0.0 && *p >= 0.0 && *p <= MAX_FORMAT_PRECISION as f64 =>
+                {
+                    Some(*p as usize)
+                }
+                Term::Num(p) => {
+                    return Err(EvalError::Other(
+                        format!("strFromNum: expected the precision to be null or an integer between 0 and {}, got {}", MAX_FORMAT_PRECISION, p),
+                        snd_pos,
+                    ))
+                }
+                _ => {
+                    return Err(EvalError::TypeError(
+                        String::from("Num"),
+                        String::from("strFromNum, 2nd argument"),
+                        snd_pos,
+                        RichTerm {
+                            term: snd,
+                            pos: pos2,
+                        },
+                    ))
+                }
+            };
+
+            let notation = match &*thd {
+                Term::Enum(id) if id.label == "Auto" => NumNotation::Auto,
+                Term::Enum(id) if id.label == "Fixed" => NumNotation::Fixed,
+                Term::Enum(id) if id.label == "Scientific" => NumNotation::Scientific,
+                _ => {
+                    return Err(EvalError::TypeError(
+                        String::from("Enum <Auto, Fixed, Scientific>"),
+                        String::from("strFromNum, 3rd argument"),
+                        thd_pos,
+                        RichTerm {
+                            term: thd,
+                            pos: pos3,
+                        },
+                    ))
+                }
+            };
+
+            let strip_trailing_zeros = match &*fth {
+                Term::Bool(b) => *b,
+                _ => {
+                    return Err(EvalError::TypeError(
+                        String::from("Bool"),
+                        String::from("strFromNum, 4th argument"),
+                        fth_pos,
+                        RichTerm {
+                            term: fth,
+                            pos: pos4,
+                        },
+                    ))
+                }
+            };
+
+            Ok(Closure::atomic_closure(RichTerm::new(
+                Term::Str(format_num(n, precision, notation, strip_trailing_zeros)),
+                pos_op_inh,
+            )))
+        }
+        NAryOp::ArrayRangeStep() => {
+            let mut args_wo_env = args
+                .into_iter()
+                .map(|(clos, pos)| (clos.body.term, clos.body.pos, pos));
+            let (fst, pos1, fst_pos) = args_wo_env.next().unwrap();
+            let (snd, pos2, snd_pos) = args_wo_env.next().unwrap();
+            let (thd, pos3, thd_pos) = args_wo_env.next().unwrap();
+            debug_assert!(args_wo_env.next().is_none());
+
+            match (&*fst, &*snd, &*thd) {
+                (Term::Num(start), Term::Num(end), Term::Num(step)) => {
+                    if start.fract() != 0.0 {
+                        return Err(EvalError::Other(format!("range: expected the 1st argument (start) to be an integer, got the floating-point value {}", start), pos_op));
+                    } else if end.fract() != 0.0 {
+                        return Err(EvalError::Other(format!("range: expected the 2nd argument (end) to be an integer, got the floating-point value {}", end), pos_op));
+                    } else if step.fract() != 0.0 || *step <= 0.0 {
+                        return Err(EvalError::Other(format!("range: expected the 3rd argument (step) to be a strictly positive integer, got {}", step), pos_op));
+                    }
+
+                    let len = if end > start {
+                        ((end - start) / step).ceil()
+                    } else {
+                        0.0
+                    };
+
+                    if len > MAX_RANGE_LENGTH as f64 {
+                        return Err(EvalError::Other(format!("range: the resulting array would have {} elements, which exceeds the maximum of {}. Ranges are not lazy: all the elements are allocated upfront", len, MAX_RANGE_LENGTH), pos_op));
+                    }
+
+                    let ts = (0..(len as usize))
+                        .map(|i| RichTerm::from(Term::Num(start + (i as f64) * step)))
+                        .collect();
+
+                    Ok(Closure::atomic_closure(RichTerm::new(
+                        Term::Array(ts),
+                        pos_op_inh,
+                    )))
+                }
+                (Term::Num(_), Term::Num(_), _) => Err(EvalError::TypeError(
+                    String::from("Num"),
+                    format!("{}, 3rd argument", n_op),
+                    thd_pos,
+                    RichTerm {
+                        term: thd,
+                        pos: pos3,
+                    },
+                )),
+                (Term::Num(_), _, _) => Err(EvalError::TypeError(
+                    String::from("Num"),
+                    format!("{}, 2nd argument", n_op),
+                    snd_pos,
+                    RichTerm {
+                        term: snd,
+                        pos: pos2,
+                    },
+                )),
+                (_, _, _) => Err(EvalError::TypeError(
+                    String::from("Num"),
+                    format!("{}, 1st argument", n_op),
+                    fst_pos,
+                    RichTerm {
+                        term: fst,
+                        pos: pos1,
+                    },
+                )),
+            }
+        }
+        NAryOp::DeserializeWith() => {
+            let mut args_wo_env = args
+                .into_iter()
+                .map(|(clos, pos)| (clos.body.term, clos.body.pos, pos));
+            let (fst, pos1, fst_pos) = args_wo_env.next().unwrap();
+            let (snd, pos2, snd_pos) = args_wo_env.next().unwrap();
+            let (thd, pos3, thd_pos) = args_wo_env.next().unwrap();
+            debug_assert!(args_wo_env.next().is_none());
+
+            let duplicate_keys = match &*fst {
+                Term::Enum(id) if id.label == "Error" => DuplicateKeys::Error,
+                Term::Enum(id) if id.label == "First" => DuplicateKeys::First,
+                Term::Enum(id) if id.label == "Last" => DuplicateKeys::Last,
+                _ => {
+                    return Err(EvalError::TypeError(
+                        String::from("Enum <Error, First, Last>"),
+                        String::from("deserializeWith, 1st argument"),
+                        fst_pos,
+                        RichTerm {
+                            term: fst,
+                            pos: pos1,
+                        },
+                    ))
+                }
+            };
+
+            let s = match &*thd {
+                Term::Str(s) => s,
+                _ => {
+                    return Err(EvalError::TypeError(
+                        String::from("Str"),
+                        String::from("deserializeWith, 3rd argument"),
+                        thd_pos,
+                        RichTerm {
+                            term: thd,
+                            pos: pos3,
+                        },
+                    ))
+                }
+            };
+
+            let rt = match &*snd {
+                Term::Enum(id) => deserialize(id, s, duplicate_keys, pos_op)?,
+                _ => None,
+            };
+
+            match rt {
+                Some(rt) => Ok(Closure::atomic_closure(rt.with_pos(pos_op_inh))),
+                None => Err(EvalError::TypeError(
+                    String::from("Enum <Json, Yaml, Toml>"),
+                    String::from("deserializeWith, 2nd argument"),
+                    snd_pos,
+                    RichTerm {
+                        term: snd,
+                        pos: pos2,
+                    },
+                )),
+            }
+        }
+        NAryOp::SerializeWith() => {
+            let mut args_iter = args.into_iter();
+            let mut next_arg = || {
+                let (Closure { body, env }, pos) = args_iter.next().unwrap();
+                (body.term, body.pos, pos, env)
+            };
+            let (fst, pos1, fst_pos, _) = next_arg();
+            let (snd, pos2, snd_pos, _) = next_arg();
+            let (thd, pos3, thd_pos, _) = next_arg();
+            let (fth, pos4, _, env4) = next_arg();
+            debug_assert!(args_iter.next().is_none());
+
+            let compact = match &*fst {
+                Term::Bool(b) => *b,
+                _ => {
+                    return Err(EvalError::TypeError(
+                        String::from("Bool"),
+                        String::from("serializeWith, 1st argument"),
+                        fst_pos,
+                        RichTerm {
+                            term: fst,
+                            pos: pos1,
+                        },
+                    ))
+                }
+            };
+
+            let indent = match &*snd {
+                Term::Num(n) if n.fract() == 0.0 && *n >= 0.0 && *n <= MAX_INDENT as f64 => {
+                    *n as usize
+                }
+                Term::Num(n) => {
+                    return Err(EvalError::Other(
+                        format!(
+                            "serializeWith: expected the indentation to be an integer between 0 and {}, got {}",
+                            MAX_INDENT, n
+                        ),
+                        snd_pos,
+                    ))
+                }
+                _ => {
+                    return Err(EvalError::TypeError(
+                        String::from("Num"),
+                        String::from("serializeWith, 2nd argument"),
+                        snd_pos,
+                        RichTerm {
+                            term: snd,
+                            pos: pos2,
+                        },
+                    ))
+                }
+            };
+
+            let format = match &*thd {
+                Term::Enum(id) if id.label == "Json" => ExportFormat::Json,
+                Term::Enum(id) if id.label == "Yaml" => ExportFormat::Yaml,
+                Term::Enum(id) if id.label == "Toml" => ExportFormat::Toml,
+                _ => {
+                    return Err(EvalError::TypeError(
+                        String::from("Enum <Json, Yaml, Toml>"),
+                        String::from("serializeWith, 3rd argument"),
+                        thd_pos,
+                        RichTerm {
+                            term: thd,
+                            pos: pos3,
+                        },
+                    ))
+                }
+            };
+
+            let style = if compact {
+                ExportStyle::Compact
+            } else {
+                ExportStyle::Pretty { indent }
+            };
+
+            // Serialization needs all variables term to be fully substituted
+            let rt = subst(
+                RichTerm {
+                    term: fth,
+                    pos: pos4,
+                },
+                &Environment::new(),
+                &env4,
+            );
+
+            serialize::validate(format, &rt)?;
+            Ok(Closure::atomic_closure(RichTerm::new(
+                Term::Str(serialize::to_string(format, style, &rt)?),
+                pos_op_inh,
+            )))
+        }
         NAryOp::MergeContract() => {
             let mut args_iter = args.into_iter();
             let (
@@ -46,6 +46,9 @@ pub enum Marker {
         Environment, /* the common environment of chunks */
     ),
     Strictness(bool),
+    /// Mark the evaluation of a sensitive value, which is recorded once evaluated. See
+    /// [`crate::redact`].
+    Sensitive,
 }
 
 impl std::fmt::Debug for Marker {
@@ -59,6 +62,7 @@ impl std::fmt::Debug for Marker {
             Marker::StrChunk(_) => write!(f, "StrChunk"),
             Marker::StrAcc(_, _, _) => write!(f, "StrAcc"),
             Marker::Strictness(s) => write!(f, "Strictness = {}", s),
+            Marker::Sensitive => write!(f, "Sensitive"),
         }
     }
 }
@@ -91,6 +95,10 @@ impl Marker {
     pub fn is_strictness(&self) -> bool {
         matches!(*self, Marker::Strictness(..))
     }
+
+    pub fn is_sensitive(&self) -> bool {
+        matches!(*self, Marker::Sensitive)
+    }
 }
 
 /// The evaluation stack.
@@ -172,6 +180,10 @@ impl Stack {
         self.0.push(Marker::Strictness(strict));
     }
 
+    pub fn push_sensitive(&mut self) {
+        self.0.push(Marker::Sensitive);
+    }
+
     /// Try to pop an argument from the top of the stack. If `None` is returned, the top element
     /// was not an argument and the stack is left unchanged.
     ///
@@ -280,11 +292,55 @@ impl Stack {
         }
     }
 
+    /// Try to pop a sensitive marker from the top of the stack. Return `false` if the top element
+    /// was not a sensitive marker, in which case the stack is left unchanged.
+    pub fn pop_sensitive(&mut self) -> bool {
+        if self.is_top_sensitive() {
+            self.0.pop();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Check if the top element is a sensitive marker.
+    pub fn is_top_sensitive(&self) -> bool {
+        self.0.last().map(Marker::is_sensitive).unwrap_or(false)
+    }
+
+    /// Check if a sensitive value is being evaluated, that is if there is a sensitive marker
+    /// anywhere on the stack.
+    pub fn has_sensitive(&self) -> bool {
+        self.0.iter().any(Marker::is_sensitive)
+    }
+
     /// Check if the top element is a thunk.
     pub fn is_top_thunk(&self) -> bool {
         self.0.last().map(Marker::is_thunk).unwrap_or(false)
     }
 
+    /// Mark all the consecutive thunks at the top of the stack as memoized. Strictness markers are
+    /// skipped. See [`ThunkUpdateFrame::memoize`].
+    pub fn memoize_thunks(&self) {
+        for marker in self.0.iter().rev() {
+            match marker {
+                Marker::Thunk(frame) => frame.memoize(),
+                Marker::Strictness(_) => (),
+                _ => break,
+            }
+        }
+    }
+
+    /// Empty the stack, restoring the thunks waiting to be updated to their suspended state. See
+    /// [`ThunkUpdateFrame::reset`].
+    pub fn reset_thunks(&mut self) {
+        for marker in self.0.drain(..) {
+            if let Marker::Thunk(frame) = marker {
+                frame.reset();
+            }
+        }
+    }
+
     /// Check if the top element is an operation continuation.
     pub fn is_top_cont(&self) -> bool {
         self.0.last().map(Marker::is_cont).unwrap_or(false)
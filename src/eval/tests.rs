@@ -54,6 +54,20 @@ fn lone_var_panics() {
     eval_no_import(mk_term::var("unbound")).unwrap();
 }
 
+#[test]
+fn unbound_identifier_suggestion() {
+    let closest = |s: &str| match eval_no_import(parse(s).unwrap()) {
+        Err(EvalError::UnboundIdentifier(_, _, closest)) => closest.map(|id| id.label),
+        result => panic!("expected an unbound identifier error, got {:?}", result),
+    };
+
+    assert_eq!(
+        closest("let server_port = 80 in server_prot"),
+        Some(String::from("server_port"))
+    );
+    assert_eq!(closest("let x = 1 in y"), None);
+}
+
 #[test]
 fn only_fun_are_applicable() {
     eval_no_import(mk_app!(Term::Bool(true), Term::Num(45.))).unwrap_err();
@@ -353,3 +367,167 @@ fn substitution() {
         parse("switch {`x => [1, 1], `y => (if false then 1 else \"Glob2\"), `z => {id = true, other = false}} true").unwrap()
     );
 }
+
+#[test]
+fn number_formatting() {
+    use operation::{format_num, NumNotation};
+
+    let cases = [
+        (0.1, "0.1"),
+        (1e21, "1e+21"),
+        (1e21 - 131072.0, "999999999999999900000"),
+        (-0.0, "-0"),
+        (1e-7, "0.0000001"),
+        (1.5e-8, "1.5e-8"),
+        (-123.456, "-123.456"),
+        (f64::MAX, "1.7976931348623157e+308"),
+        (5e-324, "5e-324"),
+    ];
+
+    for (n, expected) in cases.iter() {
+        assert_eq!(format_num(*n, None, NumNotation::Auto, false), *expected);
+    }
+
+    assert_eq!(format_num(2.5, Some(3), NumNotation::Fixed, false), "2.500");
+    assert_eq!(format_num(2.5, Some(3), NumNotation::Fixed, true), "2.5");
+    assert_eq!(format_num(2.0, Some(3), NumNotation::Fixed, true), "2");
+    assert_eq!(
+        format_num(1200.0, None, NumNotation::Scientific, false),
+        "1.2e+3"
+    );
+    assert_eq!(
+        format_num(1200.0, Some(2), NumNotation::Scientific, false),
+        "1.20e+3"
+    );
+    assert_eq!(
+        format_num(0.00012, Some(0), NumNotation::Scientific, false),
+        "1e-4"
+    );
+    assert_eq!(
+        format_num(1e21, None, NumNotation::Fixed, false),
+        "1000000000000000000000"
+    );
+}
+
+#[test]
+fn number_round_trip() {
+    let values = [
+        0.0,
+        -0.0,
+        0.1,
+        1.0 / 3.0,
+        1e21,
+        1e-7,
+        9.999999e-8,
+        123456789.125,
+        f64::MAX,
+        f64::MIN,
+        5e-324,
+        2.2250738585072014e-308,
+    ];
+    let notations = [
+        operation::NumNotation::Auto,
+        operation::NumNotation::Fixed,
+        operation::NumNotation::Scientific,
+    ];
+
+    for n in values.iter() {
+        for notation in notations.iter() {
+            let repr = operation::format_num(*n, None, *notation, false);
+            let parsed = operation::parse_num(&repr).unwrap();
+            assert_eq!(parsed.to_bits(), n.to_bits(), "round-trip of {}", repr);
+        }
+    }
+}
+
+#[test]
+fn number_parsing() {
+    use operation::parse_num;
+
+    assert_eq!(parse_num("+1.5E3"), Ok(1500.0));
+    assert_eq!(parse_num(".5"), Ok(0.5));
+    assert_eq!(parse_num("5."), Ok(5.0));
+    assert_eq!(parse_num("1,5").unwrap_err().0, Some(1));
+    assert_eq!(parse_num("1 000").unwrap_err().0, Some(1));
+    assert_eq!(parse_num("1e").unwrap_err().0, None);
+    assert_eq!(parse_num("1e400").unwrap_err().0, None);
+    assert!(parse_num("").is_err());
+    assert!(parse_num(".").is_err());
+    assert!(parse_num("inf").is_err());
+    assert!(parse_num("NaN").is_err());
+}
+
+#[test]
+fn reset_thunks() {
+    use crate::eval::lazy::ThunkState;
+
+    let mut thunk = Thunk::new(
+        Closure::atomic_closure(mk_app!(mk_fun!("x", mk_term::var("x")), Term::Num(1.))),
+        IdentKind::Let,
+    );
+    let mut stack = Stack::new();
+    stack.push_thunk(thunk.mk_update_frame().unwrap());
+    assert_eq!(thunk.state(), ThunkState::Blackholed);
+
+    stack.reset_thunks();
+    assert_eq!(thunk.state(), ThunkState::Suspended);
+    assert!(thunk.mk_update_frame().is_ok());
+}
+
+#[test]
+fn cancelled_substitution() {
+    let t: RichTerm = Term::Array((0..2000).map(|n| Term::Num(n as f64).into()).collect()).into();
+    let env = Environment::new();
+
+    assert_matches::assert_matches!(
+        subst_cancellable(t.clone(), &env, &env, &|| true),
+        Err(EvalError::Cancelled(_))
+    );
+    assert_eq!(subst_cancellable(t.clone(), &env, &env, &|| false), Ok(t));
+}
+
+#[test]
+fn memoized_revertible_thunks() {
+    use crate::eval::lazy::ThunkState;
+    use std::collections::HashSet;
+    use std::rc::Rc;
+
+    let a = Ident::from("a");
+    let dep = Thunk::new(
+        Closure::atomic_closure(Term::Num(1.).into()),
+        IdentKind::Let,
+    );
+    let mut env = Environment::new();
+    env.insert(a.clone(), dep.clone());
+
+    let mut thunk = Thunk::new_rev(
+        Closure {
+            body: mk_term::op2(BinaryOp::Plus(), mk_term::var("a"), Term::Num(1.)),
+            env,
+        },
+        IdentKind::Record,
+        Some(Rc::new(HashSet::from([a.clone()]))),
+    );
+    let frame = thunk.mk_update_frame().unwrap();
+    frame.memoize();
+    frame.update(Closure::atomic_closure(Term::Num(2.).into()));
+
+    // The memoized value is reused as long as the dependencies are bound to the same thunks.
+    let mut reverted = thunk.revert();
+    assert_eq!(reverted.state(), ThunkState::Suspended);
+    reverted.borrow_mut().env.insert(a.clone(), dep);
+    reverted.restore_memo();
+    assert_eq!(reverted.state(), ThunkState::Evaluated);
+    assert_eq!(reverted.borrow().body, Term::Num(2.).into());
+
+    let mut overridden = thunk.revert();
+    overridden.borrow_mut().env.insert(
+        a,
+        Thunk::new(
+            Closure::atomic_closure(Term::Num(2.).into()),
+            IdentKind::Let,
+        ),
+    );
+    overridden.restore_memo();
+    assert_eq!(overridden.state(), ThunkState::Suspended);
+}
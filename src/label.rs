@@ -5,6 +5,7 @@
 use std::rc::Rc;
 
 use crate::eval::lazy::Thunk;
+use crate::identifier::Ident;
 use crate::position::{RawSpan, TermPos};
 use crate::types::{AbsType, Types};
 use codespan::Files;
@@ -67,6 +68,113 @@ pub mod ty_path {
             .any(|elt| matches!(*elt, Elem::Domain | Elem::Codomain))
     }
 
+    /// Return the subtype of `ty` designated by a type path, or `None` if the path doesn't
+    /// correspond to a subtype of `ty`.
+    ///
+    /// # Example
+    ///
+    /// - Type path: `Codomain(Domain(Nil()))`
+    /// - Type : `Num -> {foo: Str} -> Num`
+    /// - Return: `{foo: Str}`
+    pub fn subtype<'a, I>(mut path_it: I, mut ty: &Types) -> Option<&Types>
+    where
+        I: Iterator<Item = &'a Elem>,
+    {
+        while let AbsType::Forall(_, body) = &ty.0 {
+            ty = body.as_ref();
+        }
+
+        match (&ty.0, path_it.next()) {
+            (_, None) => Some(ty),
+            (AbsType::Arrow(dom, _), Some(Elem::Domain)) => subtype(path_it, dom),
+            (AbsType::Arrow(_, codom), Some(Elem::Codomain)) => subtype(path_it, codom),
+            (AbsType::Array(elt), Some(Elem::Array)) => subtype(path_it, elt),
+            (AbsType::DynRecord(elt), Some(Elem::Field(_))) => subtype(path_it, elt),
+            (AbsType::StaticRecord(rows), Some(Elem::Field(ident))) => {
+                let mut row = &rows.0;
+                loop {
+                    match row {
+                        AbsType::RowExtend(id, Some(ty), _) if id == ident => {
+                            break subtype(path_it, ty)
+                        }
+                        AbsType::RowExtend(_, _, tail) => row = &tail.0,
+                        _ => break None,
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Return the path to the first pair of subtypes where `expd` and `actual` differ.
+    ///
+    /// The path is only followed as long as both types have the same shape: the same arrow, array
+    /// or record structure, and the same quantified variables. The path is empty if the types are
+    /// equal or if they already differ at the top level.
+    ///
+    /// # Example
+    ///
+    /// - Types: `Num -> {foo: Str, bar: Num}` and `Num -> {foo: Bool, bar: Num}`
+    /// - Return: `[Codomain, Field(foo)]`
+    pub fn divergence(expd: &Types, actual: &Types) -> Path {
+        let step = |elem: Elem, expd: &Types, actual: &Types| -> Path {
+            std::iter::once(elem)
+                .chain(divergence(expd, actual))
+                .collect()
+        };
+
+        match (&expd.0, &actual.0) {
+            (AbsType::Forall(id1, body1), AbsType::Forall(id2, body2)) if id1 == id2 => {
+                divergence(body1, body2)
+            }
+            (AbsType::Arrow(dom1, _), AbsType::Arrow(dom2, _)) if dom1 != dom2 => {
+                step(Elem::Domain, dom1, dom2)
+            }
+            (AbsType::Arrow(_, codom1), AbsType::Arrow(_, codom2)) if codom1 != codom2 => {
+                step(Elem::Codomain, codom1, codom2)
+            }
+            (AbsType::Array(elt1), AbsType::Array(elt2)) if elt1 != elt2 => {
+                step(Elem::Array, elt1, elt2)
+            }
+            (AbsType::StaticRecord(rows1), AbsType::StaticRecord(rows2)) => {
+                let (fields1, tail1) = fields(rows1);
+                let (fields2, tail2) = fields(rows2);
+                let same_shape = tail1 == tail2
+                    && fields1.len() == fields2.len()
+                    && fields1
+                        .iter()
+                        .all(|(id, _)| fields2.iter().any(|(other, _)| other == id));
+
+                if !same_shape {
+                    return Path::new();
+                }
+
+                fields1
+                    .iter()
+                    .find_map(|(id, ty1)| {
+                        let (_, ty2) = fields2.iter().find(|(other, _)| other == id)?;
+                        (ty1 != ty2).then(|| step(Elem::Field((*id).clone()), ty1, ty2))
+                    })
+                    .unwrap_or_default()
+            }
+            _ => Path::new(),
+        }
+    }
+
+    /// Return the fields of a record row type together with its tail.
+    fn fields(mut row: &Types) -> (Vec<(&Ident, &Types)>, &Types) {
+        let mut result = Vec::new();
+
+        while let AbsType::RowExtend(id, ty_opt, tail) = &row.0 {
+            if let Some(ty) = ty_opt {
+                result.push((id, ty.as_ref()));
+            }
+            row = tail.as_ref();
+        }
+
+        (result, row)
+    }
+
     /// Return the position span encoded by a type path in the string representation of the
     /// corresponding type.
     ///
@@ -78,6 +186,10 @@ pub mod ty_path {
     /// - Type path: `Codomain(Domain(Nil()))`
     /// - Type : `Num -> Num -> Num`
     /// - Return: `(7, 10)`, which corresponds to the second `Num` occurrence.
+    ///
+    /// If the type doesn't have the shape required by the path, which happens when a user-defined
+    /// contract manipulates the label itself (e.g. a custom function contract), the span of the
+    /// remaining type as a whole is returned.
     pub fn span<'a, I>(mut path_it: std::iter::Peekable<I>, mut ty: &Types) -> (usize, usize)
     where
         I: Iterator<Item = &'a Elem>,
@@ -138,7 +250,10 @@ pub mod ty_path {
                         let offset = (paren_offset * 2) + 4 + dom_end + forall_offset;
                         (codom_start + offset, codom_end + offset)
                     }
-                    _ => panic!(),
+                    _ => {
+                        let repr = format!("{}", ty);
+                        (forall_offset, forall_offset + repr.len())
+                    }
                 }
             }
             (AbsType::StaticRecord(rows), Some(Elem::Field(ident))) => {
@@ -154,7 +269,8 @@ pub mod ty_path {
                     match row {
                         AbsType::RowExtend(id, Some(ty), _) if id == ident => {
                             let (sub_start, sub_end) = span(path_it, ty);
-                            let full_offset = start_offset + format!("{}", id).len() + id_offset;
+                            let full_offset =
+                                forall_offset + start_offset + format!("{}", id).len() + id_offset;
                             break (full_offset + sub_start, full_offset + sub_end);
                         }
                         AbsType::RowExtend(id, Some(ty), tail) => {
@@ -165,7 +281,10 @@ pub mod ty_path {
                                 + end_offset;
                             row = &tail.0;
                         }
-                        _ => panic!(),
+                        _ => {
+                            let repr = format!("{}", ty);
+                            break (forall_offset, forall_offset + repr.len());
+                        }
                     }
                 }
             }
@@ -181,15 +300,73 @@ pub mod ty_path {
 
                 let (sub_start, sub_end) = span(path_it, ty);
                 (
-                    start_offset + paren_offset + sub_start,
-                    start_offset + paren_offset + sub_end,
+                    forall_offset + start_offset + paren_offset + sub_start,
+                    forall_offset + start_offset + paren_offset + sub_end,
                 )
             }
-            (ty, next) => panic!(
-                "label::span: unexpected type {} with path element {:?}",
-                Types(ty.clone()),
-                next
-            ),
+            (ty, _) => {
+                let repr = format!("{}", Types(ty.clone()));
+                (forall_offset, forall_offset + repr.len())
+            }
+        }
+    }
+}
+
+pub mod field_path {
+    //! Field paths.
+    //!
+    //! While a [type path](../ty_path/index.html) locates the failing part of a contract inside
+    //! the contract's type, a field path locates the failing value inside the value the contract
+    //! was originally applied to, as in `server.instances[3].port`. It is accumulated when
+    //! contracts descend into the fields of records and into the elements of arrays, and it is
+    //! reported to the user in case of a blame, to tell which one of the many values sharing the
+    //! same contract is wrong.
+
+    use crate::identifier::Ident;
+    use std::fmt;
+
+    /// An element of a field path.
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum Elem {
+        /// A field of a record.
+        Field(Ident),
+        /// An element of an array, with its index.
+        Index(usize),
+    }
+
+    /// A field path. Wrapper around a vector of elements, to implement `Display`.
+    #[derive(Debug, Clone, PartialEq, Default)]
+    pub struct Path(pub Vec<Elem>);
+
+    impl Path {
+        pub fn is_empty(&self) -> bool {
+            self.0.is_empty()
+        }
+
+        /// Return a copy of this path extended with an element.
+        pub fn with(&self, elem: Elem) -> Path {
+            let mut path = self.clone();
+            path.0.push(elem);
+            path
+        }
+
+        /// Return a copy of `other` prefixed with this path.
+        pub fn concat(&self, other: &Path) -> Path {
+            Path(self.0.iter().chain(other.0.iter()).cloned().collect())
+        }
+    }
+
+    impl fmt::Display for Path {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            for (i, elem) in self.0.iter().enumerate() {
+                match elem {
+                    Elem::Field(id) if i == 0 => write!(f, "{}", id)?,
+                    Elem::Field(id) => write!(f, ".{}", id)?,
+                    Elem::Index(index) => write!(f, "[{}]", index)?,
+                }
+            }
+
+            Ok(())
         }
     }
 }
@@ -248,6 +425,23 @@ pub struct Label {
     pub polarity: bool,
     /// The path of the type being currently checked in the original type.
     pub path: ty_path::Path,
+    /// The location of the value being currently checked inside the value the original contract
+    /// was applied to.
+    pub field_path: field_path::Path,
+    /// The position of the expression annotated by the original contract. Set by the
+    /// [`apply_contracts`](../transform/apply_contracts/index.html) transformation, and reset when
+    /// the contract is applied to another value by merging.
+    pub value_pos: TermPos,
+    /// The identifier bound to the annotated expression, when the annotation is part of a let
+    /// binding or of a record field. Set by the
+    /// [`apply_contracts`](../transform/apply_contracts/index.html) transformation.
+    pub binding: Option<Ident>,
+    /// The position of the example the contract was derived from, for contracts generated by
+    /// `contract.from_example`. Set at run-time by the interpreter.
+    pub example_pos: TermPos,
+    /// The fields of the checked record responsible for the blame, sorted by position, such as
+    /// mutually exclusive fields which are all set. Set at run-time by the interpreter.
+    pub fields: Vec<Ident>,
 }
 
 impl Label {
@@ -265,6 +459,11 @@ impl Label {
             arg_pos: TermPos::None,
             polarity: true,
             path: Vec::new(),
+            field_path: field_path::Path::default(),
+            value_pos: TermPos::None,
+            binding: None,
+            example_pos: TermPos::None,
+            fields: Vec::new(),
         }
     }
 }
@@ -283,6 +482,11 @@ impl Default for Label {
             arg_pos: TermPos::None,
             polarity: true,
             path: Vec::new(),
+            field_path: field_path::Path::default(),
+            value_pos: TermPos::None,
+            binding: None,
+            example_pos: TermPos::None,
+            fields: Vec::new(),
         }
     }
 }
@@ -1,14 +1,22 @@
+#[cfg(feature = "ast-dump")]
+pub mod ast_dump;
+pub mod bundle;
 pub mod cache;
+pub mod check;
 pub mod destruct;
 pub mod environment;
 pub mod error;
+pub mod error_codes;
 pub mod eval;
 pub mod identifier;
 pub mod label;
+pub mod lint;
 pub mod parser;
 pub mod position;
 pub mod program;
+pub mod redact;
 pub mod repl;
+pub mod report_policy;
 pub mod serialize;
 pub mod stdlib;
 pub mod term;
@@ -0,0 +1,297 @@
+//! Lints: checks of suspicious but valid programs.
+//!
+//! Lints don't prevent a program from running: they are reported as
+//! [`Warning`](../error/enum.Warning.html)s, unless they are denied, in which case the first
+//! warning of a denied lint is reported as an error instead (see
+//! [`Program::set_deny`](../program/struct.Program.html#method.set_deny), or the `--deny` option
+//! of the command-line interface).
+//!
+//! # Shadowing of the standard library
+//!
+//! A binding whose name is also a top-level name of the standard library, such as `string` or
+//! `array`, hides the latter in its scope. Using the standard library afterwards then fails with
+//! an obscure error:
+//!
+//! ```text
+//! let string = "foo" in string.length string
+//! ```
+//!
+//! The `shadow-stdlib` lint flags let bindings, function parameters, variables bound by
+//! destructuring patterns, and the fields of recursive records that are referred to inside the
+//! record. The protected names are the ones of the environment built from the loaded standard
+//! library, such that a standard library loaded from a directory is taken into account.
+//!
+//! # Unused bindings
+//!
+//! A let binding which is not used in its body is most likely a leftover, or the sign that a
+//! literal was written instead of the variable:
+//!
+//! ```text
+//! let port = 8080 in {server = {host = "localhost", port = 80}}
+//! ```
+//!
+//! The `unused-binding` lint flags the let bindings whose name doesn't occur free in their body.
+//! This includes the variables bound by destructuring patterns, but not function parameters.
+//! Bindings whose name starts with an underscore are never flagged, such that a binding can be
+//! kept on purpose.
+//!
+//! # Overrides bypassing contracts
+//!
+//! When a default value is overridden, the contracts annotating the fields of the overridden value
+//! still apply to the overriding one (see [`eval::merge`](../eval/merge/index.html)). To preserve
+//! laziness, these contracts are only retained if the default value is syntactically a record, an
+//! enriched value or a merge of such values. The contracts coming from a computed default value
+//! are silently dropped by an override:
+//!
+//! ```text
+//! let mk_server = fun host => {host | Str = host, port | Num = 80} in
+//! {server | default = mk_server "localhost"} & {server = {host = "localhost", port = "80"}}
+//! ```
+//!
+//! The `override-bypass` lint flags the default values which are the result of a function
+//! application, a field access, a switch or an import. Annotating the field itself, as in
+//! `server | default | Server = ...`, makes the contracts apply whatever the overriding value.
+use crate::error::Warning;
+use crate::identifier::Ident;
+use crate::position::TermPos;
+use crate::term::{BinaryOp, MergePriority, MetaValue, RichTerm, Term, TraverseOrder, UnaryOp};
+use crate::transform::{desugar_destructuring, free_vars};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::str::FromStr;
+
+/// The available lints.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Lint {
+    /// A binding shadows a name of the standard library.
+    ShadowStdlib,
+    /// A let binding is never used.
+    UnusedBinding,
+    /// Overriding a default value drops the contracts of its fields.
+    OverrideBypass,
+}
+
+impl Lint {
+    /// All the available lints.
+    pub const ALL: [Lint; 3] = [
+        Lint::ShadowStdlib,
+        Lint::UnusedBinding,
+        Lint::OverrideBypass,
+    ];
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Lint::ShadowStdlib => write!(f, "shadow-stdlib"),
+            Lint::UnusedBinding => write!(f, "unused-binding"),
+            Lint::OverrideBypass => write!(f, "override-bypass"),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ParseLintError(String);
+
+impl fmt::Display for ParseLintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unknown lint `{}`, expected `shadow-stdlib`, `unused-binding` or `override-bypass`",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseLintError {}
+
+impl FromStr for Lint {
+    type Err = ParseLintError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shadow-stdlib" => Ok(Lint::ShadowStdlib),
+            "unused-binding" => Ok(Lint::UnusedBinding),
+            "override-bypass" => Ok(Lint::OverrideBypass),
+            _ => Err(ParseLintError(String::from(s))),
+        }
+    }
+}
+
+/// Return the bindings of a term which shadow one of the `stdlib` names, sorted by position.
+/// Destructuring patterns don't need to be desugared beforehand.
+pub fn shadowed_stdlib(rt: &RichTerm, stdlib: &HashSet<Ident>) -> Vec<Warning> {
+    let mut bindings: Vec<Ident> = Vec::new();
+
+    let mut collect = |rt: RichTerm, bindings: &mut Vec<Ident>| -> Result<RichTerm, ()> {
+        // Desugaring patterns gives the variables they bind as let bindings.
+        let rt = desugar_destructuring::transform_one(rt);
+
+        match rt.as_ref() {
+            Term::Let(id, ..) | Term::Fun(id, _) => bindings.push(id.clone()),
+            Term::RecRecord(fields, dyn_fields, ..) => {
+                let values = fields
+                    .values()
+                    .chain(dyn_fields.iter().map(|(_, value)| value));
+                bindings.extend(
+                    fields
+                        .keys()
+                        .filter(|id| stdlib.contains(id))
+                        .filter(|id| values.clone().any(|value| refers_to(value, id)))
+                        .cloned(),
+                );
+            }
+            _ => (),
+        }
+
+        Ok(rt)
+    };
+
+    rt.clone()
+        .traverse(&mut collect, &mut bindings, TraverseOrder::TopDown)
+        .unwrap();
+
+    // Generated bindings, which don't have a position, can't be renamed by the user.
+    bindings.retain(|id| id.pos.is_def() && stdlib.contains(id));
+    bindings.sort_by_key(|id| id.pos.as_opt_ref().map(|span| span.start));
+    bindings.into_iter().map(Warning::ShadowedStdlib).collect()
+}
+
+/// Return `true` if `id` occurs as a variable in `rt`.
+fn refers_to(rt: &RichTerm, id: &Ident) -> bool {
+    let mut found = false;
+
+    rt.clone()
+        .traverse(
+            &mut |rt: RichTerm, found: &mut bool| -> Result<RichTerm, ()> {
+                *found = *found || matches!(rt.as_ref(), Term::Var(var) if var == id);
+                Ok(rt)
+            },
+            &mut found,
+            TraverseOrder::TopDown,
+        )
+        .unwrap();
+
+    found
+}
+
+/// Return the let bindings of a term which are not used in their body, sorted by position.
+/// Destructuring patterns don't need to be desugared beforehand.
+pub fn unused_bindings(rt: &RichTerm) -> Vec<Warning> {
+    // Desugaring patterns gives the variables they bind as let bindings.
+    let rt = rt
+        .clone()
+        .traverse(
+            &mut |rt: RichTerm, _: &mut ()| -> Result<RichTerm, ()> {
+                Ok(desugar_destructuring::transform_one(rt))
+            },
+            &mut (),
+            TraverseOrder::TopDown,
+        )
+        .unwrap();
+
+    let mut bindings: Vec<Ident> = Vec::new();
+    free_vars::free_vars_with(&rt, &mut |id, body_vars| {
+        if id.pos.is_def() && !id.label.starts_with('_') && !body_vars.contains(id) {
+            bindings.push(id.clone());
+        }
+    });
+
+    bindings.sort_by_key(|id| id.pos.as_opt_ref().map(|span| span.start));
+    bindings.into_iter().map(Warning::UnusedBinding).collect()
+}
+
+/// Return the positions of the default values whose contracts are dropped if they are overridden,
+/// sorted. The term may have been transformed to the share normal form.
+pub fn override_bypasses(rt: &RichTerm) -> Vec<Warning> {
+    // The share normal form moves the values of records and enriched values to generated let
+    // bindings, which are unique: they are collected first to look through the generated variables.
+    let mut shared: HashMap<Ident, RichTerm> = HashMap::new();
+    let mut defaults: Vec<RichTerm> = Vec::new();
+
+    rt.clone()
+        .traverse(
+            &mut |rt: RichTerm, _: &mut ()| -> Result<RichTerm, ()> {
+                match rt.as_ref() {
+                    Term::Let(id, bound, ..) if id.is_generated() => {
+                        shared.insert(id.clone(), bound.clone());
+                    }
+                    Term::MetaValue(MetaValue {
+                        priority: MergePriority::Default,
+                        value: Some(value),
+                        ..
+                    }) => defaults.push(value.clone()),
+                    _ => (),
+                }
+                Ok(rt)
+            },
+            &mut (),
+            TraverseOrder::TopDown,
+        )
+        .unwrap();
+
+    let mut positions: Vec<TermPos> = defaults
+        .iter()
+        .map(|value| without_contracts(value, &shared))
+        .filter(|value| is_computed(value, &shared) && !may_carry_contracts(value, &shared))
+        .map(|value| value.pos)
+        .filter(TermPos::is_def)
+        .collect();
+
+    positions.sort_by_key(|pos| pos.as_opt_ref().map(|span| span.start));
+    positions.into_iter().map(Warning::OverrideBypass).collect()
+}
+
+/// Return the value to which the contracts of an annotation are applied, or the term itself if it
+/// isn't a contract application. The applications generated by the `apply_contracts` transformation
+/// aren't computations of the program, and must not be reported as such.
+fn without_contracts<'a>(rt: &'a RichTerm, shared: &'a HashMap<Ident, RichTerm>) -> &'a RichTerm {
+    match rt.as_ref() {
+        Term::App(f, arg) if matches!(f.as_ref(), Term::Op2(BinaryOp::Assume(), ..)) => {
+            without_contracts(arg, shared)
+        }
+        Term::Var(id) => match shared.get(id) {
+            Some(bound) if !std::ptr::eq(without_contracts(bound, shared), bound) => {
+                without_contracts(bound, shared)
+            }
+            _ => rt,
+        },
+        _ => rt,
+    }
+}
+
+/// Static counterpart of `eval::merge::may_carry_contracts`: return `true` if the contracts of the
+/// fields of an overridden value are retained.
+fn may_carry_contracts(rt: &RichTerm, shared: &HashMap<Ident, RichTerm>) -> bool {
+    match rt.as_ref() {
+        Term::Var(id) => shared
+            .get(id)
+            .map(|bound| may_carry_contracts(bound, shared))
+            .unwrap_or(false),
+        Term::Let(_, _, body, _) => may_carry_contracts(body, shared),
+        Term::Op2(BinaryOp::Merge(), t1, t2) => {
+            may_carry_contracts(t1, shared) || may_carry_contracts(t2, shared)
+        }
+        Term::Record(..) | Term::RecRecord(..) | Term::MetaValue(_) => true,
+        _ => false,
+    }
+}
+
+/// Return `true` if a term is the result of a computation which may return a record.
+fn is_computed(rt: &RichTerm, shared: &HashMap<Ident, RichTerm>) -> bool {
+    match rt.as_ref() {
+        Term::Var(id) => shared
+            .get(id)
+            .map(|bound| is_computed(bound, shared))
+            .unwrap_or(false),
+        Term::Let(_, _, body, _) => is_computed(body, shared),
+        Term::Op2(BinaryOp::Merge(), t1, t2) => is_computed(t1, shared) || is_computed(t2, shared),
+        Term::App(..)
+        | Term::Switch(..)
+        | Term::Op1(UnaryOp::StaticAccess(_), _)
+        | Term::Op2(BinaryOp::DynAccess(), ..)
+        | Term::Import(_)
+        | Term::ResolvedImport(_) => true,
+        _ => false,
+    }
+}
@@ -2,8 +2,9 @@ use crate::{identifier::Ident, position::RawSpan};
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum LexicalError {
-    /// A closing brace '}' does not match an opening brace '{'.
-    UnmatchedCloseBrace(usize),
+    /// A closing brace '}' does not match an opening brace '{'. The second argument is the
+    /// position of the opening brace of the last brace group closed before, if any.
+    UnmatchedCloseBrace(usize, Option<usize>),
     /// Invalid escape sequence in a string literal.
     InvalidEscapeSequence(usize),
     /// Invalid escape ASCII code in a string literal.
@@ -33,4 +34,17 @@ pub enum ParseError {
         RawSpan, /* tail position */
         RawSpan, /* whole record position */
     ),
+    /// A field of a record literal is defined twice with values that can't be merged.
+    DuplicateField(
+        Vec<Ident>, /* the field path */
+        RawSpan,    /* first definition */
+        RawSpan,    /* second definition */
+    ),
+    /// A type alias is applied to a number of arguments different from its number of parameters.
+    TypeAliasArity(
+        Ident,   /* the alias */
+        usize,   /* the number of parameters */
+        usize,   /* the number of arguments */
+        RawSpan, /* the application of the alias */
+    ),
 }
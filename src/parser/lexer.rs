@@ -68,6 +68,8 @@ pub enum NormalToken<'input> {
     In,
     #[token("let")]
     Let,
+    #[token("type")]
+    Type,
     #[token("switch")]
     Switch,
 
@@ -111,6 +113,8 @@ pub enum NormalToken<'input> {
     Percent,
     #[token("++")]
     DoublePlus,
+    #[token("**")]
+    DoubleTimes,
     #[token("==")]
     DoubleEq,
     #[token("@")]
@@ -157,6 +161,8 @@ pub enum NormalToken<'input> {
     IsArray,
     #[token("%is_record%")]
     IsRecord,
+    #[token("%type_of%")]
+    TypeOf,
 
     #[token("%assume%")]
     Assume,
@@ -166,6 +172,8 @@ pub enum NormalToken<'input> {
     ChangePol,
     #[token("%polarity%")]
     Polarity,
+    #[token("%label_path%")]
+    LabelPath,
     #[token("%go_dom%")]
     GoDom,
     #[token("%go_codom%")]
@@ -174,6 +182,12 @@ pub enum NormalToken<'input> {
     GoField,
     #[token("%go_array%")]
     GoArray,
+    #[token("%go_array_elem%")]
+    GoArrayElem,
+    #[token("%with_example%")]
+    WithExample,
+    #[token("%with_fields%")]
+    WithFields,
 
     #[token("%wrap%")]
     Wrap,
@@ -191,6 +205,8 @@ pub enum NormalToken<'input> {
     Seq,
     #[token("%deep_seq%")]
     DeepSeq,
+    #[token("%memo%")]
+    Memo,
     #[token("%head%")]
     Head,
     #[token("%tail%")]
@@ -203,13 +219,37 @@ pub enum NormalToken<'input> {
     ValuesOf,
     #[token("%pow%")]
     Pow,
+    #[token("%bit_and%")]
+    BitAnd,
+    #[token("%bit_or%")]
+    BitOr,
+    #[token("%bit_xor%")]
+    BitXor,
+    #[token("%bit_not%")]
+    BitNot,
+    #[token("%shift_left%")]
+    ShiftLeft,
+    #[token("%shift_right%")]
+    ShiftRight,
 
     #[token("%has_field%")]
     HasField,
+    #[token("%record_diff%")]
+    RecordDiff,
+    #[token("%record_intersect%")]
+    RecordIntersect,
+    #[token("%record_keys_equal%")]
+    RecordKeysEqual,
     #[token("%map%")]
     Map,
     #[token("%elem_at%")]
     ElemAt,
+    #[token("%chunks%")]
+    ArrayChunks,
+    #[token("%windows%")]
+    ArrayWindows,
+    #[token("%pluck%")]
+    ArrayPluck,
     #[token("%generate%")]
     ArrayGen,
     #[token("merge")]
@@ -218,6 +258,8 @@ pub enum NormalToken<'input> {
     Default,
     #[token("doc")]
     Doc,
+    #[token("sensitive")]
+    Sensitive,
 
     #[token("%hash%")]
     OpHash,
@@ -225,6 +267,10 @@ pub enum NormalToken<'input> {
     Serialize,
     #[token("%deserialize%")]
     Deserialize,
+    #[token("%deserialize_with%")]
+    DeserializeWith,
+    #[token("%serialize_with%")]
+    SerializeWith,
     #[token("%str_split%")]
     StrSplit,
     #[token("%str_trim%")]
@@ -241,6 +287,8 @@ pub enum NormalToken<'input> {
     StrLowercase,
     #[token("%str_contains%")]
     StrContains,
+    #[token("%str_from_num%")]
+    StrFromNum,
     #[token("%str_replace%")]
     StrReplace,
     #[token("%str_replace_regex%")]
@@ -253,10 +301,14 @@ pub enum NormalToken<'input> {
     StrLength,
     #[token("%str_substr%")]
     StrSubstr,
+    #[token("%range_step%")]
+    ArrayRangeStep,
     #[token("%to_str%")]
     ToStr,
     #[token("%num_from_str%")]
     NumFromStr,
+    #[token("%str_to_num%")]
+    StrToNum,
     #[token("%enum_from_str%")]
     EnumFromStr,
 
@@ -303,11 +355,17 @@ pub enum StringToken<'input> {
     DoubleQuote,
     #[token("%{")]
     Interpolation,
-    #[regex("\\\\.", |lex| lex.slice().chars().nth(1))]
+    #[regex("\\\\.", escaped_char)]
     EscapedChar(char),
     // Repetition range `{2}` was not supported at the time of writing this regex.
     #[regex("\\\\x[A-Fa-f0-9][A-Fa-f0-9]", |lex| &lex.slice()[2..4])]
     EscapedAscii(&'input str),
+    /// Token emitted by the modal lexer in place of an invalid `EscapedChar`. The error is
+    /// reported by the parser, which can then go on and report the other invalid escape
+    /// sequences.
+    InvalidEscapeSequence,
+    /// Same as `InvalidEscapeSequence`, but for an invalid `EscapedAscii`.
+    InvalidAsciiEscapeCode,
 }
 
 /// The tokens in multiline string mode.
@@ -353,6 +411,96 @@ pub enum Token<'input> {
     MultiStr(MultiStringToken<'input>),
 }
 
+/// A broad category of tokens, used to summarize long lists of tokens expected by the parser.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum TokenCategory {
+    /// A token which can start an expression.
+    Expression,
+    /// A token which can start a type.
+    Type,
+}
+
+impl TokenCategory {
+    pub fn description(&self) -> &'static str {
+        match self {
+            TokenCategory::Expression => "an expression",
+            TokenCategory::Type => "a type",
+        }
+    }
+}
+
+/// A token expected by the parser, as presented to the user.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ExpectedToken {
+    /// The user-facing representation of the token: either its concrete syntax between backticks,
+    /// such as `` `=>` ``, or a description, such as `an identifier`.
+    pub repr: String,
+    pub category: Option<TokenCategory>,
+}
+
+/// Translate the name of a terminal expected by the parser to its user-facing representation.
+///
+/// LALRPOP reports the names declared in the `extern` section of the grammar, quoted (e.g.
+/// `"\"=>\""`). Most names are the concrete syntax of the token, except for primitive operators,
+/// which are named without their surrounding `%`, and for tokens carrying data, such as literals.
+/// Return `None` for tokens which are internal to the lexer and can't be written as such.
+pub fn expected_token(terminal: &str) -> Option<ExpectedToken> {
+    let name = terminal
+        .strip_prefix('"')
+        .and_then(|name| name.strip_suffix('"'))
+        .unwrap_or(terminal)
+        .replace("\\\"", "\"")
+        .replace("\\\\", "\\");
+
+    let describe = |desc: &str, category| {
+        Some(ExpectedToken {
+            repr: String::from(desc),
+            category,
+        })
+    };
+    let syntax = |syntax: &str, category| {
+        Some(ExpectedToken {
+            repr: format!("`{}`", syntax),
+            category,
+        })
+    };
+
+    match name.as_str() {
+        "identifier" => describe("an identifier", Some(TokenCategory::Expression)),
+        "num literal" => describe("a number", Some(TokenCategory::Expression)),
+        "str literal" | "multstr literal" => describe("a string", None),
+        // Escape sequences are lexed as part of a string literal.
+        "str esc char" | "invalid esc" | "invalid ascii esc" => None,
+        "multstr %{" => syntax("%{", None),
+        // Primitive operators whose name in the grammar differs from the token.
+        "str_from" => syntax("%to_str%", Some(TokenCategory::Expression)),
+        "num_from" => syntax("%num_from_str%", Some(TokenCategory::Expression)),
+        "enum_from" => syntax("%enum_from_str%", Some(TokenCategory::Expression)),
+        "fun" | "let" | "if" | "switch" | "import" | "null" | "true" | "false" | "(" | "["
+        | "{" | "\"" | "m%\"" | "-" | "!" | "`" => syntax(&name, Some(TokenCategory::Expression)),
+        "Num" | "Dyn" | "Str" | "Bool" | "Array" | "forall" | "[|" => {
+            syntax(&name, Some(TokenCategory::Type))
+        }
+        // `type` is only a keyword after `let`, and is accepted as an identifier everywhere else,
+        // which is already reported as such.
+        "type" => None,
+        _ if is_primop(&name) => syntax(&format!("%{}%", name), Some(TokenCategory::Expression)),
+        _ => syntax(&name, None),
+    }
+}
+
+/// Determine if `name` is the name of a primitive operator token, that is if `%name%` is lexed as
+/// one token.
+fn is_primop(name: &str) -> bool {
+    let source = format!("%{}%", name);
+    let mut lexer = NormalToken::lexer(&source);
+
+    matches!(
+        (lexer.next(), lexer.next()),
+        (Some(token), None) if token != NormalToken::Error && token != NormalToken::Percent
+    )
+}
+
 type NormalLexer<'input> = logos::Lexer<'input, NormalToken<'input>>;
 type StrLexer<'input> = logos::Lexer<'input, StringToken<'input>>;
 type MultiStrLexer<'input> = logos::Lexer<'input, MultiStringToken<'input>>;
@@ -416,6 +564,12 @@ pub struct Lexer<'input> {
     /// already inside an interpolated expression. In this case, once this string ends, we must
     /// restore the original brace counter, which is what this stack is used for.
     pub stack: Vec<ModeElt>,
+    /// The positions of the opening braces which are not closed yet, outside of any string.
+    pub braces: Vec<usize>,
+    /// The position of the opening brace of the last brace group closed outside of any string.
+    /// When encountering an unmatched closing brace, this is the brace which it was possibly
+    /// intended to close, an earlier closing brace being superfluous.
+    pub last_closed_brace: Option<usize>,
     /// A token that has been buffered and must be returned at the next call to `next()`. This is
     /// made necessary by an issue of Logos (<https://github.com/maciejhirsz/logos/issues/200>). See
     /// `MultiStringToken::QuotesCandidateInterpolation`.
@@ -428,10 +582,21 @@ impl<'input> Lexer<'input> {
             lexer: Some(ModalLexer::Normal(NormalToken::lexer(s))),
             stack: Vec::new(),
             count: 0,
+            braces: Vec::new(),
+            last_closed_brace: None,
             buffer: None,
         }
     }
 
+    /// The source being lexed.
+    pub fn source(&self) -> &'input str {
+        match self.lexer.as_ref().expect("lexer::source(): no lexer") {
+            ModalLexer::Normal(lexer) => lexer.source(),
+            ModalLexer::Str(lexer) => lexer.source(),
+            ModalLexer::MultiStr(lexer) => lexer.source(),
+        }
+    }
+
     fn enter_strlike<F>(&mut self, morph: F)
     where
         F: FnOnce(NormalLexer<'input>) -> ModalLexer<'input>,
@@ -542,17 +707,28 @@ impl<'input> Iterator for Lexer<'input> {
             Some(Normal(NormalToken::MultiStringStart(hash_count))) => {
                 self.enter_indstr(*hash_count)
             }
-            Some(Normal(NormalToken::LBrace)) => self.count += 1,
+            Some(Normal(NormalToken::LBrace)) => {
+                if self.stack.is_empty() {
+                    self.braces.push(span.start);
+                }
+
+                self.count += 1;
+            }
             Some(Normal(NormalToken::RBrace)) => {
                 if self.count == 0 {
                     if self.stack.is_empty() {
                         return Some(Err(ParseError::Lexical(LexicalError::UnmatchedCloseBrace(
                             span.start,
+                            self.last_closed_brace,
                         ))));
                     }
 
                     self.leave_normal();
                 } else {
+                    if self.stack.is_empty() {
+                        self.last_closed_brace = self.braces.pop();
+                    }
+
                     self.count -= 1;
                 }
             }
@@ -610,22 +786,16 @@ impl<'input> Iterator for Lexer<'input> {
             Some(Str(StringToken::Interpolation)) => self.enter_normal(),
             // Convert escape sequences to the corresponding character.
             Some(Str(StringToken::EscapedChar(c))) => {
-                if let Some(esc) = escape_char(*c) {
-                    token = Some(Str(StringToken::EscapedChar(esc)));
-                } else {
-                    return Some(Err(ParseError::Lexical(
-                        LexicalError::InvalidEscapeSequence(span.start + 1),
-                    )));
-                }
+                token = Some(Str(match escape_char(*c) {
+                    Some(esc) => StringToken::EscapedChar(esc),
+                    None => StringToken::InvalidEscapeSequence,
+                }));
             }
             Some(Str(StringToken::EscapedAscii(code))) => {
-                if let Some(esc) = escape_ascii(code) {
-                    token = Some(Str(StringToken::EscapedChar(esc)));
-                } else {
-                    return Some(Err(ParseError::Lexical(
-                        LexicalError::InvalidAsciiEscapeCode(span.start + 2),
-                    )));
-                }
+                token = Some(Str(match escape_ascii(code) {
+                    Some(esc) => StringToken::EscapedChar(esc),
+                    None => StringToken::InvalidAsciiEscapeCode,
+                }));
             }
             // If we encounter a `CandidateEnd` token with the right number of characters, this is
             // the end of a multiline string
@@ -655,7 +825,42 @@ impl<'input> Iterator for Lexer<'input> {
     }
 }
 
+/// Whether a source ends inside a string or inside a pair of delimiters, that is braces, brackets,
+/// parentheses or an interpolated expression, such that more input may complete it. Used by the
+/// REPL to detect an input spanning several lines. Return `false` on a lexical error, which more
+/// input can't fix.
+pub fn is_unterminated(source: &str) -> bool {
+    let mut lexer = Lexer::new(source);
+    // The nesting of brackets and parentheses. Braces are tracked by the lexer itself.
+    let mut depth: usize = 0;
+
+    for result in lexer.by_ref() {
+        match result {
+            Ok((_, Token::Normal(NormalToken::LBracket), _))
+            | Ok((_, Token::Normal(NormalToken::LParen), _)) => depth += 1,
+            Ok((_, Token::Normal(NormalToken::RBracket), _))
+            | Ok((_, Token::Normal(NormalToken::RParen), _)) => depth = depth.saturating_sub(1),
+            Ok(_) => (),
+            Err(_) => return false,
+        }
+    }
+
+    // Outside of any string, the counter of the lexer is the number of unclosed braces.
+    depth > 0 || lexer.count > 0 || !lexer.stack.is_empty()
+}
+
 /// Generate the character corresponding to an escape char.
+/// Callback of the `EscapedChar` token. Logos only matches the first byte of the escaped
+/// character when it is not ASCII: extend the token to the whole character, such that the token
+/// never ends in the middle of a UTF-8 sequence.
+fn escaped_char<'input>(lex: &mut logos::Lexer<'input, StringToken<'input>>) -> Option<char> {
+    // The backslash is one byte long, so the escaped character starts on a char boundary.
+    let start = lex.span().start + 1;
+    let c = lex.source()[start..].chars().next()?;
+    lex.bump(c.len_utf8() - 1);
+    Some(c)
+}
+
 fn escape_char(chr: char) -> Option<char> {
     match chr {
         '\'' => Some('\''),
@@ -29,12 +29,13 @@ impl grammar::ExtendedTermParser {
         file_id: FileId,
         lexer: lexer::Lexer,
     ) -> Result<(ExtendedTerm, ParseErrors), ParseError> {
+        let source = lexer.source();
         let mut parse_errors = Vec::new();
         let result = self
-            .parse(file_id, &mut parse_errors, lexer)
-            .map_err(|err| ParseError::from_lalrpop(err, file_id));
+            .parse(file_id, &mut parse_errors, &mut Vec::new(), lexer)
+            .map_err(|err| ParseError::from_lalrpop(err, file_id, source));
 
-        let parse_errors = ParseErrors::from_recoverable(parse_errors, file_id);
+        let parse_errors = ParseErrors::from_recoverable(parse_errors, file_id, source);
         match result {
             Ok(t) => Ok((t, parse_errors)),
             Err(e) => Err(e),
@@ -60,12 +61,13 @@ impl grammar::TermParser {
         file_id: FileId,
         lexer: lexer::Lexer,
     ) -> Result<(RichTerm, ParseErrors), ParseError> {
+        let source = lexer.source();
         let mut parse_errors = Vec::new();
         let result = self
-            .parse(file_id, &mut parse_errors, lexer)
-            .map_err(|err| ParseError::from_lalrpop(err, file_id));
+            .parse(file_id, &mut parse_errors, &mut Vec::new(), lexer)
+            .map_err(|err| ParseError::from_lalrpop(err, file_id, source));
 
-        let parse_errors = ParseErrors::from_recoverable(parse_errors, file_id);
+        let parse_errors = ParseErrors::from_recoverable(parse_errors, file_id, source);
         match result {
             Ok(t) => Ok((t, parse_errors)),
             Err(e) => Err(e),
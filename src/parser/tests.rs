@@ -137,6 +137,24 @@ fn lets() {
     assert_matches!(parse("x (let x1 = x2 in x3) y"), Ok(..));
 }
 
+#[test]
+fn pow_precedence() {
+    let pow = |t1: RichTerm, t2: RichTerm| mk_term::op2(BinaryOp::Pow(), t1, t2);
+    let neg = |t: RichTerm| mk_term::op2(BinaryOp::Sub(), Num(0.0), t);
+    let num = |n: f64| RichTerm::from(Num(n));
+
+    assert_eq!(parse_without_pos("-2 ** 2"), neg(pow(num(2.0), num(2.0))));
+    assert_eq!(parse_without_pos("2 ** -2"), pow(num(2.0), neg(num(2.0))));
+    assert_eq!(
+        parse_without_pos("2 ** 3 ** 2"),
+        pow(num(2.0), pow(num(3.0), num(2.0)))
+    );
+    assert_eq!(
+        parse_without_pos("2 * 3 ** 2"),
+        mk_term::op2(BinaryOp::Mult(), num(2.0), pow(num(3.0), num(2.0)))
+    );
+}
+
 #[test]
 fn unary_op() {
     assert_eq!(
@@ -376,3 +394,198 @@ fn line_comments() {
         parse_without_pos("{field = foo}")
     );
 }
+
+#[test]
+fn record_fields_recovery() {
+    let parse_errors = |s: &str| {
+        let id = Files::new().add("<test>", String::from(s));
+
+        super::grammar::TermParser::new()
+            .parse_term(id, Lexer::new(&s))
+            .unwrap_err()
+            .errors
+    };
+
+    // Each malformed field is reported.
+    let errors = parse_errors("{a = 1, b c = 2, d = 3, 4 = 5, e = 6}");
+    assert_eq!(errors.len(), 2);
+    assert!(errors
+        .iter()
+        .all(|e| matches!(e, ParseError::UnexpectedToken(..))));
+
+    // Including the last one.
+    assert_eq!(parse_errors("{a b, c = 1, d e}").len(), 2);
+    assert_eq!(parse_errors("{a = 1, b c}").len(), 1);
+
+    // Well-formed fields are kept.
+    let id = Files::new().add("<test>", String::from("{a = 1, b c = 2, d = 3}"));
+    let (t, errors) = super::grammar::TermParser::new()
+        .parse_term_tolerant(id, Lexer::new("{a = 1, b c = 2, d = 3}"))
+        .unwrap();
+    assert_eq!(errors.errors.len(), 1);
+    assert_matches!(t.as_ref(), RecRecord(fields, ..) if fields.len() == 2);
+}
+
+#[test]
+fn duplicate_fields() {
+    assert_matches!(
+        parse("{a = 1, b = 2, a = 3}"),
+        Err(ParseError::DuplicateField(path, fst, snd)) if path == vec![Ident::from("a")] && fst.start.0 == 1 && snd.start.0 == 15
+    );
+    assert_matches!(
+        parse("{a.b = 1, a.b = \"x\"}"),
+        Err(ParseError::DuplicateField(path, fst, snd)) if path == vec![Ident::from("a"), Ident::from("b")] && fst.start.0 == 1 && snd.end.0 == 13
+    );
+    assert_matches!(
+        parse("{\"a\" = 1, a = 2}"),
+        Err(ParseError::DuplicateField(..))
+    );
+
+    // A quoted name containing a dot is not a path.
+    match parse("{\"a.b\" = 1, \"a.b\" = 2}") {
+        Err(err @ ParseError::DuplicateField(..)) => {
+            assert_matches!(&err, ParseError::DuplicateField(path, ..) if *path == vec![Ident::from("a.b")]);
+            assert_eq!(
+                err.to_string(),
+                "duplicate definition of the field `\"a.b\"`"
+            );
+        }
+        result => panic!("unexpected parse result {:?}", result),
+    }
+
+    // Quoted names which are actually different from the bare ones.
+    assert!(parse("{\"a.b\" = 1, a.b = 2}").is_ok());
+    assert!(parse("{\"a \" = 1, a = 2}").is_ok());
+    // Piecewise definitions, metadata and dynamic fields.
+    assert!(parse("{a = {b = 1}, a = {c = 2}, a.d = 3}").is_ok());
+    assert!(parse("{a | Num, a = 1}").is_ok());
+    assert!(parse("{a | default = 1, a = 2}").is_ok());
+    assert!(parse("{a.b = 1, a.b = 1}").is_ok());
+    assert!(parse("{\"%{x}\" = 1, \"%{x}\" = 2}").is_ok());
+}
+
+#[test]
+fn expected_tokens() {
+    use super::lexer::{expected_token, TokenCategory};
+
+    let repr = |terminal: &str| expected_token(terminal).map(|token| token.repr);
+    assert_eq!(repr("\"=>\""), Some(String::from("`=>`")));
+    assert_eq!(repr("\"\\\"\""), Some(String::from("`\"`")));
+    assert_eq!(repr("\"identifier\""), Some(String::from("an identifier")));
+    assert_eq!(repr("\"tag\""), Some(String::from("`%tag%`")));
+    assert_eq!(repr("\"str_from\""), Some(String::from("`%to_str%`")));
+    assert_eq!(repr("\"str esc char\""), None);
+    assert_eq!(
+        expected_token("\"forall\"").unwrap().category,
+        Some(TokenCategory::Type)
+    );
+
+    // Every token declared in the grammar which is shown with its concrete syntax must actually be
+    // lexed as one token, to keep the translation in sync with the lexer.
+    let grammar = include_str!("../grammar.lalrpop");
+    let externs = &grammar[grammar.find("extern {").unwrap()..];
+    for line in externs.lines() {
+        let terminal = match line.trim().split_once(" => Token::") {
+            Some((terminal, _)) => terminal,
+            None => continue,
+        };
+
+        let syntax = match expected_token(terminal) {
+            Some(token) if token.repr.starts_with('`') => token.repr,
+            _ => continue,
+        };
+        let syntax = &syntax[1..syntax.len() - 1];
+
+        // String delimiters and braces switch the lexer to another mode or require a matching
+        // opening delimiter.
+        if matches!(syntax, "\"" | "m%\"" | "\"%m" | "%{" | "}") {
+            continue;
+        }
+
+        assert_matches!(
+            lex(syntax).as_deref(),
+            Ok([(0, _, end)]) if *end == syntax.len(),
+            "`{}` (from {}) is not lexed as one token",
+            syntax,
+            terminal
+        );
+    }
+}
+
+#[test]
+fn char_spans() {
+    use super::utils::mk_char_span;
+
+    let source = "a🎉é中\\x";
+    let id = Files::new().add("<test>", String::from(source));
+
+    // Every position, including the ones in the middle of a UTF-8 sequence, gives a span on char
+    // boundaries covering the character at this position.
+    for location in 0..=source.len() + 1 {
+        for count in 1..3 {
+            let span = mk_char_span(id, source, location, count);
+            let (start, end) = (span.start.to_usize(), span.end.to_usize());
+
+            assert!(source.is_char_boundary(start) && source.is_char_boundary(end));
+            assert!(start <= location.min(source.len()));
+            assert!(end > location || end == source.len());
+            assert!(source[start..end].chars().count() <= count);
+        }
+    }
+
+    let span = mk_char_span(id, source, 2, 1);
+    assert_eq!(&source[span.start.to_usize()..span.end.to_usize()], "🎉");
+    let span = mk_char_span(id, source, 5, 2);
+    assert_eq!(&source[span.start.to_usize()..span.end.to_usize()], "é中");
+}
+
+#[test]
+fn invalid_escape_sequence_span() {
+    let source = "\"🎉 \\é\"";
+    assert_matches!(
+        parse(source),
+        Err(ParseError::InvalidEscapeSequence(span))
+            if &source[span.start.to_usize()..span.end.to_usize()] == "é"
+    );
+}
+
+#[test]
+fn invalid_escape_sequences_recovery() {
+    let source = r#"{a = "\x", b = "\z \y", c = m%"\%1"%m, d = "\x7g"}"#;
+    let id = Files::new().add("<test>", String::from(source));
+    let errors = super::grammar::TermParser::new()
+        .parse_term(id, Lexer::new(source))
+        .unwrap_err()
+        .errors;
+
+    let spans: Vec<&str> = errors
+        .iter()
+        .map(|e| match e {
+            ParseError::InvalidEscapeSequence(span) | ParseError::InvalidAsciiEscapeCode(span) => {
+                &source[span.start.to_usize()..span.end.to_usize()]
+            }
+            e => panic!("unexpected error {:?}", e),
+        })
+        .collect();
+    assert_eq!(spans.len(), 4);
+    assert_eq!(&spans[..3], &["x", "z", "y"]);
+}
+
+#[test]
+fn unterminated() {
+    use super::lexer::is_unterminated;
+
+    assert!(is_unterminated("{a = 1,"));
+    assert!(is_unterminated("[1, (2"));
+    assert!(is_unterminated("\"abc"));
+    assert!(is_unterminated("\"a %{ {b = 1}.b"));
+    assert!(is_unterminated("m%\"\n  text %{ \"x\" }"));
+    assert!(is_unterminated("m%%\"\n  \"%m"));
+
+    assert!(!is_unterminated("{a = [1, (2)]}"));
+    assert!(!is_unterminated("\"a %{ {b = 1}.b }\""));
+    assert!(!is_unterminated("m%\"\n  text\n\"%m"));
+    assert!(!is_unterminated("1 +"));
+    // A closing delimiter too many can't be fixed by more input.
+    assert!(!is_unterminated("{a = 1}}"));
+}
@@ -1,9 +1,10 @@
 //! Additional AST nodes for the common UniTerm syntax (see RFC002 for more details).
 use super::*;
 use error::ParseError;
-use utils::{build_record, elaborate_field_path, FieldPath, FieldPathElem};
+use utils::{build_record, check_duplicate_fields, elaborate_field_path, FieldPath, FieldPathElem};
 
 use crate::{
+    mk_app,
     position::{RawSpan, TermPos},
     term::{Contract, MergePriority, MetaValue, RecordAttrs, RichTerm, SharedTerm, Term},
     types::{AbsType, Types, UnboundTypeVariableError},
@@ -43,6 +44,82 @@ pub enum UniTermNode {
     Term(RichTerm),
     /// A uniterm that has been determined to be a type.
     Types(Types),
+    /// A type alias in scope, as referred to by an identifier, applied to some of its arguments
+    /// so far. Once all of its arguments are given, it can be converted to a type.
+    TypeAlias(TypeAlias, Ident, Vec<Types>),
+}
+
+impl UniTermNode {
+    /// Interpret an identifier either as a reference to a type alias, if one of this name is in
+    /// scope, or as a variable otherwise. `aliases` is the stack of the type aliases in scope,
+    /// the innermost last.
+    pub fn var_or_alias(aliases: &[TypeAlias], id: Ident) -> Self {
+        match aliases.iter().rev().find(|alias| alias.name == id) {
+            Some(alias) => UniTermNode::TypeAlias(alias.clone(), id, Vec::new()),
+            None => UniTermNode::Var(id),
+        }
+    }
+}
+
+/// A type alias declared by `let type Name params = type in body`.
+///
+/// Aliases are lexically scoped: the parser maintains a stack of the aliases whose body is being
+/// parsed, and an identifier which refers to an alias is parsed as a [`UniTermNode::TypeAlias`]
+/// (see [`UniTermNode::var_or_alias`]). Such a node takes the arguments of the alias by
+/// application, as in `Pair Num Str`, and is expanded to an [`AbsType::Alias`] once complete.
+#[derive(Clone, Debug)]
+pub struct TypeAlias {
+    pub name: Ident,
+    pub params: Vec<Ident>,
+    pub body: Types,
+}
+
+impl TypeAlias {
+    /// Create a type alias. The type variables of the body which aren't parameters are fixed (see
+    /// [`fix_type_vars`]).
+    pub fn new(name: Ident, params: Vec<Ident>, body: Types) -> Self {
+        let mut body = params.iter().rev().fold(body, |acc, id| {
+            Types(AbsType::Forall(id.clone(), Box::new(acc)))
+        });
+        fix_type_vars(&mut body);
+
+        for _ in params.iter() {
+            body = match body.0 {
+                AbsType::Forall(_, ty) => *ty,
+                _ => unreachable!(),
+            };
+        }
+
+        TypeAlias { name, params, body }
+    }
+
+    /// Expand the alias applied to the given arguments. `id` is the identifier referring to the
+    /// alias, and `pos` the position of the application.
+    fn expand(&self, id: Ident, args: Vec<Types>, pos: TermPos) -> Result<Types, ParseError> {
+        if args.len() != self.params.len() {
+            let span = pos.into_opt().or_else(|| id.pos.into_opt()).unwrap();
+            return Err(ParseError::TypeAliasArity(
+                id,
+                self.params.len(),
+                args.len(),
+                span,
+            ));
+        }
+
+        let sigma = self
+            .params
+            .iter()
+            .cloned()
+            .zip(args.iter().cloned())
+            .collect();
+        let expansion = self.body.subst(&sigma);
+
+        Ok(Types(AbsType::Alias(
+            id,
+            args.into_iter().map(Box::new).collect(),
+            Box::new(expansion),
+        )))
+    }
 }
 
 /// A uniterm with positional information.
@@ -65,6 +142,43 @@ impl UniTerm {
         self.pos = pos;
         self
     }
+
+    /// Apply a uniterm to an argument. If the uniterm is a type alias which still expects
+    /// arguments, the argument is interpreted as a type and given to the alias. Otherwise, both
+    /// are interpreted as terms.
+    pub fn app(self, arg: UniTerm) -> Result<UniTerm, ParseError> {
+        match self.node {
+            UniTermNode::TypeAlias(alias, id, mut args) if args.len() < alias.params.len() => {
+                args.push(Types::try_from(arg)?);
+                Ok(UniTerm::from(UniTermNode::TypeAlias(alias, id, args)))
+            }
+            UniTermNode::TypeAlias(alias, id, args) => {
+                let given = args.len() + 1;
+                let pos = match (self.pos.into_opt(), arg.pos.into_opt()) {
+                    (Some(span), Some(arg_span)) => {
+                        RawSpan::fuse(span, arg_span).map_or(self.pos, TermPos::Original)
+                    }
+                    _ => self.pos,
+                };
+                let span = pos.into_opt().or_else(|| id.pos.into_opt()).unwrap();
+
+                Err(ParseError::TypeAliasArity(
+                    id,
+                    alias.params.len(),
+                    given,
+                    span,
+                ))
+            }
+            node => {
+                let t1 = RichTerm::try_from(UniTerm {
+                    node,
+                    pos: self.pos,
+                })?;
+                let t2 = RichTerm::try_from(arg)?;
+                Ok(UniTerm::from(mk_app!(t1, t2)))
+            }
+        }
+    }
 }
 
 impl TryFrom<UniTerm> for Types {
@@ -76,6 +190,7 @@ impl TryFrom<UniTerm> for Types {
             UniTermNode::Record(r) => Types::try_from(r),
             UniTermNode::Types(ty) => Ok(ty),
             UniTermNode::Term(rt) => Ok(Types(AbsType::Flat(rt))),
+            UniTermNode::TypeAlias(alias, id, args) => alias.expand(id, args, ut.pos),
         }
     }
 }
@@ -88,7 +203,8 @@ impl TryFrom<UniTerm> for RichTerm {
         let rt = match node {
             UniTermNode::Var(id) => RichTerm::new(Term::Var(id), pos),
             UniTermNode::Record(r) => RichTerm::try_from(r)?,
-            UniTermNode::Types(ty) => {
+            UniTermNode::Types(_) | UniTermNode::TypeAlias(..) => {
+                let ty = Types::try_from(UniTerm { node, pos })?;
                 ty.contract().map_err(|UnboundTypeVariableError(id)| {
                     // We unwrap the position of the identifier, which must be set at this stage of parsing
                     let pos = id.pos;
@@ -211,6 +327,7 @@ impl UniRecord {
                                         types: Some(ctrt),
                                         contracts,
                                         priority: MergePriority::Normal,
+                                        sensitive: false,
                                         value: None,
                                     }) if contracts.is_empty() => Ok(Types(AbsType::RowExtend(
                                         id,
@@ -269,6 +386,7 @@ impl TryFrom<UniRecord> for RichTerm {
                 })
         } else {
             let UniRecord { fields, attrs, .. } = ur;
+            check_duplicate_fields(&fields)?;
             let elaborated = fields.into_iter().map(|(path, mut rt)| {
                 fix_field_types(&mut rt);
                 elaborate_field_path(path, rt)
@@ -387,6 +505,14 @@ pub fn fix_type_vars(ty: &mut Types) {
             | AbsType::Array(ref mut ty)
             | AbsType::Enum(ref mut ty)
             | AbsType::StaticRecord(ref mut ty) => fix_type_vars_aux(ty.as_mut(), bound_vars),
+            // The arguments of an alias are substituted in its expansion, and are fixed in the
+            // same way.
+            AbsType::Alias(_, ref mut args, ref mut ty) => {
+                for arg in args.iter_mut() {
+                    fix_type_vars_aux(arg.as_mut(), Cow::Borrowed(bound_vars.as_ref()));
+                }
+                fix_type_vars_aux(ty.as_mut(), bound_vars);
+            }
         }
     }
 
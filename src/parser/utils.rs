@@ -1,18 +1,25 @@
 //! Various helpers and companion code for the parser are put here to keep the grammar definition
 //! uncluttered.
+use std::borrow::Cow;
 use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::rc::Rc;
 
 use codespan::FileId;
+use lalrpop_util::ErrorRecovery;
 
+use super::error::{LexicalError, ParseError};
+use super::lexer::Token;
 use crate::{
     identifier::Ident,
     label::Label,
     mk_app, mk_fun,
     position::{RawSpan, TermPos},
-    term::{make as mk_term, BinaryOp, MetaValue, RecordAttrs, RichTerm, StrChunk, Term, UnaryOp},
+    term::{
+        make as mk_term, BinaryOp, MergePriority, MetaValue, RecordAttrs, RichTerm, StrChunk, Term,
+        UnaryOp,
+    },
     types::Types,
 };
 
@@ -43,6 +50,42 @@ pub enum FieldPathElem {
     Expr(RichTerm),
 }
 
+impl FieldPathElem {
+    /// Return the name of the field if it can be determined statically, that is if it is an
+    /// identifier or a quoted field without interpolation. The name is borrowed from the path
+    /// element whenever possible, as this is called on every field of every record.
+    pub fn static_name(&self) -> Option<Cow<'_, str>> {
+        match self {
+            FieldPathElem::Ident(id) => Some(Cow::Borrowed(&id.label)),
+            FieldPathElem::Expr(e) => match e.term.as_ref() {
+                Term::StrChunks(chunks) => match chunks.as_slice() {
+                    [] => Some(Cow::Borrowed("")),
+                    [StrChunk::Literal(lit)] => Some(Cow::Borrowed(lit)),
+                    _ => chunks
+                        .iter()
+                        .try_fold(String::new(), |mut acc, chunk| match chunk {
+                            StrChunk::Literal(lit) => {
+                                acc.push_str(lit);
+                                Some(acc)
+                            }
+                            StrChunk::Expr(..) => None,
+                        })
+                        .map(Cow::Owned),
+                },
+                _ => None,
+            },
+        }
+    }
+
+    /// Return the position of this path element.
+    pub fn pos(&self) -> TermPos {
+        match self {
+            FieldPathElem::Ident(id) => id.pos,
+            FieldPathElem::Expr(e) => e.pos,
+        }
+    }
+}
+
 pub type FieldPath = Vec<FieldPathElem>;
 
 /// A string chunk literal atom, being either a string or a single char.
@@ -55,11 +98,14 @@ pub enum ChunkLiteralPart<'input> {
     Char(char),
 }
 
-/// The last field of a record, that can either be a normal field declaration or an ellipsis.
+/// The last field of a record, that can either be a normal field declaration, an ellipsis, or a
+/// malformed field.
 #[derive(Clone, Debug)]
 pub enum RecordLastField {
     Field((FieldPath, RichTerm)),
     Ellipsis,
+    /// A malformed last field, that the parser recovered from.
+    Error,
 }
 
 /// An infix operator that is not applied. Used for the curried operator syntax (e.g `(==)`)
@@ -180,6 +226,79 @@ pub fn elaborate_field_path(
     (fst, content)
 }
 
+/// Check that no static field path of a record literal is defined twice with a value that can't be
+/// merged, such as in `{foo = 1, bar = 2, foo = 3}`.
+///
+/// Piecewise definitions, as in `{foo = {bar = 1}, foo.baz = 2}`, are legit: only the duplicate
+/// definitions of the same path with a value which isn't a record, called scalar here, are
+/// reported. Defining a scalar several times with the same constant value, or overriding a
+/// default value, is fine as well. Fields whose name is computed dynamically are ignored.
+pub fn check_duplicate_fields(fields: &[(FieldPath, RichTerm)]) -> Result<(), ParseError> {
+    fn is_scalar(t: &RichTerm) -> bool {
+        match t.as_ref() {
+            Term::MetaValue(meta) if meta.priority == MergePriority::Normal => {
+                meta.value.as_ref().map(is_scalar).unwrap_or(false)
+            }
+            Term::Null
+            | Term::Bool(_)
+            | Term::Num(_)
+            | Term::Str(_)
+            | Term::StrChunks(_)
+            | Term::Enum(_)
+            | Term::Array(_)
+            | Term::Fun(..)
+            | Term::FunPattern(..) => true,
+            _ => false,
+        }
+    }
+
+    // Determine if two terms are the same constant, independently of their positions.
+    fn same_constant(t1: &RichTerm, t2: &RichTerm) -> bool {
+        match (t1.as_ref(), t2.as_ref()) {
+            (Term::StrChunks(chunks1), Term::StrChunks(chunks2)) => {
+                chunks1
+                    .iter()
+                    .all(|chunk| matches!(chunk, StrChunk::Literal(_)))
+                    && chunks1 == chunks2
+            }
+            (Term::Null | Term::Bool(_) | Term::Num(_) | Term::Str(_) | Term::Enum(_), term2) => {
+                t1.as_ref() == term2
+            }
+            _ => false,
+        }
+    }
+
+    let mut defined: HashMap<Vec<Cow<'_, str>>, (RawSpan, &RichTerm)> = HashMap::new();
+
+    for (path, t) in fields.iter().filter(|(_, t)| is_scalar(t)) {
+        let names: Option<Vec<Cow<'_, str>>> =
+            path.iter().map(FieldPathElem::static_name).collect();
+        let span = path
+            .first()
+            .zip(path.last())
+            .and_then(|(fst, last)| RawSpan::fuse(fst.pos().into_opt()?, last.pos().into_opt()?));
+
+        if let (Some(names), Some(span)) = (names, span) {
+            match defined.entry(names) {
+                Entry::Occupied(entry) if !same_constant(entry.get().1, t) => {
+                    let path = entry
+                        .key()
+                        .iter()
+                        .map(|name| Ident::from(name.as_ref()))
+                        .collect();
+                    return Err(ParseError::DuplicateField(path, entry.get().0, span));
+                }
+                Entry::Occupied(_) => (),
+                Entry::Vacant(entry) => {
+                    entry.insert((span, t));
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
 /// Build a record from a list of field definitions. If a field is defined several times, the
 /// different definitions are merged.
 pub fn build_record<I>(fields: I, attrs: RecordAttrs) -> Term
@@ -288,6 +407,18 @@ fn merge_field(rterm1: RichTerm, rterm2: RichTerm) -> Option<RichTerm> {
     }
 }
 
+/// Wrap a lexical error detected by the parser as a recoverable error, such that parsing can go on.
+pub fn lexical_error<'input>(
+    error: LexicalError,
+) -> ErrorRecovery<usize, Token<'input>, ParseError> {
+    ErrorRecovery {
+        error: lalrpop_util::ParseError::User {
+            error: ParseError::Lexical(error),
+        },
+        dropped_tokens: Vec::new(),
+    }
+}
+
 /// Make a span from parser byte offsets.
 pub fn mk_span(src_id: FileId, l: usize, r: usize) -> RawSpan {
     RawSpan {
@@ -297,6 +428,34 @@ pub fn mk_span(src_id: FileId, l: usize, r: usize) -> RawSpan {
     }
 }
 
+/// Return the largest char boundary of `source` which is smaller or equal to `index`, or the
+/// length of `source` if `index` is out of bounds.
+pub fn floor_char_boundary(source: &str, index: usize) -> usize {
+    if index >= source.len() {
+        source.len()
+    } else {
+        (0..=index)
+            .rev()
+            .find(|i| source.is_char_boundary(*i))
+            .unwrap_or(0)
+    }
+}
+
+/// Make a span covering `count` characters of `source`, starting from the character at byte
+/// offset `location`, or containing it. The span never splits a UTF-8 sequence: a caret placed
+/// under a non-ASCII character covers the whole character, and not only its first byte. The span
+/// stops at the end of the source.
+pub fn mk_char_span(src_id: FileId, source: &str, location: usize, count: usize) -> RawSpan {
+    let start = floor_char_boundary(source, location);
+    let end = source[start..]
+        .char_indices()
+        .nth(count)
+        .map(|(offset, _)| start + offset)
+        .unwrap_or(source.len());
+
+    mk_span(src_id, start, end)
+}
+
 pub fn mk_pos(src_id: FileId, l: usize, r: usize) -> TermPos {
     TermPos::Original(mk_span(src_id, l, r))
 }
@@ -311,6 +470,11 @@ pub fn mk_label(types: Types, src_id: FileId, l: usize, r: usize) -> Label {
         arg_pos: TermPos::None,
         polarity: true,
         path: Vec::new(),
+        field_path: Default::default(),
+        value_pos: TermPos::None,
+        binding: None,
+        example_pos: TermPos::None,
+        fields: Vec::new(),
     }
 }
 
@@ -449,7 +613,7 @@ pub fn strip_indent(mut chunks: Vec<StrChunk<RichTerm>>) -> Vec<StrChunk<RichTer
     for (index, chunk) in chunks.iter_mut().enumerate() {
         match chunk {
             StrChunk::Literal(ref mut s) => {
-                let mut buffer = String::new();
+                let mut buffer = String::with_capacity(s.len());
                 for c in s.chars() {
                     match c {
                         ' ' | '\t' if start_line && current < min => current += 1,
@@ -479,7 +643,7 @@ pub fn strip_indent(mut chunks: Vec<StrChunk<RichTerm>>) -> Vec<StrChunk<RichTer
                                 .iter()
                                 .all(|c| *c == b' ' || *c == b'\t')
                         {
-                            buffer = String::from(&buffer[(first_index + 1)..]);
+                            buffer.drain(..=first_index);
                         }
                     }
                 }
@@ -47,6 +47,10 @@ pub enum TermPos {
     Original(RawSpan),
     /// The term is the result of the evaluation of an original expression in the source.
     Inherited(RawSpan),
+    /// The term has no span, but is known to come from a file. The deserializers of external
+    /// formats (JSON, YAML and TOML) don't provide positions: the terms they produce only record
+    /// the file they come from.
+    File(FileId),
     /// The term couldn't be assigned a position (usually generated during execution or program
     /// transformations).
     None,
@@ -64,14 +68,14 @@ impl TermPos {
         match self {
             TermPos::Original(x) => TermPos::Original(f(x)),
             TermPos::Inherited(x) => TermPos::Inherited(f(x)),
-            TermPos::None => TermPos::None,
+            p => p,
         }
     }
 
     pub fn as_opt_ref(&self) -> Option<&RawSpan> {
         match self {
             TermPos::Original(ref pos) | TermPos::Inherited(ref pos) => Some(pos),
-            TermPos::None => None,
+            TermPos::File(_) | TermPos::None => None,
         }
     }
 
@@ -80,11 +84,20 @@ impl TermPos {
     pub fn into_opt(self) -> Option<RawSpan> {
         match self {
             TermPos::Original(pos) | TermPos::Inherited(pos) => Some(pos),
+            TermPos::File(_) | TermPos::None => None,
+        }
+    }
+
+    /// Return the file the term comes from, if known, whether the position has a span or not.
+    pub fn file_id(&self) -> Option<FileId> {
+        match self {
+            TermPos::Original(pos) | TermPos::Inherited(pos) => Some(pos.src_id),
+            TermPos::File(file_id) => Some(*file_id),
             TermPos::None => None,
         }
     }
 
-    /// Determine is the position is defined. Return `false` if it is `None`, and `true` otherwise.
+    /// Determine is the position is defined. Return `true` if it has a span, and `false` otherwise.
     pub fn is_def(&self) -> bool {
         matches!(self, TermPos::Original(_) | TermPos::Inherited(_))
     }
@@ -93,7 +106,7 @@ impl TermPos {
     pub fn unwrap(self) -> RawSpan {
         match self {
             TermPos::Original(x) | TermPos::Inherited(x) => x,
-            TermPos::None => panic!("TermPos::unwrap"),
+            TermPos::File(_) | TermPos::None => panic!("TermPos::unwrap"),
         }
     }
 
@@ -3,6 +3,41 @@
 //! A program is Nickel source code loaded from an input. This module offers an interface to load a
 //! program source, parse it, evaluate it and report errors.
 //!
+//! # Embedding
+//!
+//! The entry points of [`Program`] never print anything on their own: they return a structured
+//! [`Error`], whose variants keep the original error of each phase, down to the individual
+//! [`ParseErrors`](../error/struct.ParseErrors.html) of an imported file. A host program can then
+//! either report the error as the command-line interface does, with
+//! [`Program::report`](struct.Program.html#method.report), or render the diagnostics itself from
+//! the file database of the program:
+//!
+//! ```
+//! use codespan_reporting::term::{self, termcolor::Buffer};
+//! use nickel_lang::error::{Error, ToDiagnostic};
+//! use nickel_lang::program::Program;
+//!
+//! let source = "let x : Num = \"a\" in x";
+//! let mut program = Program::new_from_source(source.as_bytes(), "<example>").unwrap();
+//!
+//! let error = match program.eval_full() {
+//!     Err(Error::TypecheckError(error)) => error,
+//!     result => panic!("expected a type error, got {:?}", result),
+//! };
+//!
+//! let contracts_id = program.contracts_id();
+//! let diagnostics = error.to_diagnostic(program.files_mut(), contracts_id);
+//!
+//! let mut buffer = Buffer::no_color();
+//! let config = term::Config::default();
+//! for diagnostic in diagnostics.iter() {
+//!     term::emit(&mut buffer, &config, program.files(), diagnostic).unwrap();
+//! }
+//!
+//! let output = String::from_utf8(buffer.into_inner()).unwrap();
+//! assert!(output.contains("incompatible types"));
+//! ```
+//!
 //! # Standard library
 //!
 //! Some essential functions required for evaluation, such as builtin contracts, are written in
@@ -21,17 +56,34 @@
 //! embedded strings are then parsed by the functions in this module (see
 //! [`mk_global_env`](./struct.Program.html#method.mk_global_env)).  Each such value is added to
 //! the global environment before the evaluation of the program.
+#[cfg(feature = "ast-dump")]
+use crate::ast_dump::{self, AstFormat};
 use crate::cache::*;
-use crate::error::{Error, ToDiagnostic};
+use crate::error::{
+    generate_diagnostics, json, sarif, to_simple_diagnostics_with, Error, EvalError, FileOperation,
+    IOError, ParseError, SimpleDiagnostic, ToDiagnostic, Warning,
+};
+use crate::eval::cancellation::{CancellableWriter, CancellationToken};
 use crate::identifier::Ident;
+use crate::lint::{self, Lint};
 use crate::parser::lexer::Lexer;
-use crate::term::{RichTerm, Term};
-use crate::{eval, parser};
-use codespan::FileId;
-use codespan_reporting::term::termcolor::{ColorChoice, StandardStream};
+use crate::position::RawSpan;
+use crate::report_policy::ReportPolicy;
+use crate::serialize::{DuplicateKeys, ExportFormat, ExportStyle};
+use crate::term::{make as mk_term, BinaryOp, RichTerm, Term, UnaryOp};
+use crate::transform::share_normal_form;
+use crate::{bundle, eval, mk_record, parser, redact, serialize, typecheck};
+use codespan::{FileId, Files};
+use codespan_reporting::term::termcolor::{ColorChoice, NoColor, StandardStream, WriteColor};
+use std::collections::HashSet;
 use std::ffi::OsString;
-use std::io::{self, Read};
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 use std::result::Result;
+use std::str::FromStr;
+use std::sync::Arc;
 
 /// A Nickel program.
 ///
@@ -42,6 +94,224 @@ pub struct Program {
     main_id: FileId,
     /// The cache holding the sources and parsed terms of the main source as well as imports.
     cache: Cache,
+    /// Whether to use colors when reporting errors.
+    color_opt: ColorOpt,
+    /// Whether to replace sensitive values by a placeholder when exporting.
+    redact_sensitive: bool,
+    /// The lints whose warnings are reported as errors.
+    deny: HashSet<Lint>,
+    /// The warnings of the last run of the lints.
+    warnings: Vec<Warning>,
+    /// The overrides merged with the program before evaluation, with the id of their value in
+    /// the file database. See [`Program::set_overrides`].
+    overrides: Vec<(FieldOverride, FileId)>,
+}
+
+/// Whether to use ANSI colors when reporting errors.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum ColorOpt {
+    /// Use colors only if the standard error is a terminal.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl From<ColorOpt> for ColorChoice {
+    fn from(color_opt: ColorOpt) -> Self {
+        match color_opt {
+            ColorOpt::Auto if atty::is(atty::Stream::Stderr) => ColorChoice::Auto,
+            ColorOpt::Auto | ColorOpt::Never => ColorChoice::Never,
+            ColorOpt::Always => ColorChoice::Always,
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ParseColorOptError(String);
+
+impl fmt::Display for ParseColorOptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unsupported color option {} (expected auto, always or never)",
+            self.0
+        )
+    }
+}
+
+impl FromStr for ColorOpt {
+    type Err = ParseColorOptError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "auto" => Ok(ColorOpt::Auto),
+            "always" => Ok(ColorOpt::Always),
+            "never" => Ok(ColorOpt::Never),
+            _ => Err(ParseColorOptError(s.to_string())),
+        }
+    }
+}
+
+/// The format of the errors reported by the command-line interface.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum ErrorFormat {
+    /// Diagnostics pretty-printed as on a terminal.
+    #[default]
+    Human,
+    /// A [SARIF](../error/sarif/index.html) log, for consumption by CI systems.
+    Sarif,
+    /// An array of [JSON diagnostics](../error/json/index.html), for consumption by editors and
+    /// other tools.
+    Json,
+}
+
+impl fmt::Display for ErrorFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorFormat::Human => write!(f, "human"),
+            ErrorFormat::Sarif => write!(f, "sarif"),
+            ErrorFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ParseErrorFormatError(String);
+
+impl fmt::Display for ParseErrorFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unsupported error format {} (expected human, sarif or json)",
+            self.0
+        )
+    }
+}
+
+impl FromStr for ErrorFormat {
+    type Err = ParseErrorFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "human" => Ok(ErrorFormat::Human),
+            "sarif" => Ok(ErrorFormat::Sarif),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(ParseErrorFormatError(s.to_string())),
+        }
+    }
+}
+
+/// An override of a field of the result of a program, given on the command line as
+/// `path.to.field=value`, where `value` is a Nickel expression.
+///
+/// An override is merged with the program before evaluation, such that the contracts of the
+/// program apply to the overridden values. Merging follows the usual rules: a field with a default
+/// value, or without any value, can be overridden, but overriding a field which already has a
+/// value fails, unless both values are records.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct FieldOverride {
+    /// The path of the field.
+    pub path: Vec<Ident>,
+    /// The source of the value.
+    pub value: String,
+}
+
+impl FieldOverride {
+    /// The name of the source of the value in the file database, which is the one appearing in
+    /// error messages.
+    fn source_name(&self) -> String {
+        format!("<override {}>", self.path_string())
+    }
+
+    /// The path of the field, as written on the command line.
+    fn path_string(&self) -> String {
+        self.path
+            .iter()
+            .map(|id| id.label.as_str())
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ParseOverrideError(String);
+
+impl fmt::Display for ParseOverrideError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid override {} (expected path.to.field=value)",
+            self.0
+        )
+    }
+}
+
+impl FromStr for FieldOverride {
+    type Err = ParseOverrideError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (path, value) = s
+            .split_once('=')
+            .ok_or_else(|| ParseOverrideError(s.to_string()))?;
+        let path: Vec<Ident> = path.trim().split('.').map(Ident::from).collect();
+
+        if path.iter().any(|id| id.label.is_empty()) {
+            return Err(ParseOverrideError(s.to_string()));
+        }
+
+        Ok(FieldOverride {
+            path,
+            value: value.to_string(),
+        })
+    }
+}
+
+/// A phase of the pipeline. The errors reported in keep-going mode are grouped by phase, see
+/// [`Program::eval_full_keep_going`].
+#[derive(Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug)]
+pub enum Phase {
+    /// Parsing, including the resolution of imports and the program transformations.
+    Parse,
+    /// Typechecking, including the lints.
+    Typecheck,
+    Eval,
+    /// Serialization of the result.
+    Export,
+}
+
+impl Phase {
+    /// The phase an error usually comes from, for errors which don't happen at a known point of
+    /// the pipeline, such as the errors of the standard library.
+    fn of(error: &Error) -> Self {
+        match error {
+            Error::ParseErrors(_) | Error::ImportError(_) | Error::IOError(_) => Phase::Parse,
+            Error::TypecheckError(_) | Error::DeniedWarnings(_) => Phase::Typecheck,
+            Error::EvalError(_) | Error::ReplError(_) => Phase::Eval,
+            Error::SerializationError(_) => Phase::Export,
+        }
+    }
+
+    /// The exit status of a run whose most severe failure happened during this phase. The
+    /// earlier the phase, the more severe the failure.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Phase::Parse => 3,
+            Phase::Typecheck => 2,
+            Phase::Eval | Phase::Export => 1,
+        }
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Phase::Parse => write!(f, "parsing"),
+            Phase::Typecheck => write!(f, "typechecking"),
+            Phase::Eval => write!(f, "evaluation"),
+            Phase::Export => write!(f, "export"),
+        }
+    }
 }
 
 impl Program {
@@ -54,7 +324,15 @@ impl Program {
         let mut cache = Cache::new();
         let main_id = cache.add_file(path)?;
 
-        Ok(Program { main_id, cache })
+        Ok(Program {
+            main_id,
+            cache,
+            color_opt: ColorOpt::default(),
+            redact_sensitive: false,
+            deny: HashSet::new(),
+            warnings: Vec::new(),
+            overrides: Vec::new(),
+        })
     }
 
     /// Create a program by reading it from a generic source.
@@ -66,7 +344,15 @@ impl Program {
         let mut cache = Cache::new();
         let main_id = cache.add_source(source_name, source)?;
 
-        Ok(Program { main_id, cache })
+        Ok(Program {
+            main_id,
+            cache,
+            color_opt: ColorOpt::default(),
+            redact_sensitive: false,
+            deny: HashSet::new(),
+            warnings: Vec::new(),
+            overrides: Vec::new(),
+        })
     }
 
     /// Retrieve the parsed term and typecheck it, and generate a fresh global environment. Return
@@ -74,25 +360,278 @@ impl Program {
     fn prepare_eval(&mut self) -> Result<(RichTerm, eval::Environment), Error> {
         let GlobalEnv { eval_env, type_env } = self.cache.prepare_stdlib()?;
         self.cache.prepare(self.main_id, &type_env)?;
-        Ok((self.cache.get(self.main_id).unwrap(), eval_env))
+        self.lint(&type_env)?;
+        let t = self.cache.get(self.main_id).unwrap();
+        let t = self.apply_overrides(t, &type_env)?;
+        Ok((t, eval_env))
+    }
+
+    /// Run the [lints](../lint/index.html) on the main program, whose term must be in the cache,
+    /// and record the warnings of the lints which are not denied. Fail with all the warnings of the
+    /// denied lints.
+    fn lint(&mut self, type_env: &typecheck::Environment) -> Result<(), Error> {
+        let stdlib: HashSet<Ident> = type_env.iter_elems().map(|(id, _)| id.clone()).collect();
+        let rt = self
+            .cache
+            .get_ref(self.main_id)
+            .expect("program::lint(): expected the program to be parsed");
+
+        let (denied, warnings): (Vec<Warning>, Vec<Warning>) = lint::shadowed_stdlib(rt, &stdlib)
+            .into_iter()
+            .chain(lint::unused_bindings(rt))
+            .chain(lint::override_bypasses(rt))
+            .partition(|warning| self.deny.contains(&warning.lint()));
+        self.warnings = warnings;
+
+        if denied.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::DeniedWarnings(denied))
+        }
     }
 
     /// Parse if necessary, typecheck and then evaluate the program.
     pub fn eval(&mut self) -> Result<RichTerm, Error> {
         let (t, global_env) = self.prepare_eval()?;
-        eval::eval(t, &global_env, &mut self.cache).map_err(|e| e.into())
+        let start = self.cache.timing_start();
+        let result = eval::eval(t, &global_env, &mut self.cache);
+        self.cache.record_timing(start, |t| &mut t.eval);
+        result.map_err(|e| e.into())
     }
 
     /// Same as `eval`, but proceeds to a full evaluation.
     pub fn eval_full(&mut self) -> Result<RichTerm, Error> {
         let (t, global_env) = self.prepare_eval()?;
-        eval::eval_full(t, &global_env, &mut self.cache).map_err(|e| e.into())
+        let start = self.cache.timing_start();
+        let result = eval::eval_full(t, &global_env, &mut self.cache);
+        self.cache.record_timing(start, |t| &mut t.eval);
+        result.map_err(|e| e.into())
+    }
+
+    /// Same as `eval_full`, but don't stop at the first failing phase, such that the errors of the
+    /// different phases are reported in one run. Return either the result, or the errors of all
+    /// the phases, in the order of the pipeline.
+    ///
+    /// - Parse errors the parser recovered from don't prevent typechecking, the malformed parts
+    ///   of the program being ignored.
+    /// - Type errors don't prevent evaluation. Only the first type error is reported, as the
+    ///   typechecker stops there.
+    /// - If the evaluation fails and the program is a record, its fields are then evaluated
+    ///   separately, such that one error is reported by failing field.
+    ///
+    /// Errors which leave nothing to work with, such as an unrecoverable parse error or a missing
+    /// import, still stop the pipeline.
+    pub fn eval_full_keep_going(&mut self) -> Result<RichTerm, Vec<(Phase, Error)>> {
+        let GlobalEnv { eval_env, type_env } = self
+            .cache
+            .prepare_stdlib()
+            .map_err(|err| vec![(Phase::of(&err), err)])?;
+        let mut errors = Vec::new();
+
+        match self.cache.parse(self.main_id) {
+            Ok(CacheOp::Done(parse_errs) | CacheOp::Cached(parse_errs))
+                if !parse_errs.no_errors() =>
+            {
+                errors.push((Phase::Parse, parse_errs.into()))
+            }
+            Ok(_) => (),
+            Err(err) => return Err(vec![(Phase::Parse, err.into())]),
+        }
+
+        if let Err(cache_err) = self.cache.resolve_imports(self.main_id) {
+            let err = cache_err
+                .unwrap_error("program::eval_full_keep_going(): expected source to be parsed");
+            errors.push((Phase::Parse, err.into()));
+            return Err(errors);
+        }
+
+        if let Err(cache_err) = self.cache.typecheck(self.main_id, &type_env) {
+            let err = cache_err
+                .unwrap_error("program::eval_full_keep_going(): expected source to be parsed");
+            errors.push((Phase::Typecheck, err.into()));
+        }
+
+        if let Err(err) = self.lint(&type_env) {
+            errors.push((Phase::Typecheck, err));
+        }
+
+        if let Err(cache_err) = self.cache.transform(self.main_id) {
+            let err = cache_err
+                .unwrap_error("program::eval_full_keep_going(): expected source to be parsed");
+            errors.push((Phase::Parse, Error::ParseErrors(err.into())));
+            return Err(errors);
+        }
+
+        let t = self.cache.get(self.main_id).unwrap();
+        let t = match self.apply_overrides(t, &type_env) {
+            Ok(t) => t,
+            Err(err) => {
+                errors.push((Phase::of(&err), err));
+                return Err(errors);
+            }
+        };
+        let start = self.cache.timing_start();
+        let result = eval::eval_full(t.clone(), &eval_env, &mut self.cache);
+        self.cache.record_timing(start, |t| &mut t.eval);
+
+        match result {
+            Ok(rt) if errors.is_empty() => return Ok(rt),
+            Ok(_) => (),
+            Err(err) => errors.extend(
+                self.eval_fields(t, &eval_env, err)
+                    .into_iter()
+                    .map(|err| (Phase::Eval, err.into())),
+            ),
+        }
+
+        Err(errors)
+    }
+
+    /// Evaluate the fields of the record `t` separately, once its full evaluation failed with
+    /// `error`. Return the errors of all the failing fields, in the alphabetical order of the
+    /// fields, or just `error` if `t` isn't a record.
+    fn eval_fields(
+        &mut self,
+        t: RichTerm,
+        global_env: &eval::Environment,
+        error: EvalError,
+    ) -> Vec<EvalError> {
+        let mut fields: Vec<Ident> = match eval::eval(t.clone(), global_env, &mut self.cache) {
+            Ok(rt) => match rt.as_ref() {
+                Term::Record(fields, _) => fields.keys().cloned().collect(),
+                _ => return vec![error],
+            },
+            Err(_) => return vec![error],
+        };
+        fields.sort();
+
+        let mut errors: Vec<EvalError> = Vec::new();
+        for id in fields {
+            let field = mk_term::op1(UnaryOp::StaticAccess(id), t.clone());
+            if let Err(err) = eval::eval_full(field, global_env, &mut self.cache) {
+                // Fields depending on the same failing value fail with the same error.
+                if !errors.contains(&err) {
+                    errors.push(err);
+                }
+            }
+        }
+
+        if errors.is_empty() {
+            vec![error]
+        } else {
+            errors
+        }
+    }
+
+    /// Same as `eval`, but the evaluation can be aborted from another thread with `token`, in which
+    /// case it fails with [`EvalError::Cancelled`](../error/enum.EvalError.html#variant.Cancelled).
+    /// The token stays attached to the program afterwards, see
+    /// [`set_cancellation`](#method.set_cancellation).
+    pub fn eval_with_cancellation(&mut self, token: CancellationToken) -> Result<RichTerm, Error> {
+        self.set_cancellation(token);
+        self.eval()
     }
 
     /// Same as `eval_full`, but does not substitute all variables.
     pub fn eval_deep(&mut self) -> Result<RichTerm, Error> {
         let (t, global_env) = self.prepare_eval()?;
-        eval::eval_deep(t, &global_env, &mut self.cache).map_err(|e| e.into())
+        let start = self.cache.timing_start();
+        let result = eval::eval_deep(t, &global_env, &mut self.cache);
+        self.cache.record_timing(start, |t| &mut t.eval);
+        result.map_err(|e| e.into())
+    }
+
+    /// Fully evaluate the program and serialize the result to `output`, or to the standard output
+    /// if `output` is `None`. `style` sets the layout of the JSON output, see [`ExportStyle`].
+    ///
+    /// The output file is created before evaluation starts, such that an unwritable output fails
+    /// right away. The result is written to a temporary file which then replaces `output`: a
+    /// failed export never leaves a truncated output behind. Special files, such as `/dev/stdout`,
+    /// are written in place.
+    pub fn export(
+        &mut self,
+        format: ExportFormat,
+        style: ExportStyle,
+        output: Option<impl Into<PathBuf>>,
+    ) -> Result<(), Error> {
+        let output = output.map(OutputFile::create).transpose()?;
+        let rt = self.eval_full()?;
+        self.write_export(rt, format, style, output)
+    }
+
+    /// Same as [`export`](#method.export), but evaluate the program in keep-going mode, see
+    /// [`eval_full_keep_going`](#method.eval_full_keep_going). Nothing is exported if any phase
+    /// failed.
+    pub fn export_keep_going(
+        &mut self,
+        format: ExportFormat,
+        style: ExportStyle,
+        output: Option<impl Into<PathBuf>>,
+    ) -> Result<(), Vec<(Phase, Error)>> {
+        let output = output
+            .map(OutputFile::create)
+            .transpose()
+            .map_err(|err| vec![(Phase::Export, err.into())])?;
+        let rt = self.eval_full_keep_going()?;
+        self.write_export(rt, format, style, output)
+            .map_err(|err| vec![(Phase::Export, err)])
+    }
+
+    /// Serialize the result of the evaluation to `output`, or to the standard output.
+    fn write_export(
+        &mut self,
+        mut rt: RichTerm,
+        format: ExportFormat,
+        style: ExportStyle,
+        output: Option<OutputFile>,
+    ) -> Result<(), Error> {
+        if self.redact_sensitive {
+            let (redacted_rt, paths) = redact::redact_term(rt);
+            rt = redacted_rt;
+
+            for path in paths {
+                eprintln!("redacted sensitive value: {}", path);
+            }
+        }
+
+        // Serializing a large value takes a while: don't start if the evaluation was cancelled in
+        // the meantime.
+        if self.cache.is_cancelled() {
+            return Err(EvalError::Cancelled(rt.pos).into());
+        }
+
+        serialize::validate(format, &rt)?;
+
+        // The serialization itself is cancelled by failing to write the output.
+        let token = self.cache.cancellation().cloned().unwrap_or_default();
+        let result = if let Some(mut output) = output {
+            let writer = CancellableWriter::new(&mut output.file, token);
+            serialize::to_writer(io::BufWriter::new(writer), format, style, &rt)
+                .and_then(|()| output.commit().map_err(Into::into))
+        } else {
+            let writer = CancellableWriter::new(io::stdout().lock(), token);
+            serialize::to_writer(io::BufWriter::new(writer), format, style, &rt)
+        };
+
+        match result {
+            Err(_) if self.cache.is_cancelled() => Err(EvalError::Cancelled(rt.pos).into()),
+            result => result,
+        }
+    }
+
+    /// Parse the program and print its AST to the standard output, using the tooling
+    /// representation of the [`ast_dump`](../ast_dump/index.html) module. Imports are not resolved
+    /// and no program transformation is applied: the AST is the one produced by the parser.
+    #[cfg(feature = "ast-dump")]
+    pub fn pprint_ast(&mut self, format: AstFormat) -> Result<(), Error> {
+        self.cache.parse(self.main_id)?;
+        let rt = self
+            .cache
+            .get_ref(self.main_id)
+            .expect("program::pprint_ast(): expected source to be parsed");
+        ast_dump::to_writer(io::stdout(), format, self.cache.files(), rt)?;
+        println!();
+        Ok(())
     }
 
     /// Wrapper for [`query`](./fn.query.html).
@@ -102,133 +641,674 @@ impl Program {
     }
 
     /// Load, parse, and typecheck the program and the standard library, if not already done.
+    ///
+    /// Imports are always resolved eagerly here, even if lazy imports are enabled, such that
+    /// imported files are typechecked as well.
     pub fn typecheck(&mut self) -> Result<(), Error> {
         self.cache.parse(self.main_id)?;
         self.cache.load_stdlib()?;
         let global_env = self.cache.mk_types_env().expect("program::typecheck(): stdlib has been loaded but was not found in cache on mk_types_env()");
-        self.cache
-            .resolve_imports(self.main_id)
-            .map_err(|cache_err| {
-                cache_err.unwrap_error("program::typecheck(): expected source to be parsed")
-            })?;
+        self.resolve_imports_eagerly()?;
         self.cache
             .typecheck(self.main_id, &global_env)
             .map_err(|cache_err| {
                 cache_err.unwrap_error("program::typecheck(): expected source to be parsed")
             })?;
+        self.lint(&global_env)
+    }
+
+    /// Parse the program and resolve its imports, without typechecking nor evaluating it, and
+    /// return the absolute paths of the files it transitively imports. See
+    /// [`Cache::dependencies`].
+    ///
+    /// Imports are always resolved eagerly here, even if lazy imports are enabled.
+    pub fn imports(&mut self) -> Result<Vec<PathBuf>, Error> {
+        self.cache.parse(self.main_id)?;
+        self.resolve_imports_eagerly()?;
+        Ok(self.cache.dependencies(self.main_id))
+    }
+
+    /// Parse the program and resolve its imports, without typechecking nor evaluating it, and
+    /// return a single self-contained source bundling the program together with the files it
+    /// transitively imports. See the [`bundle`](../bundle/index.html) module.
+    ///
+    /// Imports are always resolved eagerly here, even if lazy imports are enabled.
+    pub fn bundle(&mut self) -> Result<String, Error> {
+        self.cache.parse(self.main_id)?;
+        self.resolve_imports_eagerly()?;
+        Ok(bundle::bundle(&self.cache, self.main_id)?)
+    }
+
+    /// Bundle the program, see [`bundle`](#method.bundle), and write the result to `output`, or
+    /// to the standard output if `output` is `None`. As for [`export`](#method.export), a failure
+    /// never leaves a truncated output behind.
+    pub fn write_bundle(&mut self, output: Option<impl Into<PathBuf>>) -> Result<(), Error> {
+        let output = output.map(OutputFile::create).transpose()?;
+        let source = self.bundle()?;
+
+        if let Some(mut output) = output {
+            output
+                .file
+                .write_all(source.as_bytes())
+                .map_err(|err| IOError::file(&output.path, FileOperation::Write, err))?;
+            output.commit()?;
+        } else {
+            print!("{}", source);
+        }
+
+        Ok(())
+    }
+
+    /// Resolve the imports of the program, even if lazy imports are enabled.
+    fn resolve_imports_eagerly(&mut self) -> Result<(), Error> {
+        let lazy_imports = self.cache.lazy_imports();
+        self.cache.set_lazy_imports(false);
+        let import_res = self.cache.resolve_imports(self.main_id);
+        self.cache.set_lazy_imports(lazy_imports);
+        import_res.map_err(|cache_err| {
+            cache_err
+                .unwrap_error("program::resolve_imports_eagerly(): expected source to be parsed")
+        })?;
         Ok(())
     }
 
-    /// Wrapper for [`report`](./fn.report.html).
+    /// The file database of the program, holding the sources of the program, of its imports and of
+    /// the standard library. Required to render the diagnostics of an error.
+    pub fn files(&self) -> &Files<String> {
+        self.cache.files()
+    }
+
+    /// Mutable access to the file database of the program. Generating the diagnostics of an error
+    /// with [`ToDiagnostic::to_diagnostic`] may add snippets to the database.
+    pub fn files_mut(&mut self) -> &mut Files<String> {
+        self.cache.files_mut()
+    }
+
+    /// The id of the file defining the builtin contracts, to be passed to
+    /// [`ToDiagnostic::to_diagnostic`]. See
+    /// [`Cache::contracts_id`](../cache/struct.Cache.html#method.contracts_id).
+    pub fn contracts_id(&self) -> Option<FileId> {
+        self.cache.contracts_id()
+    }
+
+    /// Wrapper for [`report_with_color`](./fn.report_with_color.html), using the color option of
+    /// the program (see [`set_color`](#method.set_color)).
     pub fn report<E>(&mut self, error: E)
     where
         E: ToDiagnostic<FileId>,
     {
-        report(&mut self.cache, error)
+        report_with_color(&mut self.cache, error, self.color_opt)
     }
 
-    #[cfg(debug_assertions)]
-    pub fn set_skip_stdlib(&mut self) {
-        self.cache.skip_stdlib = true;
+    /// Pretty-print an error to a writer, without colors.
+    pub fn report_to_writer<E, W>(&mut self, error: E, writer: W) -> io::Result<()>
+    where
+        E: ToDiagnostic<FileId>,
+        W: io::Write,
+    {
+        report_to(&mut self.cache, error, &mut NoColor::new(writer))
+            .map_err(|err| io::Error::other(err.to_string()))
     }
-}
 
-/// Query the metadata of a path of a term in the cache.
-///
-/// The path is a list of dot separated identifiers. For example, querying `{a = {b  = ..}}` with
-/// path `a.b` will return a "weak" (see below) evaluation of `b`.
-///
-/// "Weak" means that as opposed to normal evaluation, it does not try to unwrap the content of a
-/// metavalue: the evaluation stops as soon as a metavalue is encountered, although the potential
-/// term inside the meta-value is forced, so that the concrete value of the field may also be
-/// reported when present.
-//TODO: more robust implementation than `let x = (y.path) in %seq% x x`, with respect to e.g.
-//error message in case of syntax error or missing file.
-//TODO: also gather type information, such that `query a.b.c <<< '{ ... } : {a: {b: {c: Num}}}`
-//would additionally report `type: Num` for example.
-//TODO: not sure where this should go. It seems to embed too much logic to be in `Cache`, but is
-//common to both `Program` and `Repl`. Leaving it here as a stand-alone function for now
-pub fn query(
-    cache: &mut Cache,
-    file_id: FileId,
-    global_env: &GlobalEnv,
-    path: Option<String>,
-) -> Result<Term, Error> {
-    cache.prepare(file_id, &global_env.type_env)?;
+    /// Write an error as a [SARIF](../error/sarif/index.html) log. As for
+    /// [`report`](#method.report), sensitive values are redacted and the reporting policy is
+    /// applied.
+    pub fn report_sarif<E, W>(&self, error: &E, writer: &mut W) -> io::Result<()>
+    where
+        E: ToDiagnostic<FileId>,
+        W: io::Write,
+    {
+        self.report_sarif_all(std::iter::once(error), writer)
+    }
 
-    let t = if let Some(p) = path {
-        // Parsing `y.path`. We `seq` it to force the evaluation of the underlying value,
-        // which can be then showed to the user. The newline gives better messages in case of
-        // errors.
-        let source = format!("x.{}", p);
-        let query_file_id = cache.add_tmp("<query>", source.clone());
-        let new_term =
-            parser::grammar::TermParser::new().parse_term(query_file_id, Lexer::new(&source))?;
+    /// Same as [`report_sarif`](#method.report_sarif), but write several errors as one log.
+    pub fn report_sarif_all<'a, E, I, W>(&self, errors: I, writer: &mut W) -> io::Result<()>
+    where
+        E: ToDiagnostic<FileId> + 'a,
+        I: IntoIterator<Item = &'a E>,
+        W: io::Write,
+    {
+        self.write_sarif_log(&[], errors, writer)
+    }
 
-        // Substituting `y` for `t`
-        let mut env = eval::Environment::new();
-        eval::env_add(
-            &mut env,
-            Ident::from("x"),
-            cache.get_owned(file_id).unwrap(),
-            eval::Environment::new(),
-        );
-        eval::subst(new_term, &eval::Environment::new(), &env)
-    } else {
-        cache.get_owned(file_id).unwrap()
-    };
+    /// Write an error as an array of one [JSON diagnostic](../error/json/index.html). As for
+    /// [`report`](#method.report), sensitive values are redacted and the reporting policy is
+    /// applied.
+    pub fn report_json<W>(&self, error: &Error, writer: &mut W) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        self.report_json_all(std::iter::once(error), writer)
+    }
 
-    Ok(eval::eval_meta(t, &global_env.eval_env, cache)?.into())
-}
+    /// Same as [`report_json`](#method.report_json), but write several errors as one array.
+    pub fn report_json_all<'a, I, W>(&self, errors: I, writer: &mut W) -> io::Result<()>
+    where
+        I: IntoIterator<Item = &'a Error>,
+        W: io::Write,
+    {
+        self.write_json_diagnostics(&[], errors, writer)
+    }
 
-/// Pretty-print an error.
-///
-/// This function is located here in `Program` because errors need a reference to `files` in
-/// order to produce a diagnostic (see [`label_alt`](../error/fn.label_alt.html)).
-//TODO: not sure where this should go. It seems to embed too much logic to be in `Cache`, but is
-//common to both `Program` and `Repl`. Leaving it here as a stand-alone function for now
-pub fn report<E>(cache: &mut Cache, error: E)
-where
-    E: ToDiagnostic<FileId>,
-{
-    let writer = StandardStream::stderr(ColorChoice::Always);
-    let config = codespan_reporting::term::Config::default();
-    let contracts_id = cache.id_of("<stdlib/contract.ncl>");
-    let diagnostics = error.to_diagnostic(cache.files_mut(), contracts_id);
+    /// Same as [`report_sarif_all`](#method.report_sarif_all), but the log starts with the
+    /// [`warnings`](#method.warnings) of the last run, such that warnings and errors are written
+    /// as one log. There may be no error.
+    pub fn report_sarif_run<'a, E, I, W>(&self, errors: I, writer: &mut W) -> io::Result<()>
+    where
+        E: ToDiagnostic<FileId> + 'a,
+        I: IntoIterator<Item = &'a E>,
+        W: io::Write,
+    {
+        self.write_sarif_log(&self.warnings, errors, writer)
+    }
 
-    let result = diagnostics.iter().try_for_each(|d| {
-        codespan_reporting::term::emit(&mut writer.lock(), &config, cache.files_mut(), d)
-    });
-    match result {
-        Ok(()) => (),
-        Err(err) => panic!(
-            "Program::report: could not print an error on stderr: {}",
-            err
-        ),
-    };
-}
+    /// Same as [`report_json_all`](#method.report_json_all), but the array starts with the
+    /// [`warnings`](#method.warnings) of the last run, such that warnings and errors are written
+    /// as one array. There may be no error.
+    pub fn report_json_run<'a, I, W>(&self, errors: I, writer: &mut W) -> io::Result<()>
+    where
+        I: IntoIterator<Item = &'a Error>,
+        W: io::Write,
+    {
+        self.write_json_diagnostics(&self.warnings, errors, writer)
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::error::EvalError;
-    use crate::parser::{grammar, lexer};
-    use crate::position::TermPos;
-    use crate::term::SharedTerm;
-    use codespan::Files;
-    use std::io::Cursor;
+    /// Write warnings followed by errors as one SARIF log.
+    fn write_sarif_log<'a, E, I, W>(
+        &self,
+        warnings: &[Warning],
+        errors: I,
+        writer: &mut W,
+    ) -> io::Result<()>
+    where
+        E: ToDiagnostic<FileId> + 'a,
+        I: IntoIterator<Item = &'a E>,
+        W: io::Write,
+    {
+        let diagnostics: Vec<SimpleDiagnostic> = warnings
+            .iter()
+            .flat_map(|warning| report_simple(&self.cache, warning))
+            .chain(
+                errors
+                    .into_iter()
+                    .flat_map(|error| report_simple(&self.cache, error)),
+            )
+            .collect();
+        let log = sarif::to_sarif(&diagnostics);
+        serde_json::to_writer_pretty(&mut *writer, &log)?;
+        writeln!(writer)
+    }
 
-    fn parse(s: &str) -> Option<RichTerm> {
-        let id = Files::new().add("<test>", String::from(s));
+    /// Write warnings followed by errors as one array of JSON diagnostics.
+    fn write_json_diagnostics<'a, I, W>(
+        &self,
+        warnings: &[Warning],
+        errors: I,
+        writer: &mut W,
+    ) -> io::Result<()>
+    where
+        I: IntoIterator<Item = &'a Error>,
+        W: io::Write,
+    {
+        let contracts_id = self.cache.id_of("<stdlib/contract.ncl>");
+        let opts = self.cache.diagnostic_options();
+        let diagnostics: Vec<serde_json::Value> = warnings
+            .iter()
+            .map(|warning| {
+                json::warning_to_json_diagnostic_with(
+                    warning,
+                    self.cache.files(),
+                    contracts_id,
+                    opts,
+                )
+            })
+            .chain(errors.into_iter().map(|error| {
+                json::to_json_diagnostic_with(error, self.cache.files(), contracts_id, opts)
+            }))
+            .collect();
 
-        grammar::TermParser::new()
-            .parse_term(id, lexer::Lexer::new(&s))
-            .map(RichTerm::without_pos)
-            .map_err(|err| println!("{:?}", err))
-            .ok()
+        serde_json::to_writer_pretty(&mut *writer, &diagnostics)?;
+        writeln!(writer)
     }
 
-    fn eval_full(s: &str) -> Result<RichTerm, Error> {
+    /// Set whether to use colors when reporting errors. Default to [`ColorOpt::Auto`].
+    pub fn set_color(&mut self, color_opt: ColorOpt) {
+        self.color_opt = color_opt;
+    }
+
+    /// Set whether [`export`](#method.export) replaces the values marked as `sensitive` by a
+    /// placeholder, instead of outputting them. The paths of the redacted values are printed on
+    /// the standard error. Default to `false`.
+    pub fn set_redact_sensitive(&mut self, redact_sensitive: bool) {
+        self.redact_sensitive = redact_sensitive;
+    }
+
+    /// Set the lints whose warnings are reported as errors, making the evaluation or the
+    /// typechecking of the program fail. No lint is denied by default.
+    pub fn set_deny(&mut self, lints: impl IntoIterator<Item = Lint>) {
+        self.deny = lints.into_iter().collect();
+    }
+
+    /// Set the overrides merged with the program before evaluation, replacing the previous ones.
+    /// See [`FieldOverride`]. The value of each override is added to the file database as a
+    /// source of its own, named after the path of the field, such as `<override server.port>`.
+    ///
+    /// Fail if a field is overridden twice.
+    pub fn set_overrides(
+        &mut self,
+        overrides: impl IntoIterator<Item = FieldOverride>,
+    ) -> Result<(), Error> {
+        self.overrides.clear();
+
+        for over in overrides {
+            let file_id = self
+                .cache
+                .add_string(over.source_name(), over.value.clone());
+
+            if let Some((_, fst_id)) = self.overrides.iter().find(|(o, _)| o.path == over.path) {
+                return Err(ParseError::DuplicateOverride(
+                    over.path_string(),
+                    self.whole_source(*fst_id),
+                    self.whole_source(file_id),
+                )
+                .into());
+            }
+
+            self.overrides.push((over, file_id));
+        }
+
+        Ok(())
+    }
+
+    /// The span of a whole source.
+    fn whole_source(&self, file_id: FileId) -> RawSpan {
+        RawSpan {
+            src_id: file_id,
+            start: 0.into(),
+            end: (self.cache.files().source(file_id).len() as u32).into(),
+        }
+    }
+
+    /// Merge the overrides with the term of the program, which must have been transformed. The
+    /// values of the overrides go through the same phases as the program.
+    fn apply_overrides(
+        &mut self,
+        t: RichTerm,
+        type_env: &typecheck::Environment,
+    ) -> Result<RichTerm, Error> {
+        let mut result = t;
+
+        for (over, file_id) in self.overrides.clone() {
+            self.cache.prepare(file_id, type_env)?;
+            let value = self.cache.get_owned(file_id).unwrap();
+
+            // The value is wrapped in records following the path, put in share normal form as
+            // the program has been.
+            let record = over.path.iter().rev().fold(value, |acc, id| {
+                share_normal_form::transform_one(mk_record!((id.clone(), acc)))
+            });
+            result = mk_term::op2(BinaryOp::Merge(), result, record);
+        }
+
+        Ok(result)
+    }
+
+    /// The warnings reported by the lints when the program was last typechecked or evaluated,
+    /// excluding the ones of denied lints, which are errors. See the [`lint`](../lint/index.html)
+    /// module.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    /// Collect the durations of the different phases of the execution of the program, which can
+    /// then be retrieved with [`timings`](#method.timings).
+    pub fn enable_timings(&mut self) {
+        self.cache.enable_timings();
+    }
+
+    /// Return the timings collected so far, or `None` if timings are not enabled.
+    pub fn timings(&self) -> Option<&Timings> {
+        self.cache.timings()
+    }
+
+    /// Resolve imports only when they are forced during evaluation. See
+    /// [`Cache::set_lazy_imports`](../cache/struct.Cache.html#method.set_lazy_imports).
+    pub fn set_lazy_imports(&mut self, lazy: bool) {
+        self.cache.set_lazy_imports(lazy);
+    }
+
+    /// Set the maximum number of parse errors reported. See
+    /// [`Cache::set_max_parse_errors`](../cache/struct.Cache.html#method.set_max_parse_errors).
+    pub fn set_max_parse_errors(&mut self, max: Option<usize>) {
+        self.cache.set_max_parse_errors(max);
+    }
+
+    /// Set the number of calls reported at each end of the call stack of a blame error. See
+    /// [`Cache::set_call_stack_limit`](../cache/struct.Cache.html#method.set_call_stack_limit).
+    pub fn set_call_stack_limit(&mut self, limit: Option<usize>) {
+        self.cache.set_call_stack_limit(limit);
+    }
+
+    /// Set the policy applied to the diagnostics of errors before they are reported. See
+    /// [`Cache::set_report_policy`](../cache/struct.Cache.html#method.set_report_policy).
+    pub fn set_report_policy(&mut self, policy: Arc<dyn ReportPolicy>) {
+        self.cache.set_report_policy(policy);
+    }
+
+    /// Whether an error makes the program fail, according to the reporting policy, if any. Errors
+    /// are always fatal without a policy.
+    pub fn is_fatal(&self, error: &Error) -> bool {
+        match &self.cache.diagnostic_options().policy {
+            Some(policy) => policy.is_fatal(error.code()),
+            None => true,
+        }
+    }
+
+    /// Set how duplicate keys are handled in JSON imports. See
+    /// [`Cache::set_json_duplicate_keys`](../cache/struct.Cache.html#method.set_json_duplicate_keys).
+    pub fn set_json_duplicate_keys(&mut self, duplicate_keys: DuplicateKeys) {
+        self.cache.set_json_duplicate_keys(duplicate_keys);
+    }
+
+    /// Set where the standard library is loaded from. See
+    /// [`Cache::set_stdlib`](../cache/struct.Cache.html#method.set_stdlib).
+    pub fn set_stdlib(&mut self, stdlib: StdlibSource) {
+        self.cache.set_stdlib(stdlib);
+    }
+
+    /// Set the directories searched for relative imports which are not found next to the importing
+    /// file. See [`Cache::set_import_paths`](../cache/struct.Cache.html#method.set_import_paths).
+    pub fn set_import_paths(&mut self, paths: Vec<PathBuf>) {
+        self.cache.set_import_paths(paths);
+    }
+
+    /// Set the token checked during evaluation, such that the evaluation can be aborted from
+    /// another thread. See [`eval::cancellation`](../eval/cancellation/index.html).
+    pub fn set_cancellation(&mut self, token: CancellationToken) {
+        self.cache.set_cancellation(token);
+    }
+}
+
+/// An output file being written.
+///
+/// The content of a regular file, or of a file which doesn't exist yet, is written to a temporary
+/// file in the same directory, which is atomically renamed to the file by
+/// [`commit`](#method.commit). If the output is dropped before being committed, the temporary file
+/// is removed and the file is left untouched. An existing file keeps its permissions, and a
+/// symbolic link is kept as is: its target is replaced.
+///
+/// Special files, such as `/dev/stdout` or named pipes, can't be replaced: they are written in
+/// place.
+struct OutputFile {
+    /// The path given by the user, which is reported in errors.
+    path: PathBuf,
+    /// The file replaced on commit, that is `path` with its symbolic links resolved.
+    target: PathBuf,
+    /// The temporary file, or `None` if the output is written in place.
+    tmp_path: Option<PathBuf>,
+    file: fs::File,
+    committed: bool,
+}
+
+/// The maximum number of symbolic links followed when resolving an output path, as a symbolic link
+/// may point to itself.
+const MAX_SYMLINKS: usize = 40;
+
+impl OutputFile {
+    /// Open the output `path`: create its temporary file, or open it in place if it is a special
+    /// file.
+    fn create(path: impl Into<PathBuf>) -> Result<Self, IOError> {
+        let path = path.into();
+        let target = resolve_symlinks(&path);
+        let metadata = fs::metadata(&target).ok();
+
+        match &metadata {
+            Some(metadata) if metadata.is_dir() => {
+                return Err(IOError::file(
+                    path,
+                    FileOperation::Create,
+                    io::Error::other("is a directory"),
+                ));
+            }
+            Some(metadata) if !metadata.is_file() => {
+                let file = fs::OpenOptions::new()
+                    .write(true)
+                    .open(&target)
+                    .map_err(|err| IOError::file(&path, FileOperation::Create, err))?;
+
+                return Ok(OutputFile {
+                    path,
+                    target,
+                    tmp_path: None,
+                    file,
+                    committed: false,
+                });
+            }
+            _ => (),
+        }
+
+        let file_name = target.file_name().unwrap_or_default().to_string_lossy();
+        let tmp_path = target.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+        let file = fs::File::create(&tmp_path)
+            .map_err(|err| IOError::file(&path, FileOperation::Create, err))?;
+
+        let output = OutputFile {
+            path,
+            target,
+            tmp_path: Some(tmp_path),
+            file,
+            committed: false,
+        };
+
+        if let Some(metadata) = metadata {
+            output
+                .file
+                .set_permissions(metadata.permissions())
+                .map_err(|err| IOError::file(&output.path, FileOperation::Create, err))?;
+        }
+
+        Ok(output)
+    }
+
+    /// Flush the temporary file and move it to the target path. Nothing is left to do for an
+    /// output written in place.
+    fn commit(mut self) -> Result<(), IOError> {
+        if let Some(tmp_path) = &self.tmp_path {
+            self.file
+                .sync_all()
+                .map_err(|err| IOError::file(&self.path, FileOperation::Write, err))?;
+            fs::rename(tmp_path, &self.target)
+                .map_err(|err| IOError::file(&self.path, FileOperation::Rename, err))?;
+        }
+
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for OutputFile {
+    fn drop(&mut self) {
+        if let (false, Some(tmp_path)) = (self.committed, &self.tmp_path) {
+            let _ = fs::remove_file(tmp_path);
+        }
+    }
+}
+
+/// Resolve the symbolic links of a path, such that the target of a link is written instead of the
+/// link being replaced. The target of a dangling link is returned as is.
+fn resolve_symlinks(path: &Path) -> PathBuf {
+    let mut target = path.to_path_buf();
+
+    for _ in 0..MAX_SYMLINKS {
+        match fs::read_link(&target) {
+            // A relative link is relative to the directory of the link.
+            Ok(link) => {
+                target = match target.parent() {
+                    Some(parent) => parent.join(link),
+                    None => link,
+                }
+            }
+            Err(_) => break,
+        }
+    }
+
+    target
+}
+
+/// Query the metadata of a path of a term in the cache.
+///
+/// The path is a list of dot separated identifiers. For example, querying `{a = {b  = ..}}` with
+/// path `a.b` will return a "weak" (see below) evaluation of `b`.
+///
+/// "Weak" means that as opposed to normal evaluation, it does not try to unwrap the content of a
+/// metavalue: the evaluation stops as soon as a metavalue is encountered, although the potential
+/// term inside the meta-value is forced, so that the concrete value of the field may also be
+/// reported when present.
+//TODO: more robust implementation than `let x = (y.path) in %seq% x x`, with respect to e.g.
+//error message in case of syntax error or missing file.
+//TODO: also gather type information, such that `query a.b.c <<< '{ ... } : {a: {b: {c: Num}}}`
+//would additionally report `type: Num` for example.
+//TODO: not sure where this should go. It seems to embed too much logic to be in `Cache`, but is
+//common to both `Program` and `Repl`. Leaving it here as a stand-alone function for now
+pub fn query(
+    cache: &mut Cache,
+    file_id: FileId,
+    global_env: &GlobalEnv,
+    path: Option<String>,
+) -> Result<Term, Error> {
+    cache.prepare(file_id, &global_env.type_env)?;
+
+    let t = if let Some(p) = path {
+        // Parsing `y.path`. We `seq` it to force the evaluation of the underlying value,
+        // which can be then showed to the user. The newline gives better messages in case of
+        // errors.
+        let source = format!("x.{}", p);
+        let query_file_id = cache.add_tmp("<query>", source.clone());
+        let new_term =
+            parser::grammar::TermParser::new().parse_term(query_file_id, Lexer::new(&source))?;
+
+        // Substituting `y` for `t`
+        let mut env = eval::Environment::new();
+        eval::env_add(
+            &mut env,
+            Ident::from("x"),
+            cache.get_owned(file_id).unwrap(),
+            eval::Environment::new(),
+        );
+        eval::subst(new_term, &eval::Environment::new(), &env)
+    } else {
+        cache.get_owned(file_id).unwrap()
+    };
+
+    Ok(eval::eval_meta(t, &global_env.eval_env, cache)?.into())
+}
+
+/// Pretty-print an error.
+///
+/// This function is located here in `Program` because errors need a reference to `files` in
+/// order to produce a diagnostic (see [`label_alt`](../error/fn.label_alt.html)).
+//TODO: not sure where this should go. It seems to embed too much logic to be in `Cache`, but is
+//common to both `Program` and `Repl`. Leaving it here as a stand-alone function for now
+pub fn report<E>(cache: &mut Cache, error: E)
+where
+    E: ToDiagnostic<FileId>,
+{
+    report_with_color(cache, error, ColorOpt::default())
+}
+
+/// Pretty-print an error on the standard error, using colors according to `color_opt`.
+pub fn report_with_color<E>(cache: &mut Cache, error: E, color_opt: ColorOpt)
+where
+    E: ToDiagnostic<FileId>,
+{
+    let writer = StandardStream::stderr(color_opt.into());
+    let result = report_to(cache, error, &mut writer.lock());
+
+    match result {
+        Ok(()) => (),
+        Err(err) => panic!(
+            "Program::report: could not print an error on stderr: {}",
+            err
+        ),
+    };
+}
+
+/// Pretty-print an error to a writer. Colors are used if the writer supports them.
+///
+/// The sensitive values met during evaluation are redacted from the output (see
+/// [`redact`](../redact/index.html)).
+pub(crate) fn report_to<E, W>(
+    cache: &mut Cache,
+    error: E,
+    writer: &mut W,
+) -> Result<(), codespan_reporting::files::Error>
+where
+    E: ToDiagnostic<FileId>,
+    W: WriteColor,
+{
+    let config = codespan_reporting::term::Config::default();
+    let contracts_id = cache.contracts_id();
+    // Diagnostics add throwaway snippets, such as rendered types, to the file database. They are
+    // kept apart, such that reporting errors repeatedly, e.g. in the REPL, doesn't grow the file
+    // database of the cache.
+    let (mut files, mut diagnostics) = generate_diagnostics(
+        &error,
+        cache.files(),
+        contracts_id,
+        cache.diagnostic_options(),
+    );
+    files.redact_sources(&mut diagnostics);
+
+    diagnostics
+        .iter()
+        .try_for_each(|d| codespan_reporting::term::emit(writer, &config, &files, d))
+}
+
+/// Convert an error to a list of self-contained diagnostics, without mutating the file database.
+///
+/// This is an alternative to [`report`] for frontends which can't use the terminal renderer, such
+/// as the WASM REPL. See [`SimpleDiagnostic`]. As for [`report`], sensitive values are redacted from
+/// the messages, the notes and the generated snippets.
+pub fn report_simple<E>(cache: &Cache, error: &E) -> Vec<SimpleDiagnostic>
+where
+    E: ToDiagnostic<FileId>,
+{
+    to_simple_diagnostics_with(
+        error,
+        cache.files(),
+        cache.contracts_id(),
+        cache.diagnostic_options(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{
+        to_simple_diagnostics, EvalError, ImportError, ParseError, ParseErrors, TypecheckError,
+        DEFAULT_CALL_STACK_LIMIT, INTERNAL_ERROR_MSG, MAX_PARSE_DIAGNOSTICS,
+    };
+    use crate::parser::{grammar, lexer};
+    use crate::position::RawSpan;
+    use crate::position::TermPos;
+    use crate::term::SharedTerm;
+    use codespan::Files;
+    use codespan_reporting::diagnostic::Severity;
+    use codespan_reporting::term::termcolor::Buffer;
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    fn parse(s: &str) -> Option<RichTerm> {
+        let id = Files::new().add("<test>", String::from(s));
+
+        grammar::TermParser::new()
+            .parse_term(id, lexer::Lexer::new(&s))
+            .map(RichTerm::without_pos)
+            .map_err(|err| println!("{:?}", err))
+            .ok()
+    }
+
+    fn eval_full(s: &str) -> Result<RichTerm, Error> {
         let src = Cursor::new(s);
 
         let mut p = Program::new_from_source(src, "<test>").map_err(|io_err| {
@@ -271,4 +1351,1395 @@ mod tests {
         // that this test fails.
         eval_full("{y = fun x => x, x = fun y => y}").unwrap();
     }
+
+    #[test]
+    fn timings() {
+        let mut p = Program::new_from_source(Cursor::new("1 + 1"), "<test>").unwrap();
+        p.eval_full().unwrap();
+        assert_eq!(p.timings(), None);
+
+        let mut p = Program::new_from_source(Cursor::new("1 + 1"), "<test>").unwrap();
+        p.enable_timings();
+        p.eval_full().unwrap();
+        let timings = p.timings().unwrap();
+        assert!(timings.parse > Duration::ZERO);
+        assert!(timings.eval > Duration::ZERO);
+    }
+
+    #[test]
+    fn export_fails_before_evaluation() {
+        let mut p = Program::new_from_source(Cursor::new("{a = 1}"), "<test>").unwrap();
+        p.enable_timings();
+
+        let output = std::env::temp_dir()
+            .join("nickel-export-missing-dir")
+            .join("out.json");
+        let result = p.export(ExportFormat::Json, ExportStyle::default(), Some(&output));
+
+        assert_matches::assert_matches!(
+            result,
+            Err(Error::IOError(IOError::File {
+                op: FileOperation::Create,
+                kind: io::ErrorKind::NotFound,
+                ..
+            }))
+        );
+        // Nothing has been evaluated, nor even parsed.
+        assert_eq!(p.timings(), Some(&Timings::default()));
+    }
+
+    #[test]
+    fn export_replaces_output_atomically() {
+        let dir = std::env::temp_dir().join(format!("nickel-export-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("out.json");
+
+        let mut p = Program::new_from_source(Cursor::new("{a = 1}"), "<test>").unwrap();
+        p.export(ExportFormat::Json, ExportStyle::default(), Some(&output))
+            .unwrap();
+        let exported = fs::read_to_string(&output).unwrap();
+        assert!(exported.contains("\"a\": 1"));
+
+        // A failed export leaves the previous output untouched, and doesn't leave any temporary
+        // file behind.
+        let mut p = Program::new_from_source(Cursor::new("{a = 1 + \"a\"}"), "<test>").unwrap();
+        assert!(p
+            .export(ExportFormat::Json, ExportStyle::default(), Some(&output))
+            .is_err());
+        assert_eq!(fs::read_to_string(&output).unwrap(), exported);
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn export_keeps_permissions_and_symlinks() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let dir = std::env::temp_dir().join(format!("nickel-export-links-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("out.json");
+        let link = dir.join("link.json");
+        fs::write(&output, "").unwrap();
+        fs::set_permissions(&output, fs::Permissions::from_mode(0o640)).unwrap();
+        symlink("out.json", &link).unwrap();
+
+        let mut p = Program::new_from_source(Cursor::new("{a = 1}"), "<test>").unwrap();
+        p.export(ExportFormat::Json, ExportStyle::default(), Some(&link))
+            .unwrap();
+
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert!(fs::read_to_string(&output).unwrap().contains("\"a\": 1"));
+        assert_eq!(
+            fs::metadata(&output).unwrap().permissions().mode() & 0o777,
+            0o640
+        );
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn export_writes_special_files_in_place() {
+        use std::os::unix::fs::FileTypeExt;
+
+        let mut p = Program::new_from_source(Cursor::new("{a = 1}"), "<test>").unwrap();
+        p.export(
+            ExportFormat::Json,
+            ExportStyle::default(),
+            Some("/dev/null"),
+        )
+        .unwrap();
+        assert!(fs::metadata("/dev/null")
+            .unwrap()
+            .file_type()
+            .is_char_device());
+    }
+
+    fn simple_diagnostics(s: &str) -> Vec<SimpleDiagnostic> {
+        let mut p = Program::new_from_source(Cursor::new(s), "<test>").unwrap();
+        let err = p.eval_full().unwrap_err();
+        let files_count = p.cache.files().clone().add("", String::new());
+        let diags = report_simple(&p.cache, &err);
+        // The file database must be left untouched.
+        assert_eq!(p.cache.files().clone().add("", String::new()), files_count);
+        diags
+    }
+
+    #[test]
+    fn simple_diagnostics_parse_error() {
+        let diags = simple_diagnostics("{ foo = 1 bar }");
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].severity, Severity::Error);
+
+        let label = &diags[0].labels[0];
+        assert_eq!(label.file_name.as_deref(), Some("<test>"));
+        assert_eq!(label.byte_range, 10..13);
+        assert_eq!(label.line_col_range, ((0, 10), (0, 13)));
+        assert_eq!(label.snippet, None);
+    }
+
+    #[test]
+    fn simple_diagnostics_unmatched_brace() {
+        let diags = simple_diagnostics("{a = {b = 1}}}");
+        let labels: Vec<_> = diags[0]
+            .labels
+            .iter()
+            .map(|label| (label.byte_range.clone(), label.label_text.as_str()))
+            .collect();
+        assert_eq!(
+            labels,
+            vec![(13..14, ""), (0..1, "possibly intended to close this `{`")]
+        );
+
+        // Braces of interpolated expressions are not candidates.
+        let diags = simple_diagnostics("\"%{ {a = 1} }\" }");
+        assert_eq!(diags[0].labels.len(), 1);
+        assert_eq!(diags[0].labels[0].byte_range, 15..16);
+        assert_eq!(diags[0].labels[0].label_text, "no matching `{` found");
+    }
+
+    #[test]
+    fn simple_diagnostics_expected_tokens() {
+        let diags = simple_diagnostics("{ a = 1 b = 2 }");
+        assert_eq!(diags[0].notes, vec!["expected one of: `,`, `;`, `}`"]);
+
+        let diags = simple_diagnostics("let x = 1");
+        assert_eq!(diags[0].notes, vec!["expected `in`"]);
+
+        // Long lists are collapsed to categories.
+        let diags = simple_diagnostics("(1 +)");
+        assert_eq!(
+            diags[0].notes,
+            vec!["expected one of: a type, an expression"]
+        );
+
+        // Alternatives are de-duplicated and sorted, and long lists are truncated.
+        let mut files = Files::new();
+        let file_id = files.add("<test>", String::from("let"));
+        let mut notes = |expected: &[&str]| {
+            let expected = expected.iter().map(|t| format!("\"{}\"", t)).collect();
+            ParseError::UnexpectedEOF(file_id, expected)
+                .to_diagnostic(&mut files, None)
+                .remove(0)
+                .notes
+        };
+        assert_eq!(
+            notes(&["str literal", "}", "multstr literal", ","]),
+            vec!["expected one of: `,`, `}`, a string"]
+        );
+        let ops = [
+            "+", "*", "/", "%", "++", "@", "==", "!=", "<", ">", "<=", ">=", "&&", "||",
+        ];
+        assert_eq!(
+            notes(&ops),
+            vec!["expected one of: `!=`, `%`, `&&`, `*`, `+`, `++`, `/`, `<`, `<=`, `==`, `>`, `>=`, and 2 more"]
+        );
+    }
+
+    #[test]
+    fn simple_diagnostics_field_missing() {
+        let diags = simple_diagnostics("{host = \"a\", port = 80, timeout = 1}.prot");
+        assert_eq!(
+            diags[0].notes,
+            vec![
+                "available fields: host, port, timeout",
+                "help: did you mean `port`?"
+            ]
+        );
+
+        let diags = simple_diagnostics("{foo = 1, bar = 2}.qux");
+        assert_eq!(diags[0].notes, vec!["available fields: bar, foo"]);
+
+        let diags = simple_diagnostics("{}.foo");
+        assert_eq!(diags[0].notes, vec!["the record has no fields"]);
+
+        // Long lists of fields are truncated.
+        let fields: Vec<String> = (0..15).map(|i| format!("field{:02} = {}", i, i)).collect();
+        let diags = simple_diagnostics(&format!("{{{}}}.other", fields.join(", ")));
+        assert_eq!(
+            diags[0].notes,
+            vec![
+                "available fields: field00, field01, field02, field03, field04, field05, \
+                 field06, field07, field08, field09, and 5 more"
+            ]
+        );
+    }
+
+    #[test]
+    fn simple_diagnostics_blame_error() {
+        let diags = simple_diagnostics("let f | Num -> Num = fun x => \"a\" in f 1");
+        assert!(diags[0].message.starts_with("contract broken"));
+
+        let labels = &diags[0].labels;
+        assert!(labels
+            .iter()
+            .any(|l| l.file_name.as_deref() == Some("<test>")));
+        // The type path is reported on a generated snippet of the contract's type.
+        let type_label = labels
+            .iter()
+            .find(|l| l.file_name.is_none())
+            .expect("missing synthetic label");
+        assert_eq!(type_label.snippet.as_deref(), Some("Num -> Num"));
+        assert_eq!(type_label.byte_range, 7..10);
+        assert_eq!(type_label.line_col_range, ((0, 7), (0, 10)));
+    }
+
+    #[test]
+    fn simple_diagnostics_error_codes() {
+        use crate::error_codes;
+
+        let code = |s: &str| simple_diagnostics(s)[0].code.clone();
+
+        assert_eq!(code("1 + \"a\""), Some(String::from("E053")));
+        assert_eq!(code("let x : Num = \"a\" in x"), Some(String::from("E019")));
+        assert_eq!(code("let x = 1 in in x"), Some(String::from("E025")));
+
+        // The underlying errors reported after a row mismatch carry their own code.
+        let diags = simple_diagnostics("{foo = \"a\"} : {foo : Num}");
+        assert_eq!(diags[0].code, Some(String::from("E021")));
+        assert!(diags[1..].iter().all(|diag| diag.code.is_some()));
+
+        for diag in diags.iter().filter(|diag| diag.code.is_some()) {
+            assert!(error_codes::explain(diag.code.as_ref().unwrap()).is_some());
+        }
+    }
+
+    #[test]
+    fn simple_diagnostics_unbound_identifier() {
+        let diags = simple_diagnostics("let foo_bar = 1 in foo_bra + 1");
+        assert_eq!(diags[0].notes, vec!["help: did you mean `foo_bar`?"]);
+
+        // The modules of the standard library are candidates as well.
+        let diags = simple_diagnostics("strng.length \"a\"");
+        assert_eq!(diags[0].notes, vec!["help: did you mean `string`?"]);
+
+        let diags = simple_diagnostics("let foo_bar = 1 in x");
+        assert!(diags[0].notes.is_empty());
+    }
+
+    #[test]
+    fn json_diagnostic_call_stack() {
+        let mut p = Program::new_from_source(
+            Cursor::new("let g | Num -> Num = fun x => x in\nlet f = fun x => g x in\nf \"a\""),
+            "<test>",
+        )
+        .unwrap();
+        let err = p.eval_full().unwrap_err();
+        let contracts_id = p.cache.id_of("<stdlib/contract.ncl>");
+        let diag = err.to_json_diagnostic(p.cache.files(), contracts_id);
+
+        assert_eq!(diag["kind"], "eval");
+        assert_eq!(diag["code"], "E001");
+        assert_eq!(diag["related"][0]["severity"], "note");
+
+        let frames = diag["call_stack"].as_array().unwrap();
+        let functions: Vec<&serde_json::Value> =
+            frames.iter().map(|frame| &frame["function"]).collect();
+        assert_eq!(functions, vec!["g", "f"]);
+        assert_eq!(frames[0]["repeat"], 1);
+        assert_eq!(frames[0]["file"], "<test>");
+        assert_eq!(
+            frames[0]["start"],
+            serde_json::json!({"line": 2, "column": 18})
+        );
+        assert_eq!(
+            frames[1]["byte_range"],
+            serde_json::json!({"start": 59, "end": 64})
+        );
+
+        // Without the id of the contracts, the call stack can't be filtered.
+        let diag = err.to_json_diagnostic(p.cache.files(), None);
+        assert!(diag.get("call_stack").is_none());
+    }
+
+    #[test]
+    fn report_json() {
+        assert_eq!("JSON".parse::<ErrorFormat>(), Ok(ErrorFormat::Json));
+        assert_eq!(ErrorFormat::Json.to_string(), "json");
+
+        // The blamed value has no position: it is reported in a synthetic snippet.
+        let mut p =
+            Program::new_from_source(Cursor::new("{token | Num = \"abc\"}.token"), "<test>")
+                .unwrap();
+        let err = p.eval_full().unwrap_err();
+        let mut output = Vec::new();
+        p.report_json_all([&err, &err], &mut output).unwrap();
+        let diags: serde_json::Value = serde_json::from_slice(&output).unwrap();
+
+        assert_eq!(diags.as_array().unwrap().len(), 2);
+        assert_eq!(diags[0]["kind"], "eval");
+
+        let labels = diags[0]["labels"].as_array().unwrap();
+        let value = labels
+            .iter()
+            .find(|label| label["snippet"] == "\"abc\"")
+            .unwrap();
+        assert_eq!(value["file"], serde_json::Value::Null);
+        assert_eq!(value["synthetic"], true);
+        assert!(labels.iter().any(|label| label["file"] == "<test>"));
+
+        // The warnings of the run come first, followed by the errors.
+        let mut p =
+            Program::new_from_source(Cursor::new("let x = 1 in 2 + true"), "<test>").unwrap();
+        let err = p.eval_full().unwrap_err();
+        let mut output = Vec::new();
+        p.report_json_run([&err], &mut output).unwrap();
+        let diags: serde_json::Value = serde_json::from_slice(&output).unwrap();
+
+        assert_eq!(diags.as_array().unwrap().len(), 2);
+        assert_eq!(diags[0]["kind"], "warning");
+        assert_eq!(diags[0]["subkind"], "UnusedBinding");
+        assert_eq!(diags[0]["severity"], "warning");
+        assert_eq!(diags[1]["kind"], "eval");
+
+        let mut output = Vec::new();
+        p.report_sarif_run([&err], &mut output).unwrap();
+        let log: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        let results = log["runs"][0]["results"].as_array().unwrap();
+        assert_eq!(results[0]["level"], "warning");
+        assert_eq!(results.last().unwrap()["level"], "error");
+    }
+
+    #[test]
+    fn simple_diagnostics_blame_binding() {
+        let note_labels = |s: &str| {
+            let diags = simple_diagnostics(s);
+            assert_eq!(diags[1].severity, Severity::Note);
+            diags[1]
+                .labels
+                .iter()
+                .map(|l| (l.label_text.clone(), l.byte_range.clone(), l.primary))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            note_labels("let x | Num = \"a\" in x"),
+            vec![
+                (String::from("contract declared here"), 8..11, true),
+                (String::from("bound as `x`"), 4..5, false),
+            ]
+        );
+
+        // The annotated value is reported when the contract is broken elsewhere.
+        assert_eq!(
+            note_labels("let f | Num -> Num = fun x => \"a\" in f 1"),
+            vec![
+                (String::from("contract declared here"), 8..18, true),
+                (String::from("applied to this value"), 21..33, false),
+                (String::from("bound as `f`"), 4..5, false),
+            ]
+        );
+
+        assert_eq!(
+            note_labels("let C = Num in {a | C = 1, b | C = \"a\"}.b"),
+            vec![
+                (String::from("contract declared here"), 31..32, true),
+                (String::from("bound as `b`"), 27..28, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn simple_diagnostics_field_path() {
+        let field_path = |s: &str| -> Option<String> {
+            simple_diagnostics(s)[0]
+                .notes
+                .iter()
+                .find(|note| note.starts_with("at field path"))
+                .cloned()
+        };
+
+        assert_eq!(field_path("let x | Num = \"a\" in x"), None);
+        assert_eq!(
+            field_path("{a = {b = [1, \"x\"]}} | {a : {b : Array Num}}"),
+            Some(String::from("at field path `a.b[1]`"))
+        );
+        // Record contracts and arrays of record contracts.
+        assert_eq!(
+            field_path(
+                "let Port = contract.from_predicate (fun p => p > 0) in
+                 let Instance = {port | Port} in
+                 {server = {instances = [{port = 1}, {port = -1}], admin = 1}}
+                 | {server = {instances | Array Instance, admin | Port}}"
+            ),
+            Some(String::from("at field path `server.instances[1].port`"))
+        );
+        // The path survives merging.
+        assert_eq!(
+            field_path(
+                "let Schema = {server = {port | Num}} in
+                 ({server.port = \"a\"} | Schema) & {server.host = \"h\"}"
+            ),
+            Some(String::from("at field path `server.port`"))
+        );
+    }
+
+    #[test]
+    fn simple_diagnostics_extra_row() {
+        let fields: Vec<String> = (0..20).map(|i| format!("f{} = {}", i, i)).collect();
+        let record = format!("{{{}, extra = 0}}", fields.join(", "));
+        let ty_fields: Vec<String> = (0..20).map(|i| format!("f{}: Num", i)).collect();
+        let source = format!("{} : {{{}}}", record, ty_fields.join(", "));
+
+        let diags = simple_diagnostics(&source);
+        assert_eq!(diags[0].message, "type error: extra row `extra`");
+
+        let labels = &diags[0].labels;
+        assert_eq!(labels[0].byte_range, 0..record.len());
+        let start = source.find("extra").unwrap();
+        let row_label = labels
+            .iter()
+            .find(|l| l.label_text.contains("extra field"))
+            .expect("missing label on the extra field");
+        assert_eq!(row_label.byte_range, start..start + "extra".len());
+    }
+
+    #[test]
+    fn report_degenerate_errors() {
+        let mut p = Program::new_from_source(Cursor::new("null"), "<test>").unwrap();
+        let mut other_files = Files::new();
+        let stale_id = (0..100)
+            .map(|_| other_files.add("<other>", String::from("null")))
+            .last()
+            .unwrap();
+        let span = |src_id, start: u32, end: u32| RawSpan {
+            src_id,
+            start: start.into(),
+            end: end.into(),
+        };
+        let unexpected = |span| {
+            Error::ParseErrors(ParseErrors::new(vec![ParseError::UnexpectedToken(
+                span,
+                Vec::new(),
+            )]))
+        };
+
+        let errors = vec![
+            // An empty list of parse errors inside an import error.
+            Error::ImportError(ImportError::ParseErrors(
+                ParseErrors::new(Vec::new()),
+                TermPos::Original(span(p.main_id, 0, 4)),
+            )),
+            // A span past the end of the source.
+            unexpected(span(p.main_id, 10, 20)),
+            // A span inside a character.
+            unexpected(span(p.cache.add_string("<utf8>", String::from("é")), 1, 2)),
+            // A file which doesn't belong to the cache.
+            unexpected(span(stale_id, 0, 4)),
+        ];
+
+        for err in errors {
+            let diags = report_simple(&p.cache, &err);
+            assert!(!diags.is_empty());
+            assert!(diags[0].notes.iter().any(|note| note == INTERNAL_ERROR_MSG));
+
+            let mut buffer = Buffer::no_color();
+            report_to(&mut p.cache, err, &mut buffer).unwrap();
+            assert!(String::from_utf8_lossy(buffer.as_slice()).starts_with("error"));
+        }
+    }
+
+    #[test]
+    fn simple_diagnostics_known_last_file() {
+        let source = "let f : Str -> Num = fun s => 1 in (let g : Num -> Num = f in g 1) : Num";
+        let mut p = Program::new_from_source(Cursor::new(source), "<test>").unwrap();
+        let err = p.eval_full().unwrap_err();
+        let opts = p.cache.diagnostic_options();
+        assert!(opts.last_file.is_some());
+
+        // Knowing the last file spares a copy of the file database, for the same result.
+        let diags = to_simple_diagnostics_with(&err, p.cache.files(), None, opts);
+        assert_eq!(diags, to_simple_diagnostics(&err, p.cache.files(), None));
+
+        let labels = &diags[0].labels;
+        assert!(labels.iter().any(|label| label.snippet.is_some()));
+        assert!(labels
+            .iter()
+            .any(|label| label.file_name.as_deref() == Some("<test>")));
+    }
+
+    #[test]
+    fn report_leaves_files_untouched() {
+        let source = "let f : Str -> Num = fun s => 1 in (let g : Num -> Num = f in g 1) : Num";
+        let mut p = Program::new_from_source(Cursor::new(source), "<test>").unwrap();
+        let err = p.eval_full().unwrap_err();
+        let files_count = p.cache.files().clone().add("", String::new());
+
+        for _ in 0..3 {
+            let mut buffer = Buffer::no_color();
+            report_to(&mut p.cache, err.clone(), &mut buffer).unwrap();
+            // The rendered types are still shown.
+            assert!(String::from_utf8_lossy(buffer.as_slice()).contains("Str -> Num"));
+        }
+        assert_eq!(p.cache.files().clone().add("", String::new()), files_count);
+    }
+
+    #[test]
+    fn simple_diagnostics_type_mismatch_diff() {
+        let ty = |s: &str| match parse(&format!("null : {}", s)).unwrap().as_ref() {
+            Term::MetaValue(meta) => meta.types.clone().unwrap().types,
+            t => panic!("expected an annotation, got {:?}", t),
+        };
+        let p = Program::new_from_source(Cursor::new("null"), "<test>").unwrap();
+        let mismatch = |expd: &str, actual: &str| {
+            let err = Error::TypecheckError(TypecheckError::TypeMismatch(
+                ty(expd),
+                ty(actual),
+                TermPos::None,
+            ));
+            report_simple(&p.cache, &err).remove(0)
+        };
+        // The parts of the rendered types pointed at by the labels.
+        let parts = |diag: &SimpleDiagnostic| -> Vec<String> {
+            diag.labels
+                .iter()
+                .map(|l| String::from(&l.snippet.as_ref().unwrap()[l.byte_range.clone()]))
+                .collect()
+        };
+
+        let diag = mismatch(
+            "forall a. a -> {foo: Array Str, bar: Num}",
+            "forall a. a -> {bar: Num, foo: Array Bool}",
+        );
+        assert_eq!(parts(&diag), vec!["Str", "Bool"]);
+        assert_eq!(diag.notes, vec!["These types are not compatible"]);
+
+        let diag = mismatch("(Num -> Str) -> Num", "(Num -> Num) -> Num");
+        assert_eq!(parts(&diag), vec!["Str", "Num"]);
+        assert_eq!(diag.labels[0].label_text, "this part of the expected type");
+
+        // Without common structure, the whole types are printed instead.
+        for (expd, actual) in [("Num -> Num", "Array Num"), ("{foo: Num}", "{bar: Num}")] {
+            let diag = mismatch(expd, actual);
+            assert!(diag.labels.is_empty());
+            assert_eq!(diag.notes.len(), 3);
+            assert!(diag.notes[0].contains(expd));
+        }
+    }
+
+    #[test]
+    fn simple_diagnostics_call_stack() {
+        let calls = |diags: &[SimpleDiagnostic]| -> Vec<String> {
+            diags
+                .iter()
+                .flat_map(|diag| diag.labels.iter().map(|l| l.label_text.clone()))
+                .filter(|text| text.contains(") calling"))
+                .collect()
+        };
+
+        // Recursive calls are collapsed.
+        let diags = simple_diagnostics(
+            "let g | Num -> Num = fun x => x in
+             let r = {f = fun n => if n == 0 then g \"a\" else f (n - 1)} in
+             r.f 1500",
+        );
+        assert_eq!(
+            calls(&diags),
+            vec![
+                "(1) calling g",
+                "(2) calling f (repeated 1,500 times)",
+                "(3) calling f"
+            ]
+        );
+
+        // Giant call stacks are truncated.
+        let source = "let g | Num -> Num = fun x => x in
+             let r = {
+               f = fun n => if n == 0 then g \"a\" else h (n - 1),
+               h = fun n => f n,
+             } in
+             r.f 100";
+        let diags = simple_diagnostics(source);
+        let reported = calls(&diags);
+        assert_eq!(reported.len(), 2 * DEFAULT_CALL_STACK_LIMIT);
+        assert_eq!(reported[0], "(1) calling g");
+        assert_eq!(reported.last().unwrap(), "(202) calling f");
+        assert!(diags
+            .iter()
+            .any(|diag| diag.message == "... 182 frames omitted ..."));
+
+        let mut p = Program::new_from_source(Cursor::new(source), "<test>").unwrap();
+        p.set_call_stack_limit(None);
+        let err = p.eval_full().unwrap_err();
+        assert_eq!(calls(&report_simple(&p.cache, &err)).len(), 202);
+    }
+
+    fn render_parse_errors(files: &Files<String>, file_id: FileId) -> Vec<SimpleDiagnostic> {
+        let (_, errs) = grammar::TermParser::new()
+            .parse_term_tolerant(file_id, lexer::Lexer::new(files.source(file_id)))
+            .unwrap();
+        to_simple_diagnostics(&errs, files, None)
+    }
+
+    #[test]
+    fn simple_diagnostics_merge_type_mismatch() {
+        let source = format!(
+            "{{port = 8080}} & (import \"{}/tests/imports/port.yaml\")",
+            env!("CARGO_MANIFEST_DIR")
+        );
+        let diags = simple_diagnostics(&source);
+        assert_eq!(diags[0].message, "non mergeable terms");
+        assert_eq!(
+            diags[0].notes[0],
+            "the left value is a Num and the right value is a Str \u{2014} merge requires equal \
+values of the same type"
+        );
+        assert!(diags[0].notes[1].starts_with("the right value was imported from the YAML file"));
+        assert!(diags[0].notes[1].contains("port.yaml"));
+        // The imported value is shown as a snippet rather than the whole YAML file.
+        assert_eq!(diags[0].labels[1].snippet.as_deref(), Some("\"8080\""));
+
+        let diags = simple_diagnostics("{port = 8080} & {port = 80}");
+        assert_eq!(diags[0].message, "non mergeable terms");
+        assert!(diags[0].notes.is_empty());
+    }
+
+    #[test]
+    fn simple_diagnostics_concurrent() {
+        use std::sync::Arc;
+        use std::thread;
+
+        let mut cache = Cache::new();
+        let file_id = cache.add_string("<test>", String::from("{ foo = 1 bar, baz = }"));
+        let files = Arc::new(cache.files().clone());
+        let expected = render_parse_errors(&files, file_id);
+        assert!(!expected.is_empty());
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let files = Arc::clone(&files);
+                thread::spawn(move || {
+                    (0..16)
+                        .map(|_| render_parse_errors(&files, file_id))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            for diags in handle.join().unwrap() {
+                assert_eq!(diags, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn parse_errors_capped() {
+        let source = format!("[{} 1]", "1 +, ".repeat(30));
+        let mut files = Files::new();
+        let file_id = files.add("<test>", source.clone());
+        let (_, errs): (_, ParseErrors) = grammar::TermParser::new()
+            .parse_term_tolerant(file_id, lexer::Lexer::new(&source))
+            .unwrap();
+        assert_eq!(errs.errors.len(), 30);
+
+        let diags = errs.to_diagnostic_capped(&mut files, None, 5);
+        assert_eq!(diags.len(), 6);
+        assert!(diags[..5].iter().all(|d| d.severity == Severity::Error));
+        // The first errors of the file are the ones reported.
+        let starts: Vec<usize> = diags[..5].iter().map(|d| d.labels[0].range.start).collect();
+        let mut sorted = starts.clone();
+        sorted.sort_unstable();
+        assert_eq!(starts, sorted);
+        assert_eq!(starts[0], source.find(',').unwrap());
+        assert_eq!(diags[5].severity, Severity::Note);
+        assert_eq!(diags[5].message, "... and 25 more errors");
+
+        let diags = errs.to_diagnostic(&mut files, None);
+        assert_eq!(diags.len(), MAX_PARSE_DIAGNOSTICS + 1);
+        assert_eq!(
+            diags[MAX_PARSE_DIAGNOSTICS].message,
+            "... and 10 more errors"
+        );
+
+        assert_eq!(errs.to_diagnostic_capped(&mut files, None, 30).len(), 30);
+    }
+
+    #[test]
+    fn parse_errors_configured_limit() {
+        // The errors are far enough from each other not to be deduplicated.
+        let source = format!("[{} 1]", "1 +,          ".repeat(30));
+        let diagnostics = |max_parse_errors| {
+            let mut p = Program::new_from_source(Cursor::new(source.clone()), "<test>").unwrap();
+            p.set_max_parse_errors(max_parse_errors);
+            let err = p.eval_full().unwrap_err();
+            report_simple(&p.cache, &err)
+        };
+
+        let diags = diagnostics(None);
+        assert_eq!(diags.len(), 30);
+        assert!(diags.iter().all(|d| d.severity == Severity::Error));
+
+        let diags = diagnostics(Some(25));
+        assert_eq!(diags.len(), 25);
+        assert!(diags.iter().all(|d| d.severity == Severity::Error));
+
+        let diags = diagnostics(Some(MAX_PARSE_DIAGNOSTICS));
+        assert_eq!(diags.len(), MAX_PARSE_DIAGNOSTICS);
+    }
+
+    fn parse_errors(s: &str, max_parse_errors: Option<usize>) -> ParseErrors {
+        let mut p = Program::new_from_source(Cursor::new(s), "<test>").unwrap();
+        p.set_max_parse_errors(max_parse_errors);
+        match p.eval_full() {
+            Err(Error::ParseErrors(errs)) => errs,
+            res => panic!("expected parse errors, got {:?}", res),
+        }
+    }
+
+    #[test]
+    fn parse_errors_missing_in() {
+        let source = "let x = 1\n{ a = x, b = [x, x] }";
+        assert_eq!(
+            parse_errors(source, Some(MAX_PARSE_DIAGNOSTICS))
+                .errors
+                .len(),
+            1
+        );
+        assert_eq!(simple_diagnostics(source).len(), 1);
+    }
+
+    #[test]
+    fn parse_errors_dedup() {
+        // Error recovery reports one error per superfluous comma.
+        let source = "{ a = , , , b = 1 }";
+        let all = parse_errors(source, None);
+        assert_eq!(all.errors.len(), 3);
+        let errs = parse_errors(source, Some(MAX_PARSE_DIAGNOSTICS));
+        assert_eq!(errs.errors, all.errors[..1].to_vec());
+
+        let file_id = Files::new().add("<test>", String::new());
+        let span = |start: u32, end: u32| RawSpan {
+            src_id: file_id,
+            start: start.into(),
+            end: end.into(),
+        };
+        let errs = ParseErrors::new(vec![
+            ParseError::UnexpectedToken(span(10, 12), Vec::new()),
+            ParseError::ExtraToken(span(20, 21)),
+            ParseError::UnexpectedToken(span(40, 41), Vec::new()),
+            ParseError::UnexpectedEOF(file_id, Vec::new()),
+            ParseError::UnexpectedEOF(file_id, Vec::new()),
+            ParseError::InvalidEscapeSequence(span(42, 43)),
+        ]);
+
+        assert_eq!(
+            errs.clone().dedup_and_limit(10).errors,
+            vec![
+                ParseError::UnexpectedToken(span(10, 12), Vec::new()),
+                ParseError::UnexpectedToken(span(40, 41), Vec::new()),
+                ParseError::UnexpectedEOF(file_id, Vec::new()),
+                ParseError::InvalidEscapeSequence(span(42, 43)),
+            ]
+        );
+        assert_eq!(
+            errs.dedup_and_limit(2).errors,
+            vec![
+                ParseError::UnexpectedToken(span(10, 12), Vec::new()),
+                ParseError::UnexpectedToken(span(40, 41), Vec::new()),
+            ]
+        );
+    }
+
+    #[test]
+    fn diagnostics_sorted() {
+        use crate::error::sort_diagnostics;
+        use codespan_reporting::diagnostic::{Diagnostic, Label};
+
+        let mut files = Files::new();
+        let file_id = files.add("<test>", " ".repeat(50));
+        let span = |start: u32, end: u32| RawSpan {
+            src_id: file_id,
+            start: start.into(),
+            end: end.into(),
+        };
+
+        let err = Error::ParseErrors(ParseErrors::new(vec![
+            ParseError::InvalidEscapeSequence(span(40, 41)),
+            ParseError::UnexpectedToken(span(10, 12), Vec::new()),
+        ]));
+        let starts: Vec<usize> = err
+            .to_diagnostic(&mut files, None)
+            .iter()
+            .map(|diag| diag.labels[0].range.start)
+            .collect();
+        assert_eq!(starts, vec![10, 40]);
+
+        // Labelled notes follow the diagnostic they are attached to, and diagnostics without
+        // position are put last.
+        let mut diags = vec![
+            Diagnostic::note().with_message("unlocated"),
+            Diagnostic::error()
+                .with_message("second")
+                .with_labels(vec![Label::primary(file_id, 30..31)]),
+            Diagnostic::note().with_labels(vec![Label::primary(file_id, 0..1)]),
+            Diagnostic::error()
+                .with_message("first")
+                .with_labels(vec![Label::primary(file_id, 20..21)]),
+            Diagnostic::error()
+                .with_message("also first")
+                .with_labels(vec![Label::primary(file_id, 20..22)]),
+        ];
+        sort_diagnostics(&mut diags);
+        let order: Vec<(&str, Option<usize>)> = diags
+            .iter()
+            .map(|diag| {
+                (
+                    diag.message.as_str(),
+                    diag.labels.first().map(|l| l.range.start),
+                )
+            })
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                ("first", Some(20)),
+                ("also first", Some(20)),
+                ("second", Some(30)),
+                ("", Some(0)),
+                ("unlocated", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn color_opt() {
+        assert_eq!("auto".parse(), Ok(ColorOpt::Auto));
+        assert_eq!("Always".parse(), Ok(ColorOpt::Always));
+        assert_eq!("never".parse(), Ok(ColorOpt::Never));
+        assert!("sometimes".parse::<ColorOpt>().is_err());
+
+        assert_eq!(ColorChoice::from(ColorOpt::Always), ColorChoice::Always);
+        assert_eq!(ColorChoice::from(ColorOpt::Never), ColorChoice::Never);
+    }
+
+    #[test]
+    fn report_colors() {
+        let report = |mut buffer: Buffer| {
+            let mut cache = Cache::new();
+            let err = Error::EvalError(EvalError::Other(String::from("oops"), TermPos::None));
+            report_to(&mut cache, err, &mut buffer).unwrap();
+            String::from_utf8(buffer.into_inner()).unwrap()
+        };
+
+        let plain = report(Buffer::no_color());
+        assert!(plain.contains("oops"));
+        assert!(!plain.contains('\u{1b}'));
+
+        let colored = report(Buffer::ansi());
+        assert!(colored.contains("oops"));
+        assert!(colored.contains('\u{1b}'));
+    }
+
+    #[test]
+    fn sensitive_values_are_redacted() {
+        const SECRET: &str = "s3cr3t-token-abcdefghij";
+
+        let sources = [
+            format!("{{token | sensitive | Num = \"{}\"}}.token", SECRET),
+            format!(
+                "let r = {{token | sensitive = \"{}\"}} in r.token + 1",
+                SECRET
+            ),
+            format!(
+                "let r = {{token | sensitive = \"{}\"}} in (\"Bearer \" ++ r.token) + 1",
+                SECRET
+            ),
+        ];
+
+        for source in sources.iter() {
+            let mut p = Program::new_from_source(Cursor::new(source.as_str()), "<test>").unwrap();
+            let err = p.eval_full().unwrap_err();
+
+            let diags = report_simple(&p.cache, &err);
+            let simple = format!("{:?}", diags);
+            assert!(!simple.contains(SECRET), "{}", simple);
+
+            let mut json = Vec::new();
+            p.report_json(&err, &mut json).unwrap();
+            let json = String::from_utf8(json).unwrap();
+            assert!(!json.contains(SECRET), "{}", json);
+
+            let mut buffer = Buffer::no_color();
+            report_to(&mut p.cache, err, &mut buffer).unwrap();
+            let rendered = String::from_utf8(buffer.into_inner()).unwrap();
+            assert!(!rendered.contains(SECRET), "{}", rendered);
+            // The source is shown with the expression of the sensitive value redacted.
+            assert!(rendered.contains("= <redacted>}"), "{}", rendered);
+        }
+    }
+
+    #[test]
+    fn sensitive_values_are_redacted_from_snippets() {
+        let mut p = Program::new_from_source(
+            Cursor::new("{token | sensitive | Num = \"s3cr3t-token-abcdefghij\"}.token"),
+            "<test>",
+        )
+        .unwrap();
+        let err = p.eval_full().unwrap_err();
+
+        let mut buffer = Buffer::no_color();
+        report_to(&mut p.cache, err, &mut buffer).unwrap();
+        let rendered = String::from_utf8(buffer.into_inner()).unwrap();
+        assert!(
+            rendered.contains("<redacted (Str, 23 chars)>"),
+            "{}",
+            rendered
+        );
+    }
+
+    #[test]
+    fn export_redacts_sensitive_values() {
+        let (rt, paths) = crate::redact::redact_term(
+            eval_full(
+                "{port = 80, creds = [{user = \"me\", token | sensitive = \"abc\"}], \
+                 nested = {key | sensitive | default = 1}}",
+            )
+            .unwrap(),
+        );
+
+        assert_eq!(paths, vec!["creds[0].token", "nested.key"]);
+        assert_eq!(
+            serialize::to_string(ExportFormat::Json, ExportStyle::default(), &rt).unwrap(),
+            serialize::to_string(
+                ExportFormat::Json,
+                ExportStyle::default(),
+                &eval_full(
+                    "{port = 80, creds = [{user = \"me\", token = \"<redacted (Str, 3 chars)>\"}], \
+                     nested = {key = \"<redacted (Num)>\"}}"
+                )
+                .unwrap()
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn simple_diagnostics_non_exhaustive_match() {
+        let diags = simple_diagnostics("let x = `ftp in\nswitch { `http => 80, `https => 443 } x");
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].code.as_deref(), Some("E044"));
+        assert_eq!(diags[0].message, "non-exhaustive match");
+        assert_eq!(diags[0].labels.len(), 2);
+        assert!(diags[0].labels[0].label_text.contains("the tag `ftp`"));
+        assert_eq!(diags[0].labels[0].line_col_range.0, (1, 38));
+        assert_eq!(diags[0].labels[1].label_text, "in this match");
+        assert_eq!(diags[0].notes[0], "the handled tags are `http`, `https`");
+
+        let diags = simple_diagnostics("switch { } 1");
+        assert!(diags[0].labels[0]
+            .label_text
+            .contains("a value of type Num, but an enum tag was expected"));
+        assert_eq!(diags[0].notes[0], "the match doesn't handle any tag");
+    }
+
+    #[test]
+    fn wide_characters() {
+        let source = "\"🎉🎉 \\é\"";
+        let mut p = Program::new_from_source(Cursor::new(source), "<test>").unwrap();
+        let err = p.eval_full().unwrap_err();
+
+        // The label covers the whole offending character. Columns are counted in characters.
+        let diags = report_simple(&p.cache, &err);
+        assert_eq!(diags[0].labels[0].byte_range, 11..13);
+        assert_eq!(diags[0].labels[0].line_col_range, ((0, 5), (0, 6)));
+
+        // The caret is aligned with the offending character, emojis being two columns wide.
+        let mut buffer = Buffer::no_color();
+        report_to(&mut p.cache, err, &mut buffer).unwrap();
+        let output = String::from_utf8(buffer.into_inner()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        let source_line = lines.iter().position(|l| l.ends_with(source)).unwrap();
+        let offset = lines[source_line].len() - source.len();
+        assert_eq!(&lines[source_line + 1][offset..], "       ^");
+    }
+
+    #[test]
+    fn deserialization_error_position() {
+        let diags =
+            simple_diagnostics(r#"builtin.deserialize `Json "{\n  \"a\": 1,\n  \"b\": x\n}""#);
+        let labels = &diags[0].labels;
+        // The deserialized string is reported as a generated snippet.
+        assert_eq!(labels[0].file_name, None);
+        assert_eq!(
+            labels[0].snippet.as_deref(),
+            Some("{\n  \"a\": 1,\n  \"b\": x\n}")
+        );
+        assert!(labels[0].primary);
+        assert_eq!(labels[0].byte_range, 19..20);
+        assert_eq!(labels[0].line_col_range, ((2, 7), (2, 8)));
+        // The call of the builtin is still reported.
+        assert!(!labels[1].primary);
+        assert_eq!(labels[1].label_text, "deserialized here");
+
+        // An error at the end of the input points right after the last character.
+        let diags = simple_diagnostics(r#"builtin.deserialize `Toml "a = 1\nb = ""#);
+        let labels = &diags[0].labels;
+        assert_eq!(labels[0].snippet.as_deref(), Some("a = 1\nb = "));
+        assert_eq!(labels[0].byte_range, 10..10);
+    }
+
+    #[test]
+    fn report_policy() {
+        use crate::report_policy::{CodePolicy, Level, Rule};
+
+        let policy = CodePolicy::new()
+            .with_rule(
+                "E001",
+                Rule {
+                    url: Some(String::from("https://runbooks.example.com/E001")),
+                    ..Rule::default()
+                },
+            )
+            .with_rule(
+                "E008",
+                Rule {
+                    severity: Some(Level::Ignore),
+                    fatal: Some(false),
+                    ..Rule::default()
+                },
+            );
+        let policy: Arc<dyn ReportPolicy> = Arc::new(policy);
+
+        let mut p = Program::new_from_source(Cursor::new("1 | Str"), "<test>").unwrap();
+        p.set_report_policy(Arc::clone(&policy));
+        let err = p.eval_full().unwrap_err();
+        let diags = report_simple(&p.cache, &err);
+        assert_eq!(diags[0].code.as_deref(), Some("E001"));
+        assert_eq!(
+            diags[0].notes.last().map(String::as_str),
+            Some("see https://runbooks.example.com/E001")
+        );
+        assert!(p.is_fatal(&err));
+
+        let mut output = Vec::new();
+        p.report_json(&err, &mut output).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(
+            json[0]["notes"].as_array().unwrap().last().unwrap(),
+            "see https://runbooks.example.com/E001"
+        );
+
+        let mut p = Program::new_from_source(Cursor::new("x"), "<test>").unwrap();
+        p.set_report_policy(policy);
+        let err = p.eval_full().unwrap_err();
+        assert!(report_simple(&p.cache, &err).is_empty());
+        assert!(!p.is_fatal(&err));
+
+        let mut buffer = Buffer::no_color();
+        report_to(&mut p.cache, err, &mut buffer).unwrap();
+        assert!(buffer.as_slice().is_empty());
+    }
+
+    /// Write the embedded standard library to a fresh directory, applying `patch` to the content
+    /// of each module, given its file name.
+    fn stdlib_dir(name: &str, patch: impl Fn(&str, &str) -> String) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("nickel-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        for (file, (_, content)) in crate::stdlib::MODULE_FILES
+            .iter()
+            .zip(crate::stdlib::modules())
+        {
+            fs::write(dir.join(file), patch(file, content)).unwrap();
+        }
+
+        dir
+    }
+
+    #[test]
+    fn stdlib_from_dir() {
+        let dir = stdlib_dir("stdlib", |file, content| {
+            if file == "string.ncl" {
+                content.replace(
+                    "= fun s => %str_length% s,",
+                    "= fun s => 2 * %str_length% s,",
+                )
+            } else {
+                String::from(content)
+            }
+        });
+
+        let mut p =
+            Program::new_from_source(Cursor::new("string.length \"abc\""), "<test>").unwrap();
+        p.set_stdlib(StdlibSource::Dir(dir.clone()));
+        assert_eq!(p.eval_full().map(Term::from), Ok(Term::Num(6.0)));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn stdlib_from_dir_invalid_module() {
+        let dir = stdlib_dir("stdlib-invalid", |file, content| {
+            if file == "num.ncl" {
+                String::from("[1, 2]")
+            } else {
+                String::from(content)
+            }
+        });
+
+        let mut p = Program::new_from_source(Cursor::new("1 + 1"), "<test>").unwrap();
+        p.set_stdlib(StdlibSource::Dir(dir.clone()));
+        let err = p.eval_full().unwrap_err();
+        assert_matches::assert_matches!(
+            err,
+            Error::ImportError(ImportError::InvalidStdlibModule(ref module, _))
+                if module.ends_with("num.ncl")
+        );
+
+        let diags = report_simple(&p.cache, &err);
+        assert_eq!(diags[0].code.as_deref(), Some("E043"));
+        assert!(diags[0].labels[0]
+            .file_name
+            .as_deref()
+            .unwrap()
+            .ends_with("num.ncl"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn no_stdlib() {
+        let mut p =
+            Program::new_from_source(Cursor::new("string.length \"abc\""), "<test>").unwrap();
+        p.set_stdlib(StdlibSource::None);
+        assert_matches::assert_matches!(
+            p.eval_full(),
+            Err(Error::TypecheckError(TypecheckError::UnboundIdentifier(ident, ..)))
+                if ident.as_ref() == "string"
+        );
+
+        let mut p =
+            Program::new_from_source(Cursor::new("%str_length% \"abc\""), "<test>").unwrap();
+        p.set_stdlib(StdlibSource::None);
+        assert_eq!(p.eval_full().map(Term::from), Ok(Term::Num(3.0)));
+
+        // Builtin contracts are unbound as well, which is pointed out by the diagnostic.
+        let mut p = Program::new_from_source(Cursor::new("1 | Num"), "<test>").unwrap();
+        p.set_stdlib(StdlibSource::None);
+        let err = p.eval_full().unwrap_err();
+        let diags = report_simple(&p.cache, &err);
+        assert!(diags[0].notes[0].contains("`$num` is a builtin contract"));
+    }
+
+    #[test]
+    fn render_diagnostics_to_string() {
+        use crate::error::render_diagnostics;
+
+        let mut cache = Cache::new();
+        let err = Error::EvalError(EvalError::Other(String::from("oops"), TermPos::None));
+        let diags = err.to_diagnostic(cache.files_mut(), None);
+
+        let plain = render_diagnostics(cache.files(), &diags, ColorChoice::Never);
+        assert_eq!(plain, "error[E012]: oops\n\n");
+
+        let colored = render_diagnostics(cache.files(), &diags, ColorChoice::Always);
+        assert!(colored.contains("oops"));
+        assert!(colored.contains('\u{1b}'));
+    }
+
+    #[test]
+    fn cancellation() {
+        use std::thread;
+        use std::time::Instant;
+
+        let mut p = Program::new_from_source(
+            Cursor::new("{f = fun n => if n < 0 then 0 else f (n + 1)}.f 0"),
+            "<test>",
+        )
+        .unwrap();
+        let token = CancellationToken::new();
+        let watchdog = {
+            let token = token.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(100));
+                token.cancel();
+            })
+        };
+
+        let start = Instant::now();
+        assert_matches::assert_matches!(
+            p.eval_with_cancellation(token),
+            Err(Error::EvalError(EvalError::Cancelled(TermPos::Original(_))))
+        );
+        assert!(start.elapsed() < Duration::from_secs(10));
+        watchdog.join().unwrap();
+
+        let mut p = Program::new_from_source(Cursor::new("1 + 1"), "<test>").unwrap();
+        assert_eq!(
+            p.eval_with_cancellation(CancellationToken::new())
+                .map(Term::from),
+            Ok(Term::Num(2.))
+        );
+
+        // The serialization of a value fails once the token of its writer has been triggered.
+        let token = CancellationToken::new();
+        token.cancel();
+        let rt = eval_full("{a = [1, 2, 3]}").unwrap();
+        let writer = CancellableWriter::new(Vec::new(), token);
+        assert!(
+            serialize::to_writer(writer, ExportFormat::Json, ExportStyle::Compact, &rt).is_err()
+        );
+    }
+
+    #[test]
+    fn shadowed_stdlib() {
+        use crate::error::Warning;
+        use crate::lint::Lint;
+
+        let warnings = |s: &str| {
+            let mut p = Program::new_from_source(Cursor::new(s), "<test>").unwrap();
+            p.typecheck().unwrap();
+            p.warnings()
+                .iter()
+                .filter_map(|warning| match warning {
+                    Warning::ShadowedStdlib(id) => Some(id.to_string()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            warnings("let string = \"foo\" in string"),
+            vec![String::from("string")]
+        );
+        assert_eq!(
+            warnings("let f = fun array {b = num, ..record} => 1 in f"),
+            vec!["array", "num", "record"]
+        );
+        // A field of a recursive record only shadows the standard library if it's referred to.
+        assert_eq!(
+            warnings("{array = [], record = {}, x = array}"),
+            vec!["array"]
+        );
+        assert!(warnings("let strings = 1 in {num = 2}").is_empty());
+
+        let mut p =
+            Program::new_from_source(Cursor::new("let string = 1 in string"), "<test>").unwrap();
+        p.set_deny(vec![Lint::ShadowStdlib]);
+        let err = p.eval().unwrap_err();
+        assert_matches::assert_matches!(err, Error::DeniedWarnings(ref warnings) if matches!(warnings.as_slice(), [Warning::ShadowedStdlib(_)]));
+        assert!(p.warnings().is_empty());
+
+        let diags = report_simple(&p.cache, &err);
+        assert_eq!(diags[0].severity, Severity::Error);
+        assert_eq!(diags[0].code.as_deref(), Some("E047"));
+        assert_eq!(diags[0].labels[0].byte_range, 4..10);
+    }
+
+    #[test]
+    fn unused_bindings() {
+        use crate::error::Warning;
+        use crate::lint::Lint;
+
+        let warnings = |s: &str| {
+            let mut p = Program::new_from_source(Cursor::new(s), "<test>").unwrap();
+            p.typecheck().unwrap();
+            p.warnings()
+                .iter()
+                .filter_map(|warning| match warning {
+                    Warning::UnusedBinding(id) => Some(id.to_string()),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            warnings("let x = 1 in let y = 2 in let z = 3 in y"),
+            vec!["x", "z"]
+        );
+        assert_eq!(
+            warnings("let {a, b = {c}} = {a = 1, b = {c = 2}} in c"),
+            vec!["a"]
+        );
+        // A binding shadowed in its body is unused, but not one used in a contract.
+        assert_eq!(warnings("let x = 1 in let x = 2 in x"), vec!["x"]);
+        assert!(warnings("let C = fun l x => x in {foo | C = 1}").is_empty());
+        assert!(warnings("let _x = 1 in let f = fun y => 2 in f 0").is_empty());
+
+        let mut p = Program::new_from_source(Cursor::new("let x = 1 in 2"), "<test>").unwrap();
+        p.set_deny(Lint::ALL);
+        let err = p.eval().unwrap_err();
+        assert_matches::assert_matches!(err, Error::DeniedWarnings(ref warnings) if matches!(warnings.as_slice(), [Warning::UnusedBinding(_)]));
+
+        let diags = report_simple(&p.cache, &err);
+        assert_eq!(diags[0].code.as_deref(), Some("E054"));
+        assert_eq!(diags[0].labels[0].byte_range, 4..5);
+
+        // All the warnings of the denied lints are reported, not only the first one.
+        let mut p =
+            Program::new_from_source(Cursor::new("let x = 1 in let string = 2 in 3"), "<test>")
+                .unwrap();
+        p.set_deny(Lint::ALL);
+        let err = p.eval().unwrap_err();
+        assert_matches::assert_matches!(err, Error::DeniedWarnings(ref warnings) if warnings.len() == 3);
+        assert_eq!(report_simple(&p.cache, &err).len(), 3);
+        assert_eq!(err.to_string().matches("more warnings").count(), 1);
+    }
+
+    #[test]
+    fn override_bypasses() {
+        use crate::error::Warning;
+        use crate::lint::Lint;
+
+        let bypasses = |s: &str| {
+            let mut p = Program::new_from_source(Cursor::new(s), "<test>").unwrap();
+            p.eval().unwrap();
+            p.warnings()
+                .iter()
+                .filter_map(|warning| match warning {
+                    Warning::OverrideBypass(pos) => pos
+                        .as_opt_ref()
+                        .map(|span| (span.start.to_usize(), span.end.to_usize())),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mk = "let mk = fun x => {a | Num = x} in ";
+        assert_eq!(
+            bypasses(&format!("{}{{foo | default = mk 1}}", mk)),
+            vec![(52, 56)]
+        );
+        assert_eq!(
+            bypasses(&format!(
+                "{}{{foo | default = {{b = 2}} & mk 1}} & {{foo = {{}}}}",
+                mk
+            )),
+            Vec::new()
+        );
+        // The contracts of records and enriched values are retained, and the other values don't
+        // carry any.
+        assert!(bypasses("{foo | default = {a | Num = 1}, bar | default = 1 + 1}").is_empty());
+        assert!(bypasses(&format!("{}{{foo = mk 1}}", mk)).is_empty());
+        // The applications of the contracts of an annotation aren't computations.
+        let c = "let C = fun l v => v in ";
+        assert!(bypasses(&format!("{}{{foo | C | default = 1}}", c)).is_empty());
+        assert!(
+            bypasses("{foo | Num | default = 1, bar | {a | Num} | default = {a = 1}}").is_empty()
+        );
+        assert_eq!(
+            bypasses(&format!("{}{}{{foo | C | default = mk 1}}", c, mk)),
+            vec![(80, 84)]
+        );
+
+        let mut p = Program::new_from_source(
+            Cursor::new(format!("{}{{foo | default = (mk 1).a}}", mk)),
+            "<test>",
+        )
+        .unwrap();
+        p.set_deny(vec![Lint::OverrideBypass]);
+        let err = p.eval().unwrap_err();
+        assert_matches::assert_matches!(err, Error::DeniedWarnings(ref warnings) if matches!(warnings.as_slice(), [Warning::OverrideBypass(_)]));
+
+        let diags = report_simple(&p.cache, &err);
+        assert_eq!(diags[0].code.as_deref(), Some("E055"));
+        assert_eq!(diags[0].labels[0].byte_range, 52..60);
+    }
 }
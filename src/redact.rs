@@ -0,0 +1,340 @@
+//! Redaction of sensitive values.
+//!
+//! A value annotated with the `sensitive` metadata, as in `{token | sensitive = "..."}`, holds a
+//! secret such as a password or an access token, which must not end up in error messages, in
+//! the output of the REPL or in CI logs.
+//!
+//! When the evaluator forces a sensitive value, it records the string it evaluates to, together
+//! with the span of the expression defining it, in a set of [`Redactions`], which is stored in the
+//! [diagnostic options](../error/struct.DiagnosticOptions.html) of the cache. Redaction then
+//! happens where values and sources are printed, rather than on the rendered output:
+//!
+//! - The printers of values, that is the snippets of diagnostics showing a value (see
+//!   [`Redactions::repr`]) and the output of the REPL (see [`Redactions::deep_repr`]), print a
+//!   sensitive string as a placeholder of the form `<redacted (Str, 24 chars)>`.
+//! - The labels of a diagnostic pointing to a source which defines a sensitive value are moved
+//!   to a copy of this source, where the expression of the value is replaced by `<redacted>`.
+//! - The messages and the notes of diagnostics, which may quote a value, have the sensitive
+//!   strings they contain replaced by their placeholder (see [`Redactions::redact`]).
+//!
+//! Only strings are recorded: redacting every occurrence of a number or of a boolean in the output
+//! would make it unreadable. A value derived from a secret, such as `"Bearer " ++ token`, is
+//! redacted as long as it contains the secret verbatim.
+use crate::position::RawSpan;
+use crate::term::{RichTerm, Term};
+use std::collections::HashSet;
+use std::ops::Range;
+
+/// The placeholder replacing the expression of a sensitive value in a source.
+pub const SOURCE_PLACEHOLDER: &str = "<redacted>";
+
+/// The placeholder rendered in place of a sensitive string.
+pub fn placeholder(value: &str) -> String {
+    format!("<redacted (Str, {} chars)>", value.chars().count())
+}
+
+/// The placeholder rendered in place of a sensitive value, which may not be a string.
+pub fn placeholder_term(t: &Term) -> String {
+    match t {
+        Term::Str(s) => placeholder(s),
+        t => match t.type_of() {
+            Some(ty) => format!("<redacted ({})>", ty),
+            None => String::from("<redacted>"),
+        },
+    }
+}
+
+/// The sensitive strings met during evaluation, and the spans of the expressions defining
+/// sensitive values.
+#[derive(Clone, Debug, Default)]
+pub struct Redactions {
+    values: HashSet<String>,
+    spans: Vec<RawSpan>,
+}
+
+impl Redactions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record a sensitive string. Empty strings are ignored, as they can't leak anything.
+    pub fn add(&mut self, value: String) {
+        if !value.is_empty() {
+            self.values.insert(value);
+        }
+    }
+
+    /// Record the span of the expression defining a sensitive value.
+    pub fn add_span(&mut self, span: RawSpan) {
+        if !self.spans.contains(&span) {
+            self.spans.push(span);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty() && self.spans.is_empty()
+    }
+
+    /// The spans of the expressions defining sensitive values, in the order they were recorded.
+    pub fn spans(&self) -> &[RawSpan] {
+        &self.spans
+    }
+
+    /// The placeholder of a term if it must be hidden: a string containing a sensitive string, or
+    /// a value annotated as sensitive.
+    pub fn hide(&self, t: &Term) -> Option<String> {
+        match t {
+            Term::Str(s) if self.values.iter().any(|value| s.contains(value.as_str())) => {
+                Some(placeholder(s))
+            }
+            Term::MetaValue(meta) if meta.sensitive => Some(
+                meta.value
+                    .as_ref()
+                    .map(|value| placeholder_term(value.as_ref()))
+                    .unwrap_or_else(|| String::from(SOURCE_PLACEHOLDER)),
+            ),
+            _ => None,
+        }
+    }
+
+    /// The shallow representation of a term (see [`Term::shallow_repr`]), or its placeholder if
+    /// it must be hidden.
+    pub fn repr(&self, t: &Term) -> String {
+        self.hide(t).unwrap_or_else(|| t.shallow_repr())
+    }
+
+    /// The deep representation of a term (see [`Term::deep_repr`]), where the subterms which must
+    /// be hidden are replaced by their placeholder.
+    pub fn deep_repr(&self, t: &Term) -> String {
+        t.deep_repr_with(&|t| self.hide(t))
+    }
+
+    /// Replace the strings of a fully evaluated term which must be hidden by their placeholder,
+    /// as done before serializing a value for display.
+    pub fn redact_value(&self, rt: RichTerm) -> RichTerm {
+        if self.values.is_empty() {
+            return rt;
+        }
+
+        let pos = rt.pos;
+        match rt.term.into_owned() {
+            Term::Str(s) => match self.hide(&Term::Str(s.clone())) {
+                Some(placeholder) => RichTerm::new(Term::Str(placeholder), pos),
+                None => RichTerm::new(Term::Str(s), pos),
+            },
+            Term::Record(map, attrs) => {
+                let map = map
+                    .into_iter()
+                    .map(|(id, value)| (id, self.redact_value(value)))
+                    .collect();
+                RichTerm::new(Term::Record(map, attrs), pos)
+            }
+            Term::Array(ts) => {
+                let ts = ts.into_iter().map(|t| self.redact_value(t)).collect();
+                RichTerm::new(Term::Array(ts), pos)
+            }
+            Term::MetaValue(mut meta) => {
+                meta.value = meta.value.map(|value| self.redact_value(value));
+                RichTerm::new(Term::MetaValue(meta), pos)
+            }
+            t => RichTerm::new(t, pos),
+        }
+    }
+
+    /// Replace the occurrences of sensitive strings in a text by their placeholder. When several
+    /// sensitive strings start at the same position, the longest one is replaced.
+    pub fn redact(&self, text: &str) -> String {
+        if self.values.is_empty() {
+            return String::from(text);
+        }
+
+        let mut values: Vec<&String> = self.values.iter().collect();
+        values.sort_by_key(|value| std::cmp::Reverse(value.len()));
+
+        let mut result = String::with_capacity(text.len());
+        let mut rest = text;
+
+        while let Some(c) = rest.chars().next() {
+            match values.iter().find(|value| rest.starts_with(value.as_str())) {
+                Some(value) => {
+                    result.push_str(&placeholder(value));
+                    rest = &rest[value.len()..];
+                }
+                None => {
+                    result.push(c);
+                    rest = &rest[c.len_utf8()..];
+                }
+            }
+        }
+
+        result
+    }
+}
+
+/// Replace the expressions of sensitive values in a source by [`SOURCE_PLACEHOLDER`], given the
+/// byte ranges of these expressions, sorted and disjoint. Return the redacted source, together
+/// with a function mapping a byte range of the original source to the one of the redacted source.
+/// A range which overlaps a redacted expression covers the whole placeholder.
+pub fn redact_source(
+    source: &str,
+    ranges: &[Range<usize>],
+) -> (String, impl Fn(Range<usize>) -> Range<usize>) {
+    let mut redacted = String::with_capacity(source.len());
+    // The redacted ranges, with the shift of the offsets which follow them.
+    let mut shifts: Vec<(Range<usize>, isize)> = Vec::new();
+    let mut last = 0;
+    let mut shift = 0isize;
+
+    for range in ranges {
+        redacted.push_str(&source[last..range.start]);
+        redacted.push_str(SOURCE_PLACEHOLDER);
+        shift += SOURCE_PLACEHOLDER.len() as isize - range.len() as isize;
+        shifts.push((range.clone(), shift));
+        last = range.end;
+    }
+    redacted.push_str(&source[last..]);
+
+    let map_offset = move |offset: usize, is_end: bool| {
+        let mut before = 0isize;
+
+        for (range, shift) in shifts.iter() {
+            if offset <= range.start {
+                break;
+            }
+
+            if offset < range.end {
+                let start = (range.start as isize + before) as usize;
+                return if is_end {
+                    start + SOURCE_PLACEHOLDER.len()
+                } else {
+                    start
+                };
+            }
+
+            before = *shift;
+        }
+
+        (offset as isize + before) as usize
+    };
+
+    let map =
+        move |range: Range<usize>| map_offset(range.start, false)..map_offset(range.end, true);
+    (redacted, map)
+}
+
+/// Replace the sensitive values of a fully evaluated term by their placeholder, and return the
+/// paths of the replaced values, such as `server.credentials[0].token`.
+///
+/// Contrary to [`Redactions`], this function works on the term itself: it is used to export a
+/// configuration without its secrets.
+pub fn redact_term(rt: RichTerm) -> (RichTerm, Vec<String>) {
+    fn redact_(rt: RichTerm, path: &mut String, redacted: &mut Vec<String>) -> RichTerm {
+        let pos = rt.pos;
+
+        match rt.term.into_owned() {
+            Term::MetaValue(meta) if meta.sensitive && meta.value.is_some() => {
+                let value = meta.value.unwrap();
+                redacted.push(path.clone());
+                RichTerm::new(Term::Str(placeholder_term(value.as_ref())), value.pos)
+            }
+            Term::MetaValue(mut meta) => {
+                meta.value = meta.value.map(|value| redact_(value, path, redacted));
+                RichTerm::new(Term::MetaValue(meta), pos)
+            }
+            Term::Record(map, attrs) => {
+                let mut fields: Vec<_> = map.into_iter().collect();
+                // Report the paths in a deterministic order.
+                fields.sort_by(|(id1, _), (id2, _)| id1.cmp(id2));
+
+                let map = fields
+                    .into_iter()
+                    .map(|(id, value)| {
+                        let len = path.len();
+                        if !path.is_empty() {
+                            path.push('.');
+                        }
+                        path.push_str(id.as_ref());
+                        let value = redact_(value, path, redacted);
+                        path.truncate(len);
+                        (id, value)
+                    })
+                    .collect();
+
+                RichTerm::new(Term::Record(map, attrs), pos)
+            }
+            Term::Array(ts) => {
+                let ts = ts
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, value)| {
+                        let len = path.len();
+                        path.push_str(&format!("[{}]", index));
+                        let value = redact_(value, path, redacted);
+                        path.truncate(len);
+                        value
+                    })
+                    .collect();
+
+                RichTerm::new(Term::Array(ts), pos)
+            }
+            t => RichTerm::new(t, pos),
+        }
+    }
+
+    let mut redacted = Vec::new();
+    let rt = redact_(rt, &mut String::new(), &mut redacted);
+    (rt, redacted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact() {
+        let mut redactions = Redactions::new();
+        assert_eq!(redactions.redact("token: abc"), "token: abc");
+
+        redactions.add(String::from("abc"));
+        redactions.add(String::from("abcdef"));
+        redactions.add(String::new());
+
+        assert_eq!(
+            redactions.redact("abc, abcdef, ab"),
+            "<redacted (Str, 3 chars)>, <redacted (Str, 6 chars)>, ab"
+        );
+        // Placeholders are not redacted again, even if they contain a sensitive string.
+        redactions.add(String::from("Str"));
+        assert_eq!(redactions.redact("Str"), "<redacted (Str, 3 chars)>");
+        assert_eq!(
+            redactions.redact("é abc é"),
+            "é <redacted (Str, 3 chars)> é"
+        );
+    }
+
+    #[test]
+    fn redact_values() {
+        let mut redactions = Redactions::new();
+        redactions.add(String::from("abc"));
+
+        assert_eq!(
+            redactions.repr(&Term::Str(String::from("Bearer abc"))),
+            "<redacted (Str, 10 chars)>"
+        );
+        assert_eq!(redactions.repr(&Term::Str(String::from("ab"))), "\"ab\"");
+    }
+
+    #[test]
+    fn redact_sources() {
+        let source = "{a = \"abc\", b = 1, c = \"defgh\"}";
+        let (redacted, map) = redact_source(source, &[5..10, 23..30]);
+        assert_eq!(redacted, "{a = <redacted>, b = 1, c = <redacted>}");
+
+        // Before, around, inside and after the redacted expressions.
+        assert_eq!(map(1..2), 1..2);
+        assert_eq!(map(5..10), 5..15);
+        assert_eq!(map(1..7), 1..15);
+        assert_eq!(map(12..17), 17..22);
+        assert_eq!(map(12..31), 17..39);
+    }
+}
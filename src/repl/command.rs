@@ -9,7 +9,11 @@ pub enum CommandType {
     Typecheck,
     Query,
     Print,
+    Doc,
+    Env,
+    Reset,
     Help,
+    Explain,
     Exit,
 }
 
@@ -21,7 +25,16 @@ pub enum Command {
     Typecheck(String),
     Query(String),
     Print(String),
+    Doc(String),
+    /// List the bindings of the toplevel environment. `verbose` shows their types and a preview
+    /// of their values, and `all` includes the standard library.
+    Env {
+        verbose: bool,
+        all: bool,
+    },
+    Reset,
     Help(Option<String>),
+    Explain(String),
     Exit,
 }
 
@@ -50,7 +63,11 @@ impl FromStr for CommandType {
             "typecheck" | "tc" => Ok(Typecheck),
             "query" | "q" => Ok(Query),
             "print" | "p" => Ok(Print),
+            "doc" | "d" => Ok(Doc),
+            "env" => Ok(Env),
+            "reset" => Ok(Reset),
             "help" | "?" | "h" => Ok(Help),
+            "explain" | "x" => Ok(Explain),
             "exit" | "e" => Ok(Exit),
             _ => Err(UnknownCommandError {}),
         }
@@ -58,6 +75,15 @@ impl FromStr for CommandType {
 }
 
 impl CommandType {
+    /// Return all the commands.
+    pub fn all() -> Vec<CommandType> {
+        use CommandType::*;
+
+        vec![
+            Load, Typecheck, Query, Print, Doc, Env, Reset, Help, Explain, Exit,
+        ]
+    }
+
     /// Return the aliases of a command.
     pub fn aliases(&self) -> Vec<String> {
         use CommandType::*;
@@ -67,7 +93,10 @@ impl CommandType {
             Typecheck => vec![String::from("tc")],
             Query => vec![String::from("q")],
             Print => vec![String::from("p")],
+            Doc => vec![String::from("d")],
+            Env | Reset => Vec::new(),
             Help => vec![String::from("h"), String::from("?")],
+            Explain => vec![String::from("x")],
             Exit => vec![String::from("e")],
         }
     }
@@ -82,7 +111,11 @@ impl std::fmt::Display for CommandType {
             Typecheck => write!(f, "typecheck"),
             Query => write!(f, "query"),
             Print => write!(f, "print"),
+            Doc => write!(f, "doc"),
+            Env => write!(f, "env"),
+            Reset => write!(f, "reset"),
             Help => write!(f, "help"),
+            Explain => write!(f, "explain"),
             Exit => write!(f, "exit"),
         }
     }
@@ -127,6 +160,42 @@ impl FromStr for Command {
                 require_arg(cmd, &arg, None)?;
                 Ok(Command::Print(arg))
             }
+            CommandType::Doc => {
+                require_arg(
+                    cmd,
+                    &arg,
+                    Some("Please provide an expression, such as `array.map`"),
+                )?;
+                Ok(Command::Doc(arg))
+            }
+            CommandType::Env => {
+                let mut verbose = false;
+                let mut all = false;
+
+                for option in arg.split_whitespace() {
+                    match option {
+                        "-v" | "--verbose" => verbose = true,
+                        "--all" => all = true,
+                        _ => {
+                            return Err(ReplError::InvalidArg {
+                                cmd,
+                                arg: String::from(option),
+                            })
+                        }
+                    }
+                }
+
+                Ok(Command::Env { verbose, all })
+            }
+            CommandType::Reset => Ok(Command::Reset),
+            CommandType::Explain => {
+                require_arg(
+                    cmd,
+                    &arg,
+                    Some("Please provide an error code, such as `E001`"),
+                )?;
+                Ok(Command::Explain(arg))
+            }
             CommandType::Exit => Ok(Command::Exit),
             CommandType::Help => {
                 let arg_opt = if arg.trim().is_empty() {
@@ -150,7 +219,11 @@ impl Command {
             Typecheck(..) => CommandType::Typecheck,
             Query(..) => CommandType::Query,
             Print(..) => CommandType::Print,
+            Doc(..) => CommandType::Doc,
+            Env { .. } => CommandType::Env,
+            Reset => CommandType::Reset,
             Help(..) => CommandType::Help,
+            Explain(..) => CommandType::Explain,
             Exit => CommandType::Exit,
         }
     }
@@ -0,0 +1,133 @@
+//! Completion of the inputs of the REPL.
+//!
+//! Completion works on the text before the cursor. Right after the command prefix `:`, the names
+//! of the [commands](../command/enum.CommandType.html) are completed. Otherwise, the word under
+//! the cursor is completed either as an identifier bound in the environment of the REPL, which
+//! holds the standard library and the toplevel let-bindings, or as a field of a record when it
+//! follows a path of identifiers, as in `array.fo`.
+//!
+//! Completion must be instantaneous and free of side effects, hence it never evaluates anything.
+//! The fields of a value are only listed when this value is a record literal, possibly behind
+//! variables, metadata or let-bindings. This covers the modules of the standard library and the values already
+//! evaluated in the REPL session, but not, for example, a toplevel let-binding whose definition is
+//! a function application which hasn't been forced yet.
+use super::command::CommandType;
+use crate::eval::lazy::Thunk;
+use crate::eval::{Closure, Environment, IdentKind};
+use crate::identifier::Ident;
+use crate::term::{RichTerm, Term};
+use std::collections::HashMap;
+
+/// The maximum number of variables followed to reach a record literal, such that a variable bound
+/// to itself doesn't loop forever.
+const MAX_INDIRECTIONS: usize = 64;
+
+/// Complete an input, the cursor being at the byte offset `pos` of `line`. Return the offset of
+/// the start of the completed word, together with the sorted candidates.
+pub fn complete(line: &str, pos: usize, env: &Environment) -> (usize, Vec<String>) {
+    let before = &line[..pos];
+
+    if let Some(cmd) = before.strip_prefix(':') {
+        if !cmd.contains(char::is_whitespace) {
+            let candidates = CommandType::all()
+                .into_iter()
+                .map(|cmd| cmd.to_string())
+                .filter(|name| name.starts_with(cmd))
+                .collect();
+            return (1, candidates);
+        }
+    }
+
+    let start = before
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| is_ident_char(*c) || *c == '.')
+        .last()
+        .map(|(i, _)| i)
+        .unwrap_or(pos);
+    let mut path: Vec<&str> = before[start..].split('.').collect();
+    // `split` always returns at least one element.
+    let prefix = path.pop().unwrap();
+
+    let names = if path.is_empty() {
+        env.iter_elems().map(|(id, _)| id.label.clone()).collect()
+    } else {
+        field_names(env, &path).unwrap_or_default()
+    };
+
+    let mut candidates: Vec<String> = names
+        .into_iter()
+        .filter(|name| name.starts_with(prefix) && is_ident(name))
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+
+    (pos - prefix.len(), candidates)
+}
+
+/// The names of the fields of the record at the end of a path, if it is a record literal.
+fn field_names(env: &Environment, path: &[&str]) -> Option<Vec<String>> {
+    let (var, path) = path.split_first()?;
+    let mut closure = env.get(&Ident::from(*var))?.borrow().clone();
+
+    for field in path {
+        let (mut fields, env) = record_fields(closure)?;
+        closure = Closure {
+            body: fields.remove(&Ident::from(*field))?,
+            env,
+        };
+    }
+
+    record_fields(closure).map(|(fields, _)| fields.into_keys().map(|id| id.label).collect())
+}
+
+/// Follow variables, metadata and let-bindings until reaching a record literal, without
+/// evaluating anything, and return its fields together with their environment.
+///
+/// The bound terms of let-bindings are put in the environment as suspended thunks, as the
+/// evaluator would do. This is required to look into the values of the standard library and into
+/// unevaluated records, whose fields are bound by let-bindings introduced by the [share normal
+/// form](../../transform/share_normal_form/index.html) transformation.
+fn record_fields(mut closure: Closure) -> Option<(HashMap<Ident, RichTerm>, Environment)> {
+    let mut indirections = 0;
+
+    loop {
+        let Closure { body, mut env } = closure;
+        closure = match body.as_ref() {
+            Term::Record(fields, _) | Term::RecRecord(fields, ..) => {
+                return Some((fields.clone(), env))
+            }
+            Term::MetaValue(meta) => Closure {
+                body: meta.value.clone()?,
+                env,
+            },
+            Term::Let(id, bound, body, _) => {
+                let bound = Closure {
+                    body: bound.clone(),
+                    env: env.clone(),
+                };
+                env.insert(id.clone(), Thunk::new(bound, IdentKind::Let));
+                Closure {
+                    body: body.clone(),
+                    env,
+                }
+            }
+            Term::Var(id) if indirections < MAX_INDIRECTIONS => {
+                indirections += 1;
+                env.get(id)?.borrow().clone()
+            }
+            _ => return None,
+        };
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+/// Whether a name can be written as an identifier, as opposed to a field name which must be
+/// quoted.
+fn is_ident(name: &str) -> bool {
+    let mut chars = name.strip_prefix('_').unwrap_or(name).chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic()) && chars.all(is_ident_char)
+}
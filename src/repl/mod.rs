@@ -12,21 +12,18 @@ use crate::identifier::Ident;
 use crate::parser::{grammar, lexer, ExtendedTerm};
 use crate::term::{RichTerm, Term};
 use crate::transform::import_resolution;
-use crate::types::Types;
+use crate::typecheck::{ApparentType, TypeWrapper};
+use crate::types::{AbsType, Types};
 use crate::{eval, transform, typecheck};
 use codespan::FileId;
-use simple_counter::*;
+use std::collections::HashSet;
 use std::ffi::{OsStr, OsString};
 use std::io::Write;
 use std::result::Result;
 use std::str::FromStr;
 
-#[cfg(feature = "repl")]
-use rustyline::validate::{ValidationContext, ValidationResult};
-
-generate_counter!(InputNameCounter, usize);
-
 pub mod command;
+pub mod completion;
 pub mod query_print;
 #[cfg(feature = "repl")]
 pub mod rustyline_frontend;
@@ -49,6 +46,18 @@ impl From<RichTerm> for EvalResult {
     }
 }
 
+/// A binding of the toplevel environment, as listed by the `:env` command.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Binding {
+    pub id: Ident,
+    /// The apparent type of the bound value.
+    pub ty: Types,
+    /// A one-line preview of the bound value (see [`Term::shallow_repr`]). The value isn't
+    /// evaluated for the occasion: a binding which hasn't been used yet is previewed as
+    /// `<unevaluated>`, unless it is bound to a literal.
+    pub preview: String,
+}
+
 /// Interface of the REPL backend.
 pub trait Repl {
     /// Evaluate an expression, which can be either a standard term or a toplevel let-binding.
@@ -57,10 +66,22 @@ pub trait Repl {
     fn eval_full(&mut self, exp: &str) -> Result<EvalResult, Error>;
     /// Load the content of a file in the environment. Return the loaded record.
     fn load(&mut self, path: impl AsRef<OsStr>) -> Result<RichTerm, Error>;
-    /// Typecheck an expression and return its [apparent type](../typecheck/fn.apparent_type.html).
+    /// Typecheck an expression and return its type: the [inferred
+    /// type](../typecheck/fn.infer_in_env.html) if the expression is well-typed as a statically
+    /// typed term, or its [apparent type](../typecheck/fn.apparent_shape.html) otherwise.
     fn typecheck(&mut self, exp: &str) -> Result<Types, Error>;
     /// Query the metadata of an expression.
     fn query(&mut self, exp: &str) -> Result<Term, Error>;
+    /// Evaluate an expression until its metadata are found, if any, without evaluating the value
+    /// they are attached to. Used to show the documentation of a value.
+    fn doc(&mut self, exp: &str) -> Result<Term, Error>;
+    /// List the bindings of the toplevel environment, sorted by name. The bindings of the
+    /// standard library are only included if `include_stdlib` is set, but a name of the standard
+    /// library shadowed by a toplevel declaration is always listed.
+    fn bindings(&self, include_stdlib: bool) -> Vec<Binding>;
+    /// Drop the toplevel declarations and the loaded files, restoring the environment as it was
+    /// right after loading the standard library.
+    fn reset(&mut self);
     /// Required for error reporting on the frontend.
     fn cache_mut(&mut self) -> &mut Cache;
 }
@@ -77,6 +98,14 @@ pub struct ReplImpl {
     /// The initial type environment, without the toplevel declarations made inside the REPL. Used
     /// to typecheck imports in a fresh environment.
     init_type_env: typecheck::Environment,
+    /// The number of inputs processed so far, used to generate the names of input sources.
+    input_count: usize,
+    /// The global environment right after loading the stdlib, which is restored by
+    /// [`reset`](trait.Repl.html#tymethod.reset).
+    stdlib_env: GlobalEnv,
+    /// The files of the cache right after loading the stdlib. The other ones are loaded files,
+    /// inputs or their imports, which are dropped by [`reset`](trait.Repl.html#tymethod.reset).
+    stdlib_files: HashSet<FileId>,
 }
 
 impl ReplImpl {
@@ -87,14 +116,38 @@ impl ReplImpl {
             parser: grammar::ExtendedTermParser::new(),
             env: GlobalEnv::new(),
             init_type_env: typecheck::Environment::new(),
+            input_count: 0,
+            stdlib_env: GlobalEnv::new(),
+            stdlib_files: HashSet::new(),
         }
     }
 
+    /// Add an input to the file database under a fresh name `<repl:N>`, where `N` is the number
+    /// of the input in the session.
+    ///
+    /// Each input gets its own source, which is never updated afterwards: terms defined by an
+    /// input, such as toplevel let-bindings, keep pointing to the right source when they are
+    /// reported in the diagnostics of later inputs, which label the declarations of the earlier
+    /// inputs they refer to with the name of the input (see
+    /// [`DiagnosticOptions::definitions`](../error/struct.DiagnosticOptions.html#structfield.definitions)).
+    fn add_input(&mut self, exp: &str) -> FileId {
+        self.input_count += 1;
+        self.cache
+            .add_string(format!("<repl:{}>", self.input_count), String::from(exp))
+    }
+
+    /// The evaluation environment, holding the stdlib and the toplevel declarations made so far.
+    pub fn eval_env(&self) -> &eval::Environment {
+        &self.env.eval_env
+    }
+
     /// Load and process the stdlib, and use it to populate the eval environment as well as the
     /// typing environment.
     pub fn load_stdlib(&mut self) -> Result<(), Error> {
         self.env = self.cache.prepare_stdlib()?;
         self.init_type_env = self.env.type_env.clone();
+        self.stdlib_env = self.env.clone();
+        self.stdlib_files = self.cache.terms().keys().copied().collect();
         Ok(())
     }
 
@@ -105,17 +158,14 @@ impl ReplImpl {
             eval::eval
         };
 
-        let file_id = self.cache.add_string(
-            format!("repl-input-{}", InputNameCounter::next()),
-            String::from(exp),
-        );
+        let file_id = self.add_input(exp);
 
         let (term, parse_errs) = self
             .parser
             .parse_term_tolerant(file_id, lexer::Lexer::new(exp))?;
 
         if !parse_errs.no_errors() {
-            return Err(parse_errs.into());
+            return Err(self.cache.process_parse_errors(parse_errs).into());
         }
 
         // Because we don't use the cache for input, we have to perform recursive import
@@ -130,9 +180,12 @@ impl ReplImpl {
             t: RichTerm,
         ) -> Result<RichTerm, Error> {
             let (t, pending) = import_resolution::resolve_imports(t, &mut repl_impl.cache)?;
-            for id in &pending {
-                repl_impl.cache.resolve_imports(*id).unwrap();
-            }
+            repl_impl
+                .cache
+                .resolve_pending_imports(&pending)
+                .map_err(|cache_err| {
+                    cache_err.unwrap_error("repl::prepare(): expected imports to be parsed")
+                })?;
 
             typecheck::type_check_in_env(&t, &repl_impl.env.type_env, &repl_impl.cache)?;
 
@@ -145,7 +198,7 @@ impl ReplImpl {
                 );
             }
 
-            for id in &pending {
+            for (id, _) in &pending {
                 repl_impl
                     .cache
                     .typecheck(*id, &repl_impl.init_type_env)
@@ -155,7 +208,7 @@ impl ReplImpl {
             }
 
             let t = transform::transform(t).map_err(|err| Error::ParseErrors(err.into()))?;
-            for id in &pending {
+            for (id, _) in &pending {
                 repl_impl
                     .cache
                     .transform(*id)
@@ -174,6 +227,7 @@ impl ReplImpl {
                 let t = prepare(self, Some(id.clone()), t)?;
                 let local_env = self.env.eval_env.clone();
                 eval::env_add(&mut self.env.eval_env, id.clone(), t, local_env);
+                self.cache.add_definition(file_id, id.clone());
                 Ok(EvalResult::Bound(id))
             }
         }
@@ -190,9 +244,11 @@ impl Repl for ReplImpl {
     }
 
     fn load(&mut self, path: impl AsRef<OsStr>) -> Result<RichTerm, Error> {
+        // A relative path is looked for in the current directory, then in the import search path.
+        let path = self.cache.locate_import(path.as_ref(), None);
         let file_id = self
             .cache
-            .add_file(OsString::from(path.as_ref()))
+            .add_file(OsString::from(path))
             .map_err(IOError::from)?;
         self.cache.parse(file_id)?;
         let RichTerm { term, pos } = self.cache.get_ref(file_id).unwrap();
@@ -213,9 +269,9 @@ impl Repl for ReplImpl {
 
         let term = self.cache.get_owned(file_id).unwrap();
         let (term, pending) = import_resolution::resolve_imports(term, &mut self.cache)?;
-        for id in &pending {
-            self.cache.resolve_imports(*id).unwrap();
-        }
+        self.cache
+            .resolve_pending_imports(&pending)
+            .map_err(|cache_err| cache_err.unwrap_error("load(): expected imports to be parsed"))?;
         typecheck::Envs::env_add_term(&mut self.env.type_env, &term, &self.cache).unwrap();
         eval::env_add_term(&mut self.env.eval_env, term.clone()).unwrap();
 
@@ -223,30 +279,95 @@ impl Repl for ReplImpl {
     }
 
     fn typecheck(&mut self, exp: &str) -> Result<Types, Error> {
-        let file_id = self.cache.add_tmp("<repl-typecheck>", String::from(exp));
+        let file_id = self.add_input(exp);
         // We ignore non fatal errors while type checking.
         let (term, _) = self.cache.parse_nocache(file_id)?;
         let (term, pending) = import_resolution::resolve_imports(term, &mut self.cache)?;
-        for id in &pending {
-            self.cache.resolve_imports(*id).unwrap();
-        }
+        self.cache
+            .resolve_pending_imports(&pending)
+            .map_err(|cache_err| {
+                cache_err.unwrap_error("typecheck(): expected imports to be parsed")
+            })?;
         typecheck::type_check_in_env(&term, &self.env.type_env, &self.cache)?;
 
-        Ok(typecheck::apparent_type(
-            term.as_ref(),
-            Some(&typecheck::Envs::from_global(&self.env.type_env)),
-            Some(&self.cache),
-        )
-        .into())
+        let envs = typecheck::Envs::from_global(&self.env.type_env);
+
+        // A type given by the user, directly or through a variable, is kept as it is, as the
+        // inference would instantiate its polymorphic parts.
+        match typecheck::apparent_type(term.as_ref(), Some(&envs), Some(&self.cache)) {
+            ApparentType::Annotated(ty) => Ok(ty),
+            at @ ApparentType::FromEnv(TypeWrapper::Concrete(_)) => Ok(at.into()),
+            _ => Ok(
+                typecheck::infer_in_env(&term, &self.env.type_env, &self.cache).unwrap_or_else(
+                    |_| typecheck::apparent_shape(term.as_ref(), Some(&envs), Some(&self.cache)),
+                ),
+            ),
+        }
     }
 
     fn query(&mut self, exp: &str) -> Result<Term, Error> {
         use crate::program;
 
-        let file_id = self.cache.add_tmp("<repl-query>", String::from(exp));
+        let file_id = self.add_input(exp);
         program::query(&mut self.cache, file_id, &self.env, None)
     }
 
+    fn doc(&mut self, exp: &str) -> Result<Term, Error> {
+        let file_id = self.add_input(exp);
+        self.cache.prepare(file_id, &self.env.type_env)?;
+        let term = self.cache.get_owned(file_id).unwrap();
+
+        // Evaluation is not strict with respect to metavalues: it stops at the first one, which
+        // is the metadata of the expression, be it a variable or a field access.
+        let (result, _) = eval::eval_closure(
+            eval::Closure::atomic_closure(term),
+            &self.env.eval_env,
+            &mut self.cache,
+            false,
+        )?;
+        Ok(result.into())
+    }
+
+    fn bindings(&self, include_stdlib: bool) -> Vec<Binding> {
+        let mut bindings: Vec<Binding> = self
+            .env
+            .eval_env
+            .iter_elems()
+            .filter(|(id, thunk)| {
+                include_stdlib
+                    || !matches!(self.stdlib_env.eval_env.get(id), Some(std) if std.ptr_eq(thunk))
+            })
+            .map(|(id, thunk)| {
+                let ty = self
+                    .env
+                    .type_env
+                    .get(id)
+                    .map(|tyw| ApparentType::FromEnv(tyw).into())
+                    .unwrap_or(Types(AbsType::Dyn()));
+
+                Binding {
+                    id: id.clone(),
+                    ty,
+                    preview: self.cache.redactions().repr(thunk.borrow().body.as_ref()),
+                }
+            })
+            .collect();
+
+        bindings.sort_by(|b1, b2| b1.id.label.cmp(&b2.id.label));
+        bindings
+    }
+
+    fn reset(&mut self) {
+        // Thunks are reference counted: dropping the environment, together with the terms of the
+        // loaded files, frees the values which aren't reachable from the stdlib anymore.
+        self.env = self.stdlib_env.clone();
+        self.cache.clear_definitions();
+        let stdlib_files = &self.stdlib_files;
+        self.cache
+            .terms_mut()
+            .retain(|file_id, _| stdlib_files.contains(file_id));
+    }
+
     fn cache_mut(&mut self) -> &mut Cache {
         &mut self.cache
     }
@@ -265,22 +386,24 @@ pub enum InputStatus {
     Failed(ParseErrors),
 }
 
-/// Validator enabling multiline input.
+/// Parser detecting whether an input is complete, enabling multiline input.
 ///
 /// The behavior is the following:
 /// - always end an input that starts with the command prefix `:`
-/// - otherwise, try to parse the input. If an unexpected end of file error occurs, continue
-///   the input in a new line. Otherwise, accept and end the input.
-//TODO: the validator throws away the result of parsing, or the parse error, when accepting an
-//input, meaning that the work is done a second time by the REPL. Validator's work could be
-//reused. This overhead shouldn't be dramatic for the typical REPL input size, though.
+/// - otherwise, try to parse the input. If an unexpected end of file error occurs, or if the input
+///   ends inside a string or a pair of delimiters (see
+///   [`lexer::is_unterminated`](../parser/lexer/fn.is_unterminated.html)), the input is partial
+///   and continues on the next line. Otherwise, accept and end the input.
+//TODO: the parser throws away the result of parsing, or the parse error, when accepting an
+//input, meaning that the work is done a second time by the REPL. This work could be reused. This
+//overhead shouldn't be dramatic for the typical REPL input size, though.
 #[cfg_attr(
     feature = "repl",
     derive(
-        rustyline_derive::Completer,
         rustyline_derive::Helper,
         rustyline_derive::Highlighter,
-        rustyline_derive::Hinter
+        rustyline_derive::Hinter,
+        rustyline_derive::Validator
     )
 )]
 pub struct InputParser {
@@ -288,6 +411,9 @@ pub struct InputParser {
     /// Currently the parser expect a `FileId` to fill in location information. For this
     /// validator, this may be a dummy one, since for now location information is not used.
     file_id: FileId,
+    /// The environment used to complete identifiers and record fields. See
+    /// [`completion`](completion/index.html).
+    env: eval::Environment,
 }
 
 impl InputParser {
@@ -295,9 +421,15 @@ impl InputParser {
         InputParser {
             parser: grammar::ExtendedTermParser::new(),
             file_id,
+            env: eval::Environment::new(),
         }
     }
 
+    /// Set the environment used for completion, which must be updated after each input.
+    pub fn set_env(&mut self, env: eval::Environment) {
+        self.env = env;
+    }
+
     pub fn parse(&self, input: &str) -> InputStatus {
         if input.starts_with(':') || input.trim().is_empty() {
             return InputStatus::Command;
@@ -307,16 +439,14 @@ impl InputParser {
             .parser
             .parse_term_tolerant(self.file_id, lexer::Lexer::new(input));
 
-        let partial = |pe| {
-            matches!(
-                pe,
-                &ParseError::UnexpectedEOF(..) | &ParseError::UnmatchedCloseBrace(..)
-            )
-        };
+        let partial = |pe| matches!(pe, &ParseError::UnexpectedEOF(..));
 
         match result {
             Ok((t, e)) if e.no_errors() => InputStatus::Complete(t),
             Ok((_, e)) if e.errors.iter().all(|e| partial(e)) => InputStatus::Partial,
+            // The errors of an unterminated input are only reported once it is terminated, or
+            // once the user ends it with a blank line.
+            Ok(_) | Err(_) if lexer::is_unterminated(input) => InputStatus::Partial,
             Ok((_, e)) => InputStatus::Failed(e),
             Err(e) if partial(&e) => InputStatus::Partial,
             Err(err) => InputStatus::Failed(err.into()),
@@ -325,12 +455,16 @@ impl InputParser {
 }
 
 #[cfg(feature = "repl")]
-impl rustyline::validate::Validator for InputParser {
-    fn validate(&self, ctx: &mut ValidationContext<'_>) -> rustyline::Result<ValidationResult> {
-        match self.parse(ctx.input()) {
-            InputStatus::Partial => Ok(ValidationResult::Invalid(None)),
-            _ => Ok(ValidationResult::Valid(None)),
-        }
+impl rustyline::completion::Completer for InputParser {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(completion::complete(line, pos, &self.env))
     }
 }
 
@@ -389,6 +523,43 @@ pub fn print_help(out: &mut impl Write, arg: Option<&str>) -> std::io::Result<()
                 print_aliases(out, c)?;
                 writeln!(out, "Evaluate and print <expression> recursively")?;
             }
+            Ok(c @ CommandType::Doc) => {
+                writeln!(out, ":{} <expression>", c)?;
+                print_aliases(out, c)?;
+                write!(
+                    out,
+                    "Print the documentation, the contracts and the merge priority of <expression>."
+                )?;
+                writeln!(out, " The value of a field is not evaluated")?;
+            }
+            Ok(c @ CommandType::Env) => {
+                writeln!(out, ":{} [-v] [--all]", c)?;
+                print_aliases(out, c)?;
+                write!(
+                    out,
+                    "List the names bound by toplevel declarations and loaded files, in alphabetical order."
+                )?;
+                writeln!(
+                    out,
+                    " `-v` also prints their types and a preview of their values, and `--all` includes the standard library"
+                )?;
+            }
+            Ok(c @ CommandType::Reset) => {
+                writeln!(out, ":{}", c)?;
+                print_aliases(out, c)?;
+                writeln!(
+                    out,
+                    "Drop the toplevel declarations and the loaded files, keeping the standard library"
+                )?;
+            }
+            Ok(c @ CommandType::Explain) => {
+                writeln!(out, ":{} <code>", c)?;
+                print_aliases(out, c)?;
+                writeln!(
+                    out,
+                    "Print the explanation of an error code, such as `E001`"
+                )?;
+            }
             Ok(c @ CommandType::Exit) => {
                 writeln!(out, ":{}", c)?;
                 print_aliases(out, c)?;
@@ -396,12 +567,54 @@ pub fn print_help(out: &mut impl Write, arg: Option<&str>) -> std::io::Result<()
             }
             Err(UnknownCommandError {}) => {
                 writeln!(out, "Unknown command `{}`.", arg)?;
-                writeln!(out, "Available commands: ? help query load typecheck")?;
+                writeln!(
+                    out,
+                    "Available commands: ? help query doc load typecheck env reset explain"
+                )?;
             }
         };
 
         Ok(())
     } else {
-        writeln!(out, "Available commands: help query load typecheck exit")
+        writeln!(
+            out,
+            "Available commands: help query doc load typecheck env reset explain exit"
+        )
+    }
+}
+
+/// Print the bindings listed by the `:env` command, one per line. In verbose mode, each name is
+/// followed by its type and by a preview of its value.
+#[cfg(any(feature = "repl", feature = "repl-wasm"))]
+pub fn write_bindings(
+    out: &mut impl Write,
+    bindings: &[Binding],
+    verbose: bool,
+) -> std::io::Result<()> {
+    if bindings.is_empty() {
+        return writeln!(out, "No bindings.");
+    }
+
+    for binding in bindings {
+        if verbose {
+            writeln!(out, "{} : {} = {}", binding.id, binding.ty, binding.preview)?;
+        } else {
+            writeln!(out, "{}", binding.id)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the explanation of an error code, or a message stating that the code is unknown.
+#[cfg(any(feature = "repl", feature = "repl-wasm"))]
+pub fn print_explanation(out: &mut impl Write, code: &str) -> std::io::Result<()> {
+    match crate::error_codes::explain(code) {
+        Some(explanation) => writeln!(out, "{}", explanation),
+        None => writeln!(
+            out,
+            "Unknown error code `{}`. Error codes are of the form `E001`",
+            code
+        ),
     }
 }
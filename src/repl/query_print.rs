@@ -1,5 +1,6 @@
 //! Rendering of the results of a metadata query.
 use crate::identifier::Ident;
+use crate::redact;
 use crate::term::{MergePriority, MetaValue, Term};
 use std::{io, io::Write};
 
@@ -228,13 +229,22 @@ fn write_query_result_<R: QueryPrinter>(
                 found = true;
             }
 
+            // Sensitive values are never shown, even on an explicit query.
+            let repr = |t: &Term| {
+                if meta.sensitive {
+                    redact::placeholder_term(t)
+                } else {
+                    t.shallow_repr()
+                }
+            };
+
             match &meta {
                 MetaValue {
                     priority: MergePriority::Default,
                     value: Some(t),
                     ..
                 } if selected_attrs.default => {
-                    renderer.write_metadata(out, "default", &t.as_ref().shallow_repr())?;
+                    renderer.write_metadata(out, "default", &repr(t.as_ref()))?;
                     found = true;
                 }
                 MetaValue {
@@ -242,7 +252,7 @@ fn write_query_result_<R: QueryPrinter>(
                     value: Some(t),
                     ..
                 } if selected_attrs.value => {
-                    renderer.write_metadata(out, "value", &t.as_ref().shallow_repr())?;
+                    renderer.write_metadata(out, "value", &repr(t.as_ref()))?;
                     found = true;
                 }
                 _ => (),
@@ -281,3 +291,57 @@ fn write_query_result_<R: QueryPrinter>(
 
     Ok(())
 }
+
+/// Print the documentation of a value, which is the result of the `doc` command of the REPL (see
+/// [`Repl::doc`](../trait.Repl.html#tymethod.doc)): its documentation, its type and contract
+/// annotations and its merge priority. The value itself is not shown, as it hasn't been evaluated.
+///
+/// Wrapper around [`write_doc_result_`](./fn.write_doc_result_) that selects an adapated query
+/// printer at compile time.
+pub fn write_doc_result(out: &mut impl Write, term: &Term) -> io::Result<()> {
+    #[cfg(feature = "markdown")]
+    let renderer = MarkdownRenderer::new();
+
+    #[cfg(not(feature = "markdown"))]
+    let renderer = SimpleRenderer {};
+
+    write_doc_result_(out, term, &renderer)
+}
+
+/// Print the documentation of a value. See [`write_doc_result`].
+fn write_doc_result_<R: QueryPrinter>(
+    out: &mut impl Write,
+    term: &Term,
+    renderer: &R,
+) -> io::Result<()> {
+    let meta = match term {
+        Term::MetaValue(meta) => meta,
+        _ => return writeln!(out, "No documentation found for this value."),
+    };
+
+    match meta.doc {
+        Some(ref s) => renderer.write_doc(out, s)?,
+        None => writeln!(out, "No documentation found for this value.")?,
+    }
+
+    if let Some(ref types) = meta.types {
+        renderer.write_metadata(out, "type", &types.types.to_string())?;
+    }
+
+    if !meta.contracts.is_empty() {
+        let ctrs: Vec<String> = meta
+            .contracts
+            .iter()
+            // See `write_query_result_` for the use of the type stored in the label.
+            .map(|ctr| ctr.label.types.to_string())
+            .collect();
+        renderer.write_metadata(out, "contract", &ctrs.join(","))?;
+    }
+
+    // A field without a value, such as a field with only a contract, must be defined by merging.
+    match (&meta.priority, &meta.value) {
+        (_, None) => renderer.write_metadata(out, "value", "undefined"),
+        (MergePriority::Default, Some(_)) => renderer.write_metadata(out, "priority", "default"),
+        (MergePriority::Normal, Some(_)) => renderer.write_metadata(out, "priority", "normal"),
+    }
+}
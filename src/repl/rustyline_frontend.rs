@@ -1,11 +1,16 @@
 //! Native terminal implementation of a REPL frontend using rustyline.
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use super::command::Command;
 use super::*;
 
-use crate::program;
+use crate::cache::StdlibSource;
+use crate::program::{self, ColorOpt};
+use crate::report_policy::ReportPolicy;
+use crate::serialize::DuplicateKeys;
 use ansi_term::{Colour, Style};
+use codespan_reporting::term::termcolor::ColorChoice;
 use rustyline::config::OutputStreamType;
 use rustyline::error::ReadlineError;
 use rustyline::{Config, EditMode, Editor};
@@ -19,27 +24,80 @@ pub fn config() -> Config {
         .build()
 }
 
-/// Main loop of the REPL.
-pub fn repl(histfile: PathBuf) -> Result<(), InitError> {
+/// Read an input, which may span several lines. As long as the input is partial, such as a
+/// record whose closing brace is missing (see [`InputParser::parse`]), a new line is read with the
+/// continuation prompt. A blank line ends the input as it is, and Ctrl-C aborts it.
+fn read_input(
+    editor: &mut Editor<InputParser>,
+    prompt: &str,
+    continuation_prompt: &str,
+) -> rustyline::Result<String> {
+    let mut input = editor.readline(prompt)?;
+
+    while let Some(InputStatus::Partial) = editor.helper().map(|helper| helper.parse(&input)) {
+        let line = editor.readline(continuation_prompt)?;
+
+        if line.trim().is_empty() {
+            break;
+        }
+
+        input.push('\n');
+        input.push_str(&line);
+    }
+
+    Ok(input)
+}
+
+/// Main loop of the REPL. `color_opt` determines whether colors are used, both for errors and
+/// for the prompt and messages of the REPL.
+#[allow(clippy::too_many_arguments)]
+pub fn repl(
+    histfile: PathBuf,
+    color_opt: ColorOpt,
+    max_parse_errors: Option<usize>,
+    call_stack_limit: Option<usize>,
+    json_duplicate_keys: DuplicateKeys,
+    stdlib: StdlibSource,
+    report_policy: Option<Arc<dyn ReportPolicy>>,
+    import_paths: Vec<PathBuf>,
+) -> Result<(), InitError> {
+    let paint = |style: Style, text: &str| {
+        if ColorChoice::from(color_opt) == ColorChoice::Never {
+            text.to_string()
+        } else {
+            style.paint(text).to_string()
+        }
+    };
     let mut repl = ReplImpl::new();
+    repl.cache_mut().set_max_parse_errors(max_parse_errors);
+    repl.cache_mut().set_call_stack_limit(call_stack_limit);
+    repl.cache_mut()
+        .set_json_duplicate_keys(json_duplicate_keys);
+    repl.cache_mut().set_stdlib(stdlib);
+    repl.cache_mut().set_import_paths(import_paths);
+    if let Some(policy) = report_policy {
+        repl.cache_mut().set_report_policy(policy);
+    }
 
     match repl.load_stdlib() {
         Ok(()) => (),
         Err(err) => {
-            program::report(repl.cache_mut(), err);
+            program::report_with_color(repl.cache_mut(), err, color_opt);
             return Err(InitError::Stdlib);
         }
     }
 
-    let validator = InputParser::new(repl.cache_mut().add_tmp("<repl-input>", String::new()));
+    let mut validator = InputParser::new(repl.cache_mut().add_tmp("<repl-input>", String::new()));
+    validator.set_env(repl.eval_env().clone());
 
     let mut editor = Editor::with_config(config());
     let _ = editor.load_history(&histfile);
     editor.set_helper(Some(validator));
-    let prompt = Style::new().fg(Colour::Green).paint("nickel> ").to_string();
+    let prompt = paint(Style::new().fg(Colour::Green), "nickel> ");
+    let continuation_prompt = paint(Style::new().fg(Colour::Green), "..> ");
 
     let result = loop {
-        let line = editor.readline(&prompt);
+        let line = read_input(&mut editor, &prompt, &continuation_prompt);
 
         if let Ok(line) = line.as_ref() {
             editor.add_history_entry(line.clone());
@@ -76,11 +134,26 @@ pub fn repl(histfile: PathBuf) -> Result<(), InitError> {
                         )
                         .unwrap();
                     }),
+                    Ok(Command::Doc(exp)) => repl.doc(&exp).map(|t| {
+                        query_print::write_doc_result(&mut stdout, &t).unwrap();
+                    }),
+                    Ok(Command::Env { verbose, all }) => {
+                        write_bindings(&mut stdout, &repl.bindings(all), verbose).unwrap();
+                        Ok(())
+                    }
+                    Ok(Command::Reset) => {
+                        repl.reset();
+                        println!("Environment reset.");
+                        Ok(())
+                    }
                     Ok(Command::Print(exp)) => {
                         match repl.eval_full(&exp) {
-                            Ok(EvalResult::Evaluated(rt)) => println!("{}\n", rt.as_ref().deep_repr()),
+                            Ok(EvalResult::Evaluated(rt)) => println!(
+                                "{}\n",
+                                repl.cache_mut().redactions().deep_repr(rt.as_ref())
+                            ),
                             Ok(EvalResult::Bound(_)) => (),
-                            Err(err) => program::report(repl.cache_mut(), err),
+                            Err(err) => program::report_with_color(repl.cache_mut(), err, color_opt),
                         };
                         Ok(())
                     }
@@ -88,39 +161,51 @@ pub fn repl(histfile: PathBuf) -> Result<(), InitError> {
                         print_help(&mut std::io::stdout(), arg.as_deref()).unwrap();
                         Ok(())
                     }
+                    Ok(Command::Explain(code)) => {
+                        print_explanation(&mut std::io::stdout(), &code).unwrap();
+                        Ok(())
+                    }
                     Ok(Command::Exit) => {
-                        println!("{}", Style::new().bold().paint("Exiting"));
+                        println!("{}", paint(Style::new().bold(), "Exiting"));
                         break Ok(());
                     }
                     Err(err) => Err(Error::from(err)),
                 };
 
                 if let Err(err) = result {
-                    program::report(repl.cache_mut(), err);
+                    program::report_with_color(repl.cache_mut(), err, color_opt);
                 } else {
                     println!();
                 }
             }
             Ok(line) => {
                 match repl.eval_full(&line) {
-                    Ok(EvalResult::Evaluated(rt)) => println!("{}\n", rt.as_ref().deep_repr()),
+                    Ok(EvalResult::Evaluated(rt)) => {
+                        println!("{}\n", repl.cache_mut().redactions().deep_repr(rt.as_ref()))
+                    }
                     Ok(EvalResult::Bound(_)) => (),
-                    Err(err) => program::report(repl.cache_mut(), err),
+                    Err(err) => program::report_with_color(repl.cache_mut(), err, color_opt),
                 };
             }
             Err(ReadlineError::Eof) => {
-                println!("{}", Style::new().bold().paint("Ctrl+D. Exiting"));
+                println!("{}", paint(Style::new().bold(), "Ctrl+D. Exiting"));
                 break Ok(());
             }
             Err(ReadlineError::Interrupted) => (),
             Err(err) => {
                 let _ = editor.save_history(&histfile);
-                program::report(
+                program::report_with_color(
                     repl.cache_mut(),
-                    Error::IOError(IOError(format!("{}", err))),
+                    Error::IOError(IOError::Other(format!("{}", err))),
+                    color_opt,
                 );
             }
         }
+
+        // The input may have bound new names or evaluated records, which can now be completed.
+        if let Some(helper) = editor.helper_mut() {
+            helper.set_env(repl.eval_env().clone());
+        }
     };
     let _ = editor.save_history(&histfile);
     result
@@ -2,7 +2,7 @@
 //! The output may contain ANSI escape codes.
 use super::{command::Command, *};
 use crate::error::Error;
-use crate::{serialize, serialize::ExportFormat};
+use crate::serialize::{self, ExportFormat, ExportStyle};
 use std::io::Cursor;
 
 /// Add a failure mode to usual errors for features that are not supported by all REPLs (for
@@ -62,10 +62,31 @@ pub fn input<R: Repl>(repl: &mut R, line: &str) -> Result<InputResult, InputErro
                     InputResult::Success(String::from_utf8(buffer.into_inner()).unwrap())
                 })
                 .map_err(InputError::from),
+            Ok(Command::Doc(exp)) => repl
+                .doc(&exp)
+                .map(|t| {
+                    let mut buffer = Cursor::new(Vec::<u8>::new());
+                    query_print::write_doc_result(&mut buffer, &t).unwrap();
+                    InputResult::Success(String::from_utf8(buffer.into_inner()).unwrap())
+                })
+                .map_err(InputError::from),
+            Ok(Command::Env { verbose, all }) => {
+                let mut buffer = Cursor::new(Vec::<u8>::new());
+                write_bindings(&mut buffer, &repl.bindings(all), verbose).unwrap();
+                Ok(InputResult::Success(
+                    String::from_utf8(buffer.into_inner()).unwrap(),
+                ))
+            }
+            Ok(Command::Reset) => {
+                repl.reset();
+                Ok(InputResult::Success(String::from("Environment reset.")))
+            }
             Ok(Command::Print(exp)) => repl
                 .eval_full(&exp)
                 .map(|res| match res {
-                    EvalResult::Evaluated(rt) => InputResult::Success(rt.as_ref().deep_repr()),
+                    EvalResult::Evaluated(rt) => {
+                        InputResult::Success(repl.cache_mut().redactions().deep_repr(rt.as_ref()))
+                    }
                     EvalResult::Bound(_) => InputResult::Blank,
                 })
                 .map_err(InputError::from),
@@ -76,6 +97,13 @@ pub fn input<R: Repl>(repl: &mut R, line: &str) -> Result<InputResult, InputErro
                     String::from_utf8(buffer.into_inner()).unwrap(),
                 ))
             }
+            Ok(Command::Explain(code)) => {
+                let mut buffer = Cursor::new(Vec::<u8>::new());
+                print_explanation(&mut buffer, &code).unwrap();
+                Ok(InputResult::Success(
+                    String::from_utf8(buffer.into_inner()).unwrap(),
+                ))
+            }
             Ok(Command::Exit) => Ok(InputResult::Success(String::from("Exiting"))),
             Err(err) => Err(InputError::from(Error::from(err))),
         }
@@ -83,7 +111,8 @@ pub fn input<R: Repl>(repl: &mut R, line: &str) -> Result<InputResult, InputErro
         repl.eval_full(line)
             .map(|eval_res| match eval_res {
                 EvalResult::Evaluated(rt) => {
-                    InputResult::Success(format!("{}\n", rt.as_ref().deep_repr()))
+                    let output = repl.cache_mut().redactions().deep_repr(rt.as_ref());
+                    InputResult::Success(format!("{}\n", output))
                 }
                 EvalResult::Bound(_) => InputResult::Success(String::new()),
             })
@@ -99,9 +128,12 @@ pub fn serialize<R: Repl>(
 ) -> Result<InputResult, InputError> {
     repl.eval_full(input)
         .and_then(|eval_res| match eval_res {
-            EvalResult::Evaluated(t) => serialize::to_string(format, &t.into())
-                .map(InputResult::Success)
-                .map_err(Error::from),
+            EvalResult::Evaluated(t) => {
+                let rt = repl.cache_mut().redactions().redact_value(t.into());
+                serialize::to_string(format, ExportStyle::default(), &rt)
+                    .map(InputResult::Success)
+                    .map_err(Error::from)
+            }
             EvalResult::Bound(_) => Ok(InputResult::Success(String::new())),
         })
         .map_err(InputError::from)
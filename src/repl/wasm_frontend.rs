@@ -2,17 +2,19 @@
 use super::simple_frontend::{input, serialize, InputError, InputResult};
 use super::{Repl, ReplImpl};
 use crate::cache::Cache;
-use crate::error::ToDiagnostic;
+use crate::error::{
+    generate_diagnostics, render_diagnostics, render_snippet_diagnostics, DiagnosticFiles,
+    SnippetFiles,
+};
 use crate::serialize::ExportFormat;
 use codespan::{FileId, Files};
 use codespan_reporting::{
     diagnostic::{Diagnostic, Label, LabelStyle, Severity},
-    term::termcolor::Ansi,
+    term::termcolor::ColorChoice,
 };
 use serde::Serialize;
 use serde_repr::Serialize_repr;
 use std::convert::TryInto;
-use std::io::Cursor;
 use wasm_bindgen::prelude::*;
 
 /// Return codes of the WASM REPL.
@@ -83,7 +85,7 @@ pub struct WasmErrorDiagnostic {
 }
 
 impl WasmErrorDiagnostic {
-    fn from_codespan(files: &Files<String>, diag: Diagnostic<FileId>) -> Self {
+    fn from_codespan(files: &SnippetFiles, diag: Diagnostic<FileId>) -> Self {
         WasmErrorDiagnostic {
             severity: diag.severity.into(),
             msg: diag.message,
@@ -109,23 +111,20 @@ pub struct WasmErrorLabel {
 }
 
 impl WasmErrorLabel {
-    fn from_codespan(files: &Files<String>, label: Label<FileId>) -> Self {
-        let start_loc = files.location(label.file_id, label.range.start as u32);
-        let end_loc = files.location(label.file_id, label.range.end as u32);
+    fn from_codespan(files: &SnippetFiles, label: Label<FileId>) -> Self {
+        // Locations are one-based, while the ones of the WASM interface are zero-based.
+        let location = |byte_index| {
+            DiagnosticFiles::location(files, label.file_id, byte_index)
+                .map(|(line, column)| (line - 1, column - 1))
+        };
+        let start_loc = location(label.range.start);
+        let end_loc = location(label.range.end);
 
         let (line_start, col_start, line_end, col_end) = match (start_loc, end_loc) {
-            (Ok(start_loc), Ok(end_loc)) => (
-                start_loc.line.to_usize(),
-                start_loc.column.to_usize(),
-                end_loc.line.to_usize(),
-                end_loc.column.to_usize(),
-            ),
-            (Ok(loc), _) | (_, Ok(loc)) => (
-                loc.line.to_usize(),
-                loc.column.to_usize(),
-                loc.line.to_usize(),
-                loc.column.to_usize(),
-            ),
+            (Some((line_start, col_start)), Some((line_end, col_end))) => {
+                (line_start, col_start, line_end, col_end)
+            }
+            (Some((line, col)), _) | (_, Some((line, col))) => (line, col, line, col),
             _ => (0, 0, 0, 0),
         };
 
@@ -193,13 +192,20 @@ impl WasmInputResult {
     fn error(cache: &mut Cache, error: InputError) -> Self {
         let (msg, errors) = match error {
             InputError::NickelError(err) => {
-                let contracts_id = cache.id_of("<stdlib/contract.ncl>");
-                let diagnostics = err.to_diagnostic(cache.files_mut(), contracts_id);
-
-                let msg = diags_to_string(cache, &diagnostics);
+                // Generated snippets are kept apart, such that the file database doesn't grow
+                // with each reported error.
+                let (mut files, mut diagnostics) = generate_diagnostics(
+                    &err,
+                    cache.files(),
+                    cache.contracts_id(),
+                    cache.diagnostic_options(),
+                );
+                files.redact_sources(&mut diagnostics);
+
+                let msg = render_snippet_diagnostics(&files, &diagnostics, ColorChoice::Always);
                 let errors: Vec<WasmErrorDiagnostic> = diagnostics
                     .into_iter()
-                    .map(|diag| WasmErrorDiagnostic::from_codespan(cache.files(), diag))
+                    .map(|diag| WasmErrorDiagnostic::from_codespan(&files, diag))
                     .collect();
                 (msg, errors)
             }
@@ -270,28 +276,24 @@ impl TryInto<ExportFormat> for WasmExportFormat {
     }
 }
 
-/// Render error diagnostics as a string.
-pub fn diags_to_string(cache: &mut Cache, diags: &Vec<Diagnostic<FileId>>) -> String {
-    let mut buffer = Ansi::new(Cursor::new(Vec::new()));
-    let config = codespan_reporting::term::Config::default();
-
-    diags
-        .iter()
-        .try_for_each(|d| {
-            codespan_reporting::term::emit(&mut buffer, &config, cache.files_mut(), &d)
-        })
-        .unwrap();
-
-    String::from_utf8(buffer.into_inner().into_inner()).unwrap()
+/// Render error diagnostics as a string. `files` must be the file database the diagnostics were
+/// generated with.
+pub fn diags_to_string(files: &Files<String>, diags: &Vec<Diagnostic<FileId>>) -> String {
+    render_diagnostics(files, diags, ColorChoice::Always)
 }
 
 /// Render an error as a string (similar to [`diags_to_string`](./meth.diags_to_string.html)).
 pub fn err_to_string(cache: &mut Cache, error: &InputError) -> String {
     match error {
         InputError::NickelError(nickel_err) => {
-            let contracts_id = cache.id_of("<stdlib/contract.ncl>");
-            let diags = nickel_err.to_diagnostic(cache.files_mut(), contracts_id);
-            diags_to_string(cache, &diags)
+            let (mut files, mut diags) = generate_diagnostics(
+                nickel_err,
+                cache.files(),
+                cache.contracts_id(),
+                cache.diagnostic_options(),
+            );
+            files.redact_sources(&mut diags);
+            render_snippet_diagnostics(&files, &diags, ColorChoice::Always)
         }
         InputError::Other(msg) => msg.clone(),
     }
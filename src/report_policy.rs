@@ -0,0 +1,242 @@
+//! Reporting policies.
+//!
+//! A host program embedding Nickel may want to adjust the diagnostics of the interpreter before
+//! they are rendered: point to its own documentation for a given error code, downgrade the
+//! severity of an error it handles itself, or silence it altogether. A [`ReportPolicy`] is applied
+//! to every diagnostic produced when reporting an error (see
+//! [`DiagnosticOptions::policy`](../error/struct.DiagnosticOptions.html#structfield.policy)).
+//!
+//! [`CodePolicy`] is a ready-made policy, configured by a set of rules indexed by error code. It
+//! can be loaded from a TOML file, as done by the `--report-policy` option of the command-line
+//! interface:
+//!
+//! ```toml
+//! [E001]
+//! url = "https://runbooks.example.com/contracts"
+//! note = "contact the platform team if the contract looks wrong"
+//!
+//! [E034]
+//! severity = "ignore"
+//! fatal = false
+//! ```
+use crate::error::IOError;
+use codespan::FileId;
+use codespan_reporting::diagnostic::{Diagnostic, Severity};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+/// A hook adjusting the diagnostics of errors before they are rendered.
+pub trait ReportPolicy: fmt::Debug + Send + Sync {
+    /// Adjust a diagnostic of an error with the given code, or return `None` to drop it.
+    ///
+    /// An error may be reported as several diagnostics: the leading one, which describes the error
+    /// and carries the code, and additional ones, such as the call stack of a blame error. All of
+    /// them are passed to `adjust` with the code of the error. Dropping the leading diagnostic
+    /// drops the additional ones as well.
+    fn adjust(&self, code: &str, diag: Diagnostic<FileId>) -> Option<Diagnostic<FileId>>;
+
+    /// Whether an error with the given code makes the command which reported it fail. Default to
+    /// `true`.
+    fn is_fatal(&self, _code: &str) -> bool {
+        true
+    }
+}
+
+/// Apply a policy to the diagnostics of an error. Diagnostics coming before the first one with a
+/// code are left untouched.
+pub fn apply(policy: &dyn ReportPolicy, diags: Vec<Diagnostic<FileId>>) -> Vec<Diagnostic<FileId>> {
+    let mut result = Vec::with_capacity(diags.len());
+    let mut code: Option<String> = None;
+    let mut dropped = false;
+
+    for diag in diags {
+        let is_head = diag.code.is_some();
+        if is_head {
+            code = diag.code.clone();
+            dropped = false;
+        }
+
+        match code.as_deref() {
+            _ if dropped => (),
+            None => result.push(diag),
+            Some(code) => match policy.adjust(code, diag) {
+                Some(diag) => result.push(diag),
+                None => dropped = is_head,
+            },
+        }
+    }
+
+    result
+}
+
+/// The severity given to the diagnostics of an error by a [`Rule`].
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    /// Drop the diagnostics of the error.
+    Ignore,
+    Bug,
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+/// How a [`CodePolicy`] adjusts the diagnostics of the errors with a given code.
+#[derive(Clone, Eq, PartialEq, Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// Override the severity of the leading diagnostic, or drop the diagnostics with
+    /// [`Level::Ignore`].
+    pub severity: Option<Level>,
+    /// A note added to the leading diagnostic.
+    pub note: Option<String>,
+    /// A link to a documentation, added as a note to the leading diagnostic.
+    pub url: Option<String>,
+    /// Whether the error makes the command fail. Default to `true`.
+    pub fatal: Option<bool>,
+}
+
+/// A policy adjusting diagnostics according to rules indexed by error code. Codes are case
+/// insensitive.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct CodePolicy {
+    rules: HashMap<String, Rule>,
+}
+
+impl CodePolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the rule of an error code, replacing the previous one if any.
+    pub fn with_rule(mut self, code: impl AsRef<str>, rule: Rule) -> Self {
+        self.rules.insert(code.as_ref().to_uppercase(), rule);
+        self
+    }
+
+    /// Parse a policy from a TOML document, whose tables are the rules indexed by error code.
+    pub fn from_toml(s: &str) -> Result<Self, toml::de::Error> {
+        let rules: HashMap<String, Rule> = toml::from_str(s)?;
+        Ok(rules
+            .into_iter()
+            .fold(CodePolicy::new(), |policy, (code, rule)| {
+                policy.with_rule(code, rule)
+            }))
+    }
+
+    /// Load a policy from a TOML file. See [`from_toml`](#method.from_toml).
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, IOError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        Self::from_toml(&content)
+            .map_err(|err| IOError::Other(format!("{}: {}", path.display(), err)))
+    }
+
+    fn rule(&self, code: &str) -> Option<&Rule> {
+        self.rules.get(&code.to_uppercase())
+    }
+}
+
+impl ReportPolicy for CodePolicy {
+    fn adjust(&self, code: &str, mut diag: Diagnostic<FileId>) -> Option<Diagnostic<FileId>> {
+        let rule = match self.rule(code) {
+            Some(rule) => rule,
+            None => return Some(diag),
+        };
+
+        if rule.severity == Some(Level::Ignore) {
+            return None;
+        }
+
+        // Only the leading diagnostic carries the code.
+        if diag.code.is_some() {
+            diag.severity = match rule.severity {
+                Some(Level::Bug) => Severity::Bug,
+                Some(Level::Error) => Severity::Error,
+                Some(Level::Warning) => Severity::Warning,
+                Some(Level::Note) => Severity::Note,
+                Some(Level::Help) => Severity::Help,
+                Some(Level::Ignore) | None => diag.severity,
+            };
+            diag.notes.extend(rule.note.clone());
+            diag.notes
+                .extend(rule.url.as_ref().map(|url| format!("see {}", url)));
+        }
+
+        Some(diag)
+    }
+
+    fn is_fatal(&self, code: &str) -> bool {
+        self.rule(code).and_then(|rule| rule.fatal).unwrap_or(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diags() -> Vec<Diagnostic<FileId>> {
+        vec![
+            Diagnostic::error()
+                .with_message("contract broken")
+                .with_code("E001"),
+            Diagnostic::note().with_message("call stack"),
+            Diagnostic::warning()
+                .with_message("deprecated")
+                .with_code("E999"),
+        ]
+    }
+
+    #[test]
+    fn code_policy() {
+        let policy = CodePolicy::from_toml(
+            r#"
+            [E001]
+            url = "https://runbooks.example.com/E001"
+            fatal = false
+
+            [e999]
+            severity = "ignore"
+            "#,
+        )
+        .unwrap();
+
+        let diags = apply(&policy, diags());
+        assert_eq!(diags.len(), 2);
+        assert_eq!(diags[0].severity, Severity::Error);
+        assert_eq!(
+            diags[0].notes,
+            vec!["see https://runbooks.example.com/E001"]
+        );
+        assert_eq!(diags[1].message, "call stack");
+        assert!(diags[1].notes.is_empty());
+
+        assert!(!policy.is_fatal("E001"));
+        assert!(policy.is_fatal("E999"));
+        assert!(policy.is_fatal("E002"));
+    }
+
+    #[test]
+    fn dropping_the_head_drops_the_group() {
+        let policy = CodePolicy::new().with_rule(
+            "E001",
+            Rule {
+                severity: Some(Level::Ignore),
+                ..Rule::default()
+            },
+        );
+
+        let diags = apply(&policy, diags());
+        assert_eq!(diags.len(), 1);
+        assert_eq!(diags[0].message, "deprecated");
+    }
+
+    #[test]
+    fn invalid_policy() {
+        assert!(CodePolicy::from_toml("[E001]\nsevrity = \"warning\"").is_err());
+        assert!(CodePolicy::from_toml("[E001]\nseverity = \"fatal\"").is_err());
+    }
+}
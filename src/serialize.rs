@@ -1,15 +1,31 @@
 //! Serialization of an evaluated program to various data format.
-use crate::error::SerializationError;
+use crate::error::{IOError, SerializationError};
 use crate::identifier::Ident;
+use crate::label::field_path;
 use crate::term::{MetaValue, RecordAttrs, RichTerm, Term};
-use serde::de::{Deserialize, Deserializer};
+use serde::de::{
+    Deserialize, DeserializeSeed, Deserializer, Error as DeError, IgnoredAny, MapAccess, SeqAccess,
+    Visitor,
+};
 use serde::ser::{Error, Serialize, SerializeMap, Serializer};
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::fmt;
 use std::io;
+use std::ops::Range;
 use std::str::FromStr;
+use yaml_rust::{Yaml, YamlEmitter};
 
 /// Available export formats.
+///
+/// The export of a value is deterministic: the same value gives the same bytes in every format,
+/// whatever the platform, the locale or the run. The fields of records are written in the order of
+/// their names, and numbers are written with the shortest representation which reads back to the
+/// same float, independently of the locale. These guarantees are checked against the golden files
+/// of `tests/determinism`.
+///
+/// MessagePack is a binary format: it can be written with [`to_writer`] or [`to_vec`], but not
+/// with [`to_string`].
 // If you add or remove variants, remember to update the CLI docs in `src/bin/nickel.rs'
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 pub enum ExportFormat {
@@ -17,6 +33,14 @@ pub enum ExportFormat {
     Json,
     Yaml,
     Toml,
+    MessagePack,
+}
+
+impl ExportFormat {
+    /// Return `true` if the format is a binary format, whose output isn't valid UTF-8 in general.
+    pub fn is_binary(&self) -> bool {
+        matches!(self, ExportFormat::MessagePack)
+    }
 }
 
 impl std::default::Default for ExportFormat {
@@ -32,6 +56,7 @@ impl fmt::Display for ExportFormat {
             Self::Json => write!(f, "json"),
             Self::Yaml => write!(f, "yaml"),
             Self::Toml => write!(f, "toml"),
+            Self::MessagePack => write!(f, "messagepack"),
         }
     }
 }
@@ -54,11 +79,442 @@ impl FromStr for ExportFormat {
             "json" => Ok(ExportFormat::Json),
             "yaml" => Ok(ExportFormat::Yaml),
             "toml" => Ok(ExportFormat::Toml),
+            "messagepack" | "msgpack" => Ok(ExportFormat::MessagePack),
             _ => Err(ParseFormatError(String::from(s))),
         }
     }
 }
 
+/// The layout of the JSON output. Other formats have a fixed layout and ignore it.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum ExportStyle {
+    /// Human-readable output, with one value per line and nested values indented by `indent`
+    /// spaces.
+    Pretty { indent: usize },
+    /// Minified output, without any whitespace, for machine consumption.
+    Compact,
+}
+
+impl std::default::Default for ExportStyle {
+    fn default() -> Self {
+        ExportStyle::Pretty { indent: 2 }
+    }
+}
+
+/// How to handle a key appearing several times in the same object when deserializing JSON.
+///
+/// JSON technically allows duplicate keys, but they usually reveal a mistake in the program that
+/// generated the data. The default is thus to fail with an error naming the duplicated key.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum DuplicateKeys {
+    /// Fail with an error.
+    #[default]
+    Error,
+    /// Keep the first occurrence of the key and ignore the others.
+    First,
+    /// Keep the last occurrence of the key. This is the behavior of most JSON parsers.
+    Last,
+}
+
+impl fmt::Display for DuplicateKeys {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::First => write!(f, "first"),
+            Self::Last => write!(f, "last"),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ParseDuplicateKeysError(String);
+
+impl fmt::Display for ParseDuplicateKeysError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unsupported duplicate keys behavior {} (expected error, first or last)",
+            self.0
+        )
+    }
+}
+
+impl FromStr for DuplicateKeys {
+    type Err = ParseDuplicateKeysError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_ref() {
+            "error" => Ok(DuplicateKeys::Error),
+            "first" => Ok(DuplicateKeys::First),
+            "last" => Ok(DuplicateKeys::Last),
+            _ => Err(ParseDuplicateKeysError(String::from(s))),
+        }
+    }
+}
+
+/// An error raised by [`json_from_str`].
+#[derive(Debug)]
+pub struct JsonError {
+    /// The error reported by `serde_json`. For a duplicate key, its position is the one of the
+    /// second occurrence of the key.
+    pub error: serde_json::Error,
+    /// For a duplicate key, the byte ranges of the first and of the second occurrence of the key
+    /// in the source, quotes included, if they could be located.
+    pub duplicate_key: Option<(Range<usize>, Range<usize>)>,
+}
+
+impl From<serde_json::Error> for JsonError {
+    fn from(error: serde_json::Error) -> Self {
+        JsonError {
+            error,
+            duplicate_key: None,
+        }
+    }
+}
+
+/// Deserialize a JSON string to a term, handling duplicate keys as prescribed by `duplicate_keys`.
+///
+/// The derived implementation of `Deserialize` for `Term` silently keeps the last occurrence of a
+/// duplicated key, hence this dedicated deserializer. The deserializer of `serde_json` doesn't
+/// expose the positions of the keys: when a duplicate key is rejected, the source is scanned again
+/// to locate both of its occurrences (see [`JsonError::duplicate_key`]).
+pub fn json_from_str(s: &str, duplicate_keys: DuplicateKeys) -> Result<RichTerm, JsonError> {
+    let mut deserializer = serde_json::Deserializer::from_str(s);
+    let mut state = JsonState::default();
+    let result = JsonSeed {
+        duplicate_keys,
+        state: &mut state,
+    }
+    .deserialize(&mut deserializer);
+
+    match result {
+        Ok(rt) => {
+            deserializer.end()?;
+            Ok(rt)
+        }
+        Err(error) if state.duplicate => Err(JsonError {
+            error,
+            duplicate_key: locate_duplicate_key(s, &state.path),
+        }),
+        Err(error) => Err(error.into()),
+    }
+}
+
+/// Deserialize a YAML string to a term. A stream of several documents, separated by `---`, is
+/// deserialized to the array of its documents.
+pub fn yaml_from_str(s: &str) -> serde_yaml::Result<RichTerm> {
+    let mut docs = serde_yaml::Deserializer::from_str(s)
+        .map(RichTerm::deserialize)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| {
+            // Syntax errors are shared between the documents of a stream, which loses their
+            // location. Parsing the string as a single document reports the same error with its
+            // location.
+            match serde_yaml::from_str::<RichTerm>(s) {
+                Err(single) if err.location().is_none() && single.location().is_some() => single,
+                _ => err,
+            }
+        })?;
+
+    match docs.len() {
+        // Report the same error as for a single document on an empty stream.
+        0 => serde_yaml::from_str(s),
+        1 => Ok(docs.pop().unwrap()),
+        _ => Ok(Term::Array(docs).into()),
+    }
+}
+
+/// The state shared by the seeds of a JSON deserialization.
+#[derive(Default)]
+struct JsonState {
+    /// The keys leading to the current value. When a duplicate key is rejected, the
+    /// deserialization stops with the path of the duplicate key.
+    path: Vec<String>,
+    /// Whether the deserialization failed on a duplicate key.
+    duplicate: bool,
+}
+
+/// A deserialization seed for JSON values, which keeps track of the path of the current value
+/// to report duplicate keys.
+struct JsonSeed<'a> {
+    duplicate_keys: DuplicateKeys,
+    state: &'a mut JsonState,
+}
+
+impl<'a, 'de> DeserializeSeed<'de> for JsonSeed<'a> {
+    type Value = RichTerm;
+
+    fn deserialize<D>(self, deserializer: D) -> Result<RichTerm, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'a, 'de> Visitor<'de> for JsonSeed<'a> {
+    type Value = RichTerm;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a JSON value")
+    }
+
+    fn visit_unit<E>(self) -> Result<RichTerm, E> {
+        Ok(Term::Null.into())
+    }
+
+    fn visit_bool<E>(self, b: bool) -> Result<RichTerm, E> {
+        Ok(Term::Bool(b).into())
+    }
+
+    fn visit_i64<E>(self, n: i64) -> Result<RichTerm, E> {
+        Ok(Term::Num(n as f64).into())
+    }
+
+    fn visit_u64<E>(self, n: u64) -> Result<RichTerm, E> {
+        Ok(Term::Num(n as f64).into())
+    }
+
+    fn visit_f64<E>(self, n: f64) -> Result<RichTerm, E> {
+        Ok(Term::Num(n).into())
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<RichTerm, E> {
+        Ok(Term::Str(String::from(s)).into())
+    }
+
+    fn visit_string<E>(self, s: String) -> Result<RichTerm, E> {
+        Ok(Term::Str(s).into())
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<RichTerm, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut elts = Vec::new();
+
+        while let Some(elt) = seq.next_element_seed(JsonSeed {
+            duplicate_keys: self.duplicate_keys,
+            state: &mut *self.state,
+        })? {
+            elts.push(elt);
+        }
+
+        Ok(Term::Array(elts).into())
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<RichTerm, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut fields = HashMap::new();
+
+        while let Some(key) = map.next_key::<String>()? {
+            let id = Ident::from(key.as_str());
+            let duplicate = fields.contains_key(&id);
+
+            if duplicate && self.duplicate_keys == DuplicateKeys::Error {
+                self.state.path.push(key);
+                self.state.duplicate = true;
+                return Err(DeError::custom(format!(
+                    "duplicate key `{}`",
+                    self.state.path.join(".")
+                )));
+            }
+
+            self.state.path.push(key);
+            let value = map.next_value_seed(JsonSeed {
+                duplicate_keys: self.duplicate_keys,
+                state: &mut *self.state,
+            })?;
+            self.state.path.pop();
+
+            if !duplicate || self.duplicate_keys == DuplicateKeys::Last {
+                fields.insert(id, value);
+            }
+        }
+
+        Ok(Term::Record(fields, Default::default()).into())
+    }
+}
+
+/// Locate the first two occurrences of a key in the same object, given the path of the key, as
+/// byte ranges of `s`, quotes included.
+///
+/// The source is deserialized again from a reader which counts the bytes consumed by
+/// `serde_json`, such that the end of each key is known once it has been read. The path doesn't
+/// record the indices of arrays: the first object reached by the path which contains the key
+/// twice is the one reported.
+fn locate_duplicate_key(s: &str, path: &[String]) -> Option<(Range<usize>, Range<usize>)> {
+    let offset = Cell::new(0);
+    let mut found = None;
+    let reader = CountingReader {
+        bytes: s.as_bytes(),
+        offset: &offset,
+    };
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+
+    // The locator stops with an error as soon as the second occurrence is found.
+    let _ = KeyLocator {
+        source: s,
+        path,
+        offset: &offset,
+        found: &mut found,
+    }
+    .deserialize(&mut deserializer);
+
+    found
+}
+
+/// A reader over a byte slice which counts the bytes read so far.
+struct CountingReader<'a> {
+    bytes: &'a [u8],
+    offset: &'a Cell<usize>,
+}
+
+impl io::Read for CountingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = io::Read::read(&mut self.bytes, buf)?;
+        self.offset.set(self.offset.get() + count);
+        Ok(count)
+    }
+}
+
+/// A deserialization seed looking for the first two occurrences of the last key of `path` in an
+/// object reached by the other keys. See [`locate_duplicate_key`].
+struct KeyLocator<'a, 'p> {
+    source: &'a str,
+    path: &'p [String],
+    offset: &'a Cell<usize>,
+    found: &'p mut Option<(Range<usize>, Range<usize>)>,
+}
+
+impl KeyLocator<'_, '_> {
+    /// The range of the key which has just been read, quotes included. `serde_json` may have
+    /// consumed some bytes after the closing quote, which are skipped backward.
+    fn last_key_range(&self) -> Range<usize> {
+        let bytes = self.source.as_bytes();
+        let mut end = self.offset.get().min(bytes.len());
+
+        while end > 0 && bytes[end - 1] != b'"' {
+            end -= 1;
+        }
+
+        // The opening quote is the first one before the closing quote which isn't escaped, that
+        // is which isn't preceded by an odd number of backslashes.
+        let mut start = end.saturating_sub(1);
+        while start > 0 {
+            start -= 1;
+
+            if bytes[start] == b'"' {
+                let backslashes = bytes[..start]
+                    .iter()
+                    .rev()
+                    .take_while(|b| **b == b'\\')
+                    .count();
+
+                if backslashes % 2 == 0 {
+                    break;
+                }
+            }
+        }
+
+        start..end
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for KeyLocator<'_, '_> {
+    type Value = ();
+
+    fn deserialize<D>(self, deserializer: D) -> Result<(), D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for KeyLocator<'_, '_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a JSON value")
+    }
+
+    fn visit_unit<E>(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<(), A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        while seq
+            .next_element_seed(KeyLocator {
+                source: self.source,
+                path: self.path,
+                offset: self.offset,
+                found: &mut *self.found,
+            })?
+            .is_some()
+        {}
+
+        Ok(())
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<(), A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let (key, rest) = match self.path.split_first() {
+            Some(split) => split,
+            None => return Ok(()),
+        };
+        let mut first = None;
+
+        while let Some(current) = map.next_key::<String>()? {
+            if current != *key {
+                map.next_value::<IgnoredAny>()?;
+            } else if !rest.is_empty() {
+                map.next_value_seed(KeyLocator {
+                    source: self.source,
+                    path: rest,
+                    offset: self.offset,
+                    found: &mut *self.found,
+                })?;
+            } else if let Some(first) = first.take() {
+                *self.found = Some((first, self.last_key_range()));
+                return Err(DeError::custom("duplicate key located"));
+            } else {
+                first = Some(self.last_key_range());
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
 /// Implicitly convert float to integers when possible to avoid trailing zeros. Note this this
 /// only work if the float is in range of either `i64` or `f64`. It seems there's no easy general
 /// solution (working for both YAML, TOML, and JSON) to choose the way floating point values are
@@ -93,6 +549,10 @@ where
 
 /// Serializer for a record. Serialize fields in alphabetical order to get a deterministic output
 /// (by default, `HashMap`'s randomness implies a randomized order of fields in the output).
+///
+/// Fields are compared byte-wise, as done by [`Ident`]'s `Ord` instance. As nested records,
+/// including the ones inside arrays, go through this serializer as well, the output of an export
+/// is reproducible in every format, without any option to enable it.
 pub fn serialize_record<S>(
     map: &HashMap<Ident, RichTerm>,
     _attrs: &RecordAttrs,
@@ -144,8 +604,32 @@ impl<'de> Deserialize<'de> for RichTerm {
 }
 
 /// Check that a term is serializable. Serializable terms are booleans, numbers, strings, enum,
-/// arrays of serializable terms or records of serializable terms.
+/// arrays of serializable terms or records of serializable terms. Additionally, the top-level term
+/// must be a record for TOML, and arrays can't mix records with other values.
 pub fn validate(format: ExportFormat, t: &RichTerm) -> Result<(), SerializationError> {
+    if format == ExportFormat::Toml && !is_record(t) {
+        return Err(SerializationError::UnsupportedFormat(format, t.clone()));
+    }
+
+    validate_value(format, t, &field_path::Path::default())
+}
+
+/// Return `true` if a term is a record, possibly wrapped in a metavalue.
+fn is_record(t: &RichTerm) -> bool {
+    match t.term.as_ref() {
+        Term::Record(..) => true,
+        Term::MetaValue(MetaValue { value: Some(t), .. }) => is_record(t),
+        _ => false,
+    }
+}
+
+/// Check that a term is serializable, without any constraint on the top-level term. See
+/// [`validate`]. `path` is the field path of the term inside the exported value.
+fn validate_value(
+    format: ExportFormat,
+    t: &RichTerm,
+    path: &field_path::Path,
+) -> Result<(), SerializationError> {
     use crate::term;
     use Term::*;
 
@@ -158,44 +642,123 @@ pub fn validate(format: ExportFormat, t: &RichTerm) -> Result<(), SerializationE
     } else {
         match t.term.as_ref() {
             // TOML doesn't support null values
-            Null if format == ExportFormat::Json || format == ExportFormat::Yaml => Ok(()),
-            Null => Err(SerializationError::UnsupportedNull(format, t.clone())),
+            Null if format != ExportFormat::Toml => Ok(()),
+            Null => Err(SerializationError::UnsupportedNull(
+                format,
+                t.clone(),
+                path.clone(),
+            )),
             Bool(_) | Num(_) | Str(_) | Enum(_) => Ok(()),
             Record(map, _) => {
-                map.iter().try_for_each(|(_, t)| validate(format, t))?;
+                map.iter().try_for_each(|(id, t)| {
+                    validate_value(format, t, &path.with(field_path::Elem::Field(id.clone())))
+                })?;
                 Ok(())
             }
+            // An array of tables can't contain other values in TOML.
+            Array(vec)
+                if format == ExportFormat::Toml
+                    && vec.iter().any(is_record)
+                    && !vec.iter().all(is_record) =>
+            {
+                Err(SerializationError::MixedArray(
+                    format,
+                    t.clone(),
+                    path.clone(),
+                ))
+            }
             Array(vec) => {
-                vec.iter().try_for_each(|t| validate(format, t))?;
+                vec.iter().enumerate().try_for_each(|(index, t)| {
+                    validate_value(format, t, &path.with(field_path::Elem::Index(index)))
+                })?;
                 Ok(())
             }
             //TODO: have a specific error for such missing value.
             MetaValue(term::MetaValue {
                 value: Some(ref t), ..
-            }) => validate(format, t),
-            _ => Err(SerializationError::NonSerializable(t.clone())),
+            }) => validate_value(format, t, path),
+            _ => Err(SerializationError::NonSerializable(t.clone(), path.clone())),
         }
     }
 }
 
+/// Serialize a term to a writer, without building the whole output in memory first (except for
+/// TOML, whose serializer needs a complete document). The writer is flushed at the end. A failure
+/// of the writer is reported as an [`IOError`], not as a serialization error.
 pub fn to_writer<W>(
+    writer: W,
+    format: ExportFormat,
+    style: ExportStyle,
+    rt: &RichTerm,
+) -> Result<(), crate::error::Error>
+where
+    W: io::Write,
+{
+    let mut writer = TrackedWriter {
+        inner: writer,
+        error: None,
+    };
+    let result = write_term(&mut writer, format, style, rt).and_then(|()| {
+        io::Write::flush(&mut writer).map_err(|err| SerializationError::Other(err.to_string()))
+    });
+
+    // The serializers wrap the errors of the writer in their own errors, from which they can't be
+    // told apart reliably.
+    match writer.error {
+        Some(err) => Err(IOError::Other(format!("could not write the export: {}", err)).into()),
+        None => result.map_err(Into::into),
+    }
+}
+
+/// A writer which keeps the first error of the underlying writer.
+struct TrackedWriter<W> {
+    inner: W,
+    error: Option<io::Error>,
+}
+
+impl<W> TrackedWriter<W> {
+    fn record(&mut self, err: io::Error) -> io::Error {
+        let copy = io::Error::new(err.kind(), err.to_string());
+        self.error.get_or_insert(err);
+        copy
+    }
+}
+
+impl<W: io::Write> io::Write for TrackedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf).map_err(|err| self.record(err))
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.inner.write_all(buf).map_err(|err| self.record(err))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush().map_err(|err| self.record(err))
+    }
+}
+
+fn write_term<W>(
     mut writer: W,
     format: ExportFormat,
+    style: ExportStyle,
     rt: &RichTerm,
 ) -> Result<(), SerializationError>
 where
     W: io::Write,
 {
     match format {
-        ExportFormat::Json => serde_json::to_writer_pretty(writer, &rt)
-            .map_err(|err| SerializationError::Other(err.to_string())),
-        ExportFormat::Yaml => serde_yaml::to_writer(writer, &rt)
+        ExportFormat::Json => to_json_writer(writer, style, rt),
+        ExportFormat::Yaml => to_yaml_string(rt).and_then(|s| {
+            writer
+                .write_all(s.as_bytes())
+                .map_err(|err| SerializationError::Other(err.to_string()))
+        }),
+        ExportFormat::Toml => to_toml(rt).and_then(|v| {
+            write!(writer, "{}", v).map_err(|err| SerializationError::Other(err.to_string()))
+        }),
+        ExportFormat::MessagePack => rmp_serde::encode::write(&mut writer, rt)
             .map_err(|err| SerializationError::Other(err.to_string())),
-        ExportFormat::Toml => toml::Value::try_from(&rt)
-            .map_err(|err| SerializationError::Other(err.to_string()))
-            .and_then(|v| {
-                write!(writer, "{}", v).map_err(|err| SerializationError::Other(err.to_string()))
-            }),
         ExportFormat::Raw => match rt.as_ref() {
             Term::Str(s) => writer
                 .write_all(s.as_bytes())
@@ -208,16 +771,141 @@ where
     }
 }
 
-pub fn to_string(format: ExportFormat, rt: &RichTerm) -> Result<String, SerializationError> {
+/// Serialize a term to JSON with the given layout.
+fn to_json_writer<W>(writer: W, style: ExportStyle, rt: &RichTerm) -> Result<(), SerializationError>
+where
+    W: io::Write,
+{
+    let result = match style {
+        ExportStyle::Pretty { indent } => {
+            let indent = " ".repeat(indent);
+            let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+            rt.serialize(&mut serde_json::Serializer::with_formatter(
+                writer, formatter,
+            ))
+        }
+        ExportStyle::Compact => serde_json::to_writer(writer, rt),
+    };
+
+    result.map_err(|err| SerializationError::Other(err.to_string()))
+}
+
+/// Convert a term to a TOML value. Fail if the term is not a table, which is the only kind of
+/// value that can be represented as a TOML document.
+fn to_toml(rt: &RichTerm) -> Result<toml::Value, SerializationError> {
+    match toml::Value::try_from(rt) {
+        Ok(value @ toml::Value::Table(_)) => Ok(value),
+        Ok(_) => Err(SerializationError::UnsupportedFormat(
+            ExportFormat::Toml,
+            rt.clone(),
+        )),
+        Err(err) => Err(SerializationError::Other(err.to_string())),
+    }
+}
+
+/// Serialize a term to a YAML document.
+///
+/// The document is emitted as `serde_yaml` would do, but floats are formatted by Rust instead of
+/// `serde_yaml`, whose formatting of floats is shortest only in most cases. This way, a number is
+/// written the same in YAML and in JSON, and the output doesn't depend on the version of the
+/// float formatting library.
+fn to_yaml_string(rt: &RichTerm) -> Result<String, SerializationError> {
+    let yaml = to_yaml(rt)?;
+    let mut output = String::new();
+    YamlEmitter::new(&mut output)
+        .dump(&yaml)
+        .map_err(|err| SerializationError::Other(format!("{:?}", err)))?;
+    output.push('\n');
+    Ok(output)
+}
+
+/// Convert a term to a YAML value, following the same rules as the [`Serialize`] instance of
+/// [`Term`].
+fn to_yaml(rt: &RichTerm) -> Result<Yaml, SerializationError> {
+    match rt.as_ref() {
+        Term::Null => Ok(Yaml::Null),
+        Term::Bool(b) => Ok(Yaml::Boolean(*b)),
+        Term::Num(n) => Ok(yaml_num(*n)),
+        Term::Str(s) => Ok(Yaml::String(s.clone())),
+        Term::Enum(id) => Ok(Yaml::String(id.to_string())),
+        Term::Array(elts) => elts
+            .iter()
+            .map(to_yaml)
+            .collect::<Result<_, _>>()
+            .map(Yaml::Array),
+        Term::Record(map, _) => {
+            let mut entries: Vec<(_, _)> = map.iter().collect();
+            entries.sort_by_key(|(id, _)| *id);
+
+            entries
+                .into_iter()
+                .map(|(id, t)| Ok((Yaml::String(id.to_string()), to_yaml(t)?)))
+                .collect::<Result<_, _>>()
+                .map(Yaml::Hash)
+        }
+        Term::MetaValue(MetaValue { value: Some(t), .. }) => to_yaml(t),
+        // This error should not happen if the input term is validated before serialization
+        Term::MetaValue(_) => Err(SerializationError::Other(String::from("empty metavalue"))),
+        t => Err(SerializationError::Other(format!(
+            "cannot serialize a term of type {}",
+            t.type_of().unwrap_or_else(|| String::from("<unknown>"))
+        ))),
+    }
+}
+
+/// Convert a number to a YAML value. Integers are written without a fractional part, as done by
+/// [`serialize_num`], and other numbers use the shortest representation which reads back to the
+/// same float.
+fn yaml_num(n: f64) -> Yaml {
+    if n.fract() == 0.0 {
+        if n >= (i64::MIN as f64) && n <= (i64::MAX as f64) {
+            return Yaml::Integer(n as i64);
+        } else if n >= 0.0 && n <= (u64::MAX as f64) {
+            return Yaml::Real((n as u64).to_string());
+        }
+    }
+
+    Yaml::Real(if n.is_nan() {
+        String::from(".nan")
+    } else if n.is_infinite() && n > 0.0 {
+        String::from(".inf")
+    } else if n.is_infinite() {
+        String::from("-.inf")
+    } else {
+        format!("{:?}", n)
+    })
+}
+
+/// Serialize a term to bytes, in any format, binary or not.
+pub fn to_vec(
+    format: ExportFormat,
+    style: ExportStyle,
+    rt: &RichTerm,
+) -> Result<Vec<u8>, SerializationError> {
+    let mut buffer = Vec::new();
+    write_term(&mut buffer, format, style, rt)?;
+    Ok(buffer)
+}
+
+/// Serialize a term to a string. Fail for a binary format, such as MessagePack: use [`to_vec`]
+/// instead.
+pub fn to_string(
+    format: ExportFormat,
+    style: ExportStyle,
+    rt: &RichTerm,
+) -> Result<String, SerializationError> {
     match format {
-        ExportFormat::Json => serde_json::to_string_pretty(&rt)
-            .map_err(|err| SerializationError::Other(err.to_string())),
-        ExportFormat::Yaml => {
-            serde_yaml::to_string(&rt).map_err(|err| SerializationError::Other(err.to_string()))
+        ExportFormat::MessagePack => Err(SerializationError::Other(format!(
+            "{} is a binary format, which can't be serialized to a string",
+            format
+        ))),
+        ExportFormat::Json => {
+            let mut buffer = Vec::new();
+            to_json_writer(&mut buffer, style, rt)?;
+            Ok(String::from_utf8(buffer).expect("serde_json always produces valid UTF-8"))
         }
-        ExportFormat::Toml => toml::Value::try_from(&rt)
-            .map(|v| format!("{}", v))
-            .map_err(|err| SerializationError::Other(err.to_string())),
+        ExportFormat::Yaml => to_yaml_string(rt),
+        ExportFormat::Toml => to_toml(rt).map(|v| format!("{}", v)),
         ExportFormat::Raw => match rt.as_ref() {
             Term::Str(s) => Ok(s.clone()),
             t => Err(SerializationError::Other(format!(
@@ -286,40 +974,31 @@ mod tests {
     macro_rules! assert_involutory {
         ( $term:expr ) => {
             let evaluated = mk_program($term).and_then(|mut p| p.eval_full()).unwrap();
-            let from_json: RichTerm =
-                serde_json::from_str(&serde_json::to_string(&evaluated).unwrap()).unwrap();
-            let from_yaml: RichTerm =
-                serde_yaml::from_str(&serde_yaml::to_string(&evaluated).unwrap()).unwrap();
-            let from_toml: RichTerm =
-                toml::from_str(&format!("{}", &toml::Value::try_from(&evaluated).unwrap()))
-                    .unwrap();
+            let assert_same = |deserialized: RichTerm| {
+                assert_eq!(
+                    $crate::eval::eval(
+                        mk_term::op2(BinaryOp::Eq(), deserialized, evaluated.clone()),
+                        &Environment::new(),
+                        &mut $crate::cache::resolvers::DummyResolver {}
+                    )
+                    .map(Term::from),
+                    Ok(Term::Bool(true))
+                );
+            };
 
-            assert_eq!(
-                $crate::eval::eval(
-                    mk_term::op2(BinaryOp::Eq(), from_json, evaluated.clone()),
-                    &Environment::new(),
-                    &mut $crate::cache::resolvers::DummyResolver {}
-                )
-                .map(Term::from),
-                Ok(Term::Bool(true))
-            );
-            assert_eq!(
-                $crate::eval::eval(
-                    mk_term::op2(BinaryOp::Eq(), from_yaml, evaluated.clone()),
-                    &Environment::new(),
-                    &mut $crate::cache::resolvers::DummyResolver {}
-                )
-                .map(Term::from),
-                Ok(Term::Bool(true))
-            );
-            assert_eq!(
-                $crate::eval::eval(
-                    mk_term::op2(BinaryOp::Eq(), from_toml, evaluated),
-                    &Environment::new(),
-                    &mut $crate::cache::resolvers::DummyResolver {}
-                )
-                .map(Term::from),
-                Ok(Term::Bool(true))
+            // Every layout of JSON must give back the same term.
+            for style in [
+                ExportStyle::Compact,
+                ExportStyle::default(),
+                ExportStyle::Pretty { indent: 0 },
+            ] {
+                let json = to_string(ExportFormat::Json, style, &evaluated).unwrap();
+                assert_same(serde_json::from_str(&json).unwrap());
+            }
+            assert_same(serde_yaml::from_str(&serde_yaml::to_string(&evaluated).unwrap()).unwrap());
+            assert_same(
+                toml::from_str(&format!("{}", &toml::Value::try_from(&evaluated).unwrap()))
+                    .unwrap(),
             );
         };
     }
@@ -397,6 +1076,187 @@ mod tests {
         );
         assert_pass_validation!(ExportFormat::Json, "{foo = null}", true);
         assert_pass_validation!(ExportFormat::Toml, "{foo = null}", false);
+        assert_pass_validation!(ExportFormat::Json, "[1, 2]", true);
+        assert_pass_validation!(ExportFormat::Toml, "[1, 2]", false);
+        assert_pass_validation!(ExportFormat::Toml, "1", false);
+        assert_pass_validation!(ExportFormat::Toml, "{foo = [1, 2]}", true);
+        assert_pass_validation!(ExportFormat::Toml, "{foo = [1, \"a\", [2]]}", true);
+        assert_pass_validation!(ExportFormat::Toml, "{foo = [{a = 1}, {b = 2}]}", true);
+        assert_pass_validation!(ExportFormat::Toml, "{foo = [{a = 1}, 2]}", false);
+        assert_pass_validation!(ExportFormat::Json, "{foo = [{a = 1}, 2]}", true);
+        assert_pass_validation!(ExportFormat::MessagePack, "{foo = [null, 2]}", true);
+        assert_pass_validation!(ExportFormat::MessagePack, "{foo = fun x => x}", false);
+    }
+
+    #[test]
+    fn toml_top_level() {
+        let rt: RichTerm = mk_program("[{a = 1}]")
+            .and_then(|mut p| p.eval_full())
+            .unwrap();
+
+        assert!(matches!(
+            to_string(ExportFormat::Toml, ExportStyle::default(), &rt),
+            Err(SerializationError::UnsupportedFormat(ExportFormat::Toml, _))
+        ));
+    }
+
+    #[test]
+    fn error_field_path() {
+        let error_path = |format, src: &str| {
+            let rt: RichTerm = mk_program(src).and_then(|mut p| p.eval_full()).unwrap();
+
+            match validate(format, &rt) {
+                Err(SerializationError::UnsupportedNull(_, _, path))
+                | Err(SerializationError::NonSerializable(_, path))
+                | Err(SerializationError::MixedArray(_, _, path)) => path.to_string(),
+                result => panic!("unexpected validation result {:?}", result),
+            }
+        };
+
+        assert_eq!(
+            error_path(ExportFormat::Toml, "{a = {b = [1, null]}}"),
+            "a.b[1]"
+        );
+        assert_eq!(
+            error_path(ExportFormat::Json, "{a = [{f = fun x => x}]}"),
+            "a[0].f"
+        );
+        assert_eq!(error_path(ExportFormat::Toml, "{a = [{b = 1}, 2]}"), "a");
+        assert_eq!(error_path(ExportFormat::Json, "fun x => x"), "");
+    }
+
+    #[test]
+    fn json_style() {
+        let rt: RichTerm = mk_program("{a = [1, {b = \"x\"}]}")
+            .and_then(|mut p| p.eval_full())
+            .unwrap();
+        let json = |style| to_string(ExportFormat::Json, style, &rt).unwrap();
+
+        assert_eq!(json(ExportStyle::Compact), r#"{"a":[1,{"b":"x"}]}"#);
+        assert_eq!(
+            json(ExportStyle::Pretty { indent: 1 }),
+            "{\n \"a\": [\n  1,\n  {\n   \"b\": \"x\"\n  }\n ]\n}"
+        );
+        assert_eq!(
+            json(ExportStyle::default()),
+            serde_json::to_string_pretty(&rt).unwrap()
+        );
+    }
+
+    #[test]
+    fn message_pack() {
+        let rt: RichTerm = mk_program("{b = 0.5, a = [1, null, \"x\"]}")
+            .and_then(|mut p| p.eval_full())
+            .unwrap();
+
+        let mut expected = vec![
+            0x82, 0xa1, b'a', 0x93, 0x01, 0xc0, 0xa1, b'x', 0xa1, b'b', 0xcb,
+        ];
+        expected.extend_from_slice(&0.5f64.to_be_bytes());
+        assert_eq!(
+            to_vec(ExportFormat::MessagePack, ExportStyle::default(), &rt).unwrap(),
+            expected
+        );
+
+        assert!(matches!(
+            to_string(ExportFormat::MessagePack, ExportStyle::default(), &rt),
+            Err(SerializationError::Other(_))
+        ));
+    }
+
+    #[test]
+    fn writer_error() {
+        let rt: RichTerm = mk_program("{a = \"some text\"}")
+            .and_then(|mut p| p.eval_full())
+            .unwrap();
+
+        for format in [
+            ExportFormat::Json,
+            ExportFormat::Yaml,
+            ExportFormat::Toml,
+            ExportFormat::MessagePack,
+        ] {
+            let mut buffer = [0u8; 4];
+            assert_matches::assert_matches!(
+                to_writer(&mut buffer[..], format, ExportStyle::default(), &rt),
+                Err(Error::IOError(IOError::Other(_)))
+            );
+        }
+
+        // Serialization errors are still reported as such.
+        let mut buffer = Vec::new();
+        assert_matches::assert_matches!(
+            to_writer(&mut buffer, ExportFormat::Raw, ExportStyle::default(), &rt),
+            Err(Error::SerializationError(SerializationError::Other(_)))
+        );
+    }
+
+    #[test]
+    fn duplicate_key_positions() {
+        fn duplicate_key(s: &str) -> Option<(&str, &str)> {
+            json_from_str(s, DuplicateKeys::Error)
+                .unwrap_err()
+                .duplicate_key
+                .map(|(first, second)| (&s[first], &s[second]))
+        }
+
+        assert_eq!(
+            duplicate_key(r#"{"a": 1, "b": 2, "a" : 3}"#),
+            Some((r#""a""#, r#""a""#))
+        );
+        // Keys with escaped quotes, and the duplicate key of an object nested in an array.
+        assert_eq!(
+            duplicate_key(r#"[{"x": {"k\"": 1}}, {"x": {"k": 1, "k\"": 2, "k\"": 3}}]"#),
+            Some((r#""k\"""#, r#""k\"""#))
+        );
+        let s = r#"{"x": [{"k": 1}, {"k": 1, "k": 2}]}"#;
+        let (first, second) = json_from_str(s, DuplicateKeys::Error)
+            .unwrap_err()
+            .duplicate_key
+            .unwrap();
+        assert_eq!(
+            (first.start, second.start),
+            (s.rfind(r#""k": 1"#).unwrap(), s.rfind(r#""k": 2"#).unwrap())
+        );
+        // Other errors aren't duplicate keys.
+        assert_eq!(duplicate_key(r#"{"a": 1, "b": }"#), None);
+    }
+
+    #[test]
+    fn sorted_keys() {
+        let rt: RichTerm =
+            mk_program("{b = 1, a = {d = [{f = 1, e = 2}], c = 3}, B = 2} & {\"_\" = 0, aa = 1}")
+                .and_then(|mut p| p.eval_full())
+                .unwrap();
+
+        assert_eq!(
+            to_string(ExportFormat::Json, ExportStyle::Compact, &rt).unwrap(),
+            r#"{"B":2,"_":0,"a":{"c":3,"d":[{"e":2,"f":1}]},"aa":1,"b":1}"#
+        );
+        assert_eq!(
+            to_string(ExportFormat::Yaml, ExportStyle::default(), &rt).unwrap(),
+            "---\nB: 2\n_: 0\na:\n  c: 3\n  d:\n    - e: 2\n      f: 1\naa: 1\nb: 1\n"
+        );
+        assert_eq!(
+            to_string(ExportFormat::Toml, ExportStyle::default(), &rt).unwrap(),
+            "B = 2\n_ = 0\naa = 1\nb = 1\n\n[a]\nc = 3\n\n[[a.d]]\ne = 2\nf = 1\n"
+        );
+    }
+
+    #[test]
+    fn same_floats_in_json_and_yaml() {
+        let rt: RichTerm = mk_program("[0.1 + 0.2, 1 / 3, 1000000 * 1000000 * 1000000 * 1000, -0]")
+            .and_then(|mut p| p.eval_full())
+            .unwrap();
+
+        assert_eq!(
+            to_string(ExportFormat::Json, ExportStyle::Compact, &rt).unwrap(),
+            "[0.30000000000000004,0.3333333333333333,1e21,0]"
+        );
+        assert_eq!(
+            to_string(ExportFormat::Yaml, ExportStyle::default(), &rt).unwrap(),
+            "---\n- 0.30000000000000004\n- 0.3333333333333333\n- 1e21\n- 0\n"
+        );
     }
 
     #[test]
@@ -22,6 +22,21 @@ pub fn modules() -> Vec<(&'static str, &'static str)> {
     vec![BUILTIN, CONTRACT, ARRAY, RECORD, STRING, NUM, FUNCTION]
 }
 
+/// The file names of the stdlib modules, in the same order as [`modules`]. A standard library
+/// loaded from a directory must provide all of them.
+pub const MODULE_FILES: &[&str] = &[
+    "builtin.ncl",
+    "contract.ncl",
+    "array.ncl",
+    "record.ncl",
+    "string.ncl",
+    "num.ncl",
+    "function.ncl",
+];
+
+/// The position of the module defining the builtin contracts in [`modules`].
+pub const CONTRACT_INDEX: usize = 1;
+
 /// Accessors to the builtin contracts.
 pub mod contract {
     use super::*;
@@ -18,7 +18,7 @@
 //! modular definitions of contracts, record and metadata all together.
 use crate::destruct::Destruct;
 use crate::identifier::Ident;
-use crate::label::Label;
+use crate::label::{field_path, Label};
 use crate::match_sharedterm;
 use crate::position::TermPos;
 use crate::types::{AbsType, Types, UnboundTypeVariableError};
@@ -230,6 +230,9 @@ pub struct MetaValue {
     pub types: Option<Contract>,
     pub contracts: Vec<Contract>,
     pub priority: MergePriority,
+    /// If the value is sensitive, such as a password or a token. Sensitive values are redacted
+    /// from error messages and from the output of the REPL. See [`crate::redact`].
+    pub sensitive: bool,
     pub value: Option<RichTerm>,
 }
 
@@ -240,6 +243,7 @@ impl From<RichTerm> for MetaValue {
             types: None,
             contracts: Vec::new(),
             priority: Default::default(),
+            sensitive: false,
             value: Some(rt),
         }
     }
@@ -258,6 +262,7 @@ impl MetaValue {
             types: None,
             contracts: Vec::new(),
             priority: Default::default(),
+            sensitive: false,
             value: None,
         }
     }
@@ -284,6 +289,7 @@ impl MetaValue {
             types,
             mut contracts,
             priority,
+            sensitive,
             value: _,
         } = outer;
 
@@ -304,6 +310,7 @@ impl MetaValue {
             types: types.or(inner.types),
             contracts,
             priority: std::cmp::min(priority, inner.priority),
+            sensitive: sensitive || inner.sensitive,
             value: inner.value,
         }
     }
@@ -503,11 +510,24 @@ impl Term {
 
     /// Return a deep string representation of a term, used for printing in the REPL
     pub fn deep_repr(&self) -> String {
+        self.deep_repr_with(&|_| None)
+    }
+
+    /// Same as [`deep_repr`](#method.deep_repr), but the subterms for which `leaf` returns a
+    /// representation are printed as such. It is used to hide sensitive values (see
+    /// [`crate::redact`]).
+    pub fn deep_repr_with(&self, leaf: &dyn Fn(&Term) -> Option<String>) -> String {
+        if let Some(repr) = leaf(self) {
+            return repr;
+        }
+
         match self {
             Term::Record(fields, _) | Term::RecRecord(fields, ..) => {
                 let fields_str: Vec<String> = fields
                     .iter()
-                    .map(|(ident, term)| format!("{} = {}", ident, term.as_ref().deep_repr()))
+                    .map(|(ident, term)| {
+                        format!("{} = {}", ident, term.as_ref().deep_repr_with(leaf))
+                    })
                     .collect();
 
                 let suffix = match self {
@@ -520,7 +540,7 @@ impl Term {
             Term::Array(elements) => {
                 let elements_str: Vec<String> = elements
                     .iter()
-                    .map(|term| term.as_ref().deep_repr())
+                    .map(|term| term.as_ref().deep_repr_with(leaf))
                     .collect();
                 format!("[ {} ]", elements_str.join(", "))
             }
@@ -667,6 +687,9 @@ pub enum UnaryOp {
     IsArray(),
     /// Test if a term is a record.
     IsRecord(),
+    /// Return the type of the weak head normal form of a term as an enum tag, such as `` `Num``,
+    /// `` `Enum`` or `` `Record``, following [`Term::type_of`].
+    TypeOf(),
 
     // Boolean AND and OR operator are encoded as unary operators so that they can be lazy in their
     // second argument.
@@ -676,6 +699,12 @@ pub enum UnaryOp {
     BoolOr(),
     /// Boolean NOT operator.
     BoolNot(),
+    /// Bitwise NOT of an integer.
+    ///
+    /// Bitwise operations act on the two's complement representation of integers, and require
+    /// their operands to be integers exactly representable as numbers, that is between `-(2^53 -
+    /// 1)` and `2^53 - 1`.
+    BitNot(),
 
     /// Raise a blame, which stops the execution and prints an error according to the label argument.
     Blame(),
@@ -693,6 +722,9 @@ pub enum UnaryOp {
     ///
     /// Static means that the field identifier is a statically known string inside the source.
     StaticAccess(Ident),
+    /// Access to a field of an element of an array, generated by the evaluation of
+    /// [`BinaryOp::ArrayPluck`]. The index of the element is only used to report a missing field.
+    PluckField(Ident, usize),
 
     /// Map a function on each element of an array.
     ArrayMap(),
@@ -708,6 +740,10 @@ pub enum UnaryOp {
 
     /// Get the polarity of a label.
     Pol(),
+    /// Get the type path of a label, as an array whose elements are either one of the enum tags
+    /// `` `Domain``, `` `Codomain`` and `` `Element`` (for the elements of an array), or the name
+    /// of a field as a string.
+    LabelPath(),
     /// Go to the domain in the type path of a label.
     ///
     /// If the argument is a label with a [type path](../label/enum.TyPath.html) representing some
@@ -750,6 +786,10 @@ pub enum UnaryOp {
     /// during the deep sequencing of a record. This is temporary and should be stored somewhere
     /// else ideally (like on the stack).
     DeepSeq(Option<crate::eval::callstack::StackElem>),
+    /// Force the evaluation of its argument and explicitly memoize the result in the thunks
+    /// waiting for it. A memoized thunk is never reverted, such that its content is not recomputed
+    /// when the record it belongs to is merged, even if it depends on overridden fields.
+    Memo(),
 
     /// Return the head of an array.
     ArrayHead(),
@@ -789,6 +829,10 @@ pub enum UnaryOp {
     ToStr(),
     /// Transform a string to a number.
     NumFromStr(),
+    /// Parse a number literal, as produced by [`NAryOp::StrFromNum()`]. Contrary to
+    /// [`UnaryOp::NumFromStr()`], locale-specific forms, infinities and `NaN` are rejected. See
+    /// [`parse_num`](../eval/operation/fn.parse_num.html).
+    StrToNum(),
     /// Transform a string to an enum.
     EnumFromStr(),
 }
@@ -808,6 +852,17 @@ pub enum BinaryOp {
     Modulo(),
     /// Raise a number to a power.
     Pow(),
+    /// Bitwise AND of integers. See [`UnaryOp::BitNot`] for the requirements on operands.
+    BitAnd(),
+    /// Bitwise OR of integers. See [`UnaryOp::BitNot`] for the requirements on operands.
+    BitOr(),
+    /// Bitwise XOR of integers. See [`UnaryOp::BitNot`] for the requirements on operands.
+    BitXor(),
+    /// Left shift of an integer. The shift amount must be a non-negative integer, and the result
+    /// must stay in the safe integer range.
+    ShiftLeft(),
+    /// Arithmetic right shift of an integer. The shift amount must be a non-negative integer.
+    ShiftRight(),
     /// Concatenation of strings.
     StrConcat(),
     /// Polymorphic equality.
@@ -836,8 +891,20 @@ pub enum BinaryOp {
     ///
     /// See `GoDom`.
     GoField(),
+    /// Go to the element of an array at a given index. Same as `GoArray` for the type path of a
+    /// label, but also record the index in its field path.
+    ///
+    /// See `GoDom`.
+    GoArrayElem(),
     /// Set the tag text of a blame label.
     Tag(),
+    /// Record the position of the first argument in a blame label, as the example the contract
+    /// being checked was derived from. The example is only evaluated to a weak head normal form.
+    WithExample(),
+    /// Record the fields of the first argument, a record, in a blame label, as the fields
+    /// responsible for the blame, such as mutually exclusive fields which are all set. The values
+    /// of the fields are not evaluated.
+    WithFields(),
     /// Extend a record with a dynamic field.
     ///
     /// Dynamic means that the field name may be an expression instead of a statically known
@@ -851,12 +918,38 @@ pub enum BinaryOp {
     DynAccess(),
     /// Test if a record has a specific field.
     HasField(),
+    /// The fields of the first record whose name isn't a field of the second one. The values of
+    /// the fields are not evaluated.
+    RecordDiff(),
+    /// The fields of the first record whose name is also a field of the second one. The values of
+    /// the fields are not evaluated.
+    RecordIntersect(),
+    /// Test if two records have the same field names. The values of the fields are not evaluated.
+    RecordKeysEqual(),
     /// Concatenate two arrays.
     ArrayConcat(),
     /// Access the n-th element of an array.
     ArrayElemAt(),
+    /// Split an array into consecutive chunks of a given size. The last chunk may be shorter.
+    ArrayChunks(),
+    /// Return all the contiguous windows of a given size of an array.
+    ArrayWindows(),
+    /// Access the same field, given as a string, of each element of an array. The elements are
+    /// accessed lazily (see [`UnaryOp::PluckField`]).
+    ArrayPluck(),
     /// The merge operator (see the [merge module](../merge/index.html)).
     Merge(),
+    /// The merge operator in override mode (see the [merge module](../merge/index.html)). The
+    /// first argument is the value overriding the second, lower priority one, of which only the
+    /// contracts are retained. This operator is generated by the evaluation of merge and is not
+    /// accessible from the surface syntax.
+    MergeOverride(),
+    /// The merge operator applied to a field of a value and the corresponding field of a record
+    /// contract it is checked against (see the [merge module](../merge/index.html)). The first
+    /// argument is the value side. The field path is the location of the field in the value the
+    /// record contract was applied to. This operator is generated by the evaluation of merge and
+    /// is not accessible from the surface syntax.
+    MergeContractField(field_path::Path),
 
     /// Hash a string.
     Hash(),
@@ -879,7 +972,9 @@ pub enum BinaryOp {
 impl BinaryOp {
     pub fn is_strict(&self) -> bool {
         match self {
-            BinaryOp::Merge() => false,
+            BinaryOp::Merge() | BinaryOp::MergeOverride() | BinaryOp::MergeContractField(_) => {
+                false
+            }
             _ => true,
         }
     }
@@ -897,10 +992,28 @@ pub enum NAryOp {
     StrReplaceRegex(),
     /// Return a substring of an original string.
     StrSubstr(),
+    /// Format a number as a string. The arguments are in order the number, the precision (a
+    /// number of digits after the decimal point, or `null` for the shortest representation which
+    /// reads back to the same number), the notation (one of the enum tags `Auto`, `Fixed` or
+    /// `Scientific`), and a boolean specifying if trailing zeros should be stripped. See
+    /// [`format_num`](../eval/operation/fn.format_num.html).
+    StrFromNum(),
+    /// Generate the array of numbers going from a start (included) to an end (excluded) with a
+    /// given step.
+    ArrayRangeStep(),
     /// The merge operator in contract mode (see the [merge module](../merge/index.html)). The
     /// arguments are in order the contract's label, the value to check, and the contract as a
     /// record.
     MergeContract(),
+    /// Same as [`BinaryOp::Deserialize()`], with an additional first argument specifying how to
+    /// handle duplicate keys in JSON objects: one of the enum tags `Error`, `First` or `Last` (see
+    /// [`DuplicateKeys`](../serialize/enum.DuplicateKeys.html)).
+    DeserializeWith(),
+    /// Same as [`BinaryOp::Serialize()`], with two additional first arguments specifying the
+    /// layout of JSON: whether the output is compact (a boolean) and the indentation of the
+    /// pretty-printed output otherwise (a non-negative integer), see
+    /// [`ExportStyle`](../serialize/enum.ExportStyle.html).
+    SerializeWith(),
 }
 
 impl NAryOp {
@@ -909,7 +1022,10 @@ impl NAryOp {
             NAryOp::StrReplace()
             | NAryOp::StrReplaceRegex()
             | NAryOp::StrSubstr()
-            | NAryOp::MergeContract() => 3,
+            | NAryOp::ArrayRangeStep()
+            | NAryOp::MergeContract()
+            | NAryOp::DeserializeWith() => 3,
+            NAryOp::StrFromNum() | NAryOp::SerializeWith() => 4,
         }
     }
 
@@ -924,7 +1040,11 @@ impl fmt::Display for NAryOp {
             NAryOp::StrReplace() => write!(f, "strReplace"),
             NAryOp::StrReplaceRegex() => write!(f, "strReplaceRegex"),
             NAryOp::StrSubstr() => write!(f, "substring"),
+            NAryOp::StrFromNum() => write!(f, "strFromNum"),
+            NAryOp::ArrayRangeStep() => write!(f, "rangeStep"),
             NAryOp::MergeContract() => write!(f, "mergeContract"),
+            NAryOp::DeserializeWith() => write!(f, "deserializeWith"),
+            NAryOp::SerializeWith() => write!(f, "serializeWith"),
         }
     }
 }
@@ -1178,6 +1298,7 @@ impl RichTerm {
                         types,
                         contracts,
                         priority: meta.priority,
+                        sensitive: meta.sensitive,
                         value,
                     };
                 RichTerm::new(
@@ -8,7 +8,13 @@
 //!
 //! It must be run before `share_normal_form` to avoid rechecking contracts each time the inner
 //! value is unwrapped.
+//!
+//! The labels of the contracts record the position of the annotated value and, for let bindings
+//! and record fields, the bound identifier, which are reported by blame errors. As the
+//! transformation is applied top-down, the labels of a meta-value are completed when visiting its
+//! parent binding, before the contract applications are generated.
 use crate::{
+    identifier::Ident,
     match_sharedterm, mk_app,
     term::{make as mk_term, BinaryOp, RichTerm, Term},
     types::UnboundTypeVariableError,
@@ -21,6 +27,30 @@ pub fn transform_one(rt: RichTerm) -> Result<RichTerm, UnboundTypeVariableError>
     let pos = rt.pos;
     let result = match_sharedterm! {rt.term,
         with {
+            Term::Let(id, t1, t2, btype) => {
+                let t1 = with_binding(&id, t1);
+                RichTerm::new(Term::Let(id, t1, t2, btype), pos)
+            },
+            Term::Record(fields, attrs) => {
+                let fields = fields
+                    .into_iter()
+                    .map(|(id, t)| {
+                        let t = with_binding(&id, t);
+                        (id, t)
+                    })
+                    .collect();
+                RichTerm::new(Term::Record(fields, attrs), pos)
+            },
+            Term::RecRecord(fields, dyn_fields, attrs, deps) => {
+                let fields = fields
+                    .into_iter()
+                    .map(|(id, t)| {
+                        let t = with_binding(&id, t);
+                        (id, t)
+                    })
+                    .collect();
+                RichTerm::new(Term::RecRecord(fields, dyn_fields, attrs, deps), pos)
+            },
             Term::MetaValue(meta) if meta.value.is_some() => {
                 let mut meta = meta;
                 let pos_inh = pos.into_inherited();
@@ -47,3 +77,25 @@ pub fn transform_one(rt: RichTerm) -> Result<RichTerm, UnboundTypeVariableError>
     };
     Ok(result)
 }
+
+/// If `rt` is a meta-value with an inner value, record the position of this value and the
+/// identifier `id` it is bound to in the labels of its contracts. Otherwise, return the term
+/// unchanged.
+fn with_binding(id: &Ident, rt: RichTerm) -> RichTerm {
+    let pos = rt.pos;
+    match_sharedterm! {rt.term,
+        with {
+            Term::MetaValue(meta) if meta.value.is_some() => {
+                let mut meta = meta;
+                let value_pos = meta.value.as_ref().unwrap().pos;
+
+                for ctr in meta.types.iter_mut().chain(meta.contracts.iter_mut()) {
+                    ctr.label.value_pos = value_pos;
+                    ctr.label.binding = Some(id.clone());
+                }
+
+                RichTerm::new(Term::MetaValue(meta), pos)
+            }
+        } else rt
+    }
+}
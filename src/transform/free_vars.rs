@@ -14,12 +14,30 @@ use std::collections::{HashMap, HashSet};
 
 /// Apply the full free var transformation on a term.
 pub fn transform(rt: &mut RichTerm) {
-    collect_free_vars(rt, &mut HashSet::new())
+    collect_free_vars(rt, &mut HashSet::new(), &mut |_, _| ())
+}
+
+/// A callback receiving a let-bound variable and the free variables of the body of the let.
+type OnLet<'a> = dyn FnMut(&Ident, &HashSet<Ident>) + 'a;
+
+/// Return the free variables of a term.
+pub fn free_vars(rt: &RichTerm) -> HashSet<Ident> {
+    free_vars_with(rt, &mut |_, _| ())
+}
+
+/// Return the free variables of a term, and call `on_let` on each of its let bindings with the
+/// free variables of the body of the let. The free variables of all the bodies are thus computed
+/// in one pass.
+pub fn free_vars_with(rt: &RichTerm, on_let: &mut OnLet) -> HashSet<Ident> {
+    let mut free_vars = HashSet::new();
+    collect_free_vars(&mut rt.clone(), &mut free_vars, on_let);
+    free_vars
 }
 
 /// Collect the free variables of a term inside the provided hashset. Doing so, fill the recursive
-/// record dependencies data accordingly.
-fn collect_free_vars(rt: &mut RichTerm, free_vars: &mut HashSet<Ident>) {
+/// record dependencies data accordingly. Call `on_let` on each let binding with the free variables of
+/// its body.
+fn collect_free_vars(rt: &mut RichTerm, free_vars: &mut HashSet<Ident>, on_let: &mut OnLet) {
     match SharedTerm::make_mut(&mut rt.term) {
         Term::Var(id) => {
             free_vars.insert(id.clone());
@@ -37,7 +55,7 @@ fn collect_free_vars(rt: &mut RichTerm, free_vars: &mut HashSet<Ident>) {
         Term::Fun(id, t) => {
             let mut fresh = HashSet::new();
 
-            collect_free_vars(t, &mut fresh);
+            collect_free_vars(t, &mut fresh, on_let);
             fresh.remove(id);
 
             free_vars.extend(fresh);
@@ -45,7 +63,7 @@ fn collect_free_vars(rt: &mut RichTerm, free_vars: &mut HashSet<Ident>) {
         Term::FunPattern(id, dest_pat, body) => {
             let mut fresh = HashSet::new();
 
-            collect_free_vars(body, &mut fresh);
+            collect_free_vars(body, &mut fresh, on_let);
             bind_pattern(dest_pat, &mut fresh);
             if let Some(id) = id {
                 fresh.remove(id);
@@ -56,8 +74,9 @@ fn collect_free_vars(rt: &mut RichTerm, free_vars: &mut HashSet<Ident>) {
         Term::Let(id, t1, t2, _) => {
             let mut fresh = HashSet::new();
 
-            collect_free_vars(t1, free_vars);
-            collect_free_vars(t2, &mut fresh);
+            collect_free_vars(t1, free_vars, on_let);
+            collect_free_vars(t2, &mut fresh, on_let);
+            on_let(id, &fresh);
             fresh.remove(id);
 
             free_vars.extend(fresh);
@@ -65,8 +84,8 @@ fn collect_free_vars(rt: &mut RichTerm, free_vars: &mut HashSet<Ident>) {
         Term::LetPattern(id, dest_pat, t1, t2) => {
             let mut fresh = HashSet::new();
 
-            collect_free_vars(t1, free_vars);
-            collect_free_vars(t2, &mut fresh);
+            collect_free_vars(t1, free_vars, on_let);
+            collect_free_vars(t2, &mut fresh, on_let);
             bind_pattern(dest_pat, &mut fresh);
             if let Some(id) = id {
                 fresh.remove(id);
@@ -75,29 +94,29 @@ fn collect_free_vars(rt: &mut RichTerm, free_vars: &mut HashSet<Ident>) {
             free_vars.extend(fresh);
         }
         Term::App(t1, t2) => {
-            collect_free_vars(t1, free_vars);
-            collect_free_vars(t2, free_vars);
+            collect_free_vars(t1, free_vars, on_let);
+            collect_free_vars(t2, free_vars, on_let);
         }
         Term::Switch(t, cases, default) => {
-            collect_free_vars(t, free_vars);
+            collect_free_vars(t, free_vars, on_let);
             for t in cases.values_mut().chain(default.iter_mut()) {
-                collect_free_vars(t, free_vars);
+                collect_free_vars(t, free_vars, on_let);
             }
         }
-        Term::Op1(_, t) => collect_free_vars(t, free_vars),
+        Term::Op1(_, t) => collect_free_vars(t, free_vars, on_let),
         Term::Op2(_, t1, t2) => {
-            collect_free_vars(t1, free_vars);
-            collect_free_vars(t2, free_vars);
+            collect_free_vars(t1, free_vars, on_let);
+            collect_free_vars(t2, free_vars, on_let);
         }
         Term::OpN(_, ts) => {
             for t in ts {
-                collect_free_vars(t, free_vars);
+                collect_free_vars(t, free_vars, on_let);
             }
         }
-        Term::Wrapped(_, t) => collect_free_vars(t, free_vars),
+        Term::Wrapped(_, t) => collect_free_vars(t, free_vars, on_let),
         Term::Record(map, _) => {
             for t in map.values_mut() {
-                collect_free_vars(t, free_vars);
+                collect_free_vars(t, free_vars, on_let);
             }
         }
         Term::RecRecord(map, dyn_fields, _, deps) => {
@@ -111,7 +130,7 @@ fn collect_free_vars(rt: &mut RichTerm, free_vars: &mut HashSet<Ident>) {
             for (id, t) in map.iter_mut() {
                 fresh.clear();
 
-                collect_free_vars(t, &mut fresh);
+                collect_free_vars(t, &mut fresh, on_let);
                 new_deps
                     .stat_fields
                     .insert(id.clone(), &fresh & &rec_fields);
@@ -125,8 +144,8 @@ fn collect_free_vars(rt: &mut RichTerm, free_vars: &mut HashSet<Ident>) {
                 // one can't write `{foo = "hey", "%{foo}" = 5}`. Hence, we add their free
                 // variables directly in the final set without taking them into account for
                 // recursive dependencies.
-                collect_free_vars(t1, free_vars);
-                collect_free_vars(t2, &mut fresh);
+                collect_free_vars(t1, free_vars, on_let);
+                collect_free_vars(t2, &mut fresh, on_let);
                 new_deps.dyn_fields.push(&fresh & &rec_fields);
 
                 free_vars.extend(&fresh - &rec_fields);
@@ -140,24 +159,24 @@ fn collect_free_vars(rt: &mut RichTerm, free_vars: &mut HashSet<Ident>) {
         }
         Term::Array(ts) => {
             for t in ts {
-                collect_free_vars(t, free_vars);
+                collect_free_vars(t, free_vars, on_let);
             }
         }
         Term::StrChunks(chunks) => {
             for chunk in chunks {
                 match chunk {
-                    StrChunk::Expr(t, _) => collect_free_vars(t, free_vars),
+                    StrChunk::Expr(t, _) => collect_free_vars(t, free_vars, on_let),
                     _ => (),
                 }
             }
         }
         Term::MetaValue(meta) => {
             for ctr in meta.contracts.iter_mut().chain(meta.types.iter_mut()) {
-                collect_type_free_vars(&mut ctr.types, free_vars)
+                collect_type_free_vars(&mut ctr.types, free_vars, on_let)
             }
 
             if let Some(ref mut t) = meta.value {
-                collect_free_vars(t, free_vars);
+                collect_free_vars(t, free_vars, on_let);
             }
         }
     }
@@ -165,7 +184,7 @@ fn collect_free_vars(rt: &mut RichTerm, free_vars: &mut HashSet<Ident>) {
 
 /// Collect the free variables of the potential terms inside a type (custom contracts) and insert
 /// them the provided hashset. Doing so, fill the recursive records dependencies data accordingly.
-fn collect_type_free_vars(ty: &mut Types, set: &mut HashSet<Ident>) {
+fn collect_type_free_vars(ty: &mut Types, set: &mut HashSet<Ident>, on_let: &mut OnLet) {
     match &mut ty.0 {
         AbsType::Dyn()
         | AbsType::Num()
@@ -178,18 +197,20 @@ fn collect_type_free_vars(ty: &mut Types, set: &mut HashSet<Ident>) {
         | AbsType::Enum(ty)
         | AbsType::StaticRecord(ty)
         | AbsType::DynRecord(ty)
-        | AbsType::Array(ty) => collect_type_free_vars(ty.as_mut(), set),
+        | AbsType::Array(ty) => collect_type_free_vars(ty.as_mut(), set, on_let),
         AbsType::Arrow(ty1, ty2) => {
-            collect_type_free_vars(ty1.as_mut(), set);
-            collect_type_free_vars(ty2.as_mut(), set);
+            collect_type_free_vars(ty1.as_mut(), set, on_let);
+            collect_type_free_vars(ty2.as_mut(), set, on_let);
         }
         AbsType::RowExtend(_, ty_opt, tail) => {
             if let Some(ref mut ty) = ty_opt {
-                collect_type_free_vars(ty, set);
+                collect_type_free_vars(ty, set, on_let);
             }
-            collect_type_free_vars(tail.as_mut(), set);
+            collect_type_free_vars(tail.as_mut(), set, on_let);
         }
-        AbsType::Flat(ref mut rt) => collect_free_vars(rt, set),
+        AbsType::Flat(ref mut rt) => collect_free_vars(rt, set, on_let),
+        // The arguments of an alias are part of its expansion.
+        AbsType::Alias(_, _, ty) => collect_type_free_vars(ty.as_mut(), set, on_let),
     }
 }
 
@@ -3,6 +3,7 @@
 //! identifier directly.
 use super::ImportResolver;
 use crate::error::ImportError;
+use crate::position::TermPos;
 use crate::term::{RichTerm, Term, TraverseOrder};
 use codespan::FileId;
 use std::path::PathBuf;
@@ -12,20 +13,21 @@ use std::path::PathBuf;
 /// currently being processed, if any.
 struct ImportsResolutionState<'a, R> {
     resolver: &'a mut R,
-    stack: &'a mut Vec<FileId>,
+    stack: &'a mut Vec<(FileId, TermPos)>,
     parent: Option<PathBuf>,
 }
 
 /// Perform imports resolution.
 ///
-/// All resolved imports are stacked during the process. Once the term has been traversed,
-/// the elements of this stack are returned. The caller is responsible
-/// to recursively resolve imports of this stack and or to perform
-/// transformations on it.
+/// All resolved imports are stacked during the process, together with the position of the import
+/// expression. Once the term has been traversed, the elements of this stack are returned. The
+/// caller is responsible to recursively resolve imports of this stack and or to perform
+/// transformations on it (see
+/// [`Cache::resolve_pending_imports`](../../cache/struct.Cache.html#method.resolve_pending_imports)).
 pub fn resolve_imports<R>(
     rt: RichTerm,
     resolver: &mut R,
-) -> Result<(RichTerm, Vec<FileId>), ImportError>
+) -> Result<(RichTerm, Vec<(FileId, TermPos)>), ImportError>
 where
     R: ImportResolver,
 {
@@ -50,7 +52,7 @@ where
             let rt = transform_one(rt, state.resolver, &state.parent)?;
 
             if let Term::ResolvedImport(file_id) = rt.term.as_ref() {
-                state.stack.push(*file_id);
+                state.stack.push((*file_id, rt.pos));
             }
             Ok(rt)
         },
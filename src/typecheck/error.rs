@@ -110,6 +110,45 @@ pub enum UnifError {
 }
 
 impl UnifError {
+    /// Replace a type by another one in the types directly reported by this error, but not in the
+    /// types of its sub-errors. Used to report an alias rather than its expansion.
+    pub fn replace_type(self, from: &TypeWrapper, to: &TypeWrapper) -> Self {
+        let replace = |ty: TypeWrapper| if ty == *from { to.clone() } else { ty };
+
+        match self {
+            UnifError::TypeMismatch(ty1, ty2) => {
+                UnifError::TypeMismatch(replace(ty1), replace(ty2))
+            }
+            UnifError::RowMismatch(id, ty1, ty2, err) => {
+                UnifError::RowMismatch(id, replace(ty1), replace(ty2), err)
+            }
+            UnifError::MissingRow(id, ty1, ty2) => {
+                UnifError::MissingRow(id, replace(ty1), replace(ty2))
+            }
+            UnifError::MissingDynTail(ty1, ty2) => {
+                UnifError::MissingDynTail(replace(ty1), replace(ty2))
+            }
+            UnifError::ExtraRow(id, ty1, ty2) => {
+                UnifError::ExtraRow(id, replace(ty1), replace(ty2))
+            }
+            UnifError::ExtraDynTail(ty1, ty2) => {
+                UnifError::ExtraDynTail(replace(ty1), replace(ty2))
+            }
+            UnifError::RowConflict(id, ty_opt, ty1, ty2) => {
+                UnifError::RowConflict(id, ty_opt, replace(ty1), replace(ty2))
+            }
+            UnifError::WithConst(i, ty) => UnifError::WithConst(i, replace(ty)),
+            UnifError::IllformedType(ty) => UnifError::IllformedType(replace(ty)),
+            UnifError::DomainMismatch(ty1, ty2, err) => {
+                UnifError::DomainMismatch(replace(ty1), replace(ty2), err)
+            }
+            UnifError::CodomainMismatch(ty1, ty2, err) => {
+                UnifError::CodomainMismatch(replace(ty1), replace(ty2), err)
+            }
+            err => err,
+        }
+    }
+
     /// Convert a unification error to a typechecking error.
     ///
     /// Wrapper that calls [`to_typecheck_err_`](./fn.to_typecheck_err_.html) with an empty [name
@@ -187,17 +226,21 @@ impl UnifError {
                 reporting::to_type(state.table, state.names, names, tyw1),
                 reporting::to_type(state.table, state.names, names, tyw2),
                 pos_opt,
+                TermPos::None,
             ),
             UnifError::MissingDynTail(tyw1, tyw2) => TypecheckError::MissingDynTail(
                 reporting::to_type(state.table, state.names, names, tyw1),
                 reporting::to_type(state.table, state.names, names, tyw2),
                 pos_opt,
             ),
+            // Row identifiers keep the position of their definition: when the extra row comes
+            // from a record literal, this is the position of the offending field.
             UnifError::ExtraRow(id, tyw1, tyw2) => TypecheckError::ExtraRow(
-                id,
+                id.clone(),
                 reporting::to_type(state.table, state.names, names, tyw1),
                 reporting::to_type(state.table, state.names, names, tyw2),
                 pos_opt,
+                id.pos,
             ),
             UnifError::ExtraDynTail(tyw1, tyw2) => TypecheckError::ExtraDynTail(
                 reporting::to_type(state.table, state.names, names, tyw1),
@@ -42,8 +42,9 @@
 //! In non-strict mode, all let-bound expressions are given type `Dyn`, unless annotated.
 use crate::cache::ImportResolver;
 use crate::environment::Environment as GenericEnvironment;
-use crate::error::TypecheckError;
+use crate::error::{suggest, TypecheckError};
 use crate::identifier::Ident;
+use crate::position::TermPos;
 use crate::term::{Contract, MetaValue, RichTerm, StrChunk, Term};
 use crate::types::{AbsType, Types};
 use crate::{mk_tyw_arrow, mk_tyw_enum, mk_tyw_enum_row, mk_tyw_record, mk_tyw_row};
@@ -157,6 +158,14 @@ impl<'a> Envs<'a> {
         self.local.get(ident).or_else(|| self.global.get(ident))
     }
 
+    /// The error of a reference to an identifier which is bound neither in the local environment
+    /// nor in the global one.
+    fn unbound_identifier(&self, ident: &Ident, pos: TermPos) -> TypecheckError {
+        let bound = self.local.iter_elems().chain(self.global.iter_elems());
+        let closest = suggest::closest_ident(ident, bound.map(|(id, _)| id));
+        TypecheckError::UnboundIdentifier(ident.clone(), pos, closest)
+    }
+
     /// Wrapper to insert a new binding in the local environment.
     pub fn insert(&mut self, ident: Ident, tyw: TypeWrapper) {
         self.local.insert(ident, tyw);
@@ -237,6 +246,34 @@ pub fn type_check_in_env(
     t: &RichTerm,
     global: &Environment,
     resolver: &dyn ImportResolver,
+) -> Result<Types, TypecheckError> {
+    check_in_env(t, global, resolver, false)
+}
+
+/// Infer the type of a term using the given global typing environment, by typechecking it as if it
+/// were statically typed. Contrary to [`type_check_in_env`](./fn.type_check_in_env.html), which
+/// doesn't infer anything for dynamically typed code, this gives the actual type of the term, such
+/// as `Num -> Num` for `fun x => x + 1`. The parts of the type which are left undetermined, as for
+/// polymorphic functions, are generalized: the type of `fun x => x` is `forall a. a -> a`.
+///
+/// Fail if the term isn't well-typed as a statically typed term. See
+/// [`apparent_shape`](./fn.apparent_shape.html) for an approximation of its type in this case.
+pub fn infer_in_env(
+    t: &RichTerm,
+    global: &Environment,
+    resolver: &dyn ImportResolver,
+) -> Result<Types, TypecheckError> {
+    check_in_env(t, global, resolver, true)
+}
+
+/// Typecheck a term against a fresh unification variable in a global typing environment, in
+/// strict mode or not, and return the resulting type. In strict mode, the unification variables
+/// left in the type are generalized (see [`to_generalized_type`]).
+fn check_in_env(
+    t: &RichTerm,
+    global: &Environment,
+    resolver: &dyn ImportResolver,
+    strict: bool,
 ) -> Result<Types, TypecheckError> {
     let mut state = State {
         resolver,
@@ -250,12 +287,16 @@ pub fn type_check_in_env(
         Envs::from_global(global),
         &mut Linearization::new(()),
         StubHost::<()>::new(),
-        false,
+        strict,
         t,
         ty.clone(),
     )?;
 
-    Ok(to_type(state.table, ty))
+    if strict {
+        Ok(to_generalized_type(state.table, ty))
+    } else {
+        Ok(to_type(state.table, ty))
+    }
 }
 
 /// Typecheck a term against a specific type.
@@ -479,7 +520,7 @@ fn type_check_<L: Linearizer>(
         Term::Var(x) => {
             let x_ty = envs
                 .get(x)
-                .ok_or_else(|| TypecheckError::UnboundIdentifier(x.clone(), *pos))?;
+                .ok_or_else(|| envs.unbound_identifier(x, *pos))?;
 
             let instantiated = instantiate_foralls(state, x_ty, ForallInst::Ptr);
             unify(state, strict, ty, instantiated)
@@ -535,7 +576,7 @@ fn type_check_<L: Linearizer>(
                 ty.clone()
             };
 
-            if let TypeWrapper::Concrete(AbsType::DynRecord(rec_ty)) = root_ty {
+            if let TypeWrapper::Concrete(AbsType::DynRecord(rec_ty)) = root_ty.unalias() {
                 // Checking for a dynamic record
                 stat_map
                     .iter()
@@ -547,7 +588,7 @@ fn type_check_<L: Linearizer>(
                             linearizer.scope(),
                             strict,
                             t,
-                            (*rec_ty).clone(),
+                            (**rec_ty).clone(),
                         )
                     })
             } else {
@@ -578,7 +619,7 @@ fn type_check_<L: Linearizer>(
                 )?;
 
                 unify(state, strict, ty, mk_tyw_record!(; row))
-                    .map_err(|err| err.into_typecheck_err(state, rt.pos))
+                    .map_err(|err| locate_missing_row(err.into_typecheck_err(state, rt.pos), rt))
             }
         }
         Term::Op1(op, t) => {
@@ -642,6 +683,7 @@ fn type_check_<L: Linearizer>(
             value: Some(t),
             ..
         }) => {
+            check_contract_vars(&envs, ty2)?;
             let tyw2 = TypeWrapper::from(ty2.clone());
 
             let instantiated = instantiate_foralls(state, tyw2.clone(), ForallInst::Constant);
@@ -708,6 +750,44 @@ fn type_check_<L: Linearizer>(
     }
 }
 
+/// Attach the position of the closest enclosing record literal to a `MissingRow` error raised
+/// when typechecking the record literal `rt`.
+///
+/// Missing rows of nested records are reported wrapped in a succession of `RowMismatch` errors.
+/// We follow the corresponding path down the fields of `rt` for as long as they are record
+/// literals themselves, such that the error points to the innermost record missing the row.
+fn locate_missing_row(err: TypecheckError, rt: &RichTerm) -> TypecheckError {
+    fn as_record(rt: &RichTerm) -> Option<&HashMap<Ident, RichTerm>> {
+        match rt.as_ref() {
+            Term::Record(stat_map, _) | Term::RecRecord(stat_map, ..) => Some(stat_map),
+            Term::MetaValue(MetaValue {
+                value: Some(value), ..
+            }) => as_record(value),
+            _ => None,
+        }
+    }
+
+    fn locate(err: TypecheckError, rt: &RichTerm, enclosing: &RichTerm) -> TypecheckError {
+        match err {
+            TypecheckError::MissingRow(id, expd, actual, span_opt, TermPos::None) => {
+                TypecheckError::MissingRow(id, expd, actual, span_opt, enclosing.pos)
+            }
+            TypecheckError::RowMismatch(id, expd, actual, err, span_opt) => {
+                let next = as_record(rt).and_then(|stat_map| stat_map.get(&id));
+                let err = match next {
+                    Some(field) if as_record(field).is_some() => locate(*err, field, field),
+                    _ => locate(*err, rt, enclosing),
+                };
+
+                TypecheckError::RowMismatch(id, expd, actual, Box::new(err), span_opt)
+            }
+            err => err,
+        }
+    }
+
+    locate(err, rt, rt)
+}
+
 /// Determine the type of a let-bound expression, or more generally of any binding (e.g. fields)
 /// that may be stored in a typing environment at some point.
 ///
@@ -834,6 +914,54 @@ pub fn apparent_type(
     }
 }
 
+/// Determine the apparent type of a term as [`apparent_type`](fn.apparent_type.html), but also
+/// following the shape of literals: the type of a record literal is the record type of the
+/// apparent types of its fields, and the type of an array literal is an array of the apparent type
+/// of its elements, if they all have the same one.
+pub fn apparent_shape(
+    t: &Term,
+    envs: Option<&Envs>,
+    resolver: Option<&dyn ImportResolver>,
+) -> Types {
+    match t {
+        Term::Record(rec, ..) | Term::RecRecord(rec, ..) => {
+            let mut fields: Vec<_> = rec.iter().collect();
+            // Rows are built from the last field, such that fields are displayed in order.
+            fields.sort_by(|(id1, _), (id2, _)| id2.cmp(id1));
+
+            let rows = fields
+                .into_iter()
+                .fold(AbsType::RowEmpty(), |rows, (id, rt)| {
+                    AbsType::RowExtend(
+                        id.clone(),
+                        Some(Box::new(apparent_shape(rt.as_ref(), envs, resolver))),
+                        Box::new(Types(rows)),
+                    )
+                });
+
+            Types(AbsType::StaticRecord(Box::new(Types(rows))))
+        }
+        Term::Array(elts) => {
+            let mut tys = elts
+                .iter()
+                .map(|rt| apparent_shape(rt.as_ref(), envs, resolver));
+            let elt_ty = match tys.next() {
+                Some(first) if tys.all(|ty| ty == first) => first,
+                _ => Types(AbsType::Dyn()),
+            };
+
+            Types(AbsType::Array(Box::new(elt_ty)))
+        }
+        Term::MetaValue(MetaValue {
+            value: Some(rt),
+            types: None,
+            contracts,
+            ..
+        }) if contracts.is_empty() => apparent_shape(rt.as_ref(), envs, resolver),
+        t => apparent_type(t, envs, resolver).into(),
+    }
+}
+
 /// Infer the type of a non annotated record by gathering the apparent type of the fields. It's
 /// currently used essentially to type the stdlib.
 pub fn infer_type(t: &Term) -> TypeWrapper {
@@ -887,6 +1015,15 @@ impl std::convert::TryInto<Types> for TypeWrapper {
 }
 
 impl TypeWrapper {
+    /// Return the expansion of a type alias, recursively, or the type itself if it is not an
+    /// alias.
+    pub fn unalias(&self) -> &TypeWrapper {
+        match self {
+            TypeWrapper::Concrete(AbsType::Alias(_, _, ty)) => ty.unalias(),
+            ty => ty,
+        }
+    }
+
     /// Substitute all the occurrences of a type variable for a typewrapper.
     pub fn subst(self, id: Ident, to: TypeWrapper) -> TypeWrapper {
         use self::TypeWrapper::*;
@@ -929,6 +1066,13 @@ impl TypeWrapper {
                 Concrete(AbsType::DynRecord(Box::new(def_ty.subst(id, to))))
             }
             Concrete(AbsType::Array(ty)) => Concrete(AbsType::Array(Box::new(ty.subst(id, to)))),
+            Concrete(AbsType::Alias(name, args, ty)) => Concrete(AbsType::Alias(
+                name,
+                args.into_iter()
+                    .map(|arg| Box::new(arg.subst(id.clone(), to.clone())))
+                    .collect(),
+                Box::new(ty.subst(id, to)),
+            )),
             Constant(x) => Constant(x),
             Ptr(x) => Ptr(x),
         }
@@ -1033,7 +1177,25 @@ pub fn unify_(
         t2 = state.table.root(pt2);
     }
 
-    // t1 and t2 are roots of the type
+    // An alias is unified through its expansion, but is kept in the types reported in case of
+    // failure, such that errors mention the name of the alias rather than its definition.
+    match (t1, t2) {
+        (TypeWrapper::Concrete(AbsType::Alias(id, args, ty)), t2) => {
+            let expansion = (*ty).clone();
+            let alias = TypeWrapper::Concrete(AbsType::Alias(id, args, ty));
+            unify_(state, expansion.clone(), t2).map_err(|err| err.replace_type(&expansion, &alias))
+        }
+        (t1, TypeWrapper::Concrete(AbsType::Alias(id, args, ty))) => {
+            let expansion = (*ty).clone();
+            let alias = TypeWrapper::Concrete(AbsType::Alias(id, args, ty));
+            unify_(state, t1, expansion.clone()).map_err(|err| err.replace_type(&expansion, &alias))
+        }
+        (t1, t2) => unify_roots(state, t1, t2),
+    }
+}
+
+/// Unify two types which are roots in the unification table and are not aliases.
+fn unify_roots(state: &mut State, t1: TypeWrapper, t2: TypeWrapper) -> Result<(), UnifError> {
     match (t1, t2) {
         (TypeWrapper::Concrete(s1), TypeWrapper::Concrete(s2)) => match (s1, s2) {
             (AbsType::Dyn(), AbsType::Dyn()) => Ok(()),
@@ -1227,6 +1389,49 @@ fn to_type(table: &UnifTable, ty: TypeWrapper) -> Types {
     }
 }
 
+/// Same as [`to_type`], but the unification variables left unassigned are turned into type
+/// variables, named `a`, `b`, and so on in order of appearance, which are quantified at the top of
+/// the type. Type constants are still converted to `Dyn`.
+fn to_generalized_type(table: &UnifTable, ty: TypeWrapper) -> Types {
+    fn var_name(index: usize) -> Ident {
+        if index < 26 {
+            Ident::from(((b'a' + index as u8) as char).to_string())
+        } else {
+            Ident::from(format!("a{}", index))
+        }
+    }
+
+    fn generalize(table: &UnifTable, ty: TypeWrapper, vars: &mut Vec<usize>) -> Types {
+        match ty {
+            TypeWrapper::Ptr(p) => match table.root(p) {
+                t @ TypeWrapper::Concrete(_) => generalize(table, t, vars),
+                TypeWrapper::Ptr(root) => {
+                    let index = match vars.iter().position(|var| *var == root) {
+                        Some(index) => index,
+                        None => {
+                            vars.push(root);
+                            vars.len() - 1
+                        }
+                    };
+                    Types(AbsType::Var(var_name(index)))
+                }
+                TypeWrapper::Constant(_) => Types(AbsType::Dyn()),
+            },
+            TypeWrapper::Constant(_) => Types(AbsType::Dyn()),
+            TypeWrapper::Concrete(t) => {
+                Types(t.map(|btyp| Box::new(generalize(table, *btyp, vars))))
+            }
+        }
+    }
+
+    let mut vars = Vec::new();
+    let result = generalize(table, ty, &mut vars);
+
+    (0..vars.len()).rev().fold(result, |ty, index| {
+        Types(AbsType::Forall(var_name(index), Box::new(ty)))
+    })
+}
+
 /// Type of the parameter controlling instantiation of foralls.
 ///
 /// See [`instantiate_foralls`](./fn.instantiate_foralls.html).
@@ -1255,6 +1460,10 @@ fn instantiate_foralls(state: &mut State, mut ty: TypeWrapper, inst: ForallInst)
         ty = state.table.root(p);
     }
 
+    if let forall @ TypeWrapper::Concrete(AbsType::Forall(..)) = ty.unalias() {
+        ty = forall.clone();
+    }
+
     while let TypeWrapper::Concrete(AbsType::Forall(id, forall_ty)) = ty {
         let fresh_id = state.table.fresh_var();
         let var = match inst {
@@ -1272,6 +1481,41 @@ fn instantiate_foralls(state: &mut State, mut ty: TypeWrapper, inst: ForallInst)
     ty
 }
 
+/// Check that the custom contracts of a type annotation are bound. An unknown name in type
+/// position, such as a misspelled type alias, is parsed as a custom contract, and would otherwise
+/// only be reported as a type mismatch.
+fn check_contract_vars(envs: &Envs, ty: &Types) -> Result<(), TypecheckError> {
+    match &ty.0 {
+        AbsType::Dyn()
+        | AbsType::Num()
+        | AbsType::Bool()
+        | AbsType::Str()
+        | AbsType::Sym()
+        | AbsType::Var(_)
+        | AbsType::RowEmpty() => Ok(()),
+        AbsType::Flat(rt) => match rt.as_ref() {
+            Term::Var(id) if envs.get(id).is_none() => Err(envs.unbound_identifier(id, rt.pos)),
+            _ => Ok(()),
+        },
+        AbsType::Forall(_, ty)
+        | AbsType::Enum(ty)
+        | AbsType::StaticRecord(ty)
+        | AbsType::DynRecord(ty)
+        | AbsType::Array(ty)
+        | AbsType::Alias(_, _, ty) => check_contract_vars(envs, ty),
+        AbsType::Arrow(ty1, ty2) => {
+            check_contract_vars(envs, ty1)?;
+            check_contract_vars(envs, ty2)
+        }
+        AbsType::RowExtend(_, ty_opt, tail) => {
+            if let Some(ty) = ty_opt {
+                check_contract_vars(envs, ty)?;
+            }
+            check_contract_vars(envs, tail)
+        }
+    }
+}
+
 /// The unification table.
 ///
 /// Map each unification variable to either another type variable or a concrete type it has been
@@ -1414,6 +1658,7 @@ fn constrain_var(state: &mut State, tyw: &TypeWrapper, p: usize) {
                 AbsType::Enum(row) => constrain_var_(state, constr, row, p),
                 AbsType::StaticRecord(row) => constrain_var_(state, constr, row, p),
                 AbsType::DynRecord(tyw) => constrain_var_(state, constr, tyw, p),
+                AbsType::Alias(_, _, tyw) => constrain_var_(state, constr, tyw, p),
             },
             TypeWrapper::Constant(_) => (),
         }
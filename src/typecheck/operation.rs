@@ -39,6 +39,8 @@ pub fn get_uop_type(
         ),
         // Bool -> Bool
         UnaryOp::BoolNot() => (mk_typewrapper::bool(), mk_typewrapper::bool()),
+        // Num -> Num
+        UnaryOp::BitNot() => (mk_typewrapper::num(), mk_typewrapper::num()),
         // forall a. Dyn -> a
         UnaryOp::Blame() => {
             let res = TypeWrapper::Ptr(state.table.fresh_var());
@@ -47,6 +49,13 @@ pub fn get_uop_type(
         }
         // Dyn -> Bool
         UnaryOp::Pol() => (mk_typewrapper::dynamic(), mk_typewrapper::bool()),
+        // Dyn -> Dyn
+        UnaryOp::TypeOf() => (mk_typewrapper::dynamic(), mk_typewrapper::dynamic()),
+        // Dyn -> Array Dyn
+        UnaryOp::LabelPath() => (
+            mk_typewrapper::dynamic(),
+            mk_typewrapper::array(mk_typewrapper::dynamic()),
+        ),
         // forall rows. < | rows> -> <id | rows>
         UnaryOp::Embed(id) => {
             let row = TypeWrapper::Ptr(state.table.fresh_var());
@@ -72,6 +81,12 @@ pub fn get_uop_type(
 
             (mk_tyw_record!((id.clone(), res.clone()); row), res)
         }
+        // forall a. { _: a } -> a
+        UnaryOp::PluckField(..) => {
+            let res = TypeWrapper::Ptr(state.table.fresh_var());
+
+            (mk_typewrapper::dyn_record(res.clone()), res)
+        }
         // forall a b. Array a -> (a -> b) -> Array b
         UnaryOp::ArrayMap() => {
             let a = TypeWrapper::Ptr(state.table.fresh_var());
@@ -114,6 +129,11 @@ pub fn get_uop_type(
 
             (fst, mk_tyw_arrow!(snd.clone(), snd))
         }
+        // forall a. a -> a
+        UnaryOp::Memo() => {
+            let ty = TypeWrapper::Ptr(state.table.fresh_var());
+            (ty.clone(), ty)
+        }
         // forall a. Array a -> a
         UnaryOp::ArrayHead() => {
             let ty_elt = TypeWrapper::Ptr(state.table.fresh_var());
@@ -167,6 +187,8 @@ pub fn get_uop_type(
         UnaryOp::ToStr() => (mk_typewrapper::dynamic(), mk_typewrapper::num()),
         // Str -> Num
         UnaryOp::NumFromStr() => (mk_typewrapper::str(), mk_typewrapper::num()),
+        // Str -> Num
+        UnaryOp::StrToNum() => (mk_typewrapper::str(), mk_typewrapper::num()),
         // Str -> < | Dyn>
         UnaryOp::EnumFromStr() => (
             mk_typewrapper::str(),
@@ -237,6 +259,18 @@ pub fn get_bop_type(
             mk_typewrapper::dynamic(),
             mk_typewrapper::dynamic(),
         ),
+        // Num -> Dyn -> Dyn
+        BinaryOp::GoArrayElem() => (
+            mk_typewrapper::num(),
+            mk_typewrapper::dynamic(),
+            mk_typewrapper::dynamic(),
+        ),
+        // Dyn -> Dyn -> Dyn
+        BinaryOp::WithExample() | BinaryOp::WithFields() => (
+            mk_typewrapper::dynamic(),
+            mk_typewrapper::dynamic(),
+            mk_typewrapper::dynamic(),
+        ),
         // forall a. Str -> { _ : a} -> a
         BinaryOp::DynAccess() => {
             let res = TypeWrapper::Ptr(state.table.fresh_var());
@@ -274,6 +308,18 @@ pub fn get_bop_type(
                 mk_typewrapper::bool(),
             )
         }
+        // forall a b. {_: a} -> {_: b} -> {_: a}
+        BinaryOp::RecordDiff() | BinaryOp::RecordIntersect() => {
+            let ty_fst = mk_typewrapper::dyn_record(TypeWrapper::Ptr(state.table.fresh_var()));
+            let ty_snd = mk_typewrapper::dyn_record(TypeWrapper::Ptr(state.table.fresh_var()));
+            (ty_fst.clone(), ty_snd, ty_fst)
+        }
+        // forall a b. {_: a} -> {_: b} -> Bool
+        BinaryOp::RecordKeysEqual() => (
+            mk_typewrapper::dyn_record(TypeWrapper::Ptr(state.table.fresh_var())),
+            mk_typewrapper::dyn_record(TypeWrapper::Ptr(state.table.fresh_var())),
+            mk_typewrapper::bool(),
+        ),
         // forall a. Array a -> Array a -> Array a
         BinaryOp::ArrayConcat() => {
             let ty_elt = TypeWrapper::Ptr(state.table.fresh_var());
@@ -289,12 +335,22 @@ pub fn get_bop_type(
                 ty_elt,
             )
         }
+        // forall a. Array a -> Num -> Array (Array a)
+        BinaryOp::ArrayChunks() | BinaryOp::ArrayWindows() => {
+            let ty_array = mk_typewrapper::array(TypeWrapper::Ptr(state.table.fresh_var()));
+            (
+                ty_array.clone(),
+                mk_typewrapper::num(),
+                mk_typewrapper::array(ty_array),
+            )
+        }
         // Dyn -> Dyn -> Dyn
-        BinaryOp::Merge() => (
+        BinaryOp::Merge() | BinaryOp::MergeOverride() => (
             mk_typewrapper::dynamic(),
             mk_typewrapper::dynamic(),
             mk_typewrapper::dynamic(),
         ),
+        BinaryOp::MergeContractField(_) => panic!("cannot typecheck MergeContractField()"),
         // <Md5, Sha1, Sha256, Sha512> -> Str -> Str
         BinaryOp::Hash() => (
             mk_tyw_enum!(
@@ -323,7 +379,12 @@ pub fn get_bop_type(
             mk_typewrapper::dynamic(),
         ),
         // Num -> Num -> Num
-        BinaryOp::Pow() => (
+        BinaryOp::Pow()
+        | BinaryOp::BitAnd()
+        | BinaryOp::BitOr()
+        | BinaryOp::BitXor()
+        | BinaryOp::ShiftLeft()
+        | BinaryOp::ShiftRight() => (
             mk_typewrapper::num(),
             mk_typewrapper::num(),
             mk_typewrapper::num(),
@@ -356,6 +417,16 @@ pub fn get_bop_type(
             mk_typewrapper::str(),
             mk_typewrapper::array(AbsType::Str()),
         ),
+        // forall a. Str -> Array { _: a } -> Array a
+        BinaryOp::ArrayPluck() => {
+            let res = TypeWrapper::Ptr(state.table.fresh_var());
+
+            (
+                mk_typewrapper::str(),
+                mk_typewrapper::array(mk_typewrapper::dyn_record(res.clone())),
+                mk_typewrapper::array(res),
+            )
+        }
     })
 }
 
@@ -382,6 +453,44 @@ pub fn get_nop_type(
             ],
             mk_typewrapper::str(),
         ),
+        // Num -> Dyn -> <Auto, Fixed, Scientific> -> Bool -> Str
+        NAryOp::StrFromNum() => (
+            vec![
+                mk_typewrapper::num(),
+                mk_typewrapper::dynamic(),
+                mk_tyw_enum!("Auto", "Fixed", "Scientific", mk_typewrapper::row_empty()),
+                mk_typewrapper::bool(),
+            ],
+            mk_typewrapper::str(),
+        ),
+        // Num -> Num -> Num -> Array Num
+        NAryOp::ArrayRangeStep() => (
+            vec![
+                mk_typewrapper::num(),
+                mk_typewrapper::num(),
+                mk_typewrapper::num(),
+            ],
+            mk_typewrapper::array(mk_typewrapper::num()),
+        ),
+        // <Error, First, Last> -> <Json, Yaml, Toml> -> Str -> Dyn
+        NAryOp::DeserializeWith() => (
+            vec![
+                mk_tyw_enum!("Error", "First", "Last", mk_typewrapper::row_empty()),
+                mk_tyw_enum!("Json", "Yaml", "Toml", mk_typewrapper::row_empty()),
+                mk_typewrapper::str(),
+            ],
+            mk_typewrapper::dynamic(),
+        ),
+        // Bool -> Num -> <Json, Yaml, Toml> -> Dyn -> Str
+        NAryOp::SerializeWith() => (
+            vec![
+                mk_typewrapper::bool(),
+                mk_typewrapper::num(),
+                mk_tyw_enum!("Json", "Yaml", "Toml", mk_typewrapper::row_empty()),
+                mk_typewrapper::dynamic(),
+            ],
+            mk_typewrapper::str(),
+        ),
         // This should not happen, as Switch() is only produced during evaluation.
         NAryOp::MergeContract() => panic!("cannot typecheck MergeContract()"),
     })
@@ -14,6 +14,13 @@
 //! - `forall a. type`: polymorphic type
 //! - `#customContract`: an opaque type created from an user-defined contract
 //!
+//! # Type aliases
+//!
+//! A type alias, declared by `let type Name params = type in body`, is expanded as soon as it is
+//! referred to in the body of its declaration. The expansion is wrapped in an
+//! [`AbsType::Alias`] node, which stands for the expanded type everywhere but in error messages,
+//! where the type is shown as the alias rather than as its expansion.
+//!
 //! # Record types
 //!
 //! The type systems feature structural records with row-polymorphism.
@@ -56,7 +63,7 @@ use crate::identifier::Ident;
 use crate::term::make as mk_term;
 use crate::term::{RichTerm, Term};
 use crate::{mk_app, mk_fun, mk_switch};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 
 /// A Nickel type.
@@ -100,6 +107,12 @@ pub enum AbsType<Ty> {
     DynRecord(Ty /*, Ty  Row */),
     /// A parametrized array.
     Array(Ty),
+    /// A type alias applied to its arguments, together with its expansion.
+    Alias(
+        Ident,   /* the name of the alias */
+        Vec<Ty>, /* the arguments of the alias */
+        Ty,      /* the expansion of the alias */
+    ),
 }
 
 impl<Ty> AbsType<Ty> {
@@ -130,6 +143,10 @@ impl<Ty> AbsType<Ty> {
             AbsType::StaticRecord(t) => Ok(AbsType::StaticRecord(f(t)?)),
             AbsType::DynRecord(t) => Ok(AbsType::DynRecord(f(t)?)),
             AbsType::Array(t) => Ok(AbsType::Array(f(t)?)),
+            AbsType::Alias(id, args, t) => {
+                let args = args.into_iter().map(&mut f).collect::<Result<_, _>>()?;
+                Ok(AbsType::Alias(id, args, f(t)?))
+            }
         }
     }
 
@@ -314,6 +331,7 @@ impl Types {
             AbsType::DynRecord(ref ty) => {
                 mk_app!(contract::dyn_record(), ty.subcontract(h, pol, sy)?)
             }
+            AbsType::Alias(_, _, ref ty) => ty.subcontract(h, pol, sy)?,
         };
 
         Ok(ctr)
@@ -364,9 +382,61 @@ impl Types {
         match &self.0 {
             Dyn() | Num() | Bool() | Str() | Var(_) => true,
             Flat(rt) if matches!(*rt.term, Term::Var(_)) => true,
+            Alias(_, args, _) => args.is_empty(),
             _ => false,
         }
     }
+
+    /// Substitute type variables for types, as when expanding a type alias. The substitution is
+    /// capture-avoiding: a variable bound by a `forall` which occurs free in one of the
+    /// substituted types is renamed beforehand.
+    pub fn subst(&self, sigma: &HashMap<Ident, Types>) -> Types {
+        match &self.0 {
+            AbsType::Var(id) => sigma.get(id).cloned().unwrap_or_else(|| self.clone()),
+            AbsType::Forall(id, body) => {
+                let mut sigma = sigma.clone();
+                sigma.remove(id);
+
+                let mut free_vars = HashSet::new();
+                for ty in sigma.values() {
+                    ty.free_vars(&mut free_vars);
+                }
+
+                if free_vars.contains(id) {
+                    body.free_vars(&mut free_vars);
+
+                    let mut fresh = id.clone();
+                    while free_vars.contains(&fresh) {
+                        fresh.label.push('\'');
+                    }
+
+                    sigma.insert(id.clone(), Types(AbsType::Var(fresh.clone())));
+                    Types(AbsType::Forall(fresh, Box::new(body.subst(&sigma))))
+                } else {
+                    Types(AbsType::Forall(id.clone(), Box::new(body.subst(&sigma))))
+                }
+            }
+            ty => Types(ty.clone().map(|ty| Box::new(ty.subst(sigma)))),
+        }
+    }
+
+    /// Collect the type variables occurring free in a type.
+    fn free_vars(&self, set: &mut HashSet<Ident>) {
+        match &self.0 {
+            AbsType::Var(id) => {
+                set.insert(id.clone());
+            }
+            AbsType::Forall(id, body) => {
+                let mut body_vars = HashSet::new();
+                body.free_vars(&mut body_vars);
+                body_vars.remove(id);
+                set.extend(body_vars);
+            }
+            ty => {
+                ty.clone().map(|ty| ty.free_vars(set));
+            }
+        }
+    }
 }
 
 impl fmt::Display for Types {
@@ -419,24 +489,40 @@ impl fmt::Display for Types {
                 AbsType::Arrow(_, _) => write!(f, "({}) -> {}", dom, codom),
                 _ => write!(f, "{} -> {}", dom, codom),
             },
+            AbsType::Alias(id, args, _) => {
+                write!(f, "{}", id)?;
+
+                for arg in args.iter() {
+                    if arg.fmt_is_atom() {
+                        write!(f, " {}", arg)?;
+                    } else {
+                        write!(f, " ({})", arg)?;
+                    }
+                }
+
+                Ok(())
+            }
         }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::Types;
+    use super::{AbsType, Types};
+    use crate::identifier::Ident;
     use crate::parser::grammar::TermParser;
     use crate::parser::lexer::Lexer;
     use crate::term::Term;
     use codespan::Files;
+    use std::collections::HashMap;
 
     /// Parse a type represented as a string.
     fn parse_type(s: &str) -> Types {
         use crate::term::MetaValue;
 
-        // Wrap the type in a contract to have it accepted by the parser.
-        let wrapper = format!("null | {}", s);
+        // Wrap the type in a contract to have it accepted by the parser. The aliases used by the
+        // tests are declared in front of it.
+        let wrapper = format!("let type Pair a b = {{fst: a, snd: b}} in null | {}", s);
         println!("{}", wrapper);
         let id = Files::new().add("<test>", wrapper.clone());
 
@@ -486,4 +572,33 @@ mod test {
         assert_format_eq("Array (Num -> Num)");
         assert_format_eq("Array (Array (Array Dyn) -> Num)");
     }
+
+    #[test]
+    fn aliases() {
+        let ty = parse_type("Pair Num (Array Str)");
+        assert_eq!(format!("{}", ty), "Pair Num (Array Str)");
+
+        match ty.0 {
+            AbsType::Alias(id, args, expansion) => {
+                assert_eq!(id.label, "Pair");
+                assert_eq!(args.len(), 2);
+                assert_eq!(format!("{}", expansion), "{fst: Num, snd: Array Str}");
+            }
+            _ => panic!("types::test::aliases(): expected an alias"),
+        }
+    }
+
+    #[test]
+    fn subst_avoids_capture() {
+        // Peel the outer `forall` to get `a` as a free type variable.
+        let ty = match parse_type("forall a b. {fst: a, snd: b}").0 {
+            AbsType::Forall(_, ty) => *ty,
+            _ => panic!("types::test::subst_avoids_capture(): expected a forall"),
+        };
+        let sigma = HashMap::from([(Ident::from("a"), Types(AbsType::Var(Ident::from("b"))))]);
+        assert_eq!(
+            format!("{}", ty.subst(&sigma)),
+            "forall b'. {fst: b, snd: b'}"
+        );
+    }
 }
@@ -0,0 +1,30 @@
+#![cfg(feature = "ast-dump")]
+
+use nickel_lang::ast_dump::{self, AstFormat};
+use nickel_lang::cache::Cache;
+use serde_json::Value;
+
+/// Check that the tooling representation of the AST doesn't change inadvertently. See
+/// `tests/ast_dump/snapshot.ncl` for how to regenerate the snapshot after an intended change.
+#[test]
+fn json_snapshot() {
+    let mut cache = Cache::new();
+    let file_id = cache.add_string(
+        "snapshot.ncl",
+        String::from(include_str!("ast_dump/snapshot.ncl")),
+    );
+    cache.parse(file_id).unwrap();
+
+    let mut output = Vec::new();
+    ast_dump::to_writer(
+        &mut output,
+        AstFormat::Json,
+        cache.files(),
+        cache.get_ref(file_id).unwrap(),
+    )
+    .unwrap();
+
+    let actual: Value = serde_json::from_slice(&output).unwrap();
+    let expected: Value = serde_json::from_str(include_str!("ast_dump/snapshot.json")).unwrap();
+    assert_eq!(actual, expected);
+}
@@ -12,33 +12,223 @@ fn div_by_zero() {
 }
 
 #[test]
-fn comparisons() {
+fn bitwise_ops() {
     assert_matches!(
-        eval("1 < 2 < 3"),
-        Err(Error::EvalError(EvalError::TypeError(..)))
+        eval("num.bit.and 1.5 1"),
+        Err(Error::EvalError(EvalError::Other(..)))
     );
     assert_matches!(
-        eval("1 < 2 > 3"),
-        Err(Error::EvalError(EvalError::TypeError(..)))
+        eval("num.bit.not 9007199254740992"),
+        Err(Error::EvalError(EvalError::Other(..)))
     );
     assert_matches!(
-        eval("\"a\" < 2"),
-        Err(Error::EvalError(EvalError::TypeError(..)))
+        eval("num.bit.shift_left 1 53"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("num.bit.shift_right 1 (-1)"),
+        Err(Error::EvalError(EvalError::Other(..)))
     );
     assert_matches!(
-        eval("true <= []"),
+        eval("%bit_or% \"a\" 1"),
         Err(Error::EvalError(EvalError::TypeError(..)))
     );
+}
+
+#[test]
+fn number_conversions() {
+    assert_matches!(
+        eval("string.to_number \"1,5\""),
+        Err(Error::EvalError(EvalError::Other(msg, _))) if msg.contains("at offset 1")
+    );
+    assert_matches!(
+        eval("string.to_number \"1e400\""),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
     assert_matches!(
-        eval("\"a\" > \"b\""),
+        eval("string.to_number \"inf\""),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("string.from_number {precision = 1.5} 1"),
+        Err(Error::EvalError(EvalError::BlameError(..)))
+    );
+    assert_matches!(
+        eval("string.from_number {notation = `Engineering} 1"),
+        Err(Error::EvalError(EvalError::BlameError(..)))
+    );
+    assert_matches!(
+        eval("%str_from_num% 1 null `Fixed 0"),
         Err(Error::EvalError(EvalError::TypeError(..)))
     );
+}
+
+#[test]
+fn serialize_options() {
+    use nickel_lang::error::SerializationError;
+    use nickel_lang::position::TermPos;
+
+    // Report the start of the position of the value which broke a contract.
+    let blame_start = |s: &str| match eval(s) {
+        Err(Error::EvalError(EvalError::BlameError(label, _))) => {
+            match label.arg_thunk.unwrap().get_owned().body.pos {
+                TermPos::Original(span) | TermPos::Inherited(span) => Some(span.start.to_usize()),
+                TermPos::File(_) | TermPos::None => None,
+            }
+        }
+        result => panic!("expected a blame error, got {:?}", result),
+    };
+
+    assert_eq!(
+        blame_start("builtin.serialize_with {indent = 1.5} `Json {}"),
+        Some(33)
+    );
+    assert_eq!(
+        blame_start("builtin.serialize_with {indent = 17} `Json {}"),
+        Some(33)
+    );
+    assert_eq!(
+        blame_start("builtin.serialize_with {compact = 1} `Json {}"),
+        Some(34)
+    );
+    // An unknown option is reported on the whole options record.
+    assert_eq!(
+        blame_start("builtin.serialize_with {pretty = true} `Json {}"),
+        Some(23)
+    );
+    assert_eq!(blame_start("builtin.serialize_with {} `Xml {}"), Some(26));
+    assert_eq!(blame_start("builtin.serialize `Xml {}"), Some(18));
+
     assert_matches!(
-        eval("\"a\" >= \"b\""),
+        eval("builtin.serialize_with {} `Json {a = fun x => x}"),
+        Err(Error::EvalError(EvalError::SerializationError(
+            SerializationError::NonSerializable(rt, _)
+        )))
+            if matches!(rt.pos, TermPos::Original(span) if span.start.to_usize() == 37)
+    );
+    assert_matches!(
+        eval("%serialize_with% false 1.5 `Json {}"),
+        Err(Error::EvalError(EvalError::Other(_, TermPos::Original(span))))
+            if span.start.to_usize() == 23
+    );
+    assert_matches!(
+        eval("%serialize_with% 1 2 `Json {}"),
         Err(Error::EvalError(EvalError::TypeError(..)))
     );
 }
 
+/// Evaluate a term which must fail with a type error of a binary operator, and return the
+/// operator together with the types of both operands.
+fn binary_type_error(s: &str) -> Vec<String> {
+    match eval(s) {
+        Err(Error::EvalError(EvalError::BinaryTypeError {
+            op,
+            expected,
+            fst,
+            snd,
+            ..
+        })) => vec![
+            op,
+            expected,
+            fst.as_ref().type_of().unwrap(),
+            snd.as_ref().type_of().unwrap(),
+        ],
+        result => panic!("expected a binary type error, got {:?}", result),
+    }
+}
+
+#[test]
+fn comparisons() {
+    assert_eq!(
+        binary_type_error("1 < 2 < 3"),
+        vec!["<", "Num", "Bool", "Num"]
+    );
+    assert_eq!(
+        binary_type_error("1 < 2 > 3"),
+        vec![">", "Num", "Bool", "Num"]
+    );
+    assert_eq!(
+        binary_type_error("\"a\" < 2"),
+        vec!["<", "Num", "Str", "Num"]
+    );
+    assert_eq!(
+        binary_type_error("true <= []"),
+        vec!["<=", "Num", "Bool", "Array"]
+    );
+    assert_eq!(
+        binary_type_error("\"a\" > \"b\""),
+        vec![">", "Num", "Str", "Str"]
+    );
+    assert_eq!(
+        binary_type_error("\"a\" >= \"b\""),
+        vec![">=", "Num", "Str", "Str"]
+    );
+}
+
+#[test]
+fn arithmetic_and_concatenation() {
+    assert_eq!(
+        binary_type_error("let x = \"a\" in x + 1"),
+        vec!["+", "Num", "Str", "Num"]
+    );
+    assert_eq!(
+        binary_type_error("1 + null"),
+        vec!["+", "Num", "Num", "Null"]
+    );
+    assert_eq!(
+        binary_type_error("[] - 1"),
+        vec!["-", "Num", "Array", "Num"]
+    );
+    assert_eq!(
+        binary_type_error("\"version: \" ++ 2"),
+        vec!["++", "Str", "Str", "Num"]
+    );
+    assert_eq!(
+        binary_type_error("1 ++ \"a\""),
+        vec!["++", "Str", "Num", "Str"]
+    );
+    assert_eq!(
+        binary_type_error("[1, 2] @ 3"),
+        vec!["@", "Array", "Array", "Num"]
+    );
+    assert_eq!(
+        binary_type_error("{} @ [1]"),
+        vec!["@", "Array", "Record", "Array"]
+    );
+}
+
+#[test]
+fn binary_type_error_report() {
+    use nickel_lang::program::Program;
+    use std::io::Cursor;
+
+    let source = "let version = \"0.1.1\" in\nversion + 1";
+    let mut prog = Program::new_from_source(Cursor::new(source), "<test>").unwrap();
+    let err = prog.eval_full().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "type error: `+` expected two Num operands, got a Str and a Num"
+    );
+
+    let mut output = Vec::new();
+    prog.report_to_writer(err, &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(
+        output.contains("error[E053]: type error in `+`"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("`+` expects two Num operands"),
+        "{}",
+        output
+    );
+    assert!(output.contains("this is a Str"), "{}", output);
+    assert!(output.contains("this is a Num"), "{}", output);
+    // The operands are reported where they appear in the operation.
+    assert!(!output.contains("let version"), "{}", output);
+}
+
 #[test]
 fn boolean_ops() {
     assert_matches!(
@@ -62,3 +252,55 @@ fn string_chunks() {
         Err(Error::EvalError(EvalError::TypeError(..)))
     );
 }
+
+#[test]
+fn error_display() {
+    use std::error::Error as _;
+
+    let err = eval("let f | Num -> Num = fun x => \"a\" in f 1").unwrap_err();
+    assert_eq!(err.to_string(), "contract broken by a function");
+    // The top-level error is transparent: the wrapped error, whose message is already the one of
+    // the top-level error, isn't reported again as its source.
+    assert!(err.source().is_none());
+
+    let err = eval("[1] | {a : Num}").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "contract broken by a value: expected `Record`, got `Array`"
+    );
+
+    let err = eval("{ a = 1 b = 2 }").unwrap_err();
+    assert_eq!(err.to_string(), "unexpected token");
+
+    let err = eval("(1 : Str)").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "incompatible types: expected `Str`, inferred `Num`"
+    );
+
+    // The top-level error can be boxed, as done by `?` in host programs.
+    let boxed: Box<dyn std::error::Error> = Box::new(eval("x").unwrap_err());
+    assert_eq!(boxed.to_string(), "unbound identifier `x`");
+    assert!(boxed.source().is_none());
+}
+
+#[test]
+fn non_exhaustive_match() {
+    assert_matches!(
+        eval("switch { `http => 80, `https => 443 } `ftp"),
+        Err(Error::EvalError(EvalError::NonExhaustiveMatch { handled, .. }))
+            if handled.iter().map(|id| id.to_string()).collect::<Vec<_>>() == ["http", "https"]
+    );
+    assert_matches!(
+        eval("switch { `http => 80 } \"http\""),
+        Err(Error::EvalError(EvalError::NonExhaustiveMatch { .. }))
+    );
+    // A default case matches any value, even one which is not an enum tag.
+    assert_matches!(eval("switch { `http => 80, _ => 0 } \"http\""), Ok(_));
+
+    let err = eval("switch { `http => 80 } `ftp").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "non-exhaustive match: tag `ftp` is not handled"
+    );
+}
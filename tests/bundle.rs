@@ -0,0 +1,65 @@
+use nickel_lang::error::{Error, ImportError};
+use nickel_lang::program::Program;
+use nickel_lang::serialize::{self, ExportFormat, ExportStyle};
+use std::path::PathBuf;
+
+fn fixture(file: &str) -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push(format!("tests/bundle/{}", file));
+    path
+}
+
+fn bundle(file: &str) -> String {
+    Program::new_from_file(fixture(file))
+        .unwrap()
+        .bundle()
+        .unwrap()
+}
+
+fn export(program: &mut Program) -> String {
+    let rt = program.eval_full().unwrap();
+    serialize::to_string(ExportFormat::Json, ExportStyle::default(), &rt).unwrap()
+}
+
+#[test]
+fn same_export() {
+    let source = bundle("main.ncl");
+    assert!(!source.contains("import"));
+
+    let expected = export(&mut Program::new_from_file(fixture("main.ncl")).unwrap());
+    let mut program = Program::new_from_source(source.as_bytes(), "bundle.ncl").unwrap();
+    assert_eq!(export(&mut program), expected);
+}
+
+#[test]
+fn source_map() {
+    let source = bundle("broken.ncl");
+    let mut program = Program::new_from_source(source.as_bytes(), "bundle.ncl").unwrap();
+    let error = program.eval_full().unwrap_err();
+    assert!(matches!(error, Error::EvalError(_)));
+
+    let mut output = Vec::new();
+    program.report_to_writer(error, &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    // Lines and columns are the ones of the original files.
+    assert!(output.contains("lib.ncl:5:12"), "{}", output);
+    assert!(output.contains("broken.ncl:2:"), "{}", output);
+    assert!(!output.contains("bundle.ncl"), "{}", output);
+}
+
+#[test]
+fn cycle() {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push("tests/imports/cycle.ncl");
+    let mut program = Program::new_from_file(path).unwrap();
+
+    let mut err = match program.bundle() {
+        Err(Error::ImportError(err)) => err,
+        result => panic!("expected an import error, got {:?}", result),
+    };
+
+    while let ImportError::Transitive(inner, _) = err {
+        err = *inner;
+    }
+    assert!(matches!(err, ImportError::ImportCycle(..)));
+}
@@ -0,0 +1,156 @@
+use nickel_lang::check::{self, CheckOptions, CheckStatus, CheckSummary, SummaryFormat};
+use std::path::PathBuf;
+
+fn fixture(pattern: &str) -> String {
+    format!("{}/tests/check/{}", env!("CARGO_MANIFEST_DIR"), pattern)
+}
+
+fn statuses(summary: &CheckSummary) -> Vec<(String, CheckStatus)> {
+    let root = PathBuf::from(fixture(""));
+    summary
+        .results
+        .iter()
+        .map(|res| {
+            let file = res.file.strip_prefix(&root).unwrap();
+            (file.to_string_lossy().into_owned(), res.status)
+        })
+        .collect()
+}
+
+#[test]
+fn expand_globs() {
+    let root = PathBuf::from(fixture(""));
+    let files: Vec<PathBuf> = check::expand_globs(vec![fixture("**/pass*.ncl"), fixture("*.ncl")])
+        .into_iter()
+        .map(|file| file.strip_prefix(&root).unwrap().to_path_buf())
+        .collect();
+
+    assert_eq!(
+        files,
+        vec![
+            PathBuf::from("pass.ncl"),
+            PathBuf::from("nested/pass_nested.ncl"),
+            PathBuf::from("eval_fail.ncl"),
+            PathBuf::from("lib.ncl"),
+            PathBuf::from("parse_fail.ncl"),
+        ]
+    );
+}
+
+#[test]
+fn check_without_eval() {
+    let summary = check::check_globs(vec![fixture("**/*.ncl")], CheckOptions::default());
+
+    assert_eq!(
+        statuses(&summary),
+        vec![
+            (String::from("eval_fail.ncl"), CheckStatus::Ok),
+            (String::from("lib.ncl"), CheckStatus::Ok),
+            (String::from("parse_fail.ncl"), CheckStatus::ParseError),
+            (String::from("pass.ncl"), CheckStatus::Ok),
+            (String::from("nested/deep_fail.ncl"), CheckStatus::Ok),
+            (String::from("nested/pass_nested.ncl"), CheckStatus::Ok),
+        ]
+    );
+    assert_eq!(summary.failures(), 1);
+    assert_eq!(summary.exit_code(), 1);
+
+    let parse_fail = &summary.results[2];
+    assert_eq!(parse_fail.message.as_deref(), Some("unexpected token"));
+    assert!(parse_fail.report.contains("unexpected token"));
+}
+
+#[test]
+fn check_with_eval() {
+    let opts = CheckOptions {
+        eval: true,
+        ..CheckOptions::default()
+    };
+    let summary = check::check_globs(vec![fixture("**/*.ncl")], opts.clone());
+
+    assert_eq!(
+        statuses(&summary),
+        vec![
+            (String::from("eval_fail.ncl"), CheckStatus::EvalError),
+            (String::from("lib.ncl"), CheckStatus::Ok),
+            (String::from("parse_fail.ncl"), CheckStatus::ParseError),
+            (String::from("pass.ncl"), CheckStatus::Ok),
+            // Contracts are checked deeply, while functions don't cause any failure, as nothing is
+            // serialized.
+            (String::from("nested/deep_fail.ncl"), CheckStatus::EvalError),
+            (String::from("nested/pass_nested.ncl"), CheckStatus::Ok),
+        ]
+    );
+    assert_eq!(summary.failures(), 3);
+    assert_eq!(
+        summary.results[0].message.as_deref(),
+        Some("contract broken by the caller")
+    );
+
+    // The parallel mode gives the same results, in the same order.
+    let parallel = check::check_globs(vec![fixture("**/*.ncl")], CheckOptions { jobs: 3, ..opts });
+    assert_eq!(statuses(&parallel), statuses(&summary));
+}
+
+#[test]
+fn check_success() {
+    let opts = CheckOptions {
+        eval: true,
+        ..CheckOptions::default()
+    };
+    let summary = check::check_globs(vec![fixture("**/pass*.ncl")], opts);
+
+    assert_eq!(summary.results.len(), 2);
+    assert!(summary.is_success());
+    assert_eq!(summary.exit_code(), 0);
+}
+
+#[test]
+fn missing_file() {
+    let summary = check::check_globs(vec![fixture("missing.ncl")], CheckOptions::default());
+
+    assert_eq!(summary.results.len(), 1);
+    assert_eq!(summary.results[0].status, CheckStatus::IOError);
+    assert_eq!(summary.exit_code(), 1);
+}
+
+#[test]
+fn summary_formats() {
+    let summary = check::check_globs(
+        vec![fixture("pass.ncl"), fixture("parse_fail.ncl")],
+        CheckOptions::default(),
+    );
+
+    let mut out = Vec::new();
+    let mut diags = Vec::new();
+    summary
+        .write(SummaryFormat::Table, &mut out, &mut diags)
+        .unwrap();
+    let table = String::from_utf8(out).unwrap();
+    let lines: Vec<&str> = table.lines().collect();
+    assert!(lines[0].starts_with("FILE"));
+    assert!(lines[1].ends_with("ok"));
+    assert!(lines[2].ends_with("parse error  unexpected token"));
+    assert!(table.ends_with("2 file(s) checked, 1 failed\n"));
+    assert!(String::from_utf8(diags)
+        .unwrap()
+        .starts_with("error[E025]: unexpected token"));
+
+    let mut out = Vec::new();
+    let mut diags = Vec::new();
+    summary
+        .write(SummaryFormat::Json, &mut out, &mut diags)
+        .unwrap();
+    assert!(diags.is_empty());
+    let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    let results = json["results"].as_array().unwrap();
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0]["status"], "ok");
+    assert_eq!(results[0]["message"], serde_json::Value::Null);
+    assert_eq!(results[1]["status"], "parse_error");
+    assert_eq!(results[1]["message"], "unexpected token");
+    assert_eq!(
+        results[1]["diagnostics"][0]["labels"][0]["byte_range"]["start"],
+        8
+    );
+}
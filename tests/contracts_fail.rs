@@ -1,6 +1,8 @@
 use assert_matches::assert_matches;
 use codespan::Files;
 use nickel_lang::error::{Error, EvalError, ToDiagnostic};
+use nickel_lang::program::Program;
+use std::io::Cursor;
 
 use nickel_lang_utilities::eval;
 
@@ -173,6 +175,61 @@ fn lists_contracts() {
     res.unwrap_err().to_diagnostic(&mut files, None);
 }
 
+#[test]
+fn kind_mismatch_message() {
+    let check = |term: &str, expected: &str| {
+        let mut files = Files::new();
+        let diags = eval(term).unwrap_err().to_diagnostic(&mut files, None);
+        assert!(
+            diags[0].message.contains(expected),
+            "expected `{}` in the message `{}`",
+            expected,
+            diags[0].message
+        );
+    };
+
+    check("(fun x => x) | {foo: Num}", "expected `Record`, got `Fun`");
+    check("{} | Array Num", "expected `Array`, got `Record`");
+    check(
+        "({foo = 1} | {foo: Num -> Num}).foo",
+        "expected `Fun`, got `Num`",
+    );
+}
+
+#[test]
+fn merge_orderings_accumulate_contracts() {
+    let layers = ["{port | Num}", "{port | default = 80}", "{port = \"8080\"}"];
+    let orderings = [
+        [0, 1, 2],
+        [0, 2, 1],
+        [1, 0, 2],
+        [1, 2, 0],
+        [2, 0, 1],
+        [2, 1, 0],
+    ];
+
+    for ordering in orderings.iter() {
+        let merged: Vec<&str> = ordering.iter().map(|i| layers[*i]).collect();
+        assert_raise_blame!(&format!("({}).port", merged.join(" & ")));
+        assert_raise_blame!(&format!(
+            "({} & ({} & {})).port",
+            merged[0], merged[1], merged[2]
+        ));
+    }
+
+    // The contracts of the fields of an overridden default value still apply.
+    let layers = [
+        "{server | default = {port | Num = 80}}",
+        "{server = {port = \"8080\"}}",
+        "{server | default = {host = \"localhost\"}}",
+    ];
+
+    for ordering in orderings.iter() {
+        let merged: Vec<&str> = ordering.iter().map(|i| layers[*i]).collect();
+        assert_raise_blame!(&format!("({}).server.port", merged.join(" & ")));
+    }
+}
+
 #[test]
 fn records_contracts_closed() {
     assert_raise_blame!("{a=1} | {}");
@@ -180,6 +237,83 @@ fn records_contracts_closed() {
     assert_raise_blame!("let Contract = {a | Num} & {b | Num} in ({a=1, b=2, c=3} | Contract)");
 }
 
+#[test]
+fn mutually_exclusive_fields() {
+    assert_raise_blame!("{} | contract.exactly_one_of [\"a\", \"b\"]");
+    assert_raise_blame!("{a = 1, b = 2} | contract.exactly_one_of [\"a\", \"b\"]");
+    assert_raise_blame!("{a = 1, b = 2, c = 3} | contract.at_most_one_of [\"a\", \"b\", \"c\"]");
+    assert_raise_blame!("1 | contract.at_most_one_of [\"a\"]");
+
+    // The blame message lists the conflicting fields.
+    let mut files = Files::new();
+    let diags = eval("({a = 1, b = 2, c = 3} | contract.exactly_one_of [\"a\", \"c\", \"d\"]).a")
+        .unwrap_err()
+        .to_diagnostic(&mut files, None);
+    assert!(
+        diags[0].message.contains("`a`, `c`"),
+        "unexpected message `{}`",
+        diags[0].message
+    );
+
+    // The notes give the position of the conflicting fields.
+    let mut prog = Program::new_from_source(
+        Cursor::new("({a = 1,\n  c = 2, b = 3} | contract.at_most_one_of [\"a\", \"c\", \"d\"]).b"),
+        "<test>",
+    )
+    .unwrap();
+    let err = prog.eval().unwrap_err();
+    let mut output = Vec::new();
+    prog.report_to_writer(err, &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(
+        output.contains("mutually exclusive fields `a`, `c`"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("field `a` is set at <test>:1:3"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("field `c` is set at <test>:2:3"),
+        "{}",
+        output
+    );
+}
+
+#[test]
+fn contract_from_example() {
+    assert_raise_blame!("%deep_seq% ({a = 1} | contract.from_example {a = 1, b = 2}) 0");
+    assert_raise_blame!("%deep_seq% ({a = 1, c = 2} | contract.from_example {a = 1}) 0");
+    assert_raise_blame!("`Dev | contract.from_example `Prod");
+    assert_raise_blame!("%deep_seq% ([1, \"a\"] | contract.from_example [2]) 0");
+
+    let res = eval(
+        "let example = {server = {host = \"localhost\", ports = [80]}} in
+        %deep_seq% ({server = {host = \"a\", ports = [80, \"443\"]}} | contract.from_example example) 0",
+    );
+    match &res {
+        Err(Error::EvalError(EvalError::BlameError(ref l, _))) => {
+            assert_eq!(l.field_path.to_string(), "server.ports[1]");
+            assert!(l.example_pos.is_def());
+        }
+        err => panic!("expected blame error, got {:?}", err),
+    }
+
+    let mut files = Files::new();
+    let diags = res.unwrap_err().to_diagnostic(&mut files, None);
+    assert!(
+        diags[0].message.contains("expected a value of type `Num`"),
+        "unexpected message `{}`",
+        diags[0].message
+    );
+    assert!(diags[0]
+        .labels
+        .iter()
+        .any(|label| label.message == "the contract was derived from this example"));
+}
+
 // #[test]
 // fn enum_complex() {
 //     eval(
@@ -189,3 +323,75 @@ fn records_contracts_closed() {
 //     )
 //     .unwrap_err();
 // }
+
+#[test]
+fn custom_function_contract() {
+    use nickel_lang::label::ty_path::Elem;
+
+    let blame = |contract: &str, call: &str| {
+        let res = eval(format!(
+            "let NumToNum = fun label f =>
+              if builtin.is_fun f then
+                fun x =>
+                  let dom_label = label
+                    |> contract.label.go_dom
+                    |> contract.label.flip_polarity in
+                  let codom_label = contract.label.go_codom label in
+                  contract.apply Num codom_label (f (contract.apply Num dom_label x))
+              else
+                contract.blame label in
+            let f | {} = fun x => if x == 1 then \"a\" else x in
+            {}",
+            contract, call
+        ));
+        let (polarity, path) = match &res {
+            Err(Error::EvalError(EvalError::BlameError(ref l, _))) => (l.polarity, l.path.clone()),
+            err => panic!("expected blame error, got {:?}", err),
+        };
+        let mut files = Files::new();
+        let diags = res.unwrap_err().to_diagnostic(&mut files, None);
+        (polarity, path, diags[0].message.clone())
+    };
+
+    // Blame of the caller.
+    let builtin = blame("Num -> Num", "f \"a\"");
+    assert_eq!(
+        builtin,
+        (
+            false,
+            vec![Elem::Domain],
+            String::from("contract broken by the caller")
+        )
+    );
+    assert_eq!(blame("NumToNum", "f \"a\""), builtin);
+
+    // Blame of the function.
+    let builtin = blame("Num -> Num", "f 1");
+    assert_eq!(
+        builtin,
+        (
+            true,
+            vec![Elem::Codomain],
+            String::from("contract broken by a function")
+        )
+    );
+    assert_eq!(blame("NumToNum", "f 1"), builtin);
+}
+
+#[test]
+fn flipped_polarity_blame() {
+    let res = eval("let C = fun l v => contract.blame (contract.label.flip_polarity l) in 1 | C");
+    match &res {
+        Err(Error::EvalError(EvalError::BlameError(l, _))) => {
+            assert!(!l.polarity);
+            assert!(l.path.is_empty());
+        }
+        err => panic!("expected blame error, got {:?}", err),
+    }
+
+    let err = res.unwrap_err();
+    assert_eq!(err.to_string(), "contract broken by the caller");
+    let mut files = Files::new();
+    let diags = err.to_diagnostic(&mut files, None);
+    assert_eq!(diags[0].message, "contract broken by the caller");
+}
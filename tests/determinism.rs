@@ -0,0 +1,89 @@
+//! Check that exports are deterministic, by comparing them byte-for-byte against golden files.
+//!
+//! Each Nickel file of `tests/determinism` is a fixture. It is exported to every format which has
+//! a golden file of the same name next to it: `records.ncl` is checked against `records.json`,
+//! `records.yaml`, and so on. Adding a fixture thus amounts to adding a Nickel file together with
+//! the golden files of the formats it can be exported to. Golden files are created or refreshed by
+//! running the tests with `NICKEL_UPDATE_GOLDENS=1`, for the formats whose golden file already
+//! exists, or for every format the fixture can be exported to if there is none.
+//!
+//! Each fixture is exported several times, from distinct programs, such that the randomized hash
+//! maps of each run lay the fields of records out differently.
+use nickel_lang::error::SerializationError;
+use nickel_lang::program::Program;
+use nickel_lang::serialize::{self, ExportFormat, ExportStyle};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The number of times each fixture is exported.
+const RUNS: usize = 3;
+
+const FORMATS: [(ExportFormat, &str); 3] = [
+    (ExportFormat::Json, "json"),
+    (ExportFormat::Yaml, "yaml"),
+    (ExportFormat::Toml, "toml"),
+];
+
+fn fixtures() -> Vec<PathBuf> {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/determinism");
+    let mut fixtures: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ncl"))
+        .collect();
+    fixtures.sort();
+    fixtures
+}
+
+/// Export a fixture. Fail if the result can't be represented in the given format.
+fn export(path: &Path, format: ExportFormat) -> Result<String, SerializationError> {
+    let mut program = Program::new_from_file(path).unwrap();
+    let rt = program
+        .eval_full()
+        .unwrap_or_else(|err| panic!("{}: evaluation failed: {:?}", path.display(), err));
+    serialize::validate(format, &rt)?;
+
+    let mut output = Vec::new();
+    serialize::to_writer(&mut output, format, ExportStyle::default(), &rt).unwrap();
+    Ok(String::from_utf8(output).unwrap())
+}
+
+#[test]
+fn exports_match_goldens() {
+    let update = std::env::var_os("NICKEL_UPDATE_GOLDENS").is_some();
+
+    for fixture in fixtures() {
+        let goldens: Vec<_> = FORMATS
+            .iter()
+            .map(|(format, ext)| (*format, fixture.with_extension(ext)))
+            .collect();
+        let has_goldens = goldens.iter().any(|(_, golden)| golden.exists());
+
+        for (format, golden) in goldens {
+            if update && golden.exists() {
+                fs::write(&golden, export(&fixture, format).unwrap()).unwrap();
+            } else if update && !has_goldens {
+                if let Ok(output) = export(&fixture, format) {
+                    fs::write(&golden, output).unwrap();
+                }
+            }
+
+            let expected = match fs::read_to_string(&golden) {
+                Ok(expected) => expected,
+                Err(_) => continue,
+            };
+
+            for _ in 0..RUNS {
+                assert_eq!(
+                    export(&fixture, format).unwrap(),
+                    expected,
+                    "{} differs from {}",
+                    fixture.display(),
+                    golden.display()
+                );
+            }
+        }
+    }
+
+    assert!(!fixtures().is_empty(), "no fixture found");
+}
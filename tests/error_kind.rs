@@ -0,0 +1,25 @@
+use nickel_lang::error::{Error, ErrorKind, IOError};
+use nickel_lang_utilities::eval;
+
+#[test]
+fn kinds_and_subkinds() {
+    let err = eval("1 | Str").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Eval);
+    assert_eq!(err.subkind(), "BlameError");
+
+    let err = eval("let x : Num = \"a\" in x").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Typecheck);
+    assert_eq!(err.subkind(), "TypeMismatch");
+
+    let err = eval("{a = }").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Parse);
+    assert_eq!(err.subkind(), "UnexpectedToken");
+
+    let err = eval("import \"does_not_exist.ncl\"").unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::Import);
+    assert_eq!(err.subkind(), "IOError");
+
+    let err = Error::from(IOError::Other(String::from("disk full")));
+    assert_eq!(err.kind(), ErrorKind::IO);
+    assert_eq!(err.subkind(), "Other");
+}
@@ -1,5 +1,5 @@
 use assert_matches::assert_matches;
-use nickel_lang::error::{Error, EvalError, TypecheckError};
+use nickel_lang::error::{Error, EvalError, ImportError, TypecheckError};
 use nickel_lang::program::Program;
 use nickel_lang::term::Term;
 use std::io::BufReader;
@@ -100,6 +100,40 @@ fn serialize() {
     assert_eq!(prog.eval().map(Term::from), Ok(Term::Bool(true)));
 }
 
+/// Evaluate an import of `file` and return the names of the files of the import cycle reported,
+/// stripped of their directory.
+fn import_cycle(file: &str) -> Vec<String> {
+    let mut prog =
+        Program::new_from_source(BufReader::new(mk_import(file).as_bytes()), "should_fail")
+            .unwrap();
+
+    let mut err = match prog.eval() {
+        Err(Error::EvalError(EvalError::ImportError(err))) => err,
+        result => panic!("expected an import error, got {:?}", result),
+    };
+
+    while let ImportError::Transitive(inner, _) = err {
+        err = *inner;
+    }
+
+    match err {
+        ImportError::ImportCycle(chain, pos) => {
+            assert!(pos.is_def());
+            chain
+                .iter()
+                .map(|name| {
+                    PathBuf::from(name)
+                        .file_name()
+                        .unwrap()
+                        .to_string_lossy()
+                        .into_owned()
+                })
+                .collect()
+        }
+        err => panic!("expected an import cycle, got {:?}", err),
+    }
+}
+
 #[test]
 fn circular_imports_fail() {
     let mut prog = Program::new_from_source(
@@ -112,3 +146,324 @@ fn circular_imports_fail() {
         Ok(Term::RecRecord(..)) | Ok(Term::Record(..))
     );
 }
+
+#[test]
+fn self_import() {
+    let mut prog = Program::new_from_source(
+        BufReader::new(format!("({}).b", mk_import("self_import.ncl")).as_bytes()),
+        "should_success",
+    )
+    .unwrap();
+    assert_eq!(prog.eval().map(Term::from), Ok(Term::Num(1.)));
+}
+
+#[test]
+fn divergent_import_cycles_fail() {
+    assert_eq!(
+        import_cycle("divergent_cycle.ncl"),
+        vec![
+            "divergent_cycle.ncl",
+            "divergent_cycle_b.ncl",
+            "divergent_cycle.ncl"
+        ]
+    );
+    assert_eq!(
+        import_cycle("divergent_self_import.ncl"),
+        vec!["divergent_self_import.ncl", "divergent_self_import.ncl"]
+    );
+}
+
+fn lazy_program(source: String) -> Program {
+    let mut prog =
+        Program::new_from_source(BufReader::new(source.as_bytes()), "lazy_imports").unwrap();
+    prog.set_lazy_imports(true);
+    prog
+}
+
+#[test]
+fn lazy_nested() {
+    let mut prog = lazy_program(mk_import("nested.ncl"));
+    assert_eq!(prog.eval().map(Term::from), Ok(Term::Num(3.)));
+}
+
+#[test]
+fn lazy_unused_imports() {
+    let mut prog = lazy_program(format!(
+        "if true then 1 else {} + {}",
+        mk_import("missing.ncl"),
+        mk_import("parse-fail.ncl")
+    ));
+    assert_eq!(prog.eval().map(Term::from), Ok(Term::Num(1.)));
+}
+
+#[test]
+fn lazy_forced_imports_fail() {
+    let mut prog = lazy_program(format!("if false then 1 else {}", mk_import("missing.ncl")));
+    assert_matches!(
+        prog.eval(),
+        Err(Error::EvalError(EvalError::ImportError(
+            ImportError::IOError(..)
+        )))
+    );
+
+    let mut prog = lazy_program(format!(
+        "if false then 1 else {}",
+        mk_import("parse-fail.ncl")
+    ));
+    assert_matches!(
+        prog.eval(),
+        Err(Error::EvalError(EvalError::ImportError(
+            ImportError::ParseErrors(..)
+        )))
+    );
+}
+
+#[test]
+fn lazy_imports_typing() {
+    // An unresolved import is typed as `Dyn`: a contract is needed to use it in typed code.
+    let mut prog = lazy_program(format!(
+        "let x | Num = {} in (x + 1 : Num)",
+        mk_import("two.ncl")
+    ));
+    assert_eq!(prog.eval().map(Term::from), Ok(Term::Num(3.)));
+
+    let mut prog = lazy_program(format!("let x = {} in (x + 1 : Num)", mk_import("two.ncl")));
+    assert_matches!(prog.eval(), Err(Error::TypecheckError(..)));
+
+    // Lazily imported files are not typechecked, whether they are used or not.
+    let mut prog = lazy_program(format!(
+        "if true then 1 else {}",
+        mk_import("typecheck-fail.ncl")
+    ));
+    assert_eq!(prog.eval().map(Term::from), Ok(Term::Num(1.)));
+}
+
+#[test]
+fn eager_unused_imports_fail() {
+    let mut prog = Program::new_from_source(
+        BufReader::new(format!("if true then 1 else {}", mk_import("parse-fail.ncl")).as_bytes()),
+        "should_fail",
+    )
+    .unwrap();
+    assert_matches!(
+        prog.eval(),
+        Err(Error::ImportError(ImportError::ParseErrors(..)))
+    );
+}
+
+#[test]
+fn json_duplicate_keys() {
+    use nickel_lang::error::ParseError;
+    use nickel_lang::serialize::DuplicateKeys;
+
+    let eval_port = |duplicate_keys| {
+        let mut prog = Program::new_from_source(
+            BufReader::new(
+                format!("({}).server.port", mk_import("duplicate_keys.json")).as_bytes(),
+            ),
+            "json_duplicate_keys",
+        )
+        .unwrap();
+        prog.set_json_duplicate_keys(duplicate_keys);
+        prog.eval().map(Term::from)
+    };
+
+    assert_eq!(eval_port(DuplicateKeys::First), Ok(Term::Num(80.)));
+    assert_eq!(eval_port(DuplicateKeys::Last), Ok(Term::Num(8080.)));
+    match eval_port(DuplicateKeys::Error) {
+        // Both occurrences of the key are located.
+        Err(Error::ImportError(ImportError::ParseErrors(errs, _))) => assert_matches!(
+            errs.errors.as_slice(),
+            [ParseError::ExternalFormatError(format, msg, Some(second), Some(first))]
+                if format == "json"
+                    && msg.starts_with("duplicate key `server.port`")
+                    && (first.start.to_usize(), first.end.to_usize()) == (20, 26)
+                    && (second.start.to_usize(), second.end.to_usize()) == (61, 67)
+        ),
+        res => panic!("expected a duplicate key error, got {:?}", res),
+    }
+}
+
+#[test]
+fn deserialize_duplicate_keys() {
+    let mut prog = Program::new_from_source(
+        BufReader::new(
+            "builtin.deserialize `Json \"{\\\"a\\\": {\\\"b\\\": 1, \\\"b\\\": 2}}\"".as_bytes(),
+        ),
+        "should_fail",
+    )
+    .unwrap();
+    assert_matches!(
+        prog.eval_full(),
+        Err(Error::EvalError(EvalError::DeserializationError(format, msg, _, _, Some(15), Some(first))))
+            if format == "json" && msg.starts_with("duplicate key `a.b`") && first == (7..10)
+    );
+}
+
+#[test]
+fn nested_external_format_fail() {
+    use nickel_lang::error::ParseError;
+
+    let mut prog = Program::new_from_source(
+        BufReader::new(mk_import("broken-toml-nested.ncl").as_bytes()),
+        "should_fail",
+    )
+    .unwrap();
+
+    let err = prog.eval().unwrap_err();
+    match &err {
+        Error::ImportError(ImportError::Transitive(err, _)) => match err.as_ref() {
+            ImportError::Transitive(err, _) => assert_matches!(
+                err.as_ref(),
+                ImportError::ParseErrors(errs, _)
+                    if matches!(
+                        errs.errors.as_slice(),
+                        [ParseError::ExternalFormatError(format, _, Some(_), None)] if format == "toml"
+                    )
+            ),
+            err => panic!("expected a transitive import error, got {:?}", err),
+        },
+        err => panic!("expected a transitive import error, got {:?}", err),
+    }
+
+    let mut output = Vec::new();
+    prog.report_to_writer(err, &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(output.matches("imported here").count(), 3);
+    assert!(output.contains("broken-toml.ncl"));
+    assert!(output.contains("broken-toml-nested.ncl"));
+}
+
+#[test]
+fn yaml_multi_documents() {
+    let mut prog = Program::new_from_source(
+        BufReader::new(
+            format!(
+                "array.map (fun x => x.name) ({}) == [\"first\", \"second\"]",
+                mk_import("multi.yaml")
+            )
+            .as_bytes(),
+        ),
+        "yaml_multi_documents",
+    )
+    .unwrap();
+    assert_eq!(prog.eval().map(Term::from), Ok(Term::Bool(true)));
+}
+
+#[test]
+fn dependencies() {
+    let deps = |file: &str| {
+        Program::new_from_source(BufReader::new(mk_import(file).as_bytes()), "deps")
+            .unwrap()
+            .imports()
+    };
+    let paths = |files: &[&str]| -> Vec<PathBuf> {
+        files
+            .iter()
+            .map(|file| {
+                let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+                path.push(format!("tests/imports/{}", file));
+                path.canonicalize().unwrap()
+            })
+            .collect()
+    };
+
+    // Files imported several times are listed once.
+    assert_eq!(
+        deps("multi_imports.ncl"),
+        Ok(paths(&["multi_imports.ncl", "nested.ncl", "two.ncl"]))
+    );
+    assert_eq!(
+        deps("data_imports.ncl"),
+        Ok(paths(&["data_imports.ncl", "port.yaml", "two.ncl"]))
+    );
+    assert_eq!(deps("cycle.ncl"), Ok(paths(&["cycle.ncl", "cycle_b.ncl"])));
+
+    // Imports are resolved even in lazy mode.
+    assert_eq!(
+        lazy_program(mk_import("nested.ncl")).imports(),
+        Ok(paths(&["nested.ncl", "two.ncl"]))
+    );
+
+    assert_matches!(
+        deps("broken-toml-nested.ncl"),
+        Err(Error::ImportError(ImportError::Transitive(..)))
+    );
+}
+
+#[test]
+fn import_paths() {
+    let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/imports/search_path");
+    let eval_with = |paths: &[&str]| {
+        let mut prog = Program::new_from_file(dir.join("main.ncl")).unwrap();
+        prog.set_import_paths(paths.iter().map(|path| dir.join(path)).collect());
+        prog.eval().map(Term::from)
+    };
+
+    // Directories are searched in order, after the directory of the importing file.
+    assert_eq!(eval_with(&["lib", "lib2"]), Ok(Term::Num(12.)));
+    assert_eq!(eval_with(&["lib2", "lib"]), Ok(Term::Num(102.)));
+
+    // The directories which were tried are listed.
+    assert_matches!(
+        eval_with(&[]),
+        Err(Error::ImportError(ImportError::IOError(path, msg, _)))
+            if path == "shared.ncl" && !msg.contains("searched in")
+    );
+    let expected = format!(
+        "(searched in: {}, {})",
+        dir.display(),
+        dir.join("lib2").display()
+    );
+    assert_matches!(
+        eval_with(&["lib2"]),
+        Err(Error::ImportError(ImportError::IOError(path, msg, _)))
+            if path == "shared.ncl" && msg.ends_with(&expected)
+    );
+}
+
+#[test]
+fn import_cycle_diagnostic() {
+    let mut prog = Program::new_from_source(
+        BufReader::new(mk_import("divergent_self_import.ncl").as_bytes()),
+        "should_fail",
+    )
+    .unwrap();
+    let err = prog.eval().unwrap_err();
+
+    let mut output = Vec::new();
+    prog.report_to_writer(err, &mut output).unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("import cycle"));
+    assert!(output.contains("this import closes the cycle"));
+    assert!(output.contains("divergent_self_import.ncl imports "));
+}
+
+#[test]
+fn external_source_notes() {
+    let report = |field: &str| {
+        let mut prog = Program::new_from_source(
+            BufReader::new(format!("({}).{}", mk_import("external/servers.ncl"), field).as_bytes()),
+            "should_fail",
+        )
+        .unwrap();
+        let err = prog.eval_full().unwrap_err();
+
+        let mut output = Vec::new();
+        prog.report_to_writer(err, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    };
+
+    let prod = report("prod_port");
+    assert!(prod.contains("this value was imported from "));
+    assert!(prod.contains("prod.yaml imported here"));
+    assert!(!prod.contains("staging.yaml"));
+    // Values of a data file have no span: the value is shown rather than the whole file.
+    assert!(prod.contains("\"8080\""));
+    assert!(!prod.contains("host: localhost"));
+
+    let staging = report("staging_host");
+    assert!(staging.contains("this value was imported from "));
+    assert!(staging.contains("staging.yaml imported here"));
+    assert!(!staging.contains("prod.yaml"));
+}
@@ -0,0 +1,129 @@
+use nickel_lang::cache::Cache;
+use nickel_lang::error::{Error, TypecheckError};
+use std::fs;
+use std::path::PathBuf;
+
+/// A library imported by two files, themselves imported by a main file.
+fn workspace(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("nickel-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    fs::write(dir.join("lib.ncl"), "{x = 1} : {x : Num}").unwrap();
+    fs::write(
+        dir.join("a.ncl"),
+        "let lib = import \"lib.ncl\" in (lib.x + 1 : Num)",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("b.ncl"),
+        "let lib = import \"lib.ncl\" in (lib.x : Num)",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("main.ncl"),
+        "{a = import \"a.ncl\", b = import \"b.ncl\"}",
+    )
+    .unwrap();
+
+    dir
+}
+
+#[test]
+fn edit_leaf_library() {
+    let dir = workspace("incremental");
+    let mut cache = Cache::new();
+    cache.load_stdlib().unwrap();
+    let env = cache.mk_types_env().unwrap();
+
+    let main_id = cache.add_file(dir.join("main.ncl")).unwrap();
+    cache.parse(main_id).unwrap();
+    cache.resolve_imports(main_id).unwrap();
+    cache.typecheck(main_id, &env).unwrap();
+
+    let id_of = |cache: &Cache, file| cache.id_of_file(dir.join(file)).unwrap().unwrap();
+    let (lib_id, a_id, b_id) = (
+        id_of(&cache, "lib.ncl"),
+        id_of(&cache, "a.ncl"),
+        id_of(&cache, "b.ncl"),
+    );
+
+    let mut dependents = cache.dependents(lib_id);
+    let mut expected = vec![a_id, b_id];
+    assert_eq!(dependents.pop(), Some(main_id));
+    dependents.sort();
+    expected.sort();
+    assert_eq!(dependents, expected);
+
+    // The apparent type of the library doesn't change: only the library is checked again.
+    let count = cache.typecheck_count();
+    cache
+        .files_mut()
+        .update(lib_id, String::from("# The library\n{x = 2} : {x : Num}"));
+    let result = cache.invalidate_and_check(lib_id, &env);
+    assert_eq!(cache.typecheck_count(), count + 1);
+    assert_eq!(result.len(), 1);
+    assert_eq!(result[0].0, lib_id);
+    assert!(result[0].1.is_empty());
+
+    // The apparent type of the library changes: both dependents are checked again, and fail, but
+    // not the main file, as their own apparent types don't change.
+    let count = cache.typecheck_count();
+    cache
+        .files_mut()
+        .update(lib_id, String::from("{x = \"a\"} : {x : Str}"));
+    let result = cache.invalidate_and_check(lib_id, &env);
+    assert_eq!(cache.typecheck_count(), count + 3);
+
+    let mut checked: Vec<_> = result.iter().map(|(id, _)| *id).collect();
+    let mut expected = vec![lib_id, a_id, b_id];
+    checked.sort();
+    expected.sort();
+    assert_eq!(checked, expected);
+    for (id, errors) in result {
+        if id == lib_id {
+            assert!(errors.is_empty());
+        } else {
+            assert!(
+                matches!(
+                    errors.as_slice(),
+                    [Error::TypecheckError(TypecheckError::TypeMismatch(..))]
+                ),
+                "{:?}",
+                errors
+            );
+        }
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn edit_with_import_error() {
+    let dir = workspace("incremental-import-error");
+    let mut cache = Cache::new();
+    cache.load_stdlib().unwrap();
+    let env = cache.mk_types_env().unwrap();
+
+    let main_id = cache.add_file(dir.join("main.ncl")).unwrap();
+    cache.parse(main_id).unwrap();
+    cache.resolve_imports(main_id).unwrap();
+    cache.typecheck(main_id, &env).unwrap();
+    let lib_id = cache.id_of_file(dir.join("lib.ncl")).unwrap().unwrap();
+    let lib_term = cache.get_owned(lib_id);
+
+    // An import which can't be resolved leaves the entry and the dependents as they were.
+    let count = cache.typecheck_count();
+    cache
+        .files_mut()
+        .update(lib_id, String::from("import \"missing.ncl\""));
+    let result = cache.invalidate_and_check(lib_id, &env);
+    assert_eq!(cache.typecheck_count(), count);
+    assert!(
+        matches!(result.as_slice(), [(id, errors)] if *id == lib_id && errors.len() == 1),
+        "{:?}",
+        result
+    );
+    assert_eq!(cache.get_owned(lib_id), lib_term);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
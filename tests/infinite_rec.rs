@@ -1,5 +1,7 @@
 use assert_matches::assert_matches;
-use nickel_lang::error::{Error, EvalError};
+use nickel_lang::error::{to_simple_diagnostics, Error, EvalError};
+use nickel_lang::program::Program;
+use std::io::Cursor;
 
 use nickel_lang_utilities::eval;
 
@@ -22,3 +24,42 @@ fn infinite_loops() {
         Err(Error::EvalError(EvalError::InfiniteRecursion(..)))
     );
 }
+
+#[test]
+fn cycle_labels() {
+    let mut prog =
+        Program::new_from_source(Cursor::new("{x = y, y = z, z = x}.x"), "<test>").unwrap();
+    let err = prog.eval_full().unwrap_err();
+    let diags = to_simple_diagnostics(&err, prog.files(), None);
+    let labels: Vec<(&str, bool)> = diags[0]
+        .labels
+        .iter()
+        .map(|label| (label.label_text.as_str(), label.primary))
+        .collect();
+
+    assert_eq!(
+        labels,
+        vec![
+            ("`x` depends on `y`", false),
+            ("`y` depends on `z`", false),
+            ("`z` depends on `x`", true)
+        ]
+    );
+    assert_eq!(
+        diags[0].notes,
+        vec!["The cycle is `x` -> `y` -> `z` -> `x`."]
+    );
+}
+
+#[test]
+fn cycle_through_function_calls() {
+    let mut prog = Program::new_from_source(
+        Cursor::new("{f = fun a => a, x = f y, y = array.map (fun e => e) x}.x"),
+        "<test>",
+    )
+    .unwrap();
+    let err = prog.eval_full().unwrap_err();
+    let diags = to_simple_diagnostics(&err, prog.files(), None);
+
+    assert_eq!(diags[0].notes, vec!["The cycle is `x` -> `y` -> `x`."]);
+}
@@ -0,0 +1,68 @@
+use assert_matches::assert_matches;
+use nickel_lang::error::{Error, EvalError, ParseError, TypecheckError};
+use nickel_lang::program::{Phase, Program};
+use nickel_lang::serialize::{ExportFormat, ExportStyle};
+use std::io::Cursor;
+use std::path::PathBuf;
+
+fn fixture(file: &str) -> PathBuf {
+    let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    path.push(format!("tests/keep_going/{}", file));
+    path
+}
+
+#[test]
+fn all_phases() {
+    let mut prog = Program::new_from_file(fixture("all_phases.ncl")).unwrap();
+    let errors = prog.eval_full_keep_going().unwrap_err();
+    let phases: Vec<Phase> = errors.iter().map(|(phase, _)| *phase).collect();
+
+    assert_eq!(
+        phases,
+        vec![
+            Phase::Parse,
+            Phase::Typecheck,
+            Phase::Eval,
+            Phase::Eval,
+            Phase::Eval
+        ]
+    );
+    assert_matches!(
+        &errors[0].1,
+        Error::ParseErrors(errs) if matches!(errs.errors.as_slice(), [ParseError::UnexpectedToken(..)])
+    );
+    assert_matches!(
+        &errors[1].1,
+        Error::TypecheckError(TypecheckError::TypeMismatch(..))
+    );
+    // One error by failing field, in alphabetical order: `host`, `port` and `typed`.
+    assert_matches!(&errors[2].1, Error::EvalError(EvalError::BlameError(..)));
+    assert_matches!(&errors[3].1, Error::EvalError(EvalError::BlameError(..)));
+    assert_matches!(
+        &errors[4].1,
+        Error::EvalError(EvalError::BinaryTypeError { .. })
+    );
+
+    assert_eq!(phases.iter().map(|phase| phase.exit_code()).max(), Some(3));
+}
+
+#[test]
+fn success() {
+    let mut prog =
+        Program::new_from_source(Cursor::new("{a = 1, b = fun x => x}"), "<test>").unwrap();
+    assert!(prog.eval_full_keep_going().is_ok());
+}
+
+#[test]
+fn export_nothing_on_errors() {
+    let output =
+        std::env::temp_dir().join(format!("nickel_keep_going_{}.json", std::process::id()));
+    let mut prog =
+        Program::new_from_source(Cursor::new("{a | Num = \"a\", b = 1}"), "<test>").unwrap();
+
+    let errors = prog
+        .export_keep_going(ExportFormat::Json, ExportStyle::default(), Some(&output))
+        .unwrap_err();
+    assert_matches!(errors.as_slice(), [(Phase::Eval, _)]);
+    assert!(!output.exists());
+}
@@ -0,0 +1,84 @@
+use assert_matches::assert_matches;
+use nickel_lang::error::{Error, EvalError, ParseError};
+use nickel_lang::program::{FieldOverride, Program};
+use nickel_lang::serialize::{self, ExportFormat, ExportStyle};
+use std::io::Cursor;
+
+const CONFIG: &str = r#"{
+  server = {
+    host | Str | default = "localhost",
+    replicas | Num | default = 1,
+    tls | default = {enabled = false},
+  },
+}"#;
+
+fn program(overrides: &[&str]) -> Result<Program, Error> {
+    let mut prog = Program::new_from_source(Cursor::new(CONFIG), "<test>").unwrap();
+    prog.set_overrides(
+        overrides
+            .iter()
+            .map(|s| s.parse::<FieldOverride>().unwrap()),
+    )?;
+    Ok(prog)
+}
+
+fn export(overrides: &[&str]) -> Result<String, Error> {
+    let rt = program(overrides)?.eval_full()?;
+    Ok(serialize::to_string(ExportFormat::Json, ExportStyle::Compact, &rt).unwrap())
+}
+
+#[test]
+fn parse_override() {
+    let over: FieldOverride = "server.host=\"a=b\"".parse().unwrap();
+    assert_eq!(
+        over.path
+            .iter()
+            .map(|id| id.label.as_str())
+            .collect::<Vec<_>>(),
+        vec!["server", "host"]
+    );
+    assert_eq!(over.value, "\"a=b\"");
+
+    assert!("server.host".parse::<FieldOverride>().is_err());
+    assert!("server..host=1".parse::<FieldOverride>().is_err());
+    assert!("=1".parse::<FieldOverride>().is_err());
+}
+
+#[test]
+fn override_default_values() {
+    assert_eq!(
+        export(&["server.replicas=3", "server.host=\"example.com\""]).unwrap(),
+        r#"{"server":{"host":"example.com","replicas":3,"tls":{"enabled":false}}}"#
+    );
+}
+
+#[test]
+fn override_with_record() {
+    assert_eq!(
+        export(&["server.tls={enabled = true, port = 1 + 442}"]).unwrap(),
+        r#"{"server":{"host":"localhost","replicas":1,"tls":{"enabled":true,"port":443}}}"#
+    );
+}
+
+#[test]
+fn override_blames() {
+    let mut prog = program(&["server.replicas=\"three\""]).unwrap();
+    let err = prog.eval_full().unwrap_err();
+    assert_matches!(err, Error::EvalError(EvalError::BlameError(..)));
+
+    let mut report = Vec::new();
+    prog.report_to_writer(err, &mut report).unwrap();
+    let report = String::from_utf8(report).unwrap();
+    assert!(report.contains("<override server.replicas>"), "{}", report);
+}
+
+#[test]
+fn duplicate_override() {
+    let err = program(&["server.replicas=3", "server.replicas=4"])
+        .err()
+        .unwrap();
+    assert_matches!(
+        err,
+        Error::ParseErrors(errs) if matches!(errs.errors.as_slice(), [ParseError::DuplicateOverride(..)])
+    );
+}
@@ -1,4 +1,5 @@
 use nickel_lang::program::Program;
+use nickel_lang::serialize::{self, ExportFormat, ExportStyle};
 use nickel_lang::term::{SharedTerm, Term};
 
 #[test]
@@ -31,3 +32,59 @@ pub fn test_query_metadata_from_func() {
         panic!();
     }
 }
+
+#[test]
+pub fn test_query_contracted_value_schema_doc() {
+    let mut program = Program::new_from_source(
+        "let Schema = {port | Num | doc \"The port\" | default = 80} in
+        {server = {port = 8080} | Schema}"
+            .as_bytes(),
+        "regr_tests",
+    )
+    .unwrap();
+    let result = program.query(Some(String::from("server.port"))).unwrap();
+
+    if let Term::MetaValue(meta) = result {
+        assert_eq!(meta.doc, Some(String::from("The port")));
+        assert_eq!(meta.contracts.len(), 1);
+        assert_eq!(meta.value.unwrap().term, SharedTerm::new(Term::Num(8080.0)));
+    } else {
+        panic!();
+    }
+}
+
+#[test]
+pub fn test_query_contracted_value_own_doc() {
+    let mut program = Program::new_from_source(
+        "let Schema = {port | doc \"The port\" | default = 80} in
+        {server = {port | doc \"Overridden\" | default = 8080} | Schema}"
+            .as_bytes(),
+        "regr_tests",
+    )
+    .unwrap();
+    let result = program.query(Some(String::from("server.port"))).unwrap();
+
+    if let Term::MetaValue(meta) = result {
+        assert_eq!(meta.doc, Some(String::from("Overridden")));
+        assert_eq!(meta.value.unwrap().term, SharedTerm::new(Term::Num(8080.0)));
+    } else {
+        panic!();
+    }
+}
+
+#[test]
+pub fn test_export_contracted_value_defaults() {
+    let mut program = Program::new_from_source(
+        "let Schema = {host | Str | default = \"localhost\", port | default = 80} in
+        {port = 8080} | Schema"
+            .as_bytes(),
+        "regr_tests",
+    )
+    .unwrap();
+    let result = program.eval_full().unwrap();
+
+    assert_eq!(
+        serialize::to_string(ExportFormat::Json, ExportStyle::default(), &result).unwrap(),
+        "{\n  \"host\": \"localhost\",\n  \"port\": 8080\n}"
+    );
+}
@@ -1,5 +1,8 @@
 use assert_matches::assert_matches;
 use nickel_lang::error::{Error, EvalError, TypecheckError};
+use nickel_lang::program::Program;
+use nickel_lang::term::Term;
+use std::io::Cursor;
 
 use nickel_lang_utilities::eval;
 
@@ -14,6 +17,92 @@ fn records_access() {
         Err(Error::EvalError(EvalError::Other(msg, ..))) if msg.starts_with("$[ .. ]"));
 }
 
+#[test]
+fn access_on_non_record() {
+    assert_matches!(
+        eval("\"str\".len"),
+        Err(Error::EvalError(EvalError::NotARecord { field, value, .. }))
+            if field == "len" && matches!(value.as_ref(), Term::Str(..))
+    );
+    assert_matches!(
+        eval("[1,2].foo"),
+        Err(Error::EvalError(EvalError::NotARecord { field, value, .. }))
+            if field == "foo" && matches!(value.as_ref(), Term::Array(..))
+    );
+    assert_matches!(
+        eval("(1 + 1).foo"),
+        Err(Error::EvalError(EvalError::NotARecord { field, .. })) if field == "foo"
+    );
+    // Dynamic accesses give the same error.
+    assert_matches!(
+        eval("let field = \"foo\" in [1,2].\"%{field}\""),
+        Err(Error::EvalError(EvalError::NotARecord { field, .. })) if field == "foo"
+    );
+}
+
+#[test]
+fn access_on_non_record_report() {
+    let report = |source: &str| {
+        let mut prog = Program::new_from_source(Cursor::new(source), "<test>").unwrap();
+        let err = prog.eval_full().unwrap_err();
+        let mut output = Vec::new();
+        prog.report_to_writer(err, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    };
+
+    let output = report("\"str\".len");
+    assert!(
+        output.contains("field `len` accessed on a non-record value"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("this expression has type Str"),
+        "{}",
+        output
+    );
+    assert!(output.contains("field access here"), "{}", output);
+    assert!(output.contains("`string.length`"), "{}", output);
+
+    let output = report("let services = [{name = \"a\"}] in services.name");
+    assert!(
+        output.contains("this expression has type Array"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("`array.map (fun s => s.name) <array>`"),
+        "{}",
+        output
+    );
+    assert!(
+        output.contains("`array.pluck \"name\" <array>`"),
+        "{}",
+        output
+    );
+
+    let output = report("(1 + 1).foo");
+    assert!(output.contains("merge operator `&`"), "{}", output);
+}
+
+#[test]
+fn pluck_missing_field() {
+    assert_matches!(
+        eval("array.pluck \"a\" [{a = 1}, {a = 2}, {b = 3}, {a = 4}] |> array.foldl (fun acc x => acc + x) 0"),
+        Err(Error::EvalError(EvalError::FieldMissing(field, _, _, _, Some(2)))) if field == "a"
+    );
+    // The contract of `array.pluck` rejects the elements which aren't records beforehand.
+    assert_matches!(
+        eval("array.pluck \"a\" [{a = 1}, 2] |> array.foldl (fun acc x => acc + x) 0"),
+        Err(Error::EvalError(EvalError::BlameError(..)))
+    );
+    assert_matches!(
+        eval("%pluck% \"a\" [{a = 1}, 2] |> array.foldl (fun acc x => acc + x) 0"),
+        Err(Error::EvalError(EvalError::TypeError(expd, note, ..)))
+            if expd == "Record" && note.contains("index 1")
+    );
+}
+
 #[test]
 fn non_mergeable() {
     assert_matches!(
@@ -49,3 +138,19 @@ fn dynamic_not_recursive() {
         Err(Error::TypecheckError(TypecheckError::UnboundIdentifier(..)))
     );
 }
+
+#[test]
+fn record_set_operations() {
+    assert_matches!(
+        eval("%record_diff% 1 {}"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("%record_intersect% {} [1]"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+    assert_matches!(
+        eval("(record.diff {a = 1} {b = 2} : {_: Str})"),
+        Err(Error::TypecheckError(TypecheckError::TypeMismatch(..)))
+    );
+}
@@ -0,0 +1,269 @@
+use codespan_reporting::term::{self, termcolor::NoColor};
+use nickel_lang::error::{Error, ReplError, ToDiagnostic};
+use nickel_lang::repl::command::{Command, CommandType};
+use nickel_lang::repl::completion::complete;
+use nickel_lang::repl::{write_bindings, InputParser, InputStatus, Repl, ReplImpl};
+use nickel_lang::term::{MergePriority, Term};
+
+/// Render an error as the REPL frontends would, without colors.
+fn render(repl: &mut ReplImpl, error: Error) -> String {
+    let cache = repl.cache_mut();
+    let contracts_id = cache.id_of("<stdlib/contract.ncl>");
+    let opts = cache.diagnostic_options().clone();
+    let diagnostics = error.to_diagnostic_with(cache.files_mut(), contracts_id, &opts);
+
+    let mut buffer = NoColor::new(Vec::new());
+    let config = term::Config::default();
+    for diagnostic in diagnostics.iter() {
+        term::emit(&mut buffer, &config, cache.files_mut(), diagnostic).unwrap();
+    }
+
+    String::from_utf8(buffer.into_inner()).unwrap()
+}
+
+#[test]
+fn inputs_have_their_own_source() {
+    let mut repl = ReplImpl::new();
+    repl.load_stdlib().unwrap();
+
+    repl.eval_full("let f | Num -> Num = fun x => x").unwrap();
+    repl.eval_full("let a = 1").unwrap();
+    repl.eval_full("let b = 2").unwrap();
+    let error = match repl.eval_full("f \"a\" + b") {
+        Err(err) => err,
+        Ok(_) => panic!("expected a blame error"),
+    };
+    let rendered = render(&mut repl, error);
+
+    assert!(rendered.contains("<repl:4>"), "{}", rendered);
+    assert!(rendered.contains("f \"a\" + b"), "{}", rendered);
+    assert!(rendered.contains("<repl:1>"), "{}", rendered);
+    assert!(
+        rendered.contains("let f | Num -> Num = fun x => x"),
+        "{}",
+        rendered
+    );
+    assert!(
+        rendered.contains("`f` defined in `<repl:1>`"),
+        "{}",
+        rendered
+    );
+}
+
+#[test]
+fn unbound_identifiers_refer_to_declarations() {
+    let mut repl = ReplImpl::new();
+    repl.load_stdlib().unwrap();
+
+    repl.eval_full("let foo_bar = 1").unwrap();
+    repl.eval_full("let foo_bar = 2").unwrap();
+    let error = match repl.eval_full("foo_bra + 1") {
+        Err(err) => err,
+        Ok(_) => panic!("expected an unbound identifier"),
+    };
+    let rendered = render(&mut repl, error);
+
+    // The suggested name refers to the declaration which shadowed the first one.
+    assert!(rendered.contains("did you mean `foo_bar`?"), "{}", rendered);
+    assert!(
+        rendered.contains("`foo_bar` defined in `<repl:2>`"),
+        "{}",
+        rendered
+    );
+    assert!(!rendered.contains("<repl:1>"), "{}", rendered);
+
+    repl.reset();
+    let error = match repl.eval_full("foo_bra") {
+        Err(err) => err,
+        Ok(_) => panic!("expected an unbound identifier"),
+    };
+    assert!(!render(&mut repl, error).contains("defined in"));
+}
+
+#[test]
+fn typecheck_infers_types() {
+    let mut repl = ReplImpl::new();
+    repl.load_stdlib().unwrap();
+    repl.eval_full("let untyped = fun x => x").unwrap();
+
+    let mut typ = |exp: &str| repl.typecheck(exp).unwrap().to_string();
+
+    assert_eq!(typ("fun x => x + 1"), "Num -> Num");
+    assert_eq!(typ("fun x => x"), "forall a. a -> a");
+    assert_eq!(typ("{a = 1}"), "{a: Num}");
+    assert_eq!(typ("{a = {b = \"x\"}}"), "{a: {b: Str}}");
+    assert_eq!(
+        typ("array.map"),
+        "forall a b. (a -> b) -> Array a -> Array b"
+    );
+    assert_eq!(typ("{a = untyped 2}"), "{a: Dyn}");
+    assert_eq!(typ("{a = [1, 2]}"), "{a: Array Num}");
+    assert_eq!(typ("{a = 1} & {b = [true]}"), "Dyn");
+    // Not well-typed as a statically typed term: the shape of the record is kept.
+    assert_eq!(
+        typ("{a = untyped + 1, b = [\"x\"]}"),
+        "{a: Dyn, b: Array Str}"
+    );
+}
+
+#[test]
+fn completion() {
+    let mut repl = ReplImpl::new();
+    repl.load_stdlib().unwrap();
+    let complete_end = |repl: &ReplImpl, line: &str| complete(line, line.len(), repl.eval_env());
+
+    assert_eq!(
+        complete_end(&repl, ":ty"),
+        (1, vec![String::from("typecheck")])
+    );
+    assert!(complete_end(&repl, "arr")
+        .1
+        .contains(&String::from("array")));
+    assert_eq!(
+        complete_end(&repl, "1 + array.fold"),
+        (10, vec![String::from("fold"), String::from("foldl")])
+    );
+    assert_eq!(
+        complete_end(&repl, ":p string.uppe"),
+        (10, vec![String::from("uppercase")])
+    );
+
+    repl.eval_full("let config = {server = {port = 80, host = \"a\"}}")
+        .unwrap();
+    assert!(complete_end(&repl, "conf")
+        .1
+        .contains(&String::from("config")));
+    repl.eval_full("config").unwrap();
+    assert_eq!(
+        complete_end(&repl, "config.server."),
+        (14, vec![String::from("host"), String::from("port")])
+    );
+
+    // Completion doesn't evaluate anything.
+    repl.eval_full("let applied = (fun x => x) {a = 1}")
+        .unwrap();
+    assert_eq!(complete_end(&repl, "applied."), (8, Vec::new()));
+}
+
+#[test]
+fn doc() {
+    let mut repl = ReplImpl::new();
+    repl.load_stdlib().unwrap();
+    repl.eval_full(
+        "let r = {foo | doc \"The foo\" | Num | default = 1, bar | Str, baz = %blame% 1, qux | doc \"The qux\" = baz}",
+    )
+    .unwrap();
+
+    let mut meta = |exp: &str| match repl.doc(exp).unwrap() {
+        Term::MetaValue(meta) => meta,
+        t => panic!("expected metadata for {}, got {}", exp, t.shallow_repr()),
+    };
+
+    let foo = meta("r.foo");
+    assert_eq!(foo.doc.as_deref(), Some("The foo"));
+    assert_eq!(foo.priority, MergePriority::Default);
+    assert_eq!(foo.contracts.len(), 1);
+
+    let bar = meta("r.bar");
+    assert_eq!(bar.doc, None);
+    assert!(bar.value.is_none());
+
+    // The value of the field is not evaluated, as it would fail.
+    assert_eq!(meta("r.qux").doc.as_deref(), Some("The qux"));
+
+    let map = meta("array.map");
+    assert!(map.doc.unwrap().contains("applies function `f`"));
+    assert!(map.types.is_some());
+
+    assert!(!matches!(repl.doc("1").unwrap(), Term::MetaValue(_)));
+    assert!(matches!(
+        "doc".parse::<Command>(),
+        Err(ReplError::MissingArg {
+            cmd: CommandType::Doc,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn multiline_input() {
+    let mut repl = ReplImpl::new();
+    let parser = InputParser::new(repl.cache_mut().add_tmp("<repl-input>", String::new()));
+    let partial = |input: &str| matches!(parser.parse(input), InputStatus::Partial);
+
+    assert!(partial("{"));
+    assert!(partial("{\n  a = 1,"));
+    assert!(partial("[1,\n2"));
+    assert!(partial("m%\"\nsome text"));
+    assert!(partial("\"%{ {a = 1}.a"));
+    // An error inside an unterminated record is reported once the record is closed.
+    assert!(partial("{\n  a = = 1,"));
+    assert!(matches!(
+        parser.parse("{\n  a = = 1,\n}"),
+        InputStatus::Failed(_)
+    ));
+
+    assert!(matches!(
+        parser.parse("{\n  a = 1,\n}"),
+        InputStatus::Complete(_)
+    ));
+    assert!(matches!(parser.parse("{a = 1}}"), InputStatus::Failed(_)));
+    assert!(matches!(parser.parse(":help"), InputStatus::Command));
+}
+
+#[test]
+fn env_and_reset() {
+    let mut repl = ReplImpl::new();
+    repl.load_stdlib().unwrap();
+    assert!(repl.bindings(false).is_empty());
+
+    repl.eval_full("let b = \"two\"").unwrap();
+    repl.eval_full("let a | Num = 1").unwrap();
+    // Shadowing a name of the standard library.
+    repl.eval_full("let array = [1]").unwrap();
+    repl.eval_full("b").unwrap();
+
+    let bindings = repl.bindings(false);
+    let names: Vec<&str> = bindings.iter().map(|b| b.id.label.as_str()).collect();
+    assert_eq!(names, vec!["a", "array", "b"]);
+    assert_eq!(bindings[0].ty.to_string(), "Num");
+    assert_eq!(bindings[2].preview, "\"two\"");
+
+    let all = repl.bindings(true);
+    assert!(all.iter().any(|b| b.id.label == "string"));
+    assert!(all.windows(2).all(|w| w[0].id.label <= w[1].id.label));
+
+    let mut output = Vec::new();
+    write_bindings(&mut output, &bindings, false).unwrap();
+    assert_eq!(String::from_utf8(output).unwrap(), "a\narray\nb\n");
+
+    repl.load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/repl/load.ncl"))
+        .unwrap();
+    assert!(repl.bindings(false).iter().any(|b| b.id.label == "loaded"));
+
+    repl.reset();
+    assert!(repl.bindings(false).is_empty());
+    assert!(repl.eval_full("a").is_err());
+    assert!(repl.eval_full("loaded").is_err());
+    // The standard library is kept, including the names which were shadowed.
+    assert!(repl.eval_full("array.length [1, 2]").is_ok());
+    // A file can be loaded again after a reset.
+    repl.load(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/repl/load.ncl"))
+        .unwrap();
+    assert!(repl.eval_full("loaded").is_ok());
+
+    assert_eq!(
+        "env -v --all".parse::<Command>().unwrap(),
+        Command::Env {
+            verbose: true,
+            all: true
+        }
+    );
+    assert!(matches!(
+        "env --stdlib".parse::<Command>(),
+        Err(ReplError::InvalidArg {
+            cmd: CommandType::Env,
+            ..
+        })
+    ));
+}
@@ -73,3 +73,56 @@ fn head_tail() {
         Err(Error::EvalError(EvalError::BlameError(..)))
     );
 }
+
+#[test]
+fn chunks_windows() {
+    assert_matches!(
+        eval("%chunks% [1, 2, 3] 0"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("%chunks% [1, 2, 3] (-2)"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("%windows% [1, 2, 3] 1.5"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("%windows% {} 1"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+
+    assert_matches!(
+        eval("array.chunks \"a\" [1, 2]"),
+        Err(Error::EvalError(EvalError::BlameError(..)))
+    );
+    assert_matches!(
+        eval("array.windows 2 {}"),
+        Err(Error::EvalError(EvalError::BlameError(..)))
+    );
+}
+
+#[test]
+fn range() {
+    assert_matches!(
+        eval("array.range_step 0 10 0"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("array.range_step 0 10 (-1)"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("array.range 0.5 2"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("array.range 0 100000000000"),
+        Err(Error::EvalError(EvalError::Other(..)))
+    );
+    assert_matches!(
+        eval("%range_step% 0 \"a\" 1"),
+        Err(Error::EvalError(EvalError::TypeError(..)))
+    );
+}
@@ -0,0 +1,92 @@
+use assert_matches::assert_matches;
+use nickel_lang::error::{Error, EvalError, ParseError, ParseErrors, TypecheckError};
+use nickel_lang::position::TermPos;
+use nickel_lang::term::Term;
+
+use nickel_lang_utilities::eval;
+
+#[test]
+fn mismatch_names_the_alias() {
+    assert_matches!(
+        eval("let type Endpoint = {host: Str, port: Num} in (1 : Endpoint)"),
+        Err(Error::TypecheckError(TypecheckError::TypeMismatch(expd, actual, _)))
+            if expd.to_string() == "Endpoint" && actual.to_string() == "Num"
+    );
+    assert_matches!(
+        eval("let type Pair a b = {fst: a, snd: b} in (1 : Pair Num Str)"),
+        Err(Error::TypecheckError(TypecheckError::TypeMismatch(expd, ..)))
+            if expd.to_string() == "Pair Num Str"
+    );
+}
+
+#[test]
+fn parameterized_aliases() {
+    assert_matches!(
+        eval(
+            "let type Pair a b = {fst: a, snd: b} in
+             let p : Pair Num Str = {fst = 1, snd = \"a\"} in
+             p.fst + 1"
+        ),
+        Ok(Term::Num(n)) if n == 2.0
+    );
+    assert_matches!(
+        eval("let type Pair a b = {fst: a, snd: b} in ({fst = \"a\", snd = 1} : Pair Num Str)"),
+        Err(Error::TypecheckError(..))
+    );
+    // Aliases can be used inside polymorphic types, and can stand for polymorphic types.
+    assert_matches!(
+        eval(
+            "let type Endo a = a -> a in
+             let f : forall a. Endo a = fun x => x in
+             f 1"
+        ),
+        Ok(Term::Num(n)) if n == 1.0
+    );
+    assert_matches!(
+        eval(
+            "let type Id = forall a. a -> a in
+             let f : Id = fun x => x in
+             (f 1 : Num)"
+        ),
+        Ok(Term::Num(n)) if n == 1.0
+    );
+    // Aliases are also contracts.
+    assert_matches!(
+        eval("let type Pair a b = {fst: a, snd: b} in ({fst = \"a\", snd = 1} | Pair Num Str).fst"),
+        Err(Error::EvalError(EvalError::BlameError(..)))
+    );
+}
+
+#[test]
+fn wrong_arity() {
+    assert_matches!(
+        eval("let type Pair a b = {fst: a, snd: b} in null | Pair Num"),
+        Err(Error::ParseErrors(ParseErrors { errors }))
+            if matches!(errors.as_slice(), [ParseError::TypeAliasArity(id, 2, 1, _)] if id.label == "Pair")
+    );
+    assert_matches!(
+        eval("let type Endpoint = {host: Str} in null | Endpoint Num"),
+        Err(Error::ParseErrors(ParseErrors { errors }))
+            if matches!(errors.as_slice(), [ParseError::TypeAliasArity(id, 0, 1, _)] if id.label == "Endpoint")
+    );
+}
+
+#[test]
+fn unknown_alias() {
+    assert_matches!(
+        eval("let type Endpoint = {host: Str} in ({host = \"a\"} : Endpont)"),
+        Err(Error::TypecheckError(TypecheckError::UnboundIdentifier(id, TermPos::Original(_), _)))
+            if id.label == "Endpont"
+    );
+    // An alias is only in scope in the body of its declaration.
+    assert_matches!(
+        eval("(let type Port = Num in 1) + (1 : Port)"),
+        Err(Error::TypecheckError(TypecheckError::UnboundIdentifier(id, TermPos::Original(_), _)))
+            if id.label == "Port"
+    );
+}
+
+#[test]
+fn type_is_still_an_identifier() {
+    assert_matches!(eval("{type = 1}.type"), Ok(Term::Num(n)) if n == 1.0);
+}
@@ -35,6 +35,20 @@ fn unbound_variable_always_throws() {
     )
 }
 
+#[test]
+fn unbound_variable_suggestion() {
+    assert_matches!(
+        type_check_expr("let foo_bar = 1 in let baz = 2 in foo_bra + baz"),
+        Err(TypecheckError::UnboundIdentifier(id, _, Some(closest)))
+            if id.label == "foo_bra" && closest.label == "foo_bar"
+    );
+    // Only close enough identifiers are suggested.
+    assert_matches!(
+        type_check_expr("let foo_bar = 1 in bar"),
+        Err(TypecheckError::UnboundIdentifier(_, _, None))
+    );
+}
+
 #[test]
 fn promise_simple_checks() {
     assert_typecheck_fails!("true : Num");